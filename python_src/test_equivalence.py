@@ -289,6 +289,8 @@ def test_generate_index(project_root: Path, rust_bin_dir: Path) -> bool:
     rs_bin = rs_bin_in_tmp(tmp_rs, "generate_index")
     run_cmd([str(rs_bin)], cwd=tmp_rs)
     rs_index = json.loads((tmp_rs / "reports_index.json").read_text(encoding="utf-8"))
+    # Per-file details (checksums, titles, sections) are Rust-only.
+    rs_index.pop("files", None)
 
     if py_index != rs_index:
         print("FAIL: reports_index.json content differs between Python and Rust")
@@ -347,9 +349,13 @@ def test_backup(project_root: Path, rust_bin_dir: Path) -> bool:
     rs_bin = rs_bin_in_tmp(tmp_rs, "backup")
     run_cmd([str(rs_bin)], cwd=tmp_rs)
 
+    # Rust writes into a timestamped snapshot folder inside backup/
     py_backup = tmp_py / "backup"
-    rs_backup = tmp_rs / "backup"
-    return compare_dirs(py_backup, rs_backup)
+    rs_snapshots = [p for p in (tmp_rs / "backup").iterdir() if p.is_dir()]
+    if len(rs_snapshots) != 1:
+        print("FAIL: expected one Rust backup snapshot, found", len(rs_snapshots))
+        return False
+    return compare_dirs(py_backup, rs_snapshots[0])
 
 
 def main():
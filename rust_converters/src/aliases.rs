@@ -0,0 +1,148 @@
+//! Templates exported under more than one name, for notes shared between
+//! modalities (the same contrast reaction note for CT and MR). The Markdown
+//! file is written once and its front matter lists the other names:
+//!
+//! ```text
+//! ---
+//! aliases: [RM Reação ao Contraste, US Reação ao Contraste]
+//! ---
+//! ```
+//!
+//! Converting the template writes each output again under every alias,
+//! where a file of that name would be (`Templates_docx/RM Reação ao
+//! Contraste.docx`), as a plain copy that zip archives and Windows keep,
+//! unlike a symlink. The catalog, site, EPUB, snippets and PowerScribe
+//! AutoText list the template under each name, the index maps each alias to
+//! its file, `clean` keeps the copies and `verify` checks them; importing
+//! back into Markdown skips them.
+
+use anyhow::Result;
+use log::warn;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::encoding::read_text;
+use crate::files::{display_name, list_files};
+use crate::front_matter;
+
+/// The aliases the front matter of `source`, read as `content`, names.
+/// Names that are not a plain file name, or that another Markdown file
+/// beside `source` already has, are warned about and left out.
+pub fn of(source: &Path, content: &str) -> Vec<String> {
+    let Some(front) = front_matter::split(content)
+        .0
+        .and_then(|yaml| front_matter::parse(yaml).ok())
+    else {
+        return Vec::new();
+    };
+    front
+        .aliases
+        .into_iter()
+        .filter(|name| {
+            if name == "." || name == ".." || name.contains(['/', '\\']) {
+                warn!(
+                    "{}: alias `{}` is not a file name",
+                    display_name(source),
+                    name
+                );
+                false
+            } else if source.with_file_name(format!("{}.md", name)).exists() {
+                warn!(
+                    "{}: alias `{}` is the name of a template",
+                    display_name(source),
+                    name
+                );
+                false
+            } else {
+                true
+            }
+        })
+        .collect()
+}
+
+/// Where `target`, an output of the Markdown `source`, is copied to: the
+/// same folder and extension under each alias.
+pub fn targets(source: &Path, target: &Path) -> Result<Vec<PathBuf>> {
+    let extension = target
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    Ok(of(source, &read_text(source)?)
+        .into_iter()
+        .map(|name| target.with_file_name(format!("{}{}", name, extension)))
+        .collect())
+}
+
+/// Copies `target`, just converted from `source`, to its alias `targets`
+/// whose contents differ, and returns them.
+pub fn write(source: &Path, target: &Path) -> Result<Vec<PathBuf>> {
+    let aliases = targets(source, target)?;
+    if aliases.is_empty() {
+        return Ok(aliases);
+    }
+    let contents = fs::read(target)?;
+    for alias in &aliases {
+        if fs::read(alias).ok().as_ref() != Some(&contents) {
+            fs::write(alias, &contents)?;
+        }
+    }
+    Ok(aliases)
+}
+
+/// Every alias in the Markdown folder `dir`, with the template it names.
+/// An alias two templates claim stays with the first one, with a warning.
+pub fn in_folder(dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let mut aliases = BTreeMap::new();
+    if !dir.is_dir() {
+        return Ok(aliases);
+    }
+    for source in list_files(dir, "md")? {
+        for name in of(&source, &read_text(&source)?) {
+            if let Some(first) = aliases.get(&name) {
+                warn!(
+                    "{}: alias `{}` already names {}",
+                    display_name(&source),
+                    name,
+                    display_name(first)
+                );
+            } else {
+                aliases.insert(name, source.clone());
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outputs_are_copied_under_each_usable_alias() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("TC Reação ao Contraste.md");
+        fs::write(
+            &note,
+            "---\naliases: [RM Reação ao Contraste, ../fora, US Abdome]\n---\nNota\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("US Abdome.md"),
+            "---\naliases: RM Reação ao Contraste\n---\nUS\n",
+        )
+        .unwrap();
+
+        let aliases = in_folder(dir.path()).unwrap();
+        assert_eq!(aliases.len(), 1);
+        assert_eq!(aliases["RM Reação ao Contraste"], note);
+
+        let out = dir.path().join("out");
+        fs::create_dir(&out).unwrap();
+        let target = out.join("TC Reação ao Contraste.txt");
+        fs::write(&target, "Nota\n").unwrap();
+        let copy = out.join("RM Reação ao Contraste.txt");
+        assert_eq!(write(&note, &target).unwrap(), vec![copy.clone()]);
+        assert_eq!(fs::read_to_string(copy).unwrap(), "Nota\n");
+    }
+}
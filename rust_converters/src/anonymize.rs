@@ -0,0 +1,292 @@
+//! Patient identifiers pasted into templates by accident
+//! (`rtemplates anonymize`).
+//!
+//! Three kinds are looked for: CPF numbers (`123.456.789-09`, or eleven
+//! digits with valid check digits), calendar dates (`03/05/1961`,
+//! `1961-05-03`) and runs of two or more capitalized words, optionally
+//! joined by `da`, `de`, `do`, `das`, `dos` or `e` (`Maria da Silva`).
+//! Names are a heuristic and also catch eponyms or title-case headings, so
+//! hits are for review. Redacting replaces each with the `X` placeholders the
+//! templates already use.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::convert::{self, Format};
+use crate::encoding::read_text;
+use crate::files::{list_files, FileFilter};
+use crate::fill::FillOptions;
+use crate::front_matter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentifierKind {
+    Cpf,
+    Date,
+    Name,
+}
+
+impl IdentifierKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            IdentifierKind::Cpf => "cpf",
+            IdentifierKind::Date => "date",
+            IdentifierKind::Name => "name",
+        }
+    }
+
+    /// What `text`, an identifier of this kind, is redacted to.
+    fn placeholder(self, text: &str) -> &'static str {
+        match self {
+            IdentifierKind::Cpf => "XXX.XXX.XXX-XX",
+            IdentifierKind::Date if text.chars().nth(4) == Some('-') => "XXXX-XX-XX",
+            IdentifierKind::Date => "XX/XX/XXXX",
+            IdentifierKind::Name => "XXX",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    /// 1-based line number.
+    pub line: usize,
+    pub kind: IdentifierKind,
+    pub text: String,
+}
+
+fn cpf_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b(?:\d{3}\.\d{3}\.\d{3}-\d{2}|\d{11})\b").unwrap())
+}
+
+fn date_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\b(?:(\d{1,2})/(\d{1,2})/(\d{4}|\d{2})|(\d{4})-(\d{2})-(\d{2}))\b").unwrap()
+    })
+}
+
+fn name_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\b\p{Lu}\p{Ll}+(?:[ \t]+(?:(?:da|de|do|das|dos|e)[ \t]+)?\p{Lu}\p{Ll}+)+\b")
+            .unwrap()
+    })
+}
+
+/// Whether the eleven digits of `digits` end with the two CPF check digits.
+fn cpf_checks(digits: &[u32]) -> bool {
+    let check = |len: usize| {
+        let sum: u32 = (0..len)
+            .map(|i| digits[i] * (len as u32 + 1 - i as u32))
+            .sum();
+        (sum * 10 % 11) % 10
+    };
+    // Numbers of one repeated digit pass the check but are not issued.
+    digits.len() == 11
+        && digits.iter().any(|&d| d != digits[0])
+        && check(9) == digits[9]
+        && check(10) == digits[10]
+}
+
+fn is_cpf(text: &str) -> bool {
+    // Formatted numbers are flagged as they are; bare digits only when the
+    // check digits say so, leaving other long numbers alone.
+    text.contains('-') || {
+        let digits: Vec<u32> = text.chars().filter_map(|c| c.to_digit(10)).collect();
+        cpf_checks(&digits)
+    }
+}
+
+fn is_date(captures: &regex::Captures) -> bool {
+    let number = |i: usize| captures.get(i).and_then(|m| m.as_str().parse::<u32>().ok());
+    let (day, month) = match (number(1), number(2)) {
+        (Some(day), Some(month)) => (day, month),
+        _ => (number(6).unwrap_or(0), number(5).unwrap_or(0)),
+    };
+    (1..=31).contains(&day) && (1..=12).contains(&month)
+}
+
+/// The possible identifiers in one line, by byte range, in order and not
+/// overlapping.
+fn line_matches(line: &str) -> Vec<(std::ops::Range<usize>, IdentifierKind)> {
+    let mut found: Vec<(std::ops::Range<usize>, IdentifierKind)> = Vec::new();
+    let mut add = |range: std::ops::Range<usize>, kind| {
+        if !found
+            .iter()
+            .any(|(r, _)| r.start < range.end && range.start < r.end)
+        {
+            found.push((range, kind));
+        }
+    };
+    for m in cpf_regex().find_iter(line) {
+        if is_cpf(m.as_str()) {
+            add(m.range(), IdentifierKind::Cpf);
+        }
+    }
+    for captures in date_regex().captures_iter(line) {
+        if is_date(&captures) {
+            add(captures.get(0).unwrap().range(), IdentifierKind::Date);
+        }
+    }
+    for m in name_regex().find_iter(line) {
+        add(m.range(), IdentifierKind::Name);
+    }
+    found.sort_by_key(|(range, _)| range.start);
+    found
+}
+
+/// The possible identifiers in `text`.
+pub fn scan_text(text: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        for (range, kind) in line_matches(line) {
+            findings.push(Finding {
+                line: index + 1,
+                kind,
+                text: line[range].to_string(),
+            });
+        }
+    }
+    findings
+}
+
+/// `text` with every possible identifier replaced by its placeholder.
+pub fn redact_text(text: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            let mut redacted = String::new();
+            let mut position = 0;
+            for (range, kind) in line_matches(line) {
+                redacted.push_str(&line[position..range.start]);
+                redacted.push_str(kind.placeholder(&line[range.clone()]));
+                position = range.end;
+            }
+            redacted.push_str(&line[position..]);
+            redacted
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FileFindings {
+    pub path: PathBuf,
+    pub findings: Vec<Finding>,
+}
+
+/// Whether `redact_file` can rewrite `path`: Markdown and TXT. The other
+/// formats are generated from the Markdown.
+pub fn can_redact(path: &Path) -> bool {
+    matches!(convert::format_of(path), Some(Format::Md | Format::Txt))
+}
+
+/// The text a template is scanned in: the body of a Markdown or TXT file
+/// as written, with the number of front matter lines before it, or the
+/// Markdown read from any other format.
+fn scanned_text(path: &Path, format: Format) -> Result<(String, usize)> {
+    Ok(match format {
+        Format::Md | Format::Txt => {
+            let content = read_text(path)?;
+            let body = front_matter::strip(&content);
+            let offset = content[..content.len() - body.len()].lines().count();
+            (body.to_string(), offset)
+        }
+        _ => (
+            convert::render_text(format, Format::Md, path, &FillOptions::default())?,
+            0,
+        ),
+    })
+}
+
+/// Scans `path`, in any format the converters read.
+pub fn scan_file(path: &Path) -> Result<Vec<Finding>> {
+    let Some(format) = convert::format_of(path) else {
+        return Ok(Vec::new());
+    };
+    let (text, offset) =
+        scanned_text(path, format).with_context(|| format!("Cannot read {}", path.display()))?;
+    let mut findings = scan_text(&text);
+    for finding in &mut findings {
+        finding.line += offset;
+    }
+    Ok(findings)
+}
+
+/// Scans the DOCX (and RTF), Markdown, TXT, HTML and ODT folders under
+/// `root`, returning the files with possible identifiers.
+pub fn scan_folders(root: &Path, files: &FileFilter) -> Result<Vec<FileFindings>> {
+    let dirs = &crate::config::get().directories;
+    let folders = [
+        (&dirs.docx, "docx"),
+        (&dirs.docx, "rtf"),
+        (&dirs.markdown, "md"),
+        (&dirs.txt, "txt"),
+        (&dirs.html, "html"),
+        (&dirs.odt, "odt"),
+    ];
+    let mut results = Vec::new();
+    for (dir, extension) in folders {
+        let dir = root.join(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        for path in list_files(&dir, extension)? {
+            if !files.matches(path.strip_prefix(root).unwrap_or(&path)) {
+                continue;
+            }
+            let findings = scan_file(&path)?;
+            if !findings.is_empty() {
+                results.push(FileFindings { path, findings });
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// Rewrites a Markdown or TXT file with its identifiers redacted, keeping
+/// any front matter.
+pub fn redact_file(path: &Path) -> Result<()> {
+    let content = read_text(path)?;
+    let body = front_matter::strip(&content);
+    let front = &content[..content.len() - body.len()];
+    fs::write(path, format!("{}{}", front, redact_text(body)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_are_found_and_redacted() {
+        let text = "Paciente: Maria da Silva Santos, CPF 529.982.247-25 (52998224725).\n\
+                    Nascida em 03/05/1961; exame de 2024-13-01 e 2024-02-10.\n\
+                    Nódulo de 12345678901 mm em 1/2 do lobo. Fígado normal.";
+        let findings = scan_text(text);
+        let found: Vec<(usize, IdentifierKind, &str)> = findings
+            .iter()
+            .map(|f| (f.line, f.kind, f.text.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (1, IdentifierKind::Name, "Maria da Silva Santos"),
+                (1, IdentifierKind::Cpf, "529.982.247-25"),
+                (1, IdentifierKind::Cpf, "52998224725"),
+                (2, IdentifierKind::Date, "03/05/1961"),
+                (2, IdentifierKind::Date, "2024-02-10"),
+            ]
+        );
+        assert_eq!(
+            redact_text(text),
+            "Paciente: XXX, CPF XXX.XXX.XXX-XX (XXX.XXX.XXX-XX).\n\
+             Nascida em XX/XX/XXXX; exame de 2024-13-01 e XXXX-XX-XX.\n\
+             Nódulo de 12345678901 mm em 1/2 do lobo. Fígado normal."
+        );
+    }
+}
@@ -0,0 +1,448 @@
+use anyhow::{anyhow, bail, Result};
+use log::{info, warn};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::files::{glob_matches, list_files_recursive, slash_path, unique_dir, FileFilter};
+use crate::targets;
+use crate::timestamp::Timestamp;
+
+pub type IndexMap = HashMap<String, Vec<String>>;
+
+pub fn load_index(path: &Path) -> Result<IndexMap> {
+    if !path.exists() {
+        return Err(anyhow!(
+            "Index file not found at {}. Run generate_index first.",
+            path.display()
+        ));
+    }
+    let contents = fs::read_to_string(path)?;
+    // Only the folder lists matter here; other keys (such as `metadata`)
+    // are skipped.
+    let parsed: HashMap<String, serde_json::Value> = serde_json::from_str(&contents)?;
+    Ok(parsed
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, serde_json::from_value(value).ok()?)))
+        .collect())
+}
+
+fn should_keep(path: &Path, expected: &HashSet<String>, root: &Path) -> bool {
+    let rel = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string();
+    expected.contains(&rel)
+}
+
+/// Files of `folder` (holding `.ext` files) under `root` that are missing
+/// from `index` and selected by `filter`, relative to `root` in directory
+/// order.
+fn folder_unindexed(
+    root: &Path,
+    folder: &str,
+    ext: &str,
+    index: &IndexMap,
+    filter: &FileFilter,
+) -> Result<Vec<PathBuf>> {
+    let expected: HashSet<String> = index
+        .get(folder)
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    let mut found = Vec::new();
+    for entry in fs::read_dir(root.join(folder))? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let has_ext = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .map(|s| s.eq_ignore_ascii_case(ext))
+            .unwrap_or(false);
+        if !has_ext {
+            continue;
+        }
+        if should_keep(&path, &expected, root) {
+            continue;
+        }
+
+        let rel = path.strip_prefix(root).unwrap_or(&path);
+        if filter.matches(rel) {
+            found.push(rel.to_path_buf());
+        }
+    }
+    Ok(found)
+}
+
+/// The files a backup would move: those under `root` missing from `index`
+/// and selected by `filter`, relative to `root`.
+pub fn unindexed_files(root: &Path, index: &IndexMap, filter: &FileFilter) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for (folder, ext) in targets() {
+        if root.join(folder).exists() {
+            found.extend(folder_unindexed(root, folder, ext, index, filter)?);
+        }
+    }
+    Ok(found)
+}
+
+/// Moves files under `root` that are missing from `index` and selected by
+/// `filter` into `backup_dir`, mirroring their folder structure. Returns how
+/// many files were moved.
+pub fn move_unindexed(
+    root: &Path,
+    index: &IndexMap,
+    backup_dir: &Path,
+    filter: &FileFilter,
+) -> Result<usize> {
+    fs::create_dir_all(backup_dir)?;
+    let mut moved = 0usize;
+
+    for (folder, ext) in targets() {
+        let dir = root.join(folder);
+        if !dir.exists() {
+            warn!("Skipping missing folder: {}", dir.display());
+            continue;
+        }
+
+        for rel in folder_unindexed(root, folder, ext, index, filter)? {
+            let dest = backup_dir.join(&rel);
+            if dest.exists() {
+                warn!("Skip {}: destination already exists", rel.to_string_lossy());
+                continue;
+            }
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::rename(root.join(&rel), &dest)?;
+            moved += 1;
+            info!(
+                "Moved {} -> {}",
+                rel.to_string_lossy(),
+                dest.strip_prefix(root).unwrap_or(&dest).to_string_lossy()
+            );
+        }
+    }
+
+    Ok(moved)
+}
+
+/// Name of a snapshot folder taken at `time`, e.g. `2024-06-01T12-00-05`.
+pub fn snapshot_name(time: Timestamp) -> String {
+    format!(
+        "{}T{:02}-{:02}-{:02}",
+        time.iso_date(),
+        time.hour,
+        time.minute,
+        time.second
+    )
+}
+
+/// Moves the unindexed files into a new snapshot folder of `backup_dir`
+/// named after the current time, so earlier backups of the same file are
+/// kept. Returns the snapshot folder and how many files were moved; the
+/// folder is only left behind when something was moved.
+pub fn backup_snapshot(
+    root: &Path,
+    index: &IndexMap,
+    backup_dir: &Path,
+    filter: &FileFilter,
+) -> Result<(PathBuf, usize)> {
+    let snapshot = unique_dir(backup_dir, &snapshot_name(Timestamp::now()));
+    let moved = move_unindexed(root, index, &snapshot, filter)?;
+    if moved == 0 {
+        let _ = fs::remove_dir(&snapshot);
+    }
+    Ok((snapshot, moved))
+}
+
+/// A backup folder: a timestamped snapshot or, for backups made before
+/// snapshots existed, the files kept directly in `backup/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Folder name, `None` for the unversioned files.
+    pub name: Option<String>,
+    pub files: usize,
+}
+
+/// One backed-up file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackedUp {
+    pub snapshot: Option<String>,
+    /// Where the file was moved from, relative to the root.
+    pub path: PathBuf,
+}
+
+impl BackedUp {
+    /// Location inside `backup_dir`.
+    pub fn location(&self, backup_dir: &Path) -> PathBuf {
+        match &self.snapshot {
+            Some(name) => backup_dir.join(name).join(&self.path),
+            None => backup_dir.join(&self.path),
+        }
+    }
+
+    /// `snapshot/path`, as listed to the user.
+    pub fn listed_path(&self) -> String {
+        let path = slash_path(&self.path);
+        match &self.snapshot {
+            Some(name) => format!("{}/{}", name, path),
+            None => path,
+        }
+    }
+}
+
+fn is_target_folder(name: &str) -> bool {
+    targets().iter().any(|(folder, _)| *folder == name)
+}
+
+/// Every backed-up file, oldest snapshot first; empty when there is no
+/// backup folder.
+pub fn list_backed_up(backup_dir: &Path) -> Result<Vec<BackedUp>> {
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for rel in list_files_recursive(backup_dir)? {
+        let mut components = rel.components();
+        let first = components
+            .next()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if is_target_folder(&first) {
+            files.push(BackedUp {
+                snapshot: None,
+                path: rel,
+            });
+        } else if components.clone().next().is_some() {
+            files.push(BackedUp {
+                snapshot: Some(first),
+                path: components.as_path().to_path_buf(),
+            });
+        }
+    }
+    // Unversioned files predate every snapshot; snapshot names sort by time.
+    files.sort_by(|a, b| (&a.snapshot, &a.path).cmp(&(&b.snapshot, &b.path)));
+    Ok(files)
+}
+
+/// The snapshots in `backup_dir` with their file counts, oldest first.
+pub fn list_snapshots(backup_dir: &Path) -> Result<Vec<Snapshot>> {
+    let mut snapshots: Vec<Snapshot> = Vec::new();
+    for file in list_backed_up(backup_dir)? {
+        match snapshots.last_mut() {
+            Some(last) if last.name == file.snapshot => last.files += 1,
+            _ => snapshots.push(Snapshot {
+                name: file.snapshot,
+                files: 1,
+            }),
+        }
+    }
+    Ok(snapshots)
+}
+
+/// The backed-up files matching any of `patterns`, each an exact path or a
+/// glob (`Templates_docx/TC*`, `*.md`) matched against the original path or
+/// the listed `snapshot/path`. When several snapshots hold the same file,
+/// only the newest is selected.
+pub fn select_backed_up(backed_up: &[BackedUp], patterns: &[String]) -> Result<Vec<BackedUp>> {
+    let mut selected: Vec<BackedUp> = Vec::new();
+    for pattern in patterns {
+        let pattern = pattern.replace('\\', "/");
+        let matches: Vec<&BackedUp> = backed_up
+            .iter()
+            .filter(|file| {
+                glob_matches(&pattern, &slash_path(&file.path))
+                    || glob_matches(&pattern, &file.listed_path())
+            })
+            .collect();
+        if matches.is_empty() {
+            bail!("No backed-up file matches {}", pattern);
+        }
+        for file in matches {
+            if !selected.contains(file) {
+                selected.push(file.clone());
+            }
+        }
+    }
+    Ok(newest_versions(selected))
+}
+
+/// Keeps the newest version of each original path.
+pub fn newest_versions(files: Vec<BackedUp>) -> Vec<BackedUp> {
+    let mut newest: BTreeMap<PathBuf, BackedUp> = BTreeMap::new();
+    for file in files {
+        match newest.get(&file.path) {
+            Some(kept) if kept.snapshot >= file.snapshot => {}
+            _ => {
+                newest.insert(file.path.clone(), file);
+            }
+        }
+    }
+    newest.into_values().collect()
+}
+
+/// Moves each of `files` back under `root`. Existing files are only
+/// replaced with `force`. Returns how many files were restored.
+pub fn restore(root: &Path, backup_dir: &Path, files: &[BackedUp], force: bool) -> Result<usize> {
+    let mut restored = 0usize;
+    for file in files {
+        let source = file.location(backup_dir);
+        let dest = root.join(&file.path);
+        if dest.exists() && !force {
+            warn!(
+                "Skip {}: destination already exists (use --force to overwrite)",
+                file.listed_path()
+            );
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::rename(&source, &dest)?;
+        restored += 1;
+        info!("Restored {}", file.listed_path());
+
+        // Drop folders the restore left empty, up to the backup root.
+        let mut dir = source.parent();
+        while let Some(current) = dir {
+            if current == backup_dir || fs::remove_dir(current).is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn restore_brings_back_the_newest_selected_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, backup_dir) = (dir.path().join("repo"), dir.path().join("backup"));
+        let md = &config::get().directories.markdown;
+        let file = |path: PathBuf, text: &str| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        };
+        file(backup_dir.join(md).join("TC.md"), "old");
+        file(
+            backup_dir
+                .join("2024-06-01T12-00-05")
+                .join(md)
+                .join("TC.md"),
+            "newer",
+        );
+        file(
+            backup_dir
+                .join("2024-06-02T08-30-00")
+                .join(md)
+                .join("RM.md"),
+            "rm",
+        );
+        file(root.join(md).join("RM.md"), "kept");
+
+        let backed_up = list_backed_up(&backup_dir).unwrap();
+        let listed: Vec<String> = backed_up.iter().map(BackedUp::listed_path).collect();
+        assert_eq!(
+            listed,
+            [
+                format!("{}/TC.md", md),
+                format!("2024-06-01T12-00-05/{}/TC.md", md),
+                format!("2024-06-02T08-30-00/{}/RM.md", md),
+            ]
+        );
+
+        assert!(select_backed_up(&backed_up, &["US*".to_string()]).is_err());
+        let selected = select_backed_up(&backed_up, &["*.md".to_string()]).unwrap();
+        assert_eq!(selected, [backed_up[2].clone(), backed_up[1].clone()]);
+
+        // RM.md is still in the repository, so only TC.md comes back.
+        assert_eq!(restore(&root, &backup_dir, &selected, false).unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(root.join(md).join("TC.md")).unwrap(),
+            "newer"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join(md).join("RM.md")).unwrap(),
+            "kept"
+        );
+        assert!(!backup_dir.join("2024-06-01T12-00-05").exists());
+
+        assert_eq!(
+            restore(&root, &backup_dir, &selected[..1], true).unwrap(),
+            1
+        );
+        assert_eq!(
+            fs::read_to_string(root.join(md).join("RM.md")).unwrap(),
+            "rm"
+        );
+        assert_eq!(list_backed_up(&backup_dir).unwrap(), [backed_up[0].clone()]);
+    }
+
+    #[test]
+    fn each_backup_moves_into_its_own_snapshot() {
+        let time = Timestamp {
+            year: 2024,
+            month: 6,
+            day: 1,
+            hour: 12,
+            minute: 0,
+            second: 5,
+        };
+        assert_eq!(snapshot_name(time), "2024-06-01T12-00-05");
+
+        let dir = tempfile::tempdir().unwrap();
+        let (root, backup_dir) = (dir.path().join("repo"), dir.path().join("backup"));
+        let md = &config::get().directories.markdown;
+        fs::create_dir_all(root.join(md)).unwrap();
+        let kept = format!("{}/TC.md", md);
+        let index: IndexMap = HashMap::from([(md.clone(), vec![kept.clone()])]);
+        fs::write(root.join(&kept), "indexed").unwrap();
+
+        let mut snapshots = Vec::new();
+        for text in ["first", "second"] {
+            fs::write(root.join(md).join("Old.md"), text).unwrap();
+            let (snapshot, moved) =
+                backup_snapshot(&root, &index, &backup_dir, &FileFilter::default()).unwrap();
+            assert_eq!(moved, 1);
+            snapshots.push(snapshot);
+        }
+        assert_ne!(snapshots[0], snapshots[1]);
+        assert!(root.join(&kept).exists());
+        assert_eq!(
+            fs::read_to_string(snapshots[0].join(md).join("Old.md")).unwrap(),
+            "first"
+        );
+
+        // Nothing left to move: no empty snapshot is left behind.
+        let (snapshot, moved) =
+            backup_snapshot(&root, &index, &backup_dir, &FileFilter::default()).unwrap();
+        assert_eq!(moved, 0);
+        assert!(!snapshot.exists());
+
+        let listed = list_snapshots(&backup_dir).unwrap();
+        let names: Vec<Option<String>> = snapshots
+            .iter()
+            .map(|s| Some(s.file_name().unwrap().to_string_lossy().into_owned()))
+            .collect();
+        assert_eq!(
+            listed,
+            names
+                .into_iter()
+                .map(|name| Snapshot { name, files: 1 })
+                .collect::<Vec<_>>()
+        );
+    }
+}
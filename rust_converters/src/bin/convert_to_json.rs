@@ -0,0 +1,10 @@
+use anyhow::Result;
+use report_template_converters::{config, convert_to_json, logging, CONFIG_FILE};
+use std::path::Path;
+
+fn main() -> Result<()> {
+    logging::init(logging::level_from_env());
+    config::init_from_file(Path::new(CONFIG_FILE))?;
+    let dirs = &config::get().directories;
+    convert_to_json::convert_folder(Path::new(&dirs.markdown), Path::new(&dirs.json))
+}
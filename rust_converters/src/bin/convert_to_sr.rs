@@ -0,0 +1,12 @@
+use anyhow::Result;
+use report_template_converters::convert_to_sr::{self, SrMetadata};
+use report_template_converters::{config, logging, CONFIG_FILE};
+use std::path::Path;
+
+fn main() -> Result<()> {
+    logging::init(logging::level_from_env());
+    config::init_from_file(Path::new(CONFIG_FILE))?;
+    let dirs = &config::get().directories;
+    let metadata = SrMetadata::from_config(config::get());
+    convert_to_sr::convert_folder(Path::new(&dirs.markdown), Path::new(&dirs.sr), &metadata)
+}
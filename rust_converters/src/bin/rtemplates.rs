@@ -0,0 +1,1792 @@
+//! Unified command-line entry point for every template tool.
+
+use anyhow::{bail, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use report_template_converters::convert::{self, BatchOptions, Format};
+use report_template_converters::convert_to_txt::render_md_text;
+use report_template_converters::encoding::read_text;
+use report_template_converters::exports;
+use report_template_converters::files::{display_name, output_path, FileFilter};
+use report_template_converters::fill::FillOptions;
+use report_template_converters::phrases::{self, PhraseLibrary};
+use report_template_converters::query::TemplateQuery;
+use report_template_converters::sync::SyncAction;
+use report_template_converters::template::SectionKind;
+use report_template_converters::timestamp::Timestamp;
+use report_template_converters::{
+    anonymize, backup, browse, catalog, classify, clean, clipboard, config, dedupe, diff,
+    docx_diff, epub, generate_index, history, hl7, hook, inventory, journal, language, lint,
+    logging, pack, pick, powerscribe, scaffold, search, serve, signature, site, snippets, stats,
+    sync, translate, verify, watch, CACHE_FILE, CONFIG_FILE, EXTERNAL_DIR, HISTORY_DIR, INDEX_FILE,
+};
+use serde::Serialize;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+#[command(
+    name = "rtemplates",
+    version,
+    about = "Report template converter tools"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Settings file with the folders and document defaults
+    #[arg(long, global = true, default_value = CONFIG_FILE)]
+    config: PathBuf,
+    /// Body font, overriding the config file
+    #[arg(long, global = true)]
+    font: Option<String>,
+    /// Body font size in points, overriding the config file
+    #[arg(long, global = true, value_parser = clap::value_parser!(i32).range(1..=288))]
+    font_size: Option<i32>,
+    /// Language tag of the exported documents (e.g. pt-BR), overriding the config file
+    #[arg(long, global = true)]
+    language: Option<String>,
+    /// .docx or .dotx whose styles, page header and footer DOCX output uses, overriding the config file
+    #[arg(long, global = true, value_name = "FILE")]
+    reference_docx: Option<String>,
+    /// Institution profile (`[sites.<id>]` of the config) whose letterhead,
+    /// footer and details the outputs carry
+    #[arg(long, global = true, value_name = "ID")]
+    site: Option<String>,
+    /// Section written in DOCX as a locked content control, read-only in Word (repeatable), overriding the config file
+    #[arg(long = "lock-section", global = true, value_name = "SECTION", value_parser = parse_section)]
+    lock_sections: Vec<SectionKind>,
+    /// Accept or reject the tracked changes of the DOCX files read, overriding the config file
+    #[arg(long, global = true, value_enum, value_name = "ACTION")]
+    tracked_changes: Option<config::TrackedChanges>,
+    /// Messages to print: off, error, warn, info, debug or trace (default from RTEMPLATES_LOG, else info)
+    #[arg(long, global = true, value_name = "LEVEL", value_parser = parse_level)]
+    log_level: Option<log::LevelFilter>,
+}
+
+fn parse_level(value: &str) -> Result<log::LevelFilter, String> {
+    value
+        .parse()
+        .map_err(|_| "expected off, error, warn, info, debug or trace".to_string())
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert templates between DOCX, ODT, HTML, Markdown and TXT, or export to PDF, JSON, MRRT and DICOM SR
+    Convert(ConvertArgs),
+    /// Export Markdown templates edited since their DOCX to it, import DOCX edited in Word back, and list the pairs edited on both sides
+    Sync(SyncArgs),
+    /// Build reports_index.json for the template folders
+    Index(IoArgs),
+    /// Move files missing from reports_index.json into a timestamped backup/ snapshot
+    Backup(BackupArgs),
+    /// List backed-up files, or move selected ones back to their folders
+    Restore(RestoreArgs),
+    /// Move outputs whose Markdown source is gone to the trash, journaled for `undo`
+    Clean(CleanArgs),
+    /// Put back the files removed by the last `clean`
+    Undo(RootArgs),
+    /// List the indexed templates matching a modality, body region or keyword
+    Query(QueryArgs),
+    /// Zip the templates matching a query, in every format, with their index and a manifest
+    ExportPack(ExportPackArgs),
+    /// Unpack a template pack into external/<namespace>/ and merge its index into reports_index.json
+    ImportPack(ImportPackArgs),
+    /// Choose a template by fuzzy-matching its title and print it as plain text
+    Pick(PickArgs),
+    /// Browse the Markdown templates on the terminal: preview them, edit their keywords, modality and region, convert and lint them
+    Browse(BrowseArgs),
+    /// Print one template as plain text, or copy it to the clipboard
+    Show(ShowArgs),
+    /// Serve the template list over HTTP, with a WebSocket announcing each template changed on disk
+    Serve(ServeArgs),
+    /// Search the Markdown templates and print the matching lines
+    Search(SearchArgs),
+    /// Search the phrase library, or add a phrase to a template as `{{phrase ID}}`
+    Phrase(PhraseArgs),
+    /// Build an HL7 v2 ORU^R01 message from a filled-in template
+    Hl7(Hl7Args),
+    /// Cross-check the DOCX/Markdown/TXT folders and reports_index.json, writing a JSON report
+    Verify(IoArgs),
+    /// Compare two templates, in any format, section by section
+    Diff(DiffArgs),
+    /// Compare the paragraphs of two DOCX files, text and formatting, as a unified diff
+    DocxDiff(DocxDiffArgs),
+    /// Record the current version of a template in its history
+    Commit(CommitArgs),
+    /// List the recorded versions of a template
+    History(HistoryArgs),
+    /// Report clusters of identical and near-identical Markdown templates
+    Dedupe(DedupeArgs),
+    /// Look for CPF numbers, dates and names pasted into the templates, and optionally redact them
+    Anonymize(AnonymizeArgs),
+    /// Check the Markdown templates against the rules in the [lint] settings
+    Lint(LintArgs),
+    /// Git pre-commit hook: lint the staged templates, then regenerate and stage their outputs
+    Hook(HookArgs),
+    /// Copy a template with its headings translated and its text left as TODO lines
+    TranslateSkeleton(TranslateArgs),
+    /// Print the modality and body region of each Markdown template, optionally adding them to the front matter
+    Classify(ClassifyArgs),
+    /// Combine the Markdown templates into one handbook grouped by modality and body region
+    Catalog(CatalogArgs),
+    /// Build a static website for browsing, searching and copying the Markdown templates
+    Site(SiteArgs),
+    /// Bundle the Markdown templates into one EPUB book, grouped by modality with contents
+    Epub(EpubArgs),
+    /// List the Markdown templates with their title, modality, region, sections, word count and date, as CSV or XLSX
+    Inventory(InventoryArgs),
+    /// Count the Markdown templates by modality, body region and section, and list those missing an impression
+    Stats(StatsArgs),
+    /// Start a Markdown template with the standard front matter, sections and department footer
+    New(NewArgs),
+    /// Export the Markdown templates, or some of their sections, as espanso, AutoHotkey, TextExpander, aText or macOS snippets
+    #[command(alias = "espanso")]
+    Snippets(SnippetsArgs),
+    /// Export the Markdown templates as PowerScribe AutoText (RTF in XML)
+    Powerscribe(PowerscribeArgs),
+}
+
+/// Input/output locations shared by every subcommand.
+#[derive(Args)]
+struct IoArgs {
+    /// Source folder (defaults depend on the subcommand)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Destination folder or file (defaults depend on the subcommand)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Glob selection of the files a batch subcommand works on.
+#[derive(Args)]
+struct FilterArgs {
+    /// Only process files matching this glob, e.g. 'TC*' (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+    /// Skip files matching this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+}
+
+impl From<FilterArgs> for FileFilter {
+    fn from(args: FilterArgs) -> Self {
+        FileFilter {
+            include: args.include,
+            exclude: args.exclude,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    /// Every file with its status, duration and warnings, plus totals
+    Json,
+}
+
+/// `convert --report json`.
+#[derive(Serialize)]
+struct RunReport<'a> {
+    command: &'static str,
+    from: &'static str,
+    to: &'static str,
+    input: &'a Path,
+    output: &'a Path,
+    started: String,
+    #[serde(flatten)]
+    batch: &'a convert::BatchReport,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// `-` converts one document from standard input to standard output
+    #[arg(value_name = "-", conflicts_with_all = ["input", "output", "watch", "to_clipboard"])]
+    stream: Option<String>,
+    /// Document name used in place of the file name when converting from standard input
+    #[arg(long, requires = "stream", default_value = "document")]
+    name: String,
+    /// Source format
+    #[arg(long, value_enum)]
+    from: Format,
+    /// Target format
+    #[arg(long, value_enum)]
+    to: Format,
+    #[command(flatten)]
+    io: IoArgs,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// Reconvert every file, ignoring the content-hash cache
+    #[arg(long)]
+    force: bool,
+    /// Only convert the sources git reports as changed since REF (default HEAD), or new
+    #[arg(long, value_name = "REF", num_args = 0..=1, default_missing_value = "HEAD", conflicts_with_all = ["watch", "to_clipboard", "stream"])]
+    changed: Option<String>,
+    /// Keep running and reconvert each source file when it is saved
+    #[arg(long)]
+    watch: bool,
+    /// Stop at the first file that fails instead of converting the others
+    #[arg(long)]
+    fail_fast: bool,
+    /// Treat the --from format as the only source of truth: refuse to overwrite outputs edited since reports_index.json recorded them
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["watch", "to_clipboard", "stream"])]
+    canonical: Option<Format>,
+    /// Only print the files that would be written; exits non-zero if an existing file would change
+    #[arg(long, conflicts_with_all = ["watch", "to_clipboard", "stream"])]
+    dry_run: bool,
+    /// Print a run report on standard output (messages go to standard error)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["watch", "to_clipboard", "stream", "dry_run"])]
+    report: Option<ReportFormat>,
+    /// Set a variable for `{{#if NAME}}` blocks in Markdown sources (repeatable)
+    #[arg(long = "set", value_name = "NAME[=VALUE]")]
+    variables: Vec<String>,
+    /// Add this [signatures.ID] block of rtemplates.toml before the footer
+    #[arg(long, value_name = "ID")]
+    signer: Option<String>,
+    /// Folder of the `{{> name}}` partials (defaults to partials/)
+    #[arg(long)]
+    partials: Option<PathBuf>,
+    /// Folder of the `{{phrase id}}` phrase library (defaults to phrases/)
+    #[arg(long)]
+    phrases: Option<PathBuf>,
+    /// Copy the result to the clipboard instead of writing files (--to md, txt or rtf, --input is one file)
+    #[arg(long, conflicts_with = "watch")]
+    to_clipboard: bool,
+}
+
+#[derive(Args)]
+struct SyncArgs {
+    /// Markdown folder (--input) and DOCX folder (--output)
+    #[command(flatten)]
+    io: IoArgs,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// Only print what would be exported and imported
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct BackupArgs {
+    #[command(subcommand)]
+    action: Option<BackupAction>,
+    #[command(flatten)]
+    io: IoArgs,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// Only print the files that would be moved; exits non-zero if there are any
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Subcommand)]
+enum BackupAction {
+    /// List the backup snapshots and how many files each holds
+    List,
+}
+
+#[derive(Args)]
+struct RestoreArgs {
+    /// Backed-up files to restore: paths as listed, or globs such as 'Templates_docx/TC*'
+    files: Vec<String>,
+    /// Restore every backed-up file (the newest version of each)
+    #[arg(long, conflicts_with = "files")]
+    all: bool,
+    /// Only restore from this snapshot
+    #[arg(long)]
+    snapshot: Option<String>,
+    /// Overwrite files that already exist
+    #[arg(long)]
+    force: bool,
+    /// Root holding the template folders
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Backup folder (defaults to backup/ under the root)
+    #[arg(long)]
+    backup_dir: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct HookArgs {
+    #[command(flatten)]
+    root: RootArgs,
+    /// Formats to regenerate (repeatable)
+    #[arg(long, value_enum, value_name = "FORMAT", default_values_t = [Format::Docx, Format::Txt])]
+    to: Vec<Format>,
+    /// Write .git/hooks/pre-commit to run `rtemplates hook` instead
+    #[arg(long, conflicts_with = "to")]
+    install: bool,
+}
+
+#[derive(Args)]
+struct RootArgs {
+    /// Root holding the template folders
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct CleanArgs {
+    #[command(flatten)]
+    root: RootArgs,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// Only list the orphaned outputs
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    /// Modality code, e.g. CT or MR
+    #[arg(long)]
+    modality: Option<String>,
+    /// Body region from the front matter, e.g. abdome
+    #[arg(long)]
+    region: Option<String>,
+    /// Text in the title, section names or contents
+    #[arg(long)]
+    keyword: Option<String>,
+    /// Front matter tag, e.g. oncologia (repeatable; all must match)
+    #[arg(long, value_name = "TAG")]
+    tag: Vec<String>,
+    /// Root holding the template folders and reports_index.json
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// List the templates under a heading per modality and body region
+    #[arg(long)]
+    group: bool,
+}
+
+#[derive(Args)]
+struct ExportPackArgs {
+    /// Modality code, e.g. CT or MR
+    #[arg(long)]
+    modality: Option<String>,
+    /// Body region from the front matter, e.g. abdome
+    #[arg(long)]
+    region: Option<String>,
+    /// Text in the title, section names or contents
+    #[arg(long)]
+    keyword: Option<String>,
+    /// Front matter tag, e.g. oncologia (repeatable; all must match)
+    #[arg(long, value_name = "TAG")]
+    tag: Vec<String>,
+    /// Root holding the template folders and reports_index.json
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// The zip file to write
+    #[arg(short, long, default_value = "templates-pack.zip")]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct ImportPackArgs {
+    /// The zip written by `export-pack`
+    pack: PathBuf,
+    /// Folder under external/ to unpack into (defaults to the zip's name, e.g. hospital_x)
+    #[arg(long)]
+    namespace: Option<String>,
+    /// Root holding the template folders and reports_index.json
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Replace files imported earlier that the pack changes
+    #[arg(long)]
+    overwrite: bool,
+    /// Only list what would be imported
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct PickArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Copy the chosen template to the clipboard instead of printing it
+    #[arg(long)]
+    to_clipboard: bool,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Address to listen on; 0.0.0.0 lets other machines connect
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    #[arg(long, default_value_t = 8750)]
+    port: u16,
+}
+
+#[derive(Args)]
+struct BrowseArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Format `c` converts the selected template to, into its folder (repeatable)
+    #[arg(long, value_enum, default_values = ["docx", "txt"])]
+    to: Vec<Format>,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct PhraseArgs {
+    /// Words to look for in the phrase IDs, file names and text; with --insert or --to-clipboard, a phrase ID
+    query: Vec<String>,
+    /// Folder of the phrase library (defaults to phrases/)
+    #[arg(long)]
+    phrases: Option<PathBuf>,
+    /// Add `{{phrase ID}}` at the end of a section of this template (a file, or a name in Templates_markdown)
+    #[arg(long, value_name = "TEMPLATE")]
+    insert: Option<String>,
+    /// Section --insert adds the phrase to
+    #[arg(long, default_value = "aspectos_observados", value_parser = parse_section, requires = "insert")]
+    section: SectionKind,
+    /// Copy the phrase's text to the clipboard
+    #[arg(long)]
+    to_clipboard: bool,
+}
+
+#[derive(Args)]
+struct ShowArgs {
+    /// Template file, or the name of one in Templates_markdown (e.g. AngioTEP)
+    template: String,
+    /// Copy the text to the clipboard instead of printing it
+    #[arg(long)]
+    to_clipboard: bool,
+    /// Set a variable for `{{#if NAME}}` blocks (repeatable)
+    #[arg(long = "set", value_name = "NAME[=VALUE]")]
+    variables: Vec<String>,
+    /// Add this [signatures.ID] block of rtemplates.toml before the footer
+    #[arg(long, value_name = "ID")]
+    signer: Option<String>,
+    /// Print the template as it was at this recorded version (see `history`)
+    #[arg(long, value_name = "VERSION")]
+    at: Option<usize>,
+}
+
+#[derive(Args)]
+struct CommitArgs {
+    /// Template file, or the name of one in Templates_markdown
+    template: String,
+    /// What changed in this version
+    #[arg(short, long)]
+    message: Option<String>,
+}
+
+#[derive(Args)]
+struct LintArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Rewrite the measurements in the department style before checking
+    #[arg(long)]
+    fix: bool,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct ClassifyArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Add the modality and body region to the front matter of templates that lack them
+    #[arg(long)]
+    write: bool,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct CatalogArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Catalog file; .md, .html or .pdf by extension
+    #[arg(short, long, default_value = "catalog.html")]
+    output: PathBuf,
+    /// What the headings group the templates by
+    #[arg(long, value_enum, default_value = "modality")]
+    by: catalog::Grouping,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct EpubArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// EPUB file to write
+    #[arg(short, long, default_value = "templates.epub")]
+    output: PathBuf,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct InventoryArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Inventory file; .csv or .xlsx by extension
+    #[arg(short, long, default_value = "inventory.csv")]
+    output: PathBuf,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct StatsArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Print the statistics as JSON
+    #[arg(long)]
+    json: bool,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct NewArgs {
+    /// Modality: CT (TC), MR (RM), US, MG or DX (RX)
+    #[arg(long, value_parser = parse_modality)]
+    modality: scaffold::Modality,
+    /// Body region, or several (`torax abdome pelve`)
+    #[arg(long)]
+    region: String,
+    /// With intravenous contrast
+    #[arg(long)]
+    contrast: bool,
+    /// Author in the front matter
+    #[arg(long)]
+    author: Option<String>,
+    /// Template file (defaults to Templates_markdown/<name>.md, e.g. `TC Tórax COM.md`)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Overwrite the template file if it exists
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct SiteArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Folder the site is written to
+    #[arg(short, long, default_value = "site")]
+    output: PathBuf,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct SnippetsArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Snippet file to write: .yml (espanso), .ahk (AutoHotkey), .textexpander (TextExpander, aText), .csv (aText) or .plist (macOS text replacements)
+    #[arg(short, long, default_value = "templates.yml")]
+    output: PathBuf,
+    /// Put before every trigger instead of the [snippets] prefix
+    #[arg(long)]
+    prefix: Option<String>,
+    /// Export this section of each template as its own snippet instead of the whole template (repeatable)
+    #[arg(long, value_name = "SECTION")]
+    section: Vec<String>,
+    /// Leave out snippets longer than this many characters (for .plist, [snippets] max_length by default)
+    #[arg(long, value_name = "CHARS")]
+    max_length: Option<usize>,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct PowerscribeArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// AutoText XML file to write
+    #[arg(short, long, default_value = "autotext.xml")]
+    output: PathBuf,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Args)]
+struct TranslateArgs {
+    /// Template file, or the name of one in Templates_markdown
+    template: String,
+    /// Target language: pt, en or es
+    #[arg(long, value_parser = parse_language)]
+    to: language::Language,
+    /// Skeleton file (defaults to NAME.<language>.md beside the template)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Overwrite the skeleton file if it exists
+    #[arg(long)]
+    force: bool,
+}
+
+fn parse_modality(value: &str) -> Result<scaffold::Modality, String> {
+    scaffold::Modality::parse(value)
+        .ok_or_else(|| "expected CT/TC, MR/RM, US, MG or DX/RX".to_string())
+}
+
+fn parse_section(value: &str) -> Result<SectionKind, String> {
+    SectionKind::from_key(value).ok_or_else(|| {
+        "expected indicacao_clinica, tecnica, aspectos_observados or impressao".to_string()
+    })
+}
+
+fn parse_language(value: &str) -> Result<language::Language, String> {
+    language::Language::from_code(value).ok_or_else(|| "expected pt, en or es".to_string())
+}
+
+#[derive(Args)]
+struct AnonymizeArgs {
+    #[command(flatten)]
+    root: RootArgs,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// Replace the identifiers in the Markdown and TXT files with X placeholders
+    #[arg(long)]
+    redact: bool,
+}
+
+#[derive(Args)]
+struct DedupeArgs {
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Similarity from which templates are reported together, from 0 to 1
+    #[arg(long, default_value_t = dedupe::DEFAULT_THRESHOLD, value_parser = parse_threshold)]
+    threshold: f64,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+fn parse_threshold(value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|threshold| (0.0..=1.0).contains(threshold))
+        .ok_or_else(|| "expected a number from 0 to 1".to_string())
+}
+
+#[derive(Args)]
+struct HistoryArgs {
+    /// Template file, or the name of one in Templates_markdown
+    template: String,
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    /// Text to look for (ignoring case and accents unless --case-sensitive)
+    query: String,
+    /// Treat the query as a regular expression
+    #[arg(long)]
+    regex: bool,
+    /// Match letter case exactly
+    #[arg(long)]
+    case_sensitive: bool,
+    /// Only search one section: titulo, indicacao_clinica, tecnica, aspectos_observados, impressao or rodape
+    #[arg(long)]
+    section: Option<String>,
+    /// Only search templates with this front matter tag (repeatable; all must match)
+    #[arg(long, value_name = "TAG")]
+    tag: Vec<String>,
+    /// Folder of Markdown templates (defaults to Templates_markdown)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// Never highlight matches (they are highlighted when writing to a terminal)
+    #[arg(long)]
+    no_color: bool,
+    /// List the templates under a heading per modality and body region
+    #[arg(long)]
+    group: bool,
+}
+
+#[derive(Args)]
+struct Hl7Args {
+    /// Filled-in template (.md or .docx)
+    template: PathBuf,
+    /// JSON file with the patient and exam fields
+    #[arg(long)]
+    fields: PathBuf,
+    /// Message file to write (defaults to standard output)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    /// The template before the change (.md, .docx, .rtf, .odt, .html or .txt)
+    old: PathBuf,
+    /// The template after the change
+    new: PathBuf,
+    /// List the unchanged sections too
+    #[arg(long)]
+    all: bool,
+    /// Never color the changes (they are colored when writing to a terminal)
+    #[arg(long)]
+    no_color: bool,
+}
+
+#[derive(Args)]
+struct DocxDiffArgs {
+    /// The DOCX before the change
+    old: PathBuf,
+    /// The DOCX after the change
+    #[arg(required_unless_present = "textconv")]
+    new: Option<PathBuf>,
+    /// Print the paragraphs of OLD alone, one per line, for git's `diff.<driver>.textconv`
+    #[arg(long, conflicts_with_all = ["new", "context", "no_color"])]
+    textconv: bool,
+    /// Unchanged paragraphs shown around each change
+    #[arg(short = 'U', long, default_value_t = 2)]
+    context: usize,
+    /// Never color the changes (they are colored when writing to a terminal)
+    #[arg(long)]
+    no_color: bool,
+}
+
+fn run_convert(args: ConvertArgs) -> Result<()> {
+    if let Some(stream) = &args.stream {
+        if stream != "-" {
+            bail!("Expected `-` for standard input, not {}", stream);
+        }
+    }
+    let input = args
+        .io
+        .input
+        .unwrap_or_else(|| PathBuf::from(args.from.default_dir()));
+    let output = args
+        .io
+        .output
+        .unwrap_or_else(|| PathBuf::from(args.to.default_dir()));
+
+    if args
+        .canonical
+        .is_some_and(|canonical| canonical != args.from)
+    {
+        bail!("--canonical guards the outputs of its format; give it the same format as --from");
+    }
+    let mut fill = FillOptions {
+        partials_dir: args
+            .partials
+            .unwrap_or_else(|| PathBuf::from(&config::get().directories.partials)),
+        phrases_dir: args
+            .phrases
+            .unwrap_or_else(|| PathBuf::from(&config::get().directories.phrases)),
+        signature: args.signer.as_deref().map(signature::profile).transpose()?,
+        ..FillOptions::default()
+    };
+    for assignment in &args.variables {
+        fill.set(assignment)?;
+    }
+    if args.stream.is_some() {
+        return convert::convert_stream(
+            args.from,
+            args.to,
+            &args.name,
+            &mut std::io::stdin().lock(),
+            &mut std::io::stdout().lock(),
+            &fill,
+        );
+    }
+    if args.to_clipboard {
+        if !input.is_file() {
+            bail!("--to-clipboard needs --input to be one template file");
+        }
+        let text = convert::render_text(args.from, args.to, &input, &fill)?;
+        if args.to == Format::Rtf {
+            clipboard::copy_rtf(&text)?;
+        } else {
+            clipboard::copy(&text)?;
+        }
+        println!("✓ {} copied to the clipboard", display_name(&input));
+        return Ok(());
+    }
+    let options = BatchOptions {
+        cache_path: Some(PathBuf::from(CACHE_FILE)),
+        force: args.force,
+        fill,
+        files: args.filter.into(),
+        fail_fast: args.fail_fast,
+        history_dir: Some(PathBuf::from(HISTORY_DIR)),
+        canonical_index: args.canonical.map(|_| PathBuf::from(INDEX_FILE)),
+        changed_since: args.changed,
+    };
+    if args.dry_run {
+        let planned = convert::plan_folder(args.from, args.to, &input, &output, &options)?;
+        for write in &planned {
+            let action = if write.overwrites {
+                "overwrite"
+            } else {
+                "create"
+            };
+            println!(
+                "Would {} {} (from {})",
+                action,
+                write.target.display(),
+                display_name(&write.source)
+            );
+        }
+        let overwrites = planned.iter().filter(|write| write.overwrites).count();
+        println!(
+            "\n{} files would be written, {} of them overwritten",
+            planned.len(),
+            overwrites
+        );
+        if overwrites > 0 {
+            bail!("{} existing files would be overwritten", overwrites);
+        }
+        return Ok(());
+    }
+    if args.report.is_some() {
+        logging::to_stderr(true);
+    }
+    let started = Timestamp::now().iso_datetime();
+    let report = convert::convert_folder(args.from, args.to, &input, &output, &options)?;
+    if let Some(ReportFormat::Json) = args.report {
+        let run = RunReport {
+            command: "convert",
+            from: args.from.extension(),
+            to: args.to.extension(),
+            input: &input,
+            output: &output,
+            started,
+            batch: &report,
+        };
+        println!("{}", serde_json::to_string_pretty(&run)?);
+    }
+    if report.failed > 0 && !args.watch {
+        bail!(
+            "{} of {} files failed",
+            report.failed,
+            report.converted + report.failed
+        );
+    }
+
+    if args.watch {
+        let (from, to) = (args.from, args.to);
+        watch::watch_folder(&input, from.extension(), |path| {
+            if !options
+                .files
+                .matches(path.strip_prefix(&input).unwrap_or(path))
+                || (from == Format::Md && !exports::is_exported(path, to)?)
+            {
+                return Ok(());
+            }
+            convert::record_history(from, to, path, &options)?;
+            convert::convert_one(from, to, path, &output, &options.fill)?;
+            println!(
+                "✓ {} -> {}",
+                display_name(path),
+                display_name(&output_path(&output, path, to.extension()))
+            );
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+fn run_sync(args: SyncArgs) -> Result<()> {
+    let markdown = args
+        .io
+        .input
+        .unwrap_or_else(|| PathBuf::from(Format::Md.default_dir()));
+    let docx = args
+        .io
+        .output
+        .unwrap_or_else(|| PathBuf::from(Format::Docx.default_dir()));
+    let options = BatchOptions {
+        cache_path: Some(PathBuf::from(CACHE_FILE)),
+        files: args.filter.into(),
+        history_dir: Some(PathBuf::from(HISTORY_DIR)),
+        ..BatchOptions::default()
+    };
+    let items = sync::plan(&markdown, &docx, &options)?;
+    if !args.dry_run {
+        sync::apply(&items, &options)?;
+    }
+
+    let (mut exported, mut imported, mut conflicts) = (0, 0, 0);
+    let done = |dry_run: &str, done: &str| if args.dry_run { dry_run } else { done }.to_string();
+    for item in &items {
+        let (markdown, docx) = (display_name(&item.markdown), display_name(&item.docx));
+        match item.action {
+            SyncAction::UpToDate => {}
+            SyncAction::Export => {
+                exported += 1;
+                println!(
+                    "{} {} -> {}",
+                    done("Would export", "✓ Exported"),
+                    markdown,
+                    docx
+                );
+            }
+            SyncAction::Import => {
+                imported += 1;
+                println!(
+                    "{} {} -> {}",
+                    done("Would import", "✓ Imported"),
+                    docx,
+                    markdown
+                );
+            }
+            SyncAction::Conflict(reason) => {
+                conflicts += 1;
+                println!("✗ Conflict: {} and {}: {}", markdown, docx, reason);
+            }
+        }
+    }
+    println!(
+        "\n{} exported, {} imported, {} up to date, {} conflicts",
+        exported,
+        imported,
+        items.len() - exported - imported - conflicts,
+        conflicts
+    );
+    if conflicts > 0 {
+        bail!(
+            "{} templates need their Markdown and DOCX merged by hand before the next sync",
+            conflicts
+        );
+    }
+    Ok(())
+}
+
+fn run_index(args: IoArgs) -> Result<()> {
+    let root = args.input.unwrap_or_else(|| PathBuf::from("."));
+    let output = args.output.unwrap_or_else(|| root.join(INDEX_FILE));
+    generate_index::write_index(&root, &output)
+}
+
+fn run_backup(args: BackupArgs) -> Result<()> {
+    let root = args.io.input.unwrap_or_else(|| PathBuf::from("."));
+    let backup_dir = args
+        .io
+        .output
+        .unwrap_or_else(|| root.join(&config::get().directories.backup));
+
+    if let Some(BackupAction::List) = args.action {
+        let snapshots = backup::list_snapshots(&backup_dir)?;
+        for snapshot in &snapshots {
+            let name = snapshot.name.as_deref().unwrap_or("(unversioned)");
+            println!("{}  {} files", name, snapshot.files);
+        }
+        println!(
+            "\n{} snapshots in {}",
+            snapshots.len(),
+            backup_dir.display()
+        );
+        return Ok(());
+    }
+
+    let index = backup::load_index(&root.join(INDEX_FILE))?;
+    let filter = args.filter.into();
+    if args.dry_run {
+        let files = backup::unindexed_files(&root, &index, &filter)?;
+        for path in &files {
+            println!("Would move {}", path.display());
+        }
+        println!(
+            "\n{} files would be moved to {}",
+            files.len(),
+            backup_dir.display()
+        );
+        if !files.is_empty() {
+            bail!("{} files would be moved", files.len());
+        }
+        return Ok(());
+    }
+    let (snapshot, moved) = backup::backup_snapshot(&root, &index, &backup_dir, &filter)?;
+    if moved > 0 {
+        println!("\nDone. Files moved to {}: {}", snapshot.display(), moved);
+    } else {
+        println!("\nDone. Files moved: 0");
+    }
+    Ok(())
+}
+
+fn run_restore(args: RestoreArgs) -> Result<()> {
+    let root = args.input.unwrap_or_else(|| PathBuf::from("."));
+    let backup_dir = args
+        .backup_dir
+        .unwrap_or_else(|| root.join(&config::get().directories.backup));
+    let mut backed_up = backup::list_backed_up(&backup_dir)?;
+    if let Some(name) = &args.snapshot {
+        backed_up.retain(|file| file.snapshot.as_ref() == Some(name));
+        if backed_up.is_empty() {
+            bail!("No snapshot {} in {}", name, backup_dir.display());
+        }
+    }
+
+    if !args.all && args.files.is_empty() {
+        for file in &backed_up {
+            println!("{}", file.listed_path());
+        }
+        println!(
+            "\n{} backed-up files in {}",
+            backed_up.len(),
+            backup_dir.display()
+        );
+        return Ok(());
+    }
+
+    let selected = if args.all {
+        backup::newest_versions(backed_up)
+    } else {
+        backup::select_backed_up(&backed_up, &args.files)?
+    };
+    let restored = backup::restore(&root, &backup_dir, &selected, args.force)?;
+    println!("\nDone. Files restored: {}", restored);
+    Ok(())
+}
+
+fn run_clean(args: CleanArgs) -> Result<()> {
+    let root = args.root.input.unwrap_or_else(|| PathBuf::from("."));
+    let orphans = clean::orphaned_outputs(&root, &args.filter.into())?;
+    if args.dry_run {
+        for path in &orphans {
+            println!("{}", path.display());
+        }
+        println!("\n{} orphaned outputs", orphans.len());
+        return Ok(());
+    }
+
+    let operation = journal::trash(&root, &orphans, "clean")?;
+    for entry in &operation.entries {
+        println!("Removed {}", entry.path.display());
+    }
+    if operation.entries.is_empty() {
+        println!("\nDone. Files removed: 0");
+    } else {
+        println!(
+            "\nDone. Files removed: {} (run `rtemplates undo` to restore them)",
+            operation.entries.len()
+        );
+    }
+    Ok(())
+}
+
+fn run_undo(args: RootArgs) -> Result<()> {
+    let root = args.input.unwrap_or_else(|| PathBuf::from("."));
+    match journal::undo(&root)? {
+        Some((operation, restored)) => println!(
+            "\nUndid `{}` from {}. Files restored: {}",
+            operation.command, operation.time, restored
+        ),
+        None => println!("Nothing to undo"),
+    }
+    Ok(())
+}
+
+fn run_query(args: QueryArgs) -> Result<()> {
+    let root = args.input.unwrap_or_else(|| PathBuf::from("."));
+    let index = generate_index::read_index(&root.join(INDEX_FILE))?;
+    let query = TemplateQuery {
+        modality: args.modality,
+        body_region: args.region,
+        keyword: args.keyword,
+        tags: args.tag,
+        files: args.filter.into(),
+    };
+    let mut found = query.run(&root, &index)?;
+    if args.group {
+        found.sort_by_cached_key(|(_, entry)| {
+            group_label(entry.modality.as_deref(), entry.body_region.as_deref())
+        });
+    }
+    let mut group = None;
+    for (path, entry) in &found {
+        if args.group {
+            let label = group_label(entry.modality.as_deref(), entry.body_region.as_deref());
+            print_group_heading(&mut group, label);
+        }
+        println!("{}  {}", path, entry.title.as_deref().unwrap_or(""));
+    }
+    println!("\n{} templates", found.len());
+    Ok(())
+}
+
+fn run_export_pack(args: ExportPackArgs) -> Result<()> {
+    let root = args.input.unwrap_or_else(|| PathBuf::from("."));
+    let query = TemplateQuery {
+        modality: args.modality,
+        body_region: args.region,
+        keyword: args.keyword,
+        tags: args.tag,
+        files: args.filter.into(),
+    };
+    let manifest = pack::write_pack(&root, &query, &args.output)?;
+    for template in &manifest.templates {
+        println!("{}", template);
+    }
+    println!(
+        "\n{} templates ({} files) packed into {}",
+        manifest.templates.len(),
+        manifest.files.len(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+fn run_import_pack(args: ImportPackArgs) -> Result<()> {
+    let root = args.input.unwrap_or_else(|| PathBuf::from("."));
+    let options = pack::ImportOptions {
+        namespace: args.namespace,
+        overwrite: args.overwrite,
+        dry_run: args.dry_run,
+    };
+    let report = pack::import_pack(&root, &args.pack, &options)?;
+    for path in &report.written {
+        println!("{}", path);
+    }
+    for collision in &report.collisions {
+        log::warn!(
+            "{} is also a template of {}: look it up by path, not by name",
+            collision.name,
+            collision.with
+        );
+    }
+    println!(
+        "\n{} files {} into {}/{}, {} unchanged",
+        report.written.len(),
+        if args.dry_run {
+            "to import"
+        } else {
+            "imported"
+        },
+        EXTERNAL_DIR,
+        report.namespace,
+        report.unchanged.len()
+    );
+    Ok(())
+}
+
+/// `CT · abdome e pelve`, with `-` for what is unknown.
+fn group_label(modality: Option<&str>, body_region: Option<&str>) -> String {
+    format!(
+        "{} · {}",
+        modality.unwrap_or("-"),
+        body_region.unwrap_or("-")
+    )
+}
+
+/// Prints `label` as a heading when it differs from the `current` one.
+fn print_group_heading(current: &mut Option<String>, label: String) {
+    if current.as_ref() != Some(&label) {
+        if current.is_some() {
+            println!();
+        }
+        println!("== {}", label);
+        *current = Some(label);
+    }
+}
+
+/// Prints `text`, or copies it to the clipboard when `to_clipboard` is set.
+fn deliver_text(text: &str, name: &str, to_clipboard: bool) -> Result<()> {
+    if to_clipboard {
+        clipboard::copy(text)?;
+        eprintln!("Copied {} to the clipboard", name);
+    } else {
+        print!("{}", text);
+    }
+    Ok(())
+}
+
+/// The configured Markdown template folder.
+fn markdown_dir() -> PathBuf {
+    PathBuf::from(&config::get().directories.markdown)
+}
+
+fn run_serve(args: ServeArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let server = serve::Server::bind((args.host.as_str(), args.port), &dir)?;
+    let addr = server.local_addr()?;
+    log::info!(
+        "Serving {} on http://{}/templates, changes on ws://{}/events (Ctrl+C to stop)",
+        dir.display(),
+        addr,
+        addr
+    );
+    server.run()
+}
+
+fn run_pick(args: PickArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let Some(path) = pick::pick(&dir)? else {
+        return Ok(());
+    };
+    let text = render_md_text(&path, &FillOptions::default())?;
+    deliver_text(&text, &display_name(&path), args.to_clipboard)
+}
+
+fn run_browse(args: BrowseArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let outputs = args
+        .to
+        .into_iter()
+        .map(|format| (format, PathBuf::from(format.default_dir())))
+        .collect();
+    browse::browse(&dir, &args.filter.into(), outputs)
+}
+
+/// A template file, or the Markdown template named `template`.
+fn template_path(template: &str) -> Result<PathBuf> {
+    let mut path = PathBuf::from(template);
+    if !path.is_file() {
+        path = markdown_dir().join(format!("{}.md", template));
+    }
+    if !path.is_file() {
+        bail!("Template {} not found", template);
+    }
+    Ok(path)
+}
+
+fn run_show(args: ShowArgs) -> Result<()> {
+    let mut path = template_path(&args.template)?;
+    // An earlier version is read from a copy with the same file name.
+    let scratch = tempfile::TempDir::new()?;
+    if let Some(version) = args.at {
+        let contents = history::contents_at(Path::new(HISTORY_DIR), &path, version)?;
+        let copy = scratch.path().join(path.file_name().unwrap_or_default());
+        fs::write(&copy, contents)?;
+        path = copy;
+    }
+    let mut fill = FillOptions {
+        signature: args.signer.as_deref().map(signature::profile).transpose()?,
+        ..FillOptions::default()
+    };
+    for assignment in &args.variables {
+        fill.set(assignment)?;
+    }
+
+    let text = match convert::format_of(&path) {
+        Some(Format::Txt) => fs::read_to_string(&path)?,
+        Some(format) => convert::render_text(format, Format::Txt, &path, &fill)?,
+        None => bail!("Unknown template format: {}", path.display()),
+    };
+    deliver_text(&text, &display_name(&path), args.to_clipboard)
+}
+
+fn run_search(args: SearchArgs) -> Result<()> {
+    let options = search::SearchOptions {
+        regex: args.regex,
+        case_sensitive: args.case_sensitive,
+        section: args.section,
+        files: args.filter.into(),
+        tags: args.tag,
+    };
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let mut results = search::search(&dir, &args.query, &options)?;
+    let color = !args.no_color && std::io::stdout().is_terminal();
+    let label = |template: &search::TemplateHits| {
+        let classification = &template.classification;
+        group_label(
+            classification.modality.as_deref(),
+            classification.body_region.as_deref(),
+        )
+    };
+    if args.group {
+        results.sort_by_cached_key(label);
+    }
+
+    let mut count = 0usize;
+    let mut group = None;
+    for template in &results {
+        if args.group {
+            print_group_heading(&mut group, label(template));
+        }
+        println!("{}", template.path.display());
+        for hit in &template.hits {
+            println!(
+                "  {:>4} [{}] {}",
+                hit.line,
+                hit.section.unwrap_or("-"),
+                search::excerpt(hit, color)
+            );
+        }
+        count += template.hits.len();
+    }
+    println!("\n{} matching lines in {} templates", count, results.len());
+    Ok(())
+}
+
+fn run_phrase(args: PhraseArgs) -> Result<()> {
+    let dir = args
+        .phrases
+        .unwrap_or_else(|| PathBuf::from(&config::get().directories.phrases));
+    let library = PhraseLibrary::load(&dir)?;
+    let query = args.query.join(" ");
+    if args.insert.is_none() && !args.to_clipboard {
+        let found = library.search(&query);
+        let width = found
+            .iter()
+            .map(|p| p.id.chars().count())
+            .max()
+            .unwrap_or(0);
+        for phrase in &found {
+            println!("{:<width$}  {}", phrase.id, phrase.text);
+        }
+        println!("\n{} phrases in {}", found.len(), dir.display());
+        return Ok(());
+    }
+
+    let Some(phrase) = library.get(query.trim()) else {
+        bail!(
+            "Phrase `{}` not found in {}; `rtemplates phrase WORDS` lists the matching ones",
+            query,
+            dir.display()
+        );
+    };
+    if let Some(template) = &args.insert {
+        let path = template_path(template)?;
+        let content = read_text(&path)?;
+        fs::write(&path, phrases::insert(&content, &phrase.id, args.section)?)?;
+        eprintln!(
+            "Added `{{{{phrase {}}}}}` to {} ({})",
+            phrase.id,
+            display_name(&path),
+            args.section.label()
+        );
+    }
+    if args.to_clipboard {
+        deliver_text(&phrase.text, &phrase.id, true)?;
+    }
+    Ok(())
+}
+
+fn run_hl7(args: Hl7Args) -> Result<()> {
+    let message = hl7::message_for_file(&args.template, &args.fields)?;
+    match args.output {
+        Some(path) => fs::write(path, message)?,
+        None => print!("{}", message),
+    }
+    Ok(())
+}
+
+fn run_verify(args: IoArgs) -> Result<()> {
+    let root = args.input.unwrap_or_else(|| PathBuf::from("."));
+    let report = verify::verify(&root, &root.join(INDEX_FILE))?;
+    let json = serde_json::to_string_pretty(&report)?;
+    match args.output {
+        Some(path) => fs::write(path, json + "\n")?,
+        None => println!("{}", json),
+    }
+    if !report.is_ok() {
+        bail!("{} mismatches found", report.mismatches.len());
+    }
+    Ok(())
+}
+
+fn run_diff(args: DiffArgs) -> Result<()> {
+    let diffs = diff::diff_files(&args.old, &args.new)?;
+    let color = !args.no_color && std::io::stdout().is_terminal();
+    print!("{}", diff::format_diff(&diffs, args.all, color));
+    let changed = diffs
+        .iter()
+        .filter(|d| d.status != diff::SectionStatus::Unchanged)
+        .count();
+    if changed == 0 {
+        println!("No differences");
+    } else {
+        println!("\n{} of {} sections differ", changed, diffs.len());
+    }
+    Ok(())
+}
+
+fn run_docx_diff(args: DocxDiffArgs) -> Result<()> {
+    let Some(new) = &args.new else {
+        for line in docx_diff::paragraphs(&args.old)? {
+            println!("{}", line);
+        }
+        return Ok(());
+    };
+    let lines = docx_diff::diff_docx(&args.old, new)?;
+    if !docx_diff::has_changes(&lines) {
+        println!("No differences");
+        return Ok(());
+    }
+    let color = !args.no_color && std::io::stdout().is_terminal();
+    print!("{}", docx_diff::format_hunks(&lines, args.context, color));
+    let count = |side: fn(&diff::Line) -> bool| lines.iter().filter(|line| side(line)).count();
+    println!(
+        "\n{} paragraphs removed, {} added",
+        count(|line| matches!(line, diff::Line::Removed(_))),
+        count(|line| matches!(line, diff::Line::Added(_)))
+    );
+    Ok(())
+}
+
+fn run_commit(args: CommitArgs) -> Result<()> {
+    let path = template_path(&args.template)?;
+    let dir = Path::new(HISTORY_DIR);
+    match history::record(dir, &path, args.message.as_deref())? {
+        Some(version) => println!("✓ {} version {}", display_name(&path), version.version),
+        None => {
+            let last = history::versions(dir, &path)?.len();
+            println!(
+                "{} is unchanged since version {}",
+                display_name(&path),
+                last
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_history(args: HistoryArgs) -> Result<()> {
+    let path = template_path(&args.template)?;
+    let versions = history::versions(Path::new(HISTORY_DIR), &path)?;
+    for version in &versions {
+        println!(
+            "{:>4}  {}  {}  {}",
+            version.version,
+            version.time,
+            &version.hash[..12],
+            version.message.as_deref().unwrap_or("")
+        );
+    }
+    println!("\n{} versions of {}", versions.len(), display_name(&path));
+    Ok(())
+}
+
+fn run_lint(args: LintArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let filter: FileFilter = args.filter.into();
+    if args.fix {
+        for (path, count) in lint::fix_folder(&dir, &filter)? {
+            println!("✓ {}: {} measurements fixed", display_name(&path), count);
+        }
+    }
+    let results = lint::lint_folder(&dir, &filter)?;
+    let (errors, warnings) = print_problems(&results);
+    println!(
+        "\n{} errors, {} warnings in {} templates",
+        errors,
+        warnings,
+        results.len()
+    );
+    if errors > 0 {
+        bail!("{} lint errors found", errors);
+    }
+    Ok(())
+}
+
+/// Prints each lint problem as `file:line: severity[rule]: message`, and
+/// returns the numbers of errors and warnings.
+fn print_problems(results: &[lint::FileProblems]) -> (usize, usize) {
+    let (mut errors, mut warnings) = (0usize, 0usize);
+    for file in results {
+        for problem in &file.problems {
+            println!(
+                "{}:{}: {}[{}]: {}",
+                file.path.display(),
+                problem.line,
+                problem.severity,
+                problem.rule.name(),
+                problem.message
+            );
+            match problem.severity {
+                lint::Severity::Error => errors += 1,
+                _ => warnings += 1,
+            }
+        }
+    }
+    (errors, warnings)
+}
+
+fn run_hook(args: HookArgs) -> Result<()> {
+    let root = args.root.input.unwrap_or_else(|| PathBuf::from("."));
+    if args.install {
+        let path = hook::install(&root)?;
+        println!(
+            "✓ {} runs `rtemplates hook` before each commit",
+            path.display()
+        );
+        return Ok(());
+    }
+    let run = hook::run(&root, &args.to)?;
+    let (errors, _) = print_problems(&run.problems);
+    if errors > 0 {
+        bail!(
+            "{} lint errors in the staged templates; fix them, or commit with --no-verify",
+            errors
+        );
+    }
+    for path in &run.staged {
+        println!("✓ Staged {}", path.display());
+    }
+    Ok(())
+}
+
+fn run_classify(args: ClassifyArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let results = classify::classify_folder(&dir, &args.filter.into(), args.write)?;
+    let mut written = 0usize;
+    for template in &results {
+        let classification = &template.classification;
+        println!(
+            "{:<4} {:<30} {}",
+            classification.modality.as_deref().unwrap_or("-"),
+            classification.body_region.as_deref().unwrap_or("-"),
+            display_name(&template.path)
+        );
+        if !template.written.is_empty() {
+            written += 1;
+        }
+    }
+    if args.write {
+        println!("\n✓ front matter updated in {} templates", written);
+    }
+    Ok(())
+}
+
+fn run_catalog(args: CatalogArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let count = catalog::write_catalog(&dir, &args.filter.into(), args.by, &args.output)?;
+    println!("✓ {} templates in {}", count, args.output.display());
+    Ok(())
+}
+
+fn run_site(args: SiteArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let site = site::build_site(&dir, &args.filter.into(), &args.output)?;
+    println!(
+        "✓ {} templates on {} pages in {}",
+        site.templates,
+        site.pages.len(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+fn run_epub(args: EpubArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let count = epub::write_epub(&dir, &args.filter.into(), &args.output)?;
+    println!("✓ {} templates in {}", count, args.output.display());
+    Ok(())
+}
+
+fn run_inventory(args: InventoryArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let count = inventory::write_inventory(&dir, &args.filter.into(), &args.output)?;
+    println!("✓ {} templates in {}", count, args.output.display());
+    Ok(())
+}
+
+fn run_stats(args: StatsArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let stats = stats::folder_stats(&dir, &args.filter.into())?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        print!("{}", stats::to_text(&stats));
+    }
+    Ok(())
+}
+
+fn run_new(args: NewArgs) -> Result<()> {
+    let (name, content) = scaffold::scaffold(&scaffold::NewTemplate {
+        modality: args.modality,
+        region: args.region,
+        contrast: args.contrast,
+        author: args.author,
+    })?;
+    let output = args
+        .output
+        .unwrap_or_else(|| markdown_dir().join(format!("{}.md", name)));
+    if output.exists() && !args.force {
+        bail!("{} exists; use --force to overwrite it", output.display());
+    }
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&output, content)?;
+    println!("✓ {}", output.display());
+    Ok(())
+}
+
+fn run_snippets(args: SnippetsArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let options = snippets::SnippetOptions {
+        prefix: args.prefix,
+        sections: args.section,
+        max_length: args.max_length,
+        files: args.filter.into(),
+    };
+    let count = snippets::write_snippets(&dir, &options, &args.output)?;
+    println!("✓ {} snippets in {}", count, args.output.display());
+    Ok(())
+}
+
+fn run_powerscribe(args: PowerscribeArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let count = powerscribe::write_autotexts(&dir, &args.filter.into(), &args.output)?;
+    println!("✓ {} AutoText entries in {}", count, args.output.display());
+    Ok(())
+}
+
+fn run_translate_skeleton(args: TranslateArgs) -> Result<()> {
+    let path = template_path(&args.template)?;
+    let skeleton = translate::skeleton(&fs::read_to_string(&path)?, args.to)?;
+    let output = args.output.unwrap_or_else(|| {
+        path.with_file_name(format!(
+            "{}.{}.md",
+            path.file_stem().unwrap_or_default().to_string_lossy(),
+            args.to.code()
+        ))
+    });
+    if output.exists() && !args.force {
+        bail!("{} exists; use --force to overwrite it", output.display());
+    }
+    fs::write(&output, skeleton)?;
+    println!("✓ {}", output.display());
+    Ok(())
+}
+
+fn run_anonymize(args: AnonymizeArgs) -> Result<()> {
+    let root = args.root.input.unwrap_or_else(|| PathBuf::from("."));
+    let results = anonymize::scan_folders(&root, &args.filter.into())?;
+    let mut count = 0usize;
+    for file in &results {
+        println!("{}", file.path.display());
+        for finding in &file.findings {
+            println!(
+                "  {:>4} [{}] {}",
+                finding.line,
+                finding.kind.label(),
+                finding.text
+            );
+        }
+        count += file.findings.len();
+    }
+    println!(
+        "\n{} possible identifiers in {} files",
+        count,
+        results.len()
+    );
+    if !args.redact {
+        return Ok(());
+    }
+
+    let mut redacted = 0usize;
+    for file in &results {
+        if anonymize::can_redact(&file.path) {
+            anonymize::redact_file(&file.path)?;
+            redacted += 1;
+        } else {
+            log::warn!(
+                "{} not redacted: convert it again from the redacted Markdown",
+                file.path.display()
+            );
+        }
+    }
+    println!("✓ {} files redacted", redacted);
+    Ok(())
+}
+
+fn run_dedupe(args: DedupeArgs) -> Result<()> {
+    let dir = args.input.unwrap_or_else(markdown_dir);
+    let clusters = dedupe::find_duplicates(&dir, args.threshold, &args.filter.into())?;
+    for (number, cluster) in clusters.iter().enumerate() {
+        let kind = if cluster.identical {
+            "identical text"
+        } else {
+            "similar text"
+        };
+        println!("Cluster {} ({})", number + 1, kind);
+        for (path, similarity) in &cluster.members {
+            println!("  {:>4.0}%  {}", similarity * 100.0, path.display());
+        }
+    }
+    let templates: usize = clusters.iter().map(|c| c.members.len()).sum();
+    println!(
+        "\n{} clusters, {} templates in {}",
+        clusters.len(),
+        templates,
+        dir.display()
+    );
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    logging::init(cli.log_level.unwrap_or_else(logging::level_from_env));
+    let mut settings = config::Config::load(&cli.config)?;
+    if let Some(site) = &cli.site {
+        settings.select_site(site)?;
+    }
+    if let Some(font) = cli.font {
+        settings.font = font;
+    }
+    if let Some(size) = cli.font_size {
+        settings.font_size_pt = size;
+    }
+    if let Some(language) = cli.language {
+        settings.language = language;
+    }
+    if let Some(reference) = cli.reference_docx {
+        settings.reference_docx = Some(reference);
+    }
+    if !cli.lock_sections.is_empty() {
+        settings.locked_sections = cli.lock_sections;
+    }
+    if let Some(changes) = cli.tracked_changes {
+        settings.tracked_changes = changes;
+    }
+    config::init(settings);
+
+    match cli.command {
+        Command::Convert(args) => run_convert(args),
+        Command::Sync(args) => run_sync(args),
+        Command::Index(args) => run_index(args),
+        Command::Backup(args) => run_backup(args),
+        Command::Restore(args) => run_restore(args),
+        Command::Clean(args) => run_clean(args),
+        Command::Undo(args) => run_undo(args),
+        Command::Query(args) => run_query(args),
+        Command::ExportPack(args) => run_export_pack(args),
+        Command::ImportPack(args) => run_import_pack(args),
+        Command::Pick(args) => run_pick(args),
+        Command::Serve(args) => run_serve(args),
+        Command::Browse(args) => run_browse(args),
+        Command::Show(args) => run_show(args),
+        Command::Search(args) => run_search(args),
+        Command::Phrase(args) => run_phrase(args),
+        Command::Hl7(args) => run_hl7(args),
+        Command::Verify(args) => run_verify(args),
+        Command::Diff(args) => run_diff(args),
+        Command::DocxDiff(args) => run_docx_diff(args),
+        Command::Commit(args) => run_commit(args),
+        Command::History(args) => run_history(args),
+        Command::Dedupe(args) => run_dedupe(args),
+        Command::Anonymize(args) => run_anonymize(args),
+        Command::Lint(args) => run_lint(args),
+        Command::Hook(args) => run_hook(args),
+        Command::TranslateSkeleton(args) => run_translate_skeleton(args),
+        Command::Classify(args) => run_classify(args),
+        Command::Catalog(args) => run_catalog(args),
+        Command::Site(args) => run_site(args),
+        Command::Epub(args) => run_epub(args),
+        Command::Inventory(args) => run_inventory(args),
+        Command::Stats(args) => run_stats(args),
+        Command::New(args) => run_new(args),
+        Command::Snippets(args) => run_snippets(args),
+        Command::Powerscribe(args) => run_powerscribe(args),
+    }
+}
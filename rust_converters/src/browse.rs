@@ -0,0 +1,525 @@
+//! Interactive browser of the Markdown templates (`rtemplates browse`), for
+//! maintainers who would rather not remember every command.
+//!
+//! The templates are listed on the left with their modality; the right pane
+//! shows the metadata of the selected one over its plain text, or its lint
+//! problems after `l`. `/` narrows the list by fuzzy-matching file names as
+//! `pick` does; `t`, `k`, `m` and `b` edit the tags, keywords, modality and
+//! body region in the front matter; `c` converts the template to the formats chosen
+//! with `--to`; PgUp and PgDn scroll the right pane, and `q` or Esc leaves.
+
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::convert::{convert_one, Format};
+use crate::convert_to_txt::render_md_text;
+use crate::encoding::read_text;
+use crate::exports;
+use crate::files::{list_files, FileFilter};
+use crate::fill::FillOptions;
+use crate::front_matter::{self, FrontMatter};
+use crate::lint::{lint_content, Problem, WordLists};
+use crate::pick::fuzzy_match;
+use crate::template::ReportTemplate;
+
+const HELP: &str =
+    "↑↓ move  / filter  t tags  k keywords  m modality  b region  c convert  l lint  PgUp/PgDn scroll  q quit";
+
+/// The front matter keys `browse` edits, with their key and label.
+const FIELDS: [(char, &str, &str); 4] = [
+    ('t', "tags", "Tags"),
+    ('k', "keywords", "Keywords"),
+    ('m', "modality", "Modality"),
+    ('b', "body_region", "Body region"),
+];
+
+/// A Markdown template as the browser shows it.
+#[derive(Clone, Debug)]
+pub struct Item {
+    pub path: PathBuf,
+    /// The file stem.
+    pub name: String,
+    pub content: String,
+    pub front: FrontMatter,
+}
+
+impl Item {
+    fn load(path: PathBuf) -> Result<Self> {
+        let content = read_text(&path)?;
+        let front = match front_matter::split(&content).0.map(front_matter::parse) {
+            Some(Ok(front)) => front,
+            Some(Err(err)) => {
+                log::warn!("{}: front matter ignored: {}", path.display(), err);
+                FrontMatter::default()
+            }
+            None => FrontMatter::default(),
+        };
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Item {
+            path,
+            name,
+            content,
+            front,
+        })
+    }
+}
+
+/// The Markdown templates in `dir` selected by `files`, sorted by file name.
+pub fn items(dir: &Path, files: &FileFilter) -> Result<Vec<Item>> {
+    list_files(dir, "md")?
+        .into_iter()
+        .filter(|path| files.matches(path.strip_prefix(dir).unwrap_or(path)))
+        .map(Item::load)
+        .collect()
+}
+
+enum Mode {
+    List,
+    Filter,
+    Edit {
+        key: &'static str,
+        label: &'static str,
+        value: String,
+    },
+}
+
+/// The browser's state: the templates, what is selected and shown, and the
+/// line being typed.
+pub struct Browser {
+    items: Vec<Item>,
+    /// The formats `c` writes, each into its folder.
+    outputs: Vec<(Format, PathBuf)>,
+    lists: WordLists,
+    query: String,
+    /// The items matching `query`, by index.
+    visible: Vec<usize>,
+    selected: usize,
+    scroll: usize,
+    /// The lint problems of the selected item, once `l` asked for them.
+    problems: Option<Vec<Problem>>,
+    mode: Mode,
+    status: String,
+}
+
+/// `text` cut or padded to `width` characters.
+fn fit(text: &str, width: usize) -> String {
+    format!("{:<width$}", text.chars().take(width).collect::<String>())
+}
+
+/// `text` broken at spaces into lines of at most `width` characters;
+/// longer words are split.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            let length = line.chars().count();
+            if length > 0 && length + 1 + word.len() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            while word.len() > width {
+                lines.push(word.drain(..width).collect());
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.extend(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+impl Browser {
+    pub fn new(items: Vec<Item>, outputs: Vec<(Format, PathBuf)>) -> Result<Self> {
+        let mut browser = Browser {
+            items,
+            outputs,
+            lists: WordLists::load(&config::get().lint)?,
+            query: String::new(),
+            visible: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            problems: None,
+            mode: Mode::List,
+            status: String::new(),
+        };
+        browser.refilter();
+        Ok(browser)
+    }
+
+    fn refilter(&mut self) {
+        let query = &self.query;
+        self.visible = (0..self.items.len())
+            .filter(|&i| fuzzy_match(query, &self.items[i].name).is_some())
+            .collect();
+        self.select(0);
+    }
+
+    fn select(&mut self, row: usize) {
+        self.selected = row.min(self.visible.len().saturating_sub(1));
+        self.scroll = 0;
+        self.problems = None;
+    }
+
+    fn move_by(&mut self, rows: isize) {
+        self.select(self.selected.saturating_add_signed(rows));
+    }
+
+    fn scroll_by(&mut self, lines: isize) {
+        self.scroll = self.scroll.saturating_add_signed(lines);
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.visible.get(self.selected).copied()
+    }
+
+    fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.refilter();
+    }
+
+    fn start_edit(&mut self, key: char) {
+        let Some(index) = self.current() else {
+            return;
+        };
+        let Some((_, key, label)) = FIELDS.into_iter().find(|(c, _, _)| *c == key) else {
+            return;
+        };
+        let front = &self.items[index].front;
+        let value = match key {
+            "tags" => Some(front.tags.join(", ")),
+            "keywords" => front.keywords.clone(),
+            "modality" => front.modality.clone(),
+            _ => front.body_region.clone(),
+        };
+        self.mode = Mode::Edit {
+            key,
+            label,
+            value: value.unwrap_or_default(),
+        };
+    }
+
+    /// Sets `key` to `value` in the front matter of the selected template;
+    /// tags are typed separated by commas.
+    fn save(&mut self, key: &str, value: &str) -> Result<()> {
+        let Some(index) = self.current() else {
+            return Ok(());
+        };
+        let value = match key {
+            "tags" => front_matter::list_value(&front_matter::parse_list(value)),
+            _ => value.to_string(),
+        };
+        let path = self.items[index].path.clone();
+        std::fs::write(
+            &path,
+            front_matter::with_key(&self.items[index].content, key, &value),
+        )?;
+        self.items[index] = Item::load(path)?;
+        self.problems = None;
+        self.status = format!("✓ {} saved", key);
+        Ok(())
+    }
+
+    /// Converts the selected template to every output format.
+    fn convert(&mut self) {
+        let Some(index) = self.current() else {
+            return;
+        };
+        let path = &self.items[index].path;
+        let mut written = Vec::new();
+        for (format, dir) in &self.outputs {
+            match exports::is_exported(path, *format) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    self.status = format!("✗ {:#}", err);
+                    return;
+                }
+            }
+            if let Err(err) = convert_one(Format::Md, *format, path, dir, &FillOptions::default()) {
+                self.status = format!("✗ {}: {:#}", format.label(), err);
+                return;
+            }
+            written.push(format.label());
+        }
+        self.status = format!("✓ {} written", written.join(", "));
+    }
+
+    fn lint(&mut self) {
+        if let Some(index) = self.current() {
+            let problems =
+                lint_content(&self.items[index].content, &config::get().lint, &self.lists);
+            self.status = format!("{} lint problems", problems.len());
+            self.problems = Some(problems);
+            self.scroll = 0;
+        }
+    }
+
+    /// The right pane of the selected template, `width` characters wide.
+    fn details(&self, width: usize) -> Vec<String> {
+        let Some(index) = self.current() else {
+            return vec!["No templates match".to_string()];
+        };
+        let item = &self.items[index];
+        let front = &item.front;
+        let title = ReportTemplate::from_markdown(&item.content)
+            .title
+            .map(|title| title.plain());
+        let contrast = front
+            .contrast
+            .map(|contrast| if contrast { "yes" } else { "no" }.to_string());
+        let mut lines = Vec::new();
+        for (label, value, editable) in [
+            ("File", Some(format!("{}.md", item.name)), true),
+            ("Title", title, false),
+            ("Modality", front.modality.clone(), true),
+            ("Body region", front.body_region.clone(), true),
+            ("Contrast", contrast, false),
+            (
+                "Tags",
+                Some(front.tags.join(", ")).filter(|tags| !tags.is_empty()),
+                true,
+            ),
+            ("Keywords", front.keywords.clone(), true),
+            ("Author", front.author.clone(), false),
+            ("Version", front.version.clone(), false),
+            ("Created", front.created.clone(), false),
+        ] {
+            if value.is_some() || editable {
+                let value = value.unwrap_or_else(|| "-".to_string());
+                for (i, line) in wrap(&value, width.saturating_sub(12).max(1))
+                    .into_iter()
+                    .enumerate()
+                {
+                    lines.push(format!("{:<12}{}", if i == 0 { label } else { "" }, line));
+                }
+            }
+        }
+        lines.push(String::new());
+        match &self.problems {
+            Some(problems) => {
+                lines.push(format!("── Lint: {} problems ──", problems.len()));
+                for problem in problems {
+                    lines.extend(wrap(
+                        &format!(
+                            "line {}: {}[{}]: {}",
+                            problem.line,
+                            problem.severity,
+                            problem.rule.name(),
+                            problem.message
+                        ),
+                        width,
+                    ));
+                }
+            }
+            None => {
+                lines.push("── Preview ──".to_string());
+                match render_md_text(&item.path, &FillOptions::default()) {
+                    Ok(text) => lines.extend(wrap(&text, width)),
+                    Err(err) => lines.push(format!("{:#}", err)),
+                }
+            }
+        }
+        lines
+    }
+
+    /// The screen, `rows` lines of `columns` characters: the list and the
+    /// right pane, then the status, help or typed line.
+    fn screen(&self, rows: usize, columns: usize) -> Vec<String> {
+        let list_width = (columns * 2 / 5).clamp(16, 48);
+        let pane_width = columns.saturating_sub(list_width + 3).max(1);
+        let body = rows.saturating_sub(1).max(1);
+        let first = self.selected.saturating_sub(body - 1);
+        let details = self.details(pane_width);
+        let mut lines = Vec::new();
+        for row in 0..body {
+            let entry = self.visible.get(first + row).map(|&index| {
+                let item = &self.items[index];
+                let modality = item.front.modality.as_deref().unwrap_or("");
+                fit(&format!("{:<3} {}", modality, item.name), list_width)
+            });
+            let entry = match entry {
+                Some(entry) if first + row == self.selected => {
+                    format!("\x1b[7m{}\x1b[0m", entry)
+                }
+                Some(entry) => entry,
+                None => fit("", list_width),
+            };
+            let pane = details.get(self.scroll + row).map_or("", String::as_str);
+            lines.push(format!("{} │ {}", entry, pane));
+        }
+        let bottom = match &self.mode {
+            Mode::List if self.status.is_empty() => HELP.to_string(),
+            Mode::List => self.status.clone(),
+            Mode::Filter => format!("/{}", self.query),
+            Mode::Edit { label, value, .. } => format!("{}: {}", label, value),
+        };
+        lines.push(
+            bottom
+                .chars()
+                .take(columns.saturating_sub(1))
+                .collect::<String>(),
+        );
+        lines
+    }
+}
+
+/// Browses the Markdown templates in `dir` selected by `files`; `c` writes
+/// each of `outputs`, a format and its folder.
+pub fn browse(dir: &Path, files: &FileFilter, outputs: Vec<(Format, PathBuf)>) -> Result<()> {
+    let items = items(dir, files)?;
+    if items.is_empty() {
+        bail!("No .md files found in {}", dir.display());
+    }
+    terminal::run(Browser::new(items, outputs)?)
+}
+
+#[cfg(not(unix))]
+mod terminal {
+    use super::Browser;
+    use anyhow::{bail, Result};
+
+    pub fn run(_browser: Browser) -> Result<()> {
+        bail!(
+            "The template browser needs a Unix terminal (Linux or macOS); \
+             use `rtemplates search`, `query` and `show` instead"
+        )
+    }
+}
+
+#[cfg(unix)]
+mod terminal {
+    use super::{Browser, Mode};
+    use crate::terminal::{Key, RawTerminal};
+    use anyhow::Result;
+
+    /// Runs the browser until it is left with `q` or Esc.
+    pub fn run(mut browser: Browser) -> Result<()> {
+        let mut terminal = RawTerminal::open()?;
+        loop {
+            let rows = terminal.rows();
+            let page = rows.saturating_sub(2).max(1) as isize;
+            let screen = browser.screen(rows, terminal.columns());
+            terminal.show(&format!("\x1b[H\x1b[J{}", screen.join("\n")))?;
+            let key = terminal.key()?;
+            match std::mem::replace(&mut browser.mode, Mode::List) {
+                Mode::List => {
+                    browser.status.clear();
+                    match key {
+                        Key::Char('q') | Key::Cancel => return Ok(()),
+                        Key::Char('/') => browser.mode = Mode::Filter,
+                        Key::Char('c') => browser.convert(),
+                        Key::Char('l') => browser.lint(),
+                        Key::Char(c) => browser.start_edit(c),
+                        Key::Up => browser.move_by(-1),
+                        Key::Down => browser.move_by(1),
+                        Key::PageUp => browser.scroll_by(-page),
+                        Key::PageDown => browser.scroll_by(page),
+                        _ => {}
+                    }
+                }
+                Mode::Filter => {
+                    let mut query = browser.query.clone();
+                    match key {
+                        Key::Char(c) => query.push(c),
+                        Key::Backspace => {
+                            query.pop();
+                        }
+                        Key::ClearLine => query.clear(),
+                        Key::Up => browser.move_by(-1),
+                        Key::Down => browser.move_by(1),
+                        Key::Enter => continue,
+                        Key::Cancel => query.clear(),
+                        _ => {}
+                    }
+                    if query != browser.query {
+                        browser.set_query(query);
+                    }
+                    if !matches!(key, Key::Cancel) {
+                        browser.mode = Mode::Filter;
+                    }
+                }
+                Mode::Edit {
+                    key: name,
+                    label,
+                    mut value,
+                } => {
+                    match key {
+                        Key::Char(c) => value.push(c),
+                        Key::Backspace => {
+                            value.pop();
+                        }
+                        Key::ClearLine => value.clear(),
+                        Key::Enter => {
+                            if let Err(err) = browser.save(name, &value) {
+                                browser.status = format!("✗ {:#}", err);
+                            }
+                            continue;
+                        }
+                        Key::Cancel => continue,
+                        _ => {}
+                    }
+                    browser.mode = Mode::Edit {
+                        key: name,
+                        label,
+                        value,
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn templates_are_filtered_tagged_linted_and_converted() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, content: &str| fs::write(dir.path().join(name), content).unwrap();
+        write(
+            "TC Tórax.md",
+            "---\nmodality: CT\n---\nTOMOGRAFIA DO TÓRAX\n\n**Impressão:** Normal.\n",
+        );
+        write(
+            "US Abdome.md",
+            "---\nmodality: US\nkeywords: fígado\n---\n\
+             ULTRASSONOGRAFIA DO ABDOME\n\n**Impressão:** Normal.\n",
+        );
+        let out = dir.path().join("txt");
+        let mut browser = Browser::new(
+            items(dir.path(), &FileFilter::default()).unwrap(),
+            vec![(Format::Txt, out.clone())],
+        )
+        .unwrap();
+        assert_eq!(browser.visible, [0, 1]);
+        browser.set_query("usabd".to_string());
+        assert_eq!(browser.visible, [1]);
+
+        browser.save("keywords", "fígado, vesícula").unwrap();
+        browser.save("tags", "abdome agudo, , urgência").unwrap();
+        let content = fs::read_to_string(dir.path().join("US Abdome.md")).unwrap();
+        assert!(content.starts_with(
+            "---\nmodality: US\nkeywords: fígado, vesícula\ntags: [abdome agudo, urgência]\n---\n"
+        ));
+        let screen = browser.screen(20, 100).join("\n");
+        assert!(screen.contains("\x1b[7mUS  US Abdome"));
+        assert!(screen.contains("│ Tags        abdome agudo, urgência\n"));
+        assert!(screen.contains("│ Keywords    fígado, vesícula\n"));
+        assert!(screen.contains("│ ULTRASSONOGRAFIA DO ABDOME\n"));
+
+        browser.lint();
+        assert!(browser.screen(20, 100).join("\n").contains("│ ── Lint: "));
+        browser.convert();
+        assert_eq!(browser.status, "✓ TXT written");
+        assert!(out.join("US Abdome.txt").is_file());
+    }
+}
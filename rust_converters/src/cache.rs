@@ -0,0 +1,160 @@
+//! Content-hash cache used to skip conversions whose inputs did not change.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::generate_index::write_json_pretty;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// SHA-256 of the source file (and conversion context, if any) when the
+    /// target was last written.
+    pub source_hash: String,
+    /// Modification time of the target right after it was written, in
+    /// nanoseconds since the Unix epoch.
+    pub output_mtime: u128,
+}
+
+/// What was edited since a target was generated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Changes {
+    /// The source contents (or conversion context) differ.
+    pub source: bool,
+    /// The target was written to since.
+    pub target: bool,
+}
+
+/// Maps each generated target path to the state it was generated from.
+#[derive(Debug, Default)]
+pub struct Cache {
+    path: PathBuf,
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)?;
+    Ok(format!("{:x}", Sha256::digest(&bytes)))
+}
+
+/// Hash of `source` plus `context`, the other inputs of the conversion
+/// (such as fill variables); an empty context hashes the file alone.
+fn source_hash(source: &Path, context: &str) -> Result<String> {
+    if context.is_empty() {
+        return sha256_file(source);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(fs::read(source)?);
+    hasher.update([0]);
+    hasher.update(context.as_bytes());
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn mtime_nanos(path: &Path) -> Option<u128> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+fn key(target: &Path) -> String {
+    target.to_string_lossy().to_string()
+}
+
+impl Cache {
+    /// Loads the cache at `path`; a missing or unreadable file yields an empty cache.
+    pub fn load(path: &Path) -> Self {
+        let entries = fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Cache {
+            path: path.to_path_buf(),
+            entries,
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write_json_pretty(&self.entries, &self.path)
+    }
+
+    /// True when `target` was generated from the current contents of
+    /// `source` in the same `context` and has not been touched since.
+    pub fn is_fresh(&self, source: &Path, target: &Path, context: &str) -> Result<bool> {
+        let Some(entry) = self.entries.get(&key(target)) else {
+            return Ok(false);
+        };
+        if mtime_nanos(target) != Some(entry.output_mtime) {
+            return Ok(false);
+        }
+        Ok(source_hash(source, context)? == entry.source_hash)
+    }
+
+    /// What changed since `target` was last generated from `source` in
+    /// `context`; `None` when it never was, or is gone.
+    pub fn changes(&self, source: &Path, target: &Path, context: &str) -> Result<Option<Changes>> {
+        let (Some(entry), Some(mtime)) = (self.entries.get(&key(target)), mtime_nanos(target))
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Changes {
+            source: source_hash(source, context)? != entry.source_hash,
+            target: mtime != entry.output_mtime,
+        }))
+    }
+
+    /// Records that `target` was just generated from `source` in `context`.
+    pub fn record(&mut self, source: &Path, target: &Path, context: &str) -> Result<()> {
+        if let Some(output_mtime) = mtime_nanos(target) {
+            let entry = CacheEntry {
+                source_hash: source_hash(source, context)?,
+                output_mtime,
+            };
+            self.entries.insert(key(target), entry);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn targets_stay_fresh_until_the_source_context_or_target_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = (dir.path().join("TC.md"), dir.path().join("TC.docx"));
+        let cache_file = dir.path().join(".cache.json");
+        fs::write(&source, "# TC DE CRÂNIO").unwrap();
+        fs::write(&target, "docx").unwrap();
+
+        let mut cache = Cache::load(&cache_file);
+        assert!(!cache.is_fresh(&source, &target, "").unwrap());
+        assert_eq!(cache.changes(&source, &target, "").unwrap(), None);
+        cache.record(&source, &target, "").unwrap();
+        cache.save().unwrap();
+
+        let cache = Cache::load(&cache_file);
+        assert!(cache.is_fresh(&source, &target, "").unwrap());
+        assert!(!cache.is_fresh(&source, &target, "lado=direito").unwrap());
+
+        fs::write(&source, "# TC DE TÓRAX").unwrap();
+        assert!(!cache.is_fresh(&source, &target, "").unwrap());
+        fs::File::options()
+            .write(true)
+            .open(&target)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(
+            cache.changes(&source, &target, "").unwrap(),
+            Some(Changes {
+                source: true,
+                target: true,
+            })
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Computed placeholders of the fill engine: `{{volume(comprimento,
+//! largura, altura)}}` is replaced by the value the formula gives for the
+//! variables set with `--set`, so a filled report carries the derived values
+//! its measurements imply.
+//!
+//! Each argument is a variable or a number written with a `.` (commas
+//! separate the arguments); variable values may use either separator. While
+//! a variable is unset the placeholder reads `XXX`, as any value still to be
+//! filled in does. Results are written with the decimal separator of
+//! `[lint] decimal_separator`.
+
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use crate::config;
+use crate::placeholders::BLANK;
+
+struct Calculation {
+    name: &'static str,
+    parameters: &'static [&'static str],
+    decimals: usize,
+    /// `None` when the values leave the formula undefined (a zero divisor).
+    formula: fn(&[f64]) -> Option<f64>,
+}
+
+fn ratio(numerator: f64, denominator: f64) -> Option<f64> {
+    (denominator != 0.0).then(|| numerator / denominator)
+}
+
+const CALCULATIONS: &[Calculation] = &[
+    // Ellipsoid volume from three perpendicular diameters, in their unit
+    // cubed (cm gives cm³ = mL).
+    Calculation {
+        name: "volume",
+        parameters: &["a", "b", "c"],
+        decimals: 1,
+        formula: |v| Some(v[0] * v[1] * v[2] * PI / 6.0),
+    },
+    // PSA (ng/mL) per cm³ of prostate.
+    Calculation {
+        name: "psa_density",
+        parameters: &["psa", "volume"],
+        decimals: 2,
+        formula: |v| ratio(v[0], v[1]),
+    },
+    // Absolute washout, in %, from the unenhanced, portal venous and
+    // 15-minute delayed attenuations (HU).
+    Calculation {
+        name: "adrenal_washout",
+        parameters: &["pre", "post", "delayed"],
+        decimals: 0,
+        formula: |v| ratio(100.0 * (v[1] - v[2]), v[1] - v[0]),
+    },
+    // Relative washout, in %, when there is no unenhanced phase.
+    Calculation {
+        name: "adrenal_relative_washout",
+        parameters: &["post", "delayed"],
+        decimals: 0,
+        formula: |v| ratio(100.0 * (v[0] - v[1]), v[0]),
+    },
+];
+
+/// The names of the available calculations.
+pub fn names() -> Vec<&'static str> {
+    CALCULATIONS.iter().map(|c| c.name).collect()
+}
+
+fn number(text: &str) -> Option<f64> {
+    text.trim().replace(',', ".").parse().ok()
+}
+
+/// `value` rounded to `decimals` places, with the configured separator.
+fn format(value: f64, decimals: usize) -> String {
+    let text = format!("{:.*}", decimals, value);
+    // `-0` after rounding a small negative value.
+    let text = if text.trim_start_matches(['-', '0', '.']).is_empty() {
+        text.trim_start_matches('-').to_string()
+    } else {
+        text
+    };
+    text.replace('.', &config::get().lint.decimal_separator.to_string())
+}
+
+/// The text of `{{name(arguments)}}`, the arguments' variables read from
+/// `variables`; `XXX` while one of them is unset.
+pub fn expand(name: &str, arguments: &str, variables: &BTreeMap<String, String>) -> Result<String> {
+    let Some(calculation) = CALCULATIONS.iter().find(|c| c.name == name) else {
+        bail!(
+            "Unknown calculation `{}`; expected one of {}",
+            name,
+            names().join(", ")
+        );
+    };
+    let arguments: Vec<&str> = arguments.split(',').map(str::trim).collect();
+    if arguments.len() != calculation.parameters.len() {
+        bail!(
+            "`{}` takes {} values ({}), not {}",
+            name,
+            calculation.parameters.len(),
+            calculation.parameters.join(", "),
+            arguments.len()
+        );
+    }
+
+    let mut values = Vec::new();
+    for argument in arguments {
+        if let Some(value) = number(argument) {
+            values.push(value);
+            continue;
+        }
+        let Some(text) = variables.get(argument) else {
+            return Ok(BLANK.to_string());
+        };
+        let Some(value) = number(text) else {
+            bail!("`{}` in `{}` is not a number ({:?})", argument, name, text);
+        };
+        values.push(value);
+    }
+    match (calculation.formula)(&values).filter(|value| value.is_finite()) {
+        Some(value) => Ok(format(value, calculation.decimals)),
+        None => bail!("`{}` is undefined for {:?}", name, values),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fill::{fill, FillOptions};
+
+    #[test]
+    fn computed_placeholders_are_filled_from_the_variables() {
+        let mut options = FillOptions::default();
+        let source = "Próstata medindo {{volume(4.5, largura, altura)}} cm³, \
+                      densidade do PSA de {{psa_density(psa, 33.9)}} ng/mL/cm³.\n\
+                      Washout absoluto de {{ adrenal_washout(pre, 80, 40) }}%.\n";
+        assert_eq!(
+            fill(source, &options).unwrap(),
+            "Próstata medindo XXX cm³, densidade do PSA de XXX ng/mL/cm³.\n\
+             Washout absoluto de XXX%.\n"
+        );
+
+        for assignment in ["largura=4", "altura=3,6", "psa=6.8", "pre=10"] {
+            options.set(assignment).unwrap();
+        }
+        assert_eq!(
+            fill(source, &options).unwrap(),
+            "Próstata medindo 33,9 cm³, densidade do PSA de 0,20 ng/mL/cm³.\n\
+             Washout absoluto de 57%.\n"
+        );
+
+        assert!(fill("{{adrenal_washout(80, 80, 40)}}", &options).is_err());
+        assert!(fill("{{volume(4, 3)}}", &options).is_err());
+        assert!(fill("{{area(4, 3)}}", &options).is_err());
+        options.set("largura=quatro").unwrap();
+        assert!(fill("{{volume(4, largura, 3)}}", &options).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! `convert --canonical md`: the Markdown is the only source of truth, and
+//! an output edited out of band (a DOCX touched up in Word) is not silently
+//! overwritten.
+//!
+//! Before writing a target, or one of its alias copies, that the index
+//! lists, the conversion checks that it still has the checksum the index
+//! recorded; a target edited since fails to convert, and is left alone,
+//! until the edit is carried over to the Markdown or the file is indexed
+//! again. Each target written updates its index entry, so the next run
+//! compares against what it generated. Targets the index does not list are
+//! written as usual.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::aliases;
+use crate::cache::sha256_file;
+use crate::files::slash_path;
+use crate::generate_index::{file_entry, read_index, write_json_pretty, Index};
+
+pub struct Canonical {
+    index_path: PathBuf,
+    /// The folder index paths are relative to.
+    root: PathBuf,
+    index: Index,
+    changed: bool,
+}
+
+impl Canonical {
+    pub fn load(index_path: &Path) -> Result<Self> {
+        if !index_path.exists() {
+            bail!(
+                "--canonical compares outputs with the index, but {} is missing; \
+                 run `rtemplates index` first",
+                index_path.display()
+            );
+        }
+        Ok(Canonical {
+            index_path: index_path.to_path_buf(),
+            root: index_path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            index: read_index(index_path)?,
+            changed: false,
+        })
+    }
+
+    fn key(&self, target: &Path) -> String {
+        slash_path(target.strip_prefix(&self.root).unwrap_or(target))
+    }
+
+    /// Fails when `target`, or an alias copy of it, exists with contents
+    /// other than those the index recorded for it.
+    pub fn check(&self, source: &Path, target: &Path) -> Result<()> {
+        let mut targets = vec![target.to_path_buf()];
+        if source.extension().is_some_and(|ext| ext == "md") {
+            targets.extend(aliases::targets(source, target)?);
+        }
+        for target in targets {
+            let Some(entry) = self.index.files.get(&self.key(&target)) else {
+                continue;
+            };
+            if target.exists() && sha256_file(&target)? != entry.sha256 {
+                bail!(
+                    "{} was edited since it was last generated (indexed {}); carry the edit over \
+                     to the source or run `rtemplates index` before converting again",
+                    target.display(),
+                    entry.modified
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates the index entries of `target` and its alias copies, just
+    /// written from `source`.
+    pub fn record(&mut self, source: &Path, target: &Path) -> Result<()> {
+        let mut targets = vec![target.to_path_buf()];
+        if source.extension().is_some_and(|ext| ext == "md") {
+            targets.extend(aliases::targets(source, target)?);
+        }
+        for target in targets {
+            let key = self.key(&target);
+            if self.index.files.contains_key(&key) && target.exists() {
+                let entry = file_entry(&target)
+                    .with_context(|| format!("Cannot index {}", target.display()))?;
+                self.index.files.insert(key, entry);
+                self.changed = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the index back when an entry changed.
+    pub fn save(&self) -> Result<()> {
+        if self.changed {
+            write_json_pretty(&self.index, &self.index_path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::{convert_folder, BatchOptions, Format};
+    use crate::generate_index::write_index;
+    use std::fs;
+
+    #[test]
+    fn edited_outputs_are_not_overwritten() {
+        let root = tempfile::tempdir().unwrap();
+        let markdown = root.path().join("Templates_markdown");
+        let txt = root.path().join("Templates_txt");
+        fs::create_dir_all(&markdown).unwrap();
+        let source = markdown.join("US ABDOME.md");
+        fs::write(&source, "**US ABDOME**\n\nFígado normal.\n").unwrap();
+        let index_path = root.path().join("reports_index.json");
+        let options = BatchOptions {
+            canonical_index: Some(index_path.clone()),
+            ..BatchOptions::default()
+        };
+        assert!(convert_folder(Format::Md, Format::Txt, &markdown, &txt, &options).is_err());
+
+        let options_without = BatchOptions::default();
+        convert_folder(Format::Md, Format::Txt, &markdown, &txt, &options_without).unwrap();
+        write_index(root.path(), &index_path).unwrap();
+
+        // Regenerating an untouched output updates its index entry.
+        for finding in ["Fígado esteatótico.", "Fígado com cisto simples."] {
+            fs::write(&source, format!("**US ABDOME**\n\n{}\n", finding)).unwrap();
+            let report =
+                convert_folder(Format::Md, Format::Txt, &markdown, &txt, &options).unwrap();
+            assert_eq!(report.failed, 0);
+        }
+
+        let output = txt.join("US ABDOME.txt");
+        fs::write(&output, "Editado à mão.\n").unwrap();
+        fs::write(&source, "**US ABDOME**\n\nFígado normal.\n").unwrap();
+        let report = convert_folder(Format::Md, Format::Txt, &markdown, &txt, &options).unwrap();
+        assert_eq!(report.failed, 1);
+        assert_eq!(fs::read_to_string(&output).unwrap(), "Editado à mão.\n");
+    }
+}
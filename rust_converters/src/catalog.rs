@@ -0,0 +1,555 @@
+//! The template handbook (`rtemplates catalog`): every Markdown template in
+//! one document, grouped by modality and then body region (`classify`),
+//! or with `--by tag` by front matter tag and then modality, after a table
+//! of contents. A template with aliases is listed again under each alias,
+//! classified by that name.
+//!
+//! The Markdown and HTML catalogs link the contents to anchors on each
+//! group and template (`#ct-abdome`, `#tc-abdome-superior-com`); the HTML
+//! catalog starts each template on a new printed page. The PDF catalog
+//! starts each template on a new page and lists its page number in the
+//! contents.
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::classify::{classify, classify_template, Classification};
+use crate::convert_to_html::{body_html, escape_html, inline_html, page_html};
+use crate::convert_to_pdf::render_parts;
+use crate::files::{list_files, FileFilter};
+use crate::fill::FillOptions;
+use crate::front_matter;
+use crate::markdown::{heading_font_size_pt, Alignment, Block, LineStyle};
+use crate::pick::fold;
+use crate::template::ReportTemplate;
+
+pub(crate) const TITLE: &str = "Catálogo de modelos de laudo";
+pub(crate) const CONTENTS: &str = "Sumário";
+
+/// Modalities in the order the catalog lists them, with their names.
+const MODALITY_NAMES: &[(&str, &str)] = &[
+    ("CT", "Tomografia computadorizada"),
+    ("MR", "Ressonância magnética"),
+    ("US", "Ultrassonografia"),
+    ("MG", "Mamografia"),
+    ("DX", "Radiografia"),
+];
+
+const NO_MODALITY: &str = "Outros modelos";
+const NO_REGION: &str = "Região não identificada";
+const NO_TAG: &str = "Modelos sem tag";
+
+/// Printed HTML catalogs start each template on a new page.
+const PRINT_CSS: &str = "\
+nav ul { list-style: none; padding-left: 1.5em; }
+nav > ul { padding-left: 0; }
+.template { break-before: page; }
+@media screen { .template { border-top: 1px solid #999; margin-top: 2em; padding-top: 1em; } }
+";
+
+/// One template of the catalog.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// The file stem, which names the template in the contents.
+    pub name: String,
+    pub anchor: String,
+    pub template: ReportTemplate,
+    /// The Markdown template, with its partials filled in.
+    pub content: String,
+}
+
+/// The templates of one modality and body region.
+#[derive(Clone, Debug)]
+pub struct Group {
+    /// The top heading: the modality, or the tag when grouping by tag.
+    pub modality: String,
+    pub modality_anchor: String,
+    /// The heading under it: the body region, or the modality by tag.
+    pub region: String,
+    pub anchor: String,
+    pub entries: Vec<Entry>,
+}
+
+/// `text` as an anchor: lowercase ASCII words joined by `-`, with `-2`,
+/// `-3`, ... when `used` already has it.
+pub(crate) fn anchor(text: &str, used: &mut HashSet<String>) -> String {
+    let folded: String = text
+        .chars()
+        .map(fold)
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { ' ' })
+        .collect();
+    let base = folded.split_whitespace().collect::<Vec<_>>().join("-");
+    let mut candidate = base.clone();
+    let mut n = 2;
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}-{}", base, n);
+        n += 1;
+    }
+    candidate
+}
+
+fn modality_rank(modality: Option<&str>) -> (usize, String) {
+    match modality {
+        Some(code) => (
+            MODALITY_NAMES
+                .iter()
+                .position(|(known, _)| *known == code)
+                .unwrap_or(MODALITY_NAMES.len()),
+            code.to_string(),
+        ),
+        None => (MODALITY_NAMES.len() + 1, String::new()),
+    }
+}
+
+fn modality_name(modality: Option<&str>) -> String {
+    match modality {
+        Some(code) => MODALITY_NAMES
+            .iter()
+            .find(|(known, _)| *known == code)
+            .map_or(code.to_string(), |(_, name)| name.to_string()),
+        None => NO_MODALITY.to_string(),
+    }
+}
+
+/// Capitalizes the first letter of a body region.
+fn region_name(region: Option<&str>) -> String {
+    let Some(region) = region else {
+        return NO_REGION.to_string();
+    };
+    let mut chars = region.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// What the catalog headings group the templates by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Grouping {
+    /// Modality, then body region
+    #[default]
+    Modality,
+    /// Front matter tag, then modality; templates are listed under each of their tags
+    Tag,
+}
+
+/// Reads and groups the Markdown templates in `dir` selected by `files`:
+/// groups by modality (CT first), then body region, with templates without
+/// either last; templates by file name within a group.
+pub fn build(dir: &Path, files: &FileFilter) -> Result<Vec<Group>> {
+    build_grouped(dir, files, Grouping::Modality)
+}
+
+/// `build`, grouping by `grouping`. By tag, the tags come in alphabetical
+/// order, spelled as first met, and templates without one come last.
+pub fn build_grouped(dir: &Path, files: &FileFilter, grouping: Grouping) -> Result<Vec<Group>> {
+    type Key = ((usize, String), (usize, String));
+    let mut templates: Vec<(Key, String, String, Entry)> = Vec::new();
+    let mut spellings: HashMap<String, String> = HashMap::new();
+    let mut used = HashSet::new();
+    let fill = FillOptions::default();
+    for path in list_files(dir, "md")? {
+        if !files.matches(path.strip_prefix(dir).unwrap_or(&path)) {
+            continue;
+        }
+        let content = fill.read(&path)?;
+        let name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let template = ReportTemplate::from_markdown(&content);
+        let own = classify_template(&path, &content);
+        // An alias is listed as a template of its own, classified by its name.
+        let mut names = vec![(name, own.clone())];
+        for alias in aliases::of(&path, &content) {
+            let inferred = classify(&alias, None);
+            let classification = Classification {
+                modality: inferred.modality.or(own.modality.clone()),
+                body_region: inferred.body_region.or(own.body_region.clone()),
+            };
+            names.push((alias, classification));
+        }
+        for (name, classification) in names {
+            let modality = classification.modality.as_deref();
+            let region = classification.body_region.as_deref();
+            let headings: Vec<(Key, String, String)> = match grouping {
+                Grouping::Modality => vec![(
+                    (
+                        modality_rank(modality),
+                        (
+                            usize::from(region.is_none()),
+                            region.unwrap_or_default().to_string(),
+                        ),
+                    ),
+                    modality_name(modality),
+                    region_name(region),
+                )],
+                Grouping::Tag => {
+                    let tags = template.metadata().tags;
+                    let mut headings: Vec<(Key, String, String)> = Vec::new();
+                    for tag in &tags {
+                        let folded: String = tag.chars().map(fold).collect();
+                        let tag = spellings.entry(folded.clone()).or_insert(tag.clone());
+                        if !headings.iter().any(|(_, heading, _)| heading == tag) {
+                            headings.push((
+                                ((0, folded), modality_rank(modality)),
+                                tag.clone(),
+                                modality_name(modality),
+                            ));
+                        }
+                    }
+                    if headings.is_empty() {
+                        headings.push((
+                            ((1, String::new()), modality_rank(modality)),
+                            NO_TAG.to_string(),
+                            modality_name(modality),
+                        ));
+                    }
+                    headings
+                }
+            };
+            for (key, heading, subheading) in headings {
+                templates.push((
+                    key,
+                    heading,
+                    subheading,
+                    Entry {
+                        anchor: anchor(&name, &mut used),
+                        name: name.clone(),
+                        template: template.clone(),
+                        content: content.clone(),
+                    },
+                ));
+            }
+        }
+    }
+    templates.sort_by_cached_key(|(key, _, _, entry)| (key.clone(), entry.name.clone()));
+
+    let mut groups: Vec<Group> = Vec::new();
+    for (_, modality, region, entry) in templates {
+        match groups.last_mut() {
+            Some(group) if group.modality == modality && group.region == region => {
+                group.entries.push(entry)
+            }
+            last => {
+                let modality_anchor = match last {
+                    Some(group) if group.modality == modality => group.modality_anchor.clone(),
+                    _ => anchor(&modality, &mut used),
+                };
+                let region_anchor = anchor(&format!("{} {}", modality_anchor, region), &mut used);
+                groups.push(Group {
+                    modality,
+                    modality_anchor,
+                    region,
+                    anchor: region_anchor,
+                    entries: vec![entry],
+                });
+            }
+        }
+    }
+    Ok(groups)
+}
+
+/// Whether `group` is the first of its modality.
+pub(crate) fn starts_modality(groups: &[Group], index: usize) -> bool {
+    index == 0 || groups[index - 1].modality != groups[index].modality
+}
+
+pub fn to_markdown(groups: &[Group]) -> String {
+    let mut out = format!("# {}\n\n## {}\n\n", TITLE, CONTENTS);
+    for (i, group) in groups.iter().enumerate() {
+        if starts_modality(groups, i) {
+            out.push_str(&format!(
+                "- [{}](#{})\n",
+                group.modality, group.modality_anchor
+            ));
+        }
+        out.push_str(&format!("  - [{}](#{})\n", group.region, group.anchor));
+        for entry in &group.entries {
+            out.push_str(&format!("    - [{}](#{})\n", entry.name, entry.anchor));
+        }
+    }
+    for (i, group) in groups.iter().enumerate() {
+        if starts_modality(groups, i) {
+            out.push_str(&format!(
+                "\n<a id=\"{}\"></a>\n\n# {}\n",
+                group.modality_anchor, group.modality
+            ));
+        }
+        out.push_str(&format!(
+            "\n<a id=\"{}\"></a>\n\n## {}\n",
+            group.anchor, group.region
+        ));
+        for entry in &group.entries {
+            out.push_str(&format!(
+                "\n<a id=\"{}\"></a>\n\n### {}\n\n{}\n\n---\n",
+                entry.anchor,
+                entry.name,
+                front_matter::strip(&entry.content).trim_end()
+            ));
+        }
+    }
+    out
+}
+
+pub fn to_html(groups: &[Group]) -> String {
+    let link = |anchor: &str, text: &str| {
+        format!(
+            "<a href=\"#{}\">{}</a>",
+            escape_html(anchor),
+            escape_html(text)
+        )
+    };
+    let mut nav = String::from("<nav>\n<ul>\n");
+    for (i, group) in groups.iter().enumerate() {
+        if starts_modality(groups, i) {
+            if i > 0 {
+                nav.push_str("</ul></li>\n");
+            }
+            nav.push_str(&format!(
+                "<li>{}<ul>\n",
+                link(&group.modality_anchor, &group.modality)
+            ));
+        }
+        nav.push_str(&format!("<li>{}<ul>\n", link(&group.anchor, &group.region)));
+        for entry in &group.entries {
+            nav.push_str(&format!("<li>{}</li>\n", link(&entry.anchor, &entry.name)));
+        }
+        nav.push_str("</ul></li>\n");
+    }
+    if !groups.is_empty() {
+        nav.push_str("</ul></li>\n");
+    }
+    nav.push_str("</ul>\n</nav>\n");
+
+    let mut body = format!(
+        "<h1 class=\"center level1\">{}</h1>\n<h2 class=\"left level2\">{}</h2>\n{}",
+        escape_html(TITLE),
+        escape_html(CONTENTS),
+        nav
+    );
+    for (i, group) in groups.iter().enumerate() {
+        if starts_modality(groups, i) {
+            body.push_str(&format!(
+                "<h1 id=\"{}\" class=\"left level1\">{}</h1>\n",
+                escape_html(&group.modality_anchor),
+                inline_html(&group.modality, true)
+            ));
+        }
+        body.push_str(&format!(
+            "<h2 id=\"{}\" class=\"left level2\">{}</h2>\n",
+            escape_html(&group.anchor),
+            inline_html(&group.region, true)
+        ));
+        for entry in &group.entries {
+            body.push_str(&format!(
+                "<section class=\"template\" id=\"{}\">\n<h3 class=\"left level3\">{}</h3>\n{}</section>\n",
+                escape_html(&entry.anchor),
+                escape_html(&entry.name),
+                body_html(&entry.template)
+            ));
+        }
+    }
+    page_html(TITLE, None, PRINT_CSS, &body)
+}
+
+fn heading_block(text: &str, level: u8, alignment: Alignment) -> Block {
+    Block::Paragraph {
+        text: text.to_string(),
+        heading: Some(level),
+        style: LineStyle {
+            alignment,
+            font_size_pt: heading_font_size_pt(level),
+            ..LineStyle::default()
+        },
+    }
+}
+
+fn line_block(text: String) -> Block {
+    Block::Paragraph {
+        text,
+        heading: None,
+        style: LineStyle {
+            alignment: Alignment::Left,
+            ..LineStyle::default()
+        },
+    }
+}
+
+/// The contents page, with the page number of each template as `pages`
+/// gives it (one per template, in order).
+fn contents_blocks(groups: &[Group], pages: &[usize]) -> Vec<Block> {
+    let mut blocks = vec![
+        heading_block(TITLE, 1, Alignment::Center),
+        line_block(String::new()),
+        heading_block(CONTENTS, 2, Alignment::Left),
+    ];
+    let mut pages = pages.iter();
+    for (i, group) in groups.iter().enumerate() {
+        if starts_modality(groups, i) {
+            blocks.push(line_block(String::new()));
+            blocks.push(line_block(format!("**{}**", group.modality)));
+        }
+        blocks.push(line_block(format!("*{}*", group.region)));
+        for entry in &group.entries {
+            let page = pages.next().copied().unwrap_or_default();
+            blocks.push(line_block(format!("{} .... {}", entry.name, page)));
+        }
+    }
+    blocks
+}
+
+pub fn to_pdf(groups: &[Group]) -> Result<Vec<u8>> {
+    let mut parts = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        for (j, entry) in group.entries.iter().enumerate() {
+            let mut blocks = Vec::new();
+            if j == 0 {
+                if starts_modality(groups, i) {
+                    blocks.push(heading_block(&group.modality, 1, Alignment::Left));
+                }
+                blocks.push(heading_block(&group.region, 2, Alignment::Left));
+            }
+            blocks.push(heading_block(&entry.name, 3, Alignment::Left));
+            blocks.push(line_block(String::new()));
+            blocks.extend(entry.template.blocks_with_notes());
+            parts.push(blocks);
+        }
+    }
+    let count = parts.len();
+
+    // Lay the templates out once to learn their pages; the contents are as
+    // long either way, as only the numbers change.
+    let placeholder = vec![0; count];
+    parts.insert(0, contents_blocks(groups, &placeholder));
+    let (_, starts) = render_parts(&parts, &[], TITLE, None)?;
+    parts[0] = contents_blocks(groups, &starts[1..]);
+    Ok(render_parts(&parts, &[], TITLE, None)?.0)
+}
+
+/// Writes the catalog of the templates in `dir` to `output`, grouped by
+/// `grouping`, as Markdown, HTML or PDF by its extension, and returns how
+/// many templates it lists (once per tag for templates with several).
+pub fn write_catalog(
+    dir: &Path,
+    files: &FileFilter,
+    grouping: Grouping,
+    output: &Path,
+) -> Result<usize> {
+    let extension = output
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let groups = build_grouped(dir, files, grouping)?;
+    let bytes = match extension.as_str() {
+        "md" => to_markdown(&groups).into_bytes(),
+        "html" | "htm" => to_html(&groups).into_bytes(),
+        "pdf" => to_pdf(&groups)?,
+        _ => bail!(
+            "Unknown catalog format {}; expected .md, .html or .pdf",
+            output.display()
+        ),
+    };
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, bytes)?;
+    Ok(groups.iter().map(|group| group.entries.len()).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_are_grouped_with_contents_and_anchors() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, content: &str| fs::write(dir.path().join(name), content).unwrap();
+        write(
+            "TC Crânio SEM.md",
+            "TOMOGRAFIA DO CRÂNIO\n\n**Impressão:** Normal.\n",
+        );
+        write(
+            "TC Abdome.md",
+            "TOMOGRAFIA DO ABDOME\n\n**Impressão:** Normal.\n",
+        );
+        write(
+            "RM Joelho.md",
+            "RESSONÂNCIA DO JOELHO\n\n**Impressão:** Normal.\n",
+        );
+        write("Frases.md", "Frases prontas\n\nTexto.\n");
+
+        let groups = build(dir.path(), &FileFilter::default()).unwrap();
+        let headings: Vec<(&str, &str)> = groups
+            .iter()
+            .map(|group| (group.modality.as_str(), group.region.as_str()))
+            .collect();
+        assert_eq!(
+            headings,
+            [
+                ("Tomografia computadorizada", "Abdome"),
+                ("Tomografia computadorizada", "Crânio"),
+                ("Ressonância magnética", "Membro inferior"),
+                ("Outros modelos", "Região não identificada"),
+            ]
+        );
+
+        let markdown = to_markdown(&groups);
+        assert!(markdown.contains(
+            "- [Tomografia computadorizada](#tomografia-computadorizada)\n\
+             \x20 - [Abdome](#tomografia-computadorizada-abdome)\n\
+             \x20   - [TC Abdome](#tc-abdome)\n"
+        ));
+        assert!(markdown
+            .contains("<a id=\"tc-cranio-sem\"></a>\n\n### TC Crânio SEM\n\nTOMOGRAFIA DO CRÂNIO"));
+        let html = to_html(&groups);
+        assert!(html.contains("<a href=\"#rm-joelho\">RM Joelho</a>"));
+        assert!(html.contains("<section class=\"template\" id=\"rm-joelho\">"));
+    }
+
+    #[test]
+    fn templates_are_listed_under_each_tag() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, content: &str| fs::write(dir.path().join(name), content).unwrap();
+        write(
+            "TC Tórax.md",
+            "---\ntags: [oncológia, TNM]\n---\nTOMOGRAFIA DO TÓRAX\n\nTexto.\n",
+        );
+        write(
+            "RM Próstata.md",
+            "---\ntags: Oncologia, PI-RADS\n---\nRESSONÂNCIA DA PRÓSTATA\n\nTexto.\n",
+        );
+        write("RM Joelho.md", "RESSONÂNCIA DO JOELHO\n\nTexto.\n");
+
+        let groups = build_grouped(dir.path(), &FileFilter::default(), Grouping::Tag).unwrap();
+        let listed: Vec<(&str, &str, Vec<&str>)> = groups
+            .iter()
+            .map(|group| {
+                let names = group.entries.iter().map(|e| e.name.as_str()).collect();
+                (group.modality.as_str(), group.region.as_str(), names)
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("Oncologia", "Tomografia computadorizada", vec!["TC Tórax"]),
+                ("Oncologia", "Ressonância magnética", vec!["RM Próstata"]),
+                ("PI-RADS", "Ressonância magnética", vec!["RM Próstata"]),
+                ("TNM", "Tomografia computadorizada", vec!["TC Tórax"]),
+                (
+                    "Modelos sem tag",
+                    "Ressonância magnética",
+                    vec!["RM Joelho"]
+                ),
+            ]
+        );
+        // Each listing has its own anchor.
+        assert_ne!(groups[1].entries[0].anchor, groups[2].entries[0].anchor);
+    }
+}
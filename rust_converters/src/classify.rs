@@ -0,0 +1,346 @@
+//! Modality and body region of a template (`rtemplates classify`).
+//!
+//! Both come from the front matter when it sets them, and are otherwise
+//! read from the title and the file name: `TOMOGRAFIA`, `TC` or `AngioTC`
+//! make a CT, `RM` an MR, and so on, and the organs and regions named
+//! (`Crânio`, `Seios da Face`, `Abdome Pelve`, `Joelho`) make the body
+//! region, several in head-to-toe order (`crânio, tórax e abdome`). The
+//! index records the result for every template, so templates can be
+//! listed and grouped by protocol; `classify --write` adds it to the front
+//! matter of the Markdown templates that lack it.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::encoding::read_text;
+use crate::files::{list_files, FileFilter};
+use crate::front_matter;
+use crate::pick::fold;
+use crate::template::ReportTemplate;
+
+/// Title words naming the modality, checked in order against the
+/// uppercased title.
+const MODALITY_KEYWORDS: &[(&str, &str)] = &[
+    ("RESSONÂNCIA", "MR"),
+    ("RESSONANCIA", "MR"),
+    ("TOMOGRAFIA", "CT"),
+    ("ANGIOTC", "CT"),
+    ("ANGIOTEP", "CT"),
+    ("ULTRASSONOGRAFIA", "US"),
+    ("ULTRASSOM", "US"),
+    ("ECOGRAFIA", "US"),
+    ("DOPPLER", "US"),
+    ("MAMOGRAFIA", "MG"),
+    ("RADIOGRAFIA", "DX"),
+    ("RAIO-X", "DX"),
+];
+
+/// Abbreviations matched as whole words (`TC DE CRÂNIO`, `RM DO JOELHO`).
+const MODALITY_ABBREVIATIONS: &[(&str, &str)] =
+    &[("TC", "CT"), ("RM", "MR"), ("US", "US"), ("RX", "DX")];
+
+/// Words naming each body region, lowercase and without accents; a
+/// trailing `*` matches any word starting so. Regions are listed head to
+/// toe, which is the order a template naming several gets them in.
+const BODY_REGIONS: &[(&str, &[&str])] = &[
+    (
+        "crânio",
+        &[
+            "cranio",
+            "encefal*",
+            "cerebr*",
+            "sela",
+            "hipofise",
+            "mastoide*",
+            "ouvido*",
+        ],
+    ),
+    (
+        "face",
+        &["face", "orbita*", "paranasa*", "mandibula", "maxila*"],
+    ),
+    (
+        "pescoço",
+        &[
+            "pescoco",
+            "cervical",
+            "cervicais",
+            "tireoide",
+            "carotid*",
+            "laringe",
+        ],
+    ),
+    ("coluna", &["coluna*"]),
+    (
+        "tórax",
+        &[
+            "torax",
+            "esternoclavicular",
+            "toracic*",
+            "pulm*",
+            "angiotep",
+            "tep",
+            "mediastin*",
+            "cardi*",
+        ],
+    ),
+    ("mama", &["mama", "mamas", "mamari*", "mamografia"]),
+    (
+        "abdome",
+        &[
+            "abdome",
+            "abdomen",
+            "abdominal",
+            "enterotomografia",
+            "figado",
+            "hepat*",
+            "renal",
+            "renais",
+            "rins",
+            "aortoiliac*",
+        ],
+    ),
+    (
+        "pelve",
+        &[
+            "pelve",
+            "pelvic*",
+            "bacia",
+            "iliac*",
+            "aortoiliac*",
+            "prostata",
+            "utero",
+        ],
+    ),
+    (
+        "membro superior",
+        &[
+            "ombro",
+            "cotovelo",
+            "punho",
+            "mao",
+            "braco",
+            "antebraco",
+            "umero",
+        ],
+    ),
+    (
+        "membro inferior",
+        &[
+            "quadril",
+            "coxofemoral",
+            "joelho",
+            "tornozelo",
+            "pe",
+            "coxa",
+            "perna",
+            "femur",
+        ],
+    ),
+];
+
+/// The levels of a spine (`Coluna Cervical`), which are not the neck or
+/// the chest when a spine is named.
+const SPINE_LEVELS: &[&str] = &[
+    "cervical",
+    "toracica",
+    "toracico",
+    "dorsal",
+    "lombar",
+    "lombossacra",
+    "sacral",
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Classification {
+    /// DICOM modality code (`CT`, `MR`, `US`, `MG`, `DX`).
+    pub modality: Option<String>,
+    /// Lowercase Portuguese, as the front matter `body_region` is written.
+    pub body_region: Option<String>,
+}
+
+/// The modality `text` names, if any.
+pub fn modality(text: &str) -> Option<String> {
+    let upper = text.to_uppercase();
+    let modality = MODALITY_KEYWORDS
+        .iter()
+        .find(|(keyword, _)| upper.contains(keyword))
+        .or_else(|| {
+            let words: Vec<&str> = upper
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .collect();
+            MODALITY_ABBREVIATIONS
+                .iter()
+                .find(|(abbreviation, _)| words.contains(abbreviation))
+        })?;
+    Some(modality.1.to_string())
+}
+
+fn word_matches(word: &str, keyword: &str) -> bool {
+    match keyword.strip_suffix('*') {
+        Some(stem) => word.starts_with(stem),
+        None => word == keyword,
+    }
+}
+
+/// The body regions, head to toe.
+pub fn body_region_names() -> Vec<&'static str> {
+    BODY_REGIONS.iter().map(|(region, _)| *region).collect()
+}
+
+/// The body regions `text` names, joined as `a`, `a e b` or `a, b e c`.
+pub fn body_region(text: &str) -> Option<String> {
+    let folded: String = text.chars().map(fold).collect();
+    let words: Vec<&str> = folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let spine = words.iter().any(|word| word.starts_with("coluna"));
+    let regions: Vec<&str> = BODY_REGIONS
+        .iter()
+        .filter(|(_, keywords)| {
+            words.iter().any(|word| {
+                !(spine && SPINE_LEVELS.contains(word))
+                    && keywords.iter().any(|keyword| word_matches(word, keyword))
+            })
+        })
+        .map(|(region, _)| *region)
+        .collect();
+    match regions.split_last()? {
+        (last, []) => Some(last.to_string()),
+        (last, rest) => Some(format!("{} e {}", rest.join(", "), last)),
+    }
+}
+
+/// The classification of a template named `name` (its file stem) with
+/// title `title`: the modality of the title, else of the name, and the
+/// regions either names, as titles often leave out part of the protocol
+/// (`TC Crânio Tórax Abdome Pelve` titled `TOMOGRAFIA DE TÓRAX, ABDOME E
+/// PELVE`).
+pub fn classify(name: &str, title: Option<&str>) -> Classification {
+    let title = title.unwrap_or_default();
+    Classification {
+        modality: modality(title).or_else(|| modality(name)),
+        body_region: body_region(&format!("{} {}", title, name)),
+    }
+}
+
+/// The classification of the Markdown template `content` at `path`, with
+/// the front matter's `modality` and `body_region` taking precedence.
+pub fn classify_template(path: &Path, content: &str) -> Classification {
+    let template = ReportTemplate::from_markdown(content);
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    let title = template.title.as_ref().map(|title| title.plain());
+    let inferred = classify(&name, title.as_deref());
+    Classification {
+        body_region: template.metadata().body_region.or(inferred.body_region),
+        // Already the front matter's or the title's when either names one.
+        modality: template.modality.or(inferred.modality),
+    }
+}
+
+/// One template of `classify_folder`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Classified {
+    pub path: PathBuf,
+    pub classification: Classification,
+    /// The front matter keys written (`modality`, `body_region`).
+    pub written: Vec<&'static str>,
+}
+
+/// Classifies the Markdown templates in `dir` selected by `files`. With
+/// `write`, the keys a template's front matter lacks are added to it.
+pub fn classify_folder(dir: &Path, files: &FileFilter, write: bool) -> Result<Vec<Classified>> {
+    let mut results = Vec::new();
+    for path in list_files(dir, "md")? {
+        if !files.matches(path.strip_prefix(dir).unwrap_or(&path)) {
+            continue;
+        }
+        let content = read_text(&path)?;
+        let classification = classify_template(&path, &content);
+        let mut written = Vec::new();
+        if write {
+            let front = ReportTemplate::from_markdown(&content).metadata();
+            let mut keys = Vec::new();
+            if let (None, Some(modality)) = (&front.modality, &classification.modality) {
+                keys.push(("modality", modality.as_str()));
+            }
+            if let (None, Some(region)) = (&front.body_region, &classification.body_region) {
+                keys.push(("body_region", region.as_str()));
+            }
+            if !keys.is_empty() {
+                fs::write(&path, front_matter::with_keys(&content, &keys))
+                    .with_context(|| format!("Cannot write {}", path.display()))?;
+                written = keys.into_iter().map(|(key, _)| key).collect();
+            }
+        }
+        results.push(Classified {
+            path,
+            classification,
+            written,
+        });
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modality_and_region_come_from_title_and_name() {
+        let region = |text| body_region(text);
+        assert_eq!(
+            region("TC Crânio Pescoço COM").as_deref(),
+            Some("crânio e pescoço")
+        );
+        assert_eq!(
+            region("TC Crânio Tórax Abdome Pelve SEM").as_deref(),
+            Some("crânio, tórax, abdome e pelve")
+        );
+        assert_eq!(
+            region("TC Colunas Cervical, Torácica e Lombar").as_deref(),
+            Some("coluna")
+        );
+        assert_eq!(
+            region("AngioTC Crânio Cervical").as_deref(),
+            Some("crânio e pescoço")
+        );
+        assert_eq!(region("TC Seios da Face COM").as_deref(), Some("face"));
+        assert_eq!(
+            region("RM DO JOELHO DIREITO").as_deref(),
+            Some("membro inferior")
+        );
+        assert_eq!(region("Modelos simples"), None);
+
+        assert_eq!(
+            classify("AngioTEP", None),
+            Classification {
+                modality: Some("CT".to_string()),
+                body_region: Some("tórax".to_string()),
+            }
+        );
+        assert_eq!(
+            classify("US Mamas", Some("MAMOGRAFIA DIGITAL"))
+                .modality
+                .as_deref(),
+            Some("MG")
+        );
+
+        let content =
+            "---\nbody_region: neuro\n---\nTOMOGRAFIA DO CRÂNIO\n\n**Impressão:** Normal.\n";
+        let classification = classify_template(Path::new("TC Crânio.md"), content);
+        assert_eq!(classification.modality.as_deref(), Some("CT"));
+        assert_eq!(classification.body_region.as_deref(), Some("neuro"));
+        assert_eq!(
+            front_matter::with_keys("TC\n", &[("modality", "CT"), ("body_region", "crânio")]),
+            "---\nmodality: CT\nbody_region: crânio\n---\nTC\n"
+        );
+        assert_eq!(
+            front_matter::with_keys("---\ntitle: TC\n---\n\nTC\n", &[("modality", "CT")]),
+            "---\ntitle: TC\nmodality: CT\n---\n\nTC\n"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Finds outputs whose Markdown source is gone, such as a
+//! `Templates_docx/X.docx` left behind after `Templates_markdown/X.md` was
+//! deleted, or left over from a format the template no longer exports to
+//! (see `exports`).
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::aliases;
+use crate::config;
+use crate::convert::Format;
+use crate::exports;
+use crate::files::{list_files, FileFilter};
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Files in the output folders under `root` with no Markdown of the same
+/// stem, or alias of one, exported to their format, relative to `root` and
+/// sorted by folder. Only files selected by `filter` are returned.
+pub fn orphaned_outputs(root: &Path, filter: &FileFilter) -> Result<Vec<PathBuf>> {
+    let markdown_dir = root.join(&config::get().directories.markdown);
+    if !markdown_dir.is_dir() {
+        // Without the sources every output would look orphaned.
+        bail!("Markdown folder not found at {}", markdown_dir.display());
+    }
+    let mut sources: HashSet<String> = list_files(&markdown_dir, "md")?
+        .iter()
+        .map(|path| stem(path))
+        .collect();
+    sources.extend(aliases::in_folder(&markdown_dir)?.into_keys());
+
+    let mut orphans = Vec::new();
+    for format in Format::value_variants() {
+        if *format == Format::Md {
+            continue;
+        }
+        let dir = root.join(format.default_dir());
+        if !dir.is_dir() {
+            continue;
+        }
+        let left_out = exports::left_out(&markdown_dir, *format)?;
+        for path in list_files(&dir, format.extension())? {
+            let rel = path.strip_prefix(root).unwrap_or(&path);
+            let stem = stem(&path);
+            if (!sources.contains(&stem) || left_out.contains(&stem)) && filter.matches(rel) {
+                orphans.push(rel.to_path_buf());
+            }
+        }
+    }
+    Ok(orphans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn outputs_without_a_markdown_source_are_orphans() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        assert!(orphaned_outputs(root, &FileFilter::default()).is_err());
+
+        let dirs = &config::get().directories;
+        for folder in [&dirs.markdown, &dirs.docx, &dirs.txt] {
+            fs::create_dir_all(root.join(folder)).unwrap();
+        }
+        fs::write(root.join(&dirs.markdown).join("TC.md"), "# TC\n").unwrap();
+        for file in ["TC.docx", "RM.docx"] {
+            fs::write(root.join(&dirs.docx).join(file), "").unwrap();
+        }
+        for file in ["TC.txt", "RM.txt"] {
+            fs::write(root.join(&dirs.txt).join(file), "").unwrap();
+        }
+
+        assert_eq!(
+            orphaned_outputs(root, &FileFilter::default()).unwrap(),
+            [
+                Path::new(&dirs.docx).join("RM.docx"),
+                Path::new(&dirs.txt).join("RM.txt"),
+            ]
+        );
+        let only_txt = FileFilter {
+            include: vec!["*.txt".to_string()],
+            exclude: Vec::new(),
+        };
+        assert_eq!(
+            orphaned_outputs(root, &only_txt).unwrap(),
+            [Path::new(&dirs.txt).join("RM.txt")]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Copies text to the system clipboard through the platform's clipboard
+//! tool: `pbcopy` on macOS, `clip` on Windows, and `wl-copy`, `xclip` or
+//! `xsel` elsewhere. `clip` reads its input in the console code page unless
+//! it starts with a UTF-16 byte order mark, so it is given UTF-16 and keeps
+//! the accents; the others take UTF-8. RTF is copied as formatted text, for the systems that
+//! only take RTF pastes: `pbcopy` recognises it, Windows needs PowerShell and
+//! elsewhere `wl-copy` or `xclip` are told its type.
+
+use anyhow::{bail, Context, Result};
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+/// How a tool wants the text on its standard input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Input {
+    Utf8,
+    /// Little-endian, after a byte order mark.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Utf16,
+}
+
+/// A clipboard program, its arguments and the encoding it reads.
+type Tool = (&'static str, &'static [&'static str], Input);
+
+#[cfg(target_os = "macos")]
+const TOOLS: &[Tool] = &[("pbcopy", &[], Input::Utf8)];
+#[cfg(windows)]
+const TOOLS: &[Tool] = &[("clip", &[], Input::Utf16)];
+#[cfg(not(any(target_os = "macos", windows)))]
+const TOOLS: &[Tool] = &[
+    ("wl-copy", &[], Input::Utf8),
+    ("xclip", &["-selection", "clipboard"], Input::Utf8),
+    ("xsel", &["--clipboard", "--input"], Input::Utf8),
+];
+
+// RTF is ASCII, non-ASCII letters written as `\u` escapes.
+#[cfg(target_os = "macos")]
+const RTF_TOOLS: &[Tool] = &[("pbcopy", &[], Input::Utf8)];
+#[cfg(windows)]
+const RTF_TOOLS: &[Tool] = &[(
+    "powershell",
+    &[
+        "-NoProfile",
+        "-STA",
+        "-Command",
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         [System.Windows.Forms.Clipboard]::SetText([Console]::In.ReadToEnd(), 'Rtf')",
+    ],
+    Input::Utf8,
+)];
+#[cfg(not(any(target_os = "macos", windows)))]
+const RTF_TOOLS: &[Tool] = &[
+    ("wl-copy", &["--type", "text/rtf"], Input::Utf8),
+    (
+        "xclip",
+        &["-selection", "clipboard", "-t", "text/rtf"],
+        Input::Utf8,
+    ),
+];
+
+/// Puts `text` on the clipboard using the first tool that is installed.
+pub fn copy(text: &str) -> Result<()> {
+    copy_with(TOOLS, text)
+}
+
+/// Puts the RTF document `rtf` on the clipboard as formatted text.
+pub fn copy_rtf(rtf: &str) -> Result<()> {
+    copy_with(RTF_TOOLS, rtf)
+}
+
+/// `text` as `input` says.
+fn encode(text: &str, input: Input) -> Vec<u8> {
+    match input {
+        Input::Utf8 => text.as_bytes().to_vec(),
+        Input::Utf16 => std::iter::once(0xFEFF)
+            .chain(text.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect(),
+    }
+}
+
+fn copy_with(tools: &[Tool], text: &str) -> Result<()> {
+    for (program, args, input) in tools {
+        let mut child = match Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err).with_context(|| format!("Cannot run {}", program)),
+        };
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(&encode(text, *input))
+            .with_context(|| format!("Cannot write to {}", program))?;
+        let status = child.wait()?;
+        if !status.success() {
+            bail!("{} failed ({})", program, status);
+        }
+        return Ok(());
+    }
+    let names: Vec<&str> = tools.iter().map(|(program, _, _)| *program).collect();
+    bail!(
+        "No clipboard tool found; install one of: {}",
+        names.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_gets_utf16_with_a_byte_order_mark() {
+        assert_eq!(
+            encode("nódulo ção", Input::Utf16),
+            [
+                0xFF, 0xFE, b'n', 0, 0xF3, 0, b'd', 0, b'u', 0, b'l', 0, b'o', 0, b' ', 0, 0xE7, 0,
+                0xE3, 0, b'o', 0
+            ]
+        );
+        assert_eq!(encode("nódulo", Input::Utf8), "nódulo".as_bytes());
+    }
+}
@@ -0,0 +1,260 @@
+//! Word comments of an imported DOCX, kept in a sidecar next to its
+//! Markdown (`TC Crânio.comments.md`) so the review of a draft outlives the
+//! Word file.
+//!
+//! Each comment is listed with its author, its date, the text it was left
+//! on (the commented range, else the paragraph holding its mark) and what
+//! it says. The sidecar is rewritten on every import and removed once the
+//! DOCX has no comments left; the template commands leave it alone (see
+//! `files::list_files`).
+
+use anyhow::Result;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::html::{attribute, decode_entities};
+use crate::COMMENTS_SUFFIX;
+
+/// A reviewer's comment on the document.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Comment {
+    pub author: String,
+    /// `YYYY-MM-DD`, when Word recorded it.
+    pub date: Option<String>,
+    /// Its paragraphs, one per line.
+    pub text: String,
+    /// The text of the document it refers to.
+    pub anchor: Option<String>,
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid regex"))
+}
+
+/// The comments of the package: `comments_xml` (`word/comments.xml`) with
+/// the text of `document_xml` each refers to.
+pub fn read(comments_xml: &str, document_xml: &str) -> Vec<Comment> {
+    anchors(document_xml).attach(parse(comments_xml))
+}
+
+/// The comments of `word/comments.xml`, by id.
+fn parse(xml: &str) -> Vec<(String, Comment)> {
+    static COMMENT: OnceLock<Regex> = OnceLock::new();
+    static PARAGRAPH: OnceLock<Regex> = OnceLock::new();
+    static TEXT: OnceLock<Regex> = OnceLock::new();
+    let paragraph = regex(&PARAGRAPH, r"(?s)<w:p[\s>].*?</w:p>");
+    let text = regex(&TEXT, r"(?s)<w:t(?:\s[^>]*)?>(.*?)</w:t>");
+    regex(&COMMENT, r"(?s)<w:comment\s([^>]*)>(.*?)</w:comment>")
+        .captures_iter(xml)
+        .filter_map(|caps| {
+            let attrs = caps.get(1).map_or("", |m| m.as_str());
+            let id = attribute(attrs, "w:id")?.to_string();
+            let lines: Vec<String> = paragraph
+                .find_iter(&caps[2])
+                .map(|p| {
+                    let line: String = text
+                        .captures_iter(p.as_str())
+                        .map(|t| decode_entities(&t[1]))
+                        .collect();
+                    line.trim().to_string()
+                })
+                .filter(|line| !line.is_empty())
+                .collect();
+            let comment = Comment {
+                author: decode_entities(attribute(attrs, "w:author").unwrap_or("")),
+                date: attribute(attrs, "w:date")
+                    .and_then(|date| date.get(..10))
+                    .map(str::to_string),
+                text: lines.join("\n"),
+                anchor: None,
+            };
+            Some((id, comment))
+        })
+        .collect()
+}
+
+/// The text each comment of `document_xml` refers to.
+fn anchors(document_xml: &str) -> Anchors {
+    static TOKEN: OnceLock<Regex> = OnceLock::new();
+    let token = regex(
+        &TOKEN,
+        r"(?s)<w:(commentRangeStart|commentRangeEnd|commentReference)\s([^>]*)/>|<w:t(?:\s[^>]*)?>([^<]*)</w:t>|<w:tab/>|</w:p>",
+    );
+    let mut anchors = Anchors::default();
+    for caps in token.captures_iter(document_xml) {
+        if let Some(kind) = caps.get(1) {
+            let Some(id) = attribute(&caps[2], "w:id") else {
+                continue;
+            };
+            match kind.as_str() {
+                "commentRangeStart" => anchors.start(id),
+                "commentRangeEnd" => anchors.end(id),
+                _ => anchors.mark(id),
+            }
+        } else if let Some(text) = caps.get(3) {
+            anchors.text(&decode_entities(text.as_str()));
+        } else if &caps[0] == "</w:p>" {
+            anchors.end_paragraph();
+        } else {
+            anchors.text(" ");
+        }
+    }
+    anchors
+}
+
+/// The text each comment of a body refers to, gathered while its
+/// paragraphs are read in order.
+#[derive(Debug, Default)]
+struct Anchors {
+    /// Comments whose range has started and not ended.
+    open: Vec<String>,
+    /// The text of the paragraph being read.
+    paragraph: String,
+    /// Comments marked in that paragraph without a range.
+    marked: Vec<String>,
+    /// Text found so far, by comment id.
+    found: HashMap<String, String>,
+}
+
+impl Anchors {
+    fn start(&mut self, id: &str) {
+        self.open.push(id.to_string());
+        self.found.entry(id.to_string()).or_default();
+    }
+
+    fn end(&mut self, id: &str) {
+        self.open.retain(|open| open != id);
+    }
+
+    /// The comment mark of `id`, in the text of the paragraph it closes.
+    fn mark(&mut self, id: &str) {
+        self.marked.push(id.to_string());
+    }
+
+    /// Text of the document, read in order.
+    fn text(&mut self, text: &str) {
+        self.paragraph.push_str(text);
+        for id in &self.open {
+            self.found.entry(id.clone()).or_default().push_str(text);
+        }
+    }
+
+    /// Ends a paragraph: a range goes on in the next one after a space.
+    fn end_paragraph(&mut self) {
+        for id in self.marked.drain(..) {
+            let anchor = self.found.entry(id).or_default();
+            if anchor.trim().is_empty() {
+                *anchor = self.paragraph.clone();
+            }
+        }
+        for id in &self.open {
+            self.found.entry(id.clone()).or_default().push(' ');
+        }
+        self.paragraph.clear();
+    }
+
+    /// `comments` with the text each refers to.
+    fn attach(mut self, comments: Vec<(String, Comment)>) -> Vec<Comment> {
+        comments
+            .into_iter()
+            .map(|(id, mut comment)| {
+                comment.anchor = self
+                    .found
+                    .remove(&id)
+                    .map(|anchor| anchor.split_whitespace().collect::<Vec<_>>().join(" "))
+                    .filter(|anchor| !anchor.is_empty());
+                comment
+            })
+            .collect()
+    }
+}
+
+/// The sidecar of the Markdown file `md_path`: `<name>.comments.md`.
+pub fn sidecar_path(md_path: &Path) -> PathBuf {
+    let stem = md_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    md_path.with_file_name(format!("{}{}", stem, COMMENTS_SUFFIX))
+}
+
+/// Whether `path` is the sidecar of a template rather than a template.
+pub fn is_sidecar(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(COMMENTS_SUFFIX))
+}
+
+/// The sidecar listing `comments`, left on the document `source`.
+pub fn to_markdown(source: &str, comments: &[Comment]) -> String {
+    let mut markdown = format!("# Comentários de revisão: {}\n", source);
+    for (number, comment) in comments.iter().enumerate() {
+        let author = if comment.author.trim().is_empty() {
+            "Sem autor"
+        } else {
+            comment.author.trim()
+        };
+        markdown.push_str(&format!("\n## {}. {}", number + 1, author));
+        if let Some(date) = &comment.date {
+            markdown.push_str(&format!(", {}", date));
+        }
+        markdown.push_str("\n\n");
+        if let Some(anchor) = &comment.anchor {
+            markdown.push_str(&format!("> {}\n\n", anchor));
+        }
+        let text: Vec<&str> = comment.text.lines().collect();
+        markdown.push_str(&text.join("\n\n"));
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// Writes the sidecar of `md_path` listing `comments` from the document
+/// `source`, or removes it when there are none.
+pub fn write_sidecar(md_path: &Path, source: &str, comments: &[Comment]) -> Result<()> {
+    let path = sidecar_path(md_path);
+    if comments.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    fs::write(path, to_markdown(source, comments))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_are_listed_with_the_text_they_refer_to() {
+        let xml = r#"<w:comments><w:comment w:id="0" w:author="Dra. Beltrana" w:date="2026-10-01T09:30:00Z" w:initials="DB"><w:p><w:r><w:annotationRef/></w:r><w:r><w:t>Especificar o &quot;protocolo&quot;.</w:t></w:r></w:p><w:p><w:r><w:t xml:space="preserve">Ver rotina. </w:t></w:r></w:p></w:comment><w:comment w:id="1" w:author="R2"><w:p><w:r><w:t>Revisar.</w:t></w:r></w:p></w:comment></w:comments>"#;
+        assert_eq!(
+            parse(xml)[0].1.text,
+            "Especificar o \"protocolo\".\nVer rotina."
+        );
+        let document = r#"<w:body><w:p><w:r><w:t xml:space="preserve">Aquisição </w:t></w:r><w:commentRangeStart w:id="0"/><w:r><w:t>volumétrica</w:t></w:r></w:p><w:p><w:r><w:t>sem contraste</w:t></w:r><w:commentRangeEnd w:id="0"/><w:r><w:commentReference w:id="0"/></w:r><w:r><w:t>.</w:t></w:r><w:r><w:commentReference w:id="1"/></w:r></w:p></w:body>"#;
+        let comments = read(xml, document);
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].date.as_deref(), Some("2026-10-01"));
+        assert_eq!(
+            comments[0].anchor.as_deref(),
+            Some("volumétrica sem contraste")
+        );
+        assert_eq!(comments[1].anchor.as_deref(), Some("sem contraste."));
+
+        assert_eq!(
+            to_markdown("TC Crânio.docx", &comments),
+            "# Comentários de revisão: TC Crânio.docx\n\n\
+             ## 1. Dra. Beltrana, 2026-10-01\n\n> volumétrica sem contraste\n\n\
+             Especificar o \"protocolo\".\n\nVer rotina.\n\n\
+             ## 2. R2\n\n> sem contraste.\n\nRevisar.\n"
+        );
+        let sidecar = sidecar_path(Path::new("md/TC Crânio.md"));
+        assert_eq!(sidecar, Path::new("md/TC Crânio.comments.md"));
+        assert!(is_sidecar(&sidecar) && !is_sidecar(Path::new("md/TC Crânio.md")));
+    }
+}
@@ -0,0 +1,984 @@
+//! Settings read from `rtemplates.toml` in the working directory:
+//!
+//! ```toml
+//! [directories]
+//! docx = "Laudos/docx"
+//! markdown = "Laudos/md"
+//! txt = "Laudos/txt"
+//! # also pdf, html, odt, rtf, latex, json, mrrt, sr, partials, phrases and backup
+//!
+//! [document]
+//! font = "Calibri"
+//! font_size = 11
+//! language = "pt-BR"
+//! disclaimer = "Este laudo deve ser interpretado em conjunto com os dados clínicos."
+//!
+//! [section_keywords]
+//! impressao = ["parecer", "conclusões"]
+//! tecnica = ["protocolo"]
+//!
+//! [section_keywords.en]
+//! impressao = ["summary"]
+//!
+//! [markdown]
+//! underline = "html"
+//! layout = "markers"
+//!
+//! [docx]
+//! reference = "Laudos/papel_timbrado.dotx"
+//! header = "![](Laudos/assets/logo.png){height=1.5cm}"
+//! footer = "Dr. Fulano<br>CRM 12345"
+//! line_spacing = 1.15
+//! space_after = 6
+//! locked_sections = ["tecnica"]
+//! tracked_changes = "reject"
+//!
+//! [lint]
+//! line_length = "error"
+//! max_line_length = 120
+//! required_sections = ["tecnica", "impressao"]
+//! dictionary = "/usr/share/hunspell/pt_BR"
+//! allowlist = "termos_medicos.txt"
+//! lexicon = "lexico.csv"
+//! decimal_separator = ","
+//!
+//! [lint.units]
+//! hu = "UH"
+//!
+//! [txt]
+//! typography = true
+//!
+//! [txt.replace]
+//! "°" = "o"
+//!
+//! [placeholders]
+//! institution = "Hospital São Lucas"
+//!
+//! [snippets]
+//! prefix = ";"
+//! max_length = 300
+//!
+//! [snippets.triggers]
+//! "TC Crânio SEM" = ";cranio"
+//!
+//! [sr]
+//! station_name = "RTEMPLATES"
+//! manufacturer = "Radiology Templates"
+//!
+//! [latex]
+//! preamble = "Apostila/preambulo.tex"
+//! standalone = true
+//!
+//! [signatures.fulano]
+//! name = "Dr. Fulano de Tal"
+//! crm = "CRM-SP 123456"
+//! specialty = "Radiologia e Diagnóstico por Imagem"
+//!
+//! [sites.clinica_a]
+//! name = "Clínica A"
+//! address = "Rua das Flores, 100, São Paulo"
+//! department = "Radiologia"
+//! reference = "Laudos/timbrado_clinica_a.dotx"
+//! header = "![](Laudos/assets/clinica_a.png){height=1.5cm}"
+//! footer = "Clínica A – Rua das Flores, 100<br>Tel. (11) 3333-0000"
+//!
+//! [sites.clinica_a.placeholders]
+//! phone = "(11) 3333-0000"
+//! ```
+//!
+//! Every key is optional and missing ones keep the built-in value, so an
+//! absent file changes nothing. Binaries load the file once at startup
+//! (`init_from_file`); the rest of the library reads the result with `get`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::language::Language;
+use crate::lint::{Rule, Severity};
+use crate::markdown::BODY_FONT_SIZE_PT;
+use crate::template::SectionKind;
+use crate::{
+    BACKUP_DIR, DOCX_DIR, HTML_DIR, JSON_DIR, LATEX_DIR, MARKDOWN_DIR, MRRT_DIR, ODT_DIR,
+    PARTIALS_DIR, PDF_DIR, PHRASES_DIR, RTF_DIR, SR_DIR, TXT_DIR,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Directories {
+    pub docx: String,
+    pub markdown: String,
+    pub txt: String,
+    pub pdf: String,
+    pub html: String,
+    pub odt: String,
+    pub rtf: String,
+    pub latex: String,
+    pub json: String,
+    pub mrrt: String,
+    pub sr: String,
+    pub partials: String,
+    pub phrases: String,
+    pub backup: String,
+}
+
+impl Default for Directories {
+    fn default() -> Self {
+        Directories {
+            docx: DOCX_DIR.to_string(),
+            markdown: MARKDOWN_DIR.to_string(),
+            txt: TXT_DIR.to_string(),
+            pdf: PDF_DIR.to_string(),
+            html: HTML_DIR.to_string(),
+            odt: ODT_DIR.to_string(),
+            rtf: RTF_DIR.to_string(),
+            latex: LATEX_DIR.to_string(),
+            json: JSON_DIR.to_string(),
+            mrrt: MRRT_DIR.to_string(),
+            sr: SR_DIR.to_string(),
+            partials: PARTIALS_DIR.to_string(),
+            phrases: PHRASES_DIR.to_string(),
+            backup: BACKUP_DIR.to_string(),
+        }
+    }
+}
+
+/// Which lines of a template are its centered title and footer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LayoutRule {
+    /// The lines the front matter's `title:` and `footer:` name, when it
+    /// has either; otherwise the first and last non-empty lines.
+    #[default]
+    Auto,
+    /// Only the lines the front matter names; without `title:` and
+    /// `footer:` a template has neither.
+    Markers,
+}
+
+/// How underlined text is written in Markdown, which has no syntax of its
+/// own for it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnderlineMarkup {
+    /// `__text__`, as the DOCX importers have always written it; `**` is
+    /// the only bold marker.
+    #[default]
+    Underscores,
+    /// `<u>text</u>`, leaving `__text__` bold as in CommonMark.
+    Html,
+}
+
+/// What imported DOCX files keep of the changes tracked in them (see
+/// `revisions`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TrackedChanges {
+    /// The text as it reads with every change accepted.
+    #[default]
+    Accept,
+    /// The text as it read before the changes.
+    Reject,
+}
+
+/// Spacing of DOCX paragraphs; unset values keep Word's.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ParagraphSpacing {
+    /// Line height as a multiple of single spacing, e.g. 1.15.
+    pub line: Option<f64>,
+    /// Space above and below each paragraph, in points.
+    pub before_pt: Option<f64>,
+    pub after_pt: Option<f64>,
+}
+
+/// Settings of `rtemplates lint`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintSettings {
+    /// Severities set in the config, overriding `Rule::default_severity`.
+    pub levels: Vec<(Rule, Severity)>,
+    pub max_line_length: usize,
+    pub required_sections: Vec<SectionKind>,
+    /// Hunspell dictionary of the `spelling` rule, without its `.aff`/`.dic`
+    /// extension; found in the system folders when unset.
+    pub dictionary: Option<String>,
+    /// Words file accepted on top of the dictionary.
+    pub allowlist: Option<String>,
+    /// CSV lexicon of the `terminology` rule; the rule needs one.
+    pub lexicon: Option<String>,
+    /// Decimal separator of measurements, `,` or `.`.
+    pub decimal_separator: char,
+    /// Unit spellings of the department (`ml` → `mL`), on top of the
+    /// built-in ones of `measurements`.
+    pub units: Vec<(String, String)>,
+}
+
+impl Default for LintSettings {
+    fn default() -> Self {
+        LintSettings {
+            levels: Vec::new(),
+            // Long enough for the usual closing disclaimer.
+            max_line_length: 300,
+            required_sections: vec![SectionKind::Impressao],
+            dictionary: None,
+            allowlist: None,
+            lexicon: None,
+            decimal_separator: ',',
+            units: Vec::new(),
+        }
+    }
+}
+
+impl LintSettings {
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.levels
+            .iter()
+            .rev()
+            .find(|(r, _)| *r == rule)
+            .map_or(rule.default_severity(), |(_, severity)| *severity)
+    }
+}
+
+/// Settings of TXT output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxtSettings {
+    /// Whether curly quotes, dashes and special spaces are written as ASCII
+    /// (see `typography`).
+    pub typography: bool,
+    /// Replacements of single characters, on top of `typography::PLAIN`.
+    pub replace: Vec<(char, String)>,
+}
+
+/// Settings of the text expander snippets (`snippets`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnippetSettings {
+    /// Put before the trigger each template gets from its file name.
+    pub prefix: String,
+    /// Triggers by file stem, used instead of the derived ones.
+    pub triggers: Vec<(String, String)>,
+    /// Longest text, in characters, of a macOS text replacement.
+    pub max_length: usize,
+}
+
+impl Default for SnippetSettings {
+    fn default() -> Self {
+        SnippetSettings {
+            prefix: ":".to_string(),
+            triggers: Vec::new(),
+            max_length: 500,
+        }
+    }
+}
+
+/// Equipment attributes of the DICOM SR objects (`convert_to_sr`); the
+/// institution ones come from the placeholders.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SrSettings {
+    pub station_name: Option<String>,
+    pub manufacturer: Option<String>,
+}
+
+/// Settings of the LaTeX output (`convert_to_latex`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatexSettings {
+    /// A file whose contents replace the built-in preamble.
+    pub preamble: Option<String>,
+    /// Whether to write whole documents rather than bodies to `\input`.
+    pub standalone: bool,
+}
+
+impl Default for LatexSettings {
+    fn default() -> Self {
+        LatexSettings {
+            preamble: None,
+            standalone: true,
+        }
+    }
+}
+
+/// A radiologist's signature block (`[signatures.<id>]`), written before
+/// the footer of the outputs converted with `--signer <id>` (see
+/// `signature`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    /// Council registration, e.g. `CRM-SP 123456`.
+    pub crm: Option<String>,
+    pub specialty: Option<String>,
+}
+
+/// An institution the group reports for (`[sites.<id>]`), chosen per run
+/// with `--site <id>` (see `Config::select_site`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Site {
+    /// The institution's name: the `{{institution}}` placeholder and the
+    /// author of the documents whose front matter names none.
+    pub name: Option<String>,
+    /// The `{{institution_address}}` and `{{institution_department}}`
+    /// placeholders, written with the name into DICOM SR objects.
+    pub address: Option<String>,
+    pub department: Option<String>,
+    /// Replace `[docx] reference`, `header` and `footer` and
+    /// `[document] disclaimer`.
+    pub reference: Option<String>,
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    pub disclaimer: Option<String>,
+    /// Placeholder values of this site, over the `[placeholders]` ones.
+    pub placeholders: Vec<(String, String)>,
+}
+
+impl Default for TxtSettings {
+    fn default() -> Self {
+        TxtSettings {
+            typography: true,
+            replace: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub directories: Directories,
+    /// Body font of DOCX, ODT and HTML output. PDF keeps its built-in
+    /// Helvetica.
+    pub font: String,
+    /// Body text size in points; the footer stays at 8pt.
+    pub font_size_pt: i32,
+    /// BCP 47 tag written to HTML, MRRT and DICOM SR output.
+    pub language: String,
+    /// Inline Markdown added after the footer of every DOCX, PDF, TXT, ODT
+    /// and HTML output, italic 8pt and centered, unless the footer already
+    /// reads the same. Front matter `disclaimer` overrides it.
+    pub disclaimer: Option<String>,
+    /// Extra heading prefixes (lowercase) per section, tried before the
+    /// built-in ones, each for one language or, when None, for all.
+    pub section_keywords: Vec<(String, SectionKind, Option<Language>)>,
+    /// Underline markup read and written by every converter. `<u>` tags are
+    /// read either way.
+    pub underline: UnderlineMarkup,
+    /// How templates mark their title and footer lines.
+    pub layout: LayoutRule,
+    /// Inline Markdown repeated at the top of every DOCX page; `<br>` or a
+    /// newline starts another paragraph and image paths are relative to
+    /// the working directory. Front matter `page_header` overrides it.
+    pub page_header: Option<String>,
+    /// The same at the bottom of every page (`page_footer`).
+    pub page_footer: Option<String>,
+    pub paragraph_spacing: ParagraphSpacing,
+    /// Sections written in DOCX as content controls Word does not let the
+    /// reader edit or delete, such as the protocol of `tecnica`.
+    pub locked_sections: Vec<SectionKind>,
+    /// Whether DOCX imports accept or reject the changes still tracked.
+    pub tracked_changes: TrackedChanges,
+    /// A `.docx` or `.dotx` whose styles, page header and footer and page
+    /// setup DOCX output is written on (see `docx_reference`).
+    pub reference_docx: Option<String>,
+    pub lint: LintSettings,
+    pub txt: TxtSettings,
+    /// Values of the `{{name}}` placeholders (`institution`), by name; see
+    /// `placeholders`.
+    pub placeholders: Vec<(String, String)>,
+    pub snippets: SnippetSettings,
+    pub sr: SrSettings,
+    pub latex: LatexSettings,
+    /// Signature profiles by id, in file order.
+    pub signatures: Vec<(String, Signature)>,
+    /// Institution profiles by id, in file order.
+    pub sites: Vec<(String, Site)>,
+    /// The id of the selected site, if any.
+    pub site: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            directories: Directories::default(),
+            font: "Arial".to_string(),
+            font_size_pt: BODY_FONT_SIZE_PT,
+            language: "pt-BR".to_string(),
+            disclaimer: None,
+            section_keywords: Vec::new(),
+            underline: UnderlineMarkup::default(),
+            layout: LayoutRule::default(),
+            page_header: None,
+            page_footer: None,
+            paragraph_spacing: ParagraphSpacing::default(),
+            locked_sections: Vec::new(),
+            tracked_changes: TrackedChanges::default(),
+            reference_docx: None,
+            lint: LintSettings::default(),
+            txt: TxtSettings::default(),
+            placeholders: Vec::new(),
+            snippets: SnippetSettings::default(),
+            sr: SrSettings::default(),
+            latex: LatexSettings::default(),
+            signatures: Vec::new(),
+            sites: Vec::new(),
+            site: None,
+        }
+    }
+}
+
+fn table<'a>(document: &'a DocumentMut, name: &str) -> Result<Option<&'a Table>> {
+    match document.get(name) {
+        None => Ok(None),
+        Some(item) => item
+            .as_table()
+            .map(Some)
+            .ok_or_else(|| anyhow!("[{}] must be a table", name)),
+    }
+}
+
+/// Adds the keyword list `item` of section `key` under `[section_keywords]`.
+fn push_keywords(
+    keywords: &mut Vec<(String, SectionKind, Option<Language>)>,
+    key: &str,
+    item: &Item,
+    language: Option<Language>,
+) -> Result<()> {
+    let kind = SectionKind::from_key(key).ok_or_else(|| {
+        anyhow!(
+            "Unknown section `{}`; expected indicacao_clinica, tecnica, aspectos_observados, impressao or a language code",
+            key
+        )
+    })?;
+    let list = item
+        .as_array()
+        .ok_or_else(|| anyhow!("`{}` must be a list of strings", key))?;
+    for value in list.iter() {
+        let keyword = value
+            .as_str()
+            .ok_or_else(|| anyhow!("`{}` must be a list of strings", key))?;
+        keywords.push((keyword.trim().to_lowercase(), kind, language));
+    }
+    Ok(())
+}
+
+/// A list of section keys.
+fn section_list(item: &Item, key: &str) -> Result<Vec<SectionKind>> {
+    let list = item
+        .as_array()
+        .ok_or_else(|| anyhow!("`{}` must be a list of section keys", key))?;
+    list.iter()
+        .map(|value| {
+            let name = value
+                .as_str()
+                .ok_or_else(|| anyhow!("`{}` must be a list of section keys", key))?;
+            SectionKind::from_key(name).ok_or_else(|| {
+                anyhow!(
+                    "Unknown section `{}`; expected indicacao_clinica, tecnica, aspectos_observados or impressao",
+                    name
+                )
+            })
+        })
+        .collect()
+}
+
+fn string(item: &Item, key: &str) -> Result<String> {
+    item.as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("`{}` must be a string", key))
+}
+
+/// A number in `range`; TOML integers are accepted as well as floats.
+fn number(item: &Item, key: &str, range: std::ops::RangeInclusive<f64>) -> Result<f64> {
+    item.as_float()
+        .or_else(|| item.as_integer().map(|value| value as f64))
+        .filter(|value| range.contains(value))
+        .ok_or_else(|| {
+            anyhow!(
+                "`{}` must be a number from {} to {}",
+                key,
+                range.start(),
+                range.end()
+            )
+        })
+}
+
+impl Config {
+    /// Parses the contents of `rtemplates.toml`.
+    pub fn parse(source: &str) -> Result<Self> {
+        let document: DocumentMut = source.parse()?;
+        let mut config = Config::default();
+
+        if let Some(directories) = table(&document, "directories")? {
+            let dirs = &mut config.directories;
+            for (key, item) in directories.iter() {
+                let slot = match key {
+                    "docx" => &mut dirs.docx,
+                    "markdown" => &mut dirs.markdown,
+                    "txt" => &mut dirs.txt,
+                    "pdf" => &mut dirs.pdf,
+                    "html" => &mut dirs.html,
+                    "odt" => &mut dirs.odt,
+                    "rtf" => &mut dirs.rtf,
+                    "latex" => &mut dirs.latex,
+                    "json" => &mut dirs.json,
+                    "mrrt" => &mut dirs.mrrt,
+                    "sr" => &mut dirs.sr,
+                    "partials" => &mut dirs.partials,
+                    "phrases" => &mut dirs.phrases,
+                    "backup" => &mut dirs.backup,
+                    _ => bail!("Unknown directory `{}`", key),
+                };
+                *slot = string(item, key)?;
+            }
+        }
+
+        if let Some(document) = table(&document, "document")? {
+            for (key, item) in document.iter() {
+                match key {
+                    "font" => config.font = string(item, key)?,
+                    "language" => config.language = string(item, key)?,
+                    "disclaimer" => config.disclaimer = Some(string(item, key)?),
+                    "font_size" => {
+                        config.font_size_pt = item
+                            .as_integer()
+                            .filter(|size| (1..=288).contains(size))
+                            .ok_or_else(|| {
+                                anyhow!("`font_size` must be a whole number of points")
+                            })? as i32;
+                    }
+                    _ => bail!("Unknown document setting `{}`", key),
+                }
+            }
+        }
+
+        if let Some(keywords) = table(&document, "section_keywords")? {
+            for (key, item) in keywords.iter() {
+                // `[section_keywords.en]` holds the keywords of one language.
+                match (Language::from_code(key), item.as_table_like()) {
+                    (Some(language), Some(lists)) => {
+                        for (key, item) in lists.iter() {
+                            push_keywords(&mut config.section_keywords, key, item, Some(language))?;
+                        }
+                    }
+                    _ => push_keywords(&mut config.section_keywords, key, item, None)?,
+                }
+            }
+            // Longest first, like the built-in list, so "impressão final"
+            // wins over "impressão".
+            config
+                .section_keywords
+                .sort_by_key(|(keyword, _, _)| std::cmp::Reverse(keyword.chars().count()));
+        }
+
+        if let Some(markdown) = table(&document, "markdown")? {
+            for (key, item) in markdown.iter() {
+                match key {
+                    "underline" => {
+                        config.underline = match string(item, key)?.as_str() {
+                            "underscores" => UnderlineMarkup::Underscores,
+                            "html" => UnderlineMarkup::Html,
+                            other => bail!(
+                                "Unknown underline markup `{}`; expected underscores or html",
+                                other
+                            ),
+                        }
+                    }
+                    "layout" => {
+                        config.layout = match string(item, key)?.as_str() {
+                            "auto" => LayoutRule::Auto,
+                            "markers" => LayoutRule::Markers,
+                            other => bail!("Unknown layout `{}`; expected auto or markers", other),
+                        }
+                    }
+                    _ => bail!("Unknown markdown setting `{}`", key),
+                }
+            }
+        }
+
+        if let Some(docx) = table(&document, "docx")? {
+            for (key, item) in docx.iter() {
+                match key {
+                    "reference" => config.reference_docx = Some(string(item, key)?),
+                    "header" => config.page_header = Some(string(item, key)?),
+                    "footer" => config.page_footer = Some(string(item, key)?),
+                    "line_spacing" => {
+                        config.paragraph_spacing.line = Some(number(item, key, 0.5..=5.0)?)
+                    }
+                    // Word's limit is 1584pt.
+                    "space_before" => {
+                        config.paragraph_spacing.before_pt = Some(number(item, key, 0.0..=1584.0)?)
+                    }
+                    "space_after" => {
+                        config.paragraph_spacing.after_pt = Some(number(item, key, 0.0..=1584.0)?)
+                    }
+                    "locked_sections" => config.locked_sections = section_list(item, key)?,
+                    "tracked_changes" => {
+                        config.tracked_changes = match string(item, key)?.as_str() {
+                            "accept" => TrackedChanges::Accept,
+                            "reject" => TrackedChanges::Reject,
+                            other => bail!(
+                                "Unknown tracked_changes `{}`; expected accept or reject",
+                                other
+                            ),
+                        }
+                    }
+                    _ => bail!("Unknown docx setting `{}`", key),
+                }
+            }
+        }
+
+        if let Some(lint) = table(&document, "lint")? {
+            for (key, item) in lint.iter() {
+                match key {
+                    "max_line_length" => {
+                        config.lint.max_line_length = item
+                            .as_integer()
+                            .filter(|length| *length > 0)
+                            .ok_or_else(|| {
+                                anyhow!("`max_line_length` must be a positive whole number")
+                            })? as usize;
+                    }
+                    "required_sections" => config.lint.required_sections = section_list(item, key)?,
+                    "dictionary" => config.lint.dictionary = Some(string(item, key)?),
+                    "allowlist" => config.lint.allowlist = Some(string(item, key)?),
+                    "lexicon" => config.lint.lexicon = Some(string(item, key)?),
+                    "decimal_separator" => {
+                        config.lint.decimal_separator = match string(item, key)?.as_str() {
+                            "," => ',',
+                            "." => '.',
+                            other => bail!(
+                                "Unknown decimal separator `{}`; expected \",\" or \".\"",
+                                other
+                            ),
+                        }
+                    }
+                    "units" => {
+                        let units = item
+                            .as_table_like()
+                            .ok_or_else(|| anyhow!("[lint.units] must be a table"))?;
+                        for (written, preferred) in units.iter() {
+                            config
+                                .lint
+                                .units
+                                .push((written.to_string(), string(preferred, written)?));
+                        }
+                    }
+                    _ => {
+                        let rule = Rule::from_name(key)
+                            .ok_or_else(|| anyhow!("Unknown lint setting `{}`", key))?;
+                        let value = string(item, key)?;
+                        let severity = Severity::from_name(&value).ok_or_else(|| {
+                            anyhow!(
+                                "Unknown severity `{}`; expected error, warning or off",
+                                value
+                            )
+                        })?;
+                        config.lint.levels.push((rule, severity));
+                    }
+                }
+            }
+        }
+
+        if let Some(txt) = table(&document, "txt")? {
+            for (key, item) in txt.iter() {
+                match key {
+                    "typography" => {
+                        config.txt.typography = item
+                            .as_bool()
+                            .ok_or_else(|| anyhow!("`typography` must be true or false"))?;
+                    }
+                    "replace" => {
+                        let replace = item
+                            .as_table_like()
+                            .ok_or_else(|| anyhow!("[txt.replace] must be a table"))?;
+                        for (from, to) in replace.iter() {
+                            let mut chars = from.chars();
+                            let (Some(c), None) = (chars.next(), chars.next()) else {
+                                bail!("`{}` in [txt.replace] must be a single character", from);
+                            };
+                            config.txt.replace.push((c, string(to, from)?));
+                        }
+                    }
+                    _ => bail!("Unknown txt setting `{}`", key),
+                }
+            }
+        }
+
+        if let Some(placeholders) = table(&document, "placeholders")? {
+            for (key, item) in placeholders.iter() {
+                config
+                    .placeholders
+                    .push((key.to_string(), string(item, key)?));
+            }
+        }
+
+        if let Some(snippets) = table(&document, "snippets")? {
+            for (key, item) in snippets.iter() {
+                match key {
+                    "prefix" => config.snippets.prefix = string(item, key)?,
+                    "max_length" => {
+                        config.snippets.max_length =
+                            item.as_integer().filter(|n| *n > 0).ok_or_else(|| {
+                                anyhow!("`max_length` must be a positive whole number")
+                            })? as usize;
+                    }
+                    "triggers" => {
+                        let triggers = item
+                            .as_table_like()
+                            .ok_or_else(|| anyhow!("[snippets.triggers] must be a table"))?;
+                        for (name, trigger) in triggers.iter() {
+                            config
+                                .snippets
+                                .triggers
+                                .push((name.to_string(), string(trigger, name)?));
+                        }
+                    }
+                    _ => bail!("Unknown snippets setting `{}`", key),
+                }
+            }
+        }
+
+        if let Some(sr) = table(&document, "sr")? {
+            for (key, item) in sr.iter() {
+                match key {
+                    "station_name" => config.sr.station_name = Some(string(item, key)?),
+                    "manufacturer" => config.sr.manufacturer = Some(string(item, key)?),
+                    _ => bail!("Unknown SR setting `{}`", key),
+                }
+            }
+        }
+
+        if let Some(latex) = table(&document, "latex")? {
+            for (key, item) in latex.iter() {
+                match key {
+                    "preamble" => config.latex.preamble = Some(string(item, key)?),
+                    "standalone" => {
+                        config.latex.standalone = item
+                            .as_bool()
+                            .ok_or_else(|| anyhow!("`standalone` must be true or false"))?;
+                    }
+                    _ => bail!("Unknown latex setting `{}`", key),
+                }
+            }
+        }
+
+        if let Some(signatures) = table(&document, "signatures")? {
+            for (id, item) in signatures.iter() {
+                let fields = item
+                    .as_table_like()
+                    .ok_or_else(|| anyhow!("[signatures.{}] must be a table", id))?;
+                let mut signature = Signature::default();
+                for (key, item) in fields.iter() {
+                    match key {
+                        "name" => signature.name = string(item, key)?,
+                        "crm" => signature.crm = Some(string(item, key)?),
+                        "specialty" => signature.specialty = Some(string(item, key)?),
+                        _ => bail!("Unknown signature setting `{}`", key),
+                    }
+                }
+                if signature.name.trim().is_empty() {
+                    bail!("[signatures.{}] needs a `name`", id);
+                }
+                config.signatures.push((id.to_string(), signature));
+            }
+        }
+
+        if let Some(sites) = table(&document, "sites")? {
+            for (id, item) in sites.iter() {
+                let fields = item
+                    .as_table_like()
+                    .ok_or_else(|| anyhow!("[sites.{}] must be a table", id))?;
+                let mut site = Site::default();
+                for (key, item) in fields.iter() {
+                    match key {
+                        "name" => site.name = Some(string(item, key)?),
+                        "address" => site.address = Some(string(item, key)?),
+                        "department" => site.department = Some(string(item, key)?),
+                        "reference" => site.reference = Some(string(item, key)?),
+                        "header" => site.header = Some(string(item, key)?),
+                        "footer" => site.footer = Some(string(item, key)?),
+                        "disclaimer" => site.disclaimer = Some(string(item, key)?),
+                        "placeholders" => {
+                            let placeholders = item.as_table_like().ok_or_else(|| {
+                                anyhow!("[sites.{}.placeholders] must be a table", id)
+                            })?;
+                            for (name, value) in placeholders.iter() {
+                                site.placeholders
+                                    .push((name.to_string(), string(value, name)?));
+                            }
+                        }
+                        _ => bail!("Unknown site setting `{}`", key),
+                    }
+                }
+                config.sites.push((id.to_string(), site));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Makes the site `id` the one the outputs are written for: its
+    /// settings replace the document-wide ones and its name and
+    /// placeholders take precedence over `[placeholders]`.
+    pub fn select_site(&mut self, id: &str) -> Result<()> {
+        let Some((_, site)) = self.sites.iter().find(|(name, _)| name == id) else {
+            if self.sites.is_empty() {
+                bail!("Unknown site `{}`: rtemplates.toml has no [sites]", id);
+            }
+            let ids: Vec<&str> = self.sites.iter().map(|(name, _)| name.as_str()).collect();
+            bail!("Unknown site `{}`; expected one of {}", id, ids.join(", "));
+        };
+        let site = site.clone();
+        for (slot, value) in [
+            (&mut self.reference_docx, site.reference),
+            (&mut self.page_header, site.header),
+            (&mut self.page_footer, site.footer),
+            (&mut self.disclaimer, site.disclaimer),
+        ] {
+            if value.is_some() {
+                *slot = value;
+            }
+        }
+        for (name, value) in [
+            ("institution", site.name),
+            ("institution_address", site.address),
+            ("institution_department", site.department),
+        ] {
+            if let Some(value) = value {
+                self.placeholders.push((name.to_string(), value));
+            }
+        }
+        self.placeholders.extend(site.placeholders);
+        self.site = Some(id.to_string());
+        Ok(())
+    }
+
+    /// The value of the `{{name}}` placeholder: the selected site's, else
+    /// the one under `[placeholders]`.
+    pub fn placeholder(&self, name: &str) -> Option<&str> {
+        self.placeholders
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The selected site (see `select_site`).
+    pub fn selected_site(&self) -> Option<&Site> {
+        let id = self.site.as_deref()?;
+        self.sites
+            .iter()
+            .find(|(name, _)| name == id)
+            .map(|(_, site)| site)
+    }
+
+    /// Reads `path`; a missing file yields the defaults.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read config {}", path.display()))?;
+        Config::parse(&source).with_context(|| format!("Invalid config {}", path.display()))
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Makes `config` the settings returned by `get`. Only the first call has
+/// an effect.
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// Loads `path` (see `Config::load`) and makes it current.
+pub fn init_from_file(path: &Path) -> Result<()> {
+    init(Config::load(path)?);
+    Ok(())
+}
+
+/// The current settings: the ones passed to `init`, or the defaults.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_override_the_defaults_and_typos_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            Config::load(&dir.path().join("rtemplates.toml")).unwrap(),
+            Config::default()
+        );
+
+        let path = dir.path().join("rtemplates.toml");
+        fs::write(
+            &path,
+            "[directories]\nmarkdown = \"laudos/md\"\ndocx = \"laudos/docx\"\n\n\
+             [document]\nfont = \"Calibri\"\nfont_size = 11\nlanguage = \"en-US\"\n\n\
+             [section_keywords]\nimpressao = [\"Conclusão\"]\n\n\
+             [section_keywords.en]\nimpressao = [\"final impression\"]\n",
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.directories.markdown, "laudos/md");
+        assert_eq!(config.directories.docx, "laudos/docx");
+        assert_eq!(config.directories.txt, Directories::default().txt);
+        assert_eq!(
+            (
+                config.font.as_str(),
+                config.font_size_pt,
+                config.language.as_str()
+            ),
+            ("Calibri", 11, "en-US")
+        );
+        assert_eq!(
+            config.section_keywords,
+            [
+                (
+                    "final impression".to_string(),
+                    SectionKind::Impressao,
+                    Some(Language::English)
+                ),
+                ("conclusão".to_string(), SectionKind::Impressao, None),
+            ]
+        );
+
+        for invalid in [
+            "[directories]\nmarkdwon = \"md\"\n",
+            "[document]\nfont_size = 0\n",
+            "[document]\nfont = 12\n",
+            "[section_keywords]\nconclusao = [\"x\"]\n",
+            "directories = 1\n",
+        ] {
+            assert!(Config::parse(invalid).is_err(), "{}", invalid);
+        }
+        fs::write(&path, "[document]\nfont_size = 0\n").unwrap();
+        let error = format!("{:#}", Config::load(&path).unwrap_err());
+        assert!(error.starts_with("Invalid config"), "{error}");
+    }
+
+    #[test]
+    fn a_site_replaces_the_document_settings() {
+        let mut config = Config::parse(
+            "[docx]\nheader = \"Laudos\"\nfooter = \"Rodapé\"\n\n\
+             [placeholders]\ninstitution = \"Grupo\"\nphone = \"0000\"\n\n\
+             [sites.clinica_a]\nname = \"Clínica A\"\nheader = \"**Clínica A**\"\n\n\
+             [sites.clinica_a.placeholders]\nphone = \"(11) 3333-0000\"\n\n\
+             [sites.clinica_b]\nfooter = \"Clínica B<br>Tel. 2222\"\n",
+        )
+        .unwrap();
+        assert!(config.selected_site().is_none());
+        let error = config.clone().select_site("clinica_c").unwrap_err();
+        assert!(
+            error.to_string().contains("clinica_a, clinica_b"),
+            "{error}"
+        );
+        assert!(Config::parse("[sites.x]\nphone = \"1\"\n").is_err());
+
+        config.select_site("clinica_a").unwrap();
+        assert_eq!(config.site.as_deref(), Some("clinica_a"));
+        assert_eq!(config.page_header.as_deref(), Some("**Clínica A**"));
+        assert_eq!(config.page_footer.as_deref(), Some("Rodapé"));
+        assert_eq!(config.placeholder("institution"), Some("Clínica A"));
+        assert_eq!(config.placeholder("phone"), Some("(11) 3333-0000"));
+        assert_eq!(
+            config.selected_site().unwrap().name.as_deref(),
+            Some("Clínica A")
+        );
+    }
+}
@@ -0,0 +1,817 @@
+//! Format-level dispatch shared by the CLI and the watch loop.
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use log::{error, info};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tempfile::TempDir;
+
+use crate::aliases;
+use crate::cache::Cache;
+use crate::canonical::Canonical;
+use crate::exports;
+use crate::files::{display_name, list_files, output_path, FileFilter};
+use crate::fill::FillOptions;
+use crate::git;
+use crate::{
+    config, convert_to_docx, convert_to_html, convert_to_json, convert_to_latex,
+    convert_to_markdown, convert_to_mrrt, convert_to_odt, convert_to_pdf, convert_to_rtf,
+    convert_to_sr, convert_to_txt, convert_txt_to_markdown, history, logging,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// DOCX (RTF files in the same folder are also read as input)
+    Docx,
+    /// Markdown
+    Md,
+    /// Plain text
+    Txt,
+    /// PDF (output only)
+    Pdf,
+    /// HTML page (.html/.htm); read from legacy RIS exports, written from Markdown
+    Html,
+    /// OpenDocument text (LibreOffice)
+    Odt,
+    /// Rich Text Format (output only; RTF sources are read with --from docx)
+    Rtf,
+    /// LaTeX, for teaching files and handouts (.tex, output only)
+    Latex,
+    /// Named report sections as JSON (output only)
+    Json,
+    /// IHE MRRT template (.html/.htm), as published on radreport.org
+    Mrrt,
+    /// DICOM Basic Text SR (.dcm, output only, experimental)
+    Sr,
+}
+
+impl Format {
+    /// Folder for this format, from `rtemplates.toml` or the built-in name.
+    pub fn default_dir(self) -> &'static str {
+        let dirs = &config::get().directories;
+        match self {
+            Format::Docx => &dirs.docx,
+            Format::Md => &dirs.markdown,
+            Format::Txt => &dirs.txt,
+            Format::Pdf => &dirs.pdf,
+            Format::Html => &dirs.html,
+            Format::Odt => &dirs.odt,
+            Format::Rtf => &dirs.rtf,
+            Format::Latex => &dirs.latex,
+            Format::Json => &dirs.json,
+            Format::Mrrt => &dirs.mrrt,
+            Format::Sr => &dirs.sr,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Docx => "docx",
+            Format::Md => "md",
+            Format::Txt => "txt",
+            Format::Pdf => "pdf",
+            Format::Html => "html",
+            Format::Odt => "odt",
+            Format::Rtf => "rtf",
+            Format::Latex => "tex",
+            Format::Json => "json",
+            Format::Mrrt => "html",
+            Format::Sr => "dcm",
+        }
+    }
+
+    /// Name used in messages.
+    pub fn label(self) -> String {
+        match self {
+            Format::Latex => "LaTeX".to_string(),
+            Format::Mrrt => "MRRT".to_string(),
+            Format::Sr => "DICOM SR".to_string(),
+            _ => self.extension().to_uppercase(),
+        }
+    }
+
+    /// Formats that can be produced but never read.
+    pub fn output_only(self) -> bool {
+        matches!(
+            self,
+            Format::Pdf | Format::Rtf | Format::Latex | Format::Json | Format::Sr
+        )
+    }
+
+    /// Formats rendered from Markdown's layout rules.
+    pub fn rendered_from_markdown(self) -> bool {
+        matches!(
+            self,
+            Format::Pdf
+                | Format::Html
+                | Format::Rtf
+                | Format::Latex
+                | Format::Json
+                | Format::Mrrt
+                | Format::Sr
+        )
+    }
+}
+
+fn unsupported(from: Format, to: Format) -> anyhow::Error {
+    if from == to {
+        anyhow::anyhow!("Source and target formats are the same")
+    } else if from.output_only() {
+        anyhow::anyhow!("{} is an output-only format", from.label())
+    } else if to.rendered_from_markdown() {
+        anyhow::anyhow!("{} export reads Markdown; use --from md", to.label())
+    } else if to == Format::Md {
+        anyhow::anyhow!("{} -> MD is not supported", from.label())
+    } else {
+        let from = from.label();
+        anyhow::anyhow!(
+            "{} -> {} is not supported; convert {} -> Markdown first",
+            from,
+            to.label(),
+            from
+        )
+    }
+}
+
+/// Settings for a batch conversion.
+#[derive(Debug, Default, Clone)]
+pub struct BatchOptions {
+    /// Content-hash cache; when set, unchanged sources are skipped.
+    pub cache_path: Option<PathBuf>,
+    /// Reconvert everything even when the cache says it is up to date.
+    pub force: bool,
+    /// Variables and partials for filling Markdown sources.
+    pub fill: FillOptions,
+    /// Which source files to convert, by file name.
+    pub files: FileFilter,
+    /// Stop at the first file that fails instead of converting the rest.
+    pub fail_fast: bool,
+    /// Template history each Markdown source converted is recorded in.
+    pub history_dir: Option<PathBuf>,
+    /// Index whose checksums existing targets must still have to be
+    /// overwritten (`--canonical`, see `canonical`).
+    pub canonical_index: Option<PathBuf>,
+    /// Git ref: only sources changed since it are converted (`--changed`).
+    pub changed_since: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Converted,
+    /// The cache found the target up to date.
+    Unchanged,
+    Failed,
+}
+
+/// What happened to one source file in `convert_folder`.
+#[derive(Clone, Debug, Serialize)]
+pub struct FileRecord {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub status: FileStatus,
+    pub duration_ms: f64,
+    /// Warnings logged while converting the file.
+    pub warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Copies of the target under the template's aliases.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<PathBuf>,
+}
+
+/// Outcome of `convert_folder`, one record per selected source.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BatchReport {
+    pub converted: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    pub duration_ms: f64,
+    pub files: Vec<FileRecord>,
+}
+
+impl BatchReport {
+    fn push(&mut self, record: FileRecord) {
+        match record.status {
+            FileStatus::Converted => self.converted += 1,
+            FileStatus::Unchanged => self.unchanged += 1,
+            FileStatus::Failed => self.failed += 1,
+        }
+        self.files.push(record);
+    }
+
+    /// The records of the files that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &FileRecord> {
+        self.files
+            .iter()
+            .filter(|record| record.status == FileStatus::Failed)
+    }
+}
+
+fn millis(since: Instant) -> f64 {
+    (since.elapsed().as_secs_f64() * 1e6).round() / 1e3
+}
+
+/// Source files of format `from` in `input`. DOCX input also picks up `.rtf`,
+/// HTML and MRRT input also `.htm`.
+pub fn source_files(from: Format, input: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = list_files(input, from.extension())?;
+    let extra = match from {
+        Format::Docx => Some("rtf"),
+        Format::Html | Format::Mrrt => Some("htm"),
+        _ => None,
+    };
+    if let Some(extension) = extra {
+        files.extend(list_files(input, extension)?);
+        files.sort();
+    }
+    Ok(files)
+}
+
+fn check_batch(from: Format, to: Format, input: &Path) -> Result<()> {
+    if !input.exists() {
+        bail!("Source folder not found: {}", input.display());
+    }
+    if !matches!(
+        (from, to),
+        (Format::Docx, Format::Md)
+            | (Format::Docx, Format::Txt)
+            | (Format::Md, Format::Docx)
+            | (Format::Md, Format::Txt)
+            | (Format::Md, Format::Pdf)
+            | (Format::Md, Format::Html)
+            | (Format::Html, Format::Md)
+            | (Format::Html, Format::Txt)
+            | (Format::Md, Format::Odt)
+            | (Format::Md, Format::Rtf)
+            | (Format::Md, Format::Latex)
+            | (Format::Md, Format::Json)
+            | (Format::Md, Format::Mrrt)
+            | (Format::Md, Format::Sr)
+            | (Format::Mrrt, Format::Md)
+            | (Format::Mrrt, Format::Txt)
+            | (Format::Odt, Format::Md)
+            | (Format::Odt, Format::Txt)
+            | (Format::Txt, Format::Md)
+    ) {
+        return Err(unsupported(from, to));
+    }
+    Ok(())
+}
+
+/// The sources in `input` that `options.files` selects. When importing
+/// into Markdown, the copies written for aliases of the templates already in
+/// `output` are left out.
+fn selected_sources(
+    from: Format,
+    to: Format,
+    input: &Path,
+    output: &Path,
+    options: &BatchOptions,
+) -> Result<Vec<PathBuf>> {
+    let aliases = if to == Format::Md {
+        aliases::in_folder(output)?
+    } else {
+        Default::default()
+    };
+    let changed = match &options.changed_since {
+        Some(reference) => changed_sources(from, input, reference, options)?,
+        None => None,
+    };
+    let mut sources = Vec::new();
+    for source in source_files(from, input)? {
+        let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+        let relative = source.strip_prefix(input).unwrap_or(&source);
+        if !aliases.contains_key(stem.as_ref())
+            && options.files.matches(relative)
+            && changed
+                .as_ref()
+                .is_none_or(|changed| changed.contains(relative))
+            && (from != Format::Md || exports::is_exported(&source, to)?)
+        {
+            sources.push(source);
+        }
+    }
+    Ok(sources)
+}
+
+/// The files in `input` changed in git since `reference`; `None` when a
+/// partial or phrase changed, as any Markdown source may include it.
+fn changed_sources(
+    from: Format,
+    input: &Path,
+    reference: &str,
+    options: &BatchOptions,
+) -> Result<Option<BTreeSet<PathBuf>>> {
+    if from == Format::Md {
+        for dir in [&options.fill.partials_dir, &options.fill.phrases_dir] {
+            if dir.is_dir() && !git::changed_files(dir, reference)?.is_empty() {
+                info!(
+                    "{} changed since {}: converting every source",
+                    dir.display(),
+                    reference
+                );
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(git::changed_files(input, reference)?))
+}
+
+/// Cache key context: the variables and partials that fill Markdown sources.
+fn cache_context(from: Format, options: &BatchOptions) -> Result<String> {
+    if from == Format::Md {
+        options.fill.cache_context()
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// Converts every file of format `from` in `input` into `to` under `output`.
+/// A file that fails is reported and recorded in the returned report while
+/// the others are still converted, unless `options.fail_fast` is set.
+pub fn convert_folder(
+    from: Format,
+    to: Format,
+    input: &Path,
+    output: &Path,
+    options: &BatchOptions,
+) -> Result<BatchReport> {
+    check_batch(from, to, input)?;
+
+    let mut cache = options.cache_path.as_deref().map(Cache::load);
+    let mut canonical = options
+        .canonical_index
+        .as_deref()
+        .map(Canonical::load)
+        .transpose()?;
+    let context = cache_context(from, options)?;
+    let mut report = BatchReport::default();
+    let started = Instant::now();
+
+    for source in selected_sources(from, to, input, output, options)? {
+        let target = output_path(output, &source, to.extension());
+        let file_started = Instant::now();
+        let mut record = FileRecord {
+            source: source.clone(),
+            target: target.clone(),
+            status: FileStatus::Converted,
+            duration_ms: 0.0,
+            warnings: Vec::new(),
+            error: None,
+            aliases: Vec::new(),
+        };
+        record_history(from, to, &source, options)?;
+        if let Some(cache) = &cache {
+            if !options.force && cache.is_fresh(&source, &target, &context)? {
+                record.status = FileStatus::Unchanged;
+                if from == Format::Md {
+                    record.aliases = aliases::write(&source, &target)?;
+                }
+                record.duration_ms = millis(file_started);
+                report.push(record);
+                continue;
+            }
+        }
+
+        let (result, warnings) = logging::capture_warnings(|| {
+            if let Some(canonical) = &canonical {
+                canonical.check(&source, &target)?;
+            }
+            convert_one(from, to, &source, output, &options.fill)
+        });
+        record.duration_ms = millis(file_started);
+        record.warnings = warnings;
+        if let Err(error) = result {
+            if options.fail_fast {
+                if let Some(cache) = &cache {
+                    cache.save()?;
+                }
+                if let Some(canonical) = &canonical {
+                    canonical.save()?;
+                }
+                return Err(error.context(format!("Cannot convert {}", source.display())));
+            }
+            error!("✗ {}: {:#}", display_name(&source), error);
+            record.status = FileStatus::Failed;
+            record.error = Some(format!("{:#}", error));
+            report.push(record);
+            continue;
+        }
+        info!("✓ {} -> {}", display_name(&source), display_name(&target));
+        if from == Format::Md {
+            record.aliases = aliases::targets(&source, &target)?;
+        }
+        report.push(record);
+
+        if let Some(cache) = &mut cache {
+            cache.record(&source, &target, &context)?;
+        }
+        if let Some(canonical) = &mut canonical {
+            canonical.record(&source, &target)?;
+        }
+    }
+    report.duration_ms = millis(started);
+
+    if let Some(cache) = &cache {
+        cache.save()?;
+    }
+    if let Some(canonical) = &canonical {
+        canonical.save()?;
+    }
+    if report.failed == 0 {
+        info!(
+            "\n✓ {} converted, {} unchanged, files in {}",
+            report.converted,
+            report.unchanged,
+            output.display()
+        );
+    } else {
+        info!(
+            "\n✗ {} converted, {} unchanged, {} failed, files in {}",
+            report.converted,
+            report.unchanged,
+            report.failed,
+            output.display()
+        );
+        info!("\nFailed:");
+        let width = report
+            .failures()
+            .map(|failure| display_name(&failure.source).chars().count())
+            .max()
+            .unwrap_or(0);
+        for failure in report.failures() {
+            info!(
+                "  {:<width$}  {}",
+                display_name(&failure.source),
+                failure.error.as_deref().unwrap_or_default()
+            );
+        }
+    }
+    Ok(report)
+}
+
+/// A file `convert_folder` would write.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedWrite {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    /// The target exists and its contents would change.
+    pub overwrites: bool,
+}
+
+/// Records a Markdown `source` of a conversion to `to` in the template
+/// history, when `options` names one. Other sources are generated from the
+/// Markdown, or imported into it, and are not recorded.
+pub fn record_history(
+    from: Format,
+    to: Format,
+    source: &Path,
+    options: &BatchOptions,
+) -> Result<()> {
+    if let (Some(dir), Format::Md) = (&options.history_dir, from) {
+        let message = format!("convert --to {}", to.extension());
+        history::record(dir, source, Some(&message))?;
+    }
+    Ok(())
+}
+
+/// What `convert_folder` would write, without touching `output`. Each
+/// source the cache does not skip is converted into a temporary folder, and
+/// targets whose contents would stay the same are left out.
+pub fn plan_folder(
+    from: Format,
+    to: Format,
+    input: &Path,
+    output: &Path,
+    options: &BatchOptions,
+) -> Result<Vec<PlannedWrite>> {
+    check_batch(from, to, input)?;
+
+    let cache = options.cache_path.as_deref().map(Cache::load);
+    let context = cache_context(from, options)?;
+    let scratch = TempDir::new()?;
+    let mut planned = Vec::new();
+
+    for source in selected_sources(from, to, input, output, options)? {
+        let target = output_path(output, &source, to.extension());
+        let aliases = if from == Format::Md {
+            aliases::targets(&source, &target)?
+        } else {
+            Vec::new()
+        };
+        let fresh = match &cache {
+            Some(cache) => !options.force && cache.is_fresh(&source, &target, &context)?,
+            None => false,
+        };
+        // What the target holds after the conversion, when known.
+        let mut contents = None;
+        if fresh {
+            contents = fs::read(&target).ok();
+        } else {
+            let overwrites = target.exists();
+            let mut changes = true;
+            if overwrites || !aliases.is_empty() {
+                convert_one(from, to, &source, scratch.path(), &options.fill)?;
+                let result = fs::read(output_path(scratch.path(), &source, to.extension()))?;
+                changes = !overwrites || result != fs::read(&target)?;
+                contents = Some(result);
+            }
+            if changes {
+                planned.push(PlannedWrite {
+                    source: source.clone(),
+                    target: target.clone(),
+                    overwrites,
+                });
+            }
+        }
+        for alias in aliases {
+            let overwrites = alias.exists();
+            if overwrites && fs::read(&alias).ok() == contents {
+                continue;
+            }
+            planned.push(PlannedWrite {
+                source: source.clone(),
+                target: alias,
+                overwrites,
+            });
+        }
+    }
+    Ok(planned)
+}
+
+/// Converts a single `source` file of format `from` into `output_dir`;
+/// Markdown sources are filled with `fill` first, and their output is
+/// copied under their aliases (see `aliases`).
+pub fn convert_one(
+    from: Format,
+    to: Format,
+    source: &Path,
+    output_dir: &Path,
+    fill: &FillOptions,
+) -> Result<()> {
+    fs::create_dir_all(output_dir)?;
+    let target = output_path(output_dir, source, to.extension());
+
+    match (from, to) {
+        (Format::Md, Format::Docx) => convert_to_docx::convert_file(source, &target, fill)?,
+        (Format::Md, Format::Pdf) => convert_to_pdf::convert_file(source, &target, fill)?,
+        (Format::Md, Format::Html) => convert_to_html::convert_file(source, &target, fill)?,
+        (Format::Md, Format::Odt) => convert_to_odt::convert_file(source, &target, fill)?,
+        (Format::Md, Format::Json) => convert_to_json::convert_file(source, &target, fill)?,
+        (Format::Md, Format::Mrrt) => convert_to_mrrt::convert_file(source, &target, fill)?,
+        // Images in a Word document are saved next to its Markdown.
+        (Format::Docx, Format::Md) if !is_rtf(source) => {
+            convert_to_markdown::convert_docx_file(source, &target)?
+        }
+        (Format::Md, Format::Sr) => {
+            let metadata = convert_to_sr::SrMetadata::from_config(config::get());
+            convert_to_sr::convert_file(source, &target, &metadata, fill)?;
+        }
+        _ => fs::write(&target, render_text(from, to, source, fill)?)?,
+    }
+    if from == Format::Md {
+        aliases::write(source, &target)?;
+    }
+
+    Ok(())
+}
+
+/// Converts one document read from `input` and writes the result to
+/// `output`, going through a temporary folder so every format pair that
+/// `convert_one` supports works on streams as well. `name` stands in for the
+/// file stem, which some writers use as the document title.
+pub fn convert_stream(
+    from: Format,
+    to: Format,
+    name: &str,
+    input: &mut impl Read,
+    output: &mut impl Write,
+    fill: &FillOptions,
+) -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let source = tmp_dir
+        .path()
+        .join(format!("{}.{}", name, from.extension()));
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    fs::write(&source, bytes)?;
+
+    let output_dir = tmp_dir.path().join("out");
+    convert_one(from, to, &source, &output_dir, fill)?;
+    output.write_all(&fs::read(output_path(
+        &output_dir,
+        &source,
+        to.extension(),
+    ))?)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// The Markdown or TXT that `convert_one` writes for `source`.
+pub fn render_text(from: Format, to: Format, source: &Path, fill: &FillOptions) -> Result<String> {
+    Ok(match (from, to) {
+        (Format::Docx | Format::Html | Format::Odt, Format::Md) => read_as_markdown(source)?,
+        (Format::Mrrt, Format::Md) => convert_to_markdown::convert_mrrt_to_markdown(source)?,
+        (Format::Md, Format::Txt) => convert_to_txt::render_md_text(source, fill)?,
+        (Format::Md, Format::Rtf) => convert_to_rtf::render_md_rtf(source, fill)?,
+        (Format::Md, Format::Latex) => convert_to_latex::render_md_latex(source, fill)?,
+        (Format::Docx | Format::Html | Format::Odt, Format::Txt) => {
+            convert_to_txt::clean_markdown_text(&read_as_markdown(source)?)
+        }
+        (Format::Mrrt, Format::Txt) => convert_to_txt::clean_markdown_text(
+            &convert_to_markdown::convert_mrrt_to_markdown(source)?,
+        ),
+        (Format::Txt, Format::Md) => convert_txt_to_markdown::render_txt_file(source)?,
+        _ => return Err(unsupported(from, to)),
+    })
+}
+
+/// The source format of a template file, from its extension. `.html` is
+/// read as HTML; MRRT files need `--from mrrt`.
+pub fn format_of(path: &Path) -> Option<Format> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    Some(match extension.as_str() {
+        "docx" | "rtf" => Format::Docx,
+        "md" => Format::Md,
+        "txt" => Format::Txt,
+        "html" | "htm" => Format::Html,
+        "odt" => Format::Odt,
+        _ => return None,
+    })
+}
+
+fn is_rtf(source: &Path) -> bool {
+    source.extension().and_then(|s| s.to_str()) == Some("rtf")
+}
+
+/// Reads a DOCX, RTF, HTML or ODT source as Markdown, picking the reader by extension.
+fn read_as_markdown(source: &Path) -> Result<String> {
+    match source.extension().and_then(|s| s.to_str()) {
+        Some("rtf") => convert_to_markdown::convert_rtf_to_markdown(source),
+        Some("html" | "htm") => convert_to_markdown::convert_html_to_markdown(source),
+        Some("odt") => convert_to_markdown::convert_odt_to_markdown(source),
+        _ => convert_to_markdown::convert_docx_to_markdown(source),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_render_as_the_text_their_files_would_hold() {
+        assert_eq!(format_of(Path::new("A/TC.RTF")), Some(Format::Docx));
+        assert_eq!(format_of(Path::new("TC.htm")), Some(Format::Html));
+        assert_eq!(format_of(Path::new("TC.dcm")), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let md = dir.path().join("TC.md");
+        fs::write(
+            &md,
+            "**TC DE TÓRAX**\n\n**Impressão:**\n**Sem alterações.**\n",
+        )
+        .unwrap();
+        let fill = FillOptions::default();
+        let text = render_text(Format::Md, Format::Txt, &md, &fill).unwrap();
+        assert_eq!(text, "TC DE TÓRAX\n\nImpressão:\nSem alterações.\n");
+
+        let out = dir.path().join("out");
+        convert_one(Format::Md, Format::Txt, &md, &out, &fill).unwrap();
+        assert_eq!(fs::read_to_string(out.join("TC.txt")).unwrap(), text);
+        assert!(render_text(Format::Md, Format::Pdf, &md, &fill).is_err());
+    }
+
+    #[test]
+    fn a_dry_run_plans_only_the_writes_that_change_a_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, output) = (dir.path().join("md"), dir.path().join("txt"));
+        fs::create_dir_all(&input).unwrap();
+        fs::create_dir_all(&output).unwrap();
+        for name in ["Novo", "Igual", "Editado"] {
+            fs::write(
+                input.join(format!("{}.md", name)),
+                format!("**{}**\n", name),
+            )
+            .unwrap();
+        }
+        fs::write(output.join("Igual.txt"), "Igual\n").unwrap();
+        fs::write(output.join("Editado.txt"), "Texto antigo\n").unwrap();
+
+        let planned = plan_folder(
+            Format::Md,
+            Format::Txt,
+            &input,
+            &output,
+            &BatchOptions::default(),
+        )
+        .unwrap();
+        let planned: Vec<(&str, bool)> = planned
+            .iter()
+            .map(|write| {
+                (
+                    write.target.file_name().unwrap().to_str().unwrap(),
+                    write.overwrites,
+                )
+            })
+            .collect();
+        assert_eq!(planned, [("Editado.txt", true), ("Novo.txt", false)]);
+        assert!(!output.join("Novo.txt").exists());
+        assert_eq!(
+            fs::read_to_string(output.join("Editado.txt")).unwrap(),
+            "Texto antigo\n"
+        );
+    }
+
+    #[test]
+    fn a_failed_file_is_reported_without_stopping_the_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, output) = (dir.path().join("docx"), dir.path().join("md"));
+        fs::create_dir_all(&input).unwrap();
+        fs::write(input.join("A Corrompido.docx"), "not a zip").unwrap();
+        let md = dir.path().join("B Joelho.md");
+        fs::write(&md, "**RM DO JOELHO**\n").unwrap();
+        convert_one(
+            Format::Md,
+            Format::Docx,
+            &md,
+            &input,
+            &FillOptions::default(),
+        )
+        .unwrap();
+
+        let report = convert_folder(
+            Format::Docx,
+            Format::Md,
+            &input,
+            &output,
+            &BatchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!((report.converted, report.failed), (1, 1));
+        let failures: Vec<&FileRecord> = report.failures().collect();
+        assert_eq!(failures[0].source, input.join("A Corrompido.docx"));
+        assert!(failures[0].error.is_some());
+        assert!(output.join("B Joelho.md").exists());
+
+        fs::remove_dir_all(&output).unwrap();
+        let fail_fast = BatchOptions {
+            fail_fast: true,
+            ..Default::default()
+        };
+        let error =
+            convert_folder(Format::Docx, Format::Md, &input, &output, &fail_fast).unwrap_err();
+        assert!(error.to_string().contains("A Corrompido.docx"), "{error}");
+        assert!(!output.join("B Joelho.md").exists());
+    }
+
+    #[test]
+    fn streams_convert_through_the_file_converters() {
+        let fill = FillOptions::default();
+        let mut output = Vec::new();
+        let mut input = "**RM DO JOELHO**\n\nMenisco íntegro.\n".as_bytes();
+        convert_stream(
+            Format::Md,
+            Format::Txt,
+            "stdin",
+            &mut input,
+            &mut output,
+            &fill,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "RM DO JOELHO\n\nMenisco íntegro.\n"
+        );
+
+        // Binary outputs come through unchanged too.
+        let mut output = Vec::new();
+        let mut input = "**RM DO JOELHO**\n".as_bytes();
+        convert_stream(
+            Format::Md,
+            Format::Docx,
+            "stdin",
+            &mut input,
+            &mut output,
+            &fill,
+        )
+        .unwrap();
+        assert!(output.starts_with(b"PK"));
+
+        let mut input = "x".as_bytes();
+        assert!(convert_stream(
+            Format::Sr,
+            Format::Md,
+            "stdin",
+            &mut input,
+            &mut Vec::new(),
+            &fill
+        )
+        .is_err());
+    }
+}
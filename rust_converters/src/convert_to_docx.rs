@@ -0,0 +1,1598 @@
+use anyhow::Result;
+use docx_rust::content_type::{DefaultContentType, OverrideContentType};
+use docx_rust::document::{
+    AbstractNum, AbstractNumId, AvList, Blip, BlipFill, BodyContent, Break, BreakType, CNvPicPr,
+    CNvPr, ContinuationSeparator, DocPr, Drawing, Ext, Extent, FillRect, FootNote, FootNotes,
+    FooterReference, FootnoteRef, FootnoteReference, Graphic, GraphicData, GridColumn,
+    HeaderFooterReference, HeaderFooterReferenceType, HeaderReference, Hyperlink, Inline, Level,
+    LevelJustification, LevelStart, LevelText, MultiLevelType, NoteSeparator, Num, NumFmt,
+    Numbering, NvPicPr, Offset, PPr, Paragraph, ParagraphContent, Picture, PrstGeom, Run,
+    RunContent, SDTContent, SDTProperty, STDId, Separator, SpPr, Stretch, Table, TableCell,
+    TableRow, Xfrm, SDT,
+};
+use docx_rust::formatting::{
+    BorderStyle, BottomBorder, CharacterProperty, Fonts, Indent, InsideHorizonBorder,
+    InsideVerticalBorder, Justification, JustificationVal, LeftBorder, NumberingProperty,
+    ParagraphProperty, RightBorder, SectionProperty, Spacing, TableBorders, TableProperty,
+    TableWidth, TableWidthUnit, TopBorder, UnderlineStyle, VertAlign, VertAlignType,
+};
+use docx_rust::media::MediaType;
+use docx_rust::rels::{Relationship, Relationships, TargetMode};
+use docx_rust::styles::{Style, StyleType};
+use docx_rust::Docx;
+use hard_xml::XmlWrite;
+use log::warn;
+use regex::Regex;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::aliases;
+use crate::config::{self, ParagraphSpacing};
+use crate::convert::Format;
+use crate::convert_to_html::escape_html;
+use crate::docx_reference::{read_package, Reference};
+use crate::exports;
+use crate::files::{list_files, output_path};
+use crate::fill::FillOptions;
+use crate::footnotes::Footnote;
+use crate::front_matter::FrontMatter;
+use crate::images::ImageFormat;
+use crate::markdown::{
+    is_page_break, parse_inline, parse_list_item, strip_header_bold, Alignment, Block, Image,
+    InlineRun, ListItem, ListNesting, FOOTER_FONT_SIZE_PT,
+};
+use crate::template::{ReportTemplate, SectionKind};
+use crate::timestamp::Timestamp;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+// Usable width of an A4 page with default margins, in twentieths of a point.
+const TABLE_WIDTH_TWIPS: isize = 9000;
+// Border thickness in eighths of a point.
+const TABLE_BORDER_SIZE: isize = 4;
+// List levels Word supports, and the indent added per level in twips.
+const LIST_LEVELS: usize = 9;
+const LIST_INDENT_TWIPS: isize = 720;
+const LIST_HANGING_TWIPS: isize = 360;
+const BULLETS: [&str; 3] = ["•", "◦", "▪"];
+const HYPERLINK_STYLE_ID: &str = "Hyperlink";
+const HYPERLINK_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink";
+const HYPERLINK_COLOR: &str = "0563C1";
+const IMAGE_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+const DRAWINGML_NAMESPACE: &str = "http://schemas.openxmlformats.org/drawingml/2006/main";
+const PICTURE_NAMESPACE: &str = "http://schemas.openxmlformats.org/drawingml/2006/picture";
+// Image sizes are in EMU: 914400 per inch, so 9525 per pixel at 96 dpi.
+const EMU_PER_PIXEL: u64 = 9525;
+const EMU_PER_TWIP: u64 = 635;
+const FOOTNOTES_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.footnotes+xml";
+const NUMBERING_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml";
+const CORE_PROPERTIES_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties";
+const WORDML_NAMESPACE: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const RELATIONSHIPS_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const WORDPROCESSING_DRAWING_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing";
+
+/// The character properties of a run: the configured font unless a
+/// reference document sets it, and `font_size_pt` when given.
+fn run_property<'a>(relations: &Relations, font_size_pt: Option<i32>) -> CharacterProperty<'a> {
+    let mut prop = CharacterProperty::default();
+    if relations.reference_styles.is_none() {
+        prop = prop.fonts(Fonts::default().ascii(config::get().font.clone()));
+    }
+    if let Some(font_size_pt) = font_size_pt {
+        // In DOCX, font size is in half-points.
+        prop = prop.size((font_size_pt * 2) as isize);
+    }
+    prop
+}
+
+/// A footnote mark, raised: the reference in the text and the number
+/// starting the note.
+fn note_mark<'a>(
+    relations: &Relations,
+    font_size_pt: Option<i32>,
+    mark: RunContent<'a>,
+) -> Run<'a> {
+    let mut prop = run_property(relations, font_size_pt);
+    prop.vertical_align = Some(VertAlign {
+        value: Some(VertAlignType::Superscript),
+    });
+    Run::default().property(prop).push(mark)
+}
+
+/// Appends `run` to `para`. Without `font_size_pt` the size, and with a
+/// reference document the font too, are left to the styles. A reference to
+/// a note of `relations` is a footnote reference.
+fn append_run<'a>(
+    para: Paragraph<'a>,
+    relations: &mut Relations,
+    run: &InlineRun,
+    force_italic: bool,
+    font_size_pt: Option<i32>,
+) -> Paragraph<'a> {
+    // An image that cannot be embedded leaves its alt text.
+    let drawing = run
+        .image
+        .as_ref()
+        .and_then(|image| relations.drawing(image, &run.text));
+    if run.text.is_empty() && drawing.is_none() {
+        return para;
+    }
+    if let Some(id) = run
+        .note
+        .as_deref()
+        .and_then(|label| relations.note_id(label))
+    {
+        let reference = FootnoteReference {
+            supress_reference_mark: None,
+            id: Some(id.to_string().into()),
+        };
+        return para.push(note_mark(
+            relations,
+            font_size_pt,
+            RunContent::FootnoteReference(reference),
+        ));
+    }
+
+    let mut prop = run_property(relations, font_size_pt);
+    if run.bold {
+        prop = prop.bold(true);
+    }
+    if run.italic || force_italic {
+        prop = prop.italics(true);
+    }
+    if run.underline {
+        prop = prop.underline(UnderlineStyle::Single);
+    }
+    if run.link.is_some() {
+        prop = prop.style_id(HYPERLINK_STYLE_ID);
+    }
+
+    let content = Run::default().property(prop);
+    let content = match drawing {
+        Some(drawing) => content.push(drawing),
+        None => content.push_text(run.text.clone()),
+    };
+    match &run.link {
+        // `#name` links to a bookmark of the document itself.
+        Some(url) => match url.strip_prefix('#') {
+            Some(anchor) => para.push(
+                Hyperlink::default()
+                    .anchor(anchor.to_string())
+                    .content(content),
+            ),
+            None => para.push(
+                Hyperlink::default()
+                    .id(relations.link(url))
+                    .content(content),
+            ),
+        },
+        None => para.push(content),
+    }
+}
+
+/// The parts the document refers to, each with the relationship ID used
+/// for it: external link targets and embedded images.
+struct Relations {
+    /// Folder relative image sources are read from.
+    source_dir: PathBuf,
+    urls: Vec<String>,
+    images: Vec<EmbeddedImage>,
+    /// Images stored by the parts written before this one.
+    first_image: usize,
+    /// Drawings written so far; each needs its own ID.
+    drawings: usize,
+    /// The styles of the reference document, if any, which set the font
+    /// and body size.
+    reference_styles: Option<HashSet<String>>,
+    /// Labels of the footnotes the part refers to, by ID from 1.
+    notes: Vec<String>,
+}
+
+/// An image file stored in the package.
+struct EmbeddedImage {
+    source: String,
+    /// Path below `word/`, e.g. `media/image1.png`.
+    media_name: String,
+    format: ImageFormat,
+    pixels: (u32, u32),
+    data: Vec<u8>,
+}
+
+impl Relations {
+    fn new(source_dir: &Path) -> Self {
+        Relations {
+            source_dir: source_dir.to_path_buf(),
+            urls: Vec::new(),
+            images: Vec::new(),
+            first_image: 0,
+            drawings: 0,
+            reference_styles: None,
+            notes: Vec::new(),
+        }
+    }
+
+    /// The relations of another part of the same package, whose images
+    /// and drawings are numbered after this one's.
+    fn following(&self, source_dir: &Path) -> Self {
+        Relations {
+            first_image: self.first_image + self.images.len(),
+            drawings: self.drawings,
+            reference_styles: self.reference_styles.clone(),
+            ..Relations::new(source_dir)
+        }
+    }
+
+    fn note_id(&self, label: &str) -> Option<usize> {
+        Some(self.notes.iter().position(|known| known == label)? + 1)
+    }
+
+    fn link(&mut self, url: &str) -> String {
+        let index = match self.urls.iter().position(|known| known == url) {
+            Some(index) => index,
+            None => {
+                self.urls.push(url.to_string());
+                self.urls.len() - 1
+            }
+        };
+        format!("rIdLink{}", index + 1)
+    }
+
+    /// The index of the embedded copy of `source`, reading the file the
+    /// first time. Missing files and formats Word cannot show are reported
+    /// and left out.
+    fn image(&mut self, source: &str) -> Option<usize> {
+        if let Some(index) = self.images.iter().position(|image| image.source == source) {
+            return Some(index);
+        }
+        let path = self.source_dir.join(source);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                warn!("Image not embedded, {}: {}", path.display(), err);
+                return None;
+            }
+        };
+        let Some((format, pixels)) =
+            ImageFormat::sniff(&data).and_then(|format| Some((format, format.pixel_size(&data)?)))
+        else {
+            warn!(
+                "Image not embedded, {}: not a PNG, JPEG, GIF or BMP file",
+                path.display()
+            );
+            return None;
+        };
+        let number = self.first_image + self.images.len() + 1;
+        self.images.push(EmbeddedImage {
+            source: source.to_string(),
+            media_name: format!("media/image{}.{}", number, format.extension()),
+            format,
+            pixels,
+            data,
+        });
+        Some(self.images.len() - 1)
+    }
+
+    /// An inline drawing of `image`, sized as the Markdown says or, failing
+    /// that, at 96 dpi and no wider than the text.
+    fn drawing<'a>(&mut self, image: &Image, alt: &str) -> Option<Drawing<'a>> {
+        let index = self.image(&image.source)?;
+        let (px_width, px_height) = self.images[index].pixels;
+        let (cx, cy) = match (image.width, image.height) {
+            (Some(cx), Some(cy)) => (cx, cy),
+            (Some(cx), None) => (cx, cx * px_height as u64 / px_width as u64),
+            (None, Some(cy)) => (cy * px_width as u64 / px_height as u64, cy),
+            (None, None) => {
+                let cx = px_width as u64 * EMU_PER_PIXEL;
+                let cy = px_height as u64 * EMU_PER_PIXEL;
+                let max = TABLE_WIDTH_TWIPS as u64 * EMU_PER_TWIP;
+                if cx > max {
+                    (max, cy * max / cx)
+                } else {
+                    (cx, cy)
+                }
+            }
+        };
+        self.drawings += 1;
+        let id = self.drawings as isize;
+        let name = format!("Picture {}", id);
+        let descr = (!alt.is_empty()).then(|| alt.to_string().into());
+        let picture = Picture {
+            a: PICTURE_NAMESPACE.into(),
+            nv_pic_pr: NvPicPr {
+                c_nv_pr: Some(CNvPr {
+                    id: Some(0),
+                    name: Some(name.clone().into()),
+                    descr: descr.clone(),
+                }),
+                c_nv_pic_pr: Some(CNvPicPr {}),
+            },
+            fill: BlipFill {
+                blip: Blip {
+                    embed: format!("rIdImage{}", index + 1).into(),
+                    cstate: None,
+                },
+                stretch: Some(Stretch {
+                    fill_rect: Some(FillRect {}),
+                }),
+            },
+            sp_pr: SpPr {
+                xfrm: Some(Xfrm {
+                    offset: Some(Offset {
+                        x: Some(0),
+                        y: Some(0),
+                    }),
+                    ext: Some(Ext {
+                        cx: Some(cx as isize),
+                        cy: Some(cy as isize),
+                    }),
+                }),
+                prst_geom: Some(PrstGeom {
+                    prst: Some("rect".into()),
+                    av_lst: Some(AvList {}),
+                }),
+            },
+        };
+        Some(Drawing {
+            anchor: None,
+            inline: Some(Inline {
+                extent: Some(Extent { cx, cy }),
+                doc_property: DocPr {
+                    id: Some(id),
+                    name: Some(name.into()),
+                    descr,
+                },
+                graphic: Some(Graphic {
+                    a: DRAWINGML_NAMESPACE.into(),
+                    data: GraphicData {
+                        uri: PICTURE_NAMESPACE.into(),
+                        children: vec![picture],
+                    },
+                }),
+                ..Default::default()
+            }),
+        })
+    }
+
+    /// The relationships of the links and images, as the part's `.rels`
+    /// file lists them.
+    fn relationships<'a>(&self) -> Vec<Relationship<'a>> {
+        let links = self
+            .urls
+            .iter()
+            .enumerate()
+            .map(|(index, url)| Relationship {
+                id: format!("rIdLink{}", index + 1).into(),
+                target: url.clone().into(),
+                ty: HYPERLINK_RELATIONSHIP.into(),
+                target_mode: Some(TargetMode::External),
+            });
+        let images = self
+            .images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| Relationship {
+                id: format!("rIdImage{}", index + 1).into(),
+                target: image.media_name.clone().into(),
+                ty: IMAGE_RELATIONSHIP.into(),
+                target_mode: None,
+            });
+        links.chain(images).collect()
+    }
+}
+
+/// The `Hyperlink` character style Word gives link text: blue, underlined.
+fn hyperlink_style<'a>() -> Style<'a> {
+    Style::new(StyleType::Character, HYPERLINK_STYLE_ID)
+        .name("Hyperlink")
+        .character(
+            CharacterProperty::default()
+                .color(HYPERLINK_COLOR)
+                .underline(UnderlineStyle::Single),
+        )
+}
+
+/// ID of the built-in paragraph style for a level-`level` heading.
+fn heading_style_id(level: u8) -> String {
+    format!("Heading{}", level)
+}
+
+/// The `Heading N` style definition, so Word lists the paragraph as a
+/// heading and the import maps it back to the same `#` level.
+fn heading_style<'a>(level: u8) -> Style<'a> {
+    Style::new(StyleType::Paragraph, heading_style_id(level)).name(format!("heading {}", level))
+}
+
+/// The `w:spacing` of `settings`, if it sets anything. Lines are counted
+/// in 240ths of single spacing and paragraph spacing in twips.
+fn spacing(settings: &ParagraphSpacing) -> Option<Spacing> {
+    if *settings == ParagraphSpacing::default() {
+        return None;
+    }
+    let twips = |pt: f64| (pt * 20.0).round() as isize;
+    Some(Spacing {
+        line: settings.line.map(|line| (line * 240.0).round() as isize),
+        before: settings.before_pt.map(twips),
+        after: settings.after_pt.map(twips),
+        ..Spacing::default()
+    })
+}
+
+/// Builds a paragraph from inline Markdown, with the runs `parse_inline`
+/// finds and their links and images registered in `relations`. `bold`
+/// sets the initial bold state (used for headings and table headers);
+/// `style_id` names the paragraph style, if any. Line and paragraph
+/// spacing are the `[docx]` settings.
+fn markdown_paragraph<'a>(
+    relations: &mut Relations,
+    text: &str,
+    alignment: Alignment,
+    bold: bool,
+    force_italic: bool,
+    font_size_pt: i32,
+    style_id: Option<String>,
+) -> Paragraph<'a> {
+    let justification_val = match alignment {
+        Alignment::Center => JustificationVal::Center,
+        Alignment::Justify => JustificationVal::Both,
+        Alignment::Left => JustificationVal::Left,
+    };
+
+    // A reference document's styles size body text and the headings it
+    // defines.
+    let styled = relations
+        .reference_styles
+        .as_ref()
+        .is_some_and(|ids| match &style_id {
+            Some(id) => ids.contains(id),
+            None => font_size_pt == config::get().font_size_pt,
+        });
+    let font_size_pt = (!styled).then_some(font_size_pt);
+    let mut para_prop =
+        ParagraphProperty::default().justification(Justification::from(justification_val));
+    if let Some(id) = style_id {
+        para_prop = para_prop.style_id(id);
+    }
+    if let Some(spacing) = spacing(&config::get().paragraph_spacing) {
+        para_prop = para_prop.spacing(spacing);
+    }
+    let mut para = Paragraph::default().property(para_prop);
+
+    if text.is_empty() {
+        // Empty paragraph
+        return para.push_text(String::new());
+    }
+
+    for run in parse_inline(text, bold) {
+        para = append_run(para, relations, &run, force_italic, font_size_pt);
+    }
+    para
+}
+
+/// The numbering definitions for the Markdown lists of one document. Each
+/// run of consecutive list items is a list of its own; a level of it is
+/// bulleted or numbered, and starts from, as its first item says.
+#[derive(Default)]
+struct Lists {
+    nesting: ListNesting,
+    /// Per list, the kind and start of each level used so far.
+    lists: Vec<[Option<(bool, usize)>; LIST_LEVELS]>,
+    open: bool,
+    /// Added to every `abstractNumId` and `numId`, to follow the lists of
+    /// a reference document.
+    first_id: isize,
+}
+
+impl Lists {
+    /// The `(numId, ilvl)` an item is numbered with.
+    fn item(&mut self, item: &ListItem) -> (isize, isize) {
+        let mut level = self.nesting.level(item.indent).min(LIST_LEVELS - 1);
+        // `1.` items followed by `-` ones are two lists.
+        let switches_kind = self
+            .lists
+            .last()
+            .and_then(|levels| levels[0])
+            .is_some_and(|(ordered, _)| level == 0 && ordered != item.ordered);
+        if !self.open || switches_kind {
+            self.open = true;
+            self.nesting.reset();
+            level = self.nesting.level(item.indent);
+            self.lists.push([None; LIST_LEVELS]);
+        }
+        let levels = self.lists.last_mut().expect("a list was just opened");
+        levels[level].get_or_insert((item.ordered, item.number));
+        (self.first_id + self.lists.len() as isize, level as isize)
+    }
+
+    /// Any paragraph other than a list item ends the list.
+    fn end(&mut self) {
+        self.open = false;
+    }
+
+    fn numbering<'a>(&self) -> Option<Numbering<'a>> {
+        if self.lists.is_empty() {
+            return None;
+        }
+        let mut numbering = Numbering::default();
+        for (index, levels) in self.lists.iter().enumerate() {
+            numbering.abstract_numberings.push(AbstractNum {
+                abstract_num_id: Some(self.first_id + index as isize),
+                nsid: None,
+                multi_level_type: MultiLevelType {
+                    value: "hybridMultilevel".into(),
+                },
+                levels: levels
+                    .iter()
+                    .enumerate()
+                    .map(|(level, kind)| list_level(level, kind.unwrap_or((false, 0))))
+                    .collect(),
+            });
+            numbering.numberings.push(Num {
+                num_id: Some(self.first_id + index as isize + 1),
+                abstract_num_id: Some(AbstractNumId {
+                    value: Some(self.first_id + index as isize),
+                }),
+                level_overrides: Vec::new(),
+            });
+        }
+        Some(numbering)
+    }
+}
+
+fn list_level<'a>(level: usize, (ordered, start): (bool, usize)) -> Level<'a> {
+    let (format, text) = if ordered {
+        ("decimal".to_string(), format!("%{}.", level + 1))
+    } else {
+        (
+            "bullet".to_string(),
+            BULLETS[level % BULLETS.len()].to_string(),
+        )
+    };
+    Level {
+        i_level: Some(level as isize),
+        start: Some(LevelStart {
+            value: Some(start.max(1) as isize),
+        }),
+        number_format: Some(NumFmt {
+            value: format.into(),
+        }),
+        level_text: Some(LevelText { value: text.into() }),
+        justification: Some(LevelJustification {
+            value: JustificationVal::Left,
+        }),
+        p_pr: Some(PPr {
+            indent: Some(Indent {
+                left: Some(LIST_INDENT_TWIPS * (level as isize + 1)),
+                hanging: Some(LIST_HANGING_TWIPS),
+                ..Indent::default()
+            }),
+        }),
+        r_pr: Vec::new(),
+    }
+}
+
+fn table_borders<'a>() -> TableBorders<'a> {
+    TableBorders {
+        top: Some(
+            TopBorder::default()
+                .style(BorderStyle::Single)
+                .size(TABLE_BORDER_SIZE),
+        ),
+        left: Some(
+            LeftBorder::default()
+                .style(BorderStyle::Single)
+                .size(TABLE_BORDER_SIZE),
+        ),
+        bottom: Some(
+            BottomBorder::default()
+                .style(BorderStyle::Single)
+                .size(TABLE_BORDER_SIZE),
+        ),
+        right: Some(
+            RightBorder::default()
+                .style(BorderStyle::Single)
+                .size(TABLE_BORDER_SIZE),
+        ),
+        inside_horizon: Some(
+            InsideHorizonBorder::default()
+                .style(BorderStyle::Single)
+                .size(TABLE_BORDER_SIZE),
+        ),
+        inside_vertical: Some(
+            InsideVerticalBorder::default()
+                .style(BorderStyle::Single)
+                .size(TABLE_BORDER_SIZE),
+        ),
+    }
+}
+
+/// Emits a bordered DOCX table from parsed pipe table rows; the header row is bold.
+fn add_markdown_table(
+    docx: &mut Docx,
+    relations: &mut Relations,
+    header: &[String],
+    body: &[Vec<String>],
+) {
+    let columns = header.len().max(1);
+
+    let width = TableWidth {
+        value: Some(5000),
+        unit: Some(TableWidthUnit::Pct),
+    };
+    let mut table = Table::default().property(
+        TableProperty::default()
+            .width(width)
+            .borders(table_borders()),
+    );
+    for _ in 0..columns {
+        table.grids = table
+            .grids
+            .push_column(GridColumn::from(TABLE_WIDTH_TWIPS / columns as isize));
+    }
+
+    for (row_idx, cells) in std::iter::once(header)
+        .chain(body.iter().map(Vec::as_slice))
+        .enumerate()
+    {
+        let is_header = row_idx == 0;
+        let mut row = TableRow::default();
+        for col in 0..columns {
+            let cell_text = cells.get(col).map(String::as_str).unwrap_or("");
+            let mut cell = TableCell::default();
+            // `<br>` is how multi-paragraph cells are written on import.
+            for part in cell_text.split("<br>") {
+                let mut text = part.trim();
+                if is_header {
+                    text = strip_header_bold(text);
+                }
+                cell.content.push(
+                    markdown_paragraph(
+                        relations,
+                        text,
+                        Alignment::Left,
+                        is_header,
+                        false,
+                        config::get().font_size_pt,
+                        None,
+                    )
+                    .into(),
+                );
+            }
+            row = row.push_cell(cell);
+        }
+        table = table.push_row(row);
+    }
+
+    docx.document.push(table);
+}
+
+/// The page header or footer, repeated on every page.
+#[derive(Clone, Copy)]
+enum PagePart {
+    Header,
+    Footer,
+}
+
+impl PagePart {
+    /// The part's name below `word/`.
+    fn name(self) -> &'static str {
+        match self {
+            PagePart::Header => "header1.xml",
+            PagePart::Footer => "footer1.xml",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PagePart::Header => "header",
+            PagePart::Footer => "footer",
+        }
+    }
+
+    fn relationship_id(self) -> &'static str {
+        match self {
+            PagePart::Header => "rIdHeader1",
+            PagePart::Footer => "rIdFooter1",
+        }
+    }
+
+    fn relationship(self) -> &'static str {
+        match self {
+            PagePart::Header => {
+                "http://schemas.openxmlformats.org/officeDocument/2006/relationships/header"
+            }
+            PagePart::Footer => {
+                "http://schemas.openxmlformats.org/officeDocument/2006/relationships/footer"
+            }
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            PagePart::Header => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.header+xml"
+            }
+            PagePart::Footer => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.footer+xml"
+            }
+        }
+    }
+
+    fn reference<'a>(self) -> HeaderFooterReference<'a> {
+        let id = Some(self.relationship_id().into());
+        let ty = Some(HeaderFooterReferenceType::Default);
+        match self {
+            PagePart::Header => HeaderReference { ty, id }.into(),
+            PagePart::Footer => FooterReference { ty, id }.into(),
+        }
+    }
+
+    /// The part's XML: one centered paragraph per line of `markdown`, in
+    /// the body size for the header and the footer line's for the footer.
+    /// docx-rust writes headers and footers without the namespaces images
+    /// and links need, so the root element is written here.
+    fn to_xml(self, relations: &mut Relations, markdown: &str) -> Result<String> {
+        let (tag, font_size_pt) = match self {
+            PagePart::Header => ("w:hdr", config::get().font_size_pt),
+            PagePart::Footer => ("w:ftr", FOOTER_FONT_SIZE_PT),
+        };
+        let mut xml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><{tag} xmlns:w="{WORDML_NAMESPACE}" xmlns:r="{RELATIONSHIPS_NAMESPACE}" xmlns:wp="{WORDPROCESSING_DRAWING_NAMESPACE}">"#
+        );
+        for line in markdown.lines().flat_map(|line| line.split("<br>")) {
+            let paragraph = markdown_paragraph(
+                relations,
+                line.trim(),
+                Alignment::Center,
+                false,
+                false,
+                font_size_pt,
+                None,
+            );
+            xml.push_str(&paragraph.to_string()?);
+        }
+        xml.push_str(&format!("</{tag}>"));
+        Ok(xml)
+    }
+}
+
+/// The Markdown of a page header or footer and the folder its images are
+/// read from: the template's front matter, else `rtemplates.toml`. An empty
+/// value in the front matter leaves the page without one.
+fn page_part_source(
+    front_matter: Option<&String>,
+    configured: Option<&String>,
+    source_dir: &Path,
+) -> Option<(String, PathBuf)> {
+    let (markdown, dir) = match front_matter {
+        Some(markdown) => (markdown.clone(), source_dir.to_path_buf()),
+        None => (configured?.clone(), PathBuf::new()),
+    };
+    (!markdown.trim().is_empty()).then_some((markdown, dir))
+}
+
+/// `docProps/core.xml`, the properties Windows search and document
+/// management systems index: the title, the modality as subject, the
+/// author (the front matter's, else the site's), the front matter's keywords (else the body region), language and
+/// version. The document was created on the front matter's `created` date
+/// and modified at `modified`, which also stands in for a missing
+/// creation date.
+fn core_properties(
+    template: &ReportTemplate,
+    front_matter: &FrontMatter,
+    modified: Option<Timestamp>,
+) -> String {
+    static W3CDTF: OnceLock<Regex> = OnceLock::new();
+    let w3cdtf = W3CDTF.get_or_init(|| {
+        Regex::new(r"^\d{4}(-\d{2}(-\d{2}(T\d{2}:\d{2}(:\d{2})?(Z|[+-]\d{2}:\d{2}))?)?)?$")
+            .expect("valid regex")
+    });
+    let modified = modified.map(|time| time.iso_datetime());
+    let created = match &front_matter.created {
+        Some(date) if w3cdtf.is_match(date) => Some(date.clone()),
+        Some(date) => {
+            warn!(
+                "Ignoring front matter created: {:?}, expected YYYY-MM-DD",
+                date
+            );
+            modified.clone()
+        }
+        None => modified.clone(),
+    };
+    let title = template.title.as_ref().map(|title| title.plain());
+    let author = template.author();
+    let language = front_matter
+        .language
+        .clone()
+        .unwrap_or_else(|| config::get().language.clone());
+
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">"#,
+    );
+    for (tag, value) in [
+        ("dc:title", title.as_ref()),
+        ("dc:subject", template.modality.as_ref()),
+        ("dc:creator", author.as_ref()),
+        (
+            "cp:keywords",
+            front_matter
+                .keywords
+                .as_ref()
+                .or(front_matter.body_region.as_ref()),
+        ),
+        ("cp:lastModifiedBy", author.as_ref()),
+        ("dc:language", Some(&language)),
+        ("cp:version", front_matter.version.as_ref()),
+    ] {
+        if let Some(value) = value.filter(|value| !value.trim().is_empty()) {
+            xml.push_str(&format!("<{tag}>{}</{tag}>", escape_html(value.trim())));
+        }
+    }
+    for (tag, value) in [("dcterms:created", created), ("dcterms:modified", modified)] {
+        if let Some(value) = value {
+            xml.push_str(&format!(
+                r#"<{tag} xsi:type="dcterms:W3CDTF">{value}</{tag}>"#
+            ));
+        }
+    }
+    xml.push_str("</cp:coreProperties>");
+    xml
+}
+
+/// Converts one Markdown file; its modification time is the document's,
+/// and the configured reference document, if any, its base.
+pub fn convert_file(md_path: &Path, output_path: &Path, fill: &FillOptions) -> Result<()> {
+    let content = fill.read(md_path)?;
+    let source_dir = md_path.parent().unwrap_or(Path::new(""));
+    let modified = fs::metadata(md_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(Timestamp::from_system_time);
+    let reference = config::get()
+        .reference_docx
+        .as_deref()
+        .map(|path| Reference::open(Path::new(path)))
+        .transpose()?;
+    write_docx(
+        &ReportTemplate::from_markdown(&content),
+        source_dir,
+        modified,
+        reference.as_ref(),
+        output_path,
+    )
+}
+
+/// Writes `template` as a `.docx`, embedding the images it refers to;
+/// relative image paths are read from `source_dir`, the Markdown file's
+/// folder. The page header and footer come from the front matter or the
+/// `[docx]` settings, and the document properties from the front matter
+/// (see `core_properties`). With a `reference` document, its styles and
+/// page header and footer are used instead. The sections of
+/// `[docx] locked_sections` are content controls Word keeps read-only.
+pub fn write_docx(
+    template: &ReportTemplate,
+    source_dir: &Path,
+    modified: Option<Timestamp>,
+    reference: Option<&Reference>,
+    output_path: &Path,
+) -> Result<()> {
+    let mut relations = Relations::new(source_dir);
+    let notes = template.notes();
+    relations.notes = notes.iter().map(|note| note.label.clone()).collect();
+    let mut docx: Docx = Docx::default();
+    let mut heading_levels = BTreeSet::new();
+    let mut lists = Lists::default();
+    if let Some(reference) = reference {
+        relations.first_image = reference.last_image_number();
+        relations.reference_styles = Some(reference.style_ids());
+        lists.first_id = reference.first_list_id();
+    }
+    let locked_sections = &config::get().locked_sections;
+    // The kind of the locked section being written and where it starts in
+    // the body, then each one written, in order.
+    let mut open_lock: Option<(SectionKind, usize)> = None;
+    let mut locks: Vec<SectionKind> = Vec::new();
+    for (kind, block) in template.section_blocks() {
+        let locked = kind.filter(|kind| locked_sections.contains(kind));
+        if let Some((open, start)) = open_lock.filter(|(open, _)| Some(*open) != locked) {
+            locks.push(open);
+            lock_section(&mut docx, start, locks.len());
+            open_lock = None;
+        }
+        if let (Some(kind), None) = (locked, open_lock) {
+            open_lock = Some((kind, docx.document.body.content.len()));
+        }
+        match block {
+            Block::Paragraph {
+                text,
+                heading: None,
+                ..
+            } if is_page_break(&text) => {
+                lists.end();
+                let page_break = Run::default().push(Break::from(BreakType::Page));
+                docx.document.push(Paragraph::default().push(page_break));
+            }
+            Block::Paragraph {
+                text,
+                heading,
+                style,
+            } => {
+                heading_levels.extend(heading);
+                let item = parse_list_item(&text).filter(|_| heading.is_none());
+                let mut paragraph = markdown_paragraph(
+                    &mut relations,
+                    item.map_or(text.as_str(), |item| item.text),
+                    style.alignment,
+                    heading.is_some(),
+                    style.force_italic,
+                    style.font_size_pt,
+                    heading.map(heading_style_id),
+                );
+                match item {
+                    Some(item) => {
+                        let numbering = NumberingProperty::from(lists.item(&item));
+                        paragraph.property = paragraph.property.map(|p| p.numbering(numbering));
+                    }
+                    None => lists.end(),
+                }
+                docx.document.push(paragraph);
+            }
+            Block::Table { header, rows } => {
+                lists.end();
+                add_markdown_table(&mut docx, &mut relations, &header, &rows);
+            }
+        }
+    }
+    if let Some((open, start)) = open_lock {
+        locks.push(open);
+        lock_section(&mut docx, start, locks.len());
+    }
+    for level in heading_levels {
+        docx.styles.push(heading_style(level));
+    }
+    docx.document_rels
+        .get_or_insert_with(Relationships::default)
+        .relationships
+        .extend(relations.relationships());
+
+    let front_matter = template.metadata();
+    let settings = config::get();
+    let mut page_parts: Vec<(PagePart, Relations)> = Vec::new();
+    for (part, source) in [
+        (
+            PagePart::Header,
+            page_part_source(
+                front_matter.page_header.as_ref(),
+                settings.page_header.as_ref(),
+                source_dir,
+            ),
+        ),
+        (
+            PagePart::Footer,
+            page_part_source(
+                front_matter.page_footer.as_ref(),
+                settings.page_footer.as_ref(),
+                source_dir,
+            ),
+        ),
+    ] {
+        let Some((markdown, dir)) = source else {
+            continue;
+        };
+        if reference.is_some() {
+            warn!(
+                "Page {} not written: the reference document's is used",
+                part.label()
+            );
+            continue;
+        }
+        let previous = page_parts.last().map_or(&relations, |(_, last)| last);
+        let mut part_relations = previous.following(&dir);
+        let xml = part.to_xml(&mut part_relations, &markdown)?;
+        docx.custom_xml
+            .insert(format!("word/{}", part.name()), xml.into_bytes().into());
+        let rels = part_relations.relationships();
+        if !rels.is_empty() {
+            let rels = Relationships {
+                relationships: rels,
+            };
+            docx.custom_xml.insert(
+                format!("word/_rels/{}.rels", part.name()),
+                rels.to_string()?.into_bytes().into(),
+            );
+        }
+        // Listed here with its own type: docx-rust gives the parts it
+        // writes itself the header relationship, footers included.
+        docx.document_rels
+            .get_or_insert_with(Relationships::default)
+            .relationships
+            .push(Relationship {
+                id: part.relationship_id().into(),
+                target: part.name().into(),
+                ty: part.relationship().into(),
+                target_mode: None,
+            });
+        docx.content_types.overrides.push(OverrideContentType {
+            part: format!("/word/{}", part.name()).into(),
+            ty: part.content_type().into(),
+        });
+        page_parts.push((part, part_relations));
+    }
+    if !page_parts.is_empty() {
+        docx.document.push(SectionProperty {
+            header_footer_references: page_parts
+                .iter()
+                .map(|(part, _)| part.reference())
+                .collect(),
+            ..SectionProperty::default()
+        });
+    }
+
+    let mut note_relations = None;
+    if !notes.is_empty() {
+        let previous = page_parts.last().map_or(&relations, |(_, last)| last);
+        let mut part_relations = previous.following(source_dir);
+        docx.footnotes = Some(footnotes_part(&mut part_relations, &notes));
+        let rels = part_relations.relationships();
+        if !rels.is_empty() {
+            let rels = Relationships {
+                relationships: rels,
+            };
+            docx.custom_xml.insert(
+                "word/_rels/footnotes.xml.rels".to_string(),
+                rels.to_string()?.into_bytes().into(),
+            );
+        }
+        docx.content_types.overrides.push(OverrideContentType {
+            part: "/word/footnotes.xml".into(),
+            ty: FOOTNOTES_CONTENT_TYPE.into(),
+        });
+        note_relations = Some(part_relations);
+    }
+
+    let all_relations: Vec<&Relations> = std::iter::once(&relations)
+        .chain(page_parts.iter().map(|(_, part_relations)| part_relations))
+        .chain(note_relations.as_ref())
+        .collect();
+    if all_relations.iter().any(|part| !part.urls.is_empty()) {
+        docx.styles.push(hyperlink_style());
+    }
+    let mut formats = Vec::new();
+    for image in all_relations.iter().flat_map(|part| &part.images) {
+        docx.media
+            .insert(image.media_name.clone(), (MediaType::Image, &image.data));
+        if !formats.contains(&image.format) {
+            formats.push(image.format);
+            docx.content_types.defaults.push(DefaultContentType {
+                ext: image.format.extension().into(),
+                ty: image.format.content_type().into(),
+            });
+        }
+    }
+    docx.custom_xml.insert(
+        "docProps/core.xml".to_string(),
+        core_properties(template, &front_matter, modified)
+            .into_bytes()
+            .into(),
+    );
+    docx.rels.relationships.push(Relationship {
+        id: "rIdCore".into(),
+        target: "docProps/core.xml".into(),
+        ty: CORE_PROPERTIES_RELATIONSHIP.into(),
+        target_mode: None,
+    });
+    if let Some(numbering) = lists.numbering() {
+        docx.numbering = Some(numbering);
+        docx.content_types.overrides.push(OverrideContentType {
+            part: "/word/numbering.xml".into(),
+            ty: NUMBERING_CONTENT_TYPE.into(),
+        });
+    }
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if reference.is_none() && locks.is_empty() {
+        docx.write_file(output_path.to_string_lossy().as_ref())?;
+        return Ok(());
+    }
+    let mut package = docx.write(Cursor::new(Vec::new()))?.into_inner();
+    if !locks.is_empty() {
+        package = with_locks(&package, &locks)?;
+    }
+    match reference {
+        Some(reference) => reference.merge(&package, output_path)?,
+        None => fs::write(output_path, package)?,
+    }
+    Ok(())
+}
+
+/// The footnotes part: Word's separator lines, then `notes`, with the IDs
+/// the text refers to them by, a paragraph each at the footer size.
+fn footnotes_part<'a>(relations: &mut Relations, notes: &[&Footnote]) -> FootNotes<'a> {
+    let separator = |id: isize, ty: NoteSeparator, mark: RunContent<'a>| FootNote {
+        ty: Some(ty),
+        id: Some(id),
+        content: vec![BodyContent::Paragraph(
+            Paragraph::default().push(Run::default().push(mark)),
+        )],
+    };
+    let mut content = vec![
+        separator(
+            -1,
+            NoteSeparator::Separator,
+            RunContent::Separator(Separator {}),
+        ),
+        separator(
+            0,
+            NoteSeparator::ContinuationSeparator,
+            RunContent::ContinuationSeparator(ContinuationSeparator {}),
+        ),
+    ];
+    for (index, note) in notes.iter().enumerate() {
+        let mut paragraph = markdown_paragraph(
+            relations,
+            &note.text,
+            Alignment::Left,
+            false,
+            false,
+            FOOTER_FONT_SIZE_PT,
+            None,
+        );
+        let size = Some(FOOTER_FONT_SIZE_PT);
+        let mark = note_mark(relations, size, RunContent::FootnoteRef(FootnoteRef));
+        let space = Run::default()
+            .property(run_property(relations, size))
+            .push_text(" ");
+        paragraph.content.splice(
+            0..0,
+            [ParagraphContent::Run(mark), ParagraphContent::Run(space)],
+        );
+        content.push(FootNote {
+            ty: None,
+            id: Some(index as isize + 1),
+            content: vec![BodyContent::Paragraph(paragraph)],
+        });
+    }
+    FootNotes { content }
+}
+
+/// Moves the body content from `start` on into the content control `id`,
+/// whose locks `with_locks` adds.
+fn lock_section(docx: &mut Docx, start: usize, id: usize) {
+    let content: Vec<BodyContent> = docx.document.body.content.drain(start..).collect();
+    docx.document.push(SDT {
+        property: Some(SDTProperty {
+            id: Some(STDId {
+                id: Some(id as isize),
+            }),
+            ..SDTProperty::default()
+        }),
+        end_property: None,
+        content: Some(SDTContent { content }),
+    });
+}
+
+/// The DOCX `package` with the content controls of its locked sections,
+/// numbered from 1 in the order of `sections`, named after their section
+/// (the title and tag Word shows) and locked against editing and deletion.
+/// docx-rust writes neither property, so `document.xml` is edited as text.
+fn with_locks(package: &[u8], sections: &[SectionKind]) -> Result<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, mut data) in read_package(Cursor::new(package))? {
+        if name == "word/document.xml" {
+            let mut xml = String::from_utf8(data)?;
+            for (index, kind) in sections.iter().enumerate() {
+                let id = index + 1;
+                xml = xml.replacen(
+                    &format!(r#"<w:sdtPr><w:id w:val="{id}"/></w:sdtPr>"#),
+                    &format!(
+                        r#"<w:sdtPr><w:alias w:val="{}"/><w:tag w:val="{}"/><w:id w:val="{id}"/><w:lock w:val="sdtContentLocked"/></w:sdtPr>"#,
+                        escape_html(kind.label()),
+                        kind.key()
+                    ),
+                    1,
+                );
+            }
+            data = xml.into_bytes();
+        }
+        writer.start_file(name, SimpleFileOptions::default())?;
+        writer.write_all(&data)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Converts every `.md` in `source_dir` into a `.docx` in `target_dir`.
+pub fn convert_folder(source_dir: &Path, target_dir: &Path) -> Result<()> {
+    if !source_dir.exists() {
+        anyhow::bail!("Source folder not found: {}", source_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+
+    for md_file in list_files(source_dir, "md")? {
+        if !exports::is_exported(&md_file, Format::Docx)? {
+            continue;
+        }
+        let output_file = output_path(target_dir, &md_file, "docx");
+        convert_file(&md_file, &output_file, &FillOptions::default())?;
+        aliases::write(&md_file, &output_file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert_to_markdown::{convert_docx_to_markdown, docx_to_markdown};
+    use crate::markdown::{heading_font_size_pt, normalize_heading};
+
+    #[test]
+    fn inline_formatting_round_trips_through_docx() {
+        let lines = [
+            "**bold *italic* bold**",
+            "*italic **bold** italic*",
+            "***both*** plain, **bold***italic*",
+            "**Técnica do exame:** XXX",
+            "__sublinhado *e itálico*__ e normal",
+            "Ver [Fleischner **2017**](https://example.org/fleischner) e [impressão](#impressao).",
+            "| [ACR](https://www.acr.org/) | x |",
+            "*Fonte: [ACR](https://www.acr.org/).*",
+        ];
+        // Markers that pair with nothing are text, at the end of a line too.
+        let literal = ["fim *", "a _ b _", "a ** b **", "30 _"];
+        let markdown = format!(
+            "Título\n\n{}\n\n| **A** | **B** |\n| --- | --- |\n{}\n\n{}\n\n{}\n\nRodapé.",
+            lines[..6].join("\n\n"),
+            lines[6],
+            lines[7],
+            literal.join("\n\n")
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inline.docx");
+        write_docx(
+            &ReportTemplate::from_markdown(&markdown),
+            dir.path(),
+            None,
+            None,
+            &path,
+        )
+        .unwrap();
+
+        let read_back = convert_docx_to_markdown(&path).unwrap();
+        for line in lines {
+            assert!(read_back.lines().any(|l| l == line), "{line}\n{read_back}");
+        }
+        let plain = crate::template::plain;
+        for line in literal {
+            assert!(
+                read_back.lines().any(|l| plain(l) == line),
+                "{line}\n{read_back}"
+            );
+        }
+    }
+
+    #[test]
+    fn heading_levels_round_trip_through_word_heading_styles() {
+        assert_eq!(
+            normalize_heading("### Medidas"),
+            ("Medidas".to_string(), Some(3))
+        );
+        assert_eq!(normalize_heading("Texto"), ("Texto".to_string(), None));
+        let body = config::get().font_size_pt;
+        assert!(heading_font_size_pt(1) > heading_font_size_pt(2));
+        assert!(heading_font_size_pt(3) > body);
+
+        let markdown =
+            "TC DE TÓRAX\n\n# Achados\n\n## Pulmões\n\nSem nódulos.\n\n### Medidas\n\nX mm.\n\nRodapé.";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("headings.docx");
+        write_docx(
+            &ReportTemplate::from_markdown(markdown),
+            dir.path(),
+            None,
+            None,
+            &path,
+        )
+        .unwrap();
+
+        let read_back = convert_docx_to_markdown(&path).unwrap();
+        for line in ["# Achados", "## Pulmões", "### Medidas"] {
+            assert!(read_back.lines().any(|l| l == line), "{line}\n{read_back}");
+        }
+        assert!(
+            read_back.lines().any(|l| l == "Sem nódulos."),
+            "{read_back}"
+        );
+    }
+
+    #[test]
+    fn lists_round_trip_as_word_numbering() {
+        let markdown = "TC DE TÓRAX\n\nDiagnósticos diferenciais:\n\n\
+                        1. Granuloma\n2. Hamartoma\n    - calcificado\n    - com gordura\n\
+                        3. Metástase\n\n- Seguimento em 12 meses\n\nRodapé.";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lists.docx");
+        write_docx(
+            &ReportTemplate::from_markdown(markdown),
+            dir.path(),
+            None,
+            None,
+            &path,
+        )
+        .unwrap();
+
+        let read_back = convert_docx_to_markdown(&path).unwrap();
+        let lines: Vec<&str> = read_back
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        assert_eq!(
+            lines[2..8],
+            [
+                "1. Granuloma",
+                "2. Hamartoma",
+                "    - calcificado",
+                "    - com gordura",
+                "3. Metástase",
+                "- Seguimento em 12 meses",
+            ],
+            "{read_back}"
+        );
+    }
+
+    /// The signature and `IHDR` chunk of a PNG, enough to be embedded.
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(width.to_be_bytes());
+        png.extend(height.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0, 0, 0, 0, 0]);
+        png
+    }
+
+    #[test]
+    fn images_round_trip_through_docx() {
+        let dir = tempfile::tempdir().unwrap();
+        let logo = png_header(600, 150);
+        fs::create_dir(dir.path().join("assets")).unwrap();
+        fs::write(dir.path().join("assets/logo.png"), &logo).unwrap();
+        let markdown = "![Logo](assets/logo.png)\n\nTC DE TÓRAX\n\n\
+                        Achados ![](assets/logo.png){width=2cm} e ![falta](assets/nada.png).\n\n\
+                        Rodapé.";
+        let template = ReportTemplate::from_markdown(markdown);
+        assert_eq!(template.title.as_ref().unwrap().text, "TC DE TÓRAX");
+        let path = dir.path().join("images.docx");
+        write_docx(&template, dir.path(), None, None, &path).unwrap();
+
+        let (read_back, assets) = docx_to_markdown(&path).unwrap();
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].data, logo);
+        let source = &assets[0].path;
+        let lines: Vec<&str> = read_back.lines().collect();
+        // 600 px at 96 dpi, and 2 cm keeping the 4:1 shape.
+        assert_eq!(
+            lines[0],
+            format!("![Logo]({source}){{width=15.88cm height=3.97cm}}")
+        );
+        assert!(lines.contains(
+            &format!("Achados ![]({source}){{width=2cm height=0.5cm}} e falta.").as_str()
+        ));
+    }
+
+    #[test]
+    fn spacing_in_docx_units() {
+        assert!(spacing(&ParagraphSpacing::default()).is_none());
+        let settings = ParagraphSpacing {
+            line: Some(1.15),
+            before_pt: None,
+            after_pt: Some(6.0),
+        };
+        let spacing = spacing(&settings).unwrap();
+        assert_eq!(spacing.line, Some(276));
+        assert_eq!((spacing.before, spacing.after), (None, Some(120)));
+    }
+
+    #[test]
+    fn document_properties_from_front_matter() {
+        let markdown = "---\nmodality: tc\nauthor: Dr. Fulano & Cia\nbody_region: tórax\n\
+                        created: 2024-03-01\n---\n**TC DE TÓRAX**\n\nCorpo.\n\nRodapé.";
+        let modified = Timestamp {
+            year: 2026,
+            month: 10,
+            day: 14,
+            hour: 9,
+            minute: 30,
+            second: 0,
+        };
+        let core = core_properties(
+            &ReportTemplate::from_markdown(markdown),
+            &ReportTemplate::from_markdown(markdown).metadata(),
+            Some(modified),
+        );
+        for part in [
+            "<dc:title>TC DE TÓRAX</dc:title>",
+            "<dc:subject>TC</dc:subject>",
+            "<dc:creator>Dr. Fulano &amp; Cia</dc:creator>",
+            "<cp:keywords>tórax</cp:keywords>",
+            r#"<dcterms:created xsi:type="dcterms:W3CDTF">2024-03-01</dcterms:created>"#,
+            r#"<dcterms:modified xsi:type="dcterms:W3CDTF">2026-10-14T09:30:00Z</dcterms:modified>"#,
+        ] {
+            assert!(core.contains(part), "{part}\n{core}");
+        }
+        let bare = core_properties(
+            &ReportTemplate::from_markdown("Corpo."),
+            &FrontMatter::default(),
+            None,
+        );
+        assert!(
+            !bare.contains("dcterms:created") && !bare.contains("dc:creator"),
+            "{bare}"
+        );
+    }
+
+    #[test]
+    fn front_matter_sets_page_header_and_footer() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("logo.png"), png_header(600, 150)).unwrap();
+        let markdown = "---\npage_header: \"![Clínica](logo.png){height=1cm}<br>**Clínica X**\"\n\
+                        page_footer: Dr. Fulano – CRM 12345\n---\n\
+                        TC DE TÓRAX\n\n![](logo.png)\n\nRodapé.";
+        let path = dir.path().join("page.docx");
+        write_docx(
+            &ReportTemplate::from_markdown(markdown),
+            dir.path(),
+            None,
+            None,
+            &path,
+        )
+        .unwrap();
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&path).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut text = String::new();
+            std::io::Read::read_to_string(&mut archive.by_name(name).unwrap(), &mut text).unwrap();
+            text
+        };
+        let header = read("word/header1.xml");
+        assert!(header.contains("Clínica X") && header.contains(r#"r:embed="rIdImage1""#));
+        assert!(read("word/_rels/header1.xml.rels").contains(r#"Target="media/image2.png""#));
+        assert!(read("word/footer1.xml").contains("CRM 12345"));
+        assert!(!read("word/footer1.xml").contains("<w:drawing"));
+        let rels = read("word/_rels/document.xml.rels");
+        assert!(rels.contains(r#"Target="footer1.xml""#));
+        assert!(rels.contains("relationships/footer"));
+        assert!(read("word/document.xml")
+            .contains(r#"<w:footerReference w:type="default" r:id="rIdFooter1"/>"#));
+        assert!(read("[Content_Types].xml").contains("/word/header1.xml"));
+        assert!(read("_rels/.rels").contains(r#"Target="docProps/core.xml""#));
+        // The body keeps its own image and reads back as before.
+        let read_back = convert_docx_to_markdown(&path).unwrap();
+        assert!(read_back.starts_with("TC DE TÓRAX"), "{read_back}");
+    }
+
+    #[test]
+    fn front_matter_marks_title_and_footer() {
+        let markdown = "---\ntitle: TC de tórax\nfooter: Documento assinado digitalmente.\n---\n\
+                        **TC DE TÓRAX**\n\nFígado normal.\n\nSem outras alterações.\n";
+        let template = ReportTemplate::from_markdown(markdown);
+        assert_eq!(template.title.as_ref().unwrap().text, "**TC DE TÓRAX**");
+        assert!(!template.title_from_front_matter);
+        assert_eq!(template.modality.as_deref(), Some("CT"));
+        // The last line is a finding, not the footer the front matter names.
+        assert_eq!(
+            template.footer.as_ref().unwrap().text,
+            "Documento assinado digitalmente."
+        );
+        assert!(template.footer_from_front_matter);
+        assert!(template.blocks().iter().any(|block| matches!(
+            block,
+            Block::Paragraph { text, style, .. }
+                if text == "Sem outras alterações." && style.alignment == Alignment::Justify
+        )));
+        // The footer stays in the front matter only.
+        assert_eq!(template.to_markdown(), markdown);
+    }
+
+    #[test]
+    fn locked_sections_are_read_only_content_controls() {
+        let mut docx = Docx::default();
+        for text in [
+            "TC DE CRÂNIO",
+            "Técnica do exame:",
+            "Aquisição volumétrica.",
+            "Normal.",
+        ] {
+            docx.document.push(Paragraph::default().push_text(text));
+        }
+        lock_section(&mut docx, 1, 1);
+        docx.document
+            .push(Paragraph::default().push_text("Sem alterações."));
+        let package = docx.write(Cursor::new(Vec::new())).unwrap().into_inner();
+        let locked = with_locks(&package, &[SectionKind::Tecnica]).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locked.docx");
+        fs::write(&path, locked).unwrap();
+        let mut archive = zip::ZipArchive::new(fs::File::open(&path).unwrap()).unwrap();
+        let mut document = String::new();
+        std::io::Read::read_to_string(
+            &mut archive.by_name("word/document.xml").unwrap(),
+            &mut document,
+        )
+        .unwrap();
+        assert!(document.contains(
+            r#"<w:sdtPr><w:alias w:val="Técnica do exame"/><w:tag w:val="tecnica"/><w:id w:val="1"/><w:lock w:val="sdtContentLocked"/></w:sdtPr>"#
+        ));
+        assert_eq!(document.matches("<w:sdt>").count(), 1);
+        // The controlled paragraphs read back in their place.
+        let (markdown, _) = docx_to_markdown(&path).unwrap();
+        assert_eq!(
+            markdown,
+            "TC DE CRÂNIO\nTécnica do exame:\nAquisição volumétrica.\nNormal.\nSem alterações."
+        );
+    }
+
+    #[test]
+    fn footnotes_are_word_footnotes() {
+        let dir = tempfile::tempdir().unwrap();
+        let markdown = "TC DE TÓRAX\n\nNódulo de 5 mm[^fleischner], sem outros[^nada].\n\n\
+                        [^fleischner]: MacMahon H *et al.* Radiology 2017.\n\nRodapé.";
+        let template = ReportTemplate::from_markdown(markdown);
+        let path = dir.path().join("notas.docx");
+        write_docx(&template, dir.path(), None, None, &path).unwrap();
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&path).unwrap()).unwrap();
+        let mut footnotes = String::new();
+        std::io::Read::read_to_string(
+            &mut archive.by_name("word/footnotes.xml").unwrap(),
+            &mut footnotes,
+        )
+        .unwrap();
+        assert!(footnotes.contains(r#"w:type="separator" w:id="-1""#));
+        assert!(footnotes.contains("MacMahon H"));
+        // An undefined note is left as its label in brackets.
+        let read_back = convert_docx_to_markdown(&path).unwrap();
+        assert_eq!(
+            read_back,
+            "TC DE TÓRAX\n\nNódulo de 5 mm[^1], sem outros[nada].\n\n\n*Rodapé.*\n\n\
+             [^1]: MacMahon H *et al.* Radiology 2017."
+        );
+    }
+
+    #[test]
+    fn page_breaks_start_a_new_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let markdown = "TC DE CRÂNIO\n\nSem alterações.\n\\pagebreak\n\
+                        ANGIOTOMOGRAFIA ARTERIAL\n\nPérvias.\n\nRodapé.";
+        let path = dir.path().join("protocolos.docx");
+        write_docx(
+            &ReportTemplate::from_markdown(markdown),
+            dir.path(),
+            None,
+            None,
+            &path,
+        )
+        .unwrap();
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&path).unwrap()).unwrap();
+        let mut document = String::new();
+        std::io::Read::read_to_string(
+            &mut archive.by_name("word/document.xml").unwrap(),
+            &mut document,
+        )
+        .unwrap();
+        assert_eq!(document.matches(r#"<w:br w:type="page"/>"#).count(), 1);
+        assert!(!document.contains("pagebreak"));
+        let read_back = convert_docx_to_markdown(&path).unwrap();
+        assert!(read_back.contains("Sem alterações.\n\\pagebreak\nANGIOTOMOGRAFIA ARTERIAL"));
+    }
+}
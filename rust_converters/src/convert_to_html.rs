@@ -0,0 +1,311 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::config;
+use crate::convert::Format;
+use crate::exports;
+use crate::files::{list_files, output_path};
+use crate::fill::FillOptions;
+use crate::markdown::{
+    heading_font_size_pt, is_page_break, parse_inline, strip_header_bold, Alignment, Block,
+    LineStyle,
+};
+use crate::template::{Paragraph, ReportTemplate};
+
+// Mirrors the DOCX layout: the configured font (Arial 10pt by default),
+// justified body, centered title and an italic 8pt footer line.
+pub(crate) fn stylesheet() -> String {
+    let config = config::get();
+    let family = if config.font == "Arial" {
+        "Arial, Helvetica".to_string()
+    } else {
+        format!("\"{}\"", config.font.replace(['"', '\\'], ""))
+    };
+    let size = config.font_size_pt;
+    let [level1, level2, level3] = [1, 2, 3].map(heading_font_size_pt);
+    format!(
+        "\
+body {{ font-family: {family}, sans-serif; font-size: {size}pt; max-width: 46em; margin: 2em auto; padding: 0 1em; line-height: 1.15; }}
+p, h1, h2, h3, h4, h5, h6 {{ margin: 0; }}
+h1, h2, h3, h4, h5, h6 {{ font-size: {size}pt; }}
+.level1 {{ font-size: {level1}pt; }}
+.level2 {{ font-size: {level2}pt; }}
+.level3 {{ font-size: {level3}pt; }}
+{STYLE_RULES}"
+    )
+}
+
+const STYLE_RULES: &str = "\
+.justify { text-align: justify; }
+.center { text-align: center; }
+.left { text-align: left; }
+.footer { font-style: italic; font-size: 8pt; }
+.page-break { break-after: page; }
+footer.page { font-size: 8pt; }
+table { width: 100%; border-collapse: collapse; }
+th, td { border: 0.5pt solid #000; padding: 2pt 4pt; text-align: left; vertical-align: top; }
+";
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders inline Markdown as HTML, with `<strong>`/`<em>` for emphasis,
+/// `<u>` for underline and `<a>` for links.
+pub(crate) fn inline_html(text: &str, bold: bool) -> String {
+    let runs = parse_inline(text, bold);
+    let mut html = String::new();
+    for group in runs.chunk_by(|a, b| a.link == b.link) {
+        if let Some(url) = &group[0].link {
+            html.push_str(&format!(r#"<a href="{}">"#, escape_html(url)));
+        }
+        for run in group {
+            let mut piece = escape_html(&run.text);
+            if run.italic {
+                piece = format!("<em>{}</em>", piece);
+            }
+            if run.bold && !bold {
+                piece = format!("<strong>{}</strong>", piece);
+            }
+            if run.underline {
+                piece = format!("<u>{}</u>", piece);
+            }
+            html.push_str(&piece);
+        }
+        if group[0].link.is_some() {
+            html.push_str("</a>");
+        }
+    }
+    html
+}
+
+const HEADING_TAGS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+
+fn style_classes(style: LineStyle) -> &'static str {
+    match (style.alignment, style.force_italic) {
+        (Alignment::Center, true) => "center footer",
+        (Alignment::Center, false) => "center",
+        (Alignment::Left, _) => "left",
+        (Alignment::Justify, _) => "justify",
+    }
+}
+
+fn table_html(header: &[String], body: &[Vec<String>]) -> String {
+    let columns = header.len().max(1);
+    let cell = |tag: &str, text: Option<&String>, is_header: bool| {
+        // `<br>` is how multi-paragraph cells are written on import.
+        let parts: Vec<String> = text
+            .map(String::as_str)
+            .unwrap_or("")
+            .split("<br>")
+            .map(|part| {
+                let part = part.trim();
+                if is_header {
+                    inline_html(strip_header_bold(part), true)
+                } else {
+                    inline_html(part, false)
+                }
+            })
+            .collect();
+        format!("<{tag}>{}</{tag}>", parts.join("<br>"))
+    };
+
+    let mut html = String::from("<table>\n<thead>\n<tr>");
+    for col in 0..columns {
+        html.push_str(&cell("th", header.get(col), true));
+    }
+    html.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in body {
+        html.push_str("<tr>");
+        for col in 0..columns {
+            html.push_str(&cell("td", row.get(col), false));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+    html
+}
+
+/// Builds a standalone HTML page (embedded CSS, UTF-8) from a Markdown template.
+pub fn markdown_to_html(content: &str, title: &str) -> String {
+    template_to_html(&ReportTemplate::from_markdown(content), title)
+}
+
+/// The template's page, between the page header and footer of the selected
+/// site (see `ReportTemplate::page_parts`).
+pub fn template_to_html(template: &ReportTemplate, title: &str) -> String {
+    let (header, footer) = template.page_parts();
+    let body = page_part_html("header", &header)
+        + &body_html(template)
+        + &page_part_html("footer", &footer);
+    page_html(title, template.author().as_deref(), "", &body)
+}
+
+/// A `<header>` or `<footer>` (`tag`) of one centered paragraph per line.
+fn page_part_html(tag: &str, lines: &[Paragraph]) -> String {
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut html = format!("<{tag} class=\"page\">\n");
+    for line in lines {
+        html.push_str(&format!(
+            "<p class=\"center\">{}</p>\n",
+            inline_html(&line.text, false)
+        ));
+    }
+    html.push_str(&format!("</{tag}>\n"));
+    html
+}
+
+/// The HTML elements of `template`, without the page around them.
+pub(crate) fn body_html(template: &ReportTemplate) -> String {
+    let mut body = String::new();
+    for block in template.blocks_with_notes() {
+        let (text, heading, style) = match block {
+            Block::Paragraph {
+                text,
+                heading,
+                style,
+            } => (text, heading, style),
+            Block::Table { header, rows } => {
+                body.push_str(&table_html(&header, &rows));
+                continue;
+            }
+        };
+        // Seen when printed only.
+        if heading.is_none() && is_page_break(&text) {
+            body.push_str("<div class=\"page-break\"></div>\n");
+            continue;
+        }
+
+        let runs = parse_inline(&text, false);
+        let all_bold = !runs.is_empty()
+            && runs
+                .iter()
+                .all(|run| run.bold || run.text.trim().is_empty());
+        let mut classes = style_classes(style).to_string();
+        let tag = if let Some(level) = heading {
+            // Markdown headings keep their level; the footer keeps its size.
+            if !style.force_italic {
+                classes.push_str(&format!(" level{}", level.min(3)));
+            }
+            HEADING_TAGS[level as usize - 1]
+        } else if style.alignment == Alignment::Center && !style.force_italic {
+            "h1"
+        } else if all_bold && style.alignment == Alignment::Justify {
+            // Sections are also written as a wholly bold line.
+            "h2"
+        } else {
+            "p"
+        };
+
+        let inner = if text.trim().is_empty() {
+            "<br>".to_string()
+        } else if tag == "p" {
+            inline_html(&text, false)
+        } else {
+            inline_html(&text, true)
+        };
+        body.push_str(&format!("<{tag} class=\"{}\">{}</{tag}>\n", classes, inner));
+    }
+    body
+}
+
+/// A standalone page holding `body`, with the template stylesheet plus
+/// `extra_css`, and `author` in its metadata.
+pub(crate) fn page_html(title: &str, author: Option<&str>, extra_css: &str, body: &str) -> String {
+    let author = author.map_or_else(String::new, |author| {
+        format!(
+            "<meta name=\"author\" content=\"{}\">\n",
+            escape_html(author)
+        )
+    });
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n\
+         {}<title>{}</title>\n<style>\n{}{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(&config::get().language),
+        author,
+        escape_html(title),
+        stylesheet(),
+        extra_css,
+        body
+    )
+}
+
+pub fn convert_file(md_path: &Path, output_path: &Path, fill: &FillOptions) -> Result<()> {
+    let content = fill.read(md_path)?;
+    let title = md_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output_path, markdown_to_html(&content, &title))?;
+    Ok(())
+}
+
+/// Converts every `.md` in `source_dir` into an `.html` in `target_dir`.
+pub fn convert_folder(source_dir: &Path, target_dir: &Path) -> Result<()> {
+    if !source_dir.exists() {
+        anyhow::bail!("Source folder not found: {}", source_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+
+    for md_file in list_files(source_dir, "md")? {
+        if !exports::is_exported(&md_file, Format::Html)? {
+            continue;
+        }
+        let output_file = output_path(target_dir, &md_file, "html");
+        convert_file(&md_file, &output_file, &FillOptions::default())?;
+        aliases::write(&md_file, &output_file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_are_standalone_and_escape_the_text() {
+        let markdown = "# TC DE CRÂNIO\n\nIndicação: <dor> & *febre*.\n\n| a | b |\n|---|---|\n| 1 |\n\n**Rodapé**";
+        let html = markdown_to_html(markdown, "TC \"Crânio\"");
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(r#"<meta charset="utf-8">"#));
+        assert!(html.contains("<title>TC &quot;Crânio&quot;</title>"));
+        assert!(html.contains("<style>"));
+        for part in [
+            r#"<h1 class="center level1">TC DE CRÂNIO</h1>"#,
+            r#"<p class="justify">Indicação: &lt;dor&gt; &amp; <em>febre</em>.</p>"#,
+            "<tr><th>a</th><th>b</th></tr>",
+            "<tr><td>1</td><td></td></tr>",
+            r#"<p class="center footer"><strong>Rodapé</strong></p>"#,
+        ] {
+            assert!(html.contains(part), "{part}\n{html}");
+        }
+    }
+
+    #[test]
+    fn unpaired_markers_stay_in_the_text() {
+        let markdown =
+            "Título\n\nfim *\n\na _ b _\n\nx __ y __\n\na ** b **\n\n30 _\n\n**\n\nRodapé.";
+        let body = body_html(&ReportTemplate::from_markdown(markdown));
+        for line in ["fim *", "a _ b _", "x __ y __", "a ** b **", "30 _", ">**<"] {
+            assert!(body.contains(line), "{line}\n{body}");
+        }
+    }
+}
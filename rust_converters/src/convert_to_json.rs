@@ -0,0 +1,56 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::config;
+use crate::convert::Format;
+use crate::exports;
+use crate::files::{list_files, output_path};
+use crate::fill::FillOptions;
+use crate::generate_index::write_json_pretty;
+use crate::sections::ReportSections;
+use crate::template::ReportTemplate;
+
+/// The sections of a template and the institution it is written for: the
+/// selected site's, else `[placeholders] institution`.
+#[derive(Serialize)]
+struct Export<'a> {
+    #[serde(flatten)]
+    sections: ReportSections,
+    instituicao: Option<&'a str>,
+}
+
+/// Writes the named sections of a Markdown template as pretty JSON.
+pub fn convert_file(md_path: &Path, output_path: &Path, fill: &FillOptions) -> Result<()> {
+    let content = fill.read(md_path)?;
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let template = ReportTemplate::from_markdown(&content);
+    let export = Export {
+        sections: ReportSections::from(&template),
+        instituicao: config::get().placeholder("institution"),
+    };
+    write_json_pretty(&export, output_path)
+}
+
+/// Converts every `.md` in `source_dir` into a `.json` in `target_dir`.
+pub fn convert_folder(source_dir: &Path, target_dir: &Path) -> Result<()> {
+    if !source_dir.exists() {
+        anyhow::bail!("Source folder not found: {}", source_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+
+    for md_file in list_files(source_dir, "md")? {
+        if !exports::is_exported(&md_file, Format::Json)? {
+            continue;
+        }
+        let output_file = output_path(target_dir, &md_file, "json");
+        convert_file(&md_file, &output_file, &FillOptions::default())?;
+        aliases::write(&md_file, &output_file)?;
+    }
+
+    Ok(())
+}
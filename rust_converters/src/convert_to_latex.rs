@@ -0,0 +1,288 @@
+//! Markdown to LaTeX, for teaching files and book chapters.
+//!
+//! The title and headings become unnumbered sections (`\section*`,
+//! `\subsection*`, ...), as do the wholly bold section lines; runs keep
+//! bold, italics, underline and links, and tables become `tabularx` tables
+//! the width of the text. The footer is a centered italic footnote-size
+//! line. A document starts with the built-in preamble (`article` in the
+//! configured size, a Helvetica body like the DOCX output's Arial, A4 with
+//! one-inch margins) or with the file `[latex] preamble` names;
+//! `standalone = false` writes the body alone, for `\input` into a handout.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::config;
+use crate::convert::Format;
+use crate::exports;
+use crate::files::{list_files, output_path};
+use crate::fill::FillOptions;
+use crate::language::Language;
+use crate::markdown::{is_page_break, parse_inline, strip_header_bold, Alignment, Block};
+use crate::template::ReportTemplate;
+
+/// Sectioning commands by heading level.
+const SECTIONS: [&str; 4] = ["section", "subsection", "subsubsection", "paragraph"];
+
+/// `text` with LaTeX's special characters escaped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '<' => escaped.push_str("\\textless{}"),
+            '>' => escaped.push_str("\\textgreater{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The inline Markdown `text` as LaTeX; images are left out.
+fn inline_latex(text: &str, bold: bool) -> String {
+    let mut latex = String::new();
+    let runs = parse_inline(text, bold);
+    for group in runs.chunk_by(|a, b| a.link == b.link) {
+        let mut inner = String::new();
+        for run in group.iter().filter(|run| run.image.is_none()) {
+            let mut piece = escape(&run.text);
+            if run.underline {
+                piece = format!("\\underline{{{}}}", piece);
+            }
+            if run.italic {
+                piece = format!("\\textit{{{}}}", piece);
+            }
+            if run.bold && !bold {
+                piece = format!("\\textbf{{{}}}", piece);
+            }
+            inner.push_str(&piece);
+        }
+        match &group[0].link {
+            // Links inside the document have nowhere to point to.
+            Some(url) if !url.starts_with('#') => latex.push_str(&format!(
+                "\\href{{{}}}{{{}}}",
+                url.replace('%', "\\%").replace('#', "\\#"),
+                inner
+            )),
+            _ => latex.push_str(&inner),
+        }
+    }
+    latex
+}
+
+/// The built-in preamble, hyphenating in `language`.
+pub fn default_preamble(language: Language) -> String {
+    let babel = match language {
+        Language::Portuguese => "brazil",
+        Language::English => "english",
+        Language::Spanish => "spanish",
+    };
+    // `article` only has the 10, 11 and 12pt sizes.
+    let size = config::get().font_size_pt.clamp(10, 12);
+    format!(
+        "\\documentclass[a4paper,{size}pt]{{article}}\n\
+         \\usepackage[utf8]{{inputenc}}\n\
+         \\usepackage[T1]{{fontenc}}\n\
+         \\usepackage[{babel}]{{babel}}\n\
+         \\usepackage[scaled]{{helvet}}\n\
+         \\renewcommand{{\\familydefault}}{{\\sfdefault}}\n\
+         \\usepackage[margin=2.54cm]{{geometry}}\n\
+         \\usepackage{{tabularx}}\n\
+         \\usepackage[hidelinks]{{hyperref}}\n\
+         \\setlength{{\\parindent}}{{0pt}}\n"
+    )
+}
+
+fn table_latex(header: &[String], rows: &[Vec<String>]) -> String {
+    let columns = header.len().max(1);
+    let row_latex = |row: &[String], is_header: bool| {
+        let cells: Vec<String> = (0..columns)
+            .map(|column| {
+                let text = row.get(column).map(String::as_str).unwrap_or("");
+                // `<br>` is how multi-paragraph cells are written on import.
+                text.split("<br>")
+                    .map(|part| {
+                        let part = part.trim();
+                        if is_header {
+                            format!(
+                                "\\textbf{{{}}}",
+                                inline_latex(strip_header_bold(part), true)
+                            )
+                        } else {
+                            inline_latex(part, false)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" \\newline ")
+            })
+            .collect();
+        format!("{} \\\\ \\hline\n", cells.join(" & "))
+    };
+    let mut latex = format!(
+        "\\begin{{tabularx}}{{\\linewidth}}{{|{}}}\n\\hline\n",
+        "X|".repeat(columns)
+    );
+    latex.push_str(&row_latex(header, true));
+    for row in rows {
+        latex.push_str(&row_latex(row, false));
+    }
+    latex.push_str("\\end{tabularx}\n\n");
+    latex
+}
+
+/// The LaTeX of `template`, without the preamble.
+pub fn body_latex(template: &ReportTemplate) -> String {
+    let mut body = String::new();
+    for block in template.document_blocks() {
+        let (text, heading, style) = match block {
+            Block::Paragraph {
+                text,
+                heading,
+                style,
+            } => (text, heading, style),
+            Block::Table { header, rows } => {
+                body.push_str(&table_latex(&header, &rows));
+                continue;
+            }
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        if heading.is_none() && is_page_break(&text) {
+            body.push_str("\\newpage\n\n");
+            continue;
+        }
+
+        let runs = parse_inline(&text, false);
+        let all_bold = runs
+            .iter()
+            .all(|run| run.bold || run.text.trim().is_empty());
+        let level = match heading {
+            Some(level) if !style.force_italic => Some(level as usize),
+            _ if style.alignment == Alignment::Center && !style.force_italic => Some(1),
+            // Sections are also written as a wholly bold line.
+            _ if all_bold && style.alignment == Alignment::Justify => Some(2),
+            _ => None,
+        };
+        if let Some(level) = level {
+            body.push_str(&format!(
+                "\\{}*{{{}}}\n\n",
+                SECTIONS[level.min(SECTIONS.len()) - 1],
+                inline_latex(&text, true)
+            ));
+            continue;
+        }
+
+        let inner = inline_latex(&text, false);
+        body.push_str(&match (style.alignment, style.force_italic) {
+            (_, true) => format!(
+                "\\begin{{center}}\\footnotesize\\itshape\n{}\n\\end{{center}}\n\n",
+                inner
+            ),
+            (Alignment::Center, false) => {
+                format!("\\begin{{center}}\n{}\n\\end{{center}}\n\n", inner)
+            }
+            (Alignment::Left, false) => {
+                format!("\\begin{{flushleft}}\n{}\n\\end{{flushleft}}\n\n", inner)
+            }
+            (Alignment::Justify, false) => format!("{}\n\n", inner),
+        });
+    }
+    body
+}
+
+/// The `.tex` file of `template` as the `[latex]` settings say: a whole
+/// document, or the body alone.
+pub fn template_to_latex(template: &ReportTemplate) -> Result<String> {
+    let settings = &config::get().latex;
+    let body = body_latex(template);
+    if !settings.standalone {
+        return Ok(body);
+    }
+    let preamble = match &settings.preamble {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("Cannot read the LaTeX preamble {}", path))?,
+        None => default_preamble(template.language),
+    };
+    Ok(format!(
+        "{}\n\\begin{{document}}\n\n{}\\end{{document}}\n",
+        preamble.trim_end(),
+        body
+    ))
+}
+
+/// The LaTeX `convert_file` writes for `md_path`.
+pub fn render_md_latex(md_path: &Path, fill: &FillOptions) -> Result<String> {
+    let content = fill.read(md_path)?;
+    template_to_latex(&ReportTemplate::from_markdown(&content))
+}
+
+pub fn convert_file(md_path: &Path, output_path: &Path, fill: &FillOptions) -> Result<()> {
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output_path, render_md_latex(md_path, fill)?)?;
+    Ok(())
+}
+
+/// Converts every `.md` in `source_dir` into a `.tex` in `target_dir`.
+pub fn convert_folder(source_dir: &Path, target_dir: &Path) -> Result<()> {
+    if !source_dir.exists() {
+        anyhow::bail!("Source folder not found: {}", source_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+
+    for md_file in list_files(source_dir, "md")? {
+        if !exports::is_exported(&md_file, Format::Latex)? {
+            continue;
+        }
+        let output_file = output_path(target_dir, &md_file, "tex");
+        convert_file(&md_file, &output_file, &FillOptions::default())?;
+        aliases::write(&md_file, &output_file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_emphasis_and_tables_become_latex() {
+        let template = ReportTemplate::from_markdown(
+            "**TOMOGRAFIA DO TÓRAX**\n\n**Técnica:**\n\nAquisição com *contraste* & 100% de __dose__.\n\n\
+             | **Achado** | Medida |\n|---|---|\n| Nódulo_1 | 5 mm<br>estável |\n\n*Rodapé*\n",
+        );
+        assert_eq!(
+            body_latex(&template),
+            "\\section*{TOMOGRAFIA DO TÓRAX}\n\n\
+             \\subsection*{Técnica:}\n\n\
+             Aquisição com \\textit{contraste} \\& 100\\% de \\underline{dose}.\n\n\
+             \\begin{tabularx}{\\linewidth}{|X|X|}\n\\hline\n\
+             \\textbf{Achado} & \\textbf{Medida} \\\\ \\hline\n\
+             Nódulo\\_1 & 5 mm \\newline estável \\\\ \\hline\n\
+             \\end{tabularx}\n\n\
+             \\begin{center}\\footnotesize\\itshape\n\\textit{Rodapé}\n\\end{center}\n\n"
+        );
+        let document = template_to_latex(&template).unwrap();
+        assert!(document.starts_with("\\documentclass[a4paper,10pt]{article}\n"));
+        assert!(document.contains("\\usepackage[brazil]{babel}\n"));
+        assert!(document.ends_with("\\end{center}\n\n\\end{document}\n"));
+        assert_eq!(
+            inline_latex(
+                "[RSNA](https://rsna.org/a%20b) e [acima](#impressao)",
+                false
+            ),
+            "\\href{https://rsna.org/a\\%20b}{RSNA} e acima"
+        );
+    }
+}
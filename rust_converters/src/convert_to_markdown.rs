@@ -0,0 +1,886 @@
+use anyhow::{Context, Result};
+use docx_rust::document::{
+    BodyContent, Break, BreakType, Drawing, EndNotes, FootNotes, Paragraph, ParagraphContent, Run,
+    RunContent, Table, TableCellContent, TableRowContent, SDT,
+};
+use docx_rust::formatting::{Bold, Italics, SectionType, Underline, UnderlineStyle};
+use docx_rust::rels::Relationships;
+use docx_rust::DocxFile;
+use log::info;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::comments::{self, Comment};
+use crate::docx_styles::{ParagraphRole, WordStyles};
+use crate::files::{display_name, list_files, output_path};
+use crate::footnotes::Footnote;
+use crate::images::{save_assets, Asset};
+use crate::markdown::{is_page_break, spans_to_markdown, strip_header_bold, Image, PAGE_BREAK};
+use crate::rtf;
+use crate::{config, revisions, text_boxes};
+use crate::{html, mrrt, odt};
+
+pub fn convert_docx_to_markdown(docx_path: &Path) -> Result<String> {
+    Ok(docx_to_markdown(docx_path)?.0)
+}
+
+/// Converts a DOCX to Markdown, returning the images it contains as well;
+/// the Markdown refers to them as `![](assets/...)`, for `save_assets` to
+/// write next to it.
+pub fn docx_to_markdown(docx_path: &Path) -> Result<(String, Vec<Asset>)> {
+    let imported = import_docx(docx_path)?;
+    Ok((imported.markdown, imported.assets))
+}
+
+/// A DOCX read as Markdown: its text, its images and its Word comments.
+pub struct ImportedDocx {
+    pub markdown: String,
+    pub assets: Vec<Asset>,
+    pub comments: Vec<Comment>,
+}
+
+/// Converts a DOCX to Markdown, like `docx_to_markdown`, keeping the
+/// comments of its reviewers as well.
+pub fn import_docx(docx_path: &Path) -> Result<ImportedDocx> {
+    let (file, media, comments) = open_docx(docx_path)?;
+    let docx = file.parse()?;
+
+    let mut markdown_lines: Vec<String> = Vec::new();
+    let mut styles = WordStyles::new(&docx);
+    let mut parts = Parts::new(&docx, &media);
+
+    // Walk the document body in order.
+    // Paragraphs and tables are handled, inside content controls too.
+    for item in body_items(&docx.document.body.content) {
+        match item {
+            BodyContent::Paragraph(p) => {
+                push_paragraph_lines(&mut markdown_lines, p, &mut styles, &mut parts);
+            }
+            BodyContent::Table(t) => {
+                markdown_lines.extend(table_to_markdown(t, &mut parts));
+            }
+            _ => {}
+        }
+    }
+    // A break after the last page's text starts no page.
+    while let Some(last) = markdown_lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .filter(|&last| is_page_break(&markdown_lines[last]))
+    {
+        markdown_lines.remove(last);
+    }
+    // Word's footnotes and endnotes, as Markdown footnotes after the text.
+    if !parts.notes.is_empty() {
+        markdown_lines.push(String::new());
+        markdown_lines.extend(parts.notes.iter().map(|(_, note)| note.to_markdown()));
+    }
+
+    Ok(ImportedDocx {
+        markdown: markdown_lines.join("\n"),
+        assets: parts.assets,
+        comments,
+    })
+}
+
+/// The items of a document body, with those of its content controls
+/// (`w:sdt`, such as the locked sections of `convert_to_docx`) in their
+/// place.
+fn body_items<'b, 'a>(content: &'b [BodyContent<'a>]) -> Vec<&'b BodyContent<'a>> {
+    let mut items = Vec::new();
+    for item in content {
+        match item {
+            BodyContent::Sdt(sdt) => items.extend(sdt_items(sdt)),
+            _ => items.push(item),
+        }
+    }
+    items
+}
+
+/// What the content control `sdt` holds: paragraphs and tables in the
+/// body, runs inside a paragraph, cells inside a table row, with nested
+/// controls read through.
+fn sdt_items<'b, 'a>(sdt: &'b SDT<'a>) -> Vec<&'b BodyContent<'a>> {
+    sdt.content
+        .as_ref()
+        .map_or_else(Vec::new, |content| body_items(&content.content))
+}
+
+/// The body of a DOCX one paragraph per line, for `docx_diff`: each as
+/// Markdown (heading and list markers, bold, italics, underline and links),
+/// after a `[style, alignment]` tag when its properties set either. Empty
+/// paragraphs are left out, and tables are one line per row.
+pub fn docx_paragraphs(docx_path: &Path) -> Result<Vec<String>> {
+    let (file, media, _) = open_docx(docx_path)?;
+    let docx = file.parse()?;
+    let mut styles = WordStyles::new(&docx);
+    let mut parts = Parts::new(&docx, &media);
+
+    let mut lines = Vec::new();
+    for item in body_items(&docx.document.body.content) {
+        match item {
+            BodyContent::Paragraph(p) => {
+                let text = body_paragraph_to_markdown(p, &mut styles, &mut parts);
+                if text.trim().is_empty() {
+                    continue;
+                }
+                let property = p.property.as_ref();
+                let tags: Vec<String> = [
+                    property
+                        .and_then(|prop| prop.style_id.as_ref())
+                        .map(|id| id.value.to_string()),
+                    property
+                        .and_then(|prop| prop.justification.as_ref())
+                        .map(|justification| justification.value.to_string()),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                if tags.is_empty() {
+                    lines.push(text);
+                } else {
+                    lines.push(format!("[{}] {}", tags.join(", "), text));
+                }
+            }
+            BodyContent::Table(t) => lines.extend(
+                table_to_markdown(t, &mut parts)
+                    .into_iter()
+                    .filter(|line| !line.is_empty()),
+            ),
+            _ => {}
+        }
+    }
+    lines.extend(parts.notes.iter().map(|(_, note)| note.to_markdown()));
+    Ok(lines)
+}
+
+/// Converts a DOCX to the Markdown file `md_path`, saving its images under
+/// `assets/` in the same folder and its comments in the sidecar of
+/// `comments`.
+pub fn convert_docx_file(docx_path: &Path, md_path: &Path) -> Result<()> {
+    let imported = import_docx(docx_path)?;
+    let markdown_dir = md_path.parent().unwrap_or(Path::new(""));
+    save_assets(markdown_dir, &imported.assets)?;
+    fs::write(md_path, imported.markdown)?;
+    comments::write_sidecar(md_path, &display_name(docx_path), &imported.comments)?;
+    Ok(())
+}
+
+/// Package files under `word/media/`, by name.
+type Media = HashMap<String, Vec<u8>>;
+
+/// What the body refers to outside `document.xml`: link targets, image
+/// files and notes, with the images and notes met so far.
+struct Parts<'a> {
+    rels: Option<&'a Relationships<'a>>,
+    media: &'a Media,
+    footnotes: Option<&'a FootNotes<'a>>,
+    endnotes: Option<&'a EndNotes<'a>>,
+    assets: Vec<Asset>,
+    /// The notes referred to, numbered in order, with the endnote flag and
+    /// ID that Word refers to each by.
+    notes: Vec<((bool, isize), Footnote)>,
+}
+
+impl<'a> Parts<'a> {
+    fn new(docx: &'a docx_rust::Docx<'a>, media: &'a Media) -> Self {
+        Parts {
+            rels: docx.document_rels.as_ref(),
+            media,
+            footnotes: docx.footnotes.as_ref(),
+            endnotes: docx.endnotes.as_ref(),
+            assets: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// The label of Word's footnote (or endnote) `id`, whose text is kept
+    /// for the definitions after the body.
+    fn note(&mut self, endnote: bool, id: &str) -> Option<String> {
+        let id: isize = id.parse().ok()?;
+        if let Some((_, note)) = self.notes.iter().find(|(key, _)| *key == (endnote, id)) {
+            return Some(note.label.clone());
+        }
+        let content = if endnote {
+            let note = self.endnotes?.content.iter().find(|n| n.id == Some(id))?;
+            &note.content
+        } else {
+            let note = self.footnotes?.content.iter().find(|n| n.id == Some(id))?;
+            &note.content
+        };
+        // The note part has its own relationships, which docx-rust does not
+        // read: links in a note keep their text only.
+        let rels = self.rels.take();
+        let text: Vec<String> = content
+            .iter()
+            .filter_map(|item| match item {
+                BodyContent::Paragraph(p) => Some(paragraph_to_markdown(p, self)),
+                _ => None,
+            })
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+            .collect();
+        self.rels = rels;
+        let label = (self.notes.len() + 1).to_string();
+        let note = Footnote {
+            label: label.clone(),
+            text: text.join(" "),
+        };
+        self.notes.push(((endnote, id), note));
+        Some(label)
+    }
+
+    fn target(&self, id: &str) -> Option<&str> {
+        self.rels?.get_target(id)
+    }
+
+    /// The image a drawing shows, stored as an asset of the Markdown.
+    fn image(&mut self, drawing: &Drawing) -> Option<rtf::Span> {
+        let (extent, doc_property, graphic) = match (&drawing.inline, &drawing.anchor) {
+            (Some(inline), _) => (&inline.extent, &inline.doc_property, &inline.graphic),
+            (None, Some(anchor)) => (&anchor.extent, &anchor.doc_property, &anchor.graphic),
+            (None, None) => return None,
+        };
+        let picture = graphic.as_ref()?.data.children.first()?;
+        let target = self.target(&picture.fill.blip.embed)?;
+        let name = match target.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None => format!("word/{}", target),
+        };
+        let data = self.media.get(&name)?;
+        let extension = Path::new(&name).extension()?.to_string_lossy();
+        let asset = Asset::new(data.clone(), &extension);
+        let image = Image {
+            source: asset.path.clone(),
+            width: extent.as_ref().map(|e| e.cx).filter(|&cx| cx > 0),
+            height: extent.as_ref().map(|e| e.cy).filter(|&cy| cy > 0),
+        };
+        if !self.assets.contains(&asset) {
+            self.assets.push(asset);
+        }
+        Some(rtf::Span {
+            text: doc_property.descr.as_deref().unwrap_or("").to_string(),
+            image: Some(image),
+            ..Default::default()
+        })
+    }
+}
+
+/// Reads a `.docx` package with each hyperlink holding a single run:
+/// docx-rust keeps one run per `w:hyperlink`, while Word often splits link
+/// text across several (a spelling mark, a bold word), so a link of many
+/// runs becomes consecutive links to the same target, joined again when
+/// written as Markdown. Tracked changes are accepted or rejected first (see
+/// `revisions`). The files under `word/media/` are returned by name as
+/// well, since docx-rust keeps only some image formats, and the comments
+/// (see `comments`), which it reads only in part and without their anchors.
+fn open_docx(path: &Path) -> Result<(DocxFile, Media, Vec<Comment>)> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)
+        .with_context(|| format!("Not a DOCX package: {}", path.display()))?;
+    let mut package = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut media = HashMap::new();
+    let (mut document_xml, mut comments_xml) = (String::new(), None);
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        if name == "word/document.xml" {
+            let xml = text_boxes::into_body(&revisions::resolve(
+                &String::from_utf8_lossy(&data),
+                config::get().tracked_changes,
+            ));
+            data = split_hyperlink_runs(&xml).into_bytes();
+            document_xml = xml;
+        }
+        if name == "word/comments.xml" {
+            comments_xml = Some(String::from_utf8_lossy(&data).into_owned());
+            continue;
+        }
+        if name.starts_with("word/media/") {
+            media.insert(name.clone(), data.clone());
+        }
+        package.start_file(name, SimpleFileOptions::default())?;
+        package.write_all(&data)?;
+    }
+    let package = package.finish()?;
+    let file = DocxFile::from_reader(Cursor::new(package.into_inner()))?;
+    let comments = comments_xml.map_or_else(Vec::new, |xml| comments::read(&xml, &document_xml));
+    Ok((file, media, comments))
+}
+
+/// Rewrites every `w:hyperlink` holding several runs as one hyperlink per
+/// run, each with the original attributes.
+fn split_hyperlink_runs(xml: &str) -> String {
+    static HYPERLINK: OnceLock<Regex> = OnceLock::new();
+    static RUN: OnceLock<Regex> = OnceLock::new();
+    let hyperlink = HYPERLINK
+        .get_or_init(|| Regex::new(r"(?s)<w:hyperlink(\s[^>]*)?>(.*?)</w:hyperlink>").unwrap());
+    let run = RUN.get_or_init(|| Regex::new(r"(?s)<w:r[\s>].*?</w:r>").unwrap());
+    hyperlink
+        .replace_all(xml, |caps: &Captures| {
+            let runs: Vec<&str> = run.find_iter(&caps[2]).map(|m| m.as_str()).collect();
+            if runs.len() < 2 {
+                return caps[0].to_string();
+            }
+            let attrs = caps.get(1).map_or("", |m| m.as_str());
+            runs.iter()
+                .map(|r| format!("<w:hyperlink{}>{}</w:hyperlink>", attrs, r))
+                .collect()
+        })
+        .into_owned()
+}
+
+/// Adds the body paragraph `p` to `lines`, with a `PAGE_BREAK` line where
+/// Word starts a new page: at a page break (`w:br w:type="page"`), before a
+/// paragraph set to start one, and after a section that is not continuous.
+fn push_paragraph_lines(
+    lines: &mut Vec<String>,
+    p: &Paragraph,
+    styles: &mut WordStyles,
+    parts: &mut Parts,
+) {
+    let property = p.property.as_ref();
+    if property
+        .and_then(|prop| prop.page_break_before.as_ref())
+        .is_some_and(|before| before.value != Some(false))
+    {
+        push_page_break(lines);
+    }
+    let pieces = split_at_page_breaks(p);
+    for (index, piece) in pieces.iter().enumerate() {
+        if index > 0 {
+            push_page_break(lines);
+        }
+        let line = body_paragraph_to_markdown(piece, styles, parts);
+        // A break at the start or end of its paragraph leaves no empty line.
+        if pieces.len() == 1 || !line.trim().is_empty() {
+            lines.push(line);
+        }
+    }
+    let section_type = property
+        .and_then(|prop| prop.section_property.as_ref())
+        .map(|section| section.ty.as_ref().and_then(|ty| ty.ty.as_ref()));
+    match section_type {
+        None | Some(Some(SectionType::Continuous | SectionType::NextColumn)) => {}
+        Some(_) => push_page_break(lines),
+    }
+}
+
+/// Adds a `PAGE_BREAK` line, unless no text comes before it or it would
+/// follow another.
+fn push_page_break(lines: &mut Vec<String>) {
+    match lines.iter().rfind(|line| !line.trim().is_empty()) {
+        Some(last) if !is_page_break(last) => lines.push(PAGE_BREAK.to_string()),
+        _ => {}
+    }
+}
+
+/// The paragraph `p` cut at its page breaks, each piece with its
+/// properties; `p` alone when it has none.
+fn split_at_page_breaks<'a>(p: &Paragraph<'a>) -> Vec<Paragraph<'a>> {
+    let is_page_break = |content: &RunContent| {
+        matches!(
+            content,
+            RunContent::Break(Break {
+                ty: Some(BreakType::Page)
+            })
+        )
+    };
+    let empty = || Paragraph {
+        content: Vec::new(),
+        ..p.clone()
+    };
+    let mut pieces = vec![empty()];
+    for item in &p.content {
+        let run = match item {
+            ParagraphContent::Run(run) if run.content.iter().any(is_page_break) => run,
+            _ => {
+                pieces.last_mut().unwrap().content.push(item.clone());
+                continue;
+            }
+        };
+        let empty_run = || Run {
+            content: Vec::new(),
+            ..run.clone()
+        };
+        let mut current = empty_run();
+        for content in &run.content {
+            if is_page_break(content) {
+                let before = std::mem::replace(&mut current, empty_run());
+                pieces
+                    .last_mut()
+                    .unwrap()
+                    .content
+                    .push(ParagraphContent::Run(before));
+                pieces.push(empty());
+            } else {
+                current.content.push(content.clone());
+            }
+        }
+        pieces
+            .last_mut()
+            .unwrap()
+            .content
+            .push(ParagraphContent::Run(current));
+    }
+    pieces
+}
+
+/// A body paragraph as Markdown: `#` markers for headings and `-` or `1.`
+/// markers, indented four spaces per nesting level, for list items.
+fn body_paragraph_to_markdown(p: &Paragraph, styles: &mut WordStyles, parts: &mut Parts) -> String {
+    let role = styles.role(p);
+    let text = paragraph_to_markdown(p, parts);
+    if text.trim().is_empty() {
+        return text;
+    }
+    match role {
+        ParagraphRole::Body => text,
+        // The heading is bold already, as the table headers are.
+        ParagraphRole::Heading(level) => {
+            format!(
+                "{} {}",
+                "#".repeat(level as usize),
+                strip_header_bold(&text)
+            )
+        }
+        ParagraphRole::ListItem {
+            ordered,
+            level,
+            number,
+        } => {
+            let indent = "    ".repeat(level as usize);
+            if ordered {
+                format!("{}{}. {}", indent, number, text.trim_start())
+            } else {
+                format!("{}- {}", indent, text.trim_start())
+            }
+        }
+    }
+}
+
+/// Renders a table as a GitHub-flavored Markdown table, surrounded by blank
+/// lines. The first row becomes the header; cell runs keep their formatting.
+fn table_to_markdown(table: &Table, parts: &mut Parts) -> Vec<String> {
+    let rows: Vec<Vec<String>> = table
+        .rows
+        .iter()
+        .map(|row| {
+            row.cells
+                .iter()
+                .flat_map(|cell| match cell {
+                    TableRowContent::TableCell(c) => vec![c],
+                    TableRowContent::SDT(sdt) => sdt_items(sdt)
+                        .into_iter()
+                        .filter_map(|item| match item {
+                            BodyContent::TableCell(c) => Some(c),
+                            _ => None,
+                        })
+                        .collect(),
+                })
+                .map(|cell| {
+                    cell.content
+                        .iter()
+                        .map(|TableCellContent::Paragraph(p)| paragraph_to_markdown(p, parts))
+                        .filter(|text| !text.trim().is_empty())
+                        .collect::<Vec<_>>()
+                        .join("<br>")
+                })
+                .collect()
+        })
+        .collect();
+
+    rows_to_markdown(&rows)
+}
+
+/// Writes rows of inline-Markdown cells as a GitHub-flavored Markdown table,
+/// surrounded by blank lines. Short rows are padded; `|` is escaped.
+pub fn rows_to_markdown(rows: &[Vec<String>]) -> Vec<String> {
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|cells| {
+            cells
+                .iter()
+                .map(|text| text.trim().replace('|', "\\|"))
+                .collect()
+        })
+        .filter(|cells: &Vec<String>| !cells.is_empty())
+        .collect();
+
+    let Some(columns) = rows.iter().map(|r| r.len()).max() else {
+        return Vec::new();
+    };
+
+    let format_row = |cells: &[String]| {
+        let mut padded = cells.to_vec();
+        padded.resize(columns, String::new());
+        format!("| {} |", padded.join(" | "))
+    };
+
+    let mut lines = vec![String::new(), format_row(&rows[0])];
+    lines.push(format_row(&vec!["---".to_string(); columns]));
+    for row in &rows[1..] {
+        lines.push(format_row(row));
+    }
+    lines.push(String::new());
+    lines
+}
+
+fn bold_is_on(flag: &Option<Bold>) -> bool {
+    flag.as_ref()
+        .map(|b| b.value.unwrap_or(true))
+        .unwrap_or(false)
+}
+
+fn italics_is_on(flag: &Option<Italics>) -> bool {
+    flag.as_ref()
+        .map(|i| i.value.unwrap_or(true))
+        .unwrap_or(false)
+}
+
+fn underline_is_on(flag: &Option<Underline>) -> bool {
+    flag.as_ref()
+        .map(|u| match u.val.as_ref() {
+            Some(UnderlineStyle::None) => false,
+            Some(_) => true,
+            None => true, // element is present, default style is "single"
+        })
+        .unwrap_or(false)
+}
+
+/// A run's text as one span, followed by the images it shows.
+fn run_spans(run: &Run, parts: &mut Parts) -> Vec<rtf::Span> {
+    let mut format = rtf::Span::default();
+    if let Some(prop) = &run.property {
+        format.bold = bold_is_on(&prop.bold);
+        format.italic = italics_is_on(&prop.italics);
+        format.underline = underline_is_on(&prop.underline);
+    }
+
+    let mut spans = Vec::new();
+    let text = run.text();
+    if !text.is_empty() {
+        spans.push(rtf::Span {
+            text,
+            ..format.clone()
+        });
+    }
+    for content in &run.content {
+        let note = match content {
+            RunContent::Drawing(drawing) => {
+                spans.extend(parts.image(drawing).map(|image| rtf::Span {
+                    bold: format.bold,
+                    italic: format.italic,
+                    underline: format.underline,
+                    ..image
+                }));
+                continue;
+            }
+            RunContent::FootnoteReference(reference) => (false, reference.id.as_deref()),
+            RunContent::EndnoteReference(reference) => (true, reference.id.as_deref()),
+            _ => continue,
+        };
+        if let (endnote, Some(id)) = note {
+            spans.extend(parts.note(endnote, id).map(|label| rtf::Span {
+                text: format!("[{}]", label),
+                note: Some(label),
+                ..rtf::Span::default()
+            }));
+        }
+    }
+    spans
+}
+
+/// Renders a paragraph's runs as inline Markdown; hyperlinks become
+/// `[text](url)` and pictures `![alt](assets/...)`.
+fn paragraph_to_markdown(p: &Paragraph, parts: &mut Parts) -> String {
+    // Process runs to preserve bold/italic/underline.
+    let mut spans: Vec<rtf::Span> = Vec::new();
+    for pc in &p.content {
+        match pc {
+            ParagraphContent::Run(run) => spans.extend(run_spans(run, parts)),
+            ParagraphContent::SDT(sdt) => {
+                for item in sdt_items(sdt) {
+                    if let BodyContent::Run(run) = item {
+                        spans.extend(run_spans(run, parts));
+                    }
+                }
+            }
+            ParagraphContent::Link(link) => {
+                let target = match (&link.id, &link.anchor) {
+                    (Some(id), _) => parts.target(id).map(str::to_string),
+                    (None, Some(anchor)) => Some(format!("#{}", anchor)),
+                    (None, None) => None,
+                };
+                let runs = link.content.iter().flat_map(|run| run_spans(run, parts));
+                for mut span in runs.collect::<Vec<_>>() {
+                    // Link text is underlined by its look, not by the author.
+                    if target.is_some() {
+                        span.underline = false;
+                    }
+                    span.link = target.clone();
+                    spans.push(span);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let plain = p.text();
+    if plain.trim().is_empty() && spans.iter().all(|span| span.image.is_none()) {
+        return String::new();
+    }
+    if spans.is_empty() {
+        // Fall back to the aggregate paragraph text
+        plain
+    } else {
+        spans_to_markdown(&spans)
+    }
+}
+
+pub fn convert_rtf_to_markdown(rtf_path: &Path) -> Result<String> {
+    let bytes = fs::read(rtf_path)?;
+    let paragraphs = rtf::parse(&bytes);
+
+    // Markdown formatting heuristics
+    let mut markdown_lines: Vec<String> = Vec::new();
+
+    for paragraph in paragraphs {
+        let line = paragraph
+            .plain_text()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if line.is_empty() {
+            markdown_lines.push(String::new());
+            continue;
+        }
+
+        // Explicit run formatting wins over the keyword heuristics below.
+        if paragraph.has_formatting() {
+            markdown_lines.push(paragraph.to_markdown());
+            continue;
+        }
+
+        let upper = line.to_uppercase();
+        let lower = line.to_lowercase();
+
+        // Main heading (all caps, no trailing period, contains keywords)
+        if upper == line
+            && line.len() > 15
+            && line.len() < 120
+            && !line.ends_with('.')
+            && (upper.contains("TOMOGRAFIA")
+                || upper.contains("ANGIO")
+                || upper.contains("COMPUTADORIZADA"))
+        {
+            markdown_lines.push(format!("## {}", line));
+            continue;
+        }
+
+        // Important sections
+        let keywords = [
+            "indicação clínica",
+            "técnica do exame",
+            "aspectos observados",
+            "impressão",
+        ];
+
+        if keywords.iter().any(|k| lower.contains(k)) {
+            if upper == line && line.len() > 10 {
+                markdown_lines.push(format!("## {}", line));
+                continue;
+            } else {
+                let start_keywords = ["indicação", "técnica", "aspectos", "impressão"];
+                let mut handled = false;
+                for k in &start_keywords {
+                    if lower.starts_with(k) {
+                        markdown_lines.push(format!("**{}**", line));
+                        handled = true;
+                        break;
+                    }
+                }
+                if !handled {
+                    markdown_lines.push(line);
+                }
+                continue;
+            }
+        }
+
+        // Footnote-like notes → italic
+        if lower.contains("probabilidade")
+            || lower.contains("médico")
+            || lower.contains("diagnóstica")
+        {
+            markdown_lines.push(format!("*{}*", line));
+        } else {
+            markdown_lines.push(line);
+        }
+    }
+
+    Ok(collapse_blank_lines(markdown_lines).join("\n"))
+}
+
+/// Removes duplicate empty lines.
+fn collapse_blank_lines(lines: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    let mut prev_empty = false;
+    for line in lines {
+        if line.trim().is_empty() {
+            if !prev_empty {
+                result.push(String::new());
+                prev_empty = true;
+            }
+        } else {
+            result.push(line);
+            prev_empty = false;
+        }
+    }
+    result
+}
+
+/// Converts an HTML page (legacy RIS export or our own HTML output) to
+/// Markdown, keeping bold/italic/underline runs and tables.
+pub fn convert_html_to_markdown(html_path: &Path) -> Result<String> {
+    let bytes = fs::read(html_path)?;
+
+    Ok(imported_to_markdown(html::parse(&bytes)))
+}
+
+/// Converts an IHE MRRT template (radreport.org) to Markdown: sections become
+/// bold `Name:` headings and form fields their default value or `XXX`.
+pub fn convert_mrrt_to_markdown(mrrt_path: &Path) -> Result<String> {
+    let bytes = fs::read(mrrt_path)?;
+    let markdown = mrrt::parse(&bytes).to_markdown();
+    Ok(markdown.trim_end_matches('\n').to_string())
+}
+
+/// Converts an ODT document to Markdown, keeping bold/italic/underline runs
+/// (from styles or direct formatting) and tables.
+pub fn convert_odt_to_markdown(odt_path: &Path) -> Result<String> {
+    Ok(imported_to_markdown(odt::parse(odt_path)?))
+}
+
+/// A paragraph or a table produced by the HTML and ODT readers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportedBlock {
+    Paragraph(rtf::RtfParagraph),
+    /// Rows of cells already rendered as inline Markdown; cell paragraphs
+    /// are joined with `<br>`.
+    Table(Vec<Vec<String>>),
+}
+
+/// Renders reader output as Markdown without leading/trailing blank lines.
+fn imported_to_markdown(blocks: Vec<ImportedBlock>) -> String {
+    let mut markdown_lines: Vec<String> = Vec::new();
+    for block in blocks {
+        match block {
+            ImportedBlock::Paragraph(paragraph) => markdown_lines.push(paragraph.to_markdown()),
+            ImportedBlock::Table(rows) => markdown_lines.extend(rows_to_markdown(&rows)),
+        }
+    }
+
+    let mut lines = collapse_blank_lines(markdown_lines);
+    while lines.first().is_some_and(|l| l.is_empty()) {
+        lines.remove(0);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Converts every `.docx` and `.rtf` in `reports_dir` into `markdown_dir`.
+pub fn convert_folder(reports_dir: &Path, markdown_dir: &Path) -> Result<()> {
+    fs::create_dir_all(markdown_dir)?;
+
+    // Process .docx
+    let docx_files = list_files(reports_dir, "docx")?;
+    info!("Found {} .docx files", docx_files.len());
+    for docx_file in &docx_files {
+        info!("Converting {}...", display_name(docx_file));
+        let output_file = output_path(markdown_dir, docx_file, "md");
+        convert_docx_file(docx_file, &output_file)?;
+        info!("  ✓ Saved to {}", display_name(&output_file));
+    }
+
+    // Process .rtf
+    let rtf_files = list_files(reports_dir, "rtf")?;
+    info!("\nFound {} .rtf files", rtf_files.len());
+    for rtf_file in &rtf_files {
+        info!("Converting {}...", display_name(rtf_file));
+        let markdown_content = convert_rtf_to_markdown(rtf_file)?;
+        let output_file = output_path(markdown_dir, rtf_file, "md");
+        fs::write(&output_file, markdown_content)?;
+        info!("  ✓ Saved to {}", display_name(&output_file));
+    }
+
+    info!(
+        "\n✓ Conversion finished! Files saved to {}",
+        markdown_dir.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use docx_rust::document::{SDTContent, TableCell, TableRow};
+    use docx_rust::formatting::CharacterProperty;
+    use docx_rust::Docx;
+
+    #[test]
+    fn content_controls_are_read_through() {
+        let control = |content: Vec<BodyContent<'static>>| SDT {
+            content: Some(SDTContent { content }),
+            ..SDT::default()
+        };
+        let mut docx = Docx::default();
+        docx.document
+            .push(Paragraph::default().push_text("TC DE CRÂNIO"));
+        docx.document.push(control(vec![
+            Paragraph::default().push_text("Técnica do exame:").into(),
+            BodyContent::Sdt(control(vec![Paragraph::default()
+                .push_text("Sem contraste.")
+                .into()])),
+        ]));
+        docx.document
+            .push(
+                Paragraph::default()
+                    .push_text("Lateralidade: ")
+                    .push(ParagraphContent::SDT(control(vec![BodyContent::Run(
+                        Run::default()
+                            .property(CharacterProperty::default().bold(true))
+                            .push_text("direita"),
+                    )]))),
+            );
+        docx.document.push(
+            Table::default().push_row(
+                TableRow::default()
+                    .push_cell(TableCell::paragraph(
+                        Paragraph::default().push_text("Lesão"),
+                    ))
+                    .push_cell(TableRowContent::SDT(control(vec![BodyContent::TableCell(
+                        TableCell::paragraph(Paragraph::default().push_text("Medida")),
+                    )]))),
+            ),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("controls.docx");
+        docx.write_file(&path).unwrap();
+
+        let (markdown, _) = docx_to_markdown(&path).unwrap();
+        assert_eq!(
+            markdown,
+            "TC DE CRÂNIO\nTécnica do exame:\nSem contraste.\nLateralidade: **direita**\n\n\
+             | Lesão | Medida |\n| --- | --- |\n"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! IHE MRRT (Management of Radiology Report Templates) export.
+//!
+//! MRRT templates are XHTML5 pages: Dublin Core metadata in `<meta>`
+//! elements, template attributes in a `<script type="text/xml">` block and a
+//! body of `<section>` elements whose editable parts are form fields. This is
+//! the format the RSNA template library (radreport.org) accepts for upload.
+//!
+//! Each template section becomes a level-1 `<section>` and each bold `Name:`
+//! line inside it a level-2 one; the prose under a heading becomes the
+//! default value of a free-text `<textarea>` field.
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::config;
+use crate::convert::Format;
+use crate::convert_to_html::escape_html;
+use crate::exports;
+use crate::files::{list_files, output_path};
+use crate::fill::FillOptions;
+use crate::template::{plain, Content, Paragraph, ReportTemplate, Section};
+use crate::timestamp::Timestamp;
+
+/// DICOM controlled terminology, the coding scheme of the modality term.
+const DICOM_SCHEME: &str = "DCM";
+const DICOM_SCHEME_OID: &str = "1.2.840.10008.2.16.4";
+
+fn modality_meaning(code: &str) -> Option<&'static str> {
+    match code {
+        "CT" => Some("Computed Tomography"),
+        "MR" => Some("Magnetic Resonance"),
+        "US" => Some("Ultrasound"),
+        "MG" => Some("Mammography"),
+        "DX" => Some("Digital Radiography"),
+        _ => None,
+    }
+}
+
+/// A heading line without its colon or any text written after it.
+fn heading_name(heading: &Paragraph) -> String {
+    let line = heading.plain();
+    line.split(':').next().unwrap_or("").trim().to_string()
+}
+
+/// Writes MRRT section bodies, numbering sections and fields across the
+/// whole template.
+struct BodyWriter {
+    html: String,
+    sections: usize,
+    fields: usize,
+}
+
+impl BodyWriter {
+    fn field(&mut self, label: &str, lines: &mut Vec<String>) {
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        let start = lines.iter().position(|l| !l.is_empty());
+        let Some(start) = start else {
+            lines.clear();
+            return;
+        };
+
+        let mut text = String::new();
+        for line in &lines[start..] {
+            // Keep single blank lines between paragraphs.
+            if line.is_empty() && text.ends_with("\n\n") {
+                continue;
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+        lines.clear();
+
+        self.fields += 1;
+        let id = format!("T{}", self.fields);
+        self.html.push_str(&format!(
+            "<p><label for=\"{id}\">{}</label>\n<textarea id=\"{id}\" name=\"{id}\" data-field-type=\"TEXT\">{}</textarea></p>\n",
+            escape_html(label),
+            escape_html(text.trim_end())
+        ));
+    }
+
+    fn table(&mut self, header: &[String], rows: &[Vec<String>]) {
+        let columns = header.len().max(1);
+        let cell = |tag: &str, text: Option<&String>| {
+            let parts: Vec<String> = text
+                .map(String::as_str)
+                .unwrap_or("")
+                .split("<br>")
+                .map(|part| escape_html(&plain(part)))
+                .collect();
+            format!("<{tag}>{}</{tag}>", parts.join("<br/>"))
+        };
+
+        self.html.push_str("<table>\n<tr>");
+        for col in 0..columns {
+            self.html.push_str(&cell("th", header.get(col)));
+        }
+        self.html.push_str("</tr>\n");
+        for row in rows {
+            self.html.push_str("<tr>");
+            for col in 0..columns {
+                self.html.push_str(&cell("td", row.get(col)));
+            }
+            self.html.push_str("</tr>\n");
+        }
+        self.html.push_str("</table>\n");
+    }
+
+    fn section(&mut self, section: &Section) {
+        let name = section.name().unwrap_or_default();
+        let has_text = section.inline_text().is_some()
+            || section.content.iter().any(|content| match content {
+                Content::Paragraph(paragraph) => !paragraph.is_blank(),
+                Content::Table { .. } => true,
+            });
+        if !has_text {
+            return;
+        }
+
+        self.sections += 1;
+        let index = self.sections;
+        let name = if name.is_empty() {
+            "Texto".to_string()
+        } else {
+            name
+        };
+        self.html.push_str(&format!(
+            "<section id=\"S{index}\" class=\"level1\" data-section-name=\"{0}\">\n<header class=\"level1\">{0}</header>\n",
+            escape_html(&name)
+        ));
+
+        let mut label = name.clone();
+        let mut lines: Vec<String> = section.inline_text().into_iter().collect();
+        let mut subsections = 0;
+        for content in &section.content {
+            match content {
+                Content::Paragraph(paragraph) if paragraph.is_subsection() => {
+                    self.field(&label, &mut lines);
+                    if subsections > 0 {
+                        self.html.push_str("</section>\n");
+                    }
+                    subsections += 1;
+                    label = heading_name(paragraph);
+                    self.html.push_str(&format!(
+                        "<section id=\"S{index}.{subsections}\" class=\"level2\" data-section-name=\"{0}\">\n<header class=\"level2\">{0}</header>\n",
+                        escape_html(&label)
+                    ));
+                }
+                Content::Paragraph(paragraph) => lines.push(paragraph.plain()),
+                Content::Table { header, rows } => {
+                    self.field(&label, &mut lines);
+                    self.table(header, rows);
+                }
+            }
+        }
+        self.field(&label, &mut lines);
+        if subsections > 0 {
+            self.html.push_str("</section>\n");
+        }
+        self.html.push_str("</section>\n");
+    }
+}
+
+/// Builds an MRRT template page. `identifier` names the template in the
+/// library (`dcterms.identifier`) and is the title fallback; `date` is
+/// `YYYY-MM-DD`.
+pub fn template_to_mrrt(template: &ReportTemplate, identifier: &str, date: &str) -> String {
+    let title = template
+        .title
+        .as_ref()
+        .map(Paragraph::plain)
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| identifier.to_string());
+
+    let mut attributes = String::from(
+        "<template_attributes>\n<top-level-flag>true</top-level-flag>\n<status>DRAFT</status>\n",
+    );
+    if let Some((code, meaning)) = template
+        .modality
+        .as_deref()
+        .and_then(|code| Some((code, modality_meaning(code)?)))
+    {
+        attributes.push_str(&format!(
+            "<coding_schemes>\n<coding_scheme name=\"{DICOM_SCHEME}\" designator=\"{DICOM_SCHEME_OID}\"/>\n</coding_schemes>\n\
+             <term>\n<code meaning=\"{meaning}\" value=\"{code}\" scheme=\"{DICOM_SCHEME}\"/>\n</term>\n"
+        ));
+    }
+    attributes.push_str("</template_attributes>\n");
+
+    let mut body = BodyWriter {
+        html: String::new(),
+        sections: 0,
+        fields: 0,
+    };
+    for section in &template.sections {
+        body.section(section);
+    }
+    if let Some(footer) = &template.footer {
+        body.html.push_str(&format!(
+            "<p class=\"footer\">{}</p>\n",
+            escape_html(&footer.plain())
+        ));
+    }
+
+    let mut head = format!(
+        "<meta charset=\"UTF-8\"/>\n<title>{0}</title>\n<meta name=\"dcterms.title\" content=\"{0}\"/>\n",
+        escape_html(&title)
+    );
+    for (name, value) in [
+        ("identifier", identifier),
+        ("type", "IMAGE_REPORT_TEMPLATE"),
+        ("language", &config::get().language),
+        ("date", date),
+    ] {
+        head.push_str(&format!(
+            "<meta name=\"dcterms.{}\" content=\"{}\"/>\n",
+            name,
+            escape_html(value)
+        ));
+    }
+    // The institution publishing the template: the selected site's, else
+    // `[placeholders] institution`.
+    if let Some(publisher) = config::get().placeholder("institution") {
+        head.push_str(&format!(
+            "<meta name=\"dcterms.publisher\" content=\"{}\"/>\n",
+            escape_html(publisher)
+        ));
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" lang=\"{}\">\n<head>\n{}<script type=\"text/xml\">\n{}</script>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(&config::get().language),
+        head, attributes, body.html
+    )
+}
+
+/// Exports a Markdown template as MRRT; the file name is the identifier and
+/// its modification date the `dcterms.date`.
+pub fn convert_file(md_path: &Path, output_path: &Path, fill: &FillOptions) -> Result<()> {
+    let content = fill.read(md_path)?;
+    let identifier = md_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let date = Timestamp::from_system_time(fs::metadata(md_path)?.modified()?).iso_date();
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(
+        output_path,
+        template_to_mrrt(&ReportTemplate::from_markdown(&content), &identifier, &date),
+    )?;
+    Ok(())
+}
+
+/// Converts every `.md` in `source_dir` into an MRRT `.html` in `target_dir`.
+pub fn convert_folder(source_dir: &Path, target_dir: &Path) -> Result<()> {
+    if !source_dir.exists() {
+        anyhow::bail!("Source folder not found: {}", source_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+
+    for md_file in list_files(source_dir, "md")? {
+        if !exports::is_exported(&md_file, Format::Mrrt)? {
+            continue;
+        }
+        let output_file = output_path(target_dir, &md_file, "html");
+        convert_file(&md_file, &output_file, &FillOptions::default())?;
+        aliases::write(&md_file, &output_file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_become_mrrt_fields_with_coded_modality() {
+        let markdown = "**TC DE CRÂNIO**\n\n**Indicação clínica:** cefaleia & febre.\n\n**Técnica:**\n\nCortes axiais.\n\n**Impressão:**\n\nNormal.\n\n*Laudo assinado eletronicamente.*";
+        let html = template_to_mrrt(
+            &ReportTemplate::from_markdown(markdown),
+            "TC Crânio",
+            "2024-05-01",
+        );
+        for part in [
+            r#"<meta name="dcterms.title" content="TC DE CRÂNIO"/>"#,
+            r#"<meta name="dcterms.identifier" content="TC Crânio"/>"#,
+            r#"<meta name="dcterms.type" content="IMAGE_REPORT_TEMPLATE"/>"#,
+            r#"<meta name="dcterms.date" content="2024-05-01"/>"#,
+            r#"<code meaning="Computed Tomography" value="CT" scheme="DCM"/>"#,
+            r#"<section id="S1" class="level1" data-section-name="Indicação clínica">"#,
+            r#"<textarea id="T1" name="T1" data-field-type="TEXT">cefaleia &amp; febre.</textarea>"#,
+            r#"<textarea id="T3" name="T3" data-field-type="TEXT">Normal.</textarea>"#,
+            r#"<p class="footer">Laudo assinado eletronicamente.</p>"#,
+        ] {
+            assert!(html.contains(part), "{part}\n{html}");
+        }
+
+        let untitled = template_to_mrrt(&ReportTemplate::from_markdown(""), "Modelo", "2024-05-01");
+        assert!(untitled.contains("<title>Modelo</title>"));
+        assert!(!untitled.contains("<term>"));
+    }
+}
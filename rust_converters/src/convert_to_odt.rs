@@ -0,0 +1,350 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::aliases;
+use crate::config;
+use crate::convert::Format;
+use crate::convert_to_html::escape_html;
+use crate::exports;
+use crate::files::{list_files, output_path};
+use crate::fill::FillOptions;
+use crate::markdown::{
+    heading_font_size_pt, is_page_break, parse_inline, strip_header_bold, Alignment, Block,
+    InlineRun, LineStyle, FOOTER_FONT_SIZE_PT,
+};
+use crate::template::ReportTemplate;
+
+const MIMETYPE: &str = "application/vnd.oasis.opendocument.text";
+
+const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+ <manifest:file-entry manifest:full-path="/" manifest:version="1.2" manifest:media-type="application/vnd.oasis.opendocument.text"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+ <manifest:file-entry manifest:full-path="styles.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#;
+
+const NAMESPACES: &str = r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" xmlns:svg="urn:oasis:names:tc:opendocument:xmlns:svg-compatible:1.0" xmlns:xlink="http://www.w3.org/1999/xlink" office:version="1.2""#;
+
+/// Shared styles: the configured font (Arial 10pt by default) with no
+/// paragraph spacing on an A4 page with one-inch margins, matching the DOCX
+/// output.
+fn styles_xml() -> String {
+    let font = escape_html(&config::get().font);
+    let size = config::get().font_size_pt;
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-styles {NAMESPACES}>
+ <office:font-face-decls>
+  <style:font-face style:name="{font}" svg:font-family="{font}"/>
+ </office:font-face-decls>
+ <office:styles>
+  <style:default-style style:family="paragraph">
+   <style:paragraph-properties fo:margin-top="0cm" fo:margin-bottom="0cm"/>
+   <style:text-properties style:font-name="{font}" fo:font-size="{size}pt"/>
+  </style:default-style>
+  <style:style style:name="Standard" style:family="paragraph" style:class="text"/>
+ </office:styles>
+ <office:automatic-styles>
+  <style:page-layout style:name="pm1">
+   <style:page-layout-properties fo:page-width="21cm" fo:page-height="29.7cm" fo:margin-top="2.54cm" fo:margin-bottom="2.54cm" fo:margin-left="2.54cm" fo:margin-right="2.54cm"/>
+  </style:page-layout>
+ </office:automatic-styles>
+ <office:master-styles>
+  <style:master-page style:name="Standard" style:page-layout-name="pm1"/>
+ </office:master-styles>
+</office:document-styles>
+"#
+    )
+}
+
+/// Paragraph styles for each alignment, the footer line and the run
+/// emphasis combinations used by `text:span`, plus sized variants of the
+/// alignments for each of `heading_levels`.
+fn automatic_styles(heading_levels: &BTreeSet<u8>) -> String {
+    let paragraph = |name: &str, align: &str, text: &str| {
+        format!(
+            r#"  <style:style style:name="{name}" style:family="paragraph" style:parent-style-name="Standard"><style:paragraph-properties fo:text-align="{align}"/>{text}</style:style>
+"#
+        )
+    };
+    let bold =
+        r#"fo:font-weight="bold" style:font-weight-asian="bold" style:font-weight-complex="bold""#;
+    let italic = r#"fo:font-style="italic" style:font-style-asian="italic" style:font-style-complex="italic""#;
+
+    let mut xml = String::from(" <office:automatic-styles>\n");
+    xml.push_str(&paragraph("Pj", "justify", ""));
+    xml.push_str(&paragraph("Pc", "center", ""));
+    xml.push_str(&paragraph("Pl", "start", ""));
+    xml.push_str(
+        r#"  <style:style style:name="Pb" style:family="paragraph" style:parent-style-name="Standard"><style:paragraph-properties fo:break-before="page"/></style:style>
+"#,
+    );
+    xml.push_str(&paragraph(
+        "Pf",
+        "center",
+        &format!(r#"<style:text-properties fo:font-size="{FOOTER_FONT_SIZE_PT}pt" {italic}/>"#),
+    ));
+    for &level in heading_levels {
+        let size = format!(
+            r#"<style:text-properties fo:font-size="{}pt"/>"#,
+            heading_font_size_pt(level)
+        );
+        for (name, align) in [("Pj", "justify"), ("Pc", "center"), ("Pl", "start")] {
+            xml.push_str(&paragraph(&format!("{name}{level}"), align, &size));
+        }
+    }
+    for (name, props) in [
+        ("Tb", bold.to_string()),
+        ("Ti", italic.to_string()),
+        ("Tbi", format!("{bold} {italic}")),
+        (
+            "Tu",
+            r#"style:text-underline-style="solid" style:text-underline-width="auto" style:text-underline-color="font-color""#.to_string(),
+        ),
+    ] {
+        xml.push_str(&format!(
+            r#"  <style:style style:name="{name}" style:family="text"><style:text-properties {props}/></style:style>
+"#
+        ));
+    }
+    xml.push_str(
+        r#"  <style:style style:name="Tbl" style:family="table"><style:table-properties style:width="15.92cm" table:align="margins"/></style:style>
+  <style:style style:name="TblCell" style:family="table-cell"><style:table-cell-properties fo:padding="0.05cm" fo:border="0.5pt solid #000000"/></style:style>
+ </office:automatic-styles>
+"#,
+    );
+    xml
+}
+
+/// Heading levels past 3 share the level-3 styles.
+fn style_level(heading: Option<u8>, style: LineStyle) -> Option<u8> {
+    heading
+        .filter(|_| !style.force_italic)
+        .map(|level| level.min(3))
+}
+
+fn paragraph_style(style: LineStyle, heading: Option<u8>) -> String {
+    let name = match (style.alignment, style.force_italic) {
+        (Alignment::Center, true) => "Pf",
+        (Alignment::Center, false) => "Pc",
+        (Alignment::Left, _) => "Pl",
+        (Alignment::Justify, _) => "Pj",
+    };
+    match style_level(heading, style) {
+        Some(level) => format!("{}{}", name, level),
+        None => name.to_string(),
+    }
+}
+
+/// Escapes text for ODF, where runs of spaces must be written as `text:s`.
+fn odf_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut spaces = 0;
+    let flush = |out: &mut String, spaces: &mut usize| {
+        match *spaces {
+            0 => {}
+            1 => out.push(' '),
+            n => out.push_str(&format!(" <text:s text:c=\"{}\"/>", n - 1)),
+        }
+        *spaces = 0;
+    };
+    for c in text.chars() {
+        match c {
+            ' ' => spaces += 1,
+            '\t' => {
+                flush(&mut out, &mut spaces);
+                out.push_str("<text:tab/>");
+            }
+            _ => {
+                flush(&mut out, &mut spaces);
+                out.push_str(&escape_html(c.encode_utf8(&mut [0; 4])));
+            }
+        }
+    }
+    flush(&mut out, &mut spaces);
+    out
+}
+
+/// Renders inline Markdown as spans and `text:a` links; `bold` sets the
+/// initial bold state.
+fn spans_xml(text: &str, bold: bool) -> String {
+    let runs = parse_inline(text, bold);
+    let mut xml = String::new();
+    for group in runs.chunk_by(|a, b| a.link == b.link) {
+        let spans: String = group.iter().map(span_xml).collect();
+        match &group[0].link {
+            Some(url) => xml.push_str(&format!(
+                r#"<text:a xlink:type="simple" xlink:href="{}">{spans}</text:a>"#,
+                escape_html(url)
+            )),
+            None => xml.push_str(&spans),
+        }
+    }
+    xml
+}
+
+fn span_xml(run: &InlineRun) -> String {
+    let body = odf_text(&run.text);
+    let body = match (run.bold, run.italic) {
+        (false, false) => body,
+        (true, false) => format!(r#"<text:span text:style-name="Tb">{body}</text:span>"#),
+        (false, true) => format!(r#"<text:span text:style-name="Ti">{body}</text:span>"#),
+        (true, true) => format!(r#"<text:span text:style-name="Tbi">{body}</text:span>"#),
+    };
+    if run.underline {
+        format!(r#"<text:span text:style-name="Tu">{body}</text:span>"#)
+    } else {
+        body
+    }
+}
+
+fn table_xml(index: usize, header: &[String], body: &[Vec<String>]) -> String {
+    let columns = header.len().max(1);
+    let row_xml = |cells: &[String], is_header: bool| {
+        let mut xml = String::from("   <table:table-row>");
+        for col in 0..columns {
+            let text = cells.get(col).map(String::as_str).unwrap_or("");
+            xml.push_str(
+                r#"<table:table-cell table:style-name="TblCell" office:value-type="string">"#,
+            );
+            // `<br>` is how multi-paragraph cells are written on import.
+            for part in text.split("<br>") {
+                let mut part = part.trim();
+                if is_header {
+                    part = strip_header_bold(part);
+                }
+                xml.push_str(&format!(
+                    r#"<text:p text:style-name="Pl">{}</text:p>"#,
+                    spans_xml(part, is_header)
+                ));
+            }
+            xml.push_str("</table:table-cell>");
+        }
+        xml.push_str("</table:table-row>\n");
+        xml
+    };
+
+    let mut xml = format!(
+        "  <table:table table:name=\"Table{}\" table:style-name=\"Tbl\">\n   <table:table-column table:number-columns-repeated=\"{}\"/>\n   <table:table-header-rows>\n",
+        index, columns
+    );
+    xml.push_str(&row_xml(header, true));
+    xml.push_str("   </table:table-header-rows>\n");
+    for row in body {
+        xml.push_str(&row_xml(row, false));
+    }
+    xml.push_str("  </table:table>\n");
+    xml
+}
+
+/// Builds `content.xml` for a template using the DOCX layout rules.
+pub fn content_xml(template: &ReportTemplate) -> String {
+    let mut body = String::new();
+    let mut tables = 0;
+    let mut heading_levels = BTreeSet::new();
+    for block in template.document_blocks() {
+        match block {
+            // An empty paragraph starting the next page.
+            Block::Paragraph {
+                text,
+                heading: None,
+                ..
+            } if is_page_break(&text) => body.push_str("  <text:p text:style-name=\"Pb\"/>\n"),
+            Block::Paragraph {
+                text,
+                heading,
+                style,
+            } => {
+                heading_levels.extend(style_level(heading, style));
+                let (open, close) = match heading {
+                    Some(level) => (
+                        format!(
+                            r#"<text:h text:outline-level="{}" text:style-name="{}">"#,
+                            level,
+                            paragraph_style(style, heading)
+                        ),
+                        "</text:h>",
+                    ),
+                    None => (
+                        format!(
+                            r#"<text:p text:style-name="{}">"#,
+                            paragraph_style(style, heading)
+                        ),
+                        "</text:p>",
+                    ),
+                };
+                body.push_str(&format!(
+                    "  {}{}{}\n",
+                    open,
+                    spans_xml(&text, heading.is_some()),
+                    close
+                ));
+            }
+            Block::Table { header, rows } => {
+                tables += 1;
+                body.push_str(&table_xml(tables, &header, &rows));
+            }
+        }
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<office:document-content {}>\n{} <office:body>\n <office:text>\n{} </office:text>\n </office:body>\n</office:document-content>\n",
+        NAMESPACES,
+        automatic_styles(&heading_levels),
+        body
+    )
+}
+
+pub fn convert_file(md_path: &Path, output_path: &Path, fill: &FillOptions) -> Result<()> {
+    let content = fill.read(md_path)?;
+    write_odt(&ReportTemplate::from_markdown(&content), output_path)
+}
+
+pub fn write_odt(template: &ReportTemplate, output_path: &Path) -> Result<()> {
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut zip = ZipWriter::new(File::create(output_path)?);
+    // The mimetype entry must come first and be stored uncompressed.
+    zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("META-INF/manifest.xml", deflated)?;
+    zip.write_all(MANIFEST.as_bytes())?;
+    zip.start_file("styles.xml", deflated)?;
+    zip.write_all(styles_xml().as_bytes())?;
+    zip.start_file("content.xml", deflated)?;
+    zip.write_all(content_xml(template).as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Converts every `.md` in `source_dir` into an `.odt` in `target_dir`.
+pub fn convert_folder(source_dir: &Path, target_dir: &Path) -> Result<()> {
+    if !source_dir.exists() {
+        anyhow::bail!("Source folder not found: {}", source_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+
+    for md_file in list_files(source_dir, "md")? {
+        if !exports::is_exported(&md_file, Format::Odt)? {
+            continue;
+        }
+        let output_file = output_path(target_dir, &md_file, "odt");
+        convert_file(&md_file, &output_file, &FillOptions::default())?;
+        aliases::write(&md_file, &output_file)?;
+    }
+
+    Ok(())
+}
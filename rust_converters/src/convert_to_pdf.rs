@@ -0,0 +1,736 @@
+use anyhow::{anyhow, Result};
+use printpdf::{
+    BuiltinFont, Color, Line, LinePoint, Mm, Op, ParsedFont, PdfDocument, PdfFontHandle, PdfPage,
+    PdfSaveOptions, Point, Pt, Rgb, TextItem,
+};
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::convert::Format;
+use crate::exports;
+use crate::files::{list_files, output_path};
+use crate::fill::FillOptions;
+use crate::footnotes::Footnote;
+use crate::markdown::{
+    is_page_break, parse_inline, strip_header_bold, Alignment, Block, LineStyle,
+    FOOTER_FONT_SIZE_PT,
+};
+use crate::template::ReportTemplate;
+
+// A4 page; one-inch margins as in Word's default layout.
+const PAGE_WIDTH_MM: f32 = 210.0;
+const PAGE_HEIGHT_MM: f32 = 297.0;
+const MARGIN_PT: f32 = 72.0;
+// Word's "multiple 1.15" line spacing.
+const LINE_HEIGHT: f32 = 1.15;
+const CELL_PADDING_PT: f32 = 4.0;
+// Matches the DOCX table border size (4 eighths of a point).
+const TABLE_BORDER_PT: f32 = 0.5;
+// Footnote numbers: their size and how far they are raised, relative to
+// the text's size.
+const NOTE_NUMBER_SCALE: f32 = 0.65;
+const NOTE_NUMBER_RISE: f32 = 0.35;
+// Space above the rule over the footnotes of a page.
+const NOTE_SEPARATOR_PT: f32 = 8.0;
+
+fn page_width() -> f32 {
+    Pt::from(Mm(PAGE_WIDTH_MM)).0
+}
+
+fn page_height() -> f32 {
+    Pt::from(Mm(PAGE_HEIGHT_MM)).0
+}
+
+/// Helvetica is metric-compatible with Arial and is built into every PDF
+/// reader, so no font file has to be embedded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl FontStyle {
+    fn new(bold: bool, italic: bool) -> Self {
+        match (bold, italic) {
+            (false, false) => FontStyle::Regular,
+            (true, false) => FontStyle::Bold,
+            (false, true) => FontStyle::Italic,
+            (true, true) => FontStyle::BoldItalic,
+        }
+    }
+
+    fn builtin(self) -> BuiltinFont {
+        match self {
+            FontStyle::Regular => BuiltinFont::Helvetica,
+            FontStyle::Bold => BuiltinFont::HelveticaBold,
+            FontStyle::Italic => BuiltinFont::HelveticaOblique,
+            FontStyle::BoldItalic => BuiltinFont::HelveticaBoldOblique,
+        }
+    }
+}
+
+/// Glyph metrics for the four Helvetica faces.
+struct Metrics {
+    fonts: [ParsedFont; 4],
+}
+
+impl Metrics {
+    fn load() -> Result<Self> {
+        let parse = |style: FontStyle| {
+            style
+                .builtin()
+                .get_parsed_font()
+                .ok_or_else(|| anyhow!("Could not load metrics for {:?}", style.builtin()))
+        };
+        Ok(Metrics {
+            fonts: [
+                parse(FontStyle::Regular)?,
+                parse(FontStyle::Bold)?,
+                parse(FontStyle::Italic)?,
+                parse(FontStyle::BoldItalic)?,
+            ],
+        })
+    }
+
+    fn text_width(&self, text: &str, style: FontStyle, size: f32) -> f32 {
+        let font = &self.fonts[style as usize];
+        let units_per_em = font.units_per_em as f32;
+        let units: f32 = text
+            .chars()
+            .map(|c| {
+                font.lookup_glyph_index(c as u32)
+                    .and_then(|gid| font.get_glyph_width(gid))
+                    .map(f32::from)
+                    .unwrap_or(units_per_em / 2.0)
+            })
+            .sum();
+        units * size / units_per_em
+    }
+}
+
+/// A word made of one or more differently styled fragments, each with the
+/// number of the footnote it is the raised reference to, if it is one.
+#[derive(Debug, Default)]
+struct Word {
+    parts: Vec<(String, FontStyle, Option<usize>)>,
+    width: f32,
+}
+
+/// The footnotes of the document, numbered as the text refers to them.
+#[derive(Debug, Default)]
+struct Notes {
+    defined: Vec<Footnote>,
+    /// Labels by number, from 1.
+    numbered: Vec<String>,
+}
+
+impl Notes {
+    /// The number of the note `label`, the next one the first time it is
+    /// referred to; None when no note has that label.
+    fn number(&mut self, label: &str) -> Option<usize> {
+        if let Some(index) = self.numbered.iter().position(|known| known == label) {
+            return Some(index + 1);
+        }
+        self.defined.iter().find(|note| note.label == label)?;
+        self.numbered.push(label.to_string());
+        Some(self.numbered.len())
+    }
+
+    fn text(&self, number: usize) -> &str {
+        let label = &self.numbered[number - 1];
+        self.defined
+            .iter()
+            .find(|note| &note.label == label)
+            .map_or("", |note| note.text.as_str())
+    }
+}
+
+/// Breaks inline Markdown into words, keeping emphasis across run borders;
+/// footnote references become the raised numbers `notes` gives them.
+fn words(
+    metrics: &Metrics,
+    notes: &mut Notes,
+    text: &str,
+    bold: bool,
+    force_italic: bool,
+    size: f32,
+) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut current = Word::default();
+    for run in parse_inline(text, bold) {
+        let style = FontStyle::new(run.bold, run.italic || force_italic);
+        if let Some(number) = run.note.as_deref().and_then(|label| notes.number(label)) {
+            let number_text = number.to_string();
+            current.width += metrics.text_width(&number_text, style, size * NOTE_NUMBER_SCALE);
+            current.parts.push((number_text, style, Some(number)));
+            continue;
+        }
+        for (i, piece) in run.text.split(' ').enumerate() {
+            if i > 0 && !current.parts.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            if !piece.is_empty() {
+                current.width += metrics.text_width(piece, style, size);
+                current.parts.push((piece.to_string(), style, None));
+            }
+        }
+    }
+    if !current.parts.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Greedy line filling; a word wider than the line gets a line of its own.
+fn wrap(words: Vec<Word>, space: f32, max_width: f32) -> Vec<Vec<Word>> {
+    let mut lines: Vec<Vec<Word>> = Vec::new();
+    let mut current: Vec<Word> = Vec::new();
+    let mut width = 0.0;
+    for word in words {
+        let needed = if current.is_empty() {
+            word.width
+        } else {
+            width + space + word.width
+        };
+        if needed > max_width && !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+            width = word.width;
+        } else {
+            width = needed;
+        }
+        current.push(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Accumulates drawing operations page by page, top to bottom.
+struct PageWriter {
+    metrics: Metrics,
+    pages: Vec<PdfPage>,
+    ops: Vec<Op>,
+    // Top of the next line, measured from the top edge of the page.
+    y: f32,
+    notes: Notes,
+    /// The footnotes of the page being written, by number, and the height
+    /// kept for them at its foot.
+    page_notes: Vec<usize>,
+    notes_height: f32,
+    /// The footnotes on the pages so far are those up to this number.
+    notes_placed: usize,
+}
+
+impl PageWriter {
+    fn new(metrics: Metrics, notes: &[Footnote]) -> Self {
+        PageWriter {
+            metrics,
+            pages: Vec::new(),
+            ops: Vec::new(),
+            y: MARGIN_PT,
+            notes: Notes {
+                defined: notes.to_vec(),
+                numbered: Vec::new(),
+            },
+            page_notes: Vec::new(),
+            notes_height: 0.0,
+            notes_placed: 0,
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.draw_notes();
+        let ops = std::mem::take(&mut self.ops);
+        self.pages
+            .push(PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops));
+        self.y = MARGIN_PT;
+    }
+
+    fn ensure_space(&mut self, height: f32) {
+        if self.y > MARGIN_PT && self.y + height > page_height() - MARGIN_PT - self.notes_height {
+            self.new_page();
+        }
+    }
+
+    /// The lines of footnote `number`: its raised number, then its text at
+    /// the footer size, as wide as the text of the page.
+    fn note_lines(&self, number: usize) -> Vec<Vec<Word>> {
+        let size = FOOTER_FONT_SIZE_PT as f32;
+        let number_text = number.to_string();
+        let mut words = vec![Word {
+            width: self.metrics.text_width(
+                &number_text,
+                FontStyle::Regular,
+                size * NOTE_NUMBER_SCALE,
+            ),
+            parts: vec![(number_text, FontStyle::Regular, Some(number))],
+        }];
+        // References inside a note are left as written.
+        let text = self.notes.text(number).to_string();
+        words.extend(self::words(
+            &self.metrics,
+            &mut Notes::default(),
+            &text,
+            false,
+            false,
+            size,
+        ));
+        let space = self.metrics.text_width(" ", FontStyle::Regular, size);
+        wrap(words, space, page_width() - 2.0 * MARGIN_PT)
+    }
+
+    /// Keeps room at the foot of the page for the footnotes `line` refers
+    /// to that no earlier page holds, moving to a new page first when the
+    /// line and they do not fit and `paginate` allows it.
+    fn place_notes(&mut self, line: &[Word], line_height: f32, paginate: bool) {
+        let mut numbers: Vec<usize> = line
+            .iter()
+            .flat_map(|word| &word.parts)
+            .filter_map(|(_, _, number)| *number)
+            .filter(|&number| number > self.notes_placed)
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        let line_spacing = FOOTER_FONT_SIZE_PT as f32 * LINE_HEIGHT;
+        let height: f32 = numbers
+            .iter()
+            .map(|&number| self.note_lines(number).len() as f32 * line_spacing)
+            .sum();
+        if paginate {
+            let separator = if numbers.is_empty() {
+                0.0
+            } else {
+                NOTE_SEPARATOR_PT
+            };
+            self.ensure_space(line_height + height + separator);
+        }
+        let Some(&last) = numbers.last() else {
+            return;
+        };
+        if self.page_notes.is_empty() {
+            self.notes_height += NOTE_SEPARATOR_PT;
+        }
+        self.page_notes.extend(numbers);
+        self.notes_height += height;
+        self.notes_placed = last;
+    }
+
+    /// Draws the footnotes of the page below a short rule, and forgets them.
+    fn draw_notes(&mut self) {
+        if self.page_notes.is_empty() {
+            return;
+        }
+        let size = FOOTER_FONT_SIZE_PT as f32;
+        let line_height = size * LINE_HEIGHT;
+        let mut y = page_height() - MARGIN_PT - self.notes_height + NOTE_SEPARATOR_PT;
+        self.ops.push(Op::SetOutlineThickness {
+            pt: Pt(TABLE_BORDER_PT),
+        });
+        self.ops.push(Op::SetOutlineColor {
+            col: Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)),
+        });
+        let rule_width = (page_width() - 2.0 * MARGIN_PT) / 3.0;
+        self.rule(
+            (MARGIN_PT, y - NOTE_SEPARATOR_PT / 2.0),
+            (MARGIN_PT + rule_width, y - NOTE_SEPARATOR_PT / 2.0),
+        );
+        for number in std::mem::take(&mut self.page_notes) {
+            for line in self.note_lines(number) {
+                self.draw_line(&line, MARGIN_PT, y + size, size, 0.0);
+                y += line_height;
+            }
+        }
+        self.notes_height = 0.0;
+    }
+
+    fn point(x: f32, y_from_top: f32) -> Point {
+        Point {
+            x: Pt(x),
+            y: Pt(page_height() - y_from_top),
+        }
+    }
+
+    /// Writes one line at `x`, with `word_spacing` added to every space and
+    /// footnote numbers raised.
+    fn draw_line(&mut self, line: &[Word], x: f32, baseline: f32, size: f32, word_spacing: f32) {
+        let mut ops = vec![
+            Op::StartTextSection,
+            Op::SetTextCursor {
+                pos: Self::point(x, baseline),
+            },
+            Op::SetWordSpacing {
+                pt: Pt(word_spacing),
+            },
+        ];
+        let mut active = None;
+        for (i, word) in line.iter().enumerate() {
+            for (j, (text, style, number)) in word.parts.iter().enumerate() {
+                let raised = number.is_some();
+                if active != Some((*style, raised)) {
+                    let (scale, rise) = if raised {
+                        (NOTE_NUMBER_SCALE, size * NOTE_NUMBER_RISE)
+                    } else {
+                        (1.0, 0.0)
+                    };
+                    ops.push(Op::SetFont {
+                        font: PdfFontHandle::Builtin(style.builtin()),
+                        size: Pt(size * scale),
+                    });
+                    ops.push(Op::SetLineOffset { multiplier: rise });
+                    active = Some((*style, raised));
+                }
+                let text = if i > 0 && j == 0 {
+                    format!(" {}", text)
+                } else {
+                    text.clone()
+                };
+                ops.push(Op::ShowText {
+                    items: vec![TextItem::Text(text)],
+                });
+            }
+        }
+        ops.push(Op::EndTextSection);
+        self.ops.extend(ops);
+    }
+
+    /// Lays out and writes a paragraph between `left` and `left + width`.
+    fn paragraph(
+        &mut self,
+        text: &str,
+        bold: bool,
+        style: LineStyle,
+        left: f32,
+        width: f32,
+        paginate: bool,
+    ) {
+        let size = style.font_size_pt as f32;
+        let line_height = size * LINE_HEIGHT;
+        let space = self
+            .metrics
+            .text_width(" ", FontStyle::new(bold, style.force_italic), size);
+        let lines = wrap(
+            words(
+                &self.metrics,
+                &mut self.notes,
+                text,
+                bold,
+                style.force_italic,
+                size,
+            ),
+            space,
+            width,
+        );
+
+        if lines.is_empty() {
+            if paginate {
+                self.ensure_space(line_height);
+            }
+            self.y += line_height;
+            return;
+        }
+
+        let count = lines.len();
+        for (idx, line) in lines.iter().enumerate() {
+            self.place_notes(line, line_height, paginate);
+            let natural: f32 =
+                line.iter().map(|w| w.width).sum::<f32>() + space * (line.len() - 1) as f32;
+            let (x, word_spacing) = match style.alignment {
+                Alignment::Center => (left + (width - natural).max(0.0) / 2.0, 0.0),
+                Alignment::Justify if idx + 1 < count && line.len() > 1 => {
+                    (left, (width - natural).max(0.0) / (line.len() - 1) as f32)
+                }
+                _ => (left, 0.0),
+            };
+            // The baseline sits roughly one font size below the top of the line.
+            self.draw_line(line, x, self.y + size, size, word_spacing);
+            self.y += line_height;
+        }
+    }
+
+    fn rule(&mut self, from: (f32, f32), to: (f32, f32)) {
+        self.ops.push(Op::DrawLine {
+            line: Line {
+                points: vec![
+                    LinePoint {
+                        p: Self::point(from.0, from.1),
+                        bezier: false,
+                    },
+                    LinePoint {
+                        p: Self::point(to.0, to.1),
+                        bezier: false,
+                    },
+                ],
+                is_closed: false,
+            },
+        });
+    }
+
+    /// Full-width bordered table with equal columns; the header row is bold.
+    fn table(&mut self, header: &[String], body: &[Vec<String>]) {
+        let columns = header.len().max(1);
+        let content_width = page_width() - 2.0 * MARGIN_PT;
+        let column_width = content_width / columns as f32;
+        let cell_style = LineStyle {
+            alignment: Alignment::Left,
+            force_italic: false,
+            font_size_pt: crate::config::get().font_size_pt,
+        };
+
+        self.ops.push(Op::SetOutlineThickness {
+            pt: Pt(TABLE_BORDER_PT),
+        });
+        self.ops.push(Op::SetOutlineColor {
+            col: Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)),
+        });
+
+        let rows = std::iter::once(header).chain(body.iter().map(Vec::as_slice));
+        for (row_idx, cells) in rows.enumerate() {
+            let is_header = row_idx == 0;
+            let cell_parts = |col: usize| -> Vec<&str> {
+                let text = cells.get(col).map(String::as_str).unwrap_or("");
+                // `<br>` is how multi-paragraph cells are written on import.
+                text.split("<br>")
+                    .map(|part| {
+                        let part = part.trim();
+                        if is_header {
+                            strip_header_bold(part)
+                        } else {
+                            part
+                        }
+                    })
+                    .collect()
+            };
+
+            // Measure the row first so it can move to the next page whole.
+            let inner = column_width - 2.0 * CELL_PADDING_PT;
+            let size = cell_style.font_size_pt as f32;
+            let space = self.metrics.text_width(" ", FontStyle::Regular, size);
+            let row_height = (0..columns)
+                .map(|col| {
+                    cell_parts(col)
+                        .iter()
+                        .map(|part| {
+                            let lines = wrap(
+                                words(&self.metrics, &mut self.notes, part, is_header, false, size),
+                                space,
+                                inner,
+                            );
+                            lines.len().max(1) as f32 * size * LINE_HEIGHT
+                        })
+                        .sum::<f32>()
+                })
+                .fold(0.0, f32::max)
+                + 2.0 * CELL_PADDING_PT;
+
+            self.ensure_space(row_height);
+            let top = self.y;
+            for col in 0..columns {
+                let left = MARGIN_PT + col as f32 * column_width;
+                self.y = top + CELL_PADDING_PT;
+                for part in cell_parts(col) {
+                    self.paragraph(
+                        part,
+                        is_header,
+                        cell_style,
+                        left + CELL_PADDING_PT,
+                        inner,
+                        false,
+                    );
+                }
+            }
+            let bottom = top + row_height;
+            let right = MARGIN_PT + content_width;
+            self.rule((MARGIN_PT, top), (right, top));
+            self.rule((MARGIN_PT, bottom), (right, bottom));
+            for col in 0..=columns {
+                let x = MARGIN_PT + col as f32 * column_width;
+                self.rule((x, top), (x, bottom));
+            }
+            self.y = bottom;
+        }
+    }
+
+    fn finish(mut self, title: &str, author: Option<&str>) -> Vec<u8> {
+        if !self.ops.is_empty() || self.pages.is_empty() {
+            self.new_page();
+        }
+        let mut document = PdfDocument::new(title);
+        if let Some(author) = author {
+            document.metadata.info.author = author.to_string();
+        }
+        document
+            .with_pages(self.pages)
+            .save(&PdfSaveOptions::default(), &mut Vec::new())
+    }
+}
+
+pub fn convert_file(md_path: &Path, output_path: &Path, fill: &FillOptions) -> Result<()> {
+    let content = fill.read(md_path)?;
+    let title = md_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let pdf = render_pdf(&ReportTemplate::from_markdown(&content), &title)?;
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output_path, pdf)?;
+    Ok(())
+}
+
+/// Renders a template as PDF bytes, its notes as footnotes; `title` is the
+/// document title metadata and the template's author (see
+/// `ReportTemplate::author`) its author.
+pub fn render_pdf(template: &ReportTemplate, title: &str) -> Result<Vec<u8>> {
+    let author = template.author();
+    let blocks = template.framed(template.blocks());
+    Ok(render_parts(&[blocks], &template.footnotes, title, author.as_deref())?.0)
+}
+
+/// Renders `parts` one after the other, each starting on a new page, and
+/// returns the PDF bytes with the 1-based page each part starts on. The
+/// references to `notes` are footnotes of the page they are on.
+pub(crate) fn render_parts(
+    parts: &[Vec<Block>],
+    notes: &[Footnote],
+    title: &str,
+    author: Option<&str>,
+) -> Result<(Vec<u8>, Vec<usize>)> {
+    let mut writer = PageWriter::new(Metrics::load()?, notes);
+    let mut starts = Vec::new();
+    for (i, blocks) in parts.iter().enumerate() {
+        // A part that ended at the bottom of a page is already on a new one.
+        if i > 0 && !writer.ops.is_empty() {
+            writer.new_page();
+        }
+        starts.push(writer.pages.len() + 1);
+        writer.blocks(blocks);
+    }
+    Ok((writer.finish(title, author), starts))
+}
+
+impl PageWriter {
+    fn blocks(&mut self, blocks: &[Block]) {
+        let content_width = page_width() - 2.0 * MARGIN_PT;
+        for block in blocks {
+            match block {
+                Block::Paragraph {
+                    text,
+                    heading: None,
+                    ..
+                } if is_page_break(text) => {
+                    if self.y > MARGIN_PT {
+                        self.new_page();
+                    }
+                }
+                Block::Paragraph {
+                    text,
+                    heading,
+                    style,
+                } => {
+                    self.paragraph(
+                        text,
+                        heading.is_some(),
+                        *style,
+                        MARGIN_PT,
+                        content_width,
+                        true,
+                    );
+                }
+                Block::Table { header, rows } => self.table(header, rows),
+            }
+        }
+    }
+}
+
+/// Converts every `.md` in `source_dir` into a `.pdf` in `target_dir`.
+pub fn convert_folder(source_dir: &Path, target_dir: &Path) -> Result<()> {
+    if !source_dir.exists() {
+        anyhow::bail!("Source folder not found: {}", source_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+
+    for md_file in list_files(source_dir, "md")? {
+        if !exports::is_exported(&md_file, Format::Pdf)? {
+            continue;
+        }
+        let output_file = output_path(target_dir, &md_file, "pdf");
+        convert_file(&md_file, &output_file, &FillOptions::default())?;
+        aliases::write(&md_file, &output_file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_fill_greedily_and_each_part_starts_a_page() {
+        let metrics = Metrics::load().unwrap();
+        let words = words(
+            &metrics,
+            &mut Notes::default(),
+            "um **nó**dulo",
+            false,
+            false,
+            11.0,
+        );
+        let parts: Vec<_> = words
+            .iter()
+            .map(|word| {
+                word.parts
+                    .iter()
+                    .map(|(text, style, _)| (text.as_str(), *style))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                vec![("um", FontStyle::Regular)],
+                vec![("nó", FontStyle::Bold), ("dulo", FontStyle::Regular)],
+            ]
+        );
+
+        let word = |width| Word {
+            parts: vec![("x".into(), FontStyle::Regular, None)],
+            width,
+        };
+        let lines = wrap(
+            vec![word(30.0), word(30.0), word(30.0), word(200.0), word(10.0)],
+            5.0,
+            70.0,
+        );
+        let widths: Vec<Vec<f32>> = lines
+            .iter()
+            .map(|line| line.iter().map(|word| word.width).collect())
+            .collect();
+        assert_eq!(
+            widths,
+            [vec![30.0, 30.0], vec![30.0], vec![200.0], vec![10.0]]
+        );
+
+        let part = |text: &str| {
+            vec![Block::Paragraph {
+                text: text.to_string(),
+                heading: None,
+                style: Default::default(),
+            }]
+        };
+        let (pdf, starts) = render_parts(
+            &[part("TC DE CRÂNIO"), part("TC DE TÓRAX")],
+            &[],
+            "Laudos",
+            None,
+        )
+        .unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        assert_eq!(starts, [1, 2]);
+    }
+}
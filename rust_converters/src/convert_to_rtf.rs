@@ -0,0 +1,51 @@
+//! Markdown to RTF, for the systems that only take RTF pasted or imported.
+//! The layout follows the DOCX output (`rtf::write`).
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::convert::Format;
+use crate::exports;
+use crate::files::{list_files, output_path};
+use crate::fill::FillOptions;
+use crate::rtf;
+use crate::template::ReportTemplate;
+
+pub fn template_to_rtf(template: &ReportTemplate) -> String {
+    rtf::write(&template.document_blocks())
+}
+
+/// The RTF `convert_file` writes for `md_path`.
+pub fn render_md_rtf(md_path: &Path, fill: &FillOptions) -> Result<String> {
+    let content = fill.read(md_path)?;
+    Ok(template_to_rtf(&ReportTemplate::from_markdown(&content)))
+}
+
+pub fn convert_file(md_path: &Path, output_path: &Path, fill: &FillOptions) -> Result<()> {
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output_path, render_md_rtf(md_path, fill)?)?;
+    Ok(())
+}
+
+/// Converts every `.md` in `source_dir` into an `.rtf` in `target_dir`.
+pub fn convert_folder(source_dir: &Path, target_dir: &Path) -> Result<()> {
+    if !source_dir.exists() {
+        anyhow::bail!("Source folder not found: {}", source_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+
+    for md_file in list_files(source_dir, "md")? {
+        if !exports::is_exported(&md_file, Format::Rtf)? {
+            continue;
+        }
+        let output_file = output_path(target_dir, &md_file, "rtf");
+        convert_file(&md_file, &output_file, &FillOptions::default())?;
+        aliases::write(&md_file, &output_file)?;
+    }
+
+    Ok(())
+}
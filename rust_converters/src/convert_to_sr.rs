@@ -0,0 +1,393 @@
+//! Experimental DICOM Basic Text SR export following TID 2000 (Basic
+//! Diagnostic Imaging Report), for feeding the templates into structured
+//! reporting on a PACS.
+//!
+//! The title becomes the study description; each section becomes a
+//! `CONTAINER` headed with its DICOM code (History, Current Procedure
+//! Descriptions, Findings, Impressions) holding one `TEXT` item per
+//! paragraph. Patient and study attributes are left empty for the
+//! reporting system to fill; institution attributes come from the selected
+//! site or `[placeholders]`, and the equipment ones from `[sr]` (see
+//! `SrMetadata`).
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::config;
+use crate::convert::Format;
+use crate::dicom::{code, generate_uid, write_file, DataSet};
+use crate::exports;
+use crate::files::{list_files, output_path};
+use crate::fill::FillOptions;
+use crate::template::{plain, Content, ReportTemplate, SectionKind};
+use crate::timestamp::Timestamp;
+
+pub const BASIC_TEXT_SR_SOP_CLASS_UID: &str = "1.2.840.10008.5.1.4.1.1.88.11";
+/// UTF-8.
+const SPECIFIC_CHARACTER_SET: &str = "ISO_IR 192";
+/// LO values hold at most 64 characters.
+const LO_MAX_CHARS: usize = 64;
+
+/// Institution attributes written into every SR object. Missing values are
+/// left empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SrMetadata {
+    pub institution_name: String,
+    pub institution_address: String,
+    pub institution_department_name: String,
+    pub station_name: String,
+    pub manufacturer: String,
+}
+
+impl SrMetadata {
+    /// The metadata of `config`: the `{{institution}}`,
+    /// `{{institution_address}}` and `{{institution_department}}`
+    /// placeholders, which `--site` sets, and the `[sr]` equipment.
+    pub fn from_config(config: &config::Config) -> Self {
+        let value = |name: &str| config.placeholder(name).unwrap_or_default().to_string();
+        SrMetadata {
+            institution_name: value("institution"),
+            institution_address: value("institution_address"),
+            institution_department_name: value("institution_department"),
+            station_name: config.sr.station_name.clone().unwrap_or_default(),
+            manufacturer: config.sr.manufacturer.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Container and text item concept names for a section, from CID 7001 and
+/// CID 7002. Text before the first heading is reported as findings.
+fn section_concepts(kind: Option<SectionKind>) -> [(&'static str, &'static str); 2] {
+    match kind {
+        Some(SectionKind::IndicacaoClinica) => [("121060", "History"), ("121060", "History")],
+        Some(SectionKind::Tecnica) => [
+            ("121064", "Current Procedure Descriptions"),
+            ("121065", "Procedure Description"),
+        ],
+        Some(SectionKind::Impressao) => [("121072", "Impressions"), ("121073", "Impression")],
+        Some(SectionKind::AspectosObservados) | None => {
+            [("121070", "Findings"), ("121071", "Finding")]
+        }
+    }
+}
+
+/// Code meaning for the RFC 5646 language tag; unknown tags mean themselves.
+fn language_meaning(tag: &str) -> &str {
+    match tag {
+        "pt-BR" => "Portuguese (Brazil)",
+        "pt-PT" => "Portuguese (Portugal)",
+        "pt" => "Portuguese",
+        "en-US" => "English (United States)",
+        "en-GB" => "English (United Kingdom)",
+        "en" => "English",
+        "es" => "Spanish",
+        _ => tag,
+    }
+}
+
+fn content_item(relationship: &str, value_type: &str, concept: DataSet) -> DataSet {
+    let mut item = DataSet::new();
+    item.str((0x0040, 0xA010), "CS", relationship)
+        .str((0x0040, 0xA040), "CS", value_type)
+        .sequence((0x0040, 0xA043), vec![concept]);
+    item
+}
+
+fn text_item(concept: (&str, &str), text: &str) -> DataSet {
+    let mut item = content_item("CONTAINS", "TEXT", code(concept.0, "DCM", concept.1));
+    item.str((0x0040, 0xA160), "UT", text);
+    item
+}
+
+fn container(concept: DataSet, children: Vec<DataSet>) -> DataSet {
+    let mut item = content_item("CONTAINS", "CONTAINER", concept);
+    item.str((0x0040, 0xA050), "CS", "SEPARATE")
+        .sequence((0x0040, 0xA730), children);
+    item
+}
+
+/// Groups each section into paragraphs: blank lines end a paragraph and a
+/// bold `Name:` line starts one. Consecutive sections of the same kind
+/// share a container.
+fn section_containers(template: &ReportTemplate) -> Vec<DataSet> {
+    let mut groups: Vec<(Option<SectionKind>, Vec<String>)> = Vec::new();
+    for section in &template.sections {
+        let kind = section.kind.or(Some(SectionKind::AspectosObservados));
+        if groups.last().is_none_or(|(last, _)| *last != kind) {
+            groups.push((kind, Vec::new()));
+        }
+        let paragraphs = &mut groups.last_mut().unwrap().1;
+
+        let mut current: Vec<String> = section.inline_text().into_iter().collect();
+        let mut flush = |current: &mut Vec<String>| {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+        };
+        for content in &section.content {
+            match content {
+                Content::Paragraph(paragraph) if paragraph.is_blank() => flush(&mut current),
+                Content::Paragraph(paragraph) => {
+                    if paragraph.is_subsection() {
+                        flush(&mut current);
+                    }
+                    current.push(paragraph.plain());
+                }
+                Content::Table { header, rows } => {
+                    flush(&mut current);
+                    for row in std::iter::once(header).chain(rows) {
+                        let cells: Vec<String> = row.iter().map(|c| plain(c)).collect();
+                        current.push(cells.join(" | "));
+                    }
+                    flush(&mut current);
+                }
+            }
+        }
+        flush(&mut current);
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, paragraphs)| !paragraphs.is_empty())
+        .map(|(kind, paragraphs)| {
+            let [heading, element] = section_concepts(kind);
+            let children = paragraphs
+                .iter()
+                .map(|text| text_item(element, text))
+                .collect();
+            container(code(heading.0, "DCM", heading.1), children)
+        })
+        .collect()
+}
+
+/// Builds a Basic Text SR Part 10 file for `template`. `seed` makes the
+/// generated UIDs specific to the source (usually its path).
+pub fn template_to_sr(template: &ReportTemplate, metadata: &SrMetadata, seed: &str) -> Vec<u8> {
+    let now = Timestamp::now();
+    let date = format!("{:04}{:02}{:02}", now.year, now.month, now.day);
+    let time = format!("{:02}{:02}{:02}", now.hour, now.minute, now.second);
+    let sop_instance_uid = generate_uid(seed);
+    let title: String = template
+        .title
+        .as_ref()
+        .map(|t| t.plain())
+        .unwrap_or_default()
+        .chars()
+        .take(LO_MAX_CHARS)
+        .collect();
+
+    let mut language = content_item(
+        "HAS CONCEPT MOD",
+        "CODE",
+        code("121049", "DCM", "Language of Content Item and Descendants"),
+    );
+    let tag = &config::get().language;
+    language.sequence(
+        (0x0040, 0xA168),
+        vec![code(tag, "RFC5646", language_meaning(tag))],
+    );
+    let mut content = vec![language];
+    content.extend(section_containers(template));
+
+    let mut template_id = DataSet::new();
+    template_id
+        .str((0x0008, 0x0105), "CS", "DCMR")
+        .str((0x0040, 0xDB00), "CS", "2000");
+
+    let mut ds = DataSet::new();
+    // SOP Common, General Study, Patient and SR Document Series.
+    ds.str((0x0008, 0x0005), "CS", SPECIFIC_CHARACTER_SET)
+        .str((0x0008, 0x0016), "UI", BASIC_TEXT_SR_SOP_CLASS_UID)
+        .str((0x0008, 0x0018), "UI", sop_instance_uid.as_str())
+        .str((0x0008, 0x0020), "DA", date.as_str())
+        .str((0x0008, 0x0023), "DA", date.as_str())
+        .str((0x0008, 0x0030), "TM", time.as_str())
+        .str((0x0008, 0x0033), "TM", time.as_str())
+        .str((0x0008, 0x0050), "SH", "")
+        .str((0x0008, 0x0060), "CS", "SR")
+        .str((0x0008, 0x0070), "LO", metadata.manufacturer.as_str())
+        .str((0x0008, 0x0080), "LO", metadata.institution_name.as_str())
+        .str(
+            (0x0008, 0x0081),
+            "ST",
+            metadata.institution_address.as_str(),
+        )
+        .str((0x0008, 0x0090), "PN", "")
+        .str((0x0008, 0x1010), "SH", metadata.station_name.as_str())
+        .str((0x0008, 0x1030), "LO", title)
+        .str(
+            (0x0008, 0x1040),
+            "LO",
+            metadata.institution_department_name.as_str(),
+        )
+        .sequence((0x0008, 0x1111), Vec::new())
+        .str((0x0010, 0x0010), "PN", "")
+        .str((0x0010, 0x0020), "LO", "")
+        .str((0x0010, 0x0030), "DA", "")
+        .str((0x0010, 0x0040), "CS", "")
+        .str((0x0020, 0x000D), "UI", generate_uid(seed))
+        .str((0x0020, 0x000E), "UI", generate_uid(seed))
+        .str((0x0020, 0x0010), "SH", "")
+        .str((0x0020, 0x0011), "IS", "1")
+        .str((0x0020, 0x0013), "IS", "1");
+    // SR Document General and SR Document Content: an unverified,
+    // partial report, since a template has no patient findings yet.
+    ds.str((0x0040, 0xA040), "CS", "CONTAINER")
+        .sequence(
+            (0x0040, 0xA043),
+            vec![code("18748-4", "LN", "Diagnostic Imaging Report")],
+        )
+        .str((0x0040, 0xA050), "CS", "SEPARATE")
+        .sequence((0x0040, 0xA372), Vec::new())
+        .str((0x0040, 0xA491), "CS", "PARTIAL")
+        .str((0x0040, 0xA493), "CS", "UNVERIFIED")
+        .sequence((0x0040, 0xA504), vec![template_id])
+        .sequence((0x0040, 0xA730), content);
+
+    write_file(BASIC_TEXT_SR_SOP_CLASS_UID, &sop_instance_uid, &ds)
+}
+
+pub fn convert_file(
+    md_path: &Path,
+    output_path: &Path,
+    metadata: &SrMetadata,
+    fill: &FillOptions,
+) -> Result<()> {
+    let content = fill.read(md_path)?;
+    let template = ReportTemplate::from_markdown(&content);
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(
+        output_path,
+        template_to_sr(&template, metadata, &md_path.to_string_lossy()),
+    )?;
+    Ok(())
+}
+
+/// Converts every `.md` in `source_dir` into a `.dcm` in `target_dir`.
+pub fn convert_folder(source_dir: &Path, target_dir: &Path, metadata: &SrMetadata) -> Result<()> {
+    if !source_dir.exists() {
+        anyhow::bail!("Source folder not found: {}", source_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+
+    for md_file in list_files(source_dir, "md")? {
+        if !exports::is_exported(&md_file, Format::Sr)? {
+            continue;
+        }
+        let output_file = output_path(target_dir, &md_file, "dcm");
+        convert_file(&md_file, &output_file, metadata, &FillOptions::default())?;
+        aliases::write(&md_file, &output_file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dicom::{decode_file, Value};
+
+    fn text(dataset: &DataSet, tag: (u16, u16)) -> &str {
+        match dataset.get(tag) {
+            Some(Value::Str(_, text)) => text,
+            other => panic!("{tag:04X?}: {other:?}"),
+        }
+    }
+
+    fn items(dataset: &DataSet, tag: (u16, u16)) -> &[DataSet] {
+        match dataset.get(tag) {
+            Some(Value::Sequence(items)) => items,
+            other => panic!("{tag:04X?}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn sections_become_coded_containers_of_text_items() {
+        let markdown = "**TC DE CRÂNIO**\n\n**Indicação clínica:** cefaleia.\n\n**Técnica:**\n\nCortes axiais.\n\n**Encéfalo:**\nSem alterações.\n\n**Impressão:**\n\nNormal.\n\n*Laudo assinado eletronicamente.*";
+        let metadata = SrMetadata {
+            institution_name: "Hospital Modelo".into(),
+            ..Default::default()
+        };
+        let file = template_to_sr(&ReportTemplate::from_markdown(markdown), &metadata, "TC.md");
+        let (meta, dataset) = decode_file(&file);
+        assert_eq!(text(&meta, (0x0002, 0x0002)), BASIC_TEXT_SR_SOP_CLASS_UID);
+        assert_eq!(
+            text(&dataset, (0x0008, 0x0018)),
+            text(&meta, (0x0002, 0x0003))
+        );
+        assert_eq!(text(&dataset, (0x0008, 0x0080)), "Hospital Modelo");
+        assert_eq!(text(&dataset, (0x0008, 0x1030)), "TC DE CRÂNIO");
+        assert_eq!(text(&dataset, (0x0040, 0xA493)), "UNVERIFIED");
+
+        let meaning =
+            |item: &DataSet| text(&items(item, (0x0040, 0xA043))[0], (0x0008, 0x0104)).to_string();
+        let content = items(&dataset, (0x0040, 0xA730));
+        let containers: Vec<(String, Vec<String>)> = content[1..]
+            .iter()
+            .map(|container| {
+                let texts = items(container, (0x0040, 0xA730))
+                    .iter()
+                    .map(|item| text(item, (0x0040, 0xA160)).to_string())
+                    .collect();
+                (meaning(container), texts)
+            })
+            .collect();
+        assert_eq!(
+            meaning(&content[0]),
+            "Language of Content Item and Descendants"
+        );
+        assert_eq!(
+            containers,
+            [
+                ("History".into(), vec!["cefaleia.".into()]),
+                (
+                    "Current Procedure Descriptions".into(),
+                    vec!["Cortes axiais.".into()]
+                ),
+                ("Findings".into(), vec!["Encéfalo:\nSem alterações.".into()]),
+                ("Impressions".into(), vec!["Normal.".into()]),
+            ]
+        );
+    }
+    #[test]
+    fn the_selected_site_is_the_institution_of_the_report() {
+        let mut settings = config::Config::parse(
+            "[placeholders]\ninstitution = \"Grupo Imagem\"\n\n\
+             [sr]\nstation_name = \"RTEMPLATES\"\n\n\
+             [sites.clinica_a]\nname = \"Clínica A\"\n\
+             address = \"Rua das Flores, 100\"\ndepartment = \"Radiologia\"\n",
+        )
+        .unwrap();
+        let template = ReportTemplate::from_markdown("**TC DE CRÂNIO**\n\nNormal.\n");
+        let institution = |settings: &config::Config| {
+            let file = template_to_sr(&template, &SrMetadata::from_config(settings), "TC.md");
+            let (_, dataset) = decode_file(&file);
+            [
+                (0x0008, 0x0080),
+                (0x0008, 0x0081),
+                (0x0008, 0x1040),
+                (0x0008, 0x1010),
+            ]
+            .map(|tag| text(&dataset, tag).to_string())
+        };
+        assert_eq!(
+            institution(&settings),
+            ["Grupo Imagem", "", "", "RTEMPLATES"]
+        );
+        settings.select_site("clinica_a").unwrap();
+        assert_eq!(
+            institution(&settings),
+            [
+                "Clínica A",
+                "Rua das Flores, 100",
+                "Radiologia",
+                "RTEMPLATES"
+            ]
+        );
+    }
+}
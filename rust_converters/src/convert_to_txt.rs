@@ -0,0 +1,215 @@
+use anyhow::Result;
+use log::info;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+use crate::aliases;
+use crate::config;
+use crate::convert::Format;
+use crate::convert_to_markdown::convert_docx_to_markdown;
+use crate::exports;
+use crate::files::{display_name, list_files, output_path};
+use crate::fill::FillOptions;
+use crate::front_matter;
+use crate::markdown::{is_page_break, parse_inline};
+use crate::template::{Paragraph, ReportTemplate};
+use crate::typography;
+
+/// Plain text of a Markdown template: heading `#` markers and the emphasis
+/// markers are removed line by line and backslash escapes resolved, while a `*` or `_`
+/// that is not emphasis (`5*4*3 cm`, `snake_case`) is kept. A link is its
+/// text followed by the URL in parentheses, unless the text is the URL or
+/// the link points inside the document (`#impressao`). Typographic
+/// punctuation is written as ASCII (`typography`), and a page break is a
+/// blank line.
+pub fn clean_markdown_text(text: &str) -> String {
+    let text = text
+        .split('\n')
+        .map(|line| {
+            if is_page_break(line) {
+                return String::new();
+            }
+            let body = line.trim_start();
+            let line = format!(
+                "{}{}",
+                &line[..line.len() - body.len()],
+                body.trim_start_matches('#')
+            );
+            let runs = parse_inline(&line, false);
+            let mut plain = String::new();
+            for group in runs.chunk_by(|a, b| a.link == b.link) {
+                let text: String = group.iter().map(|run| run.text.as_str()).collect();
+                plain.push_str(&text);
+                match &group[0].link {
+                    Some(url) if text.trim() != url && !url.starts_with('#') => {
+                        plain.push_str(&format!(" ({})", url))
+                    }
+                    _ => {}
+                }
+            }
+            plain
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    typography::to_plain(&text, &config::get().txt)
+}
+
+/// The plain text `convert_md_file` writes for `md_path`, ending with the
+/// standard disclaimer when one applies, between the page header and footer
+/// lines of the selected site (see `ReportTemplate::page_parts`).
+pub fn render_md_text(md_path: &Path, fill: &FillOptions) -> Result<String> {
+    let content = fill.read(md_path)?;
+    let text = markdown_text(&content);
+    let (header, footer) = ReportTemplate::from_markdown(&content).page_parts();
+    if header.is_empty() && footer.is_empty() {
+        return Ok(text);
+    }
+    let lines = |part: &[Paragraph]| -> String {
+        part.iter()
+            .map(|paragraph| clean_markdown_text(&paragraph.text).trim().to_string() + "\n")
+            .collect()
+    };
+    let mut page = lines(&header);
+    if !page.is_empty() {
+        page.push('\n');
+    }
+    page.push_str(&text);
+    if !footer.is_empty() {
+        page.push('\n');
+        page.push_str(&lines(&footer));
+    }
+    Ok(page)
+}
+
+/// The plain text of the Markdown template `content`, as `render_md_text`.
+pub fn markdown_text(content: &str) -> String {
+    let text = clean_markdown_text(front_matter::strip(content));
+    match ReportTemplate::from_markdown(content).disclaimer() {
+        Some(disclaimer) => {
+            let body = text.trim_end_matches('\n');
+            format!(
+                "{}\n\n{}{}",
+                body,
+                clean_markdown_text(&disclaimer.text),
+                &text[body.len()..]
+            )
+        }
+        None => text,
+    }
+}
+
+pub fn convert_md_file(md_path: &Path, output_dir: &Path, fill: &FillOptions) -> Result<()> {
+    fs::create_dir_all(output_dir)?;
+    let txt_path = output_path(output_dir, md_path, "txt");
+    fs::write(txt_path, render_md_text(md_path, fill)?)?;
+    Ok(())
+}
+
+/// Converts every `.md` in `md_dir` into a `.txt` in `output_dir`.
+pub fn convert_markdown_folder(md_dir: &Path, output_dir: &Path) -> Result<()> {
+    let md_files = list_files(md_dir, "md")?;
+
+    if md_files.is_empty() {
+        info!("No .md files found in {}", md_dir.display());
+        return Ok(());
+    }
+
+    for md_file in md_files {
+        if !exports::is_exported(&md_file, Format::Txt)? {
+            continue;
+        }
+        convert_md_file(&md_file, output_dir, &FillOptions::default())?;
+        aliases::write(&md_file, &output_path(output_dir, &md_file, "txt"))?;
+        info!(
+            "✓ {} -> {}.txt",
+            display_name(&md_file),
+            md_file.file_stem().unwrap().to_string_lossy()
+        );
+    }
+
+    Ok(())
+}
+
+/// Converts every `.docx` in `docx_dir` to Markdown in a temporary folder,
+/// then to `.txt` in `output_dir`.
+pub fn convert_from_docx(docx_dir: &Path, output_dir: &Path) -> Result<()> {
+    let docx_files = list_files(docx_dir, "docx")?;
+
+    if docx_files.is_empty() {
+        info!("No .docx files found in {}", docx_dir.display());
+        return Ok(());
+    }
+
+    let tmp_dir: TempDir = TempDir::new()?;
+    let tmp_md_dir = tmp_dir.path();
+
+    for docx_file in &docx_files {
+        let markdown_content = convert_docx_to_markdown(docx_file)?;
+        let md_output = output_path(tmp_md_dir, docx_file, "md");
+        fs::write(&md_output, markdown_content)?;
+        info!("Generated temporary: {}", display_name(&md_output));
+    }
+
+    convert_markdown_folder(tmp_md_dir, output_dir)?;
+    // TempDir cleans up automatically when it goes out of scope
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_text_keeps_literal_markers() {
+        assert_eq!(
+            clean_markdown_text("## **Título**\n*Nódulo de 5*4*3 cm* e \\*nota\\*"),
+            " Título\nNódulo de 5*4*3 cm e *nota*"
+        );
+        assert_eq!(
+            clean_markdown_text("fim *\na _ b _\nx __ y __\na ** b **\n30 _\n**\n__"),
+            "fim *\na _ b _\nx __ y __\na ** b **\n30 _\n**\n__"
+        );
+    }
+
+    #[test]
+    fn clean_text_writes_link_targets() {
+        assert_eq!(
+            clean_markdown_text(
+                "Ver [Fleischner **2017**](https://example.org/f), <https://acr.org> \
+                 e [impressão](#impressao)."
+            ),
+            "Ver Fleischner 2017 (https://example.org/f), <https://acr.org> e impressão."
+        );
+        assert_eq!(
+            clean_markdown_text("[https://acr.org](https://acr.org)"),
+            "https://acr.org"
+        );
+    }
+
+    #[test]
+    fn disclaimer_follows_the_footer_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("laudo.md");
+        let front = "---\ndisclaimer: \"*Laudo sujeito a revisão.*\"\n---\n";
+        fs::write(
+            &path,
+            format!("{front}TC DE TÓRAX\n\nNormal.\n\n*Rodapé.*\n"),
+        )
+        .unwrap();
+        assert_eq!(
+            render_md_text(&path, &FillOptions::default()).unwrap(),
+            "TC DE TÓRAX\n\nNormal.\n\nRodapé.\n\nLaudo sujeito a revisão.\n"
+        );
+        // A template already ending with it is left as it is.
+        fs::write(
+            &path,
+            format!("{front}TC DE TÓRAX\n\n_Laudo sujeito a revisão._\n"),
+        )
+        .unwrap();
+        assert_eq!(
+            render_md_text(&path, &FillOptions::default()).unwrap(),
+            "TC DE TÓRAX\n\nLaudo sujeito a revisão.\n"
+        );
+    }
+}
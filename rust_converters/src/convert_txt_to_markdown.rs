@@ -0,0 +1,143 @@
+use anyhow::Result;
+use log::info;
+use std::fs;
+use std::path::Path;
+
+use crate::encoding::read_text;
+use crate::files::{display_name, list_files, output_path};
+use crate::language::{template_language, Language};
+use crate::markdown::spans_to_markdown;
+use crate::rtf::Span;
+
+const SECTION_PREFIXES: &[&str] = &[
+    "técnica do exame:",
+    "aspectos observados:",
+    "impressão:",
+    "informe clínico:",
+    "indicação clínica:",
+    "indicação:",
+];
+
+const ENGLISH_SECTION_PREFIXES: &[&str] = &[
+    "technique:",
+    "findings:",
+    "impression:",
+    "clinical history:",
+    "clinical indication:",
+    "indication:",
+];
+
+const SPANISH_SECTION_PREFIXES: &[&str] = &[
+    "técnica del examen:",
+    "hallazgos:",
+    "impresión:",
+    "información clínica:",
+    "indicación clínica:",
+    "indicación:",
+];
+
+fn find_first_last_nonempty(lines: &[String]) -> Option<(usize, usize)> {
+    let nonempty_indices: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            if line.trim().is_empty() {
+                None
+            } else {
+                Some(i)
+            }
+        })
+        .collect();
+
+    if nonempty_indices.is_empty() {
+        None
+    } else {
+        Some((nonempty_indices[0], *nonempty_indices.last().unwrap()))
+    }
+}
+
+fn should_bold_section(line: &str, language: Language) -> bool {
+    let lowered = line.to_lowercase();
+    let trimmed = lowered.trim();
+
+    let prefixes = match language {
+        Language::Portuguese => SECTION_PREFIXES,
+        Language::English => ENGLISH_SECTION_PREFIXES,
+        Language::Spanish => SPANISH_SECTION_PREFIXES,
+    };
+    if prefixes.iter().any(|prefix| trimmed.starts_with(prefix)) {
+        return true;
+    }
+
+    if trimmed.ends_with(':') && trimmed.len() <= 120 {
+        return true;
+    }
+
+    false
+}
+
+pub fn format_lines_as_markdown(lines: &[String]) -> Vec<String> {
+    let first_last = find_first_last_nonempty(lines);
+    let (first_idx, last_idx) = first_last.unwrap_or((usize::MAX, usize::MAX));
+    let language = template_language(None, &lines.join("\n"));
+
+    let mut output: Vec<String> = Vec::new();
+
+    for (idx, line) in lines.iter().enumerate() {
+        let stripped = line.trim();
+        if stripped.is_empty() {
+            output.push(String::new());
+            continue;
+        }
+
+        let is_first = idx == first_idx;
+        let is_last = idx == last_idx;
+
+        // Asterisks and underscores of the text itself come out escaped.
+        let span = Span {
+            text: stripped.to_string(),
+            italic: is_last,
+            bold: !is_last && (is_first || should_bold_section(stripped, language)),
+            ..Default::default()
+        };
+        output.push(spans_to_markdown(&[span]));
+    }
+
+    output
+}
+
+/// The Markdown `convert_txt_file` writes for `txt_path`.
+pub fn render_txt_file(txt_path: &Path) -> Result<String> {
+    let content = read_text(txt_path)?;
+    let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+    Ok(format_lines_as_markdown(&lines).join("\n"))
+}
+
+pub fn convert_txt_file(txt_path: &Path, output_dir: &Path) -> Result<()> {
+    let markdown = render_txt_file(txt_path)?;
+    fs::create_dir_all(output_dir)?;
+    let md_path = output_path(output_dir, txt_path, "md");
+    fs::write(&md_path, markdown)?;
+    Ok(())
+}
+
+/// Converts every `.txt` in `txt_dir` into a `.md` in `output_dir`.
+pub fn convert_folder(txt_dir: &Path, output_dir: &Path) -> Result<()> {
+    let txt_files = list_files(txt_dir, "txt")?;
+
+    if txt_files.is_empty() {
+        info!("No .txt files found in {}", txt_dir.display());
+        return Ok(());
+    }
+
+    for txt_file in txt_files {
+        convert_txt_file(&txt_file, output_dir)?;
+        info!(
+            "✓ {} -> {}",
+            display_name(&txt_file),
+            display_name(&output_path(output_dir, &txt_file, "md"))
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,220 @@
+//! Duplicate and near-duplicate templates (`rtemplates dedupe`).
+//!
+//! Templates are compared on the plain text of their sections, without the
+//! title, which near-duplicates often spell differently, and the footer,
+//! which most templates share. Text that is the same word for word after
+//! dropping case, emphasis and punctuation is identical; otherwise the
+//! similarity is the Jaccard index of the two sets of three-word shingles.
+//! Templates at or above the threshold with any other member of a cluster
+//! join it.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::encoding::read_text;
+use crate::files::{list_files, FileFilter};
+use crate::sections::parse_sections;
+
+/// Words per shingle.
+const SHINGLE_WORDS: usize = 3;
+
+/// The default similarity from which two templates are reported together.
+pub const DEFAULT_THRESHOLD: f64 = 0.9;
+
+/// What duplicates are compared on.
+struct Fingerprint {
+    /// SHA-256 of the normalized words.
+    hash: String,
+    shingles: HashSet<u64>,
+}
+
+fn fingerprint(markdown: &str) -> Fingerprint {
+    let sections = parse_sections(markdown);
+    let text = [
+        sections.indicacao_clinica,
+        sections.tecnica,
+        sections.aspectos_observados,
+        sections.impressao,
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join("\n")
+    .to_lowercase();
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    let hash = format!("{:x}", Sha256::digest(words.join(" ")));
+    let shingles = words
+        .windows(SHINGLE_WORDS.min(words.len()).max(1))
+        .map(|shingle| {
+            let mut hasher = DefaultHasher::new();
+            shingle.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect();
+    Fingerprint { hash, shingles }
+}
+
+fn similarity(a: &Fingerprint, b: &Fingerprint) -> f64 {
+    if a.hash == b.hash {
+        return 1.0;
+    }
+    let union = a.shingles.union(&b.shingles).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.shingles.intersection(&b.shingles).count() as f64 / union as f64
+}
+
+/// Templates reported as copies of each other.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cluster {
+    /// Whether every member has the same text.
+    pub identical: bool,
+    /// Each template, by path, with its similarity to the first.
+    pub members: Vec<(PathBuf, f64)>,
+}
+
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Groups the Markdown templates in `dir` selected by `files` whose
+/// similarity is at least `threshold`, from 0 to 1. Clusters come in path
+/// order.
+pub fn find_duplicates(dir: &Path, threshold: f64, files: &FileFilter) -> Result<Vec<Cluster>> {
+    let mut paths = Vec::new();
+    let mut prints = Vec::new();
+    for path in list_files(dir, "md")? {
+        if !files.matches(path.strip_prefix(dir).unwrap_or(&path)) {
+            continue;
+        }
+        let content = read_text(&path)?;
+        let print = fingerprint(&content);
+        // Templates without section text would all match each other.
+        if print.shingles.is_empty() {
+            continue;
+        }
+        paths.push(path);
+        prints.push(print);
+    }
+
+    let mut parents: Vec<usize> = (0..paths.len()).collect();
+    for i in 0..prints.len() {
+        for j in i + 1..prints.len() {
+            let (small, large) = (prints[i].shingles.len(), prints[j].shingles.len());
+            // The index cannot exceed the ratio of the two sizes.
+            if prints[i].hash != prints[j].hash
+                && (small.min(large) as f64) < threshold * small.max(large) as f64
+            {
+                continue;
+            }
+            if similarity(&prints[i], &prints[j]) >= threshold {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut clusters = Vec::new();
+    for first in 0..paths.len() {
+        if root(&mut parents, first) != first {
+            continue;
+        }
+        let members: Vec<usize> = (first..paths.len())
+            .filter(|&i| root(&mut parents, i) == first)
+            .collect();
+        if members.len() < 2 {
+            continue;
+        }
+        clusters.push(Cluster {
+            identical: members
+                .iter()
+                .all(|&i| prints[i].hash == prints[first].hash),
+            members: members
+                .iter()
+                .map(|&i| (paths[i].clone(), similarity(&prints[first], &prints[i])))
+                .collect(),
+        });
+    }
+    Ok(clusters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn near_copies_are_clustered() {
+        let dir = tempfile::tempdir().unwrap();
+        let findings = "Fígado de dimensões normais e contornos regulares. Vesícula biliar \
+                        normodistendida, de paredes finas. Vias biliares sem dilatação. \
+                        Pâncreas de aspecto habitual. Baço homogêneo. Rins tópicos, sem \
+                        cálculos ou hidronefrose. Bexiga com paredes regulares.";
+        let template = |title: &str, findings: &str, impression: &str| {
+            format!("{title}\n\n**Análise:**\n\n{findings}\n\n**Impressão:**\n\n{impression}\n\n*Rodapé.*\n")
+        };
+        let files = [
+            (
+                "US Abdome.md",
+                template("US DE ABDOME", findings, "Exame normal."),
+            ),
+            // Another title and emphasis: the same text.
+            (
+                "US Abdome (1).md",
+                template(
+                    "**USG ABDOME TOTAL**",
+                    &findings.to_uppercase(),
+                    "*Exame normal.*",
+                ),
+            ),
+            // One word changed.
+            (
+                "US Abdome v2.md",
+                template(
+                    "US DE ABDOME",
+                    &findings.replace("Baço", "Baço de"),
+                    "Exame normal.",
+                ),
+            ),
+            (
+                "TC Crânio.md",
+                template("TC DE CRÂNIO", "Parênquima encefálico normal.", "Normal."),
+            ),
+        ];
+        for (name, content) in &files {
+            std::fs::write(dir.path().join(name), content).unwrap();
+        }
+
+        let clusters = find_duplicates(dir.path(), 0.8, &FileFilter::default()).unwrap();
+        assert_eq!(clusters.len(), 1);
+        let names: Vec<String> = clusters[0]
+            .members
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            ["US Abdome (1).md", "US Abdome v2.md", "US Abdome.md"]
+        );
+        assert!(!clusters[0].identical);
+        assert_eq!(clusters[0].members[2].1, 1.0);
+        assert!(clusters[0].members[1].1 > 0.8 && clusters[0].members[1].1 < 1.0);
+
+        let strict = find_duplicates(dir.path(), 1.0, &FileFilter::default()).unwrap();
+        assert_eq!(strict.len(), 1);
+        assert!(strict[0].identical);
+        assert_eq!(strict[0].members.len(), 2);
+    }
+}
@@ -0,0 +1,287 @@
+//! Minimal DICOM Part 10 writer (Explicit VR Little Endian), enough to
+//! encode structured report objects: strings, sequences and the file meta
+//! header.
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1";
+/// UUID-derived root (`2.25`) identifying this implementation.
+pub const IMPLEMENTATION_CLASS_UID: &str = "2.25.173730441363781871625514913539238750988";
+pub const IMPLEMENTATION_VERSION_NAME: &str = "RTEMPLATES_01";
+
+/// A data element tag, `(group, element)`.
+pub type Tag = (u16, u16);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    /// A string value with its two-letter VR (`CS`, `LO`, `UT`, ...).
+    Str(&'static str, String),
+    /// An unsigned 32-bit value (`UL`).
+    U32(u32),
+    Bytes(Vec<u8>),
+    Sequence(Vec<DataSet>),
+}
+
+/// Elements keyed by tag, so they are always written in ascending order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DataSet {
+    elements: BTreeMap<Tag, Value>,
+}
+
+impl DataSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(&mut self, tag: Tag, value: Value) -> &mut Self {
+        self.elements.insert(tag, value);
+        self
+    }
+
+    pub fn str(&mut self, tag: Tag, vr: &'static str, value: impl Into<String>) -> &mut Self {
+        self.put(tag, Value::Str(vr, value.into()))
+    }
+
+    pub fn sequence(&mut self, tag: Tag, items: Vec<DataSet>) -> &mut Self {
+        self.put(tag, Value::Sequence(items))
+    }
+
+    pub fn get(&self, tag: Tag) -> Option<&Value> {
+        self.elements.get(&tag)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (&tag, value) in &self.elements {
+            encode_element(&mut out, tag, value);
+        }
+        out
+    }
+}
+
+fn push_tag(out: &mut Vec<u8>, (group, element): Tag) {
+    out.extend_from_slice(&group.to_le_bytes());
+    out.extend_from_slice(&element.to_le_bytes());
+}
+
+/// VRs with a 2-byte reserved field and a 32-bit length.
+fn has_long_length(vr: &str) -> bool {
+    matches!(vr, "OB" | "OW" | "OF" | "SQ" | "UT" | "UN" | "UC" | "UR")
+}
+
+fn encode_element(out: &mut Vec<u8>, tag: Tag, value: &Value) {
+    let (vr, body) = match value {
+        Value::Str(vr, text) => {
+            let mut bytes = text.as_bytes().to_vec();
+            // Values have even length: UIDs pad with NUL, text with space.
+            if bytes.len() % 2 == 1 {
+                bytes.push(if *vr == "UI" { 0 } else { b' ' });
+            }
+            (*vr, bytes)
+        }
+        Value::U32(n) => ("UL", n.to_le_bytes().to_vec()),
+        Value::Bytes(bytes) => {
+            let mut bytes = bytes.clone();
+            if bytes.len() % 2 == 1 {
+                bytes.push(0);
+            }
+            ("OB", bytes)
+        }
+        Value::Sequence(items) => {
+            let mut body = Vec::new();
+            for item in items {
+                let encoded = item.encode();
+                push_tag(&mut body, (0xFFFE, 0xE000));
+                body.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+                body.extend_from_slice(&encoded);
+            }
+            ("SQ", body)
+        }
+    };
+
+    push_tag(out, tag);
+    out.extend_from_slice(vr.as_bytes());
+    if has_long_length(vr) {
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    } else {
+        out.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    }
+    out.extend_from_slice(&body);
+}
+
+/// A code sequence item: `(0008,0100)` value, `(0008,0102)` scheme and
+/// `(0008,0104)` meaning.
+pub fn code(value: &str, scheme: &str, meaning: &str) -> DataSet {
+    let mut item = DataSet::new();
+    item.str((0x0008, 0x0100), "SH", value)
+        .str((0x0008, 0x0102), "SH", scheme)
+        .str((0x0008, 0x0104), "LO", meaning);
+    item
+}
+
+/// A new UID under the UUID-derived root `2.25`, hashed from `seed`, the
+/// clock and a process-wide counter so repeated calls never collide.
+pub fn generate_uid(seed: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let mut hasher = Sha256::new();
+    hasher.update(seed.as_bytes());
+    hasher.update(nanos.to_le_bytes());
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    format!("2.25.{}", u128::from_be_bytes(bytes))
+}
+
+/// Encodes a Part 10 file: preamble, `DICM`, the group 0002 meta header and
+/// the data set.
+pub fn write_file(sop_class_uid: &str, sop_instance_uid: &str, dataset: &DataSet) -> Vec<u8> {
+    let mut meta = DataSet::new();
+    meta.put((0x0002, 0x0001), Value::Bytes(vec![0x00, 0x01]))
+        .str((0x0002, 0x0002), "UI", sop_class_uid)
+        .str((0x0002, 0x0003), "UI", sop_instance_uid)
+        .str((0x0002, 0x0010), "UI", EXPLICIT_VR_LITTLE_ENDIAN)
+        .str((0x0002, 0x0012), "UI", IMPLEMENTATION_CLASS_UID)
+        .str((0x0002, 0x0013), "SH", IMPLEMENTATION_VERSION_NAME);
+    let meta_bytes = meta.encode();
+
+    let mut out = vec![0u8; 128];
+    out.extend_from_slice(b"DICM");
+    encode_element(
+        &mut out,
+        (0x0002, 0x0000),
+        &Value::U32(meta_bytes.len() as u32),
+    );
+    out.extend_from_slice(&meta_bytes);
+    out.extend_from_slice(&dataset.encode());
+    out
+}
+
+/// Reads a data set back as `encode` writes it, with string values
+/// unpadded; for the tests only, the writer being all the exports need.
+#[cfg(test)]
+pub(crate) fn decode(mut bytes: &[u8]) -> DataSet {
+    const STRING_VRS: [&str; 16] = [
+        "AE", "AS", "CS", "DA", "DS", "DT", "IS", "LO", "LT", "PN", "SH", "ST", "TM", "UI", "UT",
+        "UC",
+    ];
+    let u16_at = |bytes: &[u8], at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+    let u32_at = |bytes: &[u8], at: usize| {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    };
+    let mut dataset = DataSet::new();
+    while !bytes.is_empty() {
+        let tag = (u16_at(bytes, 0), u16_at(bytes, 2));
+        let vr = std::str::from_utf8(&bytes[4..6]).unwrap();
+        let (start, len) = if has_long_length(vr) {
+            (12, u32_at(bytes, 8) as usize)
+        } else {
+            (8, u16_at(bytes, 6) as usize)
+        };
+        let body = &bytes[start..start + len];
+        let value = match vr {
+            "UL" => Value::U32(u32_at(body, 0)),
+            "OB" => Value::Bytes(body.to_vec()),
+            "SQ" => {
+                let mut items = Vec::new();
+                let mut rest = body;
+                while !rest.is_empty() {
+                    assert_eq!((u16_at(rest, 0), u16_at(rest, 2)), (0xFFFE, 0xE000));
+                    let len = u32_at(rest, 4) as usize;
+                    items.push(decode(&rest[8..8 + len]));
+                    rest = &rest[8 + len..];
+                }
+                Value::Sequence(items)
+            }
+            _ => {
+                let vr = STRING_VRS.iter().find(|known| **known == vr).unwrap();
+                let text = std::str::from_utf8(body).unwrap();
+                let pad = if *vr == "UI" { '\0' } else { ' ' };
+                Value::Str(vr, text.strip_suffix(pad).unwrap_or(text).to_string())
+            }
+        };
+        dataset.put(tag, value);
+        bytes = &bytes[start + len..];
+    }
+    dataset
+}
+
+/// The meta header and the data set of a Part 10 file; for the tests only.
+#[cfg(test)]
+pub(crate) fn decode_file(bytes: &[u8]) -> (DataSet, DataSet) {
+    assert!(bytes[..128].iter().all(|&b| b == 0));
+    assert_eq!(&bytes[128..132], b"DICM");
+    let Some(&Value::U32(meta_len)) = decode(&bytes[132..144]).get((0x0002, 0x0000)) else {
+        panic!("no group length");
+    };
+    let meta_end = 144 + meta_len as usize;
+    (decode(&bytes[144..meta_end]), decode(&bytes[meta_end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elements_are_padded_to_even_length_and_read_back() {
+        let bytes = |tag, vr, text: &str| {
+            let mut dataset = DataSet::new();
+            dataset.str(tag, vr, text);
+            dataset.encode()
+        };
+        assert_eq!(
+            bytes((0x0008, 0x0080), "LO", "HCX"),
+            b"\x08\x00\x80\x00LO\x04\x00HCX "
+        );
+        assert_eq!(
+            bytes((0x0008, 0x0018), "UI", "1.2"),
+            b"\x08\x00\x18\x00UI\x04\x001.2\0"
+        );
+        assert_eq!(
+            bytes((0x0040, 0xA160), "UT", "ok"),
+            b"\x40\x00\x60\xA1UT\0\0\x02\x00\x00\x00ok"
+        );
+
+        let mut dataset = DataSet::new();
+        dataset
+            .str((0x0040, 0xA160), "UT", "Nódulo de 5 mm")
+            .str((0x0008, 0x0060), "CS", "SR")
+            .sequence(
+                (0x0040, 0xA043),
+                vec![
+                    code("121070", "DCM", "Findings"),
+                    code("121071", "DCM", "Finding"),
+                ],
+            );
+        let encoded = dataset.encode();
+        assert_eq!(encoded.len() % 2, 0);
+        // Written in ascending tag order whatever order they were put in.
+        assert_eq!(&encoded[..4], b"\x08\x00\x60\x00");
+        assert_eq!(decode(&encoded), dataset);
+
+        let file = write_file("1.2.3", "1.2.3.4", &dataset);
+        let (meta, body) = decode_file(&file);
+        assert_eq!(
+            meta.get((0x0002, 0x0010)),
+            Some(&Value::Str("UI", EXPLICIT_VR_LITTLE_ENDIAN.into()))
+        );
+        assert_eq!(
+            meta.get((0x0002, 0x0003)),
+            Some(&Value::Str("UI", "1.2.3.4".into()))
+        );
+        assert_eq!(body, dataset);
+
+        let uid = generate_uid("TC");
+        assert!(uid.starts_with("2.25.") && uid.len() <= 64);
+        assert_ne!(uid, generate_uid("TC"));
+    }
+}
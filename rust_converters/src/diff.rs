@@ -0,0 +1,323 @@
+//! Section-level comparison of two templates (`rtemplates diff`).
+//!
+//! Both files are read as `ReportTemplate`s, whatever their format, and
+//! matched part by part: the front matter, the title, each recognised
+//! section by its kind (so `IMPRESSÃO` and `**Conclusão:**` are the same
+//! section), other headings and `**Tórax:**` subsections by their text, and
+//! the footer. The lines of a
+//! part found on both sides are compared with a longest common subsequence,
+//! so a changed sentence in the impression shows as that line alone.
+
+use anyhow::{bail, Result};
+use std::path::Path;
+
+use crate::convert::{self, Format};
+use crate::convert_to_markdown::rows_to_markdown;
+use crate::encoding::read_text;
+use crate::fill::FillOptions;
+use crate::template::{Content, Paragraph, ReportTemplate, Section};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionStatus {
+    Added,
+    Removed,
+    Modified,
+    Unchanged,
+}
+
+impl SectionStatus {
+    /// The marker printed before the section name.
+    fn marker(self) -> char {
+        match self {
+            SectionStatus::Added => '+',
+            SectionStatus::Removed => '-',
+            SectionStatus::Modified => '~',
+            SectionStatus::Unchanged => '=',
+        }
+    }
+}
+
+/// One Markdown line of a part, and which side has it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SectionDiff {
+    /// The heading as written (without the colon and any text after it),
+    /// or `Front matter`, `Title` and `Footer`.
+    pub name: String,
+    pub status: SectionStatus,
+    pub lines: Vec<Line>,
+}
+
+/// A part of a template, with the key it is matched on.
+struct Part {
+    key: String,
+    name: String,
+    lines: Vec<String>,
+}
+
+/// The name a heading line is shown with: its text before the colon.
+fn heading_name(heading: &Paragraph) -> Option<String> {
+    let text = heading.plain();
+    let name = text.split(':').next().unwrap_or_default().trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// A section as parts: the heading with the text up to the first bold
+/// `Name:` subsection (`**Tórax:**`), then each subsection, keyed under the
+/// section's key.
+fn section_parts(section: &Section) -> Vec<Part> {
+    let name = section.heading.as_ref().and_then(heading_name);
+    let key = match (section.kind, &name) {
+        (Some(kind), _) => kind.key().to_string(),
+        (None, Some(name)) => name.to_lowercase(),
+        (None, None) => String::new(),
+    };
+    let mut parts = vec![Part {
+        key: key.clone(),
+        name: name
+            .or_else(|| section.kind.map(|kind| kind.label().to_string()))
+            .unwrap_or_else(|| "(before the first heading)".to_string()),
+        lines: section.heading.iter().map(Paragraph::to_markdown).collect(),
+    }];
+    for content in &section.content {
+        match content {
+            Content::Paragraph(paragraph) if paragraph.is_subsection() => {
+                let name = heading_name(paragraph).unwrap_or_default();
+                parts.push(Part {
+                    key: format!("{}/{}", key, name.to_lowercase()),
+                    name,
+                    lines: vec![paragraph.to_markdown()],
+                });
+            }
+            Content::Paragraph(paragraph) if !paragraph.is_blank() => parts
+                .last_mut()
+                .unwrap()
+                .lines
+                .push(paragraph.to_markdown()),
+            Content::Paragraph(_) => {}
+            Content::Table { header, rows } => {
+                let all_rows: Vec<Vec<String>> =
+                    std::iter::once(header).chain(rows).cloned().collect();
+                parts.last_mut().unwrap().lines.extend(
+                    rows_to_markdown(&all_rows)
+                        .into_iter()
+                        .filter(|line| !line.is_empty()),
+                );
+            }
+        }
+    }
+    parts.retain(|part| !part.lines.is_empty());
+    parts
+}
+
+fn parts(template: &ReportTemplate) -> Vec<Part> {
+    let mut parts = Vec::new();
+    if let Some(block) = &template.front_matter {
+        let lines = block
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && *line != "---")
+            .map(str::to_string)
+            .collect();
+        parts.push(Part {
+            key: "front_matter".to_string(),
+            name: "Front matter".to_string(),
+            lines,
+        });
+    }
+    if let Some(title) = &template.title {
+        parts.push(Part {
+            key: "titulo".to_string(),
+            name: "Title".to_string(),
+            lines: vec![title.to_markdown()],
+        });
+    }
+    for mut part in template.sections.iter().flat_map(section_parts) {
+        // A heading used twice is told apart by its position.
+        let repeats = parts.iter().filter(|p| p.key == part.key).count();
+        if repeats > 0 {
+            part.key = format!("{}#{}", part.key, repeats + 1);
+        }
+        parts.push(part);
+    }
+    if let Some(footer) = &template.footer {
+        parts.push(Part {
+            key: "rodape".to_string(),
+            name: "Footer".to_string(),
+            lines: vec![footer.to_markdown()],
+        });
+    }
+    parts
+}
+
+/// Lines of `old` and `new` in order, each marked as kept, removed or added,
+/// keeping a longest common subsequence of the two.
+pub(crate) fn diff_lines(old: &[String], new: &[String]) -> Vec<Line> {
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same(old[i].clone()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(Line::Removed(old[i].clone()));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j].clone()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// The parts of `old` and `new`, in the order of `new` with removed parts
+/// placed after the part they followed in `old`.
+pub fn diff_templates(old: &ReportTemplate, new: &ReportTemplate) -> Vec<SectionDiff> {
+    let (old, new) = (parts(old), parts(new));
+    let removed = |part: &Part| SectionDiff {
+        name: part.name.clone(),
+        status: SectionStatus::Removed,
+        lines: part.lines.iter().cloned().map(Line::Removed).collect(),
+    };
+    let kept = |part: &Part| new.iter().any(|p| p.key == part.key);
+    // Removed parts following the old part at `index`, up to the next kept one.
+    let removed_after = |index: usize| {
+        old[index..]
+            .iter()
+            .take_while(|part| !kept(part))
+            .map(removed)
+            .collect::<Vec<_>>()
+    };
+
+    let mut diffs = removed_after(0);
+    for part in &new {
+        let Some(index) = old.iter().position(|p| p.key == part.key) else {
+            diffs.push(SectionDiff {
+                name: part.name.clone(),
+                status: SectionStatus::Added,
+                lines: part.lines.iter().cloned().map(Line::Added).collect(),
+            });
+            continue;
+        };
+        let lines = diff_lines(&old[index].lines, &part.lines);
+        let status = if lines.iter().all(|line| matches!(line, Line::Same(_))) {
+            SectionStatus::Unchanged
+        } else {
+            SectionStatus::Modified
+        };
+        diffs.push(SectionDiff {
+            name: part.name.clone(),
+            status,
+            lines,
+        });
+        diffs.extend(removed_after(index + 1));
+    }
+    diffs
+}
+
+/// Reads a template in any format the converters read: Markdown as
+/// written (front matter and `{{...}}` placeholders included), the others
+/// through their Markdown importer.
+pub fn read_template(path: &Path) -> Result<ReportTemplate> {
+    let markdown = match convert::format_of(path) {
+        Some(Format::Md) => read_text(path)?,
+        Some(format) => convert::render_text(format, Format::Md, path, &FillOptions::default())?,
+        None => bail!("Unknown template format: {}", path.display()),
+    };
+    Ok(ReportTemplate::from_markdown(&markdown))
+}
+
+/// Compares the templates at `old` and `new`.
+pub fn diff_files(old: &Path, new: &Path) -> Result<Vec<SectionDiff>> {
+    Ok(diff_templates(&read_template(old)?, &read_template(new)?))
+}
+
+pub(crate) const REMOVED_COLOR: &str = "\x1b[31m";
+pub(crate) const ADDED_COLOR: &str = "\x1b[32m";
+pub(crate) const RESET: &str = "\x1b[0m";
+
+/// `diffs` as printed by `rtemplates diff`: a `+`, `-` or `~` line per
+/// added, removed or modified part and its lines marked the same way, with
+/// unchanged parts only when `all` is set. `color` adds ANSI escapes.
+pub fn format_diff(diffs: &[SectionDiff], all: bool, color: bool) -> String {
+    let mut out = String::new();
+    for diff in diffs {
+        if diff.status == SectionStatus::Unchanged && !all {
+            continue;
+        }
+        out.push_str(&format!("{} {}\n", diff.status.marker(), diff.name));
+        for line in &diff.lines {
+            let (marker, line_color, text) = match line {
+                Line::Same(text) => (' ', "", text),
+                Line::Removed(text) => ('-', REMOVED_COLOR, text),
+                Line::Added(text) => ('+', ADDED_COLOR, text),
+            };
+            if color && !line_color.is_empty() {
+                out.push_str(&format!("  {line_color}{marker} {text}{RESET}\n"));
+            } else {
+                out.push_str(&format!("  {marker} {text}\n"));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_are_matched_by_kind_and_heading() {
+        let old = ReportTemplate::from_markdown(
+            "TC DE TÓRAX\n\n**Técnica do exame:** Cortes axiais.\n\n**Análise:**\n\n\
+             Pulmões normais.\n\n**Nota:**\n\nRevisar.\n\n**Impressão:**\n\n\
+             Exame normal.\n\nSem nódulos.\n\n*Rodapé.*\n",
+        );
+        let new = ReportTemplate::from_markdown(
+            "TC DE TÓRAX\n\n**Técnica do exame:** Cortes axiais.\n\n**Análise:**\n\n\
+             Pulmões normais.\n\n**CONCLUSÃO:**\n\nExame sem alterações.\n\nSem nódulos.\n\n\
+             **Comparação:**\n\nNão há.\n\n*Rodapé.*\n",
+        );
+        let diffs = diff_templates(&old, &new);
+        let summary: Vec<(&str, SectionStatus)> = diffs
+            .iter()
+            .map(|diff| (diff.name.as_str(), diff.status))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Title", SectionStatus::Unchanged),
+                ("Técnica do exame", SectionStatus::Unchanged),
+                ("Análise", SectionStatus::Unchanged),
+                ("Nota", SectionStatus::Removed),
+                ("CONCLUSÃO", SectionStatus::Modified),
+                ("Comparação", SectionStatus::Added),
+                ("Footer", SectionStatus::Unchanged),
+            ]
+        );
+        assert_eq!(
+            format_diff(&diffs[4..5], false, false),
+            "~ CONCLUSÃO\n  - **Impressão:**\n  - Exame normal.\n  + **CONCLUSÃO:**\n  \
+             + Exame sem alterações.\n    Sem nódulos.\n"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Paragraph-level comparison of two DOCX files (`rtemplates docx-diff`),
+//! so that a pull request changing a Word template can be reviewed from
+//! what changed in it rather than from two binary blobs.
+//!
+//! Each file is read one body paragraph per line (see
+//! `convert_to_markdown::docx_paragraphs`): its text as Markdown, keeping
+//! bold, italics, underline and heading and list markers, after its style
+//! and alignment. The lines are compared with a longest common subsequence
+//! and printed as unified-diff hunks. `docx-diff --textconv` prints the
+//! lines of one file, for git to diff DOCX files itself.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::convert_to_markdown::docx_paragraphs;
+use crate::diff::{diff_lines, Line, ADDED_COLOR, REMOVED_COLOR, RESET};
+
+/// The paragraphs of `path`, one per line.
+pub fn paragraphs(path: &Path) -> Result<Vec<String>> {
+    docx_paragraphs(path).with_context(|| format!("Cannot read {}", path.display()))
+}
+
+/// The paragraphs of `old` and `new`, matched line by line.
+pub fn diff_docx(old: &Path, new: &Path) -> Result<Vec<Line>> {
+    Ok(diff_lines(&paragraphs(old)?, &paragraphs(new)?))
+}
+
+/// Whether `lines` has any paragraph on one side only.
+pub fn has_changes(lines: &[Line]) -> bool {
+    lines.iter().any(|line| !matches!(line, Line::Same(_)))
+}
+
+/// `lines` as unified-diff hunks, each changed paragraph with up to
+/// `context` unchanged ones around it. `color` adds ANSI escapes.
+pub fn format_hunks(lines: &[Line], context: usize, color: bool) -> String {
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&k| !matches!(lines[k], Line::Same(_)))
+        .collect();
+
+    // Spans of `lines`, end excluded, merged when their context touches.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &k in &changed {
+        let start = k.saturating_sub(context);
+        let end = (k + 1 + context).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut out = String::new();
+    for (start, end) in hunks {
+        let before = &lines[..start];
+        let old_start = before
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_start = before
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        let hunk = &lines[start..end];
+        let old_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_count > 0),
+            old_count,
+            new_start + usize::from(new_count > 0),
+            new_count
+        ));
+        for line in hunk {
+            let (marker, line_color, text) = match line {
+                Line::Same(text) => (' ', "", text),
+                Line::Removed(text) => ('-', REMOVED_COLOR, text),
+                Line::Added(text) => ('+', ADDED_COLOR, text),
+            };
+            if color && !line_color.is_empty() {
+                out.push_str(&format!("{line_color}{marker}{text}{RESET}\n"));
+            } else {
+                out.push_str(&format!("{marker}{text}\n"));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert_to_docx::convert_file;
+    use crate::fill::FillOptions;
+    use std::fs;
+
+    #[test]
+    fn paragraphs_are_compared_with_their_formatting() {
+        let dir = tempfile::tempdir().unwrap();
+        let docx = |name: &str, markdown: &str| {
+            let source = dir.path().join(format!("{name}.md"));
+            let target = dir.path().join(format!("{name}.docx"));
+            fs::write(&source, markdown).unwrap();
+            convert_file(&source, &target, &FillOptions::default()).unwrap();
+            target
+        };
+        let old = docx(
+            "old",
+            "**US ABDOME**\n\n**Análise:**\n\nFígado normal.\n\nBaço normal.\n\n\
+             Rins normais.\n\nBexiga normal.\n\n**Impressão:**\n\nExame normal.\n",
+        );
+        let new = docx(
+            "new",
+            "**US ABDOME**\n\n**Análise:**\n\nFígado *esteatótico*.\n\nBaço normal.\n\n\
+             Rins normais.\n\nBexiga normal.\n\n**Impressão:**\n\nExame normal.\n",
+        );
+
+        assert!(!has_changes(&diff_docx(&old, &old).unwrap()));
+        let lines = diff_docx(&old, &new).unwrap();
+        assert!(has_changes(&lines));
+        let hunks = format_hunks(&lines, 1, false);
+        assert!(hunks.starts_with("@@ -2,3 +2,3 @@\n"), "{hunks}");
+        assert!(hunks.contains(
+            "\n-[both] Fígado normal.\n+[both] Fígado *esteatótico*.\n [both] Baço normal.\n"
+        ));
+        assert_eq!(hunks.matches("@@").count(), 2);
+    }
+}
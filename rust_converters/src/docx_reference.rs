@@ -0,0 +1,441 @@
+//! DOCX output built on a reference document (`[docx] reference`), such as
+//! the official letterhead `.dotx`.
+//!
+//! The package `write_docx` builds is laid over the reference one: the
+//! reference keeps its styles, theme, settings, page header and footer and
+//! page setup (the body's last `w:sectPr`), and the Markdown replaces its
+//! body and footnotes. Images, lists, document properties, and styles the
+//! reference does not define are added to it. The parts are edited as text, as
+//! `convert_to_markdown` does, since docx-rust drops what it does not know
+//! when it reads a package and writes it back.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const DOCUMENT: &str = "word/document.xml";
+const DOCUMENT_RELS: &str = "word/_rels/document.xml.rels";
+const STYLES: &str = "word/styles.xml";
+const NUMBERING: &str = "word/numbering.xml";
+const FOOTNOTES: &str = "word/footnotes.xml";
+const FOOTNOTES_RELS: &str = "word/_rels/footnotes.xml.rels";
+const CONTENT_TYPES: &str = "[Content_Types].xml";
+const PACKAGE_RELS: &str = "_rels/.rels";
+const CORE: &str = "docProps/core.xml";
+const TEMPLATE_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.template.main+xml";
+const DOCUMENT_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml";
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid regex"))
+}
+
+/// The parts of a reference `.docx` or `.dotx`, in package order.
+pub struct Reference {
+    parts: Vec<(String, Vec<u8>)>,
+}
+
+impl Reference {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = fs::File::open(path)
+            .with_context(|| format!("Cannot read reference document {}", path.display()))?;
+        let parts = read_package(file)
+            .with_context(|| format!("Not a DOCX package: {}", path.display()))?;
+        Ok(Reference { parts })
+    }
+
+    fn part(&self, name: &str) -> Option<String> {
+        self.parts
+            .iter()
+            .find(|(part, _)| part == name)
+            .map(|(_, data)| String::from_utf8_lossy(data).into_owned())
+    }
+
+    /// The IDs of the styles the reference defines.
+    pub fn style_ids(&self) -> HashSet<String> {
+        self.part(STYLES)
+            .map_or_else(HashSet::new, |styles| style_ids(&styles))
+    }
+
+    /// The highest `N` of the reference's `word/media/imageN.*` files;
+    /// added images are numbered after it.
+    pub fn last_image_number(&self) -> usize {
+        static IMAGE: OnceLock<Regex> = OnceLock::new();
+        let image = regex(&IMAGE, r"^word/media/image(\d+)\.");
+        self.parts
+            .iter()
+            .filter_map(|(name, _)| image.captures(name)?[1].parse().ok())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// A number above the reference's list IDs, both `abstractNumId` and
+    /// `numId`, for added lists to start from.
+    pub fn first_list_id(&self) -> isize {
+        static ID: OnceLock<Regex> = OnceLock::new();
+        let id = regex(&ID, r#"w:(?:abstractNumId|numId)="(\d+)""#);
+        self.part(NUMBERING).map_or(0, |numbering| {
+            id.captures_iter(&numbering)
+                .filter_map(|caps| caps[1].parse::<isize>().ok())
+                .map(|id| id + 1)
+                .max()
+                .unwrap_or(0)
+        })
+    }
+
+    /// Writes `generated`, the package `write_docx` built, over this one.
+    pub fn merge(&self, generated: &[u8], output_path: &Path) -> Result<()> {
+        let generated: HashMap<String, Vec<u8>> =
+            read_package(Cursor::new(generated))?.into_iter().collect();
+        let text = |name: &str| {
+            generated
+                .get(name)
+                .map(|data| String::from_utf8_lossy(data).into_owned())
+        };
+        let numbering = text(NUMBERING);
+        let footnotes = generated.contains_key(FOOTNOTES);
+
+        let mut parts: Vec<(&str, Vec<u8>)> = Vec::new();
+        for (name, data) in &self.parts {
+            let reference = String::from_utf8_lossy(data);
+            let merged = match name.as_str() {
+                DOCUMENT => text(DOCUMENT).map(|body| with_section(&body, &reference)),
+                DOCUMENT_RELS => text(DOCUMENT_RELS).map(|rels| {
+                    merged_relationships(&reference, &rels, numbering.is_some(), footnotes)
+                }),
+                STYLES => text(STYLES).map(|styles| merged_styles(&reference, &styles)),
+                NUMBERING => numbering
+                    .as_deref()
+                    .map(|numbering| merged_numbering(&reference, numbering)),
+                CONTENT_TYPES => {
+                    text(CONTENT_TYPES).map(|types| merged_content_types(&reference, &types))
+                }
+                PACKAGE_RELS if !reference.contains("/core-properties\"") => text(PACKAGE_RELS)
+                    .map(|rels| {
+                        let core = relationships(&rels, |ty| ty.ends_with("/core-properties"));
+                        insert_before(&reference, "</Relationships>", &core)
+                    }),
+                CORE | FOOTNOTES | FOOTNOTES_RELS => text(name),
+                _ => None,
+            };
+            parts.push((
+                name,
+                merged.map_or_else(|| data.clone(), String::into_bytes),
+            ));
+        }
+        // New images, and the numbering, footnotes and properties the
+        // reference lacks.
+        let mut added: Vec<&String> = generated
+            .keys()
+            .filter(|name| {
+                (name.starts_with("word/media/")
+                    || [NUMBERING, FOOTNOTES, FOOTNOTES_RELS, CORE].contains(&name.as_str()))
+                    && !self.parts.iter().any(|(part, _)| part == *name)
+            })
+            .collect();
+        added.sort();
+        parts.extend(
+            added
+                .into_iter()
+                .map(|name| (name.as_str(), generated[name].clone())),
+        );
+
+        let mut package = ZipWriter::new(fs::File::create(output_path)?);
+        for (name, data) in parts {
+            package.start_file(name, SimpleFileOptions::default())?;
+            package.write_all(&data)?;
+        }
+        package.finish()?;
+        Ok(())
+    }
+}
+
+pub(crate) fn read_package<R: Read + std::io::Seek>(reader: R) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let mut parts = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        parts.push((entry.name().to_string(), data));
+    }
+    Ok(parts)
+}
+
+fn insert_before(xml: &str, end_tag: &str, addition: &str) -> String {
+    match xml.rfind(end_tag) {
+        Some(at) => format!("{}{}{}", &xml[..at], addition, &xml[at..]),
+        None => xml.to_string(),
+    }
+}
+
+/// The generated document with the reference body's section properties,
+/// which link its header and footer and set the page.
+fn with_section(generated: &str, reference: &str) -> String {
+    match (body_section(reference), body_section(generated)) {
+        (Some(section), Some(own)) => format!(
+            "{}{}{}",
+            &generated[..own.start],
+            &reference[section],
+            &generated[own.end..]
+        ),
+        (Some(section), None) => insert_before(generated, "</w:body>", &reference[section]),
+        (None, _) => generated.to_string(),
+    }
+}
+
+/// Where the `w:sectPr` ending the body is; the others belong to
+/// paragraphs.
+fn body_section(document: &str) -> Option<std::ops::Range<usize>> {
+    static SECTION: OnceLock<Regex> = OnceLock::new();
+    let section = regex(&SECTION, r"(?s)<w:sectPr\b(?:[^>]*/>|.*?</w:sectPr>)");
+    let body_end = document.rfind("</w:body>")?;
+    let last = section.find_iter(&document[..body_end]).last()?;
+    document[last.end()..body_end]
+        .trim()
+        .is_empty()
+        .then(|| last.range())
+}
+
+/// The `Relationship` elements of `rels` whose type `keep` accepts.
+fn relationships(rels: &str, keep: impl Fn(&str) -> bool) -> String {
+    static RELATIONSHIP: OnceLock<Regex> = OnceLock::new();
+    static TYPE: OnceLock<Regex> = OnceLock::new();
+    let relationship = regex(&RELATIONSHIP, r"<Relationship\s[^>]*/>");
+    let ty = regex(&TYPE, r#"\sType="([^"]*)""#);
+    relationship
+        .find_iter(rels)
+        .map(|m| m.as_str())
+        .filter(|element| ty.captures(element).is_some_and(|caps| keep(&caps[1])))
+        .collect()
+}
+
+/// The reference relationships plus the generated links and images, and
+/// the numbering and footnotes when the reference has none.
+fn merged_relationships(
+    reference: &str,
+    generated: &str,
+    numbering: bool,
+    footnotes: bool,
+) -> String {
+    let needs_numbering = numbering && !reference.contains("/numbering\"");
+    let needs_footnotes = footnotes && !reference.contains("/footnotes\"");
+    let added = relationships(generated, |ty| {
+        ty.ends_with("/hyperlink")
+            || ty.ends_with("/image")
+            || (needs_numbering && ty.ends_with("/numbering"))
+            || (needs_footnotes && ty.ends_with("/footnotes"))
+    });
+    insert_before(reference, "</Relationships>", &added)
+}
+
+/// The reference styles plus the generated ones it does not define, such
+/// as `Hyperlink` or a heading level it lacks.
+fn merged_styles(reference: &str, generated: &str) -> String {
+    static STYLE: OnceLock<Regex> = OnceLock::new();
+    let style = regex(&STYLE, r"(?s)<w:style\b.*?</w:style>");
+    let defined = style_ids(reference);
+    let added: String = style
+        .find_iter(generated)
+        .map(|m| m.as_str())
+        .filter(|element| style_ids(element).is_disjoint(&defined))
+        .collect();
+    insert_before(reference, "</w:styles>", &added)
+}
+
+fn style_ids(xml: &str) -> HashSet<String> {
+    static STYLE_ID: OnceLock<Regex> = OnceLock::new();
+    let style_id = regex(&STYLE_ID, r#"w:styleId="([^"]*)""#);
+    style_id
+        .captures_iter(xml)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+/// The reference numbering with the generated lists added, whose IDs
+/// start after the reference's (`Reference::first_list_id`).
+fn merged_numbering(reference: &str, generated: &str) -> String {
+    static ABSTRACT: OnceLock<Regex> = OnceLock::new();
+    static NUM: OnceLock<Regex> = OnceLock::new();
+    let abstract_num = regex(&ABSTRACT, r"(?s)<w:abstractNum\b.*?</w:abstractNum>");
+    let num = regex(&NUM, r"(?s)<w:num\b.*?</w:num>");
+    let abstracts: String = abstract_num
+        .find_iter(generated)
+        .map(|m| m.as_str())
+        .collect();
+    let nums: String = num.find_iter(generated).map(|m| m.as_str()).collect();
+    // Every `w:abstractNum` comes before the first `w:num`.
+    let merged = match num.find(reference) {
+        Some(first) => format!(
+            "{}{}{}",
+            &reference[..first.start()],
+            abstracts,
+            &reference[first.start()..]
+        ),
+        None => insert_before(reference, "</w:numbering>", &abstracts),
+    };
+    insert_before(&merged, "</w:numbering>", &nums)
+}
+
+/// The reference content types, as a document rather than a template, with
+/// the image extensions and parts the generated package adds.
+fn merged_content_types(reference: &str, generated: &str) -> String {
+    static DEFAULT: OnceLock<Regex> = OnceLock::new();
+    static OVERRIDE: OnceLock<Regex> = OnceLock::new();
+    let default = regex(&DEFAULT, r#"<Default\s[^>]*Extension="([^"]*)"[^>]*/>"#);
+    let override_ = regex(&OVERRIDE, r#"<Override\s[^>]*PartName="([^"]*)"[^>]*/>"#);
+    let merged = reference.replace(TEMPLATE_CONTENT_TYPE, DOCUMENT_CONTENT_TYPE);
+    let extensions: HashSet<String> = default
+        .captures_iter(&merged)
+        .map(|caps| caps[1].to_lowercase())
+        .collect();
+    let parts: HashSet<&str> = override_
+        .captures_iter(&merged)
+        .map(|caps| caps.get(1).map_or("", |m| m.as_str()))
+        .collect();
+    let mut added = String::new();
+    for caps in default.captures_iter(generated) {
+        if !extensions.contains(&caps[1].to_lowercase()) {
+            added.push_str(&caps[0]);
+        }
+    }
+    for caps in override_.captures_iter(generated) {
+        if [
+            "/word/numbering.xml",
+            "/word/footnotes.xml",
+            "/docProps/core.xml",
+        ]
+        .contains(&&caps[1])
+            && !parts.contains(&caps[1])
+        {
+            added.push_str(&caps[0]);
+        }
+    }
+    insert_before(&merged, "</Types>", &added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert_to_docx::write_docx;
+    use crate::template::ReportTemplate;
+
+    const W: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+    const R: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+    /// A letterhead `.dotx`: a header with a logo, its own heading style,
+    /// one list and a body to be replaced.
+    fn letterhead(path: &Path) {
+        let parts = [
+            (
+                CONTENT_TYPES,
+                format!(
+                    r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Default Extension="PNG" ContentType="image/png"/><Override PartName="/word/document.xml" ContentType="{TEMPLATE_CONTENT_TYPE}"/><Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/><Override PartName="/word/numbering.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml"/><Override PartName="/word/header1.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.header+xml"/></Types>"#
+                ),
+            ),
+            (
+                PACKAGE_RELS,
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#.to_string(),
+            ),
+            (
+                DOCUMENT,
+                format!(
+                    r#"<w:document xmlns:w="{W}" xmlns:r="{R}"><w:body><w:p><w:r><w:t>Texto do modelo</w:t></w:r></w:p><w:sectPr><w:headerReference w:type="default" r:id="rId2"/><w:pgSz w:w="11906" w:h="16838"/></w:sectPr></w:body></w:document>"#
+                ),
+            ),
+            (
+                DOCUMENT_RELS,
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/header" Target="header1.xml"/><Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering" Target="numbering.xml"/></Relationships>"#.to_string(),
+            ),
+            (
+                STYLES,
+                format!(
+                    r#"<w:styles xmlns:w="{W}"><w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:rPr><w:sz w:val="36"/></w:rPr></w:style></w:styles>"#
+                ),
+            ),
+            (
+                NUMBERING,
+                format!(
+                    r#"<w:numbering xmlns:w="{W}"><w:abstractNum w:abstractNumId="0"><w:lvl w:ilvl="0"><w:numFmt w:val="bullet"/></w:lvl></w:abstractNum><w:num w:numId="1"><w:abstractNumId w:val="0"/></w:num></w:numbering>"#
+                ),
+            ),
+            (
+                "word/header1.xml",
+                format!(r#"<w:hdr xmlns:w="{W}"><w:p><w:r><w:t>Clínica Oficial</w:t></w:r></w:p></w:hdr>"#),
+            ),
+            (
+                "word/_rels/header1.xml.rels",
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/image" Target="media/image1.png"/></Relationships>"#.to_string(),
+            ),
+            ("word/media/image1.png", "logo".to_string()),
+        ];
+        let mut package = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, data) in parts {
+            package
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            package.write_all(data.as_bytes()).unwrap();
+        }
+        package.finish().unwrap();
+    }
+
+    #[test]
+    fn markdown_is_written_on_the_reference_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let reference_path = dir.path().join("timbrado.dotx");
+        letterhead(&reference_path);
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend([0, 0, 0, 10, 0, 0, 0, 10]);
+        fs::write(dir.path().join("seta.png"), png).unwrap();
+        let markdown = "TC DE TÓRAX\n\n# Achados\n\n- nódulo ![](seta.png)\n- [ACR](https://www.acr.org/)\n\nRodapé.";
+        let reference = Reference::open(&reference_path).unwrap();
+        let path = dir.path().join("laudo.docx");
+        write_docx(
+            &ReportTemplate::from_markdown(markdown),
+            dir.path(),
+            None,
+            Some(&reference),
+            &path,
+        )
+        .unwrap();
+
+        let parts: HashMap<String, String> = read_package(fs::File::open(&path).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|(name, data)| (name, String::from_utf8_lossy(&data).into_owned()))
+            .collect();
+        let document = &parts[DOCUMENT];
+        assert!(!document.contains("Texto do modelo") && document.contains("Achados"));
+        assert!(document.contains(r#"<w:headerReference w:type="default" r:id="rId2"/>"#));
+        assert!(!document.contains("<w:rFonts"), "{document}");
+        assert!(parts["word/header1.xml"].contains("Clínica Oficial"));
+        assert_eq!(parts["word/media/image1.png"], "logo");
+        assert!(parts.contains_key("word/media/image2.png"));
+        let rels = &parts[DOCUMENT_RELS];
+        assert!(
+            rels.contains(r#"Target="media/image2.png""#) && rels.contains("https://www.acr.org/")
+        );
+        // The reference's heading style stays, and Hyperlink is added.
+        assert_eq!(parts[STYLES].matches(r#"w:styleId="Heading1""#).count(), 1);
+        assert!(parts[STYLES].contains(r#"w:styleId="Hyperlink""#));
+        // The added list is numbered after the reference's.
+        assert!(parts[NUMBERING].contains(r#"<w:num w:numId="1">"#));
+        assert!(parts[NUMBERING].contains(r#"<w:num w:numId="3"><w:abstractNumId w:val="2"/>"#));
+        assert!(document.contains(r#"<w:numId w:val="3"/>"#));
+        let types = &parts[CONTENT_TYPES];
+        assert!(types.contains(DOCUMENT_CONTENT_TYPE) && !types.contains(TEMPLATE_CONTENT_TYPE));
+        assert_eq!(
+            types.to_lowercase().matches(r#"extension="png""#).count(),
+            1
+        );
+        assert!(parts[PACKAGE_RELS].contains(r#"Target="docProps/core.xml""#));
+        assert!(parts.contains_key(CORE));
+    }
+}
@@ -0,0 +1,281 @@
+//! Paragraph roles read from a DOCX's style definitions and numbering.
+//!
+//! Word records what a paragraph is in its style (`Heading 2`, `Título 1`,
+//! a custom style based on either) and in its numbering properties (`numPr`,
+//! set on the paragraph or inherited from a style such as `List Bullet`), so
+//! headings and list items are found without guessing from the text.
+
+use docx_rust::document::{Numbering, Paragraph};
+use docx_rust::formatting::NumberingProperty;
+use docx_rust::styles::Styles;
+use docx_rust::Docx;
+use std::collections::HashMap;
+
+/// Style names of headings, followed by their level (`Heading 2`,
+/// `Título 2`, `Überschrift 2`), in the languages Word ships.
+const HEADING_NAMES: &[&str] = &[
+    "heading",
+    "título",
+    "titulo",
+    "titre",
+    "überschrift",
+    "titolo",
+    "kop",
+];
+
+/// Style names of the document title, used without a level.
+const TITLE_NAMES: &[&str] = &["título", "titulo", "titre", "titel", "titolo"];
+
+/// Guards against `basedOn` cycles in hand-edited files.
+const MAX_STYLE_DEPTH: usize = 16;
+
+/// What a body paragraph is, according to its style and numbering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParagraphRole {
+    Body,
+    /// A heading of level 1 (`Title`, `Heading 1`) to 6.
+    Heading(u8),
+    /// An item of a bulleted or numbered list; `level` 0 is the outermost.
+    ListItem {
+        ordered: bool,
+        level: u8,
+        /// Position among the items of its list at this level, from the
+        /// list's start value.
+        number: usize,
+    },
+}
+
+/// Heading level of a Word style name: `Title` and `Heading 1` are level 1
+/// through `Heading 6` (as the Python importer reads them), plus the
+/// localized names Word writes for custom and translated styles.
+pub fn style_heading_level(name: &str) -> Option<u8> {
+    let name = name.trim().to_lowercase();
+    if name.contains("title") || TITLE_NAMES.contains(&name.as_str()) {
+        return Some(1);
+    }
+    let rest = HEADING_NAMES
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))?;
+    let rest = rest.trim_start();
+    match rest.chars().next() {
+        None => Some(1),
+        Some(c) => {
+            let level = c.to_digit(10).filter(|level| (1..=6).contains(level))?;
+            Some(level as u8)
+        }
+    }
+}
+
+struct StyleInfo {
+    heading: Option<u8>,
+    based_on: Option<String>,
+    numbering: Option<(isize, isize)>,
+}
+
+/// `(numId, ilvl)` of a numbering property; `numId` 0 removes numbering.
+fn numbering_ref(numbering: &NumberingProperty) -> Option<(isize, isize)> {
+    let id = numbering.id.as_ref()?.value;
+    let level = numbering.level.as_ref().map(|l| l.value).unwrap_or(0);
+    Some((id, level))
+}
+
+/// Resolves paragraph roles for one document, numbering list items in
+/// document order.
+pub struct WordStyles<'a> {
+    styles: HashMap<String, StyleInfo>,
+    numbering: Option<&'a Numbering<'a>>,
+    /// Last number given per list and level.
+    counters: HashMap<(isize, isize), usize>,
+}
+
+impl<'a> WordStyles<'a> {
+    pub fn new(docx: &'a Docx<'a>) -> Self {
+        WordStyles {
+            styles: style_infos(&docx.styles),
+            numbering: docx.numbering.as_ref(),
+            counters: HashMap::new(),
+        }
+    }
+
+    /// The style and the styles it is based on, nearest first.
+    fn style_chain(&self, id: &str) -> Vec<&StyleInfo> {
+        let mut chain = Vec::new();
+        let mut next = Some(id);
+        while let Some(id) = next {
+            let Some(info) = self.styles.get(id) else {
+                break;
+            };
+            chain.push(info);
+            if chain.len() == MAX_STYLE_DEPTH {
+                break;
+            }
+            next = info.based_on.as_deref();
+        }
+        chain
+    }
+
+    fn heading_level(&self, style_id: &str) -> Option<u8> {
+        match self.style_chain(style_id).iter().find_map(|s| s.heading) {
+            Some(level) => Some(level),
+            // Styles missing from `styles.xml` are known by their ID only.
+            None if !self.styles.contains_key(style_id) => style_heading_level(style_id),
+            None => None,
+        }
+    }
+
+    /// The paragraph's own numbering, else the one of its style.
+    fn numbering_of(&self, p: &Paragraph, style_id: Option<&str>) -> Option<(isize, isize)> {
+        let own = p
+            .property
+            .as_ref()
+            .and_then(|prop| prop.numbering.as_ref())
+            .and_then(numbering_ref);
+        own.or_else(|| self.style_chain(style_id?).iter().find_map(|s| s.numbering))
+            .filter(|(id, _)| *id != 0)
+    }
+
+    /// Whether the list level is numbered and the number it starts from.
+    /// Lists without a definition in `numbering.xml` are taken as bullets.
+    fn list_format(&self, id: isize, level: isize) -> (bool, usize) {
+        let details = self.numbering.and_then(|n| n.numbering_details(id));
+        let level = details
+            .as_ref()
+            .and_then(|d| d.levels.iter().find(|l| l.i_level == Some(level)));
+        let ordered = level
+            .and_then(|l| l.number_format.as_ref())
+            .map(|f| !matches!(f.value.as_ref(), "bullet" | "none"))
+            .unwrap_or(false);
+        let start = level
+            .and_then(|l| l.start.as_ref())
+            .and_then(|s| s.value)
+            .unwrap_or(1);
+        (ordered, start.max(0) as usize)
+    }
+
+    /// The role of the next body paragraph. Call once per paragraph in
+    /// document order so numbered items get consecutive numbers.
+    pub fn role(&mut self, p: &Paragraph) -> ParagraphRole {
+        let style_id = p
+            .property
+            .as_ref()
+            .and_then(|prop| prop.style_id.as_ref())
+            .map(|id| id.value.to_string());
+        // Numbered headings ("1. Técnica") are still headings.
+        if let Some(level) = style_id.as_deref().and_then(|id| self.heading_level(id)) {
+            return ParagraphRole::Heading(level);
+        }
+        let Some((id, level)) = self.numbering_of(p, style_id.as_deref()) else {
+            return ParagraphRole::Body;
+        };
+
+        let (ordered, start) = self.list_format(id, level);
+        // A new item restarts the numbering of the levels nested under it.
+        self.counters
+            .retain(|&(list, nested), _| list != id || nested <= level);
+        let counter = self.counters.entry((id, level)).or_insert(0);
+        *counter += 1;
+        ParagraphRole::ListItem {
+            ordered,
+            level: level.clamp(0, 8) as u8,
+            number: start + *counter - 1,
+        }
+    }
+}
+
+fn style_infos(styles: &Styles) -> HashMap<String, StyleInfo> {
+    styles
+        .styles
+        .iter()
+        .map(|style| {
+            let info = StyleInfo {
+                heading: style
+                    .name
+                    .as_ref()
+                    .and_then(|name| style_heading_level(&name.value)),
+                based_on: style.base.as_ref().map(|b| b.value.to_string()),
+                numbering: style
+                    .paragraph
+                    .as_ref()
+                    .and_then(|p| p.numbering.as_ref())
+                    .and_then(numbering_ref),
+            };
+            (style.style_id.to_string(), info)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use docx_rust::formatting::ParagraphProperty;
+    use docx_rust::styles::{BasedOn, Style, StyleType};
+
+    #[test]
+    fn roles_follow_styles_their_bases_and_numbering() {
+        assert_eq!(style_heading_level("Heading 2"), Some(2));
+        assert_eq!(style_heading_level("Título 3"), Some(3));
+        assert_eq!(style_heading_level("Title"), Some(1));
+        assert_eq!(style_heading_level("Überschrift"), Some(1));
+        assert_eq!(style_heading_level("Heading 9"), None);
+        assert_eq!(style_heading_level("Normal"), None);
+
+        let mut docx = Docx::default();
+        docx.styles
+            .push(Style::new(StyleType::Paragraph, "Ttulo2").name("Título 2"));
+        let mut section = Style::new(StyleType::Paragraph, "Secao").name("Seção do laudo");
+        section.base = Some(BasedOn {
+            value: "Ttulo2".into(),
+        });
+        docx.styles.push(section);
+        docx.styles.push(
+            Style::new(StyleType::Paragraph, "ListBullet")
+                .name("List Bullet")
+                .paragraph(ParagraphProperty::default().numbering((7isize, 0isize))),
+        );
+
+        let styled = |id: &str| {
+            Paragraph::default().property(ParagraphProperty::default().style_id(id.to_string()))
+        };
+        let numbered = |level: isize| {
+            Paragraph::default().property(ParagraphProperty::default().numbering((3isize, level)))
+        };
+        let item = |level: u8, number: usize| ParagraphRole::ListItem {
+            ordered: false,
+            level,
+            number,
+        };
+        let paragraphs = [
+            styled("Secao"),
+            styled("Heading3"),
+            styled("Normal"),
+            styled("ListBullet"),
+            styled("ListBullet"),
+            numbered(0),
+            numbered(1),
+            numbered(1),
+            numbered(0),
+            numbered(1),
+            Paragraph::default().property(ParagraphProperty::default().numbering((0isize, 0isize))),
+        ];
+        let mut styles = WordStyles::new(&docx);
+        let roles: Vec<ParagraphRole> = paragraphs.iter().map(|p| styles.role(p)).collect();
+        assert_eq!(
+            roles,
+            [
+                ParagraphRole::Heading(2),
+                // Missing from styles.xml, so read from its ID.
+                ParagraphRole::Heading(3),
+                ParagraphRole::Body,
+                item(0, 1),
+                item(0, 2),
+                item(0, 1),
+                item(1, 1),
+                item(1, 2),
+                item(0, 2),
+                // A new outer item restarts the items nested under it.
+                item(1, 1),
+                ParagraphRole::Body,
+            ]
+        );
+    }
+}
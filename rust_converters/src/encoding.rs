@@ -0,0 +1,93 @@
+//! Reading text inputs (TXT, Markdown and HTML templates) whatever their
+//! encoding.
+//!
+//! Files exported from Windows are often ISO-8859-1 or Windows-1252 rather
+//! than UTF-8, and editors on macOS may save accents decomposed (`e` and a
+//! combining acute accent instead of `é`). The bytes are decoded by their
+//! byte order mark, as UTF-8 when they are valid UTF-8, as UTF-16 when
+//! every other byte is zero, and as Windows-1252 (a superset of the
+//! printable ISO-8859-1) otherwise. The text is then put in Unicode NFC, so
+//! the same word is the same string whichever way it was saved.
+
+use anyhow::{Context, Result};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use std::fs;
+use std::path::Path;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// The encoding `bytes` are most likely in, and the length of the byte
+/// order mark to skip.
+pub fn detect(bytes: &[u8]) -> (&'static Encoding, usize) {
+    if let Some(found) = Encoding::for_bom(bytes) {
+        return found;
+    }
+    // Text has no zero bytes, but Latin text in UTF-16 has one in most
+    // characters' high byte.
+    let zeros = |offset: usize| {
+        bytes
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let pairs = bytes.len() / 2;
+    if bytes.len().is_multiple_of(2) && zeros(0) + zeros(1) > 0 {
+        if zeros(1) * 10 >= pairs * 3 && zeros(0) == 0 {
+            return (UTF_16LE, 0);
+        }
+        if zeros(0) * 10 >= pairs * 3 && zeros(1) == 0 {
+            return (UTF_16BE, 0);
+        }
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return (UTF_8, 0);
+    }
+    (WINDOWS_1252, 0)
+}
+
+/// `text` in Unicode NFC.
+pub fn nfc(text: String) -> String {
+    match is_nfc_quick(text.chars()) {
+        IsNormalized::Yes => text,
+        _ => text.nfc().collect(),
+    }
+}
+
+/// `bytes` decoded as `detect` says and in NFC.
+pub fn decode(bytes: &[u8]) -> String {
+    let (encoding, bom) = detect(bytes);
+    let (text, _) = encoding.decode_without_bom_handling(&bytes[bom..]);
+    nfc(text.into_owned())
+}
+
+/// Reads the text file at `path`, in any encoding `decode` reads.
+pub fn read_text(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let (encoding, _) = detect(&bytes);
+    if encoding != UTF_8 {
+        log::debug!("{} read as {}", path.display(), encoding.name());
+    }
+    Ok(decode(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_encodings_and_decomposed_accents_are_read() {
+        assert_eq!(decode("Impressão".as_bytes()), "Impressão");
+        assert_eq!(
+            decode(b"Impress\xe3o: n\xe3o h\xe1 \x96 nada"),
+            "Impressão: não há – nada"
+        );
+        assert_eq!(decode(b"\xef\xbb\xbfT\xc3\xa9cnica"), "Técnica");
+        let utf16: Vec<u8> = "Técnica"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(decode(&utf16), "Técnica");
+        assert_eq!(decode("Te\u{301}cnica".as_bytes()), "Técnica");
+    }
+}
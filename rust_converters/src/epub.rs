@@ -0,0 +1,293 @@
+//! The template library as an EPUB book (`rtemplates epub`), for reading
+//! offline on a tablet or e-reader.
+//!
+//! The book holds the catalog's groups (`catalog::build`): a chapter per
+//! modality, with a heading per body region and each template starting on
+//! a new page, and the contents nested modality, region, template. It is
+//! an EPUB 3 package with the HTML output's stylesheet. The identifier is
+//! derived from the title, so a reader replaces an earlier export instead
+//! of shelving a second copy.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::catalog::{self, starts_modality, Group, CONTENTS, TITLE};
+use crate::config;
+use crate::convert_to_html::{body_html, escape_html, stylesheet};
+use crate::files::FileFilter;
+use crate::timestamp::Timestamp;
+
+const MIMETYPE: &str = "application/epub+zip";
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+<rootfiles>\n\
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n\
+</rootfiles>\n\
+</container>\n";
+
+/// Reading systems set their own margins and page width.
+const EPUB_CSS: &str = "\
+body { max-width: none; margin: 0; padding: 0; }
+nav ol { list-style: none; padding-left: 1.5em; }
+nav > ol { padding-left: 0; }
+.template { break-before: page; page-break-before: always; }
+";
+
+fn language() -> String {
+    escape_html(&config::get().language)
+}
+
+/// An XHTML document of the book titled `title` holding `body`.
+fn xhtml(title: &str, body: &str, epub_type: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+         lang=\"{lang}\" xml:lang=\"{lang}\">\n<head>\n<meta charset=\"utf-8\"/>\n\
+         <title>{}</title>\n<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n\
+         </head>\n<body epub:type=\"{}\">\n{}</body>\n</html>\n",
+        escape_html(title),
+        epub_type,
+        body,
+        lang = language()
+    )
+}
+
+/// The chapter file of each group's modality.
+fn chapter_file(group: &Group) -> String {
+    format!("{}.xhtml", group.modality_anchor)
+}
+
+/// The chapters, one per modality, as (file, title, XHTML).
+fn chapters(groups: &[Group]) -> Vec<(String, String, String)> {
+    let mut chapters: Vec<(String, String, String)> = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        if starts_modality(groups, i) {
+            chapters.push((
+                chapter_file(group),
+                group.modality.clone(),
+                format!(
+                    "<h1 id=\"{}\" class=\"left level1\">{}</h1>\n",
+                    escape_html(&group.modality_anchor),
+                    escape_html(&group.modality)
+                ),
+            ));
+        }
+        let Some((_, _, body)) = chapters.last_mut() else {
+            continue;
+        };
+        body.push_str(&format!(
+            "<h2 id=\"{}\" class=\"left level2\">{}</h2>\n",
+            escape_html(&group.anchor),
+            escape_html(&group.region)
+        ));
+        for entry in &group.entries {
+            body.push_str(&format!(
+                "<section class=\"template\" id=\"{}\">\n<h3 class=\"left level3\">{}</h3>\n{}</section>\n",
+                escape_html(&entry.anchor),
+                escape_html(&entry.name),
+                // XHTML closes empty elements.
+                body_html(&entry.template).replace("<br>", "<br/>")
+            ));
+        }
+    }
+    chapters
+        .into_iter()
+        .map(|(file, title, body)| {
+            let page = xhtml(&title, &body, "chapter");
+            (file, title, page)
+        })
+        .collect()
+}
+
+/// The navigation document, with the nested contents.
+fn nav(groups: &[Group]) -> String {
+    let link = |href: String, text: &str| {
+        format!(
+            "<a href=\"{}\">{}</a>",
+            escape_html(&href),
+            escape_html(text)
+        )
+    };
+    let mut ol = String::from("<ol>\n");
+    for (i, group) in groups.iter().enumerate() {
+        let file = chapter_file(group);
+        if starts_modality(groups, i) {
+            if i > 0 {
+                ol.push_str("</ol></li>\n");
+            }
+            ol.push_str(&format!(
+                "<li>{}<ol>\n",
+                link(file.clone(), &group.modality)
+            ));
+        }
+        ol.push_str(&format!(
+            "<li>{}<ol>\n",
+            link(format!("{}#{}", file, group.anchor), &group.region)
+        ));
+        for entry in &group.entries {
+            ol.push_str(&format!(
+                "<li>{}</li>\n",
+                link(format!("{}#{}", file, entry.anchor), &entry.name)
+            ));
+        }
+        ol.push_str("</ol></li>\n");
+    }
+    if !groups.is_empty() {
+        ol.push_str("</ol></li>\n");
+    }
+    ol.push_str("</ol>\n");
+    xhtml(
+        CONTENTS,
+        &format!(
+            "<h1 class=\"center level1\">{}</h1>\n<nav epub:type=\"toc\" id=\"toc\">\n\
+             <h2 class=\"left level2\">{}</h2>\n{}</nav>\n",
+            escape_html(TITLE),
+            escape_html(CONTENTS),
+            ol
+        ),
+        "frontmatter",
+    )
+}
+
+/// The package document listing `chapters` after the contents.
+fn package(chapters: &[(String, String, String)]) -> String {
+    let digest = Sha256::digest(TITLE.as_bytes());
+    let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    let uuid = format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    );
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::from("<itemref idref=\"nav\"/>\n");
+    for (i, (file, _, _)) in chapters.iter().enumerate() {
+        manifest.push_str(&format!(
+            "<item id=\"chapter{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            escape_html(file)
+        ));
+        spine.push_str(&format!("<itemref idref=\"chapter{}\"/>\n", i + 1));
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n\
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+         <dc:identifier id=\"book-id\">urn:uuid:{}</dc:identifier>\n\
+         <dc:title>{}</dc:title>\n\
+         <dc:language>{}</dc:language>\n\
+         <meta property=\"dcterms:modified\">{}</meta>\n\
+         </metadata>\n<manifest>\n{}</manifest>\n<spine>\n{}</spine>\n</package>\n",
+        uuid,
+        escape_html(TITLE),
+        language(),
+        Timestamp::now().iso_datetime(),
+        manifest,
+        spine
+    )
+}
+
+/// The EPUB file of `groups`.
+pub fn to_epub(groups: &[Group]) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    // The mimetype entry must come first and be stored uncompressed.
+    zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let chapters = chapters(groups);
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(package(&chapters).as_bytes())?;
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav(groups).as_bytes())?;
+    zip.start_file("OEBPS/style.css", deflated)?;
+    zip.write_all(format!("{}{}", stylesheet(), EPUB_CSS).as_bytes())?;
+    for (file, _, page) in &chapters {
+        zip.start_file(format!("OEBPS/{}", file), deflated)?;
+        zip.write_all(page.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Writes the EPUB of the templates in `dir` selected by `files` to
+/// `output` and returns how many templates it holds.
+pub fn write_epub(dir: &Path, files: &FileFilter, output: &Path) -> Result<usize> {
+    let groups = catalog::build(dir, files)?;
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, to_epub(&groups)?)
+        .with_context(|| format!("Cannot write {}", output.display()))?;
+    Ok(groups.iter().map(|group| group.entries.len()).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn templates_become_a_chapter_per_modality_with_contents() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, content: &str| fs::write(dir.path().join(name), content).unwrap();
+        write(
+            "TC Crânio SEM.md",
+            "TOMOGRAFIA DO CRÂNIO\n\n**Impressão:** Normal.\n\n| A | B |\n|---|---|\n| 1<br>2 | 3 |\n",
+        );
+        write(
+            "RM Joelho.md",
+            "RESSONÂNCIA DO JOELHO\n\n**Impressão:** Normal.\n",
+        );
+
+        let groups = catalog::build(dir.path(), &FileFilter::default()).unwrap();
+        let bytes = to_epub(&groups).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        assert_eq!(names[0], "mimetype");
+        assert_eq!(
+            archive.by_index(0).unwrap().compression(),
+            CompressionMethod::Stored
+        );
+        let read = |archive: &mut zip::ZipArchive<Cursor<Vec<u8>>>, name: &str| {
+            let mut text = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            text
+        };
+
+        let opf = read(&mut archive, "OEBPS/content.opf");
+        let spine = opf.split("<spine>").nth(1).unwrap();
+        assert_eq!(
+            spine.matches("<itemref").count(),
+            3,
+            "contents and two chapters"
+        );
+        let nav = read(&mut archive, "OEBPS/nav.xhtml");
+        assert!(nav.contains(
+            "<a href=\"tomografia-computadorizada.xhtml#tc-cranio-sem\">TC Crânio SEM</a>"
+        ));
+        let chapter = read(&mut archive, "OEBPS/tomografia-computadorizada.xhtml");
+        assert!(chapter.contains("<section class=\"template\" id=\"tc-cranio-sem\">"));
+        assert!(chapter.contains("<td>1<br/>2</td>"));
+        assert!(!chapter.contains("<br>"));
+    }
+}
@@ -0,0 +1,151 @@
+//! Templates kept out of some output formats, such as internal checklists
+//! that only exist as Markdown reference documents. The front matter lists
+//! the formats the template is converted to, by their `--to` names:
+//!
+//! ```text
+//! ---
+//! exports: [md]
+//! ---
+//! ```
+//!
+//! Without `exports` a template goes to every format. Every converter skips
+//! the formats it leaves out, and so does `convert --watch`; the index does
+//! not list outputs left over from before, so a backup moves them away,
+//! `clean` reports them as orphans and `verify` neither expects nor checks
+//! them. Aliases follow the template they name.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use log::warn;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use crate::aliases;
+use crate::convert::Format;
+use crate::encoding::read_text;
+use crate::files::{display_name, list_files};
+use crate::front_matter;
+use crate::{config, targets};
+
+/// The formats the front matter of `source`, read as `content`, exports it
+/// to; `None` without `exports`. Names that are not a format are warned
+/// about and left out.
+pub fn of(source: &Path, content: &str) -> Option<Vec<Format>> {
+    let names = front_matter::split(content)
+        .0
+        .and_then(|yaml| front_matter::parse(yaml).ok())?
+        .exports?;
+    Some(
+        names
+            .iter()
+            .filter_map(|name| {
+                let format = Format::from_str(name, true).ok();
+                if format.is_none() {
+                    warn!(
+                        "{}: `{}` in exports is not an output format",
+                        display_name(source),
+                        name
+                    );
+                }
+                format
+            })
+            .collect(),
+    )
+}
+
+/// Whether the Markdown template `source` is converted to `format`; the
+/// Markdown itself always is.
+pub fn is_exported(source: &Path, format: Format) -> Result<bool> {
+    if format == Format::Md {
+        return Ok(true);
+    }
+    let content = read_text(source)?;
+    Ok(of(source, &content).is_none_or(|formats| formats.contains(&format)))
+}
+
+/// The stems of the Markdown templates in `dir` not converted to `format`,
+/// with their aliases.
+pub fn left_out(dir: &Path, format: Format) -> Result<HashSet<String>> {
+    let mut stems = HashSet::new();
+    if !dir.is_dir() {
+        return Ok(stems);
+    }
+    for source in list_files(dir, "md")? {
+        if is_exported(&source, format)? {
+            continue;
+        }
+        let content = read_text(&source)?;
+        let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+        stems.insert(stem.to_string());
+        stems.extend(aliases::of(&source, &content));
+    }
+    Ok(stems)
+}
+
+/// `left_out` for each tracked output folder under `root`, by folder name.
+pub fn left_out_by_folder(root: &Path) -> Result<BTreeMap<&'static str, HashSet<String>>> {
+    let markdown = root.join(&config::get().directories.markdown);
+    let mut folders = BTreeMap::new();
+    for (folder, _) in targets() {
+        let format = Format::value_variants()
+            .iter()
+            .find(|format| format.default_dir() == folder);
+        if let Some(&format) = format.filter(|&&format| format != Format::Md) {
+            folders.insert(folder, left_out(&markdown, format)?);
+        }
+    }
+    Ok(folders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{convert_folder, BatchOptions};
+    use crate::generate_index::{read_index, write_index};
+    use std::fs;
+
+    #[test]
+    fn templates_are_only_converted_to_the_formats_they_export() {
+        let root = tempfile::tempdir().unwrap();
+        let markdown = root.path().join("Templates_markdown");
+        let txt = root.path().join("Templates_txt");
+        fs::create_dir_all(&markdown).unwrap();
+        fs::write(markdown.join("US ABDOME.md"), "**US ABDOME**\n\nNormal.\n").unwrap();
+        let checklist = markdown.join("CHECKLIST TC.md");
+        fs::write(
+            &checklist,
+            "---\nexports: [md, docx, pptx]\naliases: [CHECKLIST RM]\n---\n**CHECKLIST**\n",
+        )
+        .unwrap();
+
+        assert!(is_exported(&checklist, Format::Docx).unwrap());
+        assert!(!is_exported(&checklist, Format::Txt).unwrap());
+        let mut stems: Vec<_> = left_out(&markdown, Format::Txt)
+            .unwrap()
+            .into_iter()
+            .collect();
+        stems.sort();
+        assert_eq!(stems, ["CHECKLIST RM", "CHECKLIST TC"]);
+
+        let report = convert_folder(
+            Format::Md,
+            Format::Txt,
+            &markdown,
+            &txt,
+            &BatchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.converted, 1);
+        assert!(txt.join("US ABDOME.txt").exists());
+        assert!(!txt.join("CHECKLIST TC.txt").exists());
+
+        fs::write(txt.join("CHECKLIST TC.txt"), "CHECKLIST\n").unwrap();
+        let index_path = root.path().join("reports_index.json");
+        write_index(root.path(), &index_path).unwrap();
+        let index = read_index(&index_path).unwrap();
+        assert_eq!(
+            index.folders["Templates_txt"],
+            ["Templates_txt/US ABDOME.txt"]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::comments::is_sidecar;
+
+/// Sorted list of the files directly inside `dir` with extension `ext`,
+/// leaving out the comment sidecars of imported templates.
+pub fn list_files(dir: &Path, ext: &str) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some(ext))
+        .filter(|p| !is_sidecar(p))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// `dir/<stem of source>.<ext>`.
+pub fn output_path(dir: &Path, source: &Path, ext: &str) -> PathBuf {
+    dir.join(
+        source
+            .file_stem()
+            .expect("file without stem")
+            .to_string_lossy()
+            .to_string()
+            + "."
+            + ext,
+    )
+}
+
+/// File name of `path` for progress messages.
+pub fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// `parent/name`, or `parent/name-2`, `name-3`, ... when that already
+/// exists.
+pub fn unique_dir(parent: &Path, name: &str) -> PathBuf {
+    let mut dir = parent.join(name);
+    let mut version = 1;
+    while dir.exists() {
+        version += 1;
+        dir = parent.join(format!("{}-{}", name, version));
+    }
+    dir
+}
+
+/// Every file under `dir`, recursively, as sorted paths relative to `dir`.
+pub fn list_files_recursive(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if let Ok(rel) = path.strip_prefix(dir) {
+                files.push(rel.to_path_buf());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `path` with `/` separators, as globs and listings show it.
+pub fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `--include`/`--exclude` selection of the files a batch command works on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileFilter {
+    /// When non-empty, only paths matching one of these globs are selected.
+    pub include: Vec<String>,
+    /// Paths matching any of these globs are never selected.
+    pub exclude: Vec<String>,
+}
+
+impl FileFilter {
+    /// Whether `path` is selected; globs follow `glob_matches`, so `TC*` is
+    /// compared with the file name and `Templates_docx/TC*` with the path.
+    pub fn matches(&self, path: &Path) -> bool {
+        let path = slash_path(path);
+        (self.include.is_empty() || self.include.iter().any(|p| glob_matches(p, &path)))
+            && !self.exclude.iter().any(|p| glob_matches(p, &path))
+    }
+}
+
+/// Shell-style match of `path` (with `/` separators) against `pattern`:
+/// `?` is one character and `*` any run of characters within a path
+/// component, `**` any run including `/`. A pattern without `/` is matched
+/// against the file name alone.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let subject = if pattern.contains('/') {
+        path
+    } else {
+        path.rsplit('/').next().unwrap_or(path)
+    };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let subject: Vec<char> = subject.chars().collect();
+    glob_match_from(&pattern, &subject)
+}
+
+fn glob_match_from(pattern: &[char], subject: &[char]) -> bool {
+    match pattern.first() {
+        None => subject.is_empty(),
+        Some('*') => {
+            let crosses = pattern.get(1) == Some(&'*');
+            let rest = &pattern[if crosses { 2 } else { 1 }..];
+            for skip in 0..=subject.len() {
+                if glob_match_from(rest, &subject[skip..]) {
+                    return true;
+                }
+                if skip < subject.len() && subject[skip] == '/' && !crosses {
+                    return false;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            subject.first().is_some_and(|&c| c != '/')
+                && glob_match_from(&pattern[1..], &subject[1..])
+        }
+        Some(&c) => subject.first() == Some(&c) && glob_match_from(&pattern[1..], &subject[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_select_by_file_name_or_by_path() {
+        assert!(glob_matches("TC*", "Templates_docx/TC Tórax.docx"));
+        assert!(!glob_matches("TC*", "Templates_docx/RM TC.docx"));
+        assert!(glob_matches("?M*.md", "md/RM Joelho.md"));
+        assert!(glob_matches(
+            "Templates_docx/TC*",
+            "Templates_docx/TC Tórax.docx"
+        ));
+        assert!(!glob_matches(
+            "Templates_docx/TC*",
+            "Templates_txt/TC Tórax.txt"
+        ));
+        assert!(!glob_matches("*/TC*", "a/b/TC.md"));
+        assert!(glob_matches("**/TC*", "a/b/TC.md"));
+        assert!(!glob_matches("a?b/*", "a/b/TC.md"));
+
+        let filter = FileFilter {
+            include: vec!["TC*".to_string(), "US*".to_string()],
+            exclude: vec!["*COM*".to_string()],
+        };
+        assert!(filter.matches(Path::new("Templates_markdown/TC Tórax.md")));
+        assert!(filter.matches(Path::new("Templates_markdown/US Tireoide.md")));
+        assert!(!filter.matches(Path::new("Templates_markdown/TC Tórax COM.md")));
+        assert!(!filter.matches(Path::new("Templates_markdown/RM Joelho.md")));
+        assert!(FileFilter::default().matches(Path::new("RM Joelho.md")));
+    }
+}
@@ -0,0 +1,401 @@
+//! Fill engine: expands template directives in a Markdown source before it is
+//! rendered, so one file can produce several variants of a report.
+//!
+//! `{{#if contraste}}...{{else}}...{{/if}}` keeps its first branch when the
+//! variable is set and true, the `{{else}}` branch otherwise;
+//! `{{#unless name}}...{{/unless}}` is the negation. Blocks nest, and a line
+//! holding only directives is removed together with its line break.
+//!
+//! `{{> tecnica_tc_abdome}}` includes `partials/tecnica_tc_abdome.md`, itself
+//! filled, so shared boilerplate is written once. An include alone on its line
+//! is replaced by the whole partial; inside a line, by the partial without its
+//! final line break.
+//!
+//! `{{phrase figado-normal}}` is replaced by that sentence of the phrase
+//! library, `phrases/` (see `phrases`), and `{{volume(a, b, c)}}` by the
+//! value computed from the variables (see `calculations`). `{{today}}` and
+//! other plain `{{name}}` placeholders are replaced by their value (see
+//! `placeholders`). With a signer, the filled template gets their signature
+//! block before its footer (see `signature`).
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::cache::sha256_file;
+use crate::calculations;
+use crate::config::{self, Signature};
+use crate::encoding::read_text;
+use crate::files::list_files;
+use crate::phrases::PhraseLibrary;
+use crate::placeholders;
+use crate::signature;
+
+/// Settings for filling every Markdown source of a conversion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FillOptions {
+    /// Condition values by name; names not listed are false.
+    pub variables: BTreeMap<String, String>,
+    /// Folder holding the `{{> name}}` partials as `name.md`.
+    pub partials_dir: PathBuf,
+    /// Folder of the phrase library `{{phrase id}}` reads.
+    pub phrases_dir: PathBuf,
+    /// The signature block written before the footer (`--signer`).
+    pub signature: Option<Signature>,
+}
+
+impl Default for FillOptions {
+    fn default() -> Self {
+        FillOptions {
+            variables: BTreeMap::new(),
+            partials_dir: PathBuf::from(&config::get().directories.partials),
+            phrases_dir: PathBuf::from(&config::get().directories.phrases),
+            signature: None,
+        }
+    }
+}
+
+impl FillOptions {
+    /// Parses `name` (set to `true`) or `name=value`.
+    pub fn set(&mut self, assignment: &str) -> Result<()> {
+        let (name, value) = assignment.split_once('=').unwrap_or((assignment, "true"));
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(is_name_char) {
+            bail!("Invalid variable name in {:?}", assignment);
+        }
+        self.variables
+            .insert(name.to_string(), value.trim().to_string());
+        Ok(())
+    }
+
+    fn is_true(&self, name: &str) -> bool {
+        self.variables.get(name).is_some_and(|value| {
+            !matches!(
+                value.to_lowercase().as_str(),
+                "" | "false" | "0" | "no" | "não" | "nao"
+            )
+        })
+    }
+
+    /// Everything besides the source text that the filled output depends on
+    /// (variables, partials, phrases, the signature and the site), for the
+    /// conversion cache; empty when there are none.
+    pub fn cache_context(&self) -> Result<String> {
+        let mut context: String = self
+            .variables
+            .iter()
+            .map(|(name, value)| format!("{}={}\n", name, value))
+            .collect();
+        for dir in [&self.partials_dir, &self.phrases_dir] {
+            if !dir.is_dir() {
+                continue;
+            }
+            for file in list_files(dir, "md")? {
+                context.push_str(&format!("{} {}\n", file.display(), sha256_file(&file)?));
+            }
+        }
+        if let Some(signature) = &self.signature {
+            context.push_str(&format!(
+                "signature {}\n",
+                signature::lines(signature).join(" ")
+            ));
+        }
+        let settings = config::get();
+        if let (Some(id), Some(site)) = (&settings.site, settings.selected_site()) {
+            context.push_str(&format!("site {} {:?}\n", id, site));
+        }
+        Ok(context)
+    }
+
+    fn partial(&self, name: &str) -> Result<String> {
+        let path = self.partials_dir.join(format!("{}.md", name));
+        read_text(&path)
+            .with_context(|| format!("Partial `{}` not found ({})", name, path.display()))
+    }
+
+    /// Reads and fills the Markdown source at `md_path`.
+    pub fn read(&self, md_path: &Path) -> Result<String> {
+        let content = read_text(md_path)?;
+        fill(&content, self).with_context(|| format!("Cannot fill {}", md_path.display()))
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn directive_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"\{\{\s*(?:(#if\b|#unless\b|else\b|/if\b|/unless\b|phrase\b|>)\s*([^}]*?)",
+            r"|([a-z][a-z_]*)\(([^)}]*)\)|([a-z][a-z0-9_]*))\s*\}\}"
+        ))
+        .unwrap()
+    })
+}
+
+/// Whether `placeholder`, a whole `{{...}}`, is replaced when filling.
+pub fn is_directive(placeholder: &str) -> bool {
+    directive_regex()
+        .find(placeholder)
+        .is_some_and(|found| found.len() == placeholder.len())
+}
+
+enum Piece<'a> {
+    Text(&'a str),
+    Directive(&'a str, &'a str),
+    /// A computed placeholder, with its arguments.
+    Call(&'a str, &'a str),
+    /// A value placeholder.
+    Value(&'a str),
+}
+
+struct Block<'a> {
+    keyword: &'a str,
+    name: &'a str,
+    line: usize,
+    /// Whether the branch being read is kept.
+    keep: bool,
+    in_else: bool,
+}
+
+/// Expands the conditional blocks and includes of `source`, and adds the
+/// signature block.
+pub fn fill(source: &str, options: &FillOptions) -> Result<String> {
+    let filled = fill_nested(source, options, &mut Vec::new(), &mut None)?;
+    Ok(match &options.signature {
+        Some(signature) => signature::sign(&filled, signature),
+        None => filled,
+    })
+}
+
+/// `including` lists the partials being expanded, to reject cycles;
+/// `library` is the phrase library, read when first needed.
+fn fill_nested(
+    source: &str,
+    options: &FillOptions,
+    including: &mut Vec<String>,
+    library: &mut Option<PhraseLibrary>,
+) -> Result<String> {
+    let re = directive_regex();
+    let mut out = String::with_capacity(source.len());
+    let mut open: Vec<Block> = Vec::new();
+
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        let number = index + 1;
+        let mut pieces = Vec::new();
+        let mut last = 0;
+        for caps in re.captures_iter(line) {
+            let whole = caps.get(0).unwrap();
+            pieces.push(Piece::Text(&line[last..whole.start()]));
+            pieces.push(match (caps.get(1), caps.get(3), caps.get(5)) {
+                (Some(keyword), _, _) => {
+                    Piece::Directive(keyword.as_str(), caps.get(2).unwrap().as_str())
+                }
+                (_, Some(name), _) => Piece::Call(name.as_str(), caps.get(4).unwrap().as_str()),
+                (_, _, name) => Piece::Value(name.unwrap().as_str()),
+            });
+            last = whole.end();
+        }
+        pieces.push(Piece::Text(&line[last..]));
+
+        let has_directive = pieces.iter().any(|p| matches!(p, Piece::Directive(..)));
+        let only_directives = has_directive
+            && pieces.iter().all(|p| match p {
+                Piece::Text(text) => text.trim().is_empty(),
+                Piece::Directive(..) => true,
+                Piece::Call(..) | Piece::Value(..) => false,
+            });
+
+        for piece in pieces {
+            let keep = open.iter().all(|b| b.keep);
+            match piece {
+                Piece::Text(text) => {
+                    if !only_directives && keep {
+                        out.push_str(text);
+                    }
+                }
+                Piece::Call(name, arguments) => {
+                    if keep {
+                        let value = calculations::expand(name, arguments, &options.variables)
+                            .with_context(|| format!("line {}", number))?;
+                        out.push_str(&value);
+                    }
+                }
+                Piece::Value(name) => {
+                    if keep {
+                        out.push_str(&placeholders::value(name, &options.variables));
+                    }
+                }
+                Piece::Directive(">", name) => {
+                    if !keep {
+                        continue;
+                    }
+                    if name.is_empty() || !name.chars().all(|c| is_name_char(c) || c == '/') {
+                        bail!("line {}: `{{{{>}}}}` needs a partial name", number);
+                    }
+                    if including.iter().any(|n| n == name) {
+                        bail!("line {}: partial `{}` includes itself", number, name);
+                    }
+                    including.push(name.to_string());
+                    let partial = fill_nested(&options.partial(name)?, options, including, library)
+                        .with_context(|| format!("In partial `{}`", name))?;
+                    including.pop();
+                    if only_directives {
+                        out.push_str(&partial);
+                        if !partial.is_empty() && !partial.ends_with('\n') && line.ends_with('\n') {
+                            out.push('\n');
+                        }
+                    } else {
+                        out.push_str(partial.strip_suffix('\n').unwrap_or(&partial));
+                    }
+                }
+                Piece::Directive("phrase", id) => {
+                    if !keep {
+                        continue;
+                    }
+                    if library.is_none() {
+                        *library = Some(PhraseLibrary::load(&options.phrases_dir)?);
+                    }
+                    let phrase = library.as_ref().and_then(|l| l.get(id)).ok_or_else(|| {
+                        anyhow!(
+                            "line {}: phrase `{}` not found in {}",
+                            number,
+                            id,
+                            options.phrases_dir.display()
+                        )
+                    })?;
+                    out.push_str(&phrase.text);
+                    if only_directives && line.ends_with('\n') {
+                        out.push('\n');
+                    }
+                }
+                Piece::Directive(keyword @ ("#if" | "#unless"), name) => {
+                    if name.is_empty() || !name.chars().all(is_name_char) {
+                        bail!(
+                            "line {}: `{{{{{}}}}}` needs a variable name",
+                            number,
+                            keyword
+                        );
+                    }
+                    let value = options.is_true(name);
+                    open.push(Block {
+                        keyword,
+                        name,
+                        line: number,
+                        keep: if keyword == "#if" { value } else { !value },
+                        in_else: false,
+                    });
+                }
+                Piece::Directive("else", _) => {
+                    let block = open.last_mut().filter(|b| !b.in_else).ok_or_else(|| {
+                        anyhow!("line {}: `{{{{else}}}}` outside a block", number)
+                    })?;
+                    block.keep = !block.keep;
+                    block.in_else = true;
+                }
+                Piece::Directive(close, _) => {
+                    let block = open.pop().ok_or_else(|| {
+                        anyhow!("line {}: `{{{{{}}}}}` without an open block", number, close)
+                    })?;
+                    if close[1..] != block.keyword[1..] {
+                        bail!(
+                            "line {}: `{{{{{}}}}}` closes `{{{{{} {}}}}}` from line {}",
+                            number,
+                            close,
+                            block.keyword,
+                            block.name,
+                            block.line
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(block) = open.last() {
+        bail!(
+            "line {}: `{{{{{} {}}}}}` is never closed",
+            block.line,
+            block.keyword,
+            block.name
+        );
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditional_blocks_keep_the_branch_their_variable_selects() {
+        let source = "Técnica:\n{{#if contraste}}\nCom contraste{{#if fase_tardia}} e fase tardia{{/if}}.\n{{else}}\nSem contraste.\n{{/if}}\n{{#unless sedacao}}Sem sedação.{{/unless}}\nFim.\n";
+        let filled = |assignments: &[&str]| {
+            let mut options = FillOptions::default();
+            for assignment in assignments {
+                options.set(assignment).unwrap();
+            }
+            fill(source, &options).unwrap()
+        };
+        assert_eq!(
+            filled(&[]),
+            "Técnica:\nSem contraste.\nSem sedação.\nFim.\n"
+        );
+        assert_eq!(
+            filled(&["contraste", "fase_tardia=sim", "sedacao=não"]),
+            "Técnica:\nCom contraste e fase tardia.\nSem sedação.\nFim.\n"
+        );
+        assert_eq!(
+            filled(&["contraste=1", "fase_tardia=false", "sedacao"]),
+            "Técnica:\nCom contraste.\n\nFim.\n"
+        );
+
+        let error =
+            |source: &str| format!("{:#}", fill(source, &FillOptions::default()).unwrap_err());
+        assert_eq!(
+            error("{{#if a}}\nx\n"),
+            "line 1: `{{#if a}}` is never closed"
+        );
+        assert_eq!(
+            error("x\n{{/if}}"),
+            "line 2: `{{/if}}` without an open block"
+        );
+        assert_eq!(
+            error("{{#if a}}\n{{/unless}}"),
+            "line 2: `{{/unless}}` closes `{{#if a}}` from line 1"
+        );
+        assert!(FillOptions::default().set("não vale").is_err());
+    }
+
+    #[test]
+    fn includes_are_filled_partials_and_cycles_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, text: &str| std::fs::write(dir.path().join(name), text).unwrap();
+        write(
+            "tecnica.md",
+            "Cortes axiais{{#if contraste}} com contraste{{/if}}.\n",
+        );
+        write("eco.md", "{{> eco}}\n");
+        let mut options = FillOptions {
+            partials_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        options.set("contraste").unwrap();
+
+        assert_eq!(
+            fill("**Técnica:**\n{{> tecnica}}\nFim.\n", &options).unwrap(),
+            "**Técnica:**\nCortes axiais com contraste.\nFim.\n"
+        );
+        assert_eq!(
+            fill("**Técnica:** {{> tecnica}} Fim.\n", &options).unwrap(),
+            "**Técnica:** Cortes axiais com contraste. Fim.\n"
+        );
+        let error = |source: &str| format!("{:#}", fill(source, &options).unwrap_err());
+        assert!(error("{{> eco}}\n").ends_with("partial `eco` includes itself"));
+        assert!(error("{{> nenhum}}\n").starts_with("Partial `nenhum` not found"));
+    }
+}
@@ -0,0 +1,121 @@
+//! Footnotes, such as the citation of the Fleischner criteria under a
+//! nodule measurement: `[^1]` in the text refers to the note defined on a
+//! line of its own, `[^1]: MacMahon H et al. Radiology 2017.`, anywhere in
+//! the template.
+//!
+//! `ReportTemplate::from_markdown` takes the definitions out of the body,
+//! so the title and footer lines are found as without them. DOCX and PDF
+//! write real footnotes, numbered in the order the text refers to them, at
+//! the foot of the page; the other formats show the reference as its label in
+//! brackets, `[1]`, and list the notes after the text (`ReportTemplate::blocks_with_notes`).
+//! The DOCX importer writes Word's footnotes and endnotes this way.
+
+use crate::markdown::{note_at, parse_inline, Block};
+
+/// A note and the label the text refers to it by.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Footnote {
+    pub label: String,
+    /// Inline Markdown.
+    pub text: String,
+}
+
+impl Footnote {
+    /// The note's definition line, `[^label]: text`.
+    pub fn to_markdown(&self) -> String {
+        format!("[^{}]: {}", self.label, self.text)
+    }
+}
+
+/// The note `line` defines, if it is a `[^label]: text` line.
+pub fn definition(line: &str) -> Option<Footnote> {
+    let body = line.trim_start();
+    if line.len() - body.len() > 3 {
+        return None;
+    }
+    let chars: Vec<char> = body.chars().collect();
+    let (label, end) = note_at(&chars, 0)?;
+    if chars.get(end) != Some(&':') {
+        return None;
+    }
+    let text: String = chars[end + 1..].iter().collect();
+    Some(Footnote {
+        label,
+        text: text.trim().to_string(),
+    })
+}
+
+/// `content` without its definition lines, and the notes they define in
+/// the order written.
+pub fn split(content: &str) -> (String, Vec<Footnote>) {
+    let mut notes = Vec::new();
+    let mut body = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        match definition(line) {
+            Some(note) => notes.push(note),
+            None => body.push_str(line),
+        }
+    }
+    (body, notes)
+}
+
+/// The labels of the notes the inline Markdown `text` refers to, in order.
+pub fn references(text: &str) -> Vec<String> {
+    parse_inline(text, false)
+        .into_iter()
+        .filter_map(|run| run.note)
+        .collect()
+}
+
+/// The notes of `notes` the text of `blocks` refers to, in the order of
+/// their first reference; those never referred to are left out.
+pub fn referenced<'a>(blocks: &[Block], notes: &'a [Footnote]) -> Vec<&'a Footnote> {
+    let mut referenced: Vec<&Footnote> = Vec::new();
+    let texts = blocks.iter().flat_map(|block| match block {
+        Block::Paragraph { text, .. } => vec![text.as_str()],
+        Block::Table { header, rows } => header
+            .iter()
+            .chain(rows.iter().flatten())
+            .map(String::as_str)
+            .collect(),
+    });
+    for label in texts.flat_map(references) {
+        match notes.iter().find(|note| note.label == label) {
+            Some(note) if !referenced.contains(&note) => referenced.push(note),
+            _ => {}
+        }
+    }
+    referenced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::LineStyle;
+
+    #[test]
+    fn definitions_are_split_from_the_text() {
+        let content = "TC\n\nNódulo de 5 mm[^fleischner].[^2]\n\n\
+                       [^2]: Segunda nota.\n[^fleischner]: MacMahon H *et al.*\nRodapé.\n";
+        let (body, notes) = split(content);
+        assert_eq!(body, "TC\n\nNódulo de 5 mm[^fleischner].[^2]\n\nRodapé.\n");
+        assert_eq!(notes[1].label, "fleischner");
+        assert_eq!(notes[1].text, "MacMahon H *et al.*");
+        assert_eq!(notes[0].to_markdown(), "[^2]: Segunda nota.");
+        assert!(definition("[^a b]: não").is_none() && definition("[^1] texto").is_none());
+
+        let runs = parse_inline("5 mm[^fleischner]", false);
+        assert_eq!(runs[1].text, "[fleischner]");
+        assert_eq!(runs[1].note.as_deref(), Some("fleischner"));
+        let paragraph = Block::Paragraph {
+            text: "Nódulo[^fleischner] e [^3][^2].".to_string(),
+            heading: None,
+            style: LineStyle::default(),
+        };
+        let labels: Vec<&str> = referenced(&[paragraph], &notes)
+            .iter()
+            .map(|note| note.label.as_str())
+            .collect();
+        assert_eq!(labels, ["fleischner", "2"]);
+    }
+}
@@ -0,0 +1,333 @@
+//! YAML front matter at the top of a Markdown template:
+//!
+//! ```text
+//! ---
+//! title: TC DE TÓRAX
+//! footer: Laudo sujeito a revisão.
+//! modality: CT
+//! body_region: abdome
+//! contrast: true
+//! language: pt-BR
+//! author: Dr. Fulano
+//! version: 1.2
+//! keywords: nódulo pulmonar, Lung-RADS
+//! tags: [oncologia, estadiamento, TNM]
+//! aliases: [RM Reação ao Contraste]
+//! exports: [md, docx, txt]
+//! created: 2024-03-01
+//! ---
+//! ```
+//!
+//! Only flat `key: value` pairs are read; values may be quoted and `#`
+//! starts a comment. `tags` is a list, in brackets or just separated by
+//! commas, and so are `aliases` and `exports`. The block is metadata:
+//! writers render only the title and footer it names when the body lacks
+//! them, and the index lists it per template.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::pick::fold;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrontMatter {
+    /// The title and closing footer line: the first and last lines of the
+    /// body when they match, else rendered from here (see `template`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modality: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_region: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contrast: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<String>,
+    /// Free labels to organize the library by beyond modality
+    /// (`oncologia`, `TNM`), as written.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Other names the template is also exported under (see `aliases`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// The output formats the template is converted to, by their `--to`
+    /// names; every format when absent (see `exports`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exports: Option<Vec<String>>,
+    /// When the template was first written, `YYYY-MM-DD`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// DOCX page header and footer of this template, in place of the ones
+    /// in `rtemplates.toml`; image paths are relative to the Markdown file
+    /// and an empty value leaves the page without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_header: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_footer: Option<String>,
+    /// The disclaimer added after the footer, in place of the one in
+    /// `rtemplates.toml`; an empty value leaves it out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disclaimer: Option<String>,
+    /// Any other keys, as written.
+    #[serde(flatten)]
+    pub other: BTreeMap<String, String>,
+}
+
+impl FrontMatter {
+    /// Whether `tag` is one of the tags, ignoring case and accents.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let folded = |text: &str| text.trim().chars().map(fold).collect::<String>();
+        let tag = folded(tag);
+        self.tags.iter().any(|own| folded(own) == tag)
+    }
+}
+
+fn is_fence(line: &str, markers: &[&str]) -> bool {
+    markers.contains(&line.trim_end())
+}
+
+/// Splits `content` into its front matter (the lines between the `---`
+/// fences) and the body after it. Blank lines following the closing fence
+/// belong to the front matter block. Without an opening fence on the first
+/// line, or without a closing one, the whole content is the body.
+pub fn split(content: &str) -> (Option<&str>, &str) {
+    let mut lines = content.split_inclusive('\n');
+    if !lines.next().is_some_and(|first| is_fence(first, &["---"])) {
+        return (None, content);
+    }
+    let yaml_start = content.find('\n').map_or(content.len(), |i| i + 1);
+
+    let mut offset = yaml_start;
+    for line in lines {
+        let end = offset + line.len();
+        if is_fence(line, &["---", "..."]) {
+            let yaml = &content[yaml_start..offset];
+            let mut body = end;
+            for rest in content[end..].split_inclusive('\n') {
+                if !rest.trim().is_empty() {
+                    break;
+                }
+                body += rest.len();
+            }
+            return (Some(yaml), &content[body..]);
+        }
+        offset = end;
+    }
+    (None, content)
+}
+
+/// `content` without its front matter.
+pub fn strip(content: &str) -> &str {
+    split(content).1
+}
+
+/// `content` with `key: value` lines added at the end of its front matter,
+/// which is created when there is none. Keys already set are not checked.
+pub fn with_keys(content: &str, keys: &[(&str, &str)]) -> String {
+    let lines: String = keys
+        .iter()
+        .map(|(key, value)| format!("{}: {}\n", key, value))
+        .collect();
+    match split(content).0 {
+        Some(yaml) => {
+            let start = content.find('\n').map_or(content.len(), |i| i + 1);
+            let end = start + yaml.len();
+            format!("{}{}{}", &content[..end], lines, &content[end..])
+        }
+        None => format!("---\n{}---\n{}", lines, content),
+    }
+}
+
+/// `content` with `key` set to `value` in its front matter: the key's line
+/// is replaced, or added as by `with_keys`, and an empty value removes it.
+/// Values a comment or quotes would change are written quoted.
+pub fn with_key(content: &str, key: &str, value: &str) -> String {
+    let value = value.trim();
+    let value = if value.starts_with(['"', '\'']) || value.contains(" #") {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    };
+    let Some(yaml) = split(content).0 else {
+        return if value.is_empty() {
+            content.to_string()
+        } else {
+            with_keys(content, &[(key, &value)])
+        };
+    };
+    let start = content.find('\n').map_or(content.len(), |i| i + 1);
+    let end = start + yaml.len();
+    let mut found = false;
+    let mut lines = String::new();
+    for line in yaml.split_inclusive('\n') {
+        let names_key = !line.starts_with(char::is_whitespace)
+            && line
+                .split_once(':')
+                .is_some_and(|(name, _)| name.trim() == key);
+        if !names_key {
+            lines.push_str(line);
+        } else if !found && !value.is_empty() {
+            lines.push_str(&format!("{}: {}\n", key, value));
+        }
+        found |= names_key;
+    }
+    if !found && !value.is_empty() {
+        lines.push_str(&format!("{}: {}\n", key, value));
+    }
+    format!("{}{}{}", &content[..start], lines, &content[end..])
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+/// Drops a `#` comment that is not inside quotes.
+fn without_comment(value: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in value.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) if i == 0 || value[..i].ends_with(char::is_whitespace) => {
+                return &value[..i];
+            }
+            _ => {}
+        }
+    }
+    value
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "sim" | "com" => Ok(true),
+        "false" | "no" | "não" | "nao" | "sem" => Ok(false),
+        _ => bail!("`{}` must be true or false, not {:?}", key, value),
+    }
+}
+
+/// The items of a `[a, b]` or `a, b` list.
+pub fn parse_list(value: &str) -> Vec<String> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .unwrap_or(value);
+    value
+        .split(',')
+        .map(|item| unquote(item.trim()).trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// `items` as the `tags` value `parse_list` reads back.
+pub fn list_value(items: &[String]) -> String {
+    if items.is_empty() {
+        String::new()
+    } else {
+        format!("[{}]", items.join(", "))
+    }
+}
+
+/// Parses the text between the fences.
+pub fn parse(yaml: &str) -> Result<FrontMatter> {
+    let mut front = FrontMatter::default();
+    for (index, line) in yaml.lines().enumerate() {
+        let line = without_comment(line).trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with(char::is_whitespace) {
+            bail!("line {}: nested values are not supported", index + 1);
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            bail!("line {}: expected `key: value`", index + 1);
+        };
+        let key = key.trim();
+        let value = unquote(value.trim()).to_string();
+        let slot = match key {
+            "title" => &mut front.title,
+            "footer" => &mut front.footer,
+            "modality" => &mut front.modality,
+            "body_region" => &mut front.body_region,
+            "language" => &mut front.language,
+            "author" => &mut front.author,
+            "version" => &mut front.version,
+            "keywords" => &mut front.keywords,
+            "created" => &mut front.created,
+            "page_header" => &mut front.page_header,
+            "page_footer" => &mut front.page_footer,
+            "disclaimer" => &mut front.disclaimer,
+            "contrast" => {
+                front.contrast = Some(parse_bool(key, &value)?);
+                continue;
+            }
+            "tags" => {
+                front.tags = parse_list(&value);
+                continue;
+            }
+            "aliases" => {
+                front.aliases = parse_list(&value);
+                continue;
+            }
+            "exports" => {
+                front.exports = Some(parse_list(&value));
+                continue;
+            }
+            _ => {
+                front.other.insert(key.to_string(), value);
+                continue;
+            }
+        };
+        *slot = Some(value);
+    }
+    Ok(front)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_is_split_from_the_body_and_read_as_flat_keys() {
+        let content = "---\ntitle: \"TC DE TÓRAX\"\nmodality: CT # tomografia\ncontrast: sim\ntags: [Oncologia, 'TNM']\nsala: 3\n---\n\n**TC DE TÓRAX**\n";
+        let (yaml, body) = split(content);
+        assert_eq!(body, "**TC DE TÓRAX**\n");
+        assert_eq!(strip("**TC**\n---\n"), "**TC**\n---\n");
+        assert_eq!(split("---\ntitle: x\n"), (None, "---\ntitle: x\n"));
+
+        let front = parse(yaml.unwrap()).unwrap();
+        assert_eq!(front.title.as_deref(), Some("TC DE TÓRAX"));
+        assert_eq!(front.modality.as_deref(), Some("CT"));
+        assert_eq!(front.contrast, Some(true));
+        assert_eq!(front.tags, ["Oncologia", "TNM"]);
+        assert!(front.has_tag("oncologia"));
+        assert_eq!(front.other["sala"], "3");
+
+        assert_eq!(
+            parse("title: x\n  nested: y").unwrap_err().to_string(),
+            "line 2: nested values are not supported"
+        );
+        assert!(parse("contrast: talvez").is_err());
+        assert_eq!(
+            with_keys("**TC**\n", &[("modality", "CT")]),
+            "---\nmodality: CT\n---\n**TC**\n"
+        );
+    }
+}
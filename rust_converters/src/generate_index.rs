@@ -0,0 +1,269 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::ser::{PrettyFormatter, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::cache::sha256_file;
+use crate::classify::classify;
+use crate::convert_txt_to_markdown::format_lines_as_markdown;
+use crate::encoding::read_text;
+use crate::exports;
+use crate::files::slash_path;
+use crate::front_matter::{self, FrontMatter};
+use crate::metrics::{metrics, Metrics};
+use crate::pack;
+use crate::pick::fold;
+use crate::targets;
+use crate::template::ReportTemplate;
+use crate::timestamp::Timestamp;
+
+/// The file lists of every tracked folder, plus what was read from each
+/// file when the index was built.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    #[serde(flatten)]
+    pub folders: BTreeMap<String, Vec<String>>,
+    /// The Markdown templates carrying each front matter tag, by tag; tags
+    /// differing only in case or accents are one, spelled as first met.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, Vec<String>>,
+    /// The Markdown template each alias names, both as paths in `folders`
+    /// (`Templates_markdown/RM Reação ao Contraste.md`); the alias has no
+    /// Markdown file of its own (see `aliases`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// Keyed by the file's path in `folders`.
+    #[serde(default)]
+    pub files: BTreeMap<String, FileEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub sha256: String,
+    pub size: u64,
+    /// Modification time in UTC, `YYYY-MM-DDTHH:MM:SSZ`.
+    pub modified: String,
+    pub title: Option<String>,
+    pub modality: Option<String>,
+    /// The front matter `body_region`, or the one the title or file name
+    /// names (see `classify`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_region: Option<String>,
+    /// Section names in order (`Informe clínico`, `Técnica do exame`, ...).
+    pub sections: Vec<String>,
+    /// Length and readability, for templates that could be parsed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<Metrics>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub front_matter: Option<FrontMatter>,
+}
+
+pub fn collect_files(root: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut index = BTreeMap::new();
+
+    for (folder, ext) in targets() {
+        let dir = root.join(folder);
+        if !dir.exists() {
+            warn!("Skipping missing folder: {}", dir.display());
+            index.insert(folder.to_string(), Vec::new());
+            continue;
+        }
+
+        let mut files: Vec<String> = fs::read_dir(&dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                p.is_file()
+                    && p.extension()
+                        .and_then(|s| s.to_str())
+                        .map(|s| s.eq_ignore_ascii_case(ext))
+                        .unwrap_or(false)
+            })
+            .map(|p| {
+                p.strip_prefix(root)
+                    .unwrap_or(&p)
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+
+        files.sort();
+        index.insert(folder.to_string(), files);
+    }
+
+    Ok(index)
+}
+
+fn read_template(path: &Path) -> Result<ReportTemplate> {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("docx") => ReportTemplate::from_docx(path),
+        Some("txt") => {
+            let content = read_text(path)?;
+            let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+            Ok(ReportTemplate::from_markdown(
+                &format_lines_as_markdown(&lines).join("\n"),
+            ))
+        }
+        _ => Ok(ReportTemplate::from_markdown(&read_text(path)?)),
+    }
+}
+
+/// Checksum, size, modification time and detected structure of `path`.
+/// A file that cannot be parsed is still listed, without title or sections.
+pub fn file_entry(path: &Path) -> Result<FileEntry> {
+    let metadata = fs::metadata(path)?;
+    let mut entry = FileEntry {
+        sha256: sha256_file(path)?,
+        size: metadata.len(),
+        modified: Timestamp::from_system_time(metadata.modified()?).iso_datetime(),
+        title: None,
+        modality: None,
+        body_region: None,
+        sections: Vec::new(),
+        metrics: None,
+        front_matter: None,
+    };
+
+    if path.extension().and_then(|s| s.to_str()) == Some("md") {
+        let content = read_text(path)?;
+        if let (Some(yaml), _) = front_matter::split(&content) {
+            let front = front_matter::parse(yaml)
+                .with_context(|| format!("Invalid front matter in {}", path.display()))?;
+            entry.front_matter = Some(front);
+        }
+    }
+    match read_template(path) {
+        Ok(template) => {
+            entry.title = template
+                .title
+                .as_ref()
+                .map(|title| title.plain())
+                .filter(|title| !title.is_empty());
+            entry.sections = template.sections.iter().filter_map(|s| s.name()).collect();
+            entry.metrics = Some(metrics(&template));
+            entry.modality = template.modality;
+        }
+        Err(err) => warn!("Cannot read {}: {}", path.display(), err),
+    }
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    let inferred = classify(&name, entry.title.as_deref());
+    entry.modality = entry.modality.or(inferred.modality);
+    entry.body_region = entry
+        .front_matter
+        .as_ref()
+        .and_then(|front| front.body_region.clone())
+        .or(inferred.body_region);
+    Ok(entry)
+}
+
+/// Reads an index written by `write_index`; older indexes without `files`
+/// are accepted.
+pub fn read_index(path: &Path) -> Result<Index> {
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).with_context(|| format!("Invalid index {}", path.display()))
+}
+
+/// `value` as JSON indented by two spaces, as the index is written.
+pub fn to_json_pretty<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let formatter = PrettyFormatter::with_indent(b"  ");
+    let mut serializer = Serializer::with_formatter(&mut buffer, formatter);
+    value.serialize(&mut serializer)?;
+    Ok(buffer)
+}
+
+pub fn write_json_pretty<T: Serialize>(value: &T, path: &Path) -> Result<()> {
+    fs::write(path, to_json_pretty(value)?)?;
+    Ok(())
+}
+
+/// Indexes the template folders under `root` and writes the result to `output`.
+/// Outputs of templates not converted to their format are left out, so a
+/// backup moves them away. Imported packs are indexed too, though `folders`
+/// lists the root's own folders alone.
+pub fn write_index(root: &Path, output: &Path) -> Result<()> {
+    let mut folders = collect_files(root)?;
+    for (folder, stems) in exports::left_out_by_folder(root)? {
+        if let Some(files) = folders.get_mut(folder) {
+            files.retain(|file| {
+                let stem = Path::new(file).file_stem().unwrap_or_default();
+                !stems.contains(stem.to_string_lossy().as_ref())
+            });
+        }
+    }
+    let mut files = BTreeMap::new();
+    let mut tags: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut aliases = BTreeMap::new();
+    let mut spellings: HashMap<String, String> = HashMap::new();
+    let imported = pack::imported_files(root)?;
+    for file in folders.values().flatten().chain(&imported) {
+        let path = root.join(file);
+        let entry = file_entry(&path)?;
+        if path.extension().and_then(|s| s.to_str()) == Some("md") {
+            for alias in aliases::of(&path, &read_text(&path)?) {
+                let alias = slash_path(&Path::new(file).with_file_name(format!("{}.md", alias)));
+                aliases.entry(alias).or_insert_with(|| file.clone());
+            }
+        }
+        for tag in entry.front_matter.iter().flat_map(|front| &front.tags) {
+            let folded = tag.chars().map(fold).collect();
+            let tag = spellings.entry(folded).or_insert(tag.clone());
+            let files = tags.entry(tag.clone()).or_default();
+            if !files.contains(file) {
+                files.push(file.clone());
+            }
+        }
+        files.insert(file.clone(), entry);
+    }
+    let index = Index {
+        folders,
+        tags,
+        aliases,
+        files,
+    };
+    write_json_pretty(&index, output)?;
+    info!("\nIndex written to {}", output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn entries_record_checksum_title_and_sections_per_file() {
+        let root = tempfile::tempdir().unwrap();
+        let markdown = root.path().join(&config::get().directories.markdown);
+        fs::create_dir_all(&markdown).unwrap();
+        let content = "---\ntags: [Oncologia]\n---\n**TC DE TÓRAX**\n\n**Técnica do exame:**\n\nCortes.\n\n**Impressão:**\n\nNormal.\n";
+        fs::write(markdown.join("TC Torax.md"), content).unwrap();
+        fs::write(markdown.join("notas.txt"), "x").unwrap();
+
+        let output = root.path().join("reports_index.json");
+        write_index(root.path(), &output).unwrap();
+        let index = read_index(&output).unwrap();
+        let path = format!("{}/TC Torax.md", config::get().directories.markdown);
+        assert_eq!(
+            index.folders[&config::get().directories.markdown],
+            std::slice::from_ref(&path)
+        );
+        assert_eq!(index.tags["Oncologia"], std::slice::from_ref(&path));
+
+        let entry = &index.files[&path];
+        assert_eq!(entry.size, content.len() as u64);
+        assert_eq!(
+            entry.sha256,
+            format!("{:x}", Sha256::digest(content.as_bytes()))
+        );
+        assert_eq!(entry.title.as_deref(), Some("TC DE TÓRAX"));
+        assert_eq!(entry.modality.as_deref(), Some("CT"));
+        assert_eq!(entry.sections, ["Técnica do exame", "Impressão"]);
+        assert!(entry.modified.ends_with('Z'));
+    }
+}
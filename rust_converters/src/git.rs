@@ -0,0 +1,152 @@
+//! Asks git which files changed, for `convert --changed`, so that CI
+//! regenerating the outputs converts only the templates touched since the
+//! last commit, or any other ref, instead of the whole library; and which
+//! are staged, for the pre-commit `hook`.
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs `git` in `dir` and returns what it prints.
+fn run(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Cannot run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed in {}: {}",
+            args.join(" "),
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs `git` in `dir` and returns the NUL-separated names it prints.
+fn names(dir: &Path, args: &[&str]) -> Result<Vec<PathBuf>> {
+    Ok(run(dir, args)?
+        .split('\0')
+        .filter(|name| !name.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// The files under `dir` whose working tree contents differ from
+/// `reference`, relative to `dir`, with the new files git does not track
+/// yet (ignored ones aside). Deleted files are listed too.
+pub fn changed_files(dir: &Path, reference: &str) -> Result<BTreeSet<PathBuf>> {
+    let mut changed: BTreeSet<PathBuf> = names(
+        dir,
+        &[
+            "diff",
+            "--name-only",
+            "--relative",
+            "-z",
+            reference,
+            "--",
+            ".",
+        ],
+    )?
+    .into_iter()
+    .collect();
+    changed.extend(names(
+        dir,
+        &[
+            "ls-files",
+            "--others",
+            "--exclude-standard",
+            "-z",
+            "--",
+            ".",
+        ],
+    )?);
+    Ok(changed)
+}
+
+/// The files under `dir` added, copied, modified or renamed in the index,
+/// relative to `dir`.
+pub fn staged_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    names(
+        dir,
+        &[
+            "diff",
+            "--cached",
+            "--name-only",
+            "--relative",
+            "--diff-filter=ACMR",
+            "-z",
+            "--",
+            ".",
+        ],
+    )
+}
+
+/// Adds `paths`, relative to `dir`, to the index.
+pub fn stage(dir: &Path, paths: &[PathBuf]) -> Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    let mut args = vec!["add", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    run(dir, &args)?;
+    Ok(())
+}
+
+/// The hooks folder of the repository `dir` is in.
+pub fn hooks_dir(dir: &Path) -> Result<PathBuf> {
+    let path = PathBuf::from(run(dir, &["rev-parse", "--git-path", "hooks"])?.trim());
+    Ok(dir.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn changed_files_lists_edited_and_new_files_since_the_ref() {
+        let repo = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(repo.path())
+                .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        let markdown = repo.path().join("Templates_markdown");
+        fs::create_dir_all(&markdown).unwrap();
+        for name in ["US ABDOME.md", "TC TÓRAX.md", "RM CRÂNIO.md"] {
+            fs::write(markdown.join(name), "**LAUDO**\n").unwrap();
+        }
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "Templates"]);
+
+        assert!(changed_files(&markdown, "HEAD").unwrap().is_empty());
+        fs::write(markdown.join("TC TÓRAX.md"), "**TC DE TÓRAX**\n").unwrap();
+        fs::write(markdown.join("US PÉLVICA.md"), "**US**\n").unwrap();
+        fs::write(repo.path().join("outro.md"), "**LAUDO**\n").unwrap();
+        let changed: Vec<_> = changed_files(&markdown, "HEAD")
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(
+            changed,
+            [Path::new("TC TÓRAX.md"), Path::new("US PÉLVICA.md")]
+        );
+        assert!(changed_files(&markdown, "nonexistent-ref").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Version history of templates. Each recorded version of a file keeps its
+//! SHA-256, the time and an optional message in `<dir>/<path>.json`, and
+//! its contents under `<dir>/objects/`, stored once per hash, so any
+//! version can be read back (`rtemplates show --at`).
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::generate_index::write_json_pretty;
+use crate::timestamp::Timestamp;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Version {
+    /// 1 for the first recorded version of the file.
+    pub version: usize,
+    /// SHA-256 of the file's contents.
+    pub hash: String,
+    pub time: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Where the versions of `template` are listed: its path relative to the
+/// working directory (or without its root when outside it), plus `.json`.
+fn log_path(dir: &Path, template: &Path) -> PathBuf {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let relative = template.strip_prefix(&cwd).unwrap_or(template);
+    let mut name: PathBuf = relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect();
+    name.as_mut_os_string().push(".json");
+    dir.join(name)
+}
+
+fn object_path(dir: &Path, hash: &str) -> PathBuf {
+    dir.join("objects").join(hash)
+}
+
+/// The recorded versions of `template`, oldest first; none when it has no
+/// history yet.
+pub fn versions(dir: &Path, template: &Path) -> Result<Vec<Version>> {
+    let path = log_path(dir, template);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Cannot read history {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Invalid history {}", path.display()))
+}
+
+/// Records the current contents of `template` as a new version, unless
+/// they are those of the last one. Returns the version recorded.
+pub fn record(dir: &Path, template: &Path, message: Option<&str>) -> Result<Option<Version>> {
+    let data = fs::read(template)?;
+    let hash = format!("{:x}", Sha256::digest(&data));
+    let mut versions = versions(dir, template)?;
+    if versions.last().is_some_and(|last| last.hash == hash) {
+        return Ok(None);
+    }
+
+    let object = object_path(dir, &hash);
+    if !object.exists() {
+        fs::create_dir_all(object.parent().unwrap())?;
+        fs::write(&object, &data)?;
+    }
+    let version = Version {
+        version: versions.len() + 1,
+        hash,
+        time: Timestamp::now().iso_datetime(),
+        message: message.map(str::to_string),
+    };
+    versions.push(version.clone());
+    let path = log_path(dir, template);
+    fs::create_dir_all(path.parent().unwrap())?;
+    write_json_pretty(&versions, &path)?;
+    Ok(Some(version))
+}
+
+/// The contents `template` had at `version`.
+pub fn contents_at(dir: &Path, template: &Path, version: usize) -> Result<Vec<u8>> {
+    let versions = versions(dir, template)?;
+    let Some(found) = versions.iter().find(|v| v.version == version) else {
+        match versions.len() {
+            0 => bail!("{} has no recorded versions", template.display()),
+            count => bail!(
+                "{} has no version {}; versions 1 to {} are recorded",
+                template.display(),
+                version,
+                count
+            ),
+        }
+    };
+    let object = object_path(dir, &found.hash);
+    fs::read(&object).with_context(|| {
+        format!(
+            "Version {} of {} is missing from {}",
+            version,
+            template.display(),
+            object.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_recorded_once_per_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = dir.path().join("history");
+        let template = dir.path().join("laudo.md");
+
+        fs::write(&template, "TC DE TÓRAX\n").unwrap();
+        let first = record(&history, &template, Some("first draft")).unwrap();
+        assert_eq!(first.unwrap().version, 1);
+        assert_eq!(record(&history, &template, None).unwrap(), None);
+        fs::write(&template, "TC DE TÓRAX\n\nNormal.\n").unwrap();
+        assert_eq!(
+            record(&history, &template, None).unwrap().unwrap().version,
+            2
+        );
+
+        let versions = versions(&history, &template).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].message.as_deref(), Some("first draft"));
+        assert_eq!(
+            contents_at(&history, &template, 1).unwrap(),
+            "TC DE TÓRAX\n".as_bytes()
+        );
+        assert!(contents_at(&history, &template, 3).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+//! HL7 v2 ORU^R01 result messages from a filled-in template, for pushing
+//! reports into a RIS.
+//!
+//! Patient and exam identifiers come from a small JSON file (see
+//! `ExamFields`); the report text comes from the template sections, one
+//! `OBX` segment per section coded with its LOINC radiology section code.
+//! Lines inside a section are joined with the `\.br\` line break escape of
+//! the `FT` data type.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::config;
+use crate::fill::FillOptions;
+use crate::sections::ReportSections;
+use crate::template::ReportTemplate;
+use crate::timestamp::Timestamp;
+
+const VERSION: &str = "2.5.1";
+/// Segments are terminated by a carriage return.
+const SEGMENT_END: &str = "\r";
+
+/// Patient and exam fields written into the message. Missing keys are left
+/// empty, except where noted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ExamFields {
+    pub patient_id: String,
+    /// `Family^Given`, as HL7 expects.
+    pub patient_name: String,
+    /// `YYYYMMDD`.
+    pub birth_date: String,
+    /// `F`, `M`, `O` or `U`.
+    pub sex: String,
+    pub accession_number: String,
+    pub exam_code: String,
+    /// Defaults to the template title.
+    pub exam_description: String,
+    /// `YYYYMMDDHHMMSS`; defaults to the time the message is built.
+    pub exam_datetime: String,
+    pub ordering_provider: String,
+    /// Defaults to `RTEMPLATES`.
+    pub sending_application: String,
+    /// Defaults to the `{{institution}}` placeholder, the selected site's
+    /// name.
+    pub sending_facility: String,
+    pub receiving_application: String,
+    pub receiving_facility: String,
+    /// Defaults to a timestamp-based ID.
+    pub message_control_id: String,
+    /// `P` (preliminary) or `F` (final); defaults to `F`.
+    pub result_status: String,
+    /// `P` (production), `T` (training) or `D` (debugging); defaults to `P`.
+    pub processing_id: String,
+}
+
+impl Default for ExamFields {
+    fn default() -> Self {
+        ExamFields {
+            patient_id: String::new(),
+            patient_name: String::new(),
+            birth_date: String::new(),
+            sex: String::new(),
+            accession_number: String::new(),
+            exam_code: String::new(),
+            exam_description: String::new(),
+            exam_datetime: String::new(),
+            ordering_provider: String::new(),
+            sending_application: "RTEMPLATES".to_string(),
+            sending_facility: String::new(),
+            receiving_application: String::new(),
+            receiving_facility: String::new(),
+            message_control_id: String::new(),
+            result_status: "F".to_string(),
+            processing_id: "P".to_string(),
+        }
+    }
+}
+
+impl ExamFields {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read exam fields {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid exam fields in {}", path.display()))
+    }
+}
+
+/// Escapes the encoding characters (`|^~\&`) inside a field value.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\E\\"),
+            '|' => out.push_str("\\F\\"),
+            '^' => out.push_str("\\S\\"),
+            '~' => out.push_str("\\R\\"),
+            '&' => out.push_str("\\T\\"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// A formatted-text value: escaped lines joined with `\.br\`.
+fn formatted_text(text: &str) -> String {
+    text.lines().map(escape).collect::<Vec<_>>().join("\\.br\\")
+}
+
+fn segment(fields: &[&str]) -> String {
+    format!("{}{}", fields.join("|"), SEGMENT_END)
+}
+
+/// Builds an ORU^R01 message for `template`, with `now` as the message time.
+pub fn oru_r01(template: &ReportTemplate, fields: &ExamFields, now: Timestamp) -> String {
+    let sections = ReportSections::from(template);
+    let timestamp = format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        now.year, now.month, now.day, now.hour, now.minute, now.second
+    );
+    let or_default = |value: &str, default: &str| {
+        if value.is_empty() {
+            default.to_string()
+        } else {
+            value.to_string()
+        }
+    };
+    let control_id = or_default(&fields.message_control_id, &timestamp);
+    let facility = or_default(
+        &fields.sending_facility,
+        config::get().placeholder("institution").unwrap_or(""),
+    );
+    let exam_datetime = or_default(&fields.exam_datetime, &timestamp);
+    let description = or_default(
+        &fields.exam_description,
+        sections.titulo.as_deref().unwrap_or(""),
+    );
+    let status = escape(&fields.result_status);
+
+    let mut message = segment(&[
+        "MSH",
+        "^~\\&",
+        &escape(&fields.sending_application),
+        &escape(&facility),
+        &escape(&fields.receiving_application),
+        &escape(&fields.receiving_facility),
+        &timestamp,
+        "",
+        "ORU^R01^ORU_R01",
+        &escape(&control_id),
+        &escape(&fields.processing_id),
+        VERSION,
+    ]);
+    message.push_str(&segment(&[
+        "PID",
+        "1",
+        "",
+        &escape(&fields.patient_id),
+        "",
+        &fields
+            .patient_name
+            .split('^')
+            .map(escape)
+            .collect::<Vec<_>>()
+            .join("^"),
+        "",
+        &escape(&fields.birth_date),
+        &escape(&fields.sex),
+    ]));
+    let service = format!("{}^{}", escape(&fields.exam_code), escape(&description));
+    let mut obr = vec![
+        "OBR".to_string(),
+        "1".to_string(),
+        String::new(),
+        escape(&fields.accession_number),
+        service,
+        String::new(),
+        String::new(),
+        escape(&exam_datetime),
+    ];
+    obr.resize(16, String::new());
+    obr.push(escape(&fields.ordering_provider));
+    obr.resize(22, String::new());
+    obr.push(timestamp.clone());
+    obr.resize(25, String::new());
+    obr.push(status.clone());
+    message.push_str(&segment(
+        &obr.iter().map(String::as_str).collect::<Vec<_>>(),
+    ));
+
+    let observations = [
+        (
+            "18785-6",
+            "Radiology Reason for study",
+            &sections.indicacao_clinica,
+        ),
+        (
+            "55111-9",
+            "Current imaging procedure descriptions",
+            &sections.tecnica,
+        ),
+        (
+            "18782-3",
+            "Radiology Study observation",
+            &sections.aspectos_observados,
+        ),
+        ("19005-8", "Radiology Impression", &sections.impressao),
+    ];
+    let mut set_id = 0;
+    for (code, name, text) in observations {
+        let Some(text) = text else { continue };
+        set_id += 1;
+        message.push_str(&segment(&[
+            "OBX",
+            &set_id.to_string(),
+            "FT",
+            &format!("{code}^{name}^LN"),
+            "",
+            &formatted_text(text),
+            "",
+            "",
+            "",
+            "",
+            "",
+            &status,
+        ]));
+    }
+    if let Some(footer) = &sections.rodape {
+        message.push_str(&segment(&["NTE", "1", "L", &formatted_text(footer)]));
+    }
+    message
+}
+
+/// Reads a `.md` or `.docx` template and the exam fields at `fields_path`
+/// and returns the ORU^R01 message.
+pub fn message_for_file(template_path: &Path, fields_path: &Path) -> Result<String> {
+    let is_docx = template_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("docx"));
+    let template = if is_docx {
+        ReportTemplate::from_docx(template_path)?
+    } else {
+        let content = FillOptions::default()
+            .read(template_path)
+            .with_context(|| format!("Cannot read template {}", template_path.display()))?;
+        ReportTemplate::from_markdown(&content)
+    };
+    let fields = ExamFields::load(fields_path)?;
+    Ok(oru_r01(&template, &fields, Timestamp::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_become_escaped_obx_segments() {
+        assert_eq!(escape(r"a|b^c~d\e&f"), r"a\F\b\S\c\R\d\E\e\T\f");
+        let markdown = "**TC DE CRÂNIO**\n\n**Indicação clínica:** cefaleia | febre.\n\n**Técnica:**\n\nCortes axiais.\nSem contraste.\n\n**Impressão:**\n\nNormal.\n\n*Laudo assinado eletronicamente.*";
+        let fields = ExamFields {
+            patient_id: "123".into(),
+            patient_name: "Silva^Maria".into(),
+            accession_number: "A1".into(),
+            exam_code: "CT-HEAD".into(),
+            message_control_id: "MSG1".into(),
+            ..Default::default()
+        };
+        let now = Timestamp {
+            year: 2024,
+            month: 5,
+            day: 1,
+            hour: 8,
+            minute: 30,
+            second: 0,
+        };
+        let message = oru_r01(&ReportTemplate::from_markdown(markdown), &fields, now);
+        let segments: Vec<&str> = message.split_terminator(SEGMENT_END).collect();
+        assert_eq!(
+            segments,
+            [
+                r"MSH|^~\&|RTEMPLATES||||20240501083000||ORU^R01^ORU_R01|MSG1|P|2.5.1",
+                "PID|1||123||Silva^Maria|||",
+                "OBR|1||A1|CT-HEAD^TC DE CRÂNIO|||20240501083000|||||||||||||||20240501083000|||F",
+                r"OBX|1|FT|18785-6^Radiology Reason for study^LN||cefaleia \F\ febre.||||||F",
+                r"OBX|2|FT|55111-9^Current imaging procedure descriptions^LN||Cortes axiais.\.br\Sem contraste.||||||F",
+                "OBX|3|FT|19005-8^Radiology Impression^LN||Normal.||||||F",
+                "NTE|1|L|Laudo assinado eletronicamente.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! `rtemplates hook`, run by git before each commit: the staged Markdown
+//! templates are linted, and when none has lint errors their DOCX and TXT
+//! (or the formats asked for) are regenerated and staged along with them,
+//! so a commit never carries outputs out of step with their sources. With
+//! lint errors nothing is converted and the commit is refused.
+//!
+//! The working copy of each staged template is what is linted and
+//! converted. `rtemplates hook --install` writes the `pre-commit` script
+//! that runs it.
+
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::aliases;
+use crate::config;
+use crate::convert::{convert_one, Format};
+use crate::encoding::read_text;
+use crate::exports;
+use crate::files::output_path;
+use crate::fill::FillOptions;
+use crate::git;
+use crate::lint::{lint_content, FileProblems, Severity, WordLists};
+
+/// The `pre-commit` script `install` writes.
+const SCRIPT: &str = concat!(
+    "#!/bin/sh\n",
+    "# Lints the staged templates and stages their regenerated outputs.\n",
+    "exec rtemplates hook\n",
+);
+
+#[derive(Debug, Default)]
+pub struct HookRun {
+    /// The staged templates with lint problems, relative to the root.
+    pub problems: Vec<FileProblems>,
+    /// The outputs regenerated and staged, relative to the root; none when
+    /// a template has lint errors.
+    pub staged: Vec<PathBuf>,
+}
+
+impl HookRun {
+    pub fn errors(&self) -> usize {
+        self.problems
+            .iter()
+            .flat_map(|file| &file.problems)
+            .filter(|problem| problem.severity == Severity::Error)
+            .count()
+    }
+}
+
+/// Lints the Markdown templates staged in the repository at `root` and,
+/// without lint errors, converts them to `formats` and stages the outputs.
+pub fn run(root: &Path, formats: &[Format]) -> Result<HookRun> {
+    let markdown_dir = PathBuf::from(&config::get().directories.markdown);
+    let templates: Vec<PathBuf> = git::staged_files(&root.join(&markdown_dir))?
+        .into_iter()
+        .filter(|path| path.parent() == Some(Path::new("")))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .map(|path| markdown_dir.join(path))
+        .collect();
+
+    let mut run = HookRun::default();
+    let settings = &config::get().lint;
+    let lists = WordLists::load(settings)?;
+    for template in &templates {
+        let problems = lint_content(&read_text(&root.join(template))?, settings, &lists);
+        if !problems.is_empty() {
+            run.problems.push(FileProblems {
+                path: template.clone(),
+                problems,
+            });
+        }
+    }
+    if run.errors() > 0 {
+        return Ok(run);
+    }
+
+    let fill = FillOptions::default();
+    for template in &templates {
+        let source = root.join(template);
+        for &format in formats {
+            if !exports::is_exported(&source, format)? {
+                continue;
+            }
+            let dir = PathBuf::from(format.default_dir());
+            convert_one(Format::Md, format, &source, &root.join(&dir), &fill)?;
+            let target = output_path(&dir, template, format.extension());
+            let copies = aliases::targets(&source, &target)?;
+            run.staged.push(target);
+            run.staged.extend(copies);
+        }
+    }
+    git::stage(root, &run.staged)?;
+    Ok(run)
+}
+
+/// Writes the `pre-commit` hook of the repository at `root`, and returns
+/// its path. A hook of other contents already there is left alone.
+pub fn install(root: &Path) -> Result<PathBuf> {
+    let path = git::hooks_dir(root)?.join("pre-commit");
+    if path.exists() && fs::read_to_string(&path).ok().as_deref() != Some(SCRIPT) {
+        bail!(
+            "{} already exists; add `rtemplates hook` to it instead",
+            path.display()
+        );
+    }
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&path, SCRIPT)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn staged_templates_are_linted_converted_and_staged() {
+        let repo = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(repo.path())
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?}", args);
+            String::from_utf8(output.stdout).unwrap()
+        };
+        git(&["init", "-q"]);
+        let markdown = repo.path().join("Templates_markdown");
+        fs::create_dir_all(&markdown).unwrap();
+        let template = markdown.join("US ABDOME.md");
+        fs::write(&template, "**US ABDOME**\n\n**Impressão:**\n\n*Rodapé.*\n").unwrap();
+        fs::write(markdown.join("TC.md"), "**TC**\n").unwrap();
+        git(&["add", "Templates_markdown/US ABDOME.md"]);
+
+        let first = run(repo.path(), &[Format::Txt]).unwrap();
+        assert_eq!(first.errors(), 1);
+        assert!(first.staged.is_empty());
+        assert!(!repo.path().join("Templates_txt").exists());
+
+        fs::write(
+            &template,
+            "**US ABDOME**\n\n**Impressão:**\nNormal.\n\n*Rodapé.*\n",
+        )
+        .unwrap();
+        let second = run(repo.path(), &[Format::Txt]).unwrap();
+        assert_eq!(second.errors(), 0);
+        assert_eq!(second.staged, [Path::new("Templates_txt/US ABDOME.txt")]);
+        let staged = git(&["diff", "--cached", "--name-only", "-z"]);
+        assert!(staged.contains("Templates_txt/US ABDOME.txt"));
+        assert!(!repo.path().join("Templates_txt/TC.txt").exists());
+
+        let hook = install(repo.path()).unwrap();
+        assert_eq!(fs::read_to_string(&hook).unwrap(), SCRIPT);
+        assert!(install(repo.path()).is_ok());
+        fs::write(&hook, "#!/bin/sh\nmake check\n").unwrap();
+        assert!(install(repo.path()).is_err());
+    }
+}
@@ -0,0 +1,553 @@
+//! Minimal HTML reader for legacy RIS exports: a tokenizer for tags and text,
+//! plus an interpreter that tracks bold/italic/underline per element and
+//! yields formatted paragraphs and tables.
+
+use encoding_rs::{Encoding, WINDOWS_1252};
+
+use crate::convert_to_markdown::ImportedBlock;
+use crate::encoding::nfc;
+use crate::rtf::{RtfParagraph, Span};
+
+/// A lexical HTML element. Tag names are lowercased.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
+    StartTag {
+        name: String,
+        /// Raw attribute source, e.g. `class="x" style="font-weight:bold"`.
+        attrs: &'a str,
+    },
+    EndTag(String),
+    Text(&'a str),
+}
+
+/// Elements whose content is never document text.
+const SKIPPED_ELEMENTS: &[&str] = &["head", "script", "style", "title", "noscript"];
+
+/// Elements that never have an end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "br", "hr", "img", "meta", "link", "input", "col", "area", "base", "wbr",
+];
+
+/// Elements that start and end a paragraph.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "ul",
+    "ol",
+    "blockquote",
+    "center",
+    "pre",
+    "body",
+    "hr",
+];
+
+/// Splits HTML source into tokens. Comments, doctypes and processing
+/// instructions are dropped; `<script>`/`<style>` bodies become one text
+/// token so markup inside them is not interpreted.
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let bytes = input.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'<' {
+            let end = input[i..].find('<').map_or(input.len(), |n| i + n);
+            tokens.push(Token::Text(&input[i..end]));
+            i = end;
+            continue;
+        }
+
+        let rest = &input[i..];
+        if rest.starts_with("<!--") {
+            i += rest.find("-->").map_or(rest.len(), |n| n + 3);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            i += rest.find('>').map_or(rest.len(), |n| n + 1);
+            continue;
+        }
+
+        let closing = rest.starts_with("</");
+        let name_start = if closing { 2 } else { 1 };
+        let name_len = rest[name_start..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len() - name_start);
+        if name_len == 0 {
+            // A stray `<` is text.
+            tokens.push(Token::Text(&input[i..i + 1]));
+            i += 1;
+            continue;
+        }
+        let name = rest[name_start..name_start + name_len].to_ascii_lowercase();
+        let tag_end = rest.find('>').map_or(rest.len(), |n| n + 1);
+        let attrs = rest[name_start + name_len..tag_end]
+            .trim_end_matches('>')
+            .trim_end_matches('/')
+            .trim();
+        i += tag_end;
+
+        if closing {
+            tokens.push(Token::EndTag(name));
+            continue;
+        }
+
+        let raw_text = name == "script" || name == "style";
+        tokens.push(Token::StartTag {
+            name: name.clone(),
+            attrs,
+        });
+        if raw_text {
+            let close = format!("</{}", name);
+            let body_end = input[i..]
+                .to_ascii_lowercase()
+                .find(&close)
+                .map_or(input.len(), |n| i + n);
+            tokens.push(Token::Text(&input[i..body_end]));
+            i = body_end;
+        }
+    }
+
+    tokens
+}
+
+/// Named entities seen in Portuguese report templates, beyond the XML five.
+const NAMED_ENTITIES: &[(&str, char)] = &[
+    ("nbsp", '\u{a0}'),
+    ("aacute", 'á'),
+    ("eacute", 'é'),
+    ("iacute", 'í'),
+    ("oacute", 'ó'),
+    ("uacute", 'ú'),
+    ("Aacute", 'Á'),
+    ("Eacute", 'É'),
+    ("Iacute", 'Í'),
+    ("Oacute", 'Ó'),
+    ("Uacute", 'Ú'),
+    ("agrave", 'à'),
+    ("Agrave", 'À'),
+    ("acirc", 'â'),
+    ("ecirc", 'ê'),
+    ("ocirc", 'ô'),
+    ("Acirc", 'Â'),
+    ("Ecirc", 'Ê'),
+    ("Ocirc", 'Ô'),
+    ("atilde", 'ã'),
+    ("otilde", 'õ'),
+    ("Atilde", 'Ã'),
+    ("Otilde", 'Õ'),
+    ("ccedil", 'ç'),
+    ("Ccedil", 'Ç'),
+    ("uuml", 'ü'),
+    ("Uuml", 'Ü'),
+    ("ordm", 'º'),
+    ("ordf", 'ª'),
+    ("deg", '°'),
+    ("plusmn", '±'),
+    ("times", '×'),
+    ("middot", '·'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("hellip", '…'),
+];
+
+/// Decodes `&name;`, `&#N;` and `&#xH;` references. Unknown entities are
+/// kept verbatim.
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest[1..].find(';').map(|n| n + 1).filter(|&n| n <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                u32::from_str_radix(&entity[2..], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+            }
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+            _ => NAMED_ENTITIES
+                .iter()
+                .find(|(name, _)| *name == entity)
+                .map(|&(_, c)| c),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Decodes HTML bytes: UTF-8 when valid, otherwise the `charset` declared in
+/// a `<meta>` tag, falling back to Windows-1252 like the RTF reader. The
+/// text is in NFC (`encoding::nfc`).
+pub fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return nfc(text.to_string());
+    }
+
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(2048)]).to_ascii_lowercase();
+    let encoding = head
+        .find("charset=")
+        .map(|n| {
+            head[n + 8..]
+                .trim_start_matches(['"', '\''])
+                .split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                .next()
+                .unwrap_or("")
+        })
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(WINDOWS_1252);
+    nfc(encoding.decode(bytes).0.into_owned())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ElementState {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    skip: bool,
+}
+
+impl ElementState {
+    fn child(self, name: &str, attrs: &str) -> Self {
+        let mut state = self;
+        match name {
+            "b" | "strong" | "th" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => state.bold = true,
+            "i" | "em" | "cite" => state.italic = true,
+            "u" | "ins" => state.underline = true,
+            _ => {}
+        }
+        if SKIPPED_ELEMENTS.contains(&name) {
+            state.skip = true;
+        }
+        if let Some(style) = attribute(attrs, "style") {
+            state.apply_style(&style.to_ascii_lowercase());
+        }
+        state
+    }
+
+    /// Honours the inline CSS that word processors emit instead of `<b>`.
+    fn apply_style(&mut self, style: &str) {
+        for declaration in style.split(';') {
+            let Some((property, value)) = declaration.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match property.trim() {
+                "font-weight" => {
+                    self.bold = matches!(value, "bold" | "bolder" | "600" | "700" | "800" | "900")
+                }
+                "font-style" => self.italic = value == "italic" || value == "oblique",
+                "text-decoration" | "text-decoration-line" => {
+                    self.underline = value.contains("underline")
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Value of attribute `name` in raw attribute source, unquoted.
+pub fn attribute<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let lower = attrs.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(n) = lower[from..].find(name) {
+        let start = from + n;
+        from = start + name.len();
+        // `type` must not match inside `data-field-type`.
+        let boundary = start == 0 || lower.as_bytes()[start - 1].is_ascii_whitespace();
+        let rest = attrs[from..].trim_start();
+        if !boundary || !rest.starts_with('=') {
+            continue;
+        }
+        let value = rest[1..].trim_start();
+        return Some(match value.chars().next() {
+            Some(q @ ('"' | '\'')) => value[1..].split(q).next().unwrap_or(""),
+            _ => value.split_whitespace().next().unwrap_or(""),
+        });
+    }
+    None
+}
+
+/// Table being collected; only the outermost table keeps its structure.
+#[derive(Default)]
+struct TableState {
+    rows: Vec<Vec<String>>,
+    row: Option<Vec<String>>,
+    cell: Option<Vec<RtfParagraph>>,
+}
+
+struct Interpreter {
+    blocks: Vec<ImportedBlock>,
+    current: RtfParagraph,
+    /// Open elements with the formatting in effect inside each.
+    stack: Vec<(String, ElementState)>,
+    table: Option<TableState>,
+    table_depth: usize,
+    /// Whitespace seen since the last character, collapsed to one space.
+    pending_space: bool,
+    /// A non-breaking space was seen: `<p>&nbsp;</p>` is a deliberate blank line.
+    saw_nbsp: bool,
+}
+
+impl Interpreter {
+    fn new() -> Self {
+        Interpreter {
+            blocks: Vec::new(),
+            current: RtfParagraph::default(),
+            stack: Vec::new(),
+            table: None,
+            table_depth: 0,
+            pending_space: false,
+            saw_nbsp: false,
+        }
+    }
+
+    fn state(&self) -> ElementState {
+        self.stack.last().map(|(_, s)| *s).unwrap_or_default()
+    }
+
+    fn push_char(&mut self, c: char) {
+        let state = self.state();
+        match self.current.spans.last_mut() {
+            Some(span)
+                if span.bold == state.bold
+                    && span.italic == state.italic
+                    && span.underline == state.underline =>
+            {
+                span.text.push(c)
+            }
+            _ => self.current.spans.push(Span {
+                text: c.to_string(),
+                bold: state.bold,
+                italic: state.italic,
+                underline: state.underline,
+                link: None,
+                image: None,
+                note: None,
+            }),
+        }
+    }
+
+    fn text(&mut self, raw: &str) {
+        if self.state().skip {
+            return;
+        }
+        for c in decode_entities(raw).chars() {
+            if c.is_whitespace() {
+                self.pending_space = true;
+                self.saw_nbsp |= c == '\u{a0}';
+                continue;
+            }
+            if self.pending_space && !self.current.spans.is_empty() {
+                self.push_char(' ');
+            }
+            self.pending_space = false;
+            self.push_char(c);
+        }
+    }
+
+    /// Ends the current paragraph. `keep_empty` records blank lines (`<br>`).
+    fn end_paragraph(&mut self, keep_empty: bool) {
+        let keep_empty = keep_empty || std::mem::take(&mut self.saw_nbsp);
+        self.pending_space = false;
+        if self.current.spans.is_empty() && !keep_empty {
+            return;
+        }
+        let paragraph = std::mem::take(&mut self.current);
+        match self.table.as_mut() {
+            Some(table) => {
+                // Text between cells is not valid table content; drop it.
+                if let Some(cell) = table.cell.as_mut() {
+                    cell.push(paragraph);
+                }
+            }
+            None => self.blocks.push(ImportedBlock::Paragraph(paragraph)),
+        }
+    }
+
+    /// Pops unclosed elements back to (and including) the innermost of
+    /// `names` inside the current table, so `<td>` without `</td>` does not
+    /// leak its formatting into the next cell.
+    fn pop_within_table(&mut self, names: &[&str]) {
+        let table = self
+            .stack
+            .iter()
+            .rposition(|(open, _)| open == "table")
+            .unwrap_or(0);
+        if let Some(pos) = self.stack[table..]
+            .iter()
+            .rposition(|(open, _)| names.contains(&open.as_str()))
+        {
+            self.stack.truncate(table + pos);
+        }
+    }
+
+    fn close_cell(&mut self) {
+        self.end_paragraph(false);
+        self.pop_within_table(&["td", "th"]);
+        let Some(table) = self.table.as_mut() else {
+            return;
+        };
+        if let Some(cell) = table.cell.take() {
+            let text = cell
+                .iter()
+                .map(|p| p.to_markdown())
+                .filter(|t| !t.is_empty())
+                .collect::<Vec<_>>()
+                .join("<br>");
+            table.row.get_or_insert_with(Vec::new).push(text);
+        }
+    }
+
+    fn close_row(&mut self) {
+        self.close_cell();
+        self.pop_within_table(&["tr"]);
+        if let Some(table) = self.table.as_mut() {
+            if let Some(row) = table.row.take() {
+                table.rows.push(row);
+            }
+        }
+    }
+
+    fn start_tag(&mut self, name: &str, attrs: &str) {
+        match name {
+            "table" => {
+                self.table_depth += 1;
+                if self.table_depth == 1 {
+                    self.end_paragraph(false);
+                    self.table = Some(TableState::default());
+                }
+            }
+            "tr" if self.table_depth == 1 => self.close_row(),
+            "td" | "th" if self.table_depth == 1 => {
+                self.close_cell();
+                if let Some(table) = self.table.as_mut() {
+                    table.cell = Some(Vec::new());
+                }
+            }
+            "br" => self.end_paragraph(true),
+            _ if BLOCK_ELEMENTS.contains(&name) => self.end_paragraph(false),
+            _ => {}
+        }
+
+        if !VOID_ELEMENTS.contains(&name) {
+            let state = self.state().child(name, attrs);
+            self.stack.push((name.to_string(), state));
+        }
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        match name {
+            "table" if self.table_depth > 0 => {
+                self.table_depth -= 1;
+                if self.table_depth == 0 {
+                    self.close_row();
+                    if let Some(table) = self.table.take() {
+                        if !table.rows.is_empty() {
+                            self.blocks.push(ImportedBlock::Table(table.rows));
+                        }
+                    }
+                }
+            }
+            "tr" if self.table_depth == 1 => self.close_row(),
+            "td" | "th" if self.table_depth == 1 => self.close_cell(),
+            _ if BLOCK_ELEMENTS.contains(&name) => self.end_paragraph(false),
+            _ => {}
+        }
+
+        // Tolerate unclosed inline elements: pop back to the matching tag.
+        if let Some(pos) = self.stack.iter().rposition(|(open, _)| open == name) {
+            self.stack.truncate(pos);
+        }
+    }
+
+    fn run(mut self, tokens: &[Token]) -> Vec<ImportedBlock> {
+        for token in tokens {
+            match token {
+                Token::StartTag { name, attrs } => self.start_tag(name, attrs),
+                Token::EndTag(name) => self.end_tag(name),
+                Token::Text(text) => self.text(text),
+            }
+        }
+        while self.table_depth > 0 {
+            self.end_tag("table");
+        }
+        self.end_paragraph(false);
+        self.blocks
+    }
+}
+
+/// Parses HTML source into formatted paragraphs and tables.
+pub fn parse(input: &[u8]) -> Vec<ImportedBlock> {
+    let text = decode(input);
+    Interpreter::new().run(&tokenize(&text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_exports_yield_formatted_paragraphs_and_tables() {
+        let source = b"<html><head><meta charset=\"windows-1252\"><style>p{}</style></head><body>\
+            <p><span style=\"font-weight:bold\">T\xc9CNICA:</span> cortes &lt;5&nbsp;mm&#8203;</p>\
+            <div><i>sem</i> contraste<br>venoso</div>\
+            <table><tr><th>Les\xe3o</th><th>mm</th></tr><tr><td><p>a</p><p>b</p></td><td>12</td></tr></table>\
+            </body></html>";
+        assert_eq!(
+            decode_entities("&amp;&#xE9;&#233;&ccedil;&bogus; & x"),
+            "&ééç&bogus; & x"
+        );
+        assert_eq!(attribute(r#"data-type="x" type='y'"#, "type"), Some("y"));
+
+        let blocks = parse(source);
+        let markdown: Vec<String> = blocks
+            .iter()
+            .map(|block| match block {
+                ImportedBlock::Paragraph(paragraph) => paragraph.to_markdown(),
+                ImportedBlock::Table(rows) => format!("{rows:?}"),
+            })
+            .collect();
+        assert_eq!(
+            markdown,
+            [
+                "**TÉCNICA:** cortes <5 mm\u{200b}",
+                "*sem* contraste",
+                "venoso",
+                r#"[["**Lesão**", "**mm**"], ["a<br>b", "12"]]"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Images referenced from Markdown templates (`![](assets/....png)`).
+//!
+//! Imported images are stored in an `assets/` folder next to the Markdown,
+//! named after a hash of their contents, so a logo shared by many templates
+//! is kept once and reconverting a document does not rename its images.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use crate::ASSETS_DIR;
+
+/// Image formats DOCX output can embed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+}
+
+impl ImageFormat {
+    /// The format of an image file, from its first bytes.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if data.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Bmp => "bmp",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Bmp => "image/bmp",
+        }
+    }
+
+    /// Width and height in pixels, read from the file header.
+    pub fn pixel_size(self, data: &[u8]) -> Option<(u32, u32)> {
+        let be32 = |at: usize| Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?));
+        let le16 = |at: usize| Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+        let le32 = |at: usize| Some(i32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+        let size = match self {
+            ImageFormat::Png => (be32(16)?, be32(20)?),
+            ImageFormat::Gif => (le16(6)? as u32, le16(8)? as u32),
+            // Rows are stored bottom-up unless the height is negative.
+            ImageFormat::Bmp => (le32(18)?.unsigned_abs(), le32(22)?.unsigned_abs()),
+            ImageFormat::Jpeg => jpeg_size(data)?,
+        };
+        (size.0 > 0 && size.1 > 0).then_some(size)
+    }
+}
+
+/// The size in the first start-of-frame segment of a JPEG.
+fn jpeg_size(data: &[u8]) -> Option<(u32, u32)> {
+    let mut i = 2;
+    loop {
+        if *data.get(i)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(i + 1)?;
+        let length = u16::from_be_bytes([*data.get(i + 2)?, *data.get(i + 3)?]) as usize;
+        // SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC).
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let height = u16::from_be_bytes([*data.get(i + 5)?, *data.get(i + 6)?]);
+            let width = u16::from_be_bytes([*data.get(i + 7)?, *data.get(i + 8)?]);
+            return Some((width as u32, height as u32));
+        }
+        i += 2 + length;
+    }
+}
+
+/// An image a Markdown document refers to, still to be written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Asset {
+    /// Path relative to the Markdown file, e.g. `assets/3f2a9c1e5b7d0a64.png`.
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+impl Asset {
+    /// An asset named after its contents, keeping `extension`.
+    pub fn new(data: Vec<u8>, extension: &str) -> Self {
+        let hash = format!("{:x}", Sha256::digest(&data));
+        Asset {
+            path: format!(
+                "{}/{}.{}",
+                ASSETS_DIR,
+                &hash[..16],
+                extension.to_lowercase()
+            ),
+            data,
+        }
+    }
+}
+
+/// Writes `assets` under `markdown_dir`, leaving files already there alone.
+pub fn save_assets(markdown_dir: &Path, assets: &[Asset]) -> Result<()> {
+    for asset in assets {
+        let path = markdown_dir.join(&asset.path);
+        if path.exists() {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &asset.data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_from_file_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend([0, 0, 2, 0x58, 0, 0, 0, 0x96]);
+        let gif = b"GIF89a\x58\x02\x96\x00".to_vec();
+        let mut bmp = b"BM".to_vec();
+        bmp.resize(18, 0);
+        bmp.extend(600i32.to_le_bytes());
+        bmp.extend((-150i32).to_le_bytes());
+        // SOI, an APP0 segment, then SOF0 with height 150 and width 600.
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0];
+        jpeg.extend([0xFF, 0xC0, 0, 17, 8, 0, 0x96, 0x02, 0x58, 3]);
+        for data in [png, gif, bmp, jpeg] {
+            let format = ImageFormat::sniff(&data).unwrap();
+            assert_eq!(format.pixel_size(&data), Some((600, 150)), "{format:?}");
+        }
+        assert_eq!(ImageFormat::sniff(b"<svg/>"), None);
+    }
+
+    #[test]
+    fn assets_are_named_by_content() {
+        let logo = Asset::new(b"logo".to_vec(), "PNG");
+        assert_eq!(logo, Asset::new(b"logo".to_vec(), "png"));
+        assert_ne!(logo.path, Asset::new(b"outro".to_vec(), "png").path);
+        assert!(logo.path.starts_with("assets/") && logo.path.ends_with(".png"));
+        assert_eq!(logo.path.len(), "assets/".len() + 16 + ".png".len());
+    }
+}
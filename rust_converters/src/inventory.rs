@@ -0,0 +1,296 @@
+//! The template inventory (`rtemplates inventory`), a spreadsheet for
+//! auditing which exams and regions the templates cover.
+//!
+//! Each Markdown template is a row: file name, title, modality, body
+//! region, sections present, word count and last modification, read as
+//! `generate_index` reads them, and words counted as `metrics` counts them,
+//! partials filled in. The inventory is written as CSV
+//! or, for Excel, as an `.xlsx` workbook with a frozen, filterable header.
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::convert_to_html::escape_html;
+use crate::files::{list_files, FileFilter};
+use crate::fill::FillOptions;
+use crate::generate_index::file_entry;
+use crate::metrics::{metrics, Metrics};
+use crate::snippets::csv_field;
+use crate::template::{ReportTemplate, SectionKind};
+
+const HEADER: [&str; 7] = [
+    "file",
+    "title",
+    "modality",
+    "body_region",
+    "sections",
+    "words",
+    "modified",
+];
+
+/// One template of the inventory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub file: String,
+    pub title: Option<String>,
+    pub modality: Option<String>,
+    pub body_region: Option<String>,
+    /// Section names in order.
+    pub sections: Vec<String>,
+    /// The standard sections among them.
+    pub kinds: Vec<SectionKind>,
+    /// Modification time in UTC, `YYYY-MM-DDTHH:MM:SSZ`.
+    pub modified: String,
+    pub metrics: Metrics,
+}
+
+impl Row {
+    /// The text cells, in `HEADER` order; `words` is left out.
+    fn cells(&self) -> [String; 6] {
+        [
+            self.file.clone(),
+            self.title.clone().unwrap_or_default(),
+            self.modality.clone().unwrap_or_default(),
+            self.body_region.clone().unwrap_or_default(),
+            self.sections.join("; "),
+            self.modified.clone(),
+        ]
+    }
+}
+
+/// The rows of the Markdown templates in `dir` selected by `files`.
+pub fn inventory(dir: &Path, files: &FileFilter) -> Result<Vec<Row>> {
+    let fill = FillOptions::default();
+    let mut rows = Vec::new();
+    for path in list_files(dir, "md")? {
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        if !files.matches(relative) {
+            continue;
+        }
+        let entry = file_entry(&path)?;
+        let template = ReportTemplate::from_markdown(&fill.read(&path)?);
+        let metrics = metrics(&template);
+        let kinds = template
+            .sections
+            .iter()
+            .filter_map(|section| section.kind)
+            .collect();
+        rows.push(Row {
+            file: relative.to_string_lossy().replace('\\', "/"),
+            title: entry.title,
+            modality: entry.modality,
+            body_region: entry.body_region,
+            sections: entry.sections,
+            kinds,
+            modified: entry.modified,
+            metrics,
+        });
+    }
+    Ok(rows)
+}
+
+pub fn to_csv(rows: &[Row]) -> String {
+    let mut csv = format!("{}\n", HEADER.join(","));
+    for row in rows {
+        let [file, title, modality, region, sections, modified] = row.cells();
+        let fields = [
+            file,
+            title,
+            modality,
+            region,
+            sections,
+            row.metrics.words.to_string(),
+            modified,
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+const CONTENT_TYPES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+<Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+<Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+<Override PartName=\"/xl/workbook.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml\"/>\
+<Override PartName=\"/xl/worksheets/sheet1.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>\
+<Override PartName=\"/xl/styles.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml\"/>\
+</Types>\n";
+
+const ROOT_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" Target=\"xl/workbook.xml\"/>\
+</Relationships>\n";
+
+const WORKBOOK: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<workbook xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" \
+xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\">\
+<sheets><sheet name=\"Inventory\" sheetId=\"1\" r:id=\"rId1\"/></sheets>\
+</workbook>\n";
+
+const WORKBOOK_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" Target=\"worksheets/sheet1.xml\"/>\
+<Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles\" Target=\"styles.xml\"/>\
+</Relationships>\n";
+
+/// Style 1 is the bold header.
+const STYLES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<styleSheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+<fonts count=\"2\"><font><sz val=\"11\"/><name val=\"Calibri\"/></font>\
+<font><b/><sz val=\"11\"/><name val=\"Calibri\"/></font></fonts>\
+<fills count=\"2\"><fill><patternFill patternType=\"none\"/></fill>\
+<fill><patternFill patternType=\"gray125\"/></fill></fills>\
+<borders count=\"1\"><border><left/><right/><top/><bottom/><diagonal/></border></borders>\
+<cellStyleXfs count=\"1\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\"/></cellStyleXfs>\
+<cellXfs count=\"2\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\"/>\
+<xf numFmtId=\"0\" fontId=\"1\" fillId=\"0\" borderId=\"0\" xfId=\"0\" applyFont=\"1\"/></cellXfs>\
+</styleSheet>\n";
+
+/// Column widths, in characters.
+const WIDTHS: [u32; 7] = [40, 50, 10, 22, 60, 8, 22];
+
+fn column(index: usize) -> char {
+    (b'A' + index as u8) as char
+}
+
+fn text_cell(reference: &str, text: &str, style: u8) -> String {
+    format!(
+        "<c r=\"{}\" s=\"{}\" t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+        reference,
+        style,
+        escape_html(text)
+    )
+}
+
+fn sheet(rows: &[Row]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <worksheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+         <sheetViews><sheetView workbookViewId=\"0\">\
+         <pane ySplit=\"1\" topLeftCell=\"A2\" activePane=\"bottomLeft\" state=\"frozen\"/>\
+         </sheetView></sheetViews><cols>",
+    );
+    for (i, width) in WIDTHS.iter().enumerate() {
+        xml.push_str(&format!(
+            "<col min=\"{0}\" max=\"{0}\" width=\"{1}\" customWidth=\"1\"/>",
+            i + 1,
+            width
+        ));
+    }
+    xml.push_str("</cols><sheetData><row r=\"1\">");
+    for (i, name) in HEADER.iter().enumerate() {
+        xml.push_str(&text_cell(&format!("{}1", column(i)), name, 1));
+    }
+    xml.push_str("</row>");
+    for (n, row) in rows.iter().enumerate() {
+        let r = n + 2;
+        let [file, title, modality, region, sections, modified] = row.cells();
+        xml.push_str(&format!("<row r=\"{}\">", r));
+        for (i, text) in [file, title, modality, region, sections].iter().enumerate() {
+            xml.push_str(&text_cell(&format!("{}{}", column(i), r), text, 0));
+        }
+        xml.push_str(&format!("<c r=\"F{}\"><v>{}</v></c>", r, row.metrics.words));
+        xml.push_str(&text_cell(&format!("G{}", r), &modified, 0));
+        xml.push_str("</row>");
+    }
+    xml.push_str(&format!(
+        "</sheetData><autoFilter ref=\"A1:G{}\"/></worksheet>\n",
+        rows.len() + 1
+    ));
+    xml
+}
+
+/// The inventory as an Excel workbook.
+pub fn to_xlsx(rows: &[Row]) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    for (name, content) in [
+        ("[Content_Types].xml", CONTENT_TYPES.to_string()),
+        ("_rels/.rels", ROOT_RELS.to_string()),
+        ("xl/workbook.xml", WORKBOOK.to_string()),
+        ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS.to_string()),
+        ("xl/styles.xml", STYLES.to_string()),
+        ("xl/worksheets/sheet1.xml", sheet(rows)),
+    ] {
+        zip.start_file(name, options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Writes the inventory of the templates in `dir` to `output`, as CSV or
+/// XLSX by its extension, and returns how many templates it lists.
+pub fn write_inventory(dir: &Path, files: &FileFilter, output: &Path) -> Result<usize> {
+    let extension = output
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let rows = inventory(dir, files)?;
+    let bytes = match extension.as_str() {
+        "csv" => to_csv(&rows).into_bytes(),
+        "xlsx" => to_xlsx(&rows)?,
+        _ => bail!(
+            "Unknown inventory format {}; expected .csv or .xlsx",
+            output.display()
+        ),
+    };
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, bytes).with_context(|| format!("Cannot write {}", output.display()))?;
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn templates_are_listed_one_row_each() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("TC Crânio SEM.md"),
+            "---\nmodality: CT\n---\n**TOMOGRAFIA DO CRÂNIO**\n\n**Técnica:** Aquisição axial.\n\n\
+             **Impressão:** Sem alterações, \"normal\".\n",
+        )
+        .unwrap();
+
+        let rows = inventory(dir.path(), &FileFilter::default()).unwrap();
+        assert_eq!(rows.len(), 1);
+        let row = &rows[0];
+        assert_eq!(row.file, "TC Crânio SEM.md");
+        assert_eq!(row.title.as_deref(), Some("TOMOGRAFIA DO CRÂNIO"));
+        assert_eq!(row.modality.as_deref(), Some("CT"));
+        assert_eq!(row.body_region.as_deref(), Some("crânio"));
+        assert_eq!(row.metrics.words, 9);
+
+        let csv = to_csv(&rows);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("file,title,modality,body_region,sections,words,modified")
+        );
+        assert!(lines.next().unwrap().starts_with(&format!(
+            "TC Crânio SEM.md,TOMOGRAFIA DO CRÂNIO,CT,crânio,{},9,",
+            csv_field(&row.sections.join("; "))
+        )));
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(to_xlsx(&rows).unwrap())).unwrap();
+        let mut sheet = String::new();
+        archive
+            .by_name("xl/worksheets/sheet1.xml")
+            .unwrap()
+            .read_to_string(&mut sheet)
+            .unwrap();
+        assert!(sheet.contains("<c r=\"F2\"><v>9</v></c>"));
+        assert!(sheet.contains("<autoFilter ref=\"A1:G2\"/>"));
+    }
+}
@@ -0,0 +1,212 @@
+//! Reversible deletes. Commands that remove files move them into a trash
+//! folder instead and record each move in a journal, so `undo` can put the
+//! files of the last operation back.
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backup::snapshot_name;
+use crate::files::unique_dir;
+use crate::generate_index::write_json_pretty;
+use crate::timestamp::Timestamp;
+use crate::{JOURNAL_FILE, TRASH_DIR};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Where the file was, relative to the root.
+    pub path: PathBuf,
+    /// Where it is kept now, relative to the root.
+    pub trashed: PathBuf,
+}
+
+/// The files removed by one command run.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operation {
+    /// Command that removed the files, e.g. `clean`.
+    pub command: String,
+    pub time: String,
+    pub entries: Vec<JournalEntry>,
+}
+
+/// Operations under one root, oldest first.
+#[derive(Debug, Default)]
+pub struct Journal {
+    path: PathBuf,
+    pub operations: Vec<Operation>,
+}
+
+impl Journal {
+    /// Loads the journal of `root`; a missing file yields an empty journal.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(JOURNAL_FILE);
+        let operations = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Cannot read journal {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid journal {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        Ok(Journal { path, operations })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write_json_pretty(&self.operations, &self.path)
+    }
+}
+
+fn remove_empty_parents(path: &Path, stop: &Path) {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == stop || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+/// Moves `files` (relative to `root`) into a new folder of the trash and
+/// records them as one `command` operation. Nothing is recorded when
+/// `files` is empty.
+pub fn trash(root: &Path, files: &[PathBuf], command: &str) -> Result<Operation> {
+    let now = Timestamp::now();
+    let operation = Operation {
+        command: command.to_string(),
+        time: now.iso_datetime(),
+        entries: Vec::new(),
+    };
+    if files.is_empty() {
+        return Ok(operation);
+    }
+
+    let trash_dir = root.join(TRASH_DIR);
+    let folder = unique_dir(&trash_dir, &snapshot_name(now));
+    let mut journal = Journal::load(root)?;
+    journal.operations.push(operation);
+    for rel in files {
+        let trashed = folder.join(rel);
+        if let Some(parent) = trashed.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(root.join(rel), &trashed)
+            .with_context(|| format!("Cannot move {} to the trash", rel.display()))?;
+        let operation = journal
+            .operations
+            .last_mut()
+            .expect("operation just pushed");
+        operation.entries.push(JournalEntry {
+            path: rel.clone(),
+            trashed: trashed.strip_prefix(root).unwrap_or(&trashed).to_path_buf(),
+        });
+        // Saved after every move so an interrupted run can still be undone.
+        journal.save()?;
+    }
+
+    let operation = journal
+        .operations
+        .last()
+        .cloned()
+        .expect("operation just pushed");
+    Ok(operation)
+}
+
+/// Moves the files of the last journaled operation back. Files whose
+/// original place is taken again are left in the trash, and the operation
+/// stays in the journal with just those. Returns the operation undone and
+/// how many files came back, or `None` when the journal is empty.
+pub fn undo(root: &Path) -> Result<Option<(Operation, usize)>> {
+    let mut journal = Journal::load(root)?;
+    let Some(operation) = journal.operations.pop() else {
+        return Ok(None);
+    };
+
+    let trash_dir = root.join(TRASH_DIR);
+    let mut remaining = Vec::new();
+    let mut restored = 0usize;
+    for entry in &operation.entries {
+        let trashed = root.join(&entry.trashed);
+        let dest = root.join(&entry.path);
+        if !trashed.exists() {
+            warn!(
+                "Skip {}: no longer in the trash ({})",
+                entry.path.display(),
+                entry.trashed.display()
+            );
+            continue;
+        }
+        if dest.exists() {
+            warn!("Skip {}: file exists again", entry.path.display());
+            remaining.push(entry.clone());
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&trashed, &dest)?;
+        restored += 1;
+        info!("Restored {}", entry.path.display());
+        remove_empty_parents(&trashed, &trash_dir);
+    }
+
+    if !remaining.is_empty() {
+        let mut kept = operation.clone();
+        kept.entries = remaining;
+        journal.operations.push(kept);
+    }
+    journal.save()?;
+    Ok(Some((operation, restored)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_puts_back_the_files_of_the_last_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("docx")).unwrap();
+        for name in ["A.docx", "B.docx", "C.docx"] {
+            fs::write(root.join("docx").join(name), name).unwrap();
+        }
+
+        let empty = trash(root, &[], "clean").unwrap();
+        assert!(empty.entries.is_empty());
+        assert!(Journal::load(root).unwrap().operations.is_empty());
+
+        trash(root, &[PathBuf::from("docx/A.docx")], "clean").unwrap();
+        let last = trash(
+            root,
+            &[PathBuf::from("docx/B.docx"), PathBuf::from("docx/C.docx")],
+            "clean",
+        )
+        .unwrap();
+        assert_eq!(last.entries.len(), 2);
+        assert!(root.join(&last.entries[0].trashed).exists());
+        assert!(!root.join("docx/B.docx").exists());
+
+        // C.docx was written again, so it stays in the trash for now.
+        fs::write(root.join("docx/C.docx"), "new").unwrap();
+        let (undone, restored) = undo(root).unwrap().unwrap();
+        assert_eq!((undone, restored), (last.clone(), 1));
+        assert_eq!(
+            fs::read_to_string(root.join("docx/B.docx")).unwrap(),
+            "B.docx"
+        );
+        let journal = Journal::load(root).unwrap();
+        assert_eq!(journal.operations.len(), 2);
+        assert_eq!(journal.operations[1].entries, last.entries[1..]);
+
+        fs::remove_file(root.join("docx/C.docx")).unwrap();
+        assert_eq!(undo(root).unwrap().unwrap().1, 1);
+        assert_eq!(undo(root).unwrap().unwrap().1, 1);
+        assert_eq!(
+            fs::read_to_string(root.join("docx/A.docx")).unwrap(),
+            "A.docx"
+        );
+        assert_eq!(undo(root).unwrap(), None);
+    }
+}
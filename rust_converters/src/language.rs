@@ -0,0 +1,144 @@
+//! The language a template is written in, which picks the section heading
+//! keywords (`template::section_keywords`).
+//!
+//! A template's language is its front matter's `language:`, else the one
+//! its function words point to (`do`, `com`, `não` for Portuguese; `the`,
+//! `with` for English; `el`, `con`, `y` for Spanish), else the `language`
+//! setting, else Portuguese.
+
+use crate::config;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Language {
+    #[default]
+    Portuguese,
+    English,
+    Spanish,
+}
+
+/// Words frequent in one of the languages and rare in the others.
+const FUNCTION_WORDS: &[(Language, &[&str])] = &[
+    (
+        Language::Portuguese,
+        &[
+            "do", "da", "dos", "das", "não", "com", "sem", "em", "ao", "à", "são", "é", "uma",
+            "pelo", "pela", "nos", "nas",
+        ],
+    ),
+    (
+        Language::English,
+        &[
+            "the", "of", "and", "with", "without", "is", "are", "in", "on", "to", "an", "at", "by",
+            "for", "from", "not",
+        ],
+    ),
+    (
+        Language::Spanish,
+        &[
+            "el", "los", "las", "del", "con", "sin", "en", "y", "es", "son", "al", "lo",
+        ],
+    ),
+];
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::Portuguese, Language::English, Language::Spanish];
+
+    /// The ISO 639-1 code (`pt`, `en`, `es`).
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::Portuguese => "pt",
+            Language::English => "en",
+            Language::Spanish => "es",
+        }
+    }
+
+    /// The language of a code or tag, by its primary subtag (`en-US`,
+    /// `pt_BR`).
+    pub fn from_code(code: &str) -> Option<Self> {
+        let primary = code
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        Language::ALL
+            .into_iter()
+            .find(|language| language.code() == primary)
+    }
+}
+
+/// The language most of the function words of `text` belong to; None when
+/// there are none or two languages tie.
+pub fn detect(text: &str) -> Option<Language> {
+    let lower = text.to_lowercase();
+    let mut counts = [0usize; 3];
+    for word in lower.split(|c: char| !c.is_alphabetic()) {
+        for (i, (_, words)) in FUNCTION_WORDS.iter().enumerate() {
+            if words.contains(&word) {
+                counts[i] += 1;
+            }
+        }
+    }
+    let best = (0..counts.len()).max_by_key(|&i| counts[i])?;
+    let unique = counts
+        .iter()
+        .filter(|&&count| count == counts[best])
+        .count()
+        == 1;
+    (counts[best] > 0 && unique).then_some(FUNCTION_WORDS[best].0)
+}
+
+/// The language of a template with front matter `language:` `declared`
+/// and body `body`.
+pub fn template_language(declared: Option<&str>, body: &str) -> Language {
+    declared
+        .and_then(Language::from_code)
+        .or_else(|| detect(body))
+        .or_else(|| Language::from_code(&config::get().language))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::SectionKind;
+
+    #[test]
+    fn languages_are_detected_from_function_words() {
+        assert_eq!(
+            detect("Fígado de dimensões normais, sem lesões focais. Não há ascite."),
+            Some(Language::Portuguese)
+        );
+        assert_eq!(
+            detect("The liver is normal in size, without focal lesions."),
+            Some(Language::English)
+        );
+        assert_eq!(
+            detect("Hígado de tamaño normal, sin lesiones focales y con bordes lisos."),
+            Some(Language::Spanish)
+        );
+        assert_eq!(detect("TC ABDOME"), None);
+        assert_eq!(
+            template_language(Some("en-US"), "Fígado sem lesões."),
+            Language::English
+        );
+
+        let template = crate::template::ReportTemplate::from_markdown(
+            "CT OF THE CHEST\n\n**Technique:** Axial images with contrast.\n\n**Findings:**\n\n\
+             The lungs are clear.\n\n**Impression:** No acute findings.\n\n*Footer.*\n",
+        );
+        assert_eq!(template.language, Language::English);
+        let kinds: Vec<_> = template.sections.iter().filter_map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                SectionKind::Tecnica,
+                SectionKind::AspectosObservados,
+                SectionKind::Impressao
+            ]
+        );
+        assert_eq!(
+            template.sections.last().unwrap().inline_text().as_deref(),
+            Some("No acute findings.")
+        );
+    }
+}
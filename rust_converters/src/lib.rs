@@ -0,0 +1,135 @@
+//! Shared conversion logic for the report template tools.
+//!
+//! Every binary in `src/bin` is a thin wrapper around the modules below, so
+//! the standalone converters and the unified `rtemplates` CLI behave the same.
+
+pub mod aliases;
+pub mod anonymize;
+pub mod backup;
+pub mod browse;
+pub mod cache;
+pub mod calculations;
+pub mod canonical;
+pub mod catalog;
+pub mod classify;
+pub mod clean;
+pub mod clipboard;
+pub mod comments;
+pub mod config;
+pub mod convert;
+pub mod convert_to_docx;
+pub mod convert_to_html;
+pub mod convert_to_json;
+pub mod convert_to_latex;
+pub mod convert_to_markdown;
+pub mod convert_to_mrrt;
+pub mod convert_to_odt;
+pub mod convert_to_pdf;
+pub mod convert_to_rtf;
+pub mod convert_to_sr;
+pub mod convert_to_txt;
+pub mod convert_txt_to_markdown;
+pub mod dedupe;
+pub mod dicom;
+pub mod diff;
+pub mod docx_diff;
+pub mod docx_reference;
+pub mod docx_styles;
+pub mod encoding;
+pub mod epub;
+pub mod exports;
+pub mod files;
+pub mod fill;
+pub mod footnotes;
+pub mod front_matter;
+pub mod generate_index;
+pub mod git;
+pub mod history;
+pub mod hl7;
+pub mod hook;
+pub mod html;
+pub mod images;
+pub mod inventory;
+pub mod journal;
+pub mod language;
+pub mod lint;
+pub mod logging;
+pub mod markdown;
+pub mod measurements;
+pub mod metrics;
+pub mod mrrt;
+pub mod odt;
+pub mod pack;
+pub mod phrases;
+pub mod pick;
+pub mod placeholders;
+pub mod powerscribe;
+pub mod query;
+pub mod revisions;
+pub mod rtf;
+pub mod scaffold;
+pub mod search;
+pub mod sections;
+pub mod serve;
+pub mod signature;
+pub mod site;
+pub mod snippets;
+pub mod spelling;
+pub mod stats;
+pub mod sync;
+pub mod template;
+#[cfg(unix)]
+mod terminal;
+pub mod terminology;
+pub mod text_boxes;
+pub mod timestamp;
+pub mod translate;
+pub mod typography;
+pub mod verify;
+pub mod watch;
+
+pub const DOCX_DIR: &str = "Templates_docx";
+pub const MARKDOWN_DIR: &str = "Templates_markdown";
+pub const TXT_DIR: &str = "Templates_txt";
+pub const PDF_DIR: &str = "Templates_pdf";
+pub const HTML_DIR: &str = "Templates_html";
+pub const ODT_DIR: &str = "Templates_odt";
+pub const RTF_DIR: &str = "Templates_rtf";
+pub const LATEX_DIR: &str = "Templates_latex";
+pub const JSON_DIR: &str = "Templates_json";
+pub const MRRT_DIR: &str = "Templates_mrrt";
+pub const SR_DIR: &str = "Templates_sr";
+pub const INDEX_FILE: &str = "reports_index.json";
+/// Template packs imported from other libraries, a folder per namespace.
+pub const EXTERNAL_DIR: &str = "external";
+pub const BACKUP_DIR: &str = "backup";
+/// Images of the Markdown templates, inside the Markdown folder.
+pub const ASSETS_DIR: &str = "assets";
+/// Shared blocks included with `{{> name}}`.
+pub const PARTIALS_DIR: &str = "partials";
+/// Normal-finding sentences included with `{{phrase id}}`.
+pub const PHRASES_DIR: &str = "phrases";
+/// Ending of the Word comments kept next to an imported template; see
+/// `comments`.
+pub const COMMENTS_SUFFIX: &str = ".comments.md";
+/// Content-hash cache used by incremental conversion.
+pub const CACHE_FILE: &str = ".rtemplates_cache.json";
+/// Record of the files moved to the trash, for `undo`.
+pub const JOURNAL_FILE: &str = ".rtemplates_journal.json";
+/// Where removed files are kept until the operation is undone.
+pub const TRASH_DIR: &str = ".rtemplates_trash";
+/// Recorded versions of the templates; see `history`.
+pub const HISTORY_DIR: &str = ".rtemplates_history";
+
+/// Optional settings file; see `config`.
+pub const CONFIG_FILE: &str = "rtemplates.toml";
+
+/// Folders tracked by the index, paired with the extension they hold.
+pub fn targets() -> [(&'static str, &'static str); 3] {
+    let dirs = &config::get().directories;
+    [
+        (dirs.docx.as_str(), "docx"),
+        (dirs.markdown.as_str(), "md"),
+        (dirs.txt.as_str(), "txt"),
+    ]
+}
@@ -0,0 +1,582 @@
+//! Style and structure checks for Markdown templates (`rtemplates lint`).
+//!
+//! Each rule reports problems at a line of the source, counting the front
+//! matter, as a warning or an error. Severities and the rule parameters
+//! come from the `[lint]` table of `rtemplates.toml` (see
+//! `config::LintSettings`); a rule set to `off` is not run. The `spelling`
+//! rule checks the words against a Hunspell dictionary (see `spelling`),
+//! `terminology` against a lexicon of preferred terms (see `terminology`)
+//! and `measurement` the way measurements are written, which `fix_folder`
+//! can also correct.
+
+use anyhow::{Context, Result};
+use log::warn;
+use regex::Regex;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::{self, LintSettings};
+use crate::encoding::read_text;
+use crate::files::{list_files, FileFilter};
+use crate::fill;
+use crate::front_matter;
+use crate::markdown::normalize_heading;
+use crate::measurements;
+use crate::spelling::{self, Dictionary};
+use crate::template::{plain, Content, ReportTemplate, SectionKind};
+use crate::terminology::Lexicon;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Off,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(Severity::Off),
+            "warning" => Some(Severity::Warning),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Off => "off",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// A section of `required_sections` is missing.
+    MissingSection,
+    /// Sections out of the standard order (indication, technique, findings,
+    /// impression).
+    SectionOrder,
+    /// The impression section has no text.
+    EmptyImpression,
+    DoubleSpaces,
+    Tabs,
+    /// A line longer than `max_line_length` characters.
+    LineLength,
+    /// Section headings in different case styles (`IMPRESSÃO` after
+    /// `Técnica do exame`).
+    HeadingCase,
+    /// A `{{...}}` that filling leaves as it is, such as `{{NOME}}`.
+    Placeholder,
+    /// A word neither the dictionary nor the allowlist knows.
+    Spelling,
+    /// A measurement written against the department style, or with an
+    /// unlikely value (see `measurements`).
+    Measurement,
+    /// The body names the side opposite to the title's (`esquerdo` in
+    /// `RM DO JOELHO DIREITO`).
+    Laterality,
+    /// A synonym written where the lexicon has a preferred term.
+    Terminology,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 12] = [
+        Rule::MissingSection,
+        Rule::SectionOrder,
+        Rule::EmptyImpression,
+        Rule::DoubleSpaces,
+        Rule::Tabs,
+        Rule::LineLength,
+        Rule::HeadingCase,
+        Rule::Placeholder,
+        Rule::Spelling,
+        Rule::Measurement,
+        Rule::Laterality,
+        Rule::Terminology,
+    ];
+
+    /// The rule's key in `[lint]` and in reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::MissingSection => "missing_section",
+            Rule::SectionOrder => "section_order",
+            Rule::EmptyImpression => "empty_impression",
+            Rule::DoubleSpaces => "double_spaces",
+            Rule::Tabs => "tabs",
+            Rule::LineLength => "line_length",
+            Rule::HeadingCase => "heading_case",
+            Rule::Placeholder => "placeholder",
+            Rule::Spelling => "spelling",
+            Rule::Measurement => "measurement",
+            Rule::Laterality => "laterality",
+            Rule::Terminology => "terminology",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Rule::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+
+    /// The severity used unless `[lint]` sets another.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Rule::EmptyImpression | Rule::Placeholder => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    /// 1-based line number in the file.
+    pub line: usize,
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileProblems {
+    pub path: PathBuf,
+    pub problems: Vec<Problem>,
+}
+
+fn placeholder_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\{\{[^}]*\}\}").unwrap())
+}
+
+/// Section headings with the index of the body line each is written on.
+fn heading_lines<'a>(
+    lines: &[&str],
+    template: &'a ReportTemplate,
+) -> Vec<(usize, &'a crate::template::Section)> {
+    let mut found = Vec::new();
+    let mut cursor = 0;
+    for section in &template.sections {
+        let Some(heading) = &section.heading else {
+            continue;
+        };
+        if let Some(index) =
+            (cursor..lines.len()).find(|&i| normalize_heading(lines[i]).0 == heading.text)
+        {
+            found.push((index, section));
+            cursor = index + 1;
+        }
+    }
+    found
+}
+
+fn side_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)\b(direit|esquerd)[oa]s?\b").unwrap())
+}
+
+/// The side words of `text`, each with whether it names the right side.
+fn sides(text: &str) -> impl Iterator<Item = (bool, &str)> {
+    side_regex().captures_iter(text).map(|captures| {
+        (
+            captures[1].eq_ignore_ascii_case("direit"),
+            captures.get(0).unwrap().as_str(),
+        )
+    })
+}
+
+/// Whether the letters of `heading` before its colon are all capitals.
+fn is_upper_case(heading: &str) -> bool {
+    let name = heading.split(':').next().unwrap_or_default();
+    name.chars().any(char::is_alphabetic) && !name.chars().any(char::is_lowercase)
+}
+
+/// The word lists some rules look words up in, read once per run.
+#[derive(Debug, Default)]
+pub struct WordLists {
+    /// Of the `spelling` rule, with the allowlist added.
+    pub dictionary: Option<Dictionary>,
+    /// Of the `terminology` rule.
+    pub lexicon: Option<Lexicon>,
+}
+
+impl WordLists {
+    /// Reads the lists `settings` name for the rules that are on. The
+    /// dictionary is the pt-BR one of the system when none is set, and left
+    /// out with a warning when there is none.
+    pub fn load(settings: &LintSettings) -> Result<WordLists> {
+        let mut lists = WordLists::default();
+        if settings.severity(Rule::Spelling) != Severity::Off {
+            let path = settings
+                .dictionary
+                .as_ref()
+                .map(PathBuf::from)
+                .or_else(spelling::find_dictionary);
+            match path {
+                Some(path) => {
+                    let mut dictionary = Dictionary::open(&path)?;
+                    if let Some(allowlist) = &settings.allowlist {
+                        dictionary.allow_file(Path::new(allowlist))?;
+                    }
+                    lists.dictionary = Some(dictionary);
+                }
+                None => warn!(
+                    "No pt_BR Hunspell dictionary found; set `dictionary` under [lint] to check spelling"
+                ),
+            }
+        }
+        if settings.severity(Rule::Terminology) != Severity::Off {
+            if let Some(path) = &settings.lexicon {
+                lists.lexicon = Some(Lexicon::open(Path::new(path))?);
+            }
+        }
+        Ok(lists)
+    }
+}
+
+/// Checks one Markdown template against `settings`, looking words up in
+/// `lists`.
+pub fn lint_content(content: &str, settings: &LintSettings, lists: &WordLists) -> Vec<Problem> {
+    let body = front_matter::strip(content);
+    let offset = content[..content.len() - body.len()].lines().count();
+    let lines: Vec<&str> = body.lines().collect();
+    let template = ReportTemplate::from_markdown(content);
+    let headings = heading_lines(&lines, &template);
+
+    let mut problems = Vec::new();
+    let mut report = |index: usize, rule: Rule, message: String| {
+        let severity = settings.severity(rule);
+        if severity != Severity::Off {
+            problems.push(Problem {
+                line: offset + index + 1,
+                rule,
+                severity,
+                message,
+            });
+        }
+    };
+
+    for &kind in &settings.required_sections {
+        if template.section(kind).is_none() {
+            report(
+                0,
+                Rule::MissingSection,
+                format!("no {} section", kind.label()),
+            );
+        }
+    }
+
+    // `SectionKind` lists the sections in the standard order.
+    let mut latest: Option<SectionKind> = None;
+    for (index, section) in &headings {
+        let Some(kind) = section.kind else {
+            continue;
+        };
+        match latest {
+            Some(before) if before as usize > kind as usize => report(
+                *index,
+                Rule::SectionOrder,
+                format!("{} comes after {}", kind.label(), before.label()),
+            ),
+            _ => latest = Some(kind),
+        }
+    }
+
+    if let Some(section) = template.section(SectionKind::Impressao) {
+        let empty = section.inline_text().is_none()
+            && section.content.iter().all(|content| match content {
+                Content::Paragraph(paragraph) => paragraph.plain().trim().is_empty(),
+                Content::Table { .. } => false,
+            });
+        if empty {
+            let index = headings
+                .iter()
+                .find(|(_, s)| s.kind == Some(SectionKind::Impressao))
+                .map_or(0, |(index, _)| *index);
+            report(
+                index,
+                Rule::EmptyImpression,
+                "the impression is empty".to_string(),
+            );
+        }
+    }
+
+    let mut first_case: Option<(bool, String)> = None;
+    for (index, section) in &headings {
+        let text = plain(&section.heading.as_ref().unwrap().text);
+        let upper = is_upper_case(&text);
+        let name = text
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        match &first_case {
+            None => first_case = Some((upper, name)),
+            Some((first_upper, first_name)) if *first_upper != upper => report(
+                *index,
+                Rule::HeadingCase,
+                format!(
+                    "heading `{}` is {} while `{}` is {}",
+                    name,
+                    if upper {
+                        "in capitals"
+                    } else {
+                        "not in capitals"
+                    },
+                    first_name,
+                    if *first_upper {
+                        "in capitals"
+                    } else {
+                        "not in capitals"
+                    },
+                ),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    // A title naming one side only; `bilateral` or both sides say nothing.
+    let title = template.title.as_ref().map(|title| title.plain());
+    let title_side = title.as_deref().and_then(|title| {
+        let mut found = sides(title).map(|(right, _)| right);
+        let first = found.next()?;
+        found.all(|right| right == first).then_some(first)
+    });
+    let title_line = title
+        .as_deref()
+        .and_then(|title| lines.iter().position(|line| plain(line.trim()) == title));
+
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(right) = title_side.filter(|_| Some(index) != title_line) {
+            for (_, word) in sides(line).filter(|(side, _)| *side != right) {
+                report(
+                    index,
+                    Rule::Laterality,
+                    format!(
+                        "`{}` in a template for the {} side",
+                        word,
+                        if right { "right" } else { "left" }
+                    ),
+                );
+            }
+        }
+        let is_table = line.trim_start().starts_with('|');
+        // Two trailing spaces are a Markdown line break and table cells
+        // are padded, so only the inside of other lines counts.
+        if !is_table && line.trim().contains("  ") {
+            report(index, Rule::DoubleSpaces, "double space".to_string());
+        }
+        if line.contains('\t') {
+            report(index, Rule::Tabs, "tab character".to_string());
+        }
+        let length = line.chars().count();
+        if length > settings.max_line_length {
+            report(
+                index,
+                Rule::LineLength,
+                format!(
+                    "line is {} characters long (more than {})",
+                    length, settings.max_line_length
+                ),
+            );
+        }
+        for placeholder in placeholder_regex().find_iter(line) {
+            if !fill::is_directive(placeholder.as_str()) {
+                report(
+                    index,
+                    Rule::Placeholder,
+                    format!("leftover placeholder {}", placeholder.as_str()),
+                );
+            }
+        }
+        for issue in measurements::check_line(line, settings) {
+            report(index, Rule::Measurement, issue.message);
+        }
+        for term in lists
+            .lexicon
+            .iter()
+            .flat_map(|lexicon| lexicon.check_line(line))
+        {
+            report(
+                index,
+                Rule::Terminology,
+                format!(
+                    "`{}`: the preferred term is `{}`",
+                    term.written, term.preferred
+                ),
+            );
+        }
+        let Some(dictionary) = &lists.dictionary else {
+            continue;
+        };
+        for word in dictionary.misspellings(line) {
+            let suggestions = dictionary.suggest(&word);
+            let message = if suggestions.is_empty() {
+                format!("unknown word `{}`", word)
+            } else {
+                format!(
+                    "unknown word `{}` (did you mean {}?)",
+                    word,
+                    suggestions.join(", ")
+                )
+            };
+            report(index, Rule::Spelling, message);
+        }
+    }
+
+    problems.sort_by_key(|problem| problem.line);
+    problems
+}
+
+/// Lints the Markdown templates in `dir` selected by `files`, with the
+/// configured settings; templates without problems are left out.
+pub fn lint_folder(dir: &Path, files: &FileFilter) -> Result<Vec<FileProblems>> {
+    let settings = &config::get().lint;
+    let lists = WordLists::load(settings)?;
+    let mut results = Vec::new();
+    for path in list_files(dir, "md")? {
+        if !files.matches(path.strip_prefix(dir).unwrap_or(&path)) {
+            continue;
+        }
+        let content = read_text(&path)?;
+        let problems = lint_content(&content, settings, &lists);
+        if !problems.is_empty() {
+            results.push(FileProblems { path, problems });
+        }
+    }
+    Ok(results)
+}
+
+/// Rewrites the measurements of the Markdown templates in `dir` selected
+/// by `files` in the configured style, keeping any front matter. Returns
+/// the templates changed, each with the number of measurements fixed.
+pub fn fix_folder(dir: &Path, files: &FileFilter) -> Result<Vec<(PathBuf, usize)>> {
+    let settings = &config::get().lint;
+    let mut fixed = Vec::new();
+    if settings.severity(Rule::Measurement) == Severity::Off {
+        return Ok(fixed);
+    }
+    for path in list_files(dir, "md")? {
+        if !files.matches(path.strip_prefix(dir).unwrap_or(&path)) {
+            continue;
+        }
+        let content = read_text(&path)?;
+        let body = front_matter::strip(&content);
+        let front = &content[..content.len() - body.len()];
+        let (body, count) = measurements::fix_text(body, settings);
+        if count > 0 {
+            fs::write(&path, format!("{}{}", front, body))
+                .with_context(|| format!("Cannot write {}", path.display()))?;
+            fixed.push((path, count));
+        }
+    }
+    Ok(fixed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(content: &str, settings: &LintSettings) -> Vec<(usize, Rule, Severity)> {
+        lint_content(content, settings, &WordLists::default())
+            .into_iter()
+            .map(|problem| (problem.line, problem.rule, problem.severity))
+            .collect()
+    }
+
+    #[test]
+    fn rules_report_lines_and_severities() {
+        let content = "---\nmodality: CT\n---\nTC DE TÓRAX\n\n**IMPRESSÃO:**\n\n\
+                       **Técnica do exame:** Cortes  axiais.\n\tNormal {{Lado}}, {{#if contraste}}com{{/if}} {{today}}.\n\n\
+                       *Rodapé.*\n";
+        let mut settings = LintSettings::default();
+        assert_eq!(
+            rules(content, &settings),
+            [
+                (6, Rule::EmptyImpression, Severity::Error),
+                (8, Rule::SectionOrder, Severity::Warning),
+                (8, Rule::HeadingCase, Severity::Warning),
+                (8, Rule::DoubleSpaces, Severity::Warning),
+                (9, Rule::Tabs, Severity::Warning),
+                (9, Rule::Placeholder, Severity::Error),
+            ]
+        );
+
+        let mirrored = "RM DO JOELHO ESQUERDO\n\n**Análise:**\n\nMenisco medial íntegro; joelho direito sem\n\
+                        derrame. Comparar com o lado esquerdo.\n\n**Impressão:**\n\nSem alterações.\n\n*Rodapé.*\n";
+        assert_eq!(
+            rules(mirrored, &settings),
+            [(5, Rule::Laterality, Severity::Warning)]
+        );
+
+        settings.levels.push((Rule::Tabs, Severity::Off));
+        settings.levels.push((Rule::DoubleSpaces, Severity::Error));
+        settings
+            .required_sections
+            .push(SectionKind::IndicacaoClinica);
+        settings.max_line_length = 30;
+        assert_eq!(
+            rules(content, &settings),
+            [
+                (4, Rule::MissingSection, Severity::Warning),
+                (6, Rule::EmptyImpression, Severity::Error),
+                (8, Rule::SectionOrder, Severity::Warning),
+                (8, Rule::HeadingCase, Severity::Warning),
+                (8, Rule::DoubleSpaces, Severity::Error),
+                (8, Rule::LineLength, Severity::Warning),
+                (9, Rule::LineLength, Severity::Warning),
+                (9, Rule::Placeholder, Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn sides_are_checked_against_a_one_sided_title() {
+        let template = |title: &str, body: &str| {
+            format!(
+                "{}\n\n**Análise:**\n\n{}\n\n**Impressão:**\n\nSem alterações.\n\n*Rodapé.*\n",
+                title, body
+            )
+        };
+        let laterality = |content: &str| -> Vec<(usize, String)> {
+            lint_content(content, &LintSettings::default(), &WordLists::default())
+                .into_iter()
+                .filter(|problem| problem.rule == Rule::Laterality)
+                .map(|problem| (problem.line, problem.message))
+                .collect()
+        };
+
+        assert_eq!(
+            laterality(&template(
+                "RM DO OMBRO DIREITO",
+                "Úmero direito normal.\nClavícula ESQUERDA e costelas esquerdas sem fraturas."
+            )),
+            [
+                (6, "`ESQUERDA` in a template for the right side".to_string()),
+                (
+                    6,
+                    "`esquerdas` in a template for the right side".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            laterality(&template(
+                "US DOS RINS",
+                "Rim esquerdo e rim direito normais."
+            )),
+            []
+        );
+        assert_eq!(
+            laterality(&template(
+                "RX DOS JOELHOS DIREITO E ESQUERDO",
+                "Joelho esquerdo normal."
+            )),
+            []
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Progress and warning messages of the library, through the `log` facade.
+//!
+//! Modules report what they do with `log::info!` (files converted, moved,
+//! restored) and `log::warn!` (files skipped, unreadable input) instead of
+//! printing. The logger installed by `init` writes info and lower levels to
+//! standard output and warnings and errors to standard error, as the plain
+//! message, so the tools print exactly what they always have. Binaries
+//! choose the level; `to_stderr` moves everything to standard error when
+//! standard output carries a machine-readable report.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Environment variable read by `level_from_env`, e.g. `RTEMPLATES_LOG=warn`.
+pub const LOG_ENV: &str = "RTEMPLATES_LOG";
+
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.level() <= Level::Warn {
+            CAPTURED.with(|captured| {
+                if let Some(warnings) = captured.borrow_mut().as_mut() {
+                    warnings.push(record.args().to_string());
+                }
+            });
+        }
+        if record.level() <= Level::Warn || TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!("{}", record.args());
+        } else {
+            let _ = writeln!(std::io::stdout().lock(), "{}", record.args());
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+static LOGGER: Logger = Logger;
+
+/// Installs the logger at `level`. Later calls only change the level.
+pub fn init(level: LevelFilter) {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
+
+/// The level named by `RTEMPLATES_LOG`, or `Info` when it is unset or not a
+/// level name.
+pub fn level_from_env() -> LevelFilter {
+    std::env::var(LOG_ENV)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(LevelFilter::Info)
+}
+
+/// Sends every message, info included, to standard error.
+pub fn to_stderr(enabled: bool) {
+    TO_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+/// Runs `f`, returning its result with the warnings and errors logged on
+/// this thread meanwhile (they are still printed).
+pub fn capture_warnings<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+    let outer = CAPTURED.with(|captured| captured.borrow_mut().replace(Vec::new()));
+    let result = f();
+    let warnings = CAPTURED.with(|captured| {
+        let mut captured = captured.borrow_mut();
+        let warnings = captured.take().unwrap_or_default();
+        *captured = outer;
+        warnings
+    });
+    (result, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::FileStatus;
+
+    #[test]
+    fn warnings_are_captured_per_run_and_reported_as_json() {
+        init(LevelFilter::Warn);
+        let (value, warnings) = capture_warnings(|| {
+            log::info!("✓ TC.md -> TC.docx");
+            log::warn!("Skip TC.txt: destination already exists");
+            let ((), inner) = capture_warnings(|| log::error!("✗ RM.docx: invalid zip"));
+            assert_eq!(inner, ["✗ RM.docx: invalid zip"]);
+            42
+        });
+        assert_eq!(value, 42);
+        // Nested runs keep their warnings to themselves; info is not a warning.
+        assert_eq!(warnings, ["Skip TC.txt: destination already exists"]);
+        assert!(capture_warnings(|| ()).1.is_empty());
+
+        assert_eq!(
+            serde_json::to_string(&[
+                FileStatus::Converted,
+                FileStatus::Unchanged,
+                FileStatus::Failed
+            ])
+            .unwrap(),
+            r#"["converted","unchanged","failed"]"#
+        );
+    }
+}
@@ -0,0 +1,1457 @@
+//! Markdown template layout shared by the document writers (DOCX, PDF).
+//!
+//! Templates follow fixed conventions: the first non-empty line is a centered
+//! title, the last non-empty line is a centered italic 8pt footer, and every
+//! other line is a justified body paragraph. Front matter can say which of
+//! the two lines are really a title and a footer (see `TitleFooter`). Lines
+//! holding only images above the title (a letterhead logo) are centered too.
+//! Pipe tables become table blocks.
+
+use crate::config::{self, UnderlineMarkup};
+use crate::rtf::Span;
+
+/// Built-in body size; `config::Config::font_size_pt` is the one in use.
+pub const BODY_FONT_SIZE_PT: i32 = 10;
+pub const FOOTER_FONT_SIZE_PT: i32 = 8;
+/// A line of its own starting a new page, as between the protocols of a
+/// multi-protocol template; written as in LaTeX.
+pub const PAGE_BREAK: &str = "\\pagebreak";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    Justify,
+    Center,
+    Left,
+}
+
+/// Paragraph-level formatting derived from a line's position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineStyle {
+    pub alignment: Alignment,
+    pub force_italic: bool,
+    pub font_size_pt: i32,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        LineStyle {
+            alignment: Alignment::Justify,
+            force_italic: false,
+            font_size_pt: crate::config::get().font_size_pt,
+        }
+    }
+}
+
+/// A piece of text sharing the same emphasis.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InlineRun {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// Target of the `[text](url)` link the text belongs to.
+    pub link: Option<String>,
+    /// Set when the run is an image, whose alt text is `text`.
+    pub image: Option<Image>,
+    /// Label of the `[^label]` footnote the run refers to; its `text` is
+    /// `[label]`, for the writers without footnotes (see `footnotes`).
+    pub note: Option<String>,
+}
+
+/// An image written `![alt](source)`, optionally followed by its size as in
+/// Pandoc: `{width=5cm height=2cm}`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Image {
+    /// Path relative to the Markdown file, or absolute.
+    pub source: String,
+    /// Size in English Metric Units (914400 per inch, 360000 per cm), the
+    /// unit of DOCX drawings.
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+}
+
+/// EMU per unit of the lengths image sizes may be given in; a bare number
+/// is pixels at 96 dpi.
+const IMAGE_UNITS: &[(&str, f64)] = &[
+    ("cm", 360_000.0),
+    ("mm", 36_000.0),
+    ("in", 914_400.0),
+    ("pt", 12_700.0),
+    ("px", 9_525.0),
+    ("", 9_525.0),
+];
+
+fn parse_length(value: &str) -> Option<u64> {
+    IMAGE_UNITS.iter().find_map(|(unit, emu)| {
+        let number: f64 = value.strip_suffix(unit)?.trim().parse().ok()?;
+        (number > 0.0).then(|| (number * emu).round() as u64)
+    })
+}
+
+/// Writes a length in EMU as centimetres, e.g. `5.08cm`.
+fn format_length(emu: u64) -> String {
+    let cm = format!("{:.2}", emu as f64 / 360_000.0);
+    format!("{}cm", cm.trim_end_matches('0').trim_end_matches('.'))
+}
+
+impl Image {
+    /// The `{width=... height=...}` attributes at `chars[start]`, if any, and
+    /// the position after them. Other attributes (`#id`, `.class`,
+    /// `key=value`) are skipped.
+    fn size_at(&mut self, chars: &[char], start: usize) -> Option<usize> {
+        if chars.get(start) != Some(&'{') {
+            return None;
+        }
+        let close = start + chars[start..].iter().position(|&c| c == '}')?;
+        let attributes: String = chars[start + 1..close].iter().collect();
+        let (mut width, mut height) = (None, None);
+        for attribute in attributes.split_whitespace() {
+            if attribute.starts_with(['#', '.']) {
+                continue;
+            }
+            match attribute.split_once('=')? {
+                ("width", value) => width = parse_length(value),
+                ("height", value) => height = parse_length(value),
+                _ => {}
+            }
+        }
+        (self.width, self.height) = (width, height);
+        Some(close + 1)
+    }
+
+    fn to_markdown(&self, alt: &str) -> String {
+        let mut out = format!("![{}]({})", alt, destination(&self.source));
+        let size: Vec<String> = [("width", self.width), ("height", self.height)]
+            .into_iter()
+            .filter_map(|(key, emu)| Some(format!("{}={}", key, format_length(emu?))))
+            .collect();
+        if !size.is_empty() {
+            out.push_str(&format!("{{{}}}", size.join(" ")));
+        }
+        out
+    }
+}
+
+/// A link or image destination, in `<...>` when it holds spaces or
+/// parentheses.
+fn destination(url: &str) -> String {
+    if url.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+        format!("<{}>", url)
+    } else {
+        url.to_string()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Block {
+    Paragraph {
+        /// Inline Markdown with any heading marker removed.
+        text: String,
+        /// Heading level, 1 for `#` through 6 for `######`.
+        heading: Option<u8>,
+        style: LineStyle,
+    },
+    Table {
+        header: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+}
+
+/// Splits a `## Name` line into its text and heading level; other lines are
+/// returned unchanged with no level.
+pub fn normalize_heading(line: &str) -> (String, Option<u8>) {
+    let stripped = line.trim_start();
+    if stripped.starts_with('#') {
+        let text = stripped.trim_start_matches('#');
+        let level = (stripped.len() - text.len()).min(6) as u8;
+        (text.trim().to_string(), Some(level))
+    } else {
+        (line.to_string(), None)
+    }
+}
+
+/// Font size of a heading: `#` is the largest, `###` and deeper sit just
+/// above the body size.
+pub fn heading_font_size_pt(level: u8) -> i32 {
+    let body = crate::config::get().font_size_pt;
+    match level {
+        1 => body + 4,
+        2 => body + 2,
+        _ => body + 1,
+    }
+}
+
+impl LineStyle {
+    /// This style for a paragraph written with `heading`: headings get their
+    /// level's size, except on the footer line.
+    pub fn with_heading(mut self, heading: Option<u8>) -> Self {
+        if let (Some(level), false) = (heading, self.force_italic) {
+            self.font_size_pt = heading_font_size_pt(level);
+        }
+        self
+    }
+}
+
+/// A Markdown list item: `- text` (or `*`, `+`) and `1. text` (or `1)`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ListItem<'a> {
+    /// Leading spaces, a tab counting as four.
+    pub indent: usize,
+    pub ordered: bool,
+    /// The number written before the item; 0 for bullets.
+    pub number: usize,
+    /// Inline Markdown after the marker.
+    pub text: &'a str,
+}
+
+/// Recognises a list item line. `*text*` is emphasis, not a bullet: the
+/// marker must be followed by a space.
+pub fn parse_list_item(line: &str) -> Option<ListItem<'_>> {
+    let body = line.trim_start_matches([' ', '\t']);
+    let indent = line[..line.len() - body.len()]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+    let (ordered, number, rest) = match body.chars().next()? {
+        '-' | '*' | '+' => (false, 0, &body[1..]),
+        _ => {
+            let digits = body.len() - body.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let rest = body[digits..]
+                .strip_prefix('.')
+                .or_else(|| body[digits..].strip_prefix(')'))?;
+            // Nine digits at most, as CommonMark allows.
+            if digits == 0 || digits > 9 {
+                return None;
+            }
+            (true, body[..digits].parse().ok()?, rest)
+        }
+    };
+    let text = rest.strip_prefix([' ', '\t'])?.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    Some(ListItem {
+        indent,
+        ordered,
+        number,
+        text,
+    })
+}
+
+/// Turns list item indents into nesting levels: an item indented past the
+/// one before opens a nested list, one indented less closes lists back to
+/// the enclosing level.
+#[derive(Debug, Default)]
+pub struct ListNesting {
+    indents: Vec<usize>,
+}
+
+impl ListNesting {
+    /// Level of an item with `indent`, 0 for the outermost list.
+    pub fn level(&mut self, indent: usize) -> usize {
+        while self.indents.last().is_some_and(|&open| open > indent) {
+            self.indents.pop();
+        }
+        if self.indents.last().is_none_or(|&open| open < indent) {
+            self.indents.push(indent);
+        }
+        self.indents.len() - 1
+    }
+
+    /// Ends the current list.
+    pub fn reset(&mut self) {
+        self.indents.clear();
+    }
+}
+
+/// A run of `*` or `_` characters that may open or close emphasis.
+#[derive(Clone, Debug)]
+struct Delimiter {
+    marker: char,
+    /// Length of the run as written.
+    length: usize,
+    /// Characters not yet used by a match; they are written as text.
+    remaining: usize,
+    can_open: bool,
+    can_close: bool,
+    active: bool,
+    /// Sizes of the matches this run opens (after its remaining characters)
+    /// and closes (before them): 1 for emphasis, 2 for strong emphasis.
+    opens: Vec<usize>,
+    closes: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum Piece {
+    Text(String),
+    Delimiter(usize),
+    /// A `<u>` or `</u>` tag, as written.
+    Underline {
+        open: bool,
+        tag: String,
+    },
+    /// The `[` of a link to the URL, and the `](url)` ending it.
+    LinkStart(String),
+    LinkEnd,
+    /// A whole `![alt](source)` image, with its alt text.
+    Image(String, Image),
+    /// A `[^label]` footnote reference, with its label.
+    Note(String),
+}
+
+fn flush_text(buffer: &mut String, pieces: &mut Vec<Piece>) {
+    if !buffer.is_empty() {
+        pieces.push(Piece::Text(std::mem::take(buffer)));
+    }
+}
+
+/// A `[text](url)` link whose `[` is at `chars[start]`: the positions of its
+/// `]` and of the character after its `)`, and the URL (written bare or in
+/// `<...>`, without a title).
+fn link_at(chars: &[char], start: usize) -> Option<(usize, usize, String)> {
+    let mut depth = 0usize;
+    let mut i = start;
+    let close = loop {
+        match chars.get(i)? {
+            '\\' => i += 1,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    break i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    };
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let rest = &chars[close + 2..];
+    let (url, length) = if rest.first() == Some(&'<') {
+        let end = rest.iter().position(|&c| c == '>')?;
+        if rest.get(end + 1) != Some(&')') {
+            return None;
+        }
+        (rest[1..end].iter().collect::<String>(), end + 2)
+    } else {
+        let end = rest.iter().position(|&c| c == ')' || c.is_whitespace())?;
+        if rest[end] != ')' {
+            return None;
+        }
+        (rest[..end].iter().collect(), end + 1)
+    };
+    Some((close, close + 2 + length, url))
+}
+
+/// A `[^label]` footnote reference whose `[` is at `chars[start]`: its
+/// label and the position after its `]`. Labels have no spaces or brackets.
+pub(crate) fn note_at(chars: &[char], start: usize) -> Option<(String, usize)> {
+    if chars.get(start) != Some(&'[') || chars.get(start + 1) != Some(&'^') {
+        return None;
+    }
+    let length = chars[start + 2..]
+        .iter()
+        .position(|&c| c == ']' || c == '[' || c.is_whitespace())?;
+    let close = start + 2 + length;
+    if length == 0 || chars[close] != ']' {
+        return None;
+    }
+    Some((chars[start + 2..close].iter().collect(), close + 1))
+}
+
+/// The `<u>` or `</u>` tag starting `chars`, if any.
+fn underline_tag(chars: &[char]) -> Option<(bool, usize)> {
+    let starts = |tag: &str| {
+        tag.chars().count() <= chars.len()
+            && tag
+                .chars()
+                .zip(chars)
+                .all(|(t, c)| t.eq_ignore_ascii_case(c))
+    };
+    if starts("<u>") {
+        Some((true, 3))
+    } else if starts("</u>") {
+        Some((false, 4))
+    } else {
+        None
+    }
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || !(c.is_alphanumeric() || c.is_whitespace())
+}
+
+/// Splits `text` into text and delimiter runs, deciding which runs may open
+/// or close emphasis with the CommonMark flanking rules. The line's start
+/// and end count as whitespace. A backslash before ASCII punctuation (`\*`,
+/// `\_`, `\\`) makes it text, and so does a `*` between digits, as in the
+/// measurement `5*4*3 cm`.
+fn delimiter_runs(text: &str) -> (Vec<Piece>, Vec<Delimiter>) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut pieces = Vec::new();
+    let mut delimiters = Vec::new();
+    let mut buffer = String::new();
+    // Where the open link's text ends, and where its `(url)` does.
+    let mut link_end: Option<(usize, usize)> = None;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some((_, end)) = link_end.filter(|&(close, _)| close == i) {
+            flush_text(&mut buffer, &mut pieces);
+            pieces.push(Piece::LinkEnd);
+            link_end = None;
+            i = end;
+            continue;
+        }
+        if c == '!' && chars.get(i + 1) == Some(&'[') {
+            if let Some((close, end, source)) = link_at(&chars, i + 1) {
+                flush_text(&mut buffer, &mut pieces);
+                // The alt text is plain: escapes resolved, markers dropped.
+                let alt: String = chars[i + 2..close].iter().collect();
+                let alt: String = inline_runs(&alt, false, UnderlineMarkup::Underscores)
+                    .into_iter()
+                    .map(|run| run.text)
+                    .collect();
+                let mut image = Image {
+                    source,
+                    ..Default::default()
+                };
+                i = image.size_at(&chars, end).unwrap_or(end);
+                pieces.push(Piece::Image(alt, image));
+                continue;
+            }
+        }
+        if c == '[' && link_end.is_none() {
+            if let Some((close, end, url)) = link_at(&chars, i) {
+                flush_text(&mut buffer, &mut pieces);
+                pieces.push(Piece::LinkStart(url));
+                link_end = Some((close, end));
+                i += 1;
+                continue;
+            }
+        }
+        if let Some((label, end)) = note_at(&chars, i) {
+            flush_text(&mut buffer, &mut pieces);
+            pieces.push(Piece::Note(label));
+            i = end;
+            continue;
+        }
+        if c == '\\'
+            && chars
+                .get(i + 1)
+                .is_some_and(|next| next.is_ascii_punctuation())
+        {
+            buffer.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+        if let Some((open, length)) = underline_tag(&chars[i..]) {
+            flush_text(&mut buffer, &mut pieces);
+            let tag = chars[i..i + length].iter().collect();
+            pieces.push(Piece::Underline { open, tag });
+            i += length;
+            continue;
+        }
+        if c != '*' && c != '_' {
+            buffer.push(c);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && chars[i] == c {
+            i += 1;
+        }
+        let before = start.checked_sub(1).map_or(' ', |j| chars[j]);
+        let after = chars.get(i).copied().unwrap_or(' ');
+        if c == '*' && before.is_ascii_digit() && after.is_ascii_digit() {
+            buffer.extend(&chars[start..i]);
+            continue;
+        }
+        let left = !after.is_whitespace()
+            && (!is_punctuation(after) || before.is_whitespace() || is_punctuation(before));
+        let right = !before.is_whitespace()
+            && (!is_punctuation(before) || after.is_whitespace() || is_punctuation(after));
+        // `_` does not emphasise parts of a word (`snake_case_name`).
+        let (can_open, can_close) = if c == '*' {
+            (left, right)
+        } else {
+            (
+                left && (!right || is_punctuation(before)),
+                right && (!left || is_punctuation(after)),
+            )
+        };
+        flush_text(&mut buffer, &mut pieces);
+        pieces.push(Piece::Delimiter(delimiters.len()));
+        delimiters.push(Delimiter {
+            marker: c,
+            length: i - start,
+            remaining: i - start,
+            can_open,
+            can_close,
+            active: true,
+            opens: Vec::new(),
+            closes: Vec::new(),
+        });
+    }
+    flush_text(&mut buffer, &mut pieces);
+    (pieces, delimiters)
+}
+
+/// Pairs openers with closers as CommonMark does: each closer takes the
+/// nearest earlier opener of the same character, two characters at a time
+/// (strong) when both runs have them. Returns how many marker characters
+/// stay unpaired.
+fn match_delimiters(delimiters: &mut [Delimiter]) -> usize {
+    for closer in 0..delimiters.len() {
+        while delimiters[closer].can_close && delimiters[closer].remaining > 0 {
+            let close = &delimiters[closer];
+            let opener = (0..closer).rev().find(|&o| {
+                let open = &delimiters[o];
+                // The "rule of 3" keeps `*a**b*` from pairing the wrong runs.
+                let both = open.can_close || close.can_open;
+                let multiple_of_3 = (open.length + close.length).is_multiple_of(3)
+                    && !(open.length.is_multiple_of(3) && close.length.is_multiple_of(3));
+                open.active
+                    && open.marker == close.marker
+                    && open.can_open
+                    && open.remaining > 0
+                    && !(both && multiple_of_3)
+            });
+            let Some(opener) = opener else {
+                if !delimiters[closer].can_open {
+                    delimiters[closer].active = false;
+                }
+                break;
+            };
+            let size = if delimiters[opener].remaining >= 2 && delimiters[closer].remaining >= 2 {
+                2
+            } else {
+                1
+            };
+            delimiters[opener].remaining -= size;
+            delimiters[opener].opens.push(size);
+            delimiters[closer].remaining -= size;
+            delimiters[closer].closes.push(size);
+            for between in &mut delimiters[opener + 1..closer] {
+                between.active = false;
+            }
+        }
+    }
+    delimiters
+        .iter()
+        .filter(|d| d.can_open || d.can_close)
+        .map(|d| d.remaining)
+        .sum()
+}
+
+/// Splits inline Markdown into runs with the CommonMark emphasis rules:
+/// `*`/`_` around text is italic and `**` bold, nested in any order
+/// (`**bold *italic* bold**`). `<u>`...`</u>` is underline, and so is `__`
+/// unless `config::Config::underline` says otherwise, in which case `__` is
+/// bold. The text of a `[text](url)` link carries its URL. Markers that do
+/// not pair up are kept as text. `bold` makes every
+/// run bold (headings, table headers).
+///
+/// The pairing is the delimiter-run algorithm of the CommonMark spec
+/// ("Emphasis and strong emphasis"), written here for the inline markup
+/// the templates use rather than taken from a full Markdown parser, so
+/// the `__` setting and the relaxed closers below fit in.
+///
+/// When the strict rules leave an opener unpaired, a run with a space
+/// before it may close emphasis too, as in `**Técnica do exame: **Realizadas`
+/// or `**IMPRESSÃO: **`: importers that wrap each Word run write the run's
+/// trailing space inside the markers.
+pub fn parse_inline(text: &str, bold: bool) -> Vec<InlineRun> {
+    inline_runs(text, bold, config::get().underline)
+}
+
+fn inline_runs(text: &str, bold: bool, markup: UnderlineMarkup) -> Vec<InlineRun> {
+    let (pieces, delimiters) = delimiter_runs(text);
+    let mut strict = delimiters.clone();
+    let mut delimiters = match match_delimiters(&mut strict) {
+        0 => strict,
+        _ => {
+            let mut lenient = delimiters;
+            for delimiter in &mut lenient {
+                delimiter.can_close = true;
+            }
+            match_delimiters(&mut lenient);
+            let unpaired = |delimiters: &[Delimiter]| -> usize {
+                delimiters.iter().map(|d| d.remaining).sum()
+            };
+            if unpaired(&lenient) < unpaired(&strict) {
+                lenient
+            } else {
+                strict
+            }
+        }
+    };
+
+    // Tags pair like brackets; the others are text.
+    let mut paired = vec![false; pieces.len()];
+    let mut open_tags = Vec::new();
+    for (index, piece) in pieces.iter().enumerate() {
+        match piece {
+            Piece::Underline { open: true, .. } => open_tags.push(index),
+            Piece::Underline { open: false, .. } => {
+                if let Some(opener) = open_tags.pop() {
+                    paired[opener] = true;
+                    paired[index] = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let underscores_underline = markup == UnderlineMarkup::Underscores;
+    let mut runs: Vec<InlineRun> = Vec::new();
+    // Open strong, emphasis and underline spans.
+    let mut depth = [0usize; 3];
+    let mut link: Option<String> = None;
+    let mut push = |text: &str,
+                    depth: [usize; 3],
+                    link: &Option<String>,
+                    image: Option<Image>,
+                    note: Option<String>| {
+        if text.is_empty() && image.is_none() {
+            return;
+        }
+        let format = (bold || depth[0] > 0, depth[1] > 0, depth[2] > 0);
+        match runs.last_mut() {
+            Some(last)
+                if (last.bold, last.italic, last.underline) == format
+                    && last.link == *link
+                    && last.image.is_none()
+                    && last.note.is_none()
+                    && image.is_none()
+                    && note.is_none() =>
+            {
+                last.text.push_str(text)
+            }
+            _ => runs.push(InlineRun {
+                text: text.to_string(),
+                bold: format.0,
+                italic: format.1,
+                underline: format.2,
+                link: link.clone(),
+                image,
+                note,
+            }),
+        }
+    };
+    for (index, piece) in pieces.into_iter().enumerate() {
+        match piece {
+            Piece::Text(text) => push(&text, depth, &link, None, None),
+            Piece::Image(alt, image) => push(&alt, depth, &link, Some(image), None),
+            Piece::Note(label) => push(&format!("[{}]", label), depth, &link, None, Some(label)),
+            Piece::Underline { open, tag } => match (paired[index], open) {
+                (true, true) => depth[2] += 1,
+                (true, false) => depth[2] -= 1,
+                (false, _) => push(&tag, depth, &link, None, None),
+            },
+            Piece::LinkStart(url) => link = Some(url),
+            Piece::LinkEnd => link = None,
+            Piece::Delimiter(index) => {
+                let delimiter = &mut delimiters[index];
+                let kind = |size: usize| match size {
+                    2 if delimiter.marker == '_' && underscores_underline => 2,
+                    2 => 0,
+                    _ => 1,
+                };
+                for size in std::mem::take(&mut delimiter.closes) {
+                    depth[kind(size)] -= 1;
+                }
+                let literal = delimiter.marker.to_string().repeat(delimiter.remaining);
+                push(&literal, depth, &link, None, None);
+                for size in std::mem::take(&mut delimiter.opens) {
+                    depth[kind(size)] += 1;
+                }
+            }
+        }
+    }
+    runs
+}
+
+/// Appends `text` to `out` with a backslash before each `*`, `_`, `\\`,
+/// `<u>` tag, link `[` or footnote `[^` that `parse_inline` would
+/// otherwise read as markup. An underscore inside a word (`snake_case`) and a `*` between
+/// digits (`5*4*3`) are already text and are left bare.
+fn escape_markers(text: &str, out: &mut String) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' {
+            // A backslash at the end may precede a marker.
+            if chars
+                .get(i + 1)
+                .is_none_or(|next| next.is_ascii_punctuation())
+            {
+                out.push('\\');
+            }
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        if c != '*' && c != '_' {
+            if underline_tag(&chars[i..]).is_some()
+                || (c == '[' && (link_at(&chars, i).is_some() || note_at(&chars, i).is_some()))
+            {
+                out.push('\\');
+            }
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && chars[i] == c {
+            i += 1;
+        }
+        let before = start.checked_sub(1).map(|j| chars[j]);
+        let after = chars.get(i).copied();
+        let between = |test: fn(&char) -> bool| {
+            before.is_some_and(|b| test(&b)) && after.is_some_and(|a| test(&a))
+        };
+        let literal = match c {
+            '*' => between(char::is_ascii_digit),
+            _ => between(|c| c.is_alphanumeric()),
+        };
+        for marker in &chars[start..i] {
+            if !literal {
+                out.push('\\');
+            }
+            out.push(*marker);
+        }
+    }
+}
+
+/// Writes formatted runs as inline Markdown that `parse_inline` reads back
+/// to the same formatting: runs sharing a format stay inside one pair of
+/// markers (`**bold *italic* bold**`), underline is wrapped in `__` or
+/// `<u>` as `config::Config::underline` says, links become `[text](url)`,
+/// footnote references `[^label]`, and whitespace at run edges stays outside the markers so they always
+/// pair.
+pub fn spans_to_markdown(spans: &[Span]) -> String {
+    write_spans(spans, config::get().underline)
+}
+
+fn write_spans(spans: &[Span], markup: UnderlineMarkup) -> String {
+    let underline = match markup {
+        UnderlineMarkup::Underscores => ("__", "__"),
+        UnderlineMarkup::Html => ("<u>", "</u>"),
+    };
+    let mut writer = SpanWriter::default();
+    for group in spans.chunk_by(|a, b| a.link == b.link) {
+        let Some(url) = &group[0].link else {
+            for span in group {
+                let wanted: Vec<(&str, &str)> = [
+                    (span.underline, underline),
+                    (span.italic, ("*", "*")),
+                    (span.bold, ("**", "**")),
+                ]
+                .into_iter()
+                .filter_map(|(on, marker)| on.then_some(marker))
+                .collect();
+                let mut core = String::new();
+                escape_markers(span.text.trim(), &mut core);
+                match (&span.image, &span.note) {
+                    (Some(image), _) => {
+                        let image = image.to_markdown(&core);
+                        writer.write(&image, &image, wanted);
+                    }
+                    (None, Some(label)) => {
+                        let reference = format!("[^{}]", label);
+                        writer.write(&reference, &reference, wanted);
+                    }
+                    (None, None) => writer.write(&span.text, &core, wanted),
+                }
+            }
+            continue;
+        };
+        // Emphasis over the whole link text stays outside the brackets, so
+        // `*see [ACR](url).*` comes back as written; the rest goes inside.
+        let shared = |on: fn(&Span) -> bool| {
+            group
+                .iter()
+                .filter(|span| span.image.is_some() || !span.text.trim().is_empty())
+                .all(on)
+        };
+        let (bold, italic, underlined) = (
+            shared(|s| s.bold),
+            shared(|s| s.italic),
+            shared(|s| s.underline),
+        );
+        let inner: Vec<Span> = group
+            .iter()
+            .map(|span| Span {
+                text: span.text.clone(),
+                bold: span.bold && !bold,
+                italic: span.italic && !italic,
+                underline: span.underline && !underlined,
+                link: None,
+                image: span.image.clone(),
+                note: span.note.clone(),
+            })
+            .collect();
+        let text = write_spans(&inner, markup);
+        let wanted = [
+            (underlined, underline),
+            (italic, ("*", "*")),
+            (bold, ("**", "**")),
+        ]
+        .into_iter()
+        .filter_map(|(on, marker)| on.then_some(marker))
+        .collect();
+        writer.write(
+            &text,
+            &format!("[{}]({})", text.trim(), destination(url)),
+            wanted,
+        );
+    }
+    writer.finish()
+}
+
+/// Inline Markdown being written: the markers open at the end of `out`, and
+/// whitespace held back until the markers before it are closed.
+#[derive(Default)]
+struct SpanWriter<'a> {
+    out: String,
+    /// Open markers with their closing ones, outermost first.
+    open: Vec<(&'a str, &'a str)>,
+    pending: String,
+}
+
+impl<'a> SpanWriter<'a> {
+    /// Writes `core`, the Markdown of `text` without its edge whitespace,
+    /// inside the `wanted` markers.
+    fn write(&mut self, text: &str, core: &str, wanted: Vec<(&'a str, &'a str)>) {
+        if text.trim().is_empty() {
+            self.pending.push_str(text);
+            return;
+        }
+        let lead = &text[..text.len() - text.trim_start().len()];
+        let trail = &text[text.trim_end().len()..];
+        // Close back to the markers this span keeps, then open the others.
+        while self.open.iter().any(|marker| !wanted.contains(marker)) {
+            if let Some((_, close)) = self.open.pop() {
+                self.out.push_str(close);
+            }
+        }
+        self.out.push_str(&self.pending);
+        self.out.push_str(lead);
+        self.pending.clear();
+        for marker in wanted {
+            if !self.open.contains(&marker) {
+                self.out.push_str(marker.0);
+                self.open.push(marker);
+            }
+        }
+        self.out.push_str(core);
+        self.pending.push_str(trail);
+    }
+
+    fn finish(mut self) -> String {
+        while let Some((_, close)) = self.open.pop() {
+            self.out.push_str(close);
+        }
+        self.out.push_str(&self.pending);
+        self.out
+    }
+}
+
+/// Splits a `| a | b |` line into trimmed cells, honouring `\|` escapes.
+/// Returns `None` when the line is not a pipe table row.
+pub fn parse_table_row(line: &str) -> Option<Vec<String>> {
+    let trimmed = line.trim();
+    if !trimmed.starts_with('|') || trimmed.len() < 2 {
+        return None;
+    }
+
+    let mut cells = Vec::new();
+    let mut current = String::new();
+    let mut chars = trimmed[1..].chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                current.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        cells.push(current.trim().to_string());
+    }
+    Some(cells)
+}
+
+/// True for the `| --- | :---: |` row separating the header from the body.
+fn is_separator_row(cells: &[String]) -> bool {
+    !cells.is_empty()
+        && cells.iter().all(|c| {
+            let inner = c.trim_start_matches(':').trim_end_matches(':');
+            !inner.is_empty() && inner.chars().all(|ch| ch == '-')
+        })
+}
+
+/// Length of the pipe table starting at `lines[start]` (header, separator
+/// and body rows), or 0 when no table starts there.
+fn table_len(lines: &[&str], start: usize) -> usize {
+    let header = match parse_table_row(lines[start]) {
+        Some(cells) => cells,
+        None => return 0,
+    };
+    match lines.get(start + 1).and_then(|l| parse_table_row(l)) {
+        Some(sep) if is_separator_row(&sep) && sep.len() == header.len() => {}
+        _ => return 0,
+    }
+    2 + lines[start + 2..]
+        .iter()
+        .take_while(|l| parse_table_row(l).is_some())
+        .count()
+}
+
+/// The lines `layout` renders as the title and the footer, by index.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TitleFooter {
+    pub title: Option<usize>,
+    pub footer: Option<usize>,
+}
+
+impl TitleFooter {
+    /// The first non-empty line, not counting a letterhead logo above it,
+    /// and the last one.
+    pub fn first_and_last(content: &str) -> Self {
+        let lines: Vec<&str> = content.lines().collect();
+        TitleFooter {
+            title: lines
+                .iter()
+                .position(|line| !line.trim().is_empty() && !is_image_line(line)),
+            footer: lines.iter().rposition(|line| !line.trim().is_empty()),
+        }
+    }
+}
+
+/// Lays out a Markdown template into blocks, centering the lines
+/// `title_footer` names and making the footer italic 8pt.
+pub fn layout(content: &str, title_footer: TitleFooter) -> Vec<Block> {
+    let lines: Vec<&str> = content.lines().collect();
+
+    if lines.is_empty() {
+        return vec![Block::Paragraph {
+            text: String::new(),
+            heading: None,
+            style: LineStyle::default(),
+        }];
+    }
+
+    // Image lines before any text are the letterhead.
+    let first_written = TitleFooter::first_and_last(content).title;
+
+    let mut blocks = Vec::new();
+    let mut idx = 0;
+    while idx < lines.len() {
+        let line = lines[idx];
+        let table_lines = table_len(&lines, idx);
+        if table_lines > 0 {
+            let rows = &lines[idx..idx + table_lines];
+            blocks.push(Block::Table {
+                header: parse_table_row(rows[0]).unwrap_or_default(),
+                rows: rows[2..]
+                    .iter()
+                    .filter_map(|l| parse_table_row(l))
+                    .collect(),
+            });
+            idx += table_lines;
+            // The blank line after a table is Markdown syntax, not content.
+            if lines.get(idx).is_some_and(|l| l.trim().is_empty()) {
+                idx += 1;
+            }
+            continue;
+        }
+        if line.trim().is_empty() && idx + 1 < lines.len() && table_len(&lines, idx + 1) > 0 {
+            idx += 1;
+            continue;
+        }
+
+        let mut style = LineStyle::default();
+        let letterhead = first_written.is_none_or(|first| idx < first) && is_image_line(line);
+        if Some(idx) == title_footer.title || letterhead {
+            style.alignment = Alignment::Center;
+        }
+        if Some(idx) == title_footer.footer {
+            style.alignment = Alignment::Center;
+            style.force_italic = true;
+            style.font_size_pt = FOOTER_FONT_SIZE_PT;
+        }
+
+        let (text, heading) = normalize_heading(line);
+        blocks.push(Block::Paragraph {
+            text,
+            heading,
+            style: style.with_heading(heading),
+        });
+        idx += 1;
+    }
+
+    blocks
+}
+
+/// Whether `line` is the `PAGE_BREAK` marker.
+pub fn is_page_break(line: &str) -> bool {
+    line.trim() == PAGE_BREAK
+}
+
+/// Whether `line` holds images and nothing else, as a logo line does.
+pub fn is_image_line(line: &str) -> bool {
+    let runs = parse_inline(line, false);
+    runs.iter().any(|run| run.image.is_some())
+        && runs
+            .iter()
+            .all(|run| run.image.is_some() || run.text.trim().is_empty())
+}
+
+/// Table header cells are bold already; explicit `**` markers around the
+/// whole cell would toggle bold off again.
+pub fn strip_header_bold(text: &str) -> &str {
+    text.strip_prefix("**")
+        .and_then(|t| t.strip_suffix("**"))
+        .unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str, bold: bool, italic: bool) -> InlineRun {
+        InlineRun {
+            text: text.to_string(),
+            bold,
+            italic,
+            underline: false,
+            link: None,
+            image: None,
+            note: None,
+        }
+    }
+
+    fn round_trip(text: &str) -> String {
+        let spans: Vec<Span> = parse_inline(text, false)
+            .into_iter()
+            .map(|run| Span {
+                text: run.text,
+                bold: run.bold,
+                italic: run.italic,
+                underline: run.underline,
+                link: run.link,
+                image: run.image,
+                note: run.note,
+            })
+            .collect();
+        spans_to_markdown(&spans)
+    }
+
+    #[test]
+    fn italic_inside_bold() {
+        assert_eq!(
+            parse_inline("**bold *italic* bold**", false),
+            [
+                run("bold ", true, false),
+                run("italic", true, true),
+                run(" bold", true, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn bold_inside_italic() {
+        assert_eq!(
+            parse_inline("*italic **bold** italic*", false),
+            [
+                run("italic ", false, true),
+                run("bold", true, true),
+                run(" italic", false, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn bold_and_italic_together() {
+        assert_eq!(
+            parse_inline("***both*** plain", false),
+            [run("both", true, true), run(" plain", false, false)]
+        );
+        assert_eq!(
+            parse_inline("**bold *and italic***", false),
+            [run("bold ", true, false), run("and italic", true, true)]
+        );
+    }
+
+    #[test]
+    fn adjacent_spans() {
+        assert_eq!(
+            parse_inline("**bold***italic*", false),
+            [run("bold", true, false), run("italic", false, true)]
+        );
+        assert_eq!(
+            parse_inline("*italic***bold**", false),
+            [run("italic", false, true), run("bold", true, false)]
+        );
+    }
+
+    #[test]
+    fn unpaired_markers_are_text() {
+        assert_eq!(
+            parse_inline("2 * 3 = 6", false),
+            [run("2 * 3 = 6", false, false)]
+        );
+        assert_eq!(parse_inline("**open", false), [run("**open", false, false)]);
+        assert_eq!(
+            parse_inline("snake_case_name", false),
+            [run("snake_case_name", false, false)]
+        );
+        // Also after the last text of the line.
+        for text in [
+            "fim *",
+            "a _ b _",
+            "x __ y __",
+            "a ** b **",
+            "30 _",
+            "**",
+            "__",
+        ] {
+            assert_eq!(parse_inline(text, false), [run(text, false, false)]);
+        }
+    }
+
+    #[test]
+    fn space_inside_closing_marker() {
+        assert_eq!(
+            parse_inline("**Técnica do exame: **Realizadas", false),
+            [
+                run("Técnica do exame: ", true, false),
+                run("Realizadas", false, false)
+            ]
+        );
+        assert_eq!(
+            parse_inline("**IMPRESSÃO: **", false),
+            [run("IMPRESSÃO: ", true, false)]
+        );
+    }
+
+    #[test]
+    fn nested_emphasis_round_trips() {
+        for text in [
+            "**bold *italic* bold**",
+            "*italic **bold** italic*",
+            "***both*** plain",
+            "**bold***italic*",
+            "plain **bold *both*** *italic*",
+            "_italic **bold** italic_",
+        ] {
+            let written = round_trip(text);
+            assert_eq!(
+                parse_inline(&written, false),
+                parse_inline(text, false),
+                "{written}"
+            );
+        }
+        assert_eq!(
+            round_trip("**bold *italic* bold**"),
+            "**bold *italic* bold**"
+        );
+        assert_eq!(
+            round_trip("*italic **bold** italic*"),
+            "*italic **bold** italic*"
+        );
+    }
+
+    #[test]
+    fn measurements_and_names_are_text() {
+        assert_eq!(
+            parse_inline("Nódulo de 5*4*3 cm", false),
+            [run("Nódulo de 5*4*3 cm", false, false)]
+        );
+        assert_eq!(
+            parse_inline("**10*8 mm** e uso de ácido_tranexâmico", false),
+            [
+                run("10*8 mm", true, false),
+                run(" e uso de ácido_tranexâmico", false, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        assert_eq!(
+            parse_inline(r"\*não itálico\* e \_nem este\_", false),
+            [run("*não itálico* e _nem este_", false, false)]
+        );
+        assert_eq!(
+            parse_inline(r"**negrito \*** C:\pasta", false),
+            [
+                run("negrito *", true, false),
+                run(r" C:\pasta", false, false)
+            ]
+        );
+        assert_eq!(
+            parse_inline(r"\\*itálico*", false),
+            [run("\\", false, false), run("itálico", false, true)]
+        );
+    }
+
+    #[test]
+    fn literal_markers_are_escaped_when_written() {
+        for text in [
+            "*nota*",
+            "5*4*3 cm",
+            "a * b",
+            "_x",
+            r"C:\",
+            r"\*",
+            "snake_case",
+        ] {
+            let spans = [Span {
+                text: text.to_string(),
+                bold: true,
+                ..Default::default()
+            }];
+            let written = spans_to_markdown(&spans);
+            assert_eq!(
+                parse_inline(&written, false),
+                [run(text, true, false)],
+                "{written}"
+            );
+        }
+        let spans = [Span {
+            text: "5*4*3 cm, snake_case".into(),
+            ..Default::default()
+        }];
+        assert_eq!(spans_to_markdown(&spans), "5*4*3 cm, snake_case");
+    }
+
+    fn underlined(text: &str, bold: bool) -> InlineRun {
+        InlineRun {
+            underline: true,
+            ..run(text, bold, false)
+        }
+    }
+
+    #[test]
+    fn underline_markup() {
+        let text = "__sublinhado__ e **negrito**";
+        assert_eq!(
+            inline_runs(text, false, UnderlineMarkup::Underscores),
+            [
+                underlined("sublinhado", false),
+                run(" e ", false, false),
+                run("negrito", true, false)
+            ]
+        );
+        assert_eq!(
+            inline_runs(text, false, UnderlineMarkup::Html),
+            [
+                run("sublinhado", true, false),
+                run(" e ", false, false),
+                run("negrito", true, false)
+            ]
+        );
+        for markup in [UnderlineMarkup::Underscores, UnderlineMarkup::Html] {
+            assert_eq!(
+                inline_runs("<u>**ambos**</u> e </u>", false, markup),
+                [underlined("ambos", true), run(" e </u>", false, false)]
+            );
+        }
+    }
+
+    #[test]
+    fn underline_round_trips_in_either_markup() {
+        let spans = [
+            Span {
+                text: "Sublinhado ".into(),
+                underline: true,
+                ..Default::default()
+            },
+            Span {
+                text: "e negrito".into(),
+                bold: true,
+                underline: true,
+                ..Default::default()
+            },
+            Span {
+                text: ", <u> literal".into(),
+                ..Default::default()
+            },
+        ];
+        for (markup, written) in [
+            (
+                UnderlineMarkup::Underscores,
+                r"__Sublinhado **e negrito**__, \<u> literal",
+            ),
+            (
+                UnderlineMarkup::Html,
+                r"<u>Sublinhado **e negrito**</u>, \<u> literal",
+            ),
+        ] {
+            assert_eq!(write_spans(&spans, markup), written);
+            assert_eq!(
+                inline_runs(written, false, markup),
+                [
+                    underlined("Sublinhado ", false),
+                    underlined("e negrito", true),
+                    run(", <u> literal", false, false)
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn spans_keep_edge_whitespace_outside_markers() {
+        let spans = [
+            Span {
+                text: "IMPRESSÃO: ".into(),
+                bold: true,
+                ..Default::default()
+            },
+            Span {
+                text: "XXX".into(),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(spans_to_markdown(&spans), "**IMPRESSÃO:** XXX");
+        // A formatted run of spaces, as Word leaves after a disclaimer,
+        // writes no markers of its own.
+        let spans = [
+            Span {
+                text: "informações.".into(),
+                italic: true,
+                ..Default::default()
+            },
+            Span {
+                text: " ".into(),
+                italic: true,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(spans_to_markdown(&spans), "*informações.* ");
+    }
+
+    fn linked(text: &str, bold: bool, italic: bool, url: &str) -> InlineRun {
+        InlineRun {
+            link: Some(url.to_string()),
+            ..run(text, bold, italic)
+        }
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            parse_inline("Ver [ACR **TI-RADS**](https://www.acr.org/) já", false),
+            [
+                run("Ver ", false, false),
+                linked("ACR ", false, false, "https://www.acr.org/"),
+                linked("TI-RADS", true, false, "https://www.acr.org/"),
+                run(" já", false, false)
+            ]
+        );
+        assert_eq!(
+            parse_inline("*Fonte: [Fleischner](<guia 2017.pdf>).*", false),
+            [
+                run("Fonte: ", false, true),
+                linked("Fleischner", false, true, "guia 2017.pdf"),
+                run(".", false, true)
+            ]
+        );
+        for text in ["[XXX] cm", "[a] (b)", r"\[a](b)", "[a](b c)"] {
+            assert!(
+                parse_inline(text, false).iter().all(|r| r.link.is_none()),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn links_round_trip() {
+        for text in [
+            "Ver [ACR **TI-RADS**](https://www.acr.org/) já",
+            "*Fonte: [Fleischner](<guia 2017.pdf>).*",
+            r"\[não é](link)",
+        ] {
+            assert_eq!(round_trip(text), text);
+        }
+        // Emphasis over all of the link text is written around the link.
+        let text = "[**negrito**](#impressao) e [*itálico*](#tecnica)";
+        assert_eq!(
+            round_trip(text),
+            "**[negrito](#impressao)** e *[itálico](#tecnica)*"
+        );
+        assert_eq!(
+            parse_inline(&round_trip(text), false),
+            parse_inline(text, false)
+        );
+    }
+
+    fn image(alt: &str, source: &str, width: Option<u64>, height: Option<u64>) -> InlineRun {
+        InlineRun {
+            image: Some(Image {
+                source: source.to_string(),
+                width,
+                height,
+            }),
+            ..run(alt, false, false)
+        }
+    }
+
+    #[test]
+    fn images() {
+        assert_eq!(
+            parse_inline("![Logo **da** clínica](assets/logo.png)", false),
+            [image("Logo da clínica", "assets/logo.png", None, None)]
+        );
+        assert_eq!(
+            parse_inline(
+                "a ![](<logo 2.png>){width=5cm height=20mm #id} ![](b.png){width=96} c",
+                false
+            ),
+            [
+                run("a ", false, false),
+                image("", "logo 2.png", Some(1_800_000), Some(720_000)),
+                run(" ", false, false),
+                image("", "b.png", Some(914_400), None),
+                run(" c", false, false)
+            ]
+        );
+        // A brace that is not an attribute list stays text.
+        assert_eq!(
+            parse_inline("![](a.png){sem atributos}", false),
+            [
+                image("", "a.png", None, None),
+                run("{sem atributos}", false, false)
+            ]
+        );
+        assert_eq!(
+            parse_inline(r"\![não](imagem)", false),
+            [
+                run("!", false, false),
+                linked("não", false, false, "imagem")
+            ]
+        );
+        assert!(is_image_line(" ![](a.png) ![logo](b.png) "));
+        assert!(!is_image_line("![](a.png) legenda"));
+        assert!(!is_image_line(""));
+    }
+
+    #[test]
+    fn images_round_trip() {
+        for text in [
+            "![Logo da clínica](assets/logo.png)",
+            "Texto ![](<logo 2.png>){width=5.08cm height=1.27cm} e **![](a.png)**",
+            "[![](assets/acr.png){width=1cm}](https://www.acr.org/)",
+        ] {
+            assert_eq!(round_trip(text), text);
+        }
+    }
+}
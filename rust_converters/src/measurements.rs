@@ -0,0 +1,249 @@
+//! Measurements in template text (the `measurement` lint rule and
+//! `rtemplates lint --fix`).
+//!
+//! A measurement is a number, or numbers joined by `x` (`1,2 x 0,8`),
+//! followed by a unit: mm, cm, mm³, cm³, mL, cc, UH/HU, mmHg or cm/s. It is
+//! flagged when written against the department style of `[lint]`: no space
+//! before the unit (`12mm`), a unit spelled otherwise than `units` says
+//! (`ml` for `mL`), the other decimal separator, or mixed ones, and
+//! dimensions not joined by ` x `. Those can be fixed in place. Values
+//! beyond what the unit makes plausible (`80 cm`) are only reported.
+
+use regex::{Captures, Regex};
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use crate::config::LintSettings;
+
+/// How units are written by default; `units` under `[lint]` adds to it.
+const UNITS: &[(&str, &str)] = &[
+    ("mm", "mm"),
+    ("cm", "cm"),
+    ("mm3", "mm³"),
+    ("mm³", "mm³"),
+    ("cm3", "cm³"),
+    ("cm³", "cm³"),
+    ("cc", "cm³"),
+    ("ml", "mL"),
+    ("mmhg", "mmHg"),
+    // Both are in use, so only the case is fixed unless `units` picks one.
+    ("hu", "HU"),
+    ("uh", "UH"),
+    ("cm/s", "cm/s"),
+];
+
+/// The largest plausible value per unit, as written after normalizing.
+const LIMITS: &[(&str, f64)] = &[
+    ("mm", 500.0),
+    ("cm", 50.0),
+    ("mL", 10000.0),
+    ("cm³", 10000.0),
+    ("UH", 4000.0),
+    ("HU", 4000.0),
+    ("mmHg", 300.0),
+    ("cm/s", 1000.0),
+];
+
+fn measurement_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        // The characters around the measurement are matched rather than
+        // looked at, so a number inside a word (`T2`, `C5`) or a unit
+        // starting a word (`cmHg`) is left out.
+        Regex::new(
+            r"(?:^|[^\p{L}\p{N}])(?P<value>\d+(?:[.,]\d+)?(?:[ \t]*[x×X][ \t]*\d+(?:[.,]\d+)?)*)(?P<space>[ \t]*)(?P<unit>(?i:mmhg|mm³|mm3|mm|cm/s|cm³|cm3|cm|ml|cc|hu|uh))(?:$|[^\p{L}\p{N}])",
+        )
+        .unwrap()
+    })
+}
+
+fn number_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(\d+)(?:([.,])(\d+))?").unwrap())
+}
+
+/// A measurement written against the style, or with an unlikely value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeasurementIssue {
+    /// Byte range of the measurement in its line.
+    pub range: Range<usize>,
+    pub message: String,
+    /// The measurement as the style writes it, when that differs.
+    pub fix: Option<String>,
+}
+
+/// The preferred spelling of `unit`: the `units` setting, then the
+/// built-in list, ignoring case.
+fn canonical_unit<'a>(unit: &'a str, settings: &'a LintSettings) -> &'a str {
+    let lower = unit.to_lowercase();
+    settings
+        .units
+        .iter()
+        .rev()
+        .find(|(written, _)| written.to_lowercase() == lower)
+        .map(|(_, preferred)| preferred.as_str())
+        .or_else(|| {
+            UNITS
+                .iter()
+                .find(|(written, _)| *written == lower)
+                .map(|(_, preferred)| *preferred)
+        })
+        .unwrap_or(unit)
+}
+
+fn issue(line: &str, captures: &Captures, settings: &LintSettings) -> Option<MeasurementIssue> {
+    let value = captures.name("value").unwrap();
+    let space = &captures["space"];
+    let unit = captures.name("unit").unwrap();
+    let range = value.start()..unit.end();
+    let preferred_unit = canonical_unit(unit.as_str(), settings);
+    let separator = settings.decimal_separator;
+    let other = if separator == ',' { '.' } else { ',' };
+
+    // Each number as the style writes it, and its value.
+    let mut numbers: Vec<(String, f64)> = Vec::new();
+    let mut separators = Vec::new();
+    for number in number_regex().captures_iter(value.as_str()) {
+        let written = number.get(2).and_then(|m| m.as_str().chars().next());
+        let fraction = number.get(3).map_or("", |m| m.as_str());
+        // `1.500` is as likely a thousands separator as a decimal one.
+        let ambiguous = written == Some(other) && fraction.len() == 3;
+        let normalized = match written {
+            Some(_) if !ambiguous => {
+                separators.push(written.unwrap());
+                format!("{}{}{}", &number[1], separator, fraction)
+            }
+            _ => number[0].to_string(),
+        };
+        let parsed = number[0].replace(',', ".").parse().unwrap_or(0.0);
+        numbers.push((normalized, parsed));
+    }
+
+    let mut messages = Vec::new();
+    if separators.contains(&separator) && separators.contains(&other) {
+        messages.push("mixed decimal separators".to_string());
+    } else if separators.contains(&other) {
+        messages.push(format!("decimal `{}` instead of `{}`", other, separator));
+    }
+    let joins = value
+        .as_str()
+        .split(|c: char| c.is_ascii_digit() || c == ',' || c == '.');
+    if joins
+        .filter(|join| !join.is_empty())
+        .any(|join| join != " x ")
+    {
+        messages.push("dimensions not joined by ` x `".to_string());
+    }
+    match space {
+        "" => messages.push("no space before the unit".to_string()),
+        " " => {}
+        _ => messages.push("more than one space before the unit".to_string()),
+    }
+    if unit.as_str() != preferred_unit {
+        messages.push(format!(
+            "`{}` written for `{}`",
+            unit.as_str(),
+            preferred_unit
+        ));
+    }
+    let fixable = !messages.is_empty();
+
+    let limit = LIMITS
+        .iter()
+        .find(|(unit, _)| *unit == preferred_unit)
+        .map(|(_, limit)| *limit);
+    if let Some(limit) = limit {
+        if numbers.iter().any(|(_, value)| *value > limit) {
+            messages.push(format!(
+                "unlikely value for {} (more than {})",
+                preferred_unit, limit
+            ));
+        }
+    }
+
+    let joined: Vec<&str> = numbers.iter().map(|(number, _)| number.as_str()).collect();
+    (!messages.is_empty()).then(|| MeasurementIssue {
+        message: format!("`{}`: {}", &line[range.clone()], messages.join(", ")),
+        fix: fixable.then(|| format!("{} {}", joined.join(" x "), preferred_unit)),
+        range,
+    })
+}
+
+/// The measurements of `line` to report, in order.
+pub fn check_line(line: &str, settings: &LintSettings) -> Vec<MeasurementIssue> {
+    let mut issues = Vec::new();
+    let mut start = 0;
+    // Matches include the characters around them, so each search starts at
+    // the end of the last measurement rather than of the last match.
+    while let Some(captures) = measurement_regex().captures_at(line, start) {
+        let end = captures.name("unit").unwrap().end();
+        issues.extend(issue(line, &captures, settings));
+        start = end;
+    }
+    issues
+}
+
+/// `text` with every fixable measurement rewritten in the style, and how
+/// many were.
+pub fn fix_text(text: &str, settings: &LintSettings) -> (String, usize) {
+    let mut count = 0;
+    let fixed = text
+        .split('\n')
+        .map(|line| {
+            let mut fixed = String::new();
+            let mut position = 0;
+            for issue in check_line(line, settings) {
+                if let Some(fix) = issue.fix {
+                    fixed.push_str(&line[position..issue.range.start]);
+                    fixed.push_str(&fix);
+                    position = issue.range.end;
+                    count += 1;
+                }
+            }
+            fixed.push_str(&line[position..]);
+            fixed
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    (fixed, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measurements_are_checked_and_fixed() {
+        let mut settings = LintSettings::default();
+        settings.units.push(("hu".to_string(), "UH".to_string()));
+        let line = "Nódulo de 1,2 x 0,8 cm, outro de 12mm e cisto de 1.2x0,8 CM; \
+                    volume 30 ml, densidade 40 HU, T2 de 3 cm e lesão de 80 cm.";
+        let issues = check_line(line, &settings);
+        let messages: Vec<&str> = issues.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "`12mm`: no space before the unit",
+                "`1.2x0,8 CM`: mixed decimal separators, dimensions not joined by ` x `, `CM` written for `cm`",
+                "`30 ml`: `ml` written for `mL`",
+                "`40 HU`: `HU` written for `UH`",
+                "`80 cm`: unlikely value for cm (more than 50)",
+            ]
+        );
+        assert_eq!(issues[4].fix, None);
+
+        let (fixed, count) = fix_text(line, &settings);
+        assert_eq!(count, 4);
+        assert_eq!(
+            fixed,
+            "Nódulo de 1,2 x 0,8 cm, outro de 12 mm e cisto de 1,2 x 0,8 cm; \
+             volume 30 mL, densidade 40 UH, T2 de 3 cm e lesão de 80 cm."
+        );
+
+        settings.decimal_separator = '.';
+        assert_eq!(
+            fix_text("Mede 1,5 cm e 1,500 mL.", &settings).0,
+            "Mede 1.5 cm e 1,500 mL."
+        );
+    }
+}
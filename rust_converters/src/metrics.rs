@@ -0,0 +1,151 @@
+//! Length and readability figures of a template, to find the overly
+//! verbose ones (`stats`, and `files` in `reports_index.json`).
+//!
+//! Words are counted in the plain text of the title, the sections (the
+//! text after a heading's colon included) and the footer. A sentence ends
+//! at `.`, `!` or `?` before a space, or at the end of a paragraph, so
+//! `5.2 mm` and headings without a period are handled; table cells add
+//! words but not sentences. Findings are the words of the Aspectos
+//! observados and Impressão sections, boilerplate everything else.
+
+use serde::{Deserialize, Serialize};
+
+use crate::template::{plain, Content, ReportTemplate, SectionKind};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    pub words: usize,
+    pub sentences: usize,
+    /// Words per sentence, to one decimal.
+    pub average_sentence_length: f64,
+    /// Words of the Aspectos observados and Impressão sections.
+    pub findings_words: usize,
+    /// Words of the title, indication, technique, footer and any text
+    /// outside the standard sections.
+    pub boilerplate_words: usize,
+    /// Share of the words that are boilerplate, to two decimals.
+    pub boilerplate_ratio: f64,
+}
+
+/// Rounds `value` to `decimals` places.
+pub(crate) fn round(value: f64, decimals: i32) -> f64 {
+    let scale = 10f64.powi(decimals);
+    (value * scale).round() / scale
+}
+
+/// `count / total`, or 0 without a total.
+pub(crate) fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+/// The sentences of the plain text of one paragraph.
+fn sentence_count(text: &str) -> usize {
+    let mut count = 0;
+    let mut open = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_alphanumeric() {
+            open = true;
+        } else if open
+            && matches!(c, '.' | '!' | '?')
+            && chars.peek().is_none_or(|next| next.is_whitespace())
+        {
+            count += 1;
+            open = false;
+        }
+    }
+    count + usize::from(open)
+}
+
+#[derive(Default)]
+struct Tally {
+    findings: usize,
+    boilerplate: usize,
+    sentences: usize,
+}
+
+impl Tally {
+    fn paragraph(&mut self, text: &str, findings: bool) {
+        self.sentences += sentence_count(text);
+        self.cell(text, findings);
+    }
+
+    fn cell(&mut self, text: &str, findings: bool) {
+        let words = text.split_whitespace().count();
+        if findings {
+            self.findings += words;
+        } else {
+            self.boilerplate += words;
+        }
+    }
+}
+
+pub fn metrics(template: &ReportTemplate) -> Metrics {
+    let mut tally = Tally::default();
+    if let Some(title) = &template.title {
+        tally.paragraph(&title.plain(), false);
+    }
+    for section in &template.sections {
+        let findings = matches!(
+            section.kind,
+            Some(SectionKind::AspectosObservados | SectionKind::Impressao)
+        );
+        // `**Impressão:** Normal.` holds its first line after the colon.
+        let heading = section.heading.as_ref().map(|heading| heading.plain());
+        if let Some((_, inline)) = heading.as_deref().and_then(|line| line.split_once(':')) {
+            tally.paragraph(inline, findings);
+        }
+        for content in &section.content {
+            match content {
+                Content::Paragraph(paragraph) => tally.paragraph(&paragraph.plain(), findings),
+                Content::Table { header, rows } => {
+                    for cell in header.iter().chain(rows.iter().flatten()) {
+                        tally.cell(&plain(&cell.replace("<br>", " ")), findings);
+                    }
+                }
+            }
+        }
+    }
+    if let Some(footer) = &template.footer {
+        tally.paragraph(&footer.plain(), false);
+    }
+
+    let words = tally.findings + tally.boilerplate;
+    Metrics {
+        words,
+        sentences: tally.sentences,
+        average_sentence_length: round(ratio(words, tally.sentences), 1),
+        findings_words: tally.findings,
+        boilerplate_words: tally.boilerplate,
+        boilerplate_ratio: round(ratio(tally.boilerplate, words), 2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn findings_and_boilerplate_are_told_apart() {
+        let template = ReportTemplate::from_markdown(
+            "**TOMOGRAFIA DO TÓRAX**\n\n**Técnica:**\n\nAquisição volumétrica sem contraste.\n\n\
+             **Aspectos observados:**\n\nNódulo de 5.2 mm no lobo superior. Sem derrame!\n\n\
+             | Medida | Valor |\n|---|---|\n| Aorta | 30 mm |\n\n\
+             **Impressão:** Nódulo pulmonar\n\n*Laudo revisado*\n",
+        );
+        let metrics = metrics(&template);
+        // Title 3, technique 4 and footer 2; findings 9, table 5 and
+        // impression 2.
+        assert_eq!(metrics.boilerplate_words, 9);
+        assert_eq!(metrics.findings_words, 16);
+        assert_eq!(metrics.words, 25);
+        // Title, technique, two findings, impression and footer.
+        assert_eq!(metrics.sentences, 6);
+        assert_eq!(metrics.average_sentence_length, 4.2);
+        assert_eq!(metrics.boilerplate_ratio, 0.36);
+    }
+}
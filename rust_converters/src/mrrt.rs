@@ -0,0 +1,615 @@
+//! IHE MRRT template reader, for templates downloaded from radreport.org.
+//!
+//! Sections and sub-sections become bold `Name:` headings and form fields are
+//! written as their default value, after their label as in
+//! `**Informe clínico:** XXX`. Empty fields get the `XXX` placeholder used
+//! throughout the templates; static text, lists and tables are kept.
+
+use std::collections::HashMap;
+
+use crate::convert_to_markdown::rows_to_markdown;
+use crate::html::{attribute, decode, decode_entities, tokenize, Token};
+use crate::template::ReportTemplate;
+
+/// Written for form fields without a default value.
+const PLACEHOLDER: &str = "XXX";
+
+/// Elements whose content is never template text.
+const SKIPPED_ELEMENTS: &[&str] = &["head", "script", "style", "title", "noscript", "button"];
+
+/// Elements that start and end a line.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "li",
+    "ul",
+    "ol",
+    "dl",
+    "dt",
+    "dd",
+    "blockquote",
+    "pre",
+    "fieldset",
+    "legend",
+];
+
+/// Collapses whitespace runs (including non-breaking spaces) to one space.
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// True when boolean attribute `name` (`checked`, `selected`) is present.
+fn has_flag(attrs: &str, name: &str) -> bool {
+    attrs
+        .to_ascii_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '=')
+        .any(|word| word == name)
+}
+
+/// Text of every token up to the end tag `name`, starting at `tokens[start]`.
+fn text_until(tokens: &[Token], start: usize, name: &str) -> String {
+    let mut text = String::new();
+    for token in &tokens[start..] {
+        match token {
+            Token::EndTag(end) if end == name => break,
+            Token::Text(raw) => text.push_str(raw),
+            _ => {}
+        }
+    }
+    collapse(&decode_entities(&text))
+}
+
+/// Header metadata plus the text of every `<label for=...>`, which is
+/// attached to its field rather than written where it appears.
+#[derive(Default)]
+struct Metadata {
+    title: Option<String>,
+    /// DICOM modality code from the template's coded terms.
+    modality: Option<String>,
+    labels: HashMap<String, String>,
+}
+
+fn metadata(tokens: &[Token]) -> Metadata {
+    let mut meta = Metadata::default();
+    let mut page_title = None;
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::StartTag { name, attrs } = token else {
+            continue;
+        };
+        match name.as_str() {
+            "title" => page_title = Some(text_until(tokens, i + 1, "title")),
+            "meta"
+                if attribute(attrs, "name")
+                    .is_some_and(|n| n.eq_ignore_ascii_case("dcterms.title")) =>
+            {
+                meta.title = attribute(attrs, "content").map(|c| collapse(&decode_entities(c)));
+            }
+            "script" => {
+                let Some(Token::Text(body)) = tokens.get(i + 1) else {
+                    continue;
+                };
+                for term in tokenize(body) {
+                    if let Token::StartTag { name, attrs } = term {
+                        if name == "code" && attribute(attrs, "scheme") == Some("DCM") {
+                            meta.modality = attribute(attrs, "value").map(str::to_string);
+                        }
+                    }
+                }
+            }
+            "label" => {
+                if let Some(id) = attribute(attrs, "for") {
+                    meta.labels
+                        .insert(id.to_string(), text_until(tokens, i + 1, "label"));
+                }
+            }
+            _ => {}
+        }
+    }
+    meta.title = meta.title.or(page_title).filter(|t| !t.is_empty());
+    meta
+}
+
+/// An open `<section>`; its heading is written before its first content.
+struct SectionFrame {
+    name: Option<String>,
+    written: bool,
+}
+
+#[derive(Default)]
+struct TableState {
+    rows: Vec<Vec<String>>,
+    row: Option<Vec<String>>,
+    cell: Option<String>,
+    /// Nested tables are read as cell text.
+    depth: usize,
+}
+
+#[derive(Default)]
+struct SelectState {
+    id: Option<String>,
+    options: Vec<(String, bool)>,
+    option: Option<(String, bool)>,
+}
+
+struct Reader<'a> {
+    meta: &'a Metadata,
+    lines: Vec<String>,
+    /// Raw text of the line being built; whitespace is collapsed on flush.
+    line: String,
+    list_item: bool,
+    /// Inside the closing disclaimer written by `convert_to_mrrt`.
+    footer: bool,
+    sections: Vec<SectionFrame>,
+    skip: Option<String>,
+    header: Option<String>,
+    heading: Option<String>,
+    textarea: Option<(Option<String>, String)>,
+    select: Option<SelectState>,
+    table: Option<TableState>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(meta: &'a Metadata) -> Self {
+        let mut lines = Vec::new();
+        if let Some(title) = &meta.title {
+            lines.push(format!("**{}**", title));
+            lines.push(String::new());
+        }
+        Reader {
+            meta,
+            lines,
+            line: String::new(),
+            list_item: false,
+            footer: false,
+            sections: Vec::new(),
+            skip: None,
+            header: None,
+            heading: None,
+            textarea: None,
+            select: None,
+            table: None,
+        }
+    }
+
+    fn flush_line(&mut self) {
+        let text = collapse(&std::mem::take(&mut self.line));
+        if text.is_empty() {
+            return;
+        }
+        if self.footer {
+            self.blank_line();
+            self.lines.push(format!("*{}*", text));
+        } else if self.list_item {
+            // Only the first line of a list item gets the marker.
+            self.lines.push(format!("-{}", text));
+            self.list_item = false;
+        } else {
+            self.lines.push(text);
+        }
+    }
+
+    fn blank_line(&mut self) {
+        if self.lines.last().is_some_and(|l| !l.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    /// Writes the headings of the enclosing sections not written yet.
+    fn open_sections(&mut self) {
+        for i in 0..self.sections.len() {
+            if self.sections[i].written {
+                continue;
+            }
+            self.sections[i].written = true;
+            if let Some(name) = self.sections[i].name.clone() {
+                let line = std::mem::take(&mut self.line);
+                self.blank_line();
+                self.lines
+                    .push(format!("**{}:**", name.trim_end_matches(':').trim()));
+                self.line = line;
+            }
+        }
+    }
+
+    fn section_name(&self) -> Option<&str> {
+        self.sections.last().and_then(|s| s.name.as_deref())
+    }
+
+    fn text(&mut self, raw: &str) {
+        if let Some(header) = self.header.as_mut().or(self.heading.as_mut()) {
+            header.push_str(raw);
+            return;
+        }
+        if let Some((_, value)) = self.textarea.as_mut() {
+            value.push_str(raw);
+            return;
+        }
+        if let Some(select) = self.select.as_mut() {
+            if let Some((text, _)) = select.option.as_mut() {
+                text.push_str(raw);
+            }
+            return;
+        }
+
+        let text = decode_entities(raw);
+        if !text.trim().is_empty() {
+            self.open_sections();
+        }
+        match self.table.as_mut() {
+            Some(table) => {
+                // Text between cells is markup whitespace.
+                if let Some(cell) = table.cell.as_mut() {
+                    cell.push_str(&text);
+                }
+            }
+            None => self.line.push_str(&text),
+        }
+    }
+
+    /// Writes a field's value, after its label unless the label only repeats
+    /// the section name.
+    fn field(&mut self, id: Option<&str>, value: &str) {
+        self.open_sections();
+        let label = id
+            .and_then(|id| self.meta.labels.get(id))
+            .map(|l| l.trim_end_matches(':').trim().to_string())
+            .filter(|l| {
+                !l.is_empty()
+                    && !self.section_name().is_some_and(|name| {
+                        name.trim_end_matches(':').trim().to_lowercase() == l.to_lowercase()
+                    })
+            });
+
+        let mut lines: Vec<String> = Vec::new();
+        for line in value.lines().map(collapse) {
+            if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+                continue;
+            }
+            lines.push(line);
+        }
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        if lines.is_empty() {
+            lines.push(PLACEHOLDER.to_string());
+        }
+        let label = label.map(|l| format!("**{}:** ", l)).unwrap_or_default();
+
+        if let Some(table) = self.table.as_mut() {
+            if let Some(cell) = table.cell.as_mut() {
+                let values: Vec<&str> = lines
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|l| !l.is_empty())
+                    .collect();
+                cell.push_str(&format!(" {}{} ", label, values.join("<br>")));
+            }
+            return;
+        }
+
+        self.line.push_str(&format!(" {}{}", label, lines[0]));
+        for line in &lines[1..] {
+            self.flush_line();
+            if line.is_empty() {
+                self.lines.push(String::new());
+            } else {
+                self.line.push_str(line);
+            }
+        }
+    }
+
+    fn start_tag(&mut self, name: &str, attrs: &str) {
+        if SKIPPED_ELEMENTS.contains(&name) {
+            self.skip = Some(name.to_string());
+            return;
+        }
+        if let Some(table) = self.table.as_mut() {
+            match name {
+                "table" => table.depth += 1,
+                "tr" if table.depth == 0 => {
+                    table.close_cell();
+                    table.close_row();
+                    table.row = Some(Vec::new());
+                }
+                "td" | "th" if table.depth == 0 => {
+                    table.close_cell();
+                    table.row.get_or_insert_with(Vec::new);
+                    table.cell = Some(String::new());
+                }
+                "br" | "p" | "div" | "li" => {
+                    if let Some(cell) = table.cell.as_mut() {
+                        if !cell.trim().is_empty() {
+                            cell.push_str("<br>");
+                        }
+                    }
+                }
+                _ => {}
+            }
+            if !matches!(name, "input" | "textarea" | "select" | "option" | "label") {
+                return;
+            }
+        }
+
+        match name {
+            "section" => {
+                self.flush_line();
+                self.sections.push(SectionFrame {
+                    name: attribute(attrs, "data-section-name")
+                        .map(|n| collapse(&decode_entities(n)))
+                        .filter(|n| !n.is_empty()),
+                    written: false,
+                });
+            }
+            "header" => {
+                self.flush_line();
+                self.header = Some(String::new());
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush_line();
+                self.heading = Some(String::new());
+            }
+            "table" => {
+                self.flush_line();
+                self.open_sections();
+                self.table = Some(TableState::default());
+            }
+            "br" => self.flush_line(),
+            "label" => {
+                let attached =
+                    attribute(attrs, "for").is_some_and(|id| self.meta.labels.contains_key(id));
+                if attached {
+                    self.skip = Some("label".to_string());
+                }
+            }
+            "input" => {
+                let kind = attribute(attrs, "type")
+                    .unwrap_or("text")
+                    .to_ascii_lowercase();
+                let id = attribute(attrs, "id");
+                match kind.as_str() {
+                    "hidden" | "button" | "submit" | "reset" | "image" | "file" => {}
+                    "checkbox" | "radio" => {
+                        if has_flag(attrs, "checked") {
+                            let text = id
+                                .and_then(|id| self.meta.labels.get(id).cloned())
+                                .or_else(|| attribute(attrs, "value").map(decode_entities))
+                                .unwrap_or_default();
+                            self.text(&format!(" {} ", text));
+                        }
+                    }
+                    _ => {
+                        let value = attribute(attrs, "value")
+                            .map(decode_entities)
+                            .unwrap_or_default();
+                        self.field(id, &value);
+                    }
+                }
+            }
+            "textarea" => {
+                self.textarea = Some((attribute(attrs, "id").map(str::to_string), String::new()));
+            }
+            "select" => {
+                self.select = Some(SelectState {
+                    id: attribute(attrs, "id").map(str::to_string),
+                    ..SelectState::default()
+                });
+            }
+            "option" => {
+                if let Some(select) = self.select.as_mut() {
+                    select.close_option();
+                    select.option = Some((String::new(), has_flag(attrs, "selected")));
+                }
+            }
+            "li" => {
+                self.flush_line();
+                self.list_item = true;
+            }
+            _ if BLOCK_ELEMENTS.contains(&name) => {
+                self.flush_line();
+                self.footer = attribute(attrs, "class").is_some_and(|c| c.contains("footer"));
+            }
+            _ => {}
+        }
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        if let Some(table) = self.table.as_mut() {
+            match name {
+                "table" if table.depth > 0 => table.depth -= 1,
+                "table" => {
+                    table.close_cell();
+                    table.close_row();
+                    let rows = std::mem::take(&mut table.rows);
+                    self.table = None;
+                    self.lines.extend(rows_to_markdown(&rows));
+                    return;
+                }
+                "td" | "th" if table.depth == 0 => table.close_cell(),
+                "tr" if table.depth == 0 => {
+                    table.close_cell();
+                    table.close_row();
+                }
+                _ => {}
+            }
+            if !matches!(name, "textarea" | "select" | "option") {
+                return;
+            }
+        }
+
+        match name {
+            "section" => {
+                self.flush_line();
+                // An empty section still shows its heading.
+                self.open_sections();
+                self.sections.pop();
+            }
+            "header" => {
+                let text = collapse(&decode_entities(&self.header.take().unwrap_or_default()));
+                match self.sections.last_mut() {
+                    Some(section) if !section.written => {
+                        if !text.is_empty() {
+                            section.name = Some(text);
+                        }
+                        self.open_sections();
+                    }
+                    _ if !text.is_empty() => {
+                        self.blank_line();
+                        self.lines.push(format!("**{}**", text));
+                    }
+                    _ => {}
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = collapse(&decode_entities(&self.heading.take().unwrap_or_default()));
+                let is_title = self
+                    .meta
+                    .title
+                    .as_ref()
+                    .is_some_and(|t| t.to_lowercase() == text.to_lowercase());
+                if !text.is_empty() && !is_title {
+                    self.open_sections();
+                    self.lines.push(format!("**{}**", text));
+                }
+            }
+            "textarea" => {
+                if let Some((id, value)) = self.textarea.take() {
+                    self.field(id.as_deref(), &decode_entities(&value));
+                }
+            }
+            "select" => {
+                if let Some(mut select) = self.select.take() {
+                    select.close_option();
+                    let selected: Vec<&str> = select
+                        .options
+                        .iter()
+                        .filter(|(_, selected)| *selected)
+                        .map(|(text, _)| text.as_str())
+                        .collect();
+                    let value = if selected.is_empty() {
+                        select
+                            .options
+                            .iter()
+                            .map(|(text, _)| text.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" / ")
+                    } else {
+                        selected.join(" / ")
+                    };
+                    self.field(select.id.as_deref(), &value);
+                }
+            }
+            "option" => {
+                if let Some(select) = self.select.as_mut() {
+                    select.close_option();
+                }
+            }
+            "li" => {
+                self.flush_line();
+                self.list_item = false;
+            }
+            _ if BLOCK_ELEMENTS.contains(&name) => {
+                self.flush_line();
+                self.footer = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn run(mut self, tokens: &[Token]) -> Vec<String> {
+        for token in tokens {
+            if let Some(skipped) = &self.skip {
+                if matches!(token, Token::EndTag(name) if name == skipped) {
+                    self.skip = None;
+                }
+                continue;
+            }
+            match token {
+                Token::StartTag { name, attrs } => self.start_tag(name, attrs),
+                Token::EndTag(name) => self.end_tag(name),
+                Token::Text(raw) => self.text(raw),
+            }
+        }
+        self.flush_line();
+        self.open_sections();
+
+        let mut lines: Vec<String> = Vec::new();
+        for line in self.lines {
+            if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+                continue;
+            }
+            lines.push(line);
+        }
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+}
+
+impl TableState {
+    fn close_cell(&mut self) {
+        if let Some(cell) = self.cell.take() {
+            self.row.get_or_insert_with(Vec::new).push(collapse(&cell));
+        }
+    }
+
+    fn close_row(&mut self) {
+        if let Some(row) = self.row.take() {
+            if !row.is_empty() {
+                self.rows.push(row);
+            }
+        }
+    }
+}
+
+impl SelectState {
+    fn close_option(&mut self) {
+        if let Some((text, selected)) = self.option.take() {
+            let text = collapse(&decode_entities(&text));
+            if !text.is_empty() {
+                self.options.push((text, selected));
+            }
+        }
+    }
+}
+
+/// Reads an MRRT template into the repository's Markdown layout.
+pub fn parse(input: &[u8]) -> ReportTemplate {
+    let source = decode(input);
+    let tokens = tokenize(&source);
+    let meta = metadata(&tokens);
+    let lines = Reader::new(&meta).run(&tokens);
+
+    let mut template = ReportTemplate::from_markdown(&lines.join("\n"));
+    if meta.modality.is_some() {
+        template.modality = meta.modality.clone();
+    }
+    template
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_written_as_their_default_after_their_label() {
+        let source = r#"<html><head><title>CT Head</title><script type="text/xml"><template_attributes><term><code meaning="Computed Tomography" value="CT" scheme="DCM"/></term></template_attributes></script></head>
+<body>
+<section data-section-name="Clinical information"><header>Clinical information</header>
+<p><label for="T1">Indication</label><textarea id="T1"></textarea></p>
+</section>
+<section data-section-name="Findings"><header>Findings</header>
+<section data-section-name="Brain"><header>Brain</header>
+<p><label for="T2">Parenchyma</label><input id="T2" type="text" value="Normal &amp; symmetric"/></p>
+<p><label for="T3">Hemorrhage</label><select id="T3"><option>Present</option><option selected>Absent</option></select></p>
+</section>
+</section>
+</body></html>"#;
+        let template = parse(source.as_bytes());
+        assert_eq!(template.modality.as_deref(), Some("CT"));
+        assert_eq!(
+            template.to_markdown(),
+            "**CT Head**\n\n**Clinical information:**\n**Indication:** XXX\n\n**Findings:**\n\n**Brain:**\n**Parenchyma:** Normal & symmetric\n**Hemorrhage:** Absent\n"
+        );
+    }
+}
@@ -0,0 +1,452 @@
+//! Minimal ODT (OpenDocument Text) reader: resolves paragraph and text
+//! styles from `styles.xml`/`content.xml` and yields formatted paragraphs
+//! and tables from the document body.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use xmlparser::{ElementEnd, Token, Tokenizer};
+
+use crate::convert_to_markdown::ImportedBlock;
+use crate::html::decode_entities;
+use crate::rtf::{RtfParagraph, Span};
+
+/// An XML element boundary or text, with `prefix:local` names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    End(String),
+    Text(String),
+}
+
+fn qualified(prefix: &str, local: &str) -> String {
+    if prefix.is_empty() {
+        local.to_string()
+    } else {
+        format!("{}:{}", prefix, local)
+    }
+}
+
+/// Flattens the tokenizer output so attributes arrive with their element
+/// and self-closing elements produce a matching `End`.
+fn events(xml: &str) -> Result<Vec<Event>> {
+    let mut events = Vec::new();
+    let mut open: Option<(String, Vec<(String, String)>)> = None;
+    for token in Tokenizer::from(xml) {
+        match token? {
+            Token::ElementStart { prefix, local, .. } => {
+                open = Some((qualified(&prefix, &local), Vec::new()));
+            }
+            Token::Attribute {
+                prefix,
+                local,
+                value,
+                ..
+            } => {
+                if let Some((_, attrs)) = open.as_mut() {
+                    attrs.push((qualified(&prefix, &local), decode_entities(&value)));
+                }
+            }
+            Token::ElementEnd { end, .. } => match end {
+                ElementEnd::Open => {
+                    if let Some((name, attrs)) = open.take() {
+                        events.push(Event::Start { name, attrs });
+                    }
+                }
+                ElementEnd::Empty => {
+                    if let Some((name, attrs)) = open.take() {
+                        events.push(Event::Start {
+                            name: name.clone(),
+                            attrs,
+                        });
+                        events.push(Event::End(name));
+                    }
+                }
+                ElementEnd::Close(prefix, local) => {
+                    events.push(Event::End(qualified(&prefix, &local)));
+                }
+            },
+            Token::Text { text } => events.push(Event::Text(decode_entities(&text))),
+            Token::Cdata { text, .. } => events.push(Event::Text(text.to_string())),
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Character formatting declared by one `style:style`; `None` inherits.
+#[derive(Debug, Clone, Default)]
+struct StyleInfo {
+    parent: Option<String>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    underline: Option<bool>,
+}
+
+/// Collects `style:style` definitions keyed by name.
+fn read_styles(events: &[Event], styles: &mut HashMap<String, StyleInfo>) {
+    let mut current: Option<String> = None;
+    for event in events {
+        match event {
+            Event::Start { name, attrs } if name == "style:style" => {
+                let Some(style_name) = attr(attrs, "style:name") else {
+                    continue;
+                };
+                styles.insert(
+                    style_name.to_string(),
+                    StyleInfo {
+                        parent: attr(attrs, "style:parent-style-name").map(str::to_string),
+                        ..StyleInfo::default()
+                    },
+                );
+                current = Some(style_name.to_string());
+            }
+            Event::Start { name, attrs } if name == "style:text-properties" => {
+                let Some(info) = current.as_ref().and_then(|n| styles.get_mut(n)) else {
+                    continue;
+                };
+                if let Some(weight) = attr(attrs, "fo:font-weight") {
+                    info.bold = Some(matches!(weight, "bold" | "600" | "700" | "800" | "900"));
+                }
+                if let Some(style) = attr(attrs, "fo:font-style") {
+                    info.italic = Some(style == "italic" || style == "oblique");
+                }
+                if let Some(underline) = attr(attrs, "style:text-underline-style") {
+                    info.underline = Some(underline != "none");
+                }
+            }
+            Event::End(name) if name == "style:style" => current = None,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Format {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    skip: bool,
+}
+
+/// Applies a named style (and its parents) on top of `base`.
+fn apply_style(styles: &HashMap<String, StyleInfo>, name: &str, base: Format) -> Format {
+    let mut chain = Vec::new();
+    let mut next = Some(name);
+    // Parent chains are short; the bound guards against cycles.
+    while let Some(style) = next.and_then(|n| styles.get(n)) {
+        chain.push(style);
+        next = style.parent.as_deref();
+        if chain.len() > 16 {
+            break;
+        }
+    }
+
+    let mut format = base;
+    for style in chain.iter().rev() {
+        format.bold = style.bold.unwrap_or(format.bold);
+        format.italic = style.italic.unwrap_or(format.italic);
+        format.underline = style.underline.unwrap_or(format.underline);
+    }
+    format
+}
+
+/// Body elements whose text is not part of the template.
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "text:note",
+    "office:annotation",
+    "text:tracked-changes",
+    "text:sequence-decls",
+    "office:forms",
+];
+
+#[derive(Default)]
+struct TableState {
+    rows: Vec<Vec<String>>,
+    row: Option<Vec<String>>,
+    cell: Option<Vec<RtfParagraph>>,
+}
+
+struct Interpreter<'s> {
+    styles: &'s HashMap<String, StyleInfo>,
+    blocks: Vec<ImportedBlock>,
+    current: RtfParagraph,
+    stack: Vec<Format>,
+    in_paragraph: usize,
+    table: Option<TableState>,
+    table_depth: usize,
+    pending_space: bool,
+}
+
+impl<'s> Interpreter<'s> {
+    fn new(styles: &'s HashMap<String, StyleInfo>) -> Self {
+        Interpreter {
+            styles,
+            blocks: Vec::new(),
+            current: RtfParagraph::default(),
+            stack: Vec::new(),
+            in_paragraph: 0,
+            table: None,
+            table_depth: 0,
+            pending_space: false,
+        }
+    }
+
+    fn format(&self) -> Format {
+        self.stack.last().copied().unwrap_or_default()
+    }
+
+    fn push_str(&mut self, text: &str) {
+        let format = self.format();
+        match self.current.spans.last_mut() {
+            Some(span)
+                if span.bold == format.bold
+                    && span.italic == format.italic
+                    && span.underline == format.underline =>
+            {
+                span.text.push_str(text)
+            }
+            _ => self.current.spans.push(Span {
+                text: text.to_string(),
+                bold: format.bold,
+                italic: format.italic,
+                underline: format.underline,
+                link: None,
+                image: None,
+                note: None,
+            }),
+        }
+    }
+
+    /// ODF collapses whitespace like HTML; `text:s` carries literal spaces.
+    fn text(&mut self, raw: &str) {
+        if self.in_paragraph == 0 || self.format().skip {
+            return;
+        }
+        for c in raw.chars() {
+            if c.is_whitespace() {
+                self.pending_space = true;
+                continue;
+            }
+            if self.pending_space && !self.current.spans.is_empty() {
+                self.push_str(" ");
+            }
+            self.pending_space = false;
+            self.push_str(c.encode_utf8(&mut [0; 4]));
+        }
+    }
+
+    fn end_paragraph(&mut self) {
+        self.pending_space = false;
+        let paragraph = std::mem::take(&mut self.current);
+        match self.table.as_mut() {
+            Some(table) => {
+                if let Some(cell) = table.cell.as_mut() {
+                    cell.push(paragraph);
+                }
+            }
+            None => self.blocks.push(ImportedBlock::Paragraph(paragraph)),
+        }
+    }
+
+    fn start(&mut self, name: &str, attrs: &[(String, String)]) {
+        let mut format = self.format();
+        if SKIPPED_ELEMENTS.contains(&name) {
+            format.skip = true;
+        }
+        if let Some(style) = attr(attrs, "text:style-name") {
+            format = apply_style(self.styles, style, format);
+        }
+
+        match name {
+            "text:p" | "text:h" => {
+                // A heading is bold unless its style says otherwise.
+                if name == "text:h" && attr(attrs, "text:style-name").is_none() {
+                    format.bold = true;
+                }
+                self.in_paragraph += 1;
+            }
+            "text:s" => {
+                let count = attr(attrs, "text:c")
+                    .and_then(|c| c.parse().ok())
+                    .unwrap_or(1usize);
+                if self.in_paragraph > 0 && !self.format().skip {
+                    self.pending_space = false;
+                    self.push_str(&" ".repeat(count.min(64)));
+                }
+            }
+            "text:tab" => self.text(" "),
+            "text:line-break" if self.in_paragraph > 0 => self.end_paragraph(),
+            "table:table" => {
+                self.table_depth += 1;
+                if self.table_depth == 1 {
+                    self.table = Some(TableState::default());
+                }
+            }
+            "table:table-row" if self.table_depth == 1 => {
+                if let Some(table) = self.table.as_mut() {
+                    table.row = Some(Vec::new());
+                }
+            }
+            "table:table-cell" | "table:covered-table-cell" if self.table_depth == 1 => {
+                if let Some(table) = self.table.as_mut() {
+                    table.cell = Some(Vec::new());
+                }
+            }
+            _ => {}
+        }
+        self.stack.push(format);
+    }
+
+    fn end(&mut self, name: &str, repeat: usize) {
+        self.stack.pop();
+        match name {
+            "text:p" | "text:h" => {
+                self.in_paragraph = self.in_paragraph.saturating_sub(1);
+                if self.in_paragraph == 0 {
+                    self.end_paragraph();
+                }
+            }
+            "table:table-cell" | "table:covered-table-cell" if self.table_depth == 1 => {
+                if let Some(table) = self.table.as_mut() {
+                    let text = table
+                        .cell
+                        .take()
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|p| p.to_markdown())
+                        .filter(|t| !t.is_empty())
+                        .collect::<Vec<_>>()
+                        .join("<br>");
+                    let row = table.row.get_or_insert_with(Vec::new);
+                    for _ in 0..repeat {
+                        row.push(text.clone());
+                    }
+                }
+            }
+            "table:table-row" if self.table_depth == 1 => {
+                if let Some(table) = self.table.as_mut() {
+                    if let Some(mut row) = table.row.take() {
+                        // Writers pad rows with repeated empty cells.
+                        while row.last().is_some_and(|c| c.is_empty()) {
+                            row.pop();
+                        }
+                        table.rows.push(row);
+                    }
+                }
+            }
+            "table:table" if self.table_depth > 0 => {
+                self.table_depth -= 1;
+                if self.table_depth == 0 {
+                    if let Some(table) = self.table.take() {
+                        if !table.rows.is_empty() {
+                            self.blocks.push(ImportedBlock::Table(table.rows));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn run(mut self, events: &[Event]) -> Vec<ImportedBlock> {
+        // Cell repeat counts, matched to their end events.
+        let mut repeats = Vec::new();
+        let mut in_body = false;
+        for event in events {
+            match event {
+                Event::Start { name, .. } if name == "office:text" => in_body = true,
+                Event::End(name) if name == "office:text" => in_body = false,
+                Event::Start { name, attrs } if in_body => {
+                    let repeat = attr(attrs, "table:number-columns-repeated")
+                        .and_then(|n| n.parse().ok())
+                        .unwrap_or(1usize)
+                        .clamp(1, 64);
+                    repeats.push(repeat);
+                    self.start(name, attrs);
+                }
+                Event::End(name) if in_body => {
+                    let repeat = repeats.pop().unwrap_or(1);
+                    self.end(name, repeat);
+                }
+                Event::Text(text) if in_body => self.text(text),
+                _ => {}
+            }
+        }
+        self.blocks
+    }
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<File>, name: &str) -> Result<Option<String>> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut xml = String::new();
+    entry.read_to_string(&mut xml)?;
+    Ok(Some(xml))
+}
+
+/// Parses an `.odt` file into formatted paragraphs and tables.
+pub fn parse(path: &Path) -> Result<Vec<ImportedBlock>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .with_context(|| format!("Not an ODT package: {}", path.display()))?;
+    let content = read_entry(&mut archive, "content.xml")?
+        .with_context(|| format!("content.xml missing in {}", path.display()))?;
+    let content = events(&content)?;
+
+    let mut styles = HashMap::new();
+    if let Some(shared) = read_entry(&mut archive, "styles.xml")? {
+        read_styles(&events(&shared)?, &mut styles);
+    }
+    read_styles(&content, &mut styles);
+
+    Ok(Interpreter::new(&styles).run(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert_to_odt::write_odt;
+    use crate::template::ReportTemplate;
+
+    #[test]
+    fn written_odt_reads_back_with_its_formatting() {
+        let markdown = "# TC DE CRÂNIO\n\n**Técnica:** cortes *axiais* de __5 mm__.\n\n| Lesão | mm |\n|---|---|\n| a | 12 |\n\nRodapé";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("TC.odt");
+        write_odt(&ReportTemplate::from_markdown(markdown), &path).unwrap();
+
+        let blocks: Vec<String> = parse(&path)
+            .unwrap()
+            .iter()
+            .map(|block| match block {
+                ImportedBlock::Paragraph(paragraph) => paragraph.to_markdown(),
+                ImportedBlock::Table(rows) => format!("{rows:?}"),
+            })
+            .filter(|text| !text.is_empty())
+            .collect();
+        assert_eq!(
+            blocks,
+            [
+                "**TC DE CRÂNIO**",
+                "**Técnica:** cortes *axiais* de __5 mm__.",
+                r#"[["**Lesão**", "**mm**"], ["a", "12"]]"#,
+                "*Rodapé*",
+            ]
+        );
+    }
+}
@@ -0,0 +1,719 @@
+//! A curated set of templates as one zip (`rtemplates export-pack`), for
+//! sharing part of the library with another hospital without handing over
+//! the whole of it, and the import of such a pack (`rtemplates
+//! import-pack`).
+//!
+//! The templates are selected from `reports_index.json` as `query` selects
+//! them (modality, body region, tags, keyword, file names). A template is
+//! packed in every format the index lists it in as soon as one of its files
+//! matches, so a tag, which only the Markdown carries, brings the DOCX and
+//! TXT along; the aliases of a packed template come with it, and so do the
+//! images its Markdown shows. Templates imported from other packs are not
+//! exported again.
+//!
+//! Each file keeps its path under the root. Beside them the pack holds a
+//! `reports_index.json` restricted to the packed files, and a
+//! `manifest.json` recording the selection, when and by what the pack was
+//! made, and the size and checksum of each file. Files changed since the
+//! index was built are refused, so that the packed index describes them.
+//!
+//! An imported pack goes to its own namespace, `external/<namespace>/`,
+//! with the same template folders as the root (mapped by extension when
+//! the other library names its folders differently) and the pack's
+//! manifest, so it never overwrites the library's own templates. Its index
+//! entries are merged into the root index, where `query` finds them, and
+//! `rtemplates index` keeps indexing them. A file already imported with
+//! other contents is only replaced when asked to; template names the
+//! library or another namespace also uses are reported, as looking them up
+//! by name becomes ambiguous.
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::aliases;
+use crate::cache::sha256_file;
+use crate::encoding::read_text;
+use crate::files::{list_files, slash_path};
+use crate::front_matter;
+use crate::generate_index::{
+    collect_files, read_index, to_json_pretty, write_index, write_json_pretty, Index,
+};
+use crate::markdown::parse_inline;
+use crate::pick::fold;
+use crate::query::TemplateQuery;
+use crate::targets;
+use crate::timestamp::Timestamp;
+use crate::{EXTERNAL_DIR, INDEX_FILE};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// The conditions the templates were selected by; those not set are left
+/// out.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Selection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modality: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_region: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl From<&TemplateQuery> for Selection {
+    fn from(query: &TemplateQuery) -> Self {
+        Selection {
+            modality: query.modality.clone(),
+            body_region: query.body_region.clone(),
+            keyword: query.keyword.clone(),
+            tags: query.tags.clone(),
+            include: query.files.include.clone(),
+            exclude: query.files.exclude.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PackedFile {
+    /// The path in the pack, which is the path under the root.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    /// `rtemplates` and its version.
+    pub generator: String,
+    /// UTC, `YYYY-MM-DDTHH:MM:SSZ`.
+    pub created: String,
+    pub selection: Selection,
+    /// The packed templates by name (`US ABDOME`), aliases included.
+    pub templates: Vec<String>,
+    /// The templates and images, not the index or the manifest.
+    pub files: Vec<PackedFile>,
+}
+
+/// The template an indexed path is a file of: the path within its folder,
+/// without the extension (`US ABDOME` for `Templates_docx/US ABDOME.docx`).
+fn template_name(path: &str) -> String {
+    let within = path.split_once('/').map_or(path, |(_, rest)| rest);
+    match within.rsplit_once('.') {
+        Some((name, _)) if !name.ends_with('/') => name.to_string(),
+        _ => within.to_string(),
+    }
+}
+
+/// The images the Markdown template `path` shows that are files next to
+/// it, as paths under `root`.
+fn images(root: &Path, path: &str) -> Result<Vec<String>> {
+    let file = root.join(path);
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let content = read_text(&file)?;
+    let mut found = Vec::new();
+    for line in front_matter::strip(&content).lines() {
+        for run in parse_inline(line, false) {
+            let Some(image) = run.image else { continue };
+            if image.source.contains("://") || Path::new(&image.source).is_absolute() {
+                continue;
+            }
+            let image = slash_path(&dir.join(&image.source));
+            if root.join(&image).is_file() && !found.contains(&image) {
+                found.push(image);
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// The index of the templates `query` selects from `index`, as it goes in
+/// the pack, and the images their Markdown shows.
+pub fn select(root: &Path, index: &Index, query: &TemplateQuery) -> Result<(Index, Vec<String>)> {
+    let external = format!("{}/", EXTERNAL_DIR);
+    let mut names: BTreeSet<String> = query
+        .run(root, index)?
+        .into_iter()
+        .filter(|(path, _)| !path.starts_with(&external))
+        .map(|(path, _)| template_name(path))
+        .collect();
+    for (alias, target) in &index.aliases {
+        let (alias, target) = (template_name(alias), template_name(target));
+        if names.contains(&alias) || names.contains(&target) {
+            names.insert(alias);
+            names.insert(target);
+        }
+    }
+    let packed = |path: &String| {
+        !path.starts_with(&external)
+            && names.contains(&template_name(path))
+            && query.files.matches(Path::new(path))
+    };
+
+    let mut selected = Index {
+        folders: index
+            .folders
+            .iter()
+            .map(|(folder, files)| {
+                let files = files.iter().filter(|path| packed(path)).cloned().collect();
+                (folder.clone(), files)
+            })
+            .collect(),
+        tags: index
+            .tags
+            .iter()
+            .map(|(tag, files)| {
+                let files: Vec<String> =
+                    files.iter().filter(|path| packed(path)).cloned().collect();
+                (tag.clone(), files)
+            })
+            .filter(|(_, files)| !files.is_empty())
+            .collect(),
+        aliases: index
+            .aliases
+            .iter()
+            .filter(|(alias, _)| names.contains(&template_name(alias)))
+            .map(|(alias, target)| (alias.clone(), target.clone()))
+            .collect(),
+        files: index
+            .files
+            .iter()
+            .filter(|(path, _)| packed(path))
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect(),
+    };
+    selected.folders.retain(|_, files| !files.is_empty());
+
+    let mut assets = Vec::new();
+    for path in selected.files.keys().filter(|path| path.ends_with(".md")) {
+        for image in images(root, path)? {
+            if !assets.contains(&image) {
+                assets.push(image);
+            }
+        }
+    }
+    assets.sort();
+    Ok((selected, assets))
+}
+
+/// Writes the pack of the templates under `root` that `query` selects to
+/// `output`, and returns its manifest.
+pub fn write_pack(root: &Path, query: &TemplateQuery, output: &Path) -> Result<Manifest> {
+    let index_path = root.join(INDEX_FILE);
+    if !index_path.exists() {
+        bail!(
+            "{} is missing; run `rtemplates index` first",
+            index_path.display()
+        );
+    }
+    let index = read_index(&index_path)?;
+    let (selected, assets) = select(root, &index, query)?;
+    if selected.files.is_empty() {
+        bail!("No template matches the selection");
+    }
+
+    let mut files = Vec::new();
+    for (path, entry) in &selected.files {
+        let file = root.join(path);
+        if !file.is_file() || sha256_file(&file)? != entry.sha256 {
+            bail!(
+                "{} changed since the index was built; run `rtemplates index` first",
+                file.display()
+            );
+        }
+        files.push(PackedFile {
+            path: path.clone(),
+            size: entry.size,
+            sha256: entry.sha256.clone(),
+        });
+    }
+    for path in &assets {
+        let file = root.join(path);
+        files.push(PackedFile {
+            path: path.clone(),
+            size: fs::metadata(&file)?.len(),
+            sha256: sha256_file(&file)?,
+        });
+    }
+    let templates: BTreeSet<String> = selected.files.keys().map(|p| template_name(p)).collect();
+    let manifest = Manifest {
+        generator: format!("rtemplates {}", env!("CARGO_PKG_VERSION")),
+        created: Timestamp::now().iso_datetime(),
+        selection: Selection::from(query),
+        templates: templates.into_iter().collect(),
+        files,
+    };
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(MANIFEST_FILE, deflated)?;
+    zip.write_all(&to_json_pretty(&manifest)?)?;
+    zip.start_file(INDEX_FILE, deflated)?;
+    zip.write_all(&to_json_pretty(&selected)?)?;
+    for file in &manifest.files {
+        zip.start_file(file.path.as_str(), deflated)?;
+        zip.write_all(&fs::read(root.join(&file.path))?)?;
+    }
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, zip.finish()?.into_inner())
+        .with_context(|| format!("Cannot write {}", output.display()))?;
+    Ok(manifest)
+}
+
+/// The template files imported into every namespace under `root`, as
+/// paths under it, for the index.
+pub fn imported_files(root: &Path) -> Result<Vec<String>> {
+    let external = root.join(EXTERNAL_DIR);
+    if !external.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut namespaces: Vec<PathBuf> = fs::read_dir(&external)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    namespaces.sort();
+    let mut files = Vec::new();
+    for namespace in namespaces {
+        for (folder, ext) in targets() {
+            let dir = namespace.join(folder);
+            if dir.is_dir() {
+                for file in list_files(&dir, ext)? {
+                    files.push(slash_path(file.strip_prefix(root).unwrap_or(&file)));
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[derive(Debug, Default)]
+pub struct ImportOptions {
+    /// The folder under `external/` to import into; the pack's file name
+    /// when not set.
+    pub namespace: Option<String>,
+    /// Replace the files imported earlier that the pack changes.
+    pub overwrite: bool,
+    /// Only report what importing would do.
+    pub dry_run: bool,
+}
+
+/// A template name of the pack that is also a template elsewhere.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Collision {
+    pub name: String,
+    /// `the library`, or the other namespace's folder (`external/hospital_y`).
+    pub with: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub namespace: String,
+    /// The files written (or to be written), as paths under the root.
+    pub written: Vec<String>,
+    /// The files already imported with the same contents.
+    pub unchanged: Vec<String>,
+    pub collisions: Vec<Collision>,
+}
+
+fn read_entry(archive: &mut ZipArchive<fs::File>, name: &str) -> Result<Vec<u8>> {
+    let mut entry = archive
+        .by_name(name)
+        .with_context(|| format!("The pack has no {}", name))?;
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Whether `path` stays inside the folder it is extracted to.
+fn is_safe(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// The local folder of each folder in the pack, by the extension of the
+/// templates in it.
+fn local_folders(manifest: &Manifest) -> BTreeMap<String, String> {
+    let mut folders = BTreeMap::new();
+    for file in &manifest.files {
+        let Some((folder, _)) = file.path.split_once('/') else {
+            continue;
+        };
+        let ext = Path::new(&file.path).extension().and_then(|s| s.to_str());
+        for (local, local_ext) in targets() {
+            if ext == Some(local_ext) {
+                folders.insert(folder.to_string(), local.to_string());
+            }
+        }
+    }
+    folders
+}
+
+/// `path` of the pack under the local folder its folder maps to.
+fn local_path(folders: &BTreeMap<String, String>, path: &str) -> Option<String> {
+    let (folder, rest) = path.split_once('/')?;
+    Some(format!("{}/{}", folders.get(folder)?, rest))
+}
+
+fn stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The names of `imported` that the library under `root`, or a namespace
+/// other than `namespace`, also has a template of.
+fn collisions(root: &Path, namespace: &str, imported: &[String]) -> Result<Vec<Collision>> {
+    let markdown = &crate::config::get().directories.markdown;
+    let mut library: BTreeSet<String> = collect_files(root)?
+        .values()
+        .flatten()
+        .map(|file| stem(file))
+        .collect();
+    library.extend(aliases::in_folder(&root.join(markdown))?.into_keys());
+    let mut others: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for file in imported_files(root)? {
+        let mut parts = file.splitn(3, '/');
+        let other = parts.nth(1).unwrap_or_default();
+        if other != namespace {
+            let with = format!("{}/{}", EXTERNAL_DIR, other);
+            others.entry(stem(&file)).or_default().insert(with);
+        }
+    }
+
+    let mut found = BTreeSet::new();
+    for file in imported {
+        let is_template = targets()
+            .iter()
+            .any(|(_, ext)| Path::new(file).extension().is_some_and(|e| e == *ext));
+        if !is_template {
+            continue;
+        }
+        let name = stem(file);
+        if library.contains(&name) {
+            found.insert(Collision {
+                name: name.clone(),
+                with: "the library".to_string(),
+            });
+        }
+        for with in others.get(&name).into_iter().flatten() {
+            found.insert(Collision {
+                name: name.clone(),
+                with: with.clone(),
+            });
+        }
+    }
+    Ok(found.into_iter().collect())
+}
+
+/// Replaces the entries of the namespace under `prefix` in the root index
+/// with those of the pack, or indexes the whole root when it has no index
+/// yet.
+fn merge_index(
+    root: &Path,
+    prefix: &str,
+    packed: &Index,
+    folders: &BTreeMap<String, String>,
+) -> Result<()> {
+    let index_path = root.join(INDEX_FILE);
+    if !index_path.exists() {
+        return write_index(root, &index_path);
+    }
+    let mut index = read_index(&index_path)?;
+    let key = |path: &String| local_path(folders, path).map(|path| format!("{}{}", prefix, path));
+
+    index.files.retain(|path, _| !path.starts_with(prefix));
+    for (path, entry) in &packed.files {
+        if let Some(key) = key(path) {
+            index.files.insert(key, entry.clone());
+        }
+    }
+    for files in index.tags.values_mut() {
+        files.retain(|path| !path.starts_with(prefix));
+    }
+    for (tag, files) in &packed.tags {
+        let folded: String = tag.chars().map(fold).collect();
+        let spelling = index
+            .tags
+            .keys()
+            .find(|known| known.chars().map(fold).collect::<String>() == folded)
+            .cloned()
+            .unwrap_or_else(|| tag.clone());
+        let listed = index.tags.entry(spelling).or_default();
+        for file in files.iter().filter_map(key) {
+            if !listed.contains(&file) {
+                listed.push(file);
+            }
+        }
+    }
+    index.tags.retain(|_, files| !files.is_empty());
+    index.aliases.retain(|alias, _| !alias.starts_with(prefix));
+    for (alias, target) in &packed.aliases {
+        if let (Some(alias), Some(target)) = (key(alias), key(target)) {
+            index.aliases.insert(alias, target);
+        }
+    }
+    write_json_pretty(&index, &index_path)
+}
+
+/// Imports the pack at `pack_path` into its namespace under `root`, and
+/// merges its index into the root index.
+pub fn import_pack(root: &Path, pack_path: &Path, options: &ImportOptions) -> Result<ImportReport> {
+    let namespace = options.namespace.clone().unwrap_or_else(|| {
+        let name = pack_path.file_stem().unwrap_or_default();
+        name.to_string_lossy().to_string()
+    });
+    if !is_safe(&namespace) || namespace.contains('/') || namespace.starts_with('.') {
+        bail!(
+            "Invalid namespace {:?}; expected a folder name such as hospital_x",
+            namespace
+        );
+    }
+    let file = fs::File::open(pack_path)
+        .with_context(|| format!("Cannot open {}", pack_path.display()))?;
+    let mut archive = ZipArchive::new(file)
+        .with_context(|| format!("{} is not a template pack", pack_path.display()))?;
+    let manifest_data = read_entry(&mut archive, MANIFEST_FILE)?;
+    let manifest: Manifest =
+        serde_json::from_slice(&manifest_data).context("Invalid pack manifest")?;
+    let packed: Index = serde_json::from_slice(&read_entry(&mut archive, INDEX_FILE)?)
+        .context("Invalid pack index")?;
+    let folders = local_folders(&manifest);
+    let prefix = format!("{}/{}/", EXTERNAL_DIR, namespace);
+
+    let mut files = Vec::new();
+    for file in &manifest.files {
+        if !is_safe(&file.path) {
+            bail!("The pack holds a file outside its folders: {}", file.path);
+        }
+        let Some(local) = local_path(&folders, &file.path) else {
+            warn!("Skipping {}: not in a template folder", file.path);
+            continue;
+        };
+        let data = read_entry(&mut archive, &file.path)?;
+        if format!("{:x}", Sha256::digest(&data)) != file.sha256 {
+            bail!(
+                "{} does not match the checksum in the pack manifest",
+                file.path
+            );
+        }
+        files.push((format!("{}{}", prefix, local), data));
+    }
+
+    let mut report = ImportReport {
+        namespace: namespace.clone(),
+        ..ImportReport::default()
+    };
+    let mut changed = Vec::new();
+    for (path, data) in &files {
+        let target = root.join(path);
+        if target.exists() {
+            if fs::read(&target)? == *data {
+                report.unchanged.push(path.clone());
+                continue;
+            }
+            changed.push(path.as_str());
+        }
+        report.written.push(path.clone());
+    }
+    if !changed.is_empty() && !options.overwrite {
+        bail!(
+            "Already imported with other contents: {}; pass --overwrite to replace them",
+            changed.join(", ")
+        );
+    }
+    let imported: Vec<String> = files.iter().map(|(path, _)| path.clone()).collect();
+    report.collisions = collisions(root, &namespace, &imported)?;
+    if options.dry_run {
+        return Ok(report);
+    }
+
+    for (path, data) in &files {
+        if report.written.contains(path) {
+            let target = root.join(path);
+            fs::create_dir_all(target.parent().unwrap())?;
+            fs::write(&target, data)
+                .with_context(|| format!("Cannot write {}", target.display()))?;
+        }
+    }
+    let base = root.join(EXTERNAL_DIR).join(&namespace);
+    fs::create_dir_all(&base)?;
+    fs::write(base.join(MANIFEST_FILE), manifest_data)?;
+    merge_index(root, &prefix, &packed, &folders)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_index::write_index;
+    use std::io::Read;
+
+    #[test]
+    fn a_tag_packs_its_templates_in_every_format_with_their_index() {
+        let root = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = root.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write(
+            "Templates_markdown/US ABDOME.md",
+            "---\ntags: [abdome]\naliases: [US ABDOME TOTAL]\n---\n![Logo](assets/logo.png)\n\n\
+             **US ABDOME**\n\nFígado normal.\n",
+        );
+        write("Templates_markdown/assets/logo.png", "logo");
+        write("Templates_markdown/TC CRÂNIO.md", "**TC**\n");
+        write("Templates_txt/US ABDOME.txt", "US ABDOME\n");
+        write("Templates_txt/US ABDOME TOTAL.txt", "US ABDOME\n");
+        write("Templates_txt/TC CRÂNIO.txt", "TC\n");
+        let index_path = root.path().join(INDEX_FILE);
+        write_index(root.path(), &index_path).unwrap();
+
+        let query = TemplateQuery {
+            tags: vec!["Abdome".to_string()],
+            ..TemplateQuery::default()
+        };
+        let output = root.path().join("pack/abdome.zip");
+        let manifest = write_pack(root.path(), &query, &output).unwrap();
+        assert_eq!(manifest.templates, ["US ABDOME", "US ABDOME TOTAL"]);
+        let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "Templates_markdown/US ABDOME.md",
+                "Templates_txt/US ABDOME TOTAL.txt",
+                "Templates_txt/US ABDOME.txt",
+                "Templates_markdown/assets/logo.png",
+            ]
+        );
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+        let mut packed = String::new();
+        archive
+            .by_name(INDEX_FILE)
+            .unwrap()
+            .read_to_string(&mut packed)
+            .unwrap();
+        let packed: Index = serde_json::from_str(&packed).unwrap();
+        assert_eq!(packed.files.len(), 3);
+        assert_eq!(packed.tags["abdome"], ["Templates_markdown/US ABDOME.md"]);
+        assert!(archive
+            .by_name("Templates_markdown/assets/logo.png")
+            .is_ok());
+        assert!(archive.by_name(MANIFEST_FILE).is_ok());
+
+        write("Templates_txt/US ABDOME.txt", "Editado.\n");
+        assert!(write_pack(root.path(), &query, &output).is_err());
+        let nothing = TemplateQuery {
+            modality: Some("MG".to_string()),
+            ..TemplateQuery::default()
+        };
+        assert!(write_pack(root.path(), &nothing, &output).is_err());
+    }
+
+    #[test]
+    fn an_imported_pack_is_namespaced_indexed_and_checked_for_collisions() {
+        let (theirs, ours) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let write = |root: &Path, path: &str, content: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write(
+            theirs.path(),
+            "Templates_markdown/US ABDOME.md",
+            "---\ntags: [Oncologia]\n---\n**US ABDOME**\n\nFígado normal.\n",
+        );
+        write(theirs.path(), "Templates_txt/US ABDOME.txt", "US ABDOME\n");
+        write_index(theirs.path(), &theirs.path().join(INDEX_FILE)).unwrap();
+        let pack = theirs.path().join("hospital_x.zip");
+        write_pack(theirs.path(), &TemplateQuery::default(), &pack).unwrap();
+
+        write(
+            ours.path(),
+            "Templates_markdown/US ABDOME.md",
+            "---\ntags: [oncologia]\n---\n**US DO ABDOME**\n",
+        );
+        let index_path = ours.path().join(INDEX_FILE);
+        write_index(ours.path(), &index_path).unwrap();
+
+        let report = import_pack(ours.path(), &pack, &ImportOptions::default()).unwrap();
+        assert_eq!(report.namespace, "hospital_x");
+        assert_eq!(
+            report.written,
+            [
+                "external/hospital_x/Templates_markdown/US ABDOME.md",
+                "external/hospital_x/Templates_txt/US ABDOME.txt",
+            ]
+        );
+        assert_eq!(
+            report.collisions,
+            [Collision {
+                name: "US ABDOME".to_string(),
+                with: "the library".to_string(),
+            }]
+        );
+        let imported = ours.path().join("external/hospital_x");
+        assert!(imported.join(MANIFEST_FILE).exists());
+        assert_eq!(
+            read_text(&ours.path().join("Templates_markdown/US ABDOME.md")).unwrap(),
+            "---\ntags: [oncologia]\n---\n**US DO ABDOME**\n"
+        );
+        let index = read_index(&index_path).unwrap();
+        assert!(index
+            .files
+            .contains_key("external/hospital_x/Templates_txt/US ABDOME.txt"));
+        assert_eq!(
+            index.tags["oncologia"],
+            [
+                "Templates_markdown/US ABDOME.md",
+                "external/hospital_x/Templates_markdown/US ABDOME.md",
+            ]
+        );
+
+        // Reindexing keeps the pack; importing it again changes nothing.
+        write_index(ours.path(), &index_path).unwrap();
+        let reindexed = read_index(&index_path).unwrap();
+        assert_eq!(reindexed.files.len(), 3);
+        assert_eq!(reindexed.tags, index.tags);
+        let again = import_pack(ours.path(), &pack, &ImportOptions::default()).unwrap();
+        assert!(again.written.is_empty() && again.unchanged.len() == 2);
+
+        write(&imported, "Templates_txt/US ABDOME.txt", "Editado.\n");
+        assert!(import_pack(ours.path(), &pack, &ImportOptions::default()).is_err());
+        let overwrite = ImportOptions {
+            overwrite: true,
+            ..ImportOptions::default()
+        };
+        import_pack(ours.path(), &pack, &overwrite).unwrap();
+        assert_eq!(
+            read_text(&imported.join("Templates_txt/US ABDOME.txt")).unwrap(),
+            "US ABDOME\n"
+        );
+        let bad = ImportOptions {
+            namespace: Some("../x".to_string()),
+            ..ImportOptions::default()
+        };
+        assert!(import_pack(ours.path(), &pack, &bad).is_err());
+    }
+}
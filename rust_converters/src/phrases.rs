@@ -0,0 +1,192 @@
+//! The phrase library (`phrases/`): normal-finding sentences written once
+//! and included in templates by ID, so wording such as "Fígado de dimensões
+//! e contornos normais" is changed in one place.
+//!
+//! Each `.md` file in the folder is a group of phrases named after it
+//! (`abdome.md`), one per line as `id: sentence`; blank lines and lines
+//! starting with `#` are skipped:
+//!
+//! ```text
+//! # Fígado
+//! figado-normal: Fígado de dimensões e contornos normais, com superfície lisa.
+//! ```
+//!
+//! `{{phrase figado-normal}}` in a template is replaced by the sentence when
+//! it is filled (see `fill`), in every format. IDs are unique across the
+//! library.
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+use crate::encoding::read_text;
+use crate::files::{display_name, list_files};
+use crate::front_matter;
+use crate::markdown::normalize_heading;
+use crate::pick::fold;
+use crate::template::{plain, section_heading, ReportTemplate, SectionKind};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Phrase {
+    pub id: String,
+    /// The stem of the file it is written in.
+    pub group: String,
+    /// The sentence, as inline Markdown.
+    pub text: String,
+}
+
+/// Every phrase of a library folder, by group and then as written.
+#[derive(Clone, Debug, Default)]
+pub struct PhraseLibrary {
+    pub phrases: Vec<Phrase>,
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+impl PhraseLibrary {
+    /// Reads the phrase files in `dir`; without the folder the library is
+    /// empty.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut library = PhraseLibrary::default();
+        if !dir.is_dir() {
+            return Ok(library);
+        }
+        for path in list_files(dir, "md")? {
+            let group = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            for (index, line) in read_text(&path)?.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let at = || format!("{} line {}", display_name(&path), index + 1);
+                let Some((id, text)) = line.split_once(':') else {
+                    bail!("{}: expected `id: sentence`", at());
+                };
+                let (id, text) = (id.trim(), text.trim());
+                if id.is_empty() || !id.chars().all(is_id_char) {
+                    bail!("{}: `{}` is not a phrase ID", at(), id);
+                }
+                if let Some(other) = library.get(id) {
+                    bail!("{}: phrase `{}` is already in {}", at(), id, other.group);
+                }
+                library.phrases.push(Phrase {
+                    id: id.to_string(),
+                    group: group.clone(),
+                    text: text.to_string(),
+                });
+            }
+        }
+        Ok(library)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Phrase> {
+        self.phrases.iter().find(|phrase| phrase.id == id)
+    }
+
+    /// The phrases whose ID, group or text holds every word of `query`,
+    /// ignoring case and accents; every phrase for an empty query.
+    pub fn search(&self, query: &str) -> Vec<&Phrase> {
+        let folded = |text: &str| text.chars().map(fold).collect::<String>();
+        let words: Vec<String> = query.split_whitespace().map(folded).collect();
+        self.phrases
+            .iter()
+            .filter(|phrase| {
+                let haystack = folded(&format!("{} {} {}", phrase.id, phrase.group, phrase.text));
+                words.iter().all(|word| haystack.contains(word.as_str()))
+            })
+            .collect()
+    }
+}
+
+/// `content` with `{{phrase id}}` added as a line of its own after the last
+/// one of its `kind` section, before the next section heading or the footer.
+pub fn insert(content: &str, id: &str, kind: SectionKind) -> Result<String> {
+    let template = ReportTemplate::from_markdown(content);
+    let body = front_matter::strip(content);
+    let start = content.len() - body.len();
+    let lines: Vec<&str> = body.split_inclusive('\n').collect();
+    let heading = |line: &str| {
+        section_heading(&plain(&normalize_heading(line).0), template.language).map(|(k, _)| k)
+    };
+
+    let first = lines
+        .iter()
+        .position(|line| heading(line) == Some(kind))
+        .with_context(|| format!("The template has no `{}` section", kind.label()))?;
+    let footer = (template.footer.is_some() && !template.footer_from_front_matter)
+        .then(|| lines.iter().rposition(|line| !line.trim().is_empty()))
+        .flatten()
+        .filter(|&footer| footer > first);
+    let end = (first + 1..lines.len())
+        .find(|&i| heading(lines[i]).is_some())
+        .or(footer)
+        .unwrap_or(lines.len());
+    let last = (first..end)
+        .rev()
+        .find(|&i| !lines[i].trim().is_empty())
+        .unwrap_or(first);
+
+    let mut out = content[..start].to_string();
+    out.extend(lines[..=last].iter().copied());
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&format!("{{{{phrase {}}}}}\n", id));
+    out.extend(lines[last + 1..].iter().copied());
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fill::{fill, FillOptions};
+    use std::fs;
+
+    #[test]
+    fn phrases_are_found_inserted_and_filled_in() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("abdome.md"),
+            "# Fígado\nfigado-normal: Fígado de dimensões e contornos normais.\n\n\
+             vesicula-normal: Vesícula biliar normodistendida.\n",
+        )
+        .unwrap();
+        let library = PhraseLibrary::load(dir.path()).unwrap();
+        assert_eq!(library.phrases.len(), 2);
+        let found: Vec<_> = library
+            .search("FIGADO contornos")
+            .iter()
+            .map(|p| &p.id)
+            .collect();
+        assert_eq!(found, ["figado-normal"]);
+        assert_eq!(library.search("abdome").len(), 2);
+
+        let template = "**US DE ABDOME**\n\n**Aspectos observados:**\nBaço normal.\n\
+                        **Impressão:**\nXXX\n\n*Rodapé.*\n";
+        let inserted = insert(template, "figado-normal", SectionKind::AspectosObservados).unwrap();
+        assert_eq!(
+            inserted,
+            "**US DE ABDOME**\n\n**Aspectos observados:**\nBaço normal.\n\
+             {{phrase figado-normal}}\n**Impressão:**\nXXX\n\n*Rodapé.*\n"
+        );
+        let at_end = insert(template, "vesicula-normal", SectionKind::Impressao).unwrap();
+        assert!(at_end.ends_with("XXX\n{{phrase vesicula-normal}}\n\n*Rodapé.*\n"));
+        assert!(insert(template, "figado-normal", SectionKind::Tecnica).is_err());
+
+        let options = FillOptions {
+            phrases_dir: dir.path().to_path_buf(),
+            ..FillOptions::default()
+        };
+        let filled = fill(&inserted, &options).unwrap();
+        assert!(filled.contains("Baço normal.\nFígado de dimensões e contornos normais.\n**"));
+        assert!(fill("{{phrase baco-normal}}", &options).is_err());
+
+        fs::write(dir.path().join("torax.md"), "figado-normal: Outra.\n").unwrap();
+        assert!(PhraseLibrary::load(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! Interactive fuzzy finder over the template titles.
+//!
+//! Typing narrows the list skim-style: the query's characters must appear in
+//! the title in order, ignoring case and accents (`tcabd` finds `TC Abdome`,
+//! `torax` finds `TÓRAX`), and tighter matches at word starts rank first.
+//! The list is drawn on the terminal (`/dev/tty`), so standard output stays
+//! free for the chosen template.
+
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+use crate::encoding::read_text;
+use crate::files::list_files;
+use crate::template::ReportTemplate;
+
+/// A template offered by the picker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub path: PathBuf,
+    /// The template title, or the file stem when it has none.
+    pub title: String,
+}
+
+/// The Markdown templates in `dir`, sorted by file name.
+pub fn candidates(dir: &Path) -> Result<Vec<Candidate>> {
+    let mut found = Vec::new();
+    for path in list_files(dir, "md")? {
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let title = read_text(&path)
+            .ok()
+            .and_then(|content| ReportTemplate::from_markdown(&content).title)
+            .map(|title| title.plain().trim().to_string())
+            .filter(|title| !title.is_empty());
+        let title = match title {
+            Some(title) if title.to_lowercase() != stem.to_lowercase() => {
+                format!("{} ({})", title, stem)
+            }
+            _ => stem,
+        };
+        found.push(Candidate { path, title });
+    }
+    Ok(found)
+}
+
+/// Lowercase `c` without its accent.
+pub(crate) fn fold(c: char) -> char {
+    let c = c.to_lowercase().next().unwrap_or(c);
+    match c {
+        'á' | 'à' | 'â' | 'ã' | 'ä' => 'a',
+        'é' | 'è' | 'ê' | 'ë' => 'e',
+        'í' | 'ì' | 'î' | 'ï' => 'i',
+        'ó' | 'ò' | 'ô' | 'õ' | 'ö' => 'o',
+        'ú' | 'ù' | 'û' | 'ü' => 'u',
+        'ç' => 'c',
+        'ñ' => 'n',
+        _ => c,
+    }
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_WORD_START: i64 = 8;
+const PENALTY_GAP: i64 = 1;
+
+/// Scores `text` against `query`, returning the score and the char
+/// positions matched; `None` when the query is not a subsequence of the
+/// text. Spaces in the query are ignored. The first matching window is
+/// found left to right and then tightened from its end, as fzf does.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(fold)
+        .collect();
+    let chars: Vec<char> = text.chars().collect();
+    let folded: Vec<char> = chars.iter().map(|&c| fold(c)).collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let mut next = 0;
+    let mut end = None;
+    for (i, &c) in folded.iter().enumerate() {
+        if c == query[next] {
+            next += 1;
+            if next == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut wanted = query.len();
+    for i in (0..=end).rev() {
+        if folded[i] == query[wanted - 1] {
+            positions.push(i);
+            wanted -= 1;
+            if wanted == 0 {
+                break;
+            }
+        }
+    }
+    positions.reverse();
+
+    let mut score = 0;
+    for (n, &i) in positions.iter().enumerate() {
+        score += SCORE_MATCH;
+        if i == 0 || !chars[i - 1].is_alphanumeric() {
+            score += BONUS_WORD_START;
+        }
+        if n > 0 {
+            let gap = (i - positions[n - 1] - 1) as i64;
+            if gap == 0 {
+                score += BONUS_CONSECUTIVE;
+            }
+            score -= gap * PENALTY_GAP;
+        }
+    }
+    Some((score, positions))
+}
+
+/// Indices of the candidates matching `query` with their matched positions,
+/// best first; ties keep file order.
+pub fn filter(candidates: &[Candidate], query: &str) -> Vec<(usize, Vec<usize>)> {
+    let mut matches: Vec<(i64, usize, Vec<usize>)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, candidate)| {
+            fuzzy_match(query, &candidate.title).map(|(score, positions)| (score, index, positions))
+        })
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    matches
+        .into_iter()
+        .map(|(_, index, positions)| (index, positions))
+        .collect()
+}
+
+/// Lets the user choose one of the templates in `dir` on the terminal.
+/// Returns `None` when the picker is closed with Esc or Ctrl-C.
+pub fn pick(dir: &Path) -> Result<Option<PathBuf>> {
+    let candidates = candidates(dir)?;
+    if candidates.is_empty() {
+        bail!("No .md files found in {}", dir.display());
+    }
+    let chosen = terminal::run(&candidates)?;
+    Ok(chosen.map(|index| candidates[index].path.clone()))
+}
+
+#[cfg(not(unix))]
+mod terminal {
+    use super::Candidate;
+    use anyhow::{bail, Result};
+
+    pub fn run(_candidates: &[Candidate]) -> Result<Option<usize>> {
+        bail!("The interactive picker needs a Unix terminal")
+    }
+}
+
+#[cfg(unix)]
+mod terminal {
+    use super::{filter, Candidate};
+    use crate::terminal::{Key, RawTerminal, RESET};
+    use anyhow::Result;
+
+    fn draw(
+        terminal: &mut RawTerminal,
+        candidates: &[Candidate],
+        query: &str,
+        matches: &[(usize, Vec<usize>)],
+        selected: usize,
+    ) -> Result<()> {
+        let visible = terminal.rows() - 2;
+        let first = selected.saturating_sub(visible - 1);
+        let mut screen = String::from("\x1b[H\x1b[J");
+        for (row, (index, positions)) in matches.iter().enumerate().skip(first).take(visible) {
+            let marker = if row == selected { "\x1b[7m>" } else { " " };
+            screen.push_str(marker);
+            screen.push(' ');
+            for (i, c) in candidates[*index].title.chars().enumerate() {
+                if positions.contains(&i) {
+                    screen.push_str("\x1b[1;31m");
+                    screen.push(c);
+                    screen.push_str(RESET);
+                    if row == selected {
+                        screen.push_str("\x1b[7m");
+                    }
+                } else {
+                    screen.push(c);
+                }
+            }
+            screen.push_str(RESET);
+            screen.push('\n');
+        }
+        screen.push_str(&format!(
+            "\x1b[{};1H  {}/{}\n> {}",
+            visible + 1,
+            matches.len(),
+            candidates.len(),
+            query
+        ));
+        terminal.show(&screen)
+    }
+
+    /// Runs the picker until a template is chosen or it is cancelled.
+    pub fn run(candidates: &[Candidate]) -> Result<Option<usize>> {
+        let mut terminal = RawTerminal::open()?;
+        let mut query = String::new();
+        let mut selected = 0usize;
+        loop {
+            let matches = filter(candidates, &query);
+            selected = selected.min(matches.len().saturating_sub(1));
+            draw(&mut terminal, candidates, &query, &matches, selected)?;
+            match terminal.key()? {
+                Key::Char(c) => {
+                    query.push(c);
+                    selected = 0;
+                }
+                Key::Backspace => {
+                    query.pop();
+                    selected = 0;
+                }
+                Key::ClearLine => {
+                    query.clear();
+                    selected = 0;
+                }
+                Key::Up => selected = selected.saturating_sub(1),
+                Key::Down => selected += 1,
+                Key::Enter => return Ok(matches.get(selected).map(|(index, _)| *index)),
+                Key::Cancel => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_match_as_accent_free_subsequences_best_first() {
+        assert_eq!(fuzzy_match("", "TC Tórax"), Some((0, Vec::new())));
+        assert_eq!(fuzzy_match("tcx", "RM Joelho"), None);
+        let (_, positions) = fuzzy_match("tc torax", "AngioTC Tórax").unwrap();
+        assert_eq!(positions, [5, 6, 8, 9, 10, 11, 12]);
+        // Matches are tightened to the last window that still holds them.
+        assert_eq!(fuzzy_match("ab", "a_a_b").unwrap().1, [2, 4]);
+        let (consecutive, _) = fuzzy_match("tor", "TC Tórax").unwrap();
+        let (scattered, _) = fuzzy_match("tor", "TC Tibia Ombro Rim").unwrap();
+        assert!(consecutive > scattered);
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("TC Torax.md"),
+            "**TC DE TÓRAX**\n\nTexto.\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("US Tireoide.md"), "").unwrap();
+        let candidates = candidates(dir.path()).unwrap();
+        let titles: Vec<&str> = candidates.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["TC DE TÓRAX (TC Torax)", "US Tireoide"]);
+
+        let ranked: Vec<usize> = filter(&candidates, "t")
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(ranked, [0, 1]);
+        let ranked: Vec<usize> = filter(&candidates, "tire")
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(ranked, [1]);
+    }
+}
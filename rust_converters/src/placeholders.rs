@@ -0,0 +1,64 @@
+//! Value placeholders of the fill engine: `{{today}}`, `{{exam_date}}`,
+//! `{{institution}}` and any other `{{name}}` are replaced while filling,
+//! so exported reports come out dated and headed without a find-and-replace
+//! in Word.
+//!
+//! A placeholder reads the variable of its name set with `--set`
+//! (`--set exam_date=14/10/2026`), else its value under `[placeholders]`
+//! in `rtemplates.toml` (`institution = "Hospital São Lucas"`), else its
+//! built-in one: `today` is the local date as `DD/MM/YYYY`. Without a value
+//! it reads `XXX`, as any value still to be filled in does.
+
+use std::collections::BTreeMap;
+
+use crate::config;
+use crate::timestamp::Timestamp;
+
+/// What a value still to be filled in reads in the library's templates.
+pub(crate) const BLANK: &str = "XXX";
+
+fn built_in(name: &str) -> Option<String> {
+    match name {
+        "today" => {
+            let now = Timestamp::now_local();
+            Some(format!("{:02}/{:02}/{:04}", now.day, now.month, now.year))
+        }
+        _ => None,
+    }
+}
+
+/// The text of `{{name}}`, given the `--set` variables.
+pub fn value(name: &str, variables: &BTreeMap<String, String>) -> String {
+    variables
+        .get(name)
+        .cloned()
+        .or_else(|| config::get().placeholder(name).map(str::to_string))
+        .or_else(|| built_in(name))
+        .unwrap_or_else(|| BLANK.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fill::{fill, FillOptions};
+    use crate::timestamp::Timestamp;
+
+    #[test]
+    fn placeholders_read_the_variables_then_the_built_in_values() {
+        let mut options = FillOptions::default();
+        options.set("exam_date=13/10/2026").unwrap();
+        let filled = fill(
+            "Data do exame: {{exam_date}}. Laudo emitido em {{ today }}.\n\
+             {{institution}}{{#if institution}} ({{institution}}){{/if}}\n",
+            &options,
+        )
+        .unwrap();
+        let today = Timestamp::now_local();
+        assert_eq!(
+            filled,
+            format!(
+                "Data do exame: 13/10/2026. Laudo emitido em {:02}/{:02}/{}.\nXXX\n",
+                today.day, today.month, today.year
+            )
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Export of the templates as PowerScribe AutoText (`rtemplates
+//! powerscribe`), to seed the speech recognition system's template store.
+//!
+//! The AutoText import reads an XML list with an `AutoText` element per
+//! template, and one per alias of a template: its `Name` (the file name), a `Description` (the title) and
+//! its formatted text as RTF in `ContentRTF` (`rtf::write`). `XXX`
+//! placeholders become PowerScribe fields, `[XXX]`, which the radiologist
+//! jumps between while dictating.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::aliases;
+use crate::convert_to_html::escape_html;
+use crate::files::{list_files, FileFilter};
+use crate::fill::FillOptions;
+use crate::front_matter;
+use crate::rtf;
+use crate::template::ReportTemplate;
+
+fn placeholder() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\bX{3,}\b").expect("valid regex"))
+}
+
+/// One AutoText entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AutoText {
+    pub name: String,
+    pub description: String,
+    pub rtf: String,
+}
+
+/// The AutoText of the Markdown template `content` named `name`.
+pub fn autotext(name: &str, content: &str) -> AutoText {
+    let body = placeholder().replace_all(front_matter::strip(content), "[$0]");
+    let fields = format!(
+        "{}{}",
+        &content[..content.len() - front_matter::strip(content).len()],
+        body
+    );
+    let template = ReportTemplate::from_markdown(&fields);
+    AutoText {
+        name: name.to_string(),
+        description: template
+            .title
+            .as_ref()
+            .map(|title| title.plain())
+            .unwrap_or_default(),
+        rtf: rtf::write(&template.blocks_with_notes()),
+    }
+}
+
+/// The AutoText of the Markdown templates in `dir` selected by `files`.
+pub fn autotexts(dir: &Path, files: &FileFilter) -> Result<Vec<AutoText>> {
+    let fill = FillOptions::default();
+    let mut entries = Vec::new();
+    for path in list_files(dir, "md")? {
+        if !files.matches(path.strip_prefix(dir).unwrap_or(&path)) {
+            continue;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let content = fill.read(&path)?;
+        entries.push(autotext(&name, &content));
+        for alias in aliases::of(&path, &content) {
+            entries.push(autotext(&alias, &content));
+        }
+    }
+    Ok(entries)
+}
+
+/// `entries` as the AutoText import XML.
+pub fn to_xml(entries: &[AutoText]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!-- Written by `rtemplates powerscribe`; edit the templates and export again. -->\n\
+         <AutoTextList>\n",
+    );
+    for entry in entries {
+        xml.push_str(&format!(
+            "  <AutoText>\n    <Name>{}</Name>\n    <Description>{}</Description>\n    \
+             <ContentRTF><![CDATA[{}]]></ContentRTF>\n  </AutoText>\n",
+            escape_html(&entry.name),
+            escape_html(&entry.description),
+            // RTF escapes are ASCII, but text may still hold `]]>`.
+            entry.rtf.replace("]]>", "]]]]><![CDATA[>")
+        ));
+    }
+    xml.push_str("</AutoTextList>\n");
+    xml
+}
+
+/// Writes the AutoText XML of the templates in `dir` to `output` and
+/// returns how many entries it holds.
+pub fn write_autotexts(dir: &Path, files: &FileFilter, output: &Path) -> Result<usize> {
+    let entries = autotexts(dir, files)?;
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, to_xml(&entries))
+        .with_context(|| format!("Cannot write {}", output.display()))?;
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_become_rtf_autotext_with_fields() {
+        let entry = autotext(
+            "TC Crânio SEM",
+            "---\nmodality: CT\n---\n**TOMOGRAFIA DO CRÂNIO**\n\n**Indicação clínica:** XXX\n\n\
+             Sem {alterações}.\n\n| Medida | Valor |\n|---|---|\n| Septo | XXX mm |\n",
+        );
+        assert_eq!(entry.description, "TOMOGRAFIA DO CRÂNIO");
+        assert!(entry.rtf.starts_with("{\\rtf1\\ansi"));
+        assert!(entry
+            .rtf
+            .contains("\\pard\\qc\\f0\\fs20 {\\b TOMOGRAFIA DO CR\\u194?NIO}\\par\n"));
+        assert!(entry
+            .rtf
+            .contains("{\\b Indica\\u231?\\u227?o cl\\u237?nica:}{ [XXX]}"));
+        assert!(entry.rtf.contains("{Sem \\{altera\\u231?\\u245?es\\}.}"));
+        assert!(entry.rtf.contains("\\intbl\\f0\\fs20 {[XXX] mm}\\cell\n"));
+
+        let text: Vec<String> = rtf::parse(entry.rtf.as_bytes())
+            .iter()
+            .map(|paragraph| paragraph.plain_text())
+            .collect();
+        assert_eq!(text[0], "TOMOGRAFIA DO CRÂNIO");
+
+        let xml = to_xml(&[entry]);
+        assert!(xml.contains("<Name>TC Crânio SEM</Name>"));
+        assert!(xml.contains("<ContentRTF><![CDATA[{\\rtf1"));
+    }
+}
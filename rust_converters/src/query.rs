@@ -0,0 +1,155 @@
+//! Looks templates up in `reports_index.json` by modality, body region,
+//! tag and keyword.
+//!
+//! Queries run over the index in memory: metadata comes from the entries
+//! `generate_index` recorded, and keywords are also matched against the text
+//! of Markdown and TXT files.
+
+use anyhow::Result;
+use std::path::Path;
+
+use crate::encoding::read_text;
+use crate::files::FileFilter;
+use crate::generate_index::{FileEntry, Index};
+use crate::pick::fold;
+use crate::search::{pattern, search_content, Pattern, SearchOptions};
+
+/// Every condition that is set must hold.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TemplateQuery {
+    /// DICOM modality code (`CT`, `MR`, ...), ignoring case and accents.
+    pub modality: Option<String>,
+    /// The body region the index recorded (the front matter's, or else the
+    /// classified one), ignoring case and accents, so `torax` finds `Tórax`.
+    pub body_region: Option<String>,
+    /// Literal text looked for in the title, the section names and, for
+    /// Markdown and TXT files, the contents.
+    pub keyword: Option<String>,
+    /// Front matter tags the template must all carry, ignoring case and
+    /// accents; only Markdown templates have them.
+    pub tags: Vec<String>,
+    /// Which indexed paths (`Templates_docx/X.docx`) to consider.
+    pub files: FileFilter,
+}
+
+fn same(wanted: &Option<String>, actual: Option<&String>) -> bool {
+    let folded = |text: &str| text.trim().chars().map(fold).collect::<String>();
+    match wanted {
+        None => true,
+        Some(wanted) => actual.is_some_and(|actual| folded(actual) == folded(wanted)),
+    }
+}
+
+fn keyword_matches(root: &Path, path: &str, entry: &FileEntry, keyword: &Pattern) -> Result<bool> {
+    let in_entry = entry
+        .title
+        .iter()
+        .chain(&entry.sections)
+        .any(|text| keyword.is_match(text));
+    if in_entry {
+        return Ok(true);
+    }
+    let file = root.join(path);
+    let content = match file.extension().and_then(|s| s.to_str()) {
+        Some("md") | Some("txt") => read_text(&file)?,
+        _ => return Ok(false),
+    };
+    Ok(!search_content(&content, keyword, None).is_empty())
+}
+
+impl TemplateQuery {
+    /// The indexed files under `root` that match, in index order.
+    pub fn run<'a>(&self, root: &Path, index: &'a Index) -> Result<Vec<(&'a str, &'a FileEntry)>> {
+        let keyword = self
+            .keyword
+            .as_deref()
+            .map(|keyword| pattern(keyword, &SearchOptions::default()))
+            .transpose()?;
+
+        let mut found = Vec::new();
+        for (path, entry) in &index.files {
+            let front = entry.front_matter.as_ref();
+            if !self.files.matches(Path::new(path))
+                || !same(&self.modality, entry.modality.as_ref())
+                || !same(
+                    &self.body_region,
+                    entry
+                        .body_region
+                        .as_ref()
+                        .or(front.and_then(|f| f.body_region.as_ref())),
+                )
+                || !self
+                    .tags
+                    .iter()
+                    .all(|tag| front.is_some_and(|f| f.has_tag(tag)))
+            {
+                continue;
+            }
+            if let Some(keyword) = &keyword {
+                if !keyword_matches(root, path, entry, keyword)? {
+                    continue;
+                }
+            }
+            found.push((path.as_str(), entry));
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use crate::generate_index::{read_index, write_index};
+    use std::fs;
+
+    #[test]
+    fn conditions_ignore_case_and_accents() {
+        let root = tempfile::tempdir().unwrap();
+        let md = &config::get().directories.markdown;
+        fs::create_dir_all(root.path().join(md)).unwrap();
+        fs::write(
+            root.path().join(md).join("TC Tórax.md"),
+            "---\ntags: [Oncologia]\n---\n**TC DE TÓRAX**\n\n**Impressão:**\nNódulo pulmonar.\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join(md).join("RM Joelho.md"),
+            "**RM DO JOELHO**\n\n**Impressão:**\nMenisco íntegro.\n",
+        )
+        .unwrap();
+        let output = root.path().join("reports_index.json");
+        write_index(root.path(), &output).unwrap();
+        let index = read_index(&output).unwrap();
+
+        let found = |query: TemplateQuery| -> Vec<String> {
+            query
+                .run(root.path(), &index)
+                .unwrap()
+                .into_iter()
+                .map(|(path, _)| path.to_string())
+                .collect()
+        };
+        let chest = vec![format!("{}/TC Tórax.md", md)];
+        for region in ["torax", "Tórax", "TÓRAX"] {
+            let query = TemplateQuery {
+                modality: Some("ct".to_string()),
+                body_region: Some(region.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(found(query), chest, "{}", region);
+        }
+        let query = TemplateQuery {
+            keyword: Some("nodulo".to_string()),
+            tags: vec!["oncologia".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(found(query), chest);
+        let query = TemplateQuery {
+            modality: Some("MR".to_string()),
+            keyword: Some("nodulo".to_string()),
+            ..Default::default()
+        };
+        assert!(found(query).is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! Tracked changes of a DOCX being imported (`[docx] tracked_changes`,
+//! `--tracked-changes`), settled before docx-rust reads the body: it knows
+//! none of Word's revision marks, so a template still under review would
+//! read as a mix of its old and new text.
+//!
+//! Accepting keeps the inserted and moved-to text and drops the deleted and
+//! moved-from text; rejecting does the opposite, turning deleted text back
+//! into text and restoring the run and paragraph formatting the changes
+//! replaced. Table rows inserted or deleted as a whole are kept or dropped
+//! the same way. A paragraph mark inserted or deleted (two paragraphs
+//! joined or split) is left as the paragraphs now read. `document.xml` is
+//! edited as text, as `convert_to_markdown` does for hyperlinks.
+
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+use crate::config::TrackedChanges;
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid regex"))
+}
+
+/// `xml` with every element `tag` (`w:ins`) and its content removed.
+fn remove(xml: &str, tag: &str) -> String {
+    Regex::new(&format!(r"(?s)<{tag}(\s[^>]*[^/>])?>.*?</{tag}>"))
+        .expect("valid regex")
+        .replace_all(xml, "")
+        .into_owned()
+}
+
+/// `xml` with every element `tag` replaced by its content.
+fn unwrap(xml: &str, tag: &str) -> String {
+    Regex::new(&format!(r"<{tag}(\s[^>]*[^/>])?>|</{tag}>"))
+        .expect("valid regex")
+        .replace_all(xml, "")
+        .into_owned()
+}
+
+/// `xml` with each `w:rPr` (`properties`) holding a `w:rPrChange` replaced
+/// by the properties the change records as former.
+fn restore_properties(xml: &str, properties: &str) -> String {
+    let open = format!("<w:{properties}>");
+    let change = format!("<w:{properties}Change");
+    let change_end = format!("</w:{properties}Change>");
+    let close = format!("</w:{properties}>");
+    let mut xml = xml.to_string();
+    let mut from = 0;
+    while let Some(offset) = xml[from..].find(&change) {
+        let at = from + offset;
+        let (Some(start), Some(end)) = (
+            xml[..at].rfind(&open),
+            xml[at..].find(&change_end).and_then(|change_end_at| {
+                let after = at + change_end_at + change_end.len();
+                xml[after..].find(&close).map(|close_at| after + close_at)
+            }),
+        ) else {
+            break;
+        };
+        let recorded = &xml[at..end];
+        let former = recorded
+            .find(&open)
+            .and_then(|inner| {
+                let content = inner + open.len();
+                recorded[content..]
+                    .find(&close)
+                    .map(|length| &recorded[content..content + length])
+            })
+            .unwrap_or("");
+        let restored = format!("{open}{former}{close}");
+        xml.replace_range(start..end + close.len(), &restored);
+        from = start + restored.len();
+    }
+    xml
+}
+
+/// The body XML `xml` with its tracked changes accepted or rejected.
+pub fn resolve(xml: &str, changes: TrackedChanges) -> String {
+    static ROW: OnceLock<Regex> = OnceLock::new();
+    static ROW_MARK: OnceLock<Regex> = OnceLock::new();
+    static MARK: OnceLock<Regex> = OnceLock::new();
+    static FORMATTING: OnceLock<Regex> = OnceLock::new();
+    static DELETED_TEXT: OnceLock<Regex> = OnceLock::new();
+    if !xml.contains("<w:ins")
+        && !xml.contains("<w:del")
+        && !xml.contains("<w:move")
+        && !xml.contains("Change")
+    {
+        return xml.to_string();
+    }
+
+    // Rows inserted or deleted whole carry the mark in their properties.
+    let dropped_row = match changes {
+        TrackedChanges::Accept => "w:del",
+        TrackedChanges::Reject => "w:ins",
+    };
+    let row_mark = regex(
+        &ROW_MARK,
+        r"(?s)<w:trPr>.*?<(w:ins|w:del)\s[^>]*/>.*?</w:trPr>",
+    );
+    let xml = regex(&ROW, r"(?s)<w:tr[\s>].*?</w:tr>").replace_all(xml, |caps: &Captures| {
+        let row = &caps[0];
+        match row_mark.captures(row) {
+            Some(mark) if &mark[1] == dropped_row => String::new(),
+            _ => row.to_string(),
+        }
+    });
+    // Marks of inserted and deleted paragraph marks and rows, now settled.
+    let xml = regex(&MARK, r"<w:(ins|del|moveFrom|moveTo)\s[^>]*/>").replace_all(&xml, "");
+
+    match changes {
+        TrackedChanges::Accept => {
+            let xml = remove(&remove(&xml, "w:del"), "w:moveFrom");
+            let xml = unwrap(&unwrap(&xml, "w:ins"), "w:moveTo");
+            regex(
+                &FORMATTING,
+                r"(?s)<w:(rPrChange|pPrChange|sectPrChange|tblPrChange|trPrChange|tcPrChange)[\s>].*?</w:(rPrChange|pPrChange|sectPrChange|tblPrChange|trPrChange|tcPrChange)>",
+            )
+            .replace_all(&xml, "")
+            .into_owned()
+        }
+        TrackedChanges::Reject => {
+            let xml = remove(&remove(&xml, "w:ins"), "w:moveTo");
+            let xml = unwrap(&unwrap(&xml, "w:del"), "w:moveFrom");
+            let xml = regex(&DELETED_TEXT, r"<(/?)w:del(Text|InstrText)([\s>])")
+                .replace_all(&xml, |caps: &Captures| {
+                    let tag = if &caps[2] == "Text" { "t" } else { "instrText" };
+                    format!("<{}w:{}{}", &caps[1], tag, &caps[3])
+                })
+                .into_owned();
+            restore_properties(&restore_properties(&xml, "rPr"), "pPr")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_accepted_or_rejected() {
+        let run = |text: &str| format!("<w:r><w:t>{text}</w:t></w:r>");
+        let deleted =
+            r#"<w:del w:id="1" w:author="R2"><w:r><w:delText>hipodenso</w:delText></w:r></w:del>"#;
+        let inserted = format!(
+            r#"<w:ins w:id="2" w:author="R2">{}</w:ins>"#,
+            run("hiperdenso")
+        );
+        let bold = r#"<w:r><w:rPr><w:b/><w:rPrChange w:id="3" w:author="R2"><w:rPr><w:i/></w:rPr></w:rPrChange></w:rPr><w:t>Nódulo</w:t></w:r>"#;
+        let xml = format!(
+            "<w:body><w:p>{bold}{}{deleted}{inserted}.</w:p>\
+             <w:tbl><w:tr><w:tc>A</w:tc></w:tr>\
+             <w:tr><w:trPr><w:ins w:id=\"4\" w:author=\"R2\"/></w:trPr><w:tc>B</w:tc></w:tr></w:tbl></w:body>",
+            run(" ")
+        );
+
+        let accepted = resolve(&xml, TrackedChanges::Accept);
+        assert_eq!(
+            accepted,
+            format!(
+                "<w:body><w:p><w:r><w:rPr><w:b/></w:rPr><w:t>Nódulo</w:t></w:r>{}{}.</w:p>\
+                 <w:tbl><w:tr><w:tc>A</w:tc></w:tr>\
+                 <w:tr><w:trPr></w:trPr><w:tc>B</w:tc></w:tr></w:tbl></w:body>",
+                run(" "),
+                run("hiperdenso")
+            )
+        );
+        let rejected = resolve(&xml, TrackedChanges::Reject);
+        assert_eq!(
+            rejected,
+            format!(
+                "<w:body><w:p><w:r><w:rPr><w:i/></w:rPr><w:t>Nódulo</w:t></w:r>{}{}.</w:p>\
+                 <w:tbl><w:tr><w:tc>A</w:tc></w:tr></w:tbl></w:body>",
+                run(" "),
+                run("hipodenso")
+            )
+        );
+        assert_eq!(resolve(&run("x"), TrackedChanges::Reject), run("x"));
+    }
+}
@@ -0,0 +1,623 @@
+//! Minimal RTF reader: a tokenizer for control words, groups and text runs,
+//! plus an interpreter that tracks character formatting per group and yields
+//! formatted paragraphs. `write` goes the other way, for the systems that
+//! take templates as RTF.
+
+use encoding_rs::{Encoding, WINDOWS_1252};
+
+use crate::config;
+use crate::markdown::{is_page_break, parse_inline, spans_to_markdown, Alignment, Block, Image};
+
+/// A lexical RTF element. Text runs borrow from the input bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
+    GroupStart,
+    GroupEnd,
+    /// `\word` or `\wordN` (e.g. `\b`, `\b0`, `\fs20`).
+    ControlWord(&'a str, Option<i32>),
+    /// `\` followed by a single non-letter (e.g. `\~`, `\{`, `\*`).
+    ControlSymbol(char),
+    /// `\'hh` escape carrying one byte in the document codepage.
+    Hex(u8),
+    Text(&'a [u8]),
+}
+
+/// Splits RTF source into tokens. Line breaks in the source are not content
+/// and are dropped; the space delimiting a control word is consumed.
+pub fn tokenize(input: &[u8]) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < input.len() {
+        match input[i] {
+            b'{' => {
+                tokens.push(Token::GroupStart);
+                i += 1;
+            }
+            b'}' => {
+                tokens.push(Token::GroupEnd);
+                i += 1;
+            }
+            b'\r' | b'\n' => i += 1,
+            b'\\' => {
+                i += 1;
+                let Some(&next) = input.get(i) else { break };
+                if next.is_ascii_alphabetic() {
+                    let start = i;
+                    while i < input.len() && input[i].is_ascii_alphabetic() {
+                        i += 1;
+                    }
+                    let name = std::str::from_utf8(&input[start..i]).unwrap_or_default();
+
+                    let param_start = i;
+                    if input.get(i) == Some(&b'-') {
+                        i += 1;
+                    }
+                    while i < input.len() && input[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    let param = std::str::from_utf8(&input[param_start..i])
+                        .ok()
+                        .and_then(|p| p.parse::<i32>().ok());
+
+                    if input.get(i) == Some(&b' ') {
+                        i += 1;
+                    }
+
+                    if name == "bin" {
+                        // Binary payload of N bytes follows; skip it entirely.
+                        i += param.unwrap_or(0).max(0) as usize;
+                        continue;
+                    }
+                    tokens.push(Token::ControlWord(name, param));
+                } else if next == b'\'' {
+                    let hex = input
+                        .get(i + 1..i + 3)
+                        .and_then(|h| std::str::from_utf8(h).ok())
+                        .and_then(|h| u8::from_str_radix(h, 16).ok());
+                    match hex {
+                        Some(byte) => {
+                            tokens.push(Token::Hex(byte));
+                            i += 3;
+                        }
+                        None => i += 1,
+                    }
+                } else {
+                    tokens.push(Token::ControlSymbol(next as char));
+                    i += 1;
+                }
+            }
+            _ => {
+                let start = i;
+                while i < input.len() && !matches!(input[i], b'{' | b'}' | b'\\' | b'\r' | b'\n') {
+                    i += 1;
+                }
+                tokens.push(Token::Text(&input[start..i]));
+            }
+        }
+    }
+
+    tokens
+}
+
+/// A run of text sharing the same character formatting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// Target when the text is a hyperlink.
+    pub link: Option<String>,
+    /// Set when the span is an image, whose alt text is `text`.
+    pub image: Option<Image>,
+    /// Label of the footnote the span refers to, written `[^label]`.
+    pub note: Option<String>,
+}
+
+/// One RTF paragraph (text up to `\par`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RtfParagraph {
+    pub spans: Vec<Span>,
+}
+
+impl RtfParagraph {
+    pub fn plain_text(&self) -> String {
+        self.spans.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    /// True when any non-blank run is bold, italic or underlined.
+    pub fn has_formatting(&self) -> bool {
+        self.spans
+            .iter()
+            .any(|s| !s.text.trim().is_empty() && (s.bold || s.italic || s.underline))
+    }
+
+    /// Renders runs using the same markers as the DOCX importer
+    /// (`**bold**`, `*italic*`, `__underline__`), see
+    /// `markdown::spans_to_markdown`.
+    pub fn to_markdown(&self) -> String {
+        spans_to_markdown(&self.spans).trim().to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GroupState {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    /// Inside a destination whose text is not document content.
+    skip: bool,
+    /// Fallback characters following each `\uN` (set by `\ucN`).
+    unicode_skip: usize,
+}
+
+impl Default for GroupState {
+    fn default() -> Self {
+        GroupState {
+            bold: false,
+            italic: false,
+            underline: false,
+            skip: false,
+            unicode_skip: 1,
+        }
+    }
+}
+
+/// Maps an `\ansicpgN` codepage number to a decoder, defaulting to
+/// Windows-1252 for codepages encoding_rs does not know.
+fn codepage_encoding(codepage: i32) -> &'static Encoding {
+    let label = match codepage {
+        932 => "shift_jis".to_string(),
+        936 => "gbk".to_string(),
+        949 => "euc-kr".to_string(),
+        950 => "big5".to_string(),
+        10000 => "macintosh".to_string(),
+        20866 => "koi8-r".to_string(),
+        65001 => "utf-8".to_string(),
+        28591..=28599 => format!("iso-8859-{}", codepage - 28590),
+        n => format!("windows-{}", n),
+    };
+    Encoding::for_label(label.as_bytes()).unwrap_or(WINDOWS_1252)
+}
+
+/// Destinations holding metadata rather than body text.
+const SKIPPED_DESTINATIONS: &[&str] = &[
+    "fonttbl",
+    "colortbl",
+    "stylesheet",
+    "info",
+    "pict",
+    "object",
+    "header",
+    "headerl",
+    "headerr",
+    "headerf",
+    "footer",
+    "footerl",
+    "footerr",
+    "footerf",
+    "listtable",
+    "listoverridetable",
+    "revtbl",
+    "rsidtbl",
+    "generator",
+    "themedata",
+    "colorschememapping",
+    "latentstyles",
+    "datastore",
+    "xmlnstbl",
+    "filetbl",
+    "pgdsctbl",
+    "fldinst",
+];
+
+struct Interpreter {
+    paragraphs: Vec<RtfParagraph>,
+    current: RtfParagraph,
+    stack: Vec<GroupState>,
+    state: GroupState,
+    /// Document codepage used for `\'hh` escapes and 8-bit text.
+    encoding: &'static Encoding,
+    /// Consecutive `\'hh` bytes, decoded together so multi-byte codepages work.
+    pending_bytes: Vec<u8>,
+    /// Fallback characters still to drop after a `\uN`.
+    fallback_left: usize,
+    /// High half of a UTF-16 surrogate pair written as two `\uN` words.
+    high_surrogate: Option<u16>,
+}
+
+impl Interpreter {
+    fn new() -> Self {
+        Interpreter {
+            paragraphs: Vec::new(),
+            current: RtfParagraph::default(),
+            stack: Vec::new(),
+            state: GroupState::default(),
+            encoding: WINDOWS_1252,
+            pending_bytes: Vec::new(),
+            fallback_left: 0,
+            high_surrogate: None,
+        }
+    }
+
+    fn flush_bytes(&mut self) {
+        if self.pending_bytes.is_empty() {
+            return;
+        }
+        let bytes = std::mem::take(&mut self.pending_bytes);
+        let (text, _) = self.encoding.decode_without_bom_handling(&bytes);
+        self.push_text(&text);
+    }
+
+    fn unicode_char(&mut self, param: i32) {
+        // Values above 32767 are written as negative signed 16-bit numbers.
+        let unit = if param < 0 { param + 65536 } else { param } as u32;
+        self.fallback_left = self.state.unicode_skip;
+
+        match (unit, self.high_surrogate.take()) {
+            (0xD800..=0xDBFF, _) => self.high_surrogate = Some(unit as u16),
+            (0xDC00..=0xDFFF, Some(high)) => {
+                let decoded = char::decode_utf16([high, unit as u16]).next();
+                if let Some(Ok(c)) = decoded {
+                    self.push_text(&c.to_string());
+                }
+            }
+            (code, _) => {
+                if let Some(c) = char::from_u32(code) {
+                    self.push_text(&c.to_string());
+                }
+            }
+        }
+    }
+
+    fn text(&mut self, bytes: &[u8]) {
+        // Documents without a codepage sometimes carry raw UTF-8.
+        let decoded = match std::str::from_utf8(bytes) {
+            Ok(text) => text.to_string(),
+            Err(_) => self
+                .encoding
+                .decode_without_bom_handling(bytes)
+                .0
+                .into_owned(),
+        };
+        let skipped = self.fallback_left.min(decoded.chars().count());
+        self.fallback_left -= skipped;
+        let text: String = decoded.chars().skip(skipped).collect();
+        self.push_text(&text);
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if self.state.skip || text.is_empty() {
+            return;
+        }
+        let (bold, italic, underline) = (self.state.bold, self.state.italic, self.state.underline);
+        match self.current.spans.last_mut() {
+            Some(last) if (last.bold, last.italic, last.underline) == (bold, italic, underline) => {
+                last.text.push_str(text);
+            }
+            _ => self.current.spans.push(Span {
+                text: text.to_string(),
+                bold,
+                italic,
+                underline,
+                link: None,
+                image: None,
+                note: None,
+            }),
+        }
+    }
+
+    fn end_paragraph(&mut self) {
+        if self.state.skip {
+            return;
+        }
+        self.paragraphs.push(std::mem::take(&mut self.current));
+    }
+
+    fn control_word(&mut self, name: &str, param: Option<i32>) {
+        let on = param != Some(0);
+        match name {
+            _ if SKIPPED_DESTINATIONS.contains(&name) => self.state.skip = true,
+            "par" | "line" | "sect" | "page" => self.end_paragraph(),
+            "tab" => self.push_text(" "),
+            "cell" => self.push_text(" | "),
+            "row" => self.end_paragraph(),
+            "b" => self.state.bold = on,
+            "i" => self.state.italic = on,
+            "ul" => self.state.underline = on,
+            "ulnone" => self.state.underline = false,
+            "plain" => {
+                self.state.bold = false;
+                self.state.italic = false;
+                self.state.underline = false;
+            }
+            "emdash" => self.push_text("—"),
+            "endash" => self.push_text("–"),
+            "bullet" => self.push_text("•"),
+            "lquote" => self.push_text("‘"),
+            "rquote" => self.push_text("’"),
+            "ldblquote" => self.push_text("“"),
+            "rdblquote" => self.push_text("”"),
+            "ansi" => self.encoding = WINDOWS_1252,
+            "mac" => self.encoding = codepage_encoding(10000),
+            "ansicpg" => self.encoding = codepage_encoding(param.unwrap_or(1252)),
+            "u" => self.unicode_char(param.unwrap_or(0)),
+            "uc" => self.state.unicode_skip = param.unwrap_or(1).max(0) as usize,
+            _ => {}
+        }
+    }
+
+    /// `after_group_start` is true when the symbol directly follows `{`,
+    /// which is where `\*` marks an ignorable destination.
+    fn control_symbol(&mut self, symbol: char, after_group_start: bool) {
+        match symbol {
+            '*' if after_group_start => self.state.skip = true,
+            '\\' | '{' | '}' => self.push_text(&symbol.to_string()),
+            '~' => self.push_text("\u{a0}"),
+            '_' => self.push_text("-"),
+            _ => {}
+        }
+    }
+
+    fn run(mut self, tokens: &[Token]) -> Vec<RtfParagraph> {
+        for (idx, token) in tokens.iter().enumerate() {
+            if !matches!(token, Token::Hex(_)) {
+                self.flush_bytes();
+            }
+            // The fallback of a high surrogate comes before the low one.
+            let fallback =
+                self.fallback_left > 0 && matches!(token, Token::Text(_) | Token::Hex(_));
+            if !fallback && !matches!(token, Token::ControlWord("u", _)) {
+                self.high_surrogate = None;
+            }
+            match token {
+                Token::GroupStart => self.stack.push(self.state),
+                Token::GroupEnd => {
+                    if let Some(state) = self.stack.pop() {
+                        self.state = state;
+                    }
+                }
+                Token::ControlWord(name, param) => {
+                    if *name != "u" {
+                        self.fallback_left = 0;
+                    }
+                    self.control_word(name, *param)
+                }
+                Token::ControlSymbol(symbol) => {
+                    let after_group_start = idx > 0 && tokens[idx - 1] == Token::GroupStart;
+                    self.control_symbol(*symbol, after_group_start);
+                }
+                Token::Hex(byte) => {
+                    if self.fallback_left > 0 {
+                        self.fallback_left -= 1;
+                    } else {
+                        self.pending_bytes.push(*byte);
+                    }
+                }
+                Token::Text(bytes) => self.text(bytes),
+            }
+        }
+        self.flush_bytes();
+
+        if !self.current.spans.is_empty() {
+            self.paragraphs.push(self.current);
+        }
+        self.paragraphs
+    }
+}
+
+/// Parses RTF source into formatted paragraphs.
+pub fn parse(input: &[u8]) -> Vec<RtfParagraph> {
+    Interpreter::new().run(&tokenize(input))
+}
+
+/// Width of a table in twips; the columns share it equally.
+const TABLE_WIDTH_TWIPS: usize = 9000;
+
+/// `text` escaped for RTF, characters outside ASCII as `\uN?`.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\t' => escaped.push_str("\\tab "),
+            '\n' => escaped.push_str("\\line "),
+            c if c.is_ascii() => escaped.push(c),
+            c => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    escaped.push_str(&format!("\\u{}?", *unit as i16));
+                }
+            }
+        }
+    }
+    escaped
+}
+
+/// The inline Markdown `text` as RTF runs; images are left out.
+fn runs(text: &str, bold: bool) -> String {
+    let mut rtf = String::new();
+    for run in parse_inline(text, bold) {
+        if run.image.is_some() {
+            continue;
+        }
+        let mut controls = String::new();
+        for (on, control) in [
+            (run.bold, "\\b"),
+            (run.italic, "\\i"),
+            (run.underline, "\\ul"),
+        ] {
+            if on {
+                controls.push_str(control);
+            }
+        }
+        if !controls.is_empty() {
+            // The space ends the last control word.
+            controls.push(' ');
+        }
+        rtf.push_str(&format!("{{{}{}}}", controls, escape(&run.text)));
+    }
+    rtf
+}
+
+/// Line and paragraph spacing as the `[docx]` settings give them.
+fn spacing() -> String {
+    let settings = &config::get().paragraph_spacing;
+    let twips = |pt: f64| (pt * 20.0).round() as i64;
+    let mut controls = String::new();
+    if let Some(line) = settings.line {
+        controls.push_str(&format!("\\sl{}\\slmult1", (line * 240.0).round() as i64));
+    }
+    if let Some(before) = settings.before_pt {
+        controls.push_str(&format!("\\sb{}", twips(before)));
+    }
+    if let Some(after) = settings.after_pt {
+        controls.push_str(&format!("\\sa{}", twips(after)));
+    }
+    controls
+}
+
+/// An RTF document of `blocks` laid out like the DOCX output: the
+/// configured font and size and paragraph spacing on an A4 page with
+/// one-inch margins.
+pub fn write(blocks: &[Block]) -> String {
+    let config = config::get();
+    let spacing = spacing();
+    let mut rtf = format!(
+        "{{\\rtf1\\ansi\\ansicpg1252\\uc1\\deff0{{\\fonttbl{{\\f0\\fswiss\\fcharset0 {};}}}}\n\
+         \\paperw11906\\paperh16838\\margl1440\\margr1440\\margt1440\\margb1440\n",
+        escape(&config.font)
+    );
+    for block in blocks {
+        match block {
+            Block::Paragraph {
+                text,
+                heading: None,
+                ..
+            } if is_page_break(text) => rtf.push_str("\\page\n"),
+            Block::Paragraph {
+                text,
+                heading,
+                style,
+            } => {
+                let alignment = match style.alignment {
+                    Alignment::Justify => "\\qj",
+                    Alignment::Center => "\\qc",
+                    Alignment::Left => "\\ql",
+                };
+                rtf.push_str(&format!(
+                    "\\pard{}{}\\f0\\fs{}{} {}\\par\n",
+                    alignment,
+                    spacing,
+                    style.font_size_pt * 2,
+                    if style.force_italic { "\\i" } else { "" },
+                    runs(text, heading.is_some())
+                ));
+            }
+            Block::Table { header, rows } => {
+                let columns = header.len().max(1);
+                let mut row_definition = String::from("\\trowd\\trgaph108");
+                for column in 1..=columns {
+                    row_definition.push_str(&format!(
+                        "\\clbrdrt\\brdrs\\clbrdrl\\brdrs\\clbrdrb\\brdrs\\clbrdrr\\brdrs\\cellx{}",
+                        TABLE_WIDTH_TWIPS * column / columns
+                    ));
+                }
+                for (index, row) in std::iter::once(header).chain(rows).enumerate() {
+                    rtf.push_str(&row_definition);
+                    rtf.push('\n');
+                    for column in 0..columns {
+                        let cell = row.get(column).map(String::as_str).unwrap_or("");
+                        rtf.push_str(&format!(
+                            "\\pard\\intbl\\f0\\fs{} {}\\cell\n",
+                            config.font_size_pt * 2,
+                            runs(&cell.replace("<br>", "\n"), index == 0)
+                        ));
+                    }
+                    rtf.push_str("\\row\n");
+                }
+                rtf.push_str("\\pard\n");
+            }
+        }
+    }
+    rtf.push_str("}\n");
+    rtf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_keep_group_formatting_and_skip_destinations() {
+        let source = br"{\rtf1{\fonttbl{\f0 Arial;}}\f0 Normal {\b bold\b0  off} {\i it}\par\{x\}}";
+        assert_eq!(
+            tokenize(br"{\b0 a\'e7\~}"),
+            [
+                Token::GroupStart,
+                Token::ControlWord("b", Some(0)),
+                Token::Text(b"a"),
+                Token::Hex(0xe7),
+                Token::ControlSymbol('~'),
+                Token::GroupEnd,
+            ]
+        );
+        let paragraphs = parse(source);
+        assert_eq!(paragraphs.len(), 2);
+        assert_eq!(paragraphs[0].plain_text(), "Normal bold off it");
+        assert_eq!(paragraphs[0].to_markdown(), "Normal **bold** off *it*");
+        assert!(paragraphs[0].has_formatting());
+        assert_eq!(paragraphs[1].plain_text(), "{x}");
+        assert!(!paragraphs[1].has_formatting());
+    }
+
+    #[test]
+    fn escapes_decode_by_codepage_and_unicode_skips_its_fallback() {
+        let text = |source: &[u8]| parse(source)[0].plain_text();
+        assert_eq!(text(br"{\rtf1\ansi\ansicpg1252 a\'e7\'e3o}"), "ação");
+        assert_eq!(text(br"{\rtf1\ansi\ansicpg1251 \'c4\'e0}"), "Да");
+        assert_eq!(text(br"{\rtf1\ansicpg932 \'93\'fa}"), "日");
+        assert_eq!(text(br"{\rtf1 n\u243?dulo}"), "nódulo");
+        assert_eq!(text(br"{\rtf1\uc2 n\u243\'6f\'3fdulo}"), "nódulo");
+        assert_eq!(text(br"{\rtf1{\uc0\u8805}x\u-3913?}"), "≥x\u{f0b7}");
+        assert_eq!(text(br"{\rtf1 \u-10179?\u-8704?}"), "😀");
+    }
+
+    #[test]
+    fn written_rtf_keeps_the_layout_and_reads_back() {
+        let template = crate::template::ReportTemplate::from_markdown(
+            "**TC DE TÓRAX**\n\n**Impressão:**\nNódulo {à direita} de *5 mm*.\n\n\
+             | **Lesão** | **Medida** |\n| --- | --- |\n| Nódulo | 5 mm |\n\n*Rodapé.*\n",
+        );
+        let rtf = write(&template.document_blocks());
+        assert!(
+            rtf.starts_with("{\\rtf1\\ansi\\ansicpg1252\\uc1\\deff0"),
+            "{rtf}"
+        );
+        assert!(rtf.contains(&format!("\\fcharset0 {};", config::get().font)));
+        assert!(
+            rtf.contains("N\\u243?dulo \\{\\u224? direita\\} de "),
+            "{rtf}"
+        );
+        assert!(rtf.contains("{\\i 5 mm}"), "{rtf}");
+        assert_eq!(rtf.matches("\\row\n").count(), 2);
+        assert!(rtf.contains("\\cellx4500\\clbrdrt"), "{rtf}");
+        assert!(rtf.ends_with("}\n"));
+        assert_eq!(escape("😀"), "\\u-10179?\\u-8704?");
+
+        let read_back: Vec<String> = parse(rtf.as_bytes())
+            .iter()
+            .map(|paragraph| paragraph.to_markdown())
+            .filter(|line| !line.is_empty())
+            .collect();
+        assert_eq!(read_back[0], "**TC DE TÓRAX**");
+        assert!(
+            read_back.contains(&"Nódulo {à direita} de *5 mm*.".to_string()),
+            "{read_back:?}"
+        );
+    }
+}
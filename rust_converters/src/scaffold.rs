@@ -0,0 +1,247 @@
+//! New Markdown templates (`rtemplates new`), started the way the library
+//! writes them.
+//!
+//! A new template has front matter with its modality, body region,
+//! contrast and creation date; the title in bold capitals
+//! (`**TOMOGRAFIA COMPUTADORIZADA DE TÓRAX**`); the four standard sections
+//! as bold `Name:` headings with `XXX` to fill in, the CT and MR technique
+//! already written; and the department footer, the `disclaimer` setting or
+//! else the library's usual one. Its file name follows the library's
+//! (`TC Tórax COM`).
+
+use anyhow::{bail, Result};
+
+use crate::classify::{body_region, body_region_names};
+use crate::config;
+use crate::template::SectionKind;
+use crate::timestamp::Timestamp;
+
+/// The footer of most templates in the library.
+const FOOTER: &str = "A impressão diagnóstica é de probabilidade, devendo ser considerada em \
+conjunto com dados clínicos e laboratoriais, além de outros exames de imagem prévios e/ou \
+subsequentes. Somente seu médico tem condições de interpretar o conjunto de todas estas \
+informações.";
+
+const CT_TECHNIQUE: &str =
+    "Realizadas aquisições volumétricas com posteriores reconstruções multiplanares.";
+const MR_TECHNIQUE: &str =
+    "Realizadas sequências multiplanares ponderadas em T1 e T2, com e sem supressão de gordura.";
+
+/// A DICOM modality with the names templates give it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Modality {
+    /// `CT`, `MR`, `US`, `MG` or `DX`.
+    pub code: &'static str,
+    /// What file names start with (`TC`, `RM`).
+    pub abbreviation: &'static str,
+    /// What titles start with.
+    pub title: &'static str,
+}
+
+const MODALITIES: [(Modality, &[&str]); 5] = [
+    (
+        Modality {
+            code: "CT",
+            abbreviation: "TC",
+            title: "TOMOGRAFIA COMPUTADORIZADA",
+        },
+        &["CT", "TC"],
+    ),
+    (
+        Modality {
+            code: "MR",
+            abbreviation: "RM",
+            title: "RESSONÂNCIA MAGNÉTICA",
+        },
+        &["MR", "RM", "RMN"],
+    ),
+    (
+        Modality {
+            code: "US",
+            abbreviation: "US",
+            title: "ULTRASSONOGRAFIA",
+        },
+        &["US", "USG"],
+    ),
+    (
+        Modality {
+            code: "MG",
+            abbreviation: "MG",
+            title: "MAMOGRAFIA",
+        },
+        &["MG", "MMG"],
+    ),
+    (
+        Modality {
+            code: "DX",
+            abbreviation: "RX",
+            title: "RADIOGRAFIA",
+        },
+        &["DX", "RX", "CR"],
+    ),
+];
+
+impl Modality {
+    /// The modality a DICOM code or Portuguese abbreviation names, in any case.
+    pub fn parse(text: &str) -> Option<Self> {
+        let upper = text.trim().to_uppercase();
+        MODALITIES
+            .iter()
+            .find(|(_, names)| names.contains(&upper.as_str()))
+            .map(|(modality, _)| *modality)
+    }
+
+    /// Whether contrast is always stated, in the title and file name.
+    fn states_contrast(self) -> bool {
+        matches!(self.code, "CT" | "MR")
+    }
+}
+
+/// What `rtemplates new` was asked for.
+#[derive(Clone, Debug)]
+pub struct NewTemplate {
+    pub modality: Modality,
+    /// The body region as given (`torax`, `abdome pelve`).
+    pub region: String,
+    pub contrast: bool,
+    pub author: Option<String>,
+}
+
+/// The technique paragraph of `new`, when the modality has a usual one.
+fn technique(new: &NewTemplate) -> Option<String> {
+    let (acquisition, contrast) = match new.modality.code {
+        "CT" => (
+            CT_TECHNIQUE,
+            if new.contrast {
+                "Imagens obtidas antes e após o uso do meio de contraste endovenoso."
+            } else {
+                "Imagens obtidas sem o uso do meio de contraste endovenoso."
+            },
+        ),
+        "MR" => (
+            MR_TECHNIQUE,
+            if new.contrast {
+                "Imagens obtidas antes e após a administração endovenosa do meio de contraste paramagnético."
+            } else {
+                "Exame realizado sem a administração do meio de contraste paramagnético."
+            },
+        ),
+        _ => return None,
+    };
+    Some(format!("{} {}", acquisition, contrast))
+}
+
+/// The file stem and Markdown of the template `new` asks for.
+pub fn scaffold(new: &NewTemplate) -> Result<(String, String)> {
+    let Some(region) = body_region(&new.region) else {
+        bail!(
+            "Unknown body region `{}`; expected one or more of {}",
+            new.region,
+            body_region_names().join(", ")
+        );
+    };
+
+    let mut name = vec![new.modality.abbreviation.to_string()];
+    for word in region
+        .split([' ', ','])
+        .filter(|w| !w.is_empty() && *w != "e")
+    {
+        let mut chars = word.chars();
+        name.push(
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default(),
+        );
+    }
+    if new.modality.states_contrast() || new.contrast {
+        name.push(if new.contrast { "COM" } else { "SEM" }.to_string());
+    }
+
+    let mut front = format!(
+        "---\nmodality: {}\nbody_region: {}\n",
+        new.modality.code, region
+    );
+    if new.modality.states_contrast() || new.contrast {
+        front.push_str(&format!("contrast: {}\n", new.contrast));
+    }
+    if let Some(author) = &new.author {
+        front.push_str(&format!("author: {}\n", author));
+    }
+    front.push_str(&format!("created: {}\n---\n", Timestamp::now().iso_date()));
+
+    let footer = config::get().disclaimer.as_deref().unwrap_or(FOOTER);
+    let blocks = [
+        format!("**{} DE {}**", new.modality.title, region.to_uppercase()),
+        format!("**{}:** XXX", SectionKind::IndicacaoClinica.label()),
+        format!(
+            "**{}:** {}",
+            SectionKind::Tecnica.label(),
+            technique(new).unwrap_or_else(|| "XXX".to_string())
+        ),
+        format!("**{}:**\nXXX", SectionKind::AspectosObservados.label()),
+        format!("**{}:**\nXXX", SectionKind::Impressao.label()),
+        format!("*{}*", footer),
+    ];
+    Ok((
+        name.join(" "),
+        format!("{}{}\n", front, blocks.join("\n\n")),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::ReportTemplate;
+
+    #[test]
+    fn new_templates_have_the_standard_skeleton() {
+        let (name, content) = scaffold(&NewTemplate {
+            modality: Modality::parse("tc").unwrap(),
+            region: "torax abdome pelve".to_string(),
+            contrast: true,
+            author: Some("Dr. Fulano".to_string()),
+        })
+        .unwrap();
+        assert_eq!(name, "TC Tórax Abdome Pelve COM");
+        assert!(content.starts_with(
+            "---\nmodality: CT\nbody_region: tórax, abdome e pelve\ncontrast: true\n\
+             author: Dr. Fulano\ncreated: "
+        ));
+        assert!(content.contains(
+            "---\n**TOMOGRAFIA COMPUTADORIZADA DE TÓRAX, ABDOME E PELVE**\n\n\
+             **Indicação clínica:** XXX\n\n**Técnica do exame:** Realizadas aquisições"
+        ));
+
+        let template = ReportTemplate::from_markdown(&content);
+        let kinds: Vec<_> = template.sections.iter().filter_map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                SectionKind::IndicacaoClinica,
+                SectionKind::Tecnica,
+                SectionKind::AspectosObservados,
+                SectionKind::Impressao
+            ]
+        );
+        assert_eq!(template.footer.unwrap().plain(), FOOTER);
+
+        let (name, content) = scaffold(&NewTemplate {
+            modality: Modality::parse("US").unwrap(),
+            region: "joelho".to_string(),
+            contrast: false,
+            author: None,
+        })
+        .unwrap();
+        assert_eq!(name, "US Membro Inferior");
+        assert!(!content.contains("contrast:"));
+        assert!(content.contains("**Técnica do exame:** XXX\n"));
+        assert!(scaffold(&NewTemplate {
+            modality: Modality::parse("RM").unwrap(),
+            region: "xyz".to_string(),
+            contrast: false,
+            author: None,
+        })
+        .is_err());
+    }
+}
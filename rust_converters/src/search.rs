@@ -0,0 +1,397 @@
+//! Full-text search over the Markdown templates.
+//!
+//! Lines are matched without their emphasis markers, so `nódulo pulmonar`
+//! finds `**Nódulo** pulmonar`. Unless the search is case-sensitive, letter
+//! case and accents are ignored as `pick::fold` drops them, so `nodulo` finds
+//! it too. Each hit carries the report section it
+//! belongs to (`titulo`, `indicacao_clinica`, `tecnica`,
+//! `aspectos_observados`, `impressao` or `rodape`, as in the JSON export).
+//! The search can be narrowed to the templates carrying some front matter
+//! `tags`.
+
+use anyhow::{bail, Context, Result};
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::classify::{classify_template, Classification};
+use crate::encoding::read_text;
+use crate::files::{list_files, FileFilter};
+use crate::front_matter;
+use crate::markdown::normalize_heading;
+use crate::pick::fold;
+use crate::template::{plain, Content, ReportTemplate, SectionKind};
+
+/// Section names accepted by `--section`.
+pub const SECTION_KEYS: [&str; 6] = [
+    "titulo",
+    "indicacao_clinica",
+    "tecnica",
+    "aspectos_observados",
+    "impressao",
+    "rodape",
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct SearchOptions {
+    /// Treat the query as a regular expression instead of literal text.
+    pub regex: bool,
+    /// Match letter case and accents exactly.
+    pub case_sensitive: bool,
+    /// Only search lines of this section.
+    pub section: Option<String>,
+    /// Which templates to search, by file name.
+    pub files: FileFilter,
+    /// Only search templates carrying every one of these front matter tags.
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hit {
+    /// 1-based line number in the file.
+    pub line: usize,
+    pub section: Option<&'static str>,
+    /// The line without emphasis markers.
+    pub text: String,
+    /// Byte ranges of the matches in `text`.
+    pub matches: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemplateHits {
+    pub path: PathBuf,
+    /// The template's modality and body region, to group results by.
+    pub classification: Classification,
+    pub hits: Vec<Hit>,
+}
+
+fn is_table_line(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+/// The section of each line of the body of `content`, following the
+/// structure `ReportTemplate::from_markdown` found in it.
+fn line_sections(content: &str) -> Vec<Option<&'static str>> {
+    let lines: Vec<&str> = front_matter::strip(content).lines().collect();
+    let mut sections = vec![None; lines.len()];
+    let template = ReportTemplate::from_markdown(content);
+
+    let first = lines.iter().position(|l| !l.trim().is_empty());
+    let last = lines.iter().rposition(|l| !l.trim().is_empty());
+    let mut cursor = 0;
+    let mut end = lines.len();
+    let title = template.title.is_some() && !template.title_from_front_matter;
+    let footer = template.footer.is_some() && !template.footer_from_front_matter;
+    if let (Some(index), true) = (first, title) {
+        sections[index] = Some("titulo");
+        cursor = index + 1;
+    }
+    if let (Some(index), true) = (last, footer) {
+        sections[index] = Some("rodape");
+        end = index;
+    }
+
+    for section in &template.sections {
+        let key = section
+            .kind
+            .unwrap_or(SectionKind::AspectosObservados)
+            .key();
+        let paragraphs = section
+            .heading
+            .iter()
+            .map(Some)
+            .chain(section.content.iter().map(|content| match content {
+                Content::Paragraph(paragraph) => Some(paragraph),
+                Content::Table { .. } => None,
+            }));
+        for paragraph in paragraphs {
+            let found = match paragraph {
+                Some(paragraph) => (cursor..end).find(|&i| {
+                    !is_table_line(lines[i]) && normalize_heading(lines[i]).0 == paragraph.text
+                }),
+                None => (cursor..end)
+                    .find(|&i| is_table_line(lines[i]))
+                    .map(|start| {
+                        (start..end)
+                            .take_while(|&i| is_table_line(lines[i]))
+                            .last()
+                            .unwrap_or(start)
+                    }),
+            };
+            let Some(found) = found else { continue };
+            for slot in &mut sections[cursor..=found] {
+                *slot = Some(key);
+            }
+            cursor = found + 1;
+        }
+    }
+    sections
+}
+
+/// A compiled query.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    regex: Regex,
+    /// Whether the text is matched folded, without case and accents.
+    fold: bool,
+}
+
+impl Pattern {
+    /// Byte ranges of the non-empty matches in `text`.
+    pub fn find(&self, text: &str) -> Vec<Range<usize>> {
+        if !self.fold {
+            return self
+                .regex
+                .find_iter(text)
+                .map(|m| m.range())
+                .filter(|range| !range.is_empty())
+                .collect();
+        }
+        // `fold` maps a char to one char, but not always of the same length;
+        // `offsets` takes each char boundary of `folded` back to `text`.
+        let mut folded = String::with_capacity(text.len());
+        let mut offsets = Vec::with_capacity(text.len() + 1);
+        for (index, c) in text.char_indices() {
+            let c = fold(c);
+            offsets.extend(std::iter::repeat_n(index, c.len_utf8()));
+            folded.push(c);
+        }
+        offsets.push(text.len());
+        self.regex
+            .find_iter(&folded)
+            .map(|m| offsets[m.start()]..offsets[m.end()])
+            .filter(|range| !range.is_empty())
+            .collect()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.find(text).is_empty()
+    }
+}
+
+/// Hits for `pattern` in one Markdown template.
+pub fn search_content(content: &str, pattern: &Pattern, section: Option<&str>) -> Vec<Hit> {
+    let body = front_matter::strip(content);
+    let offset = content[..content.len() - body.len()].lines().count();
+    let sections = line_sections(content);
+
+    let mut hits = Vec::new();
+    for (index, line) in body.lines().enumerate() {
+        if section.is_some() && sections[index] != section {
+            continue;
+        }
+        let text = plain(&normalize_heading(line).0);
+        let matches = pattern.find(&text);
+        if !matches.is_empty() {
+            hits.push(Hit {
+                line: offset + index + 1,
+                section: sections[index],
+                text,
+                matches,
+            });
+        }
+    }
+    hits
+}
+
+/// Builds the matcher for `query`.
+pub fn pattern(query: &str, options: &SearchOptions) -> Result<Pattern> {
+    let fold_query = !options.case_sensitive;
+    let source = match (options.regex, fold_query) {
+        (false, false) => regex::escape(query),
+        (false, true) => regex::escape(&query.chars().map(fold).collect::<String>()),
+        (true, false) => query.to_string(),
+        // Only the accented letters are folded, so escapes such as `\S` keep
+        // their case; the regex ignores case anyway.
+        (true, true) => query
+            .chars()
+            .map(|c| if c.is_ascii() { c } else { fold(c) })
+            .collect(),
+    };
+    let regex = RegexBuilder::new(&source)
+        .case_insensitive(fold_query)
+        .build()
+        .with_context(|| format!("Invalid search pattern {:?}", query))?;
+    Ok(Pattern {
+        regex,
+        fold: fold_query,
+    })
+}
+
+/// Searches every Markdown template in `dir`; templates without hits are
+/// left out.
+pub fn search(dir: &Path, query: &str, options: &SearchOptions) -> Result<Vec<TemplateHits>> {
+    if let Some(section) = &options.section {
+        if !SECTION_KEYS.contains(&section.as_str()) {
+            bail!(
+                "Unknown section {:?}; expected one of {}",
+                section,
+                SECTION_KEYS.join(", ")
+            );
+        }
+    }
+    let pattern = pattern(query, options)?;
+
+    let mut results = Vec::new();
+    for path in list_files(dir, "md")? {
+        if !options
+            .files
+            .matches(path.strip_prefix(dir).unwrap_or(&path))
+        {
+            continue;
+        }
+        let content = read_text(&path)?;
+        if !options.tags.is_empty() {
+            let front = ReportTemplate::from_markdown(&content).metadata();
+            if !options.tags.iter().all(|tag| front.has_tag(tag)) {
+                continue;
+            }
+        }
+        let hits = search_content(&content, &pattern, options.section.as_deref());
+        if !hits.is_empty() {
+            results.push(TemplateHits {
+                classification: classify_template(&path, &content),
+                path,
+                hits,
+            });
+        }
+    }
+    Ok(results)
+}
+
+const EXCERPT_CHARS: usize = 160;
+const HIGHLIGHT_START: &str = "\x1b[1;31m";
+const HIGHLIGHT_END: &str = "\x1b[0m";
+
+/// `hit.text` for display: long lines are cut to the part around the first
+/// match, and with `color` the matches are highlighted with ANSI escapes.
+pub fn excerpt(hit: &Hit, color: bool) -> String {
+    let text = &hit.text;
+    let mut start = 0;
+    let mut end = text.len();
+    if text.chars().count() > EXCERPT_CHARS {
+        let first = hit.matches[0].start;
+        let before: Vec<usize> = text[..first].char_indices().map(|(i, _)| i).collect();
+        start = before[before.len().saturating_sub(EXCERPT_CHARS / 3)..]
+            .first()
+            .copied()
+            .unwrap_or(first);
+        end = text[start..]
+            .char_indices()
+            .nth(EXCERPT_CHARS)
+            .map_or(text.len(), |(i, _)| start + i);
+    }
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    let mut position = start;
+    for range in &hit.matches {
+        if !color || range.start < position || range.end > end {
+            continue;
+        }
+        out.push_str(&text[position..range.start]);
+        out.push_str(HIGHLIGHT_START);
+        out.push_str(&text[range.clone()]);
+        out.push_str(HIGHLIGHT_END);
+        position = range.end;
+    }
+    out.push_str(&text[position..end]);
+    if end < text.len() {
+        out.push('…');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn default_search_ignores_case_and_accents() {
+        let content = "# TC DE TÓRAX\n\n**Nódulo** pulmonar à direita.\n";
+        let text = |hits: &[Hit]| {
+            hits.iter()
+                .map(|hit| {
+                    let range = hit.matches[0].clone();
+                    (hit.line, hit.text[range].to_string())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let literal = pattern("nodulo", &SearchOptions::default()).unwrap();
+        assert_eq!(
+            text(&search_content(content, &literal, None)),
+            [(3, "Nódulo".into())]
+        );
+        let regex = pattern(
+            r"t[óo]rax\b",
+            &SearchOptions {
+                regex: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            text(&search_content(content, &regex, None)),
+            [(1, "TÓRAX".into())]
+        );
+
+        let exact = SearchOptions {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let unaccented = pattern("Nodulo", &exact).unwrap();
+        assert!(search_content(content, &unaccented, None).is_empty());
+        assert!(pattern("Nódulo", &exact).unwrap().is_match("**Nódulo**"));
+    }
+
+    #[test]
+    fn sections_scope_the_search_and_excerpts_highlight_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("TC.md"),
+            "**TC DE TÓRAX**\n\n**Aspectos observados:**\nSem nódulos.\n\n\
+             **Impressão:**\nNódulo pulmonar.\n\n*A impressão diagnóstica é de probabilidade.*\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("RM.md"),
+            "# RM DE JOELHO\n\nMenisco íntegro.\n",
+        )
+        .unwrap();
+
+        let all = search(dir.path(), "nodulo", &SearchOptions::default()).unwrap();
+        assert_eq!(all.len(), 1);
+        let lines: Vec<usize> = all[0].hits.iter().map(|hit| hit.line).collect();
+        assert_eq!(lines, [4, 7]);
+
+        let options = SearchOptions {
+            section: Some("impressao".to_string()),
+            ..Default::default()
+        };
+        let scoped = search(dir.path(), "nodulo", &options).unwrap();
+        assert_eq!(scoped[0].hits.len(), 1);
+        let hit = &scoped[0].hits[0];
+        assert_eq!(hit.section, Some("impressao"));
+        assert_eq!(excerpt(hit, false), "Nódulo pulmonar.");
+        assert_eq!(
+            excerpt(hit, true),
+            format!("{}Nódulo{} pulmonar.", HIGHLIGHT_START, HIGHLIGHT_END)
+        );
+
+        let unknown = SearchOptions {
+            section: Some("conclusao".to_string()),
+            ..Default::default()
+        };
+        assert!(search(dir.path(), "nodulo", &unknown).is_err());
+
+        let long = format!("{} nódulo {}", "a".repeat(100), "b".repeat(200));
+        let literal = pattern("nodulo", &SearchOptions::default()).unwrap();
+        let cut = excerpt(&search_content(&long, &literal, None)[0], false);
+        assert!(cut.starts_with('…') && cut.ends_with('…') && cut.contains("nódulo"));
+        assert_eq!(cut.chars().count(), EXCERPT_CHARS + 2);
+    }
+}
@@ -0,0 +1,127 @@
+//! Splits a Markdown template into its named report sections.
+//!
+//! Section headings are recognised with the same keyword heuristics as the
+//! TXT and RTF importers, so `**Técnica do exame:**`, `## TÉCNICA DO EXAME`
+//! and `Técnica de exame: ...` all open the `tecnica` section.
+
+use serde::Serialize;
+
+use crate::template::{plain, Content, Paragraph, ReportTemplate, SectionKind};
+
+/// A template as structured data. Text is plain (emphasis markers removed);
+/// lines are joined with `\n` and blank lines between paragraphs are kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ReportSections {
+    pub titulo: Option<String>,
+    pub indicacao_clinica: Option<String>,
+    pub tecnica: Option<String>,
+    pub aspectos_observados: Option<String>,
+    pub impressao: Option<String>,
+    /// Closing disclaimer (the italic last line).
+    pub rodape: Option<String>,
+}
+
+impl ReportSections {
+    /// The section named `key`, as `search::SECTION_KEYS` names them.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        match key {
+            "titulo" => self.titulo.as_deref(),
+            "indicacao_clinica" => self.indicacao_clinica.as_deref(),
+            "tecnica" => self.tecnica.as_deref(),
+            "aspectos_observados" => self.aspectos_observados.as_deref(),
+            "impressao" => self.impressao.as_deref(),
+            "rodape" => self.rodape.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+fn finish(lines: Vec<String>) -> Option<String> {
+    let mut kept: Vec<String> = Vec::new();
+    for line in lines {
+        if line.is_empty() && kept.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        kept.push(line);
+    }
+    while kept.last().is_some_and(|l| l.is_empty()) {
+        kept.pop();
+    }
+    if kept.is_empty() {
+        None
+    } else {
+        Some(kept.join("\n"))
+    }
+}
+
+/// Parses a Markdown template into its sections. Text before the first
+/// recognised heading (other than the title) goes to `aspectos_observados`.
+pub fn parse_sections(markdown: &str) -> ReportSections {
+    ReportSections::from(&ReportTemplate::from_markdown(markdown))
+}
+
+impl From<&ReportTemplate> for ReportSections {
+    fn from(template: &ReportTemplate) -> Self {
+        let mut buckets: [Vec<String>; 4] = Default::default();
+        for section in &template.sections {
+            let bucket =
+                &mut buckets[section.kind.unwrap_or(SectionKind::AspectosObservados) as usize];
+            bucket.extend(section.inline_text());
+            for content in &section.content {
+                match content {
+                    Content::Paragraph(paragraph) => bucket.push(paragraph.plain()),
+                    Content::Table { header, rows } => {
+                        for row in std::iter::once(header).chain(rows) {
+                            let cells: Vec<String> = row.iter().map(|c| plain(c)).collect();
+                            bucket.push(cells.join(" | "));
+                        }
+                    }
+                }
+            }
+        }
+
+        let [indicacao, tecnica, aspectos, impressao] = buckets;
+        ReportSections {
+            titulo: template
+                .title
+                .as_ref()
+                .map(Paragraph::plain)
+                .filter(|l| !l.is_empty()),
+            indicacao_clinica: finish(indicacao),
+            tecnica: finish(tecnica),
+            aspectos_observados: finish(aspectos),
+            impressao: finish(impressao),
+            rodape: template
+                .footer
+                .as_ref()
+                .map(Paragraph::plain)
+                .filter(|l| !l.is_empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headings_in_any_form_open_their_section() {
+        let markdown = "**TC DE CRÂNIO**\n\nAchado inicial.\n\n**Indicação clínica:** cefaleia.\n\n## TÉCNICA DO EXAME\n\nCortes axiais.\n\n| Série | mm |\n|---|---|\n| 1 | 5 |\n\nAchados:\n\nSem alterações.\n\n\n\nNada mais.\n\nImpressão: normal.\n\n*Laudo assinado eletronicamente.*";
+        let sections = parse_sections(markdown);
+        assert_eq!(
+            sections,
+            ReportSections {
+                titulo: Some("TC DE CRÂNIO".into()),
+                indicacao_clinica: Some("cefaleia.".into()),
+                tecnica: Some("Cortes axiais.\nSérie | mm\n1 | 5".into()),
+                aspectos_observados: Some(
+                    "Achado inicial.\n\nSem alterações.\n\nNada mais.".into()
+                ),
+                impressao: Some("normal.".into()),
+                rodape: Some("Laudo assinado eletronicamente.".into()),
+            }
+        );
+        assert_eq!(sections.get("impressao"), Some("normal."));
+        assert_eq!(sections.get("conclusao"), None);
+    }
+}
@@ -0,0 +1,405 @@
+//! A local HTTP server for reporting helpers that keep a template list.
+//!
+//! `GET /templates` returns the Markdown templates as JSON, and `GET /events`
+//! opens a WebSocket on which each template created or changed on disk is
+//! announced (found by the same watcher as `convert --watch`), so a helper
+//! reloads its list without polling. Plain HTTP/1.1 and RFC 6455 on
+//! `std::net` are enough for that: the server only sends on the WebSocket
+//! and never reads what clients send.
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::pick::candidates;
+use crate::watch::watch_in_background;
+
+/// Appended to the client's key to prove the server speaks WebSocket.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Longest request head read, in bytes.
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// One entry of `GET /templates`.
+#[derive(Serialize)]
+struct TemplateEntry {
+    file: String,
+    /// The title the picker shows.
+    title: String,
+}
+
+/// What `/events` sends when a template is written.
+#[derive(Serialize)]
+struct ChangeEvent<'a> {
+    event: &'static str,
+    file: &'a str,
+}
+
+/// The clients connected to `/events`.
+#[derive(Clone, Default)]
+pub struct Clients(Arc<Mutex<Vec<TcpStream>>>);
+
+impl Clients {
+    fn add(&self, stream: TcpStream) {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(stream);
+    }
+
+    /// Sends `message` to every client as a text frame, forgetting those
+    /// that are gone; returns how many it reached.
+    pub fn broadcast(&self, message: &str) -> usize {
+        let frame = text_frame(message);
+        let mut clients = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        clients.retain_mut(|stream| stream.write_all(&frame).is_ok());
+        clients.len()
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    dir: PathBuf,
+    clients: Clients,
+}
+
+impl Server {
+    /// Listens on `addr` for the templates in `dir`.
+    pub fn bind(addr: impl ToSocketAddrs, dir: &Path) -> Result<Self> {
+        if !dir.is_dir() {
+            bail!("Markdown folder not found at {}", dir.display());
+        }
+        let listener = TcpListener::bind(addr).context("Cannot listen")?;
+        Ok(Server {
+            listener,
+            dir: dir.to_path_buf(),
+            clients: Clients::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serves until the process ends, each connection on its own thread.
+    pub fn run(self) -> Result<()> {
+        let clients = self.clients.clone();
+        watch_in_background(&self.dir, "md", move |path| {
+            let file = path.file_name().unwrap_or_default().to_string_lossy();
+            let reached = clients.broadcast(&serde_json::to_string(&ChangeEvent {
+                event: "changed",
+                file: &file,
+            })?);
+            info!("{} changed, told {} clients", file, reached);
+            Ok(())
+        })?;
+
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("Connection failed: {}", err);
+                    continue;
+                }
+            };
+            let (dir, clients) = (self.dir.clone(), self.clients.clone());
+            thread::spawn(move || {
+                if let Err(err) = handle(stream, &dir, &clients) {
+                    warn!("Request failed: {:#}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The request line's method and path, and the headers with lowercase
+/// names.
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut lines = Vec::new();
+    let mut read = 0;
+    loop {
+        let mut line = String::new();
+        read += reader.read_line(&mut line)?;
+        if read > MAX_HEAD_BYTES {
+            bail!("Request head too long");
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line.to_string());
+    }
+    let request_line = lines.first().map(String::as_str).unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        bail!("Malformed request line {:?}", request_line);
+    };
+    let headers = lines[1..]
+        .iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        headers,
+    })
+}
+
+fn respond(mut stream: &TcpStream, status: &str, content_type: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+fn handle(stream: TcpStream, dir: &Path, clients: &Clients) -> Result<()> {
+    let request = read_request(&stream)?;
+    if request.method != "GET" {
+        return respond(
+            &stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            "GET only\n",
+        );
+    }
+    // Query strings (cache busters) do not change the answer.
+    match request.path.split('?').next().unwrap_or_default() {
+        "/templates" => {
+            let entries: Vec<TemplateEntry> = candidates(dir)?
+                .into_iter()
+                .map(|candidate| TemplateEntry {
+                    file: candidate
+                        .path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned(),
+                    title: candidate.title,
+                })
+                .collect();
+            let body = serde_json::to_string(&entries)?;
+            respond(&stream, "200 OK", "application/json; charset=utf-8", &body)
+        }
+        "/events" => {
+            let upgrade = request
+                .header("upgrade")
+                .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+            let Some(key) = request.header("sec-websocket-key").filter(|_| upgrade) else {
+                return respond(
+                    &stream,
+                    "400 Bad Request",
+                    "text/plain",
+                    "/events is a WebSocket\n",
+                );
+            };
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(key)
+            )?;
+            clients.add(stream);
+            Ok(())
+        }
+        _ => respond(&stream, "404 Not Found", "text/plain", "Not found\n"),
+    }
+}
+
+/// The `Sec-WebSocket-Accept` answering a client's `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(
+        format!("{}{}", key.trim(), WEBSOCKET_GUID).as_bytes(),
+    ))
+}
+
+/// An unmasked, final WebSocket text frame, as servers send them.
+fn text_frame(payload: &str) -> Vec<u8> {
+    let bytes = payload.as_bytes();
+    let mut frame = vec![0x81];
+    match bytes.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(bytes);
+    frame
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// SHA-1, which the WebSocket handshake requires; nothing else here relies
+/// on it.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let next = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, next);
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (bytes, value) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Read;
+    use std::time::Duration;
+
+    #[test]
+    fn event_clients_hear_of_each_template_written() {
+        // The example handshake of RFC 6455, section 1.3.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(text_frame("ok"), b"\x81\x02ok");
+        assert_eq!(text_frame(&"x".repeat(200))[..4], [0x81, 126, 0, 200]);
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("TC Tórax.md"),
+            "**TC DE TÓRAX**\n\nTexto.\n",
+        )
+        .unwrap();
+        let server = Server::bind("127.0.0.1:0", dir.path()).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let request = |head: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            stream.write_all(head.as_bytes()).unwrap();
+            stream
+        };
+        let mut response = String::new();
+        request("GET /templates HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .read_to_string(&mut response)
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(
+            response.ends_with(r#"[{"file":"TC Tórax.md","title":"TC DE TÓRAX (TC Tórax)"}]"#),
+            "{response}"
+        );
+        let mut response = String::new();
+        request("GET /events HTTP/1.1\r\n\r\n")
+            .read_to_string(&mut response)
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"), "{response}");
+
+        let mut events = request(
+            "GET /events HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        );
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            events.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        let head = String::from_utf8(head).unwrap();
+        assert!(head.starts_with("HTTP/1.1 101 "), "{head}");
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        fs::write(dir.path().join("RM Joelho.md"), "**RM DO JOELHO**\n").unwrap();
+        let mut header = [0u8; 2];
+        events.read_exact(&mut header).unwrap();
+        assert_eq!(header[0], 0x81);
+        let mut payload = vec![0u8; header[1] as usize];
+        events.read_exact(&mut payload).unwrap();
+        assert_eq!(
+            String::from_utf8(payload).unwrap(),
+            r#"{"event":"changed","file":"RM Joelho.md"}"#
+        );
+    }
+}
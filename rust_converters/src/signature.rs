@@ -0,0 +1,115 @@
+//! Signature blocks (`convert --signer <id>`), for departments where
+//! several radiologists use the same templates but sign them differently.
+//!
+//! Each radiologist has a profile in `rtemplates.toml` (`[signatures.<id>]`:
+//! name, CRM and specialty). The signer's block is added to the filled
+//! Markdown before the footer line, a line each, the name in bold, so every
+//! format written from it (DOCX, TXT, PDF and the others) carries it as a
+//! part of the body. A template whose footer comes from the front matter,
+//! or that has none, gets the block at the end.
+
+use anyhow::{anyhow, Result};
+
+use crate::config::{self, Signature};
+use crate::front_matter;
+use crate::template::ReportTemplate;
+
+/// The profile `id` of the config.
+pub fn profile(id: &str) -> Result<Signature> {
+    let signatures = &config::get().signatures;
+    if let Some((_, signature)) = signatures.iter().find(|(name, _)| name == id) {
+        return Ok(signature.clone());
+    }
+    if signatures.is_empty() {
+        return Err(anyhow!(
+            "Unknown signer `{}`: rtemplates.toml has no [signatures]",
+            id
+        ));
+    }
+    let ids: Vec<&str> = signatures.iter().map(|(name, _)| name.as_str()).collect();
+    Err(anyhow!(
+        "Unknown signer `{}`; expected one of {}",
+        id,
+        ids.join(", ")
+    ))
+}
+
+/// The Markdown lines of `signature`: the name in bold, then the CRM and
+/// the specialty when set.
+pub fn lines(signature: &Signature) -> Vec<String> {
+    let mut lines = vec![format!("**{}**", signature.name.trim())];
+    lines.extend(
+        [&signature.crm, &signature.specialty]
+            .into_iter()
+            .flatten()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty()),
+    );
+    lines
+}
+
+/// The Markdown template `content` with `signature` before its footer.
+pub fn sign(content: &str, signature: &Signature) -> String {
+    let template = ReportTemplate::from_markdown(content);
+    let body = front_matter::strip(content);
+    let front = &content[..content.len() - body.len()];
+    let pieces: Vec<&str> = body.split_inclusive('\n').collect();
+    let before_footer = if template.footer.is_some() && !template.footer_from_front_matter {
+        pieces.iter().rposition(|line| !line.trim().is_empty())
+    } else {
+        None
+    };
+    let (head, tail) = pieces.split_at(before_footer.unwrap_or(pieces.len()));
+
+    let mut signed = front.to_string() + &head.concat();
+    if !signed.is_empty() && !signed.ends_with('\n') {
+        signed.push('\n');
+    }
+    if head.last().is_some_and(|line| !line.trim().is_empty()) {
+        signed.push('\n');
+    }
+    signed.push_str(&lines(signature).join("\n"));
+    signed.push('\n');
+    if !tail.is_empty() {
+        signed.push('\n');
+        signed.push_str(&tail.concat());
+    }
+    signed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::convert_to_txt::markdown_text;
+
+    #[test]
+    fn the_signature_goes_before_the_footer() {
+        let config = Config::parse(
+            "[signatures.fulano]\nname = \"Dr. Fulano de Tal\"\ncrm = \"CRM-SP 123456\"\n\
+             specialty = \"Radiologia\"\n\n[signatures.beltrana]\nname = \"Dra. Beltrana\"\n",
+        )
+        .unwrap();
+        let (id, fulano) = &config.signatures[0];
+        assert_eq!(id, "fulano");
+        assert_eq!(
+            lines(fulano),
+            ["**Dr. Fulano de Tal**", "CRM-SP 123456", "Radiologia"]
+        );
+        assert!(Config::parse("[signatures.x]\ncrm = \"CRM 1\"\n").is_err());
+
+        let beltrana = &config.signatures[1].1;
+        let template = "**US ABDOME**\n\n**Impressão:**\nNormal.\n\n*Rodapé.*\n";
+        let signed = sign(template, beltrana);
+        assert_eq!(
+            signed,
+            "**US ABDOME**\n\n**Impressão:**\nNormal.\n\n**Dra. Beltrana**\n\n*Rodapé.*\n"
+        );
+        let parsed = ReportTemplate::from_markdown(&signed);
+        assert_eq!(parsed.footer.unwrap().text, "*Rodapé.*");
+        assert_eq!(
+            markdown_text(&sign("---\nfooter: Rodapé\n---\nTC\n\nNormal.", beltrana)),
+            "TC\n\nNormal.\n\nDra. Beltrana\n"
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! A static website of the template library (`rtemplates site`), for
+//! browsing and copying templates without any of the tools installed.
+//!
+//! `index.html` lists the modalities and has a search box; each modality
+//! gets a page (`tomografia-computadorizada.html`) holding its templates
+//! grouped by body region as in the catalog, each with a button copying
+//! its plain text (as TXT export writes it) for pasting into the RIS. The
+//! search runs in the browser over `search.js`, so the site works from a
+//! shared folder or `file://` as well as from a web server.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::catalog::{self, Group};
+use crate::convert_to_html::{body_html, escape_html, page_html};
+use crate::convert_to_txt::markdown_text;
+use crate::files::FileFilter;
+use crate::pick::fold;
+
+const TITLE: &str = "Modelos de laudo";
+
+const SITE_CSS: &str = "\
+header { border-bottom: 1px solid #999; margin-bottom: 1.5em; padding-bottom: 0.5em; }
+header a { margin-right: 1em; }
+nav ul { list-style: none; padding-left: 1.5em; }
+nav > ul { padding-left: 0; }
+#search { font-size: 1.1em; padding: 0.3em; width: 100%; box-sizing: border-box; }
+#results li { margin: 0.3em 0; }
+#results .group { color: #666; font-size: 0.9em; margin-left: 0.5em; }
+.template { border-top: 1px solid #999; margin-top: 2em; padding-top: 1em; }
+.template h3 { display: inline-block; margin-right: 1em; }
+.copy { cursor: pointer; font-size: 0.9em; }
+.copy-text { display: none; }
+";
+
+/// Copies the plain text of a template; `navigator.clipboard` is missing
+/// outside secure contexts, where the selection is copied instead.
+const SITE_JS: &str = r#"function copyTemplate(button) {
+  var text = document.getElementById(button.dataset.copy).textContent;
+  var done = function () {
+    var label = button.dataset.label || (button.dataset.label = button.textContent);
+    button.textContent = "Copiado ✓";
+    setTimeout(function () { button.textContent = label; }, 1500);
+  };
+  if (navigator.clipboard && window.isSecureContext) {
+    navigator.clipboard.writeText(text).then(done);
+    return;
+  }
+  var area = document.createElement("textarea");
+  area.value = text;
+  document.body.appendChild(area);
+  area.select();
+  document.execCommand("copy");
+  document.body.removeChild(area);
+  done();
+}
+
+function fold(text) {
+  return text.toLowerCase().normalize("NFD").replace(/[\u0300-\u036f]/g, "");
+}
+
+function search(query) {
+  var words = fold(query).split(/\s+/).filter(Boolean);
+  var results = document.getElementById("results");
+  var browse = document.getElementById("browse");
+  results.innerHTML = "";
+  browse.hidden = words.length > 0;
+  if (!words.length) return;
+  TEMPLATES.filter(function (t) {
+    return words.every(function (w) { return t.name_folded.indexOf(w) >= 0 || t.text.indexOf(w) >= 0; });
+  }).forEach(function (t) {
+    var item = document.createElement("li");
+    var link = document.createElement("a");
+    link.href = t.href;
+    link.textContent = t.name;
+    var group = document.createElement("span");
+    group.className = "group";
+    group.textContent = t.group;
+    item.appendChild(link);
+    item.appendChild(group);
+    results.appendChild(item);
+  });
+  if (!results.children.length) {
+    results.innerHTML = "<li>Nenhum modelo encontrado.</li>";
+  }
+}
+"#;
+
+/// One template in `search.js`.
+#[derive(Serialize)]
+struct SearchEntry<'a> {
+    name: &'a str,
+    /// Lowercase and without accents, as `fold` in the page's script.
+    name_folded: String,
+    group: String,
+    href: String,
+    text: String,
+}
+
+/// What `build_site` wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Site {
+    pub pages: Vec<PathBuf>,
+    pub templates: usize,
+}
+
+fn folded(text: &str) -> String {
+    text.chars().map(fold).collect()
+}
+
+fn page_name(modality_anchor: &str) -> String {
+    format!("{}.html", modality_anchor)
+}
+
+fn header_html(groups: &[Group]) -> String {
+    let mut header = format!("<header>\n<a href=\"index.html\">{}</a>", TITLE);
+    for (i, group) in groups.iter().enumerate() {
+        if i == 0 || groups[i - 1].modality != group.modality {
+            header.push_str(&format!(
+                "<a href=\"{}\">{}</a>",
+                escape_html(&page_name(&group.modality_anchor)),
+                escape_html(&group.modality)
+            ));
+        }
+    }
+    header.push_str("\n</header>\n");
+    header
+}
+
+fn site_page(title: &str, groups: &[Group], body: &str, scripts: &[&str]) -> String {
+    let mut body = format!("{}{}", header_html(groups), body);
+    for script in scripts {
+        body.push_str(&format!("<script src=\"{}\"></script>\n", script));
+    }
+    page_html(title, None, SITE_CSS, &body)
+}
+
+/// The index page: the modalities and their regions, replaced by the
+/// search results while the search box has text.
+fn index_html(groups: &[Group]) -> String {
+    let mut body = format!(
+        "<h1 class=\"left level1\">{}</h1>\n\
+         <input id=\"search\" type=\"search\" placeholder=\"Buscar modelos (ex.: derrame pleural)\" \
+         autofocus oninput=\"search(this.value)\">\n<ul id=\"results\"></ul>\n\
+         <nav id=\"browse\">\n<ul>\n",
+        TITLE
+    );
+    for (i, group) in groups.iter().enumerate() {
+        let page = page_name(&group.modality_anchor);
+        if i == 0 || groups[i - 1].modality != group.modality {
+            if i > 0 {
+                body.push_str("</ul></li>\n");
+            }
+            let count: usize = groups
+                .iter()
+                .filter(|other| other.modality == group.modality)
+                .map(|other| other.entries.len())
+                .sum();
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a> ({})<ul>\n",
+                escape_html(&page),
+                escape_html(&group.modality),
+                count
+            ));
+        }
+        body.push_str(&format!(
+            "<li><a href=\"{}#{}\">{}</a> ({})</li>\n",
+            escape_html(&page),
+            escape_html(&group.anchor),
+            escape_html(&group.region),
+            group.entries.len()
+        ));
+    }
+    if !groups.is_empty() {
+        body.push_str("</ul></li>\n");
+    }
+    body.push_str("</ul>\n</nav>\n");
+    site_page(TITLE, groups, &body, &["search.js", "site.js"])
+}
+
+/// The page of the modality whose groups are `modality`.
+fn modality_html(groups: &[Group], modality: &[Group]) -> String {
+    let name = &modality[0].modality;
+    let mut nav = String::from("<nav>\n<ul>\n");
+    let mut sections = String::new();
+    for group in modality {
+        nav.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a><ul>\n",
+            escape_html(&group.anchor),
+            escape_html(&group.region)
+        ));
+        sections.push_str(&format!(
+            "<h2 id=\"{}\" class=\"left level2\">{}</h2>\n",
+            escape_html(&group.anchor),
+            escape_html(&group.region)
+        ));
+        for entry in &group.entries {
+            let anchor = escape_html(&entry.anchor);
+            nav.push_str(&format!(
+                "<li><a href=\"#{}\">{}</a></li>\n",
+                anchor,
+                escape_html(&entry.name)
+            ));
+            sections.push_str(&format!(
+                "<section class=\"template\" id=\"{anchor}\">\n\
+                 <h3 class=\"left level3\">{}</h3>\
+                 <button class=\"copy\" data-copy=\"text-{anchor}\" onclick=\"copyTemplate(this)\">Copiar texto</button>\n\
+                 {}<pre class=\"copy-text\" id=\"text-{anchor}\">{}</pre>\n</section>\n",
+                escape_html(&entry.name),
+                body_html(&entry.template),
+                escape_html(markdown_text(&entry.content).trim_end()),
+            ));
+        }
+        nav.push_str("</ul></li>\n");
+    }
+    nav.push_str("</ul>\n</nav>\n");
+    let body = format!(
+        "<h1 class=\"left level1\">{}</h1>\n{}{}",
+        escape_html(name),
+        nav,
+        sections
+    );
+    site_page(
+        &format!("{} · {}", name, TITLE),
+        groups,
+        &body,
+        &["site.js"],
+    )
+}
+
+/// `search.js`: every template's name, group, page and folded plain text.
+fn search_js(groups: &[Group]) -> Result<String> {
+    let mut entries = Vec::new();
+    for group in groups {
+        for entry in &group.entries {
+            entries.push(SearchEntry {
+                name: &entry.name,
+                name_folded: folded(&entry.name),
+                group: format!("{} · {}", group.modality, group.region),
+                href: format!("{}#{}", page_name(&group.modality_anchor), entry.anchor),
+                text: folded(&markdown_text(&entry.content)),
+            });
+        }
+    }
+    Ok(format!(
+        "var TEMPLATES = {};\n",
+        serde_json::to_string(&entries)?
+    ))
+}
+
+/// Writes the site of the Markdown templates in `dir` selected by `files`
+/// to `out_dir`, replacing the files of an earlier build.
+pub fn build_site(dir: &Path, files: &FileFilter, out_dir: &Path) -> Result<Site> {
+    let groups = catalog::build(dir, files)?;
+    fs::create_dir_all(out_dir).with_context(|| format!("Cannot create {}", out_dir.display()))?;
+    let mut site = Site {
+        templates: groups.iter().map(|group| group.entries.len()).sum(),
+        ..Site::default()
+    };
+    let mut write = |name: &str, content: String| -> Result<()> {
+        let path = out_dir.join(name);
+        fs::write(&path, content).with_context(|| format!("Cannot write {}", path.display()))?;
+        if name.ends_with(".html") {
+            site.pages.push(path);
+        }
+        Ok(())
+    };
+    write("index.html", index_html(&groups))?;
+    for modality in groups.chunk_by(|a, b| a.modality == b.modality) {
+        write(
+            &page_name(&modality[0].modality_anchor),
+            modality_html(&groups, modality),
+        )?;
+    }
+    write("search.js", search_js(&groups)?)?;
+    write("site.js", SITE_JS.to_string())?;
+    Ok(site)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn site_has_index_modality_pages_and_search_data() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("site");
+        fs::write(
+            dir.path().join("TC Tórax.md"),
+            "TOMOGRAFIA DO TÓRAX\n\n**Impressão:** Derrame pleural “laminar”.\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("RM Joelho.md"),
+            "RESSONÂNCIA DO JOELHO\n\n**Impressão:** Normal.\n",
+        )
+        .unwrap();
+
+        let site = build_site(dir.path(), &FileFilter::default(), &out).unwrap();
+        assert_eq!(site.templates, 2);
+        assert_eq!(
+            site.pages,
+            [
+                out.join("index.html"),
+                out.join("tomografia-computadorizada.html"),
+                out.join("ressonancia-magnetica.html"),
+            ]
+        );
+
+        let index = fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(index.contains("id=\"search\""));
+        assert!(index.contains(
+            "<a href=\"tomografia-computadorizada.html#tomografia-computadorizada-torax\">Tórax</a> (1)"
+        ));
+        let page = fs::read_to_string(out.join("tomografia-computadorizada.html")).unwrap();
+        assert!(page.contains("data-copy=\"text-tc-torax\""));
+        assert!(page.contains(
+            "<pre class=\"copy-text\" id=\"text-tc-torax\">TOMOGRAFIA DO TÓRAX\n\nImpressão: Derrame pleural &quot;laminar&quot;.</pre>"
+        ));
+        let search = fs::read_to_string(out.join("search.js")).unwrap();
+        assert!(search.contains("\"href\":\"tomografia-computadorizada.html#tc-torax\""));
+        assert!(search.contains("impressao: derrame pleural"));
+    }
+}
@@ -0,0 +1,475 @@
+//! Export of the templates as text expander snippets (`rtemplates
+//! snippets`), so a template is typed out wherever its abbreviation is
+//! typed: an espanso match file (`.yml`), an AutoHotkey v2 hotstring script
+//! (`.ahk`), a TextExpander group (`.textexpander`, which aText imports
+//! too), an aText CSV (`.csv`) or a macOS Text Replacements list
+//! (`.plist`), which iCloud syncs to the iPhone and iPad keyboard.
+//!
+//! Each template's trigger is the `[snippets] prefix` (`:` built in)
+//! followed by its file name in lowercase ASCII, `TC Crânio SEM` giving
+//! `:tc-cranio-sem`; `[snippets.triggers]` sets any template's trigger by
+//! hand; an alias of a template gets a snippet of its own, named and
+//! triggered by the alias. The text is the plain text TXT export writes. With sections
+//! selected, each template gets a snippet per section instead,
+//! `:tc-cranio-sem-impressao` typing its impression alone. Text
+//! replacements are meant for short phrases, so the `.plist` only holds the
+//! snippets up to `[snippets] max_length` characters.
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::aliases;
+use crate::catalog::anchor;
+use crate::config;
+use crate::convert_to_html::escape_html;
+use crate::convert_to_txt::markdown_text;
+use crate::files::{list_files, FileFilter};
+use crate::fill::FillOptions;
+use crate::search::SECTION_KEYS;
+use crate::sections::parse_sections;
+use crate::typography;
+
+/// Name of the TextExpander group.
+const GROUP_NAME: &str = "Modelos de laudo";
+
+#[derive(Clone, Debug, Default)]
+pub struct SnippetOptions {
+    /// Overrides `[snippets] prefix`.
+    pub prefix: Option<String>,
+    /// Sections to export, by `SECTION_KEYS` name; empty for whole
+    /// templates.
+    pub sections: Vec<String>,
+    /// Leave out snippets longer than this many characters; `.plist` files
+    /// default to `[snippets] max_length`.
+    pub max_length: Option<usize>,
+    pub files: FileFilter,
+}
+
+/// One abbreviation and the text it expands to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snippet {
+    pub trigger: String,
+    /// Shown in the expander's search or snippet list.
+    pub label: String,
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnippetFormat {
+    Espanso,
+    Autohotkey,
+    TextExpander,
+    Csv,
+    TextReplacements,
+}
+
+impl SnippetFormat {
+    /// The format of a snippet file named `path`, by its extension.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        Ok(match extension.as_str() {
+            "yml" | "yaml" => SnippetFormat::Espanso,
+            "ahk" => SnippetFormat::Autohotkey,
+            "textexpander" => SnippetFormat::TextExpander,
+            "csv" => SnippetFormat::Csv,
+            "plist" => SnippetFormat::TextReplacements,
+            _ => bail!(
+                "Unknown snippet format {}; expected .yml (espanso), .ahk (AutoHotkey), \
+                 .textexpander (TextExpander, aText), .csv (aText) or .plist (macOS text replacements)",
+                path.display()
+            ),
+        })
+    }
+
+    pub fn write(self, snippets: &[Snippet]) -> String {
+        match self {
+            SnippetFormat::Espanso => to_espanso(snippets),
+            SnippetFormat::Autohotkey => to_autohotkey(snippets),
+            SnippetFormat::TextExpander => to_textexpander(snippets),
+            SnippetFormat::Csv => to_csv(snippets),
+            SnippetFormat::TextReplacements => to_text_replacements(snippets),
+        }
+    }
+
+    /// The longest snippet written, in characters: `options.max_length`, or
+    /// for text replacements `[snippets] max_length`.
+    pub fn max_length(self, options: &SnippetOptions) -> Option<usize> {
+        match self {
+            SnippetFormat::TextReplacements => options
+                .max_length
+                .or(Some(config::get().snippets.max_length)),
+            _ => options.max_length,
+        }
+    }
+}
+
+/// The snippets of the Markdown templates in `dir` selected by `options`.
+pub fn snippets(dir: &Path, options: &SnippetOptions) -> Result<Vec<Snippet>> {
+    for section in &options.sections {
+        if !SECTION_KEYS.contains(&section.as_str()) {
+            bail!(
+                "Unknown section {:?}; expected one of {}",
+                section,
+                SECTION_KEYS.join(", ")
+            );
+        }
+    }
+    let settings = &config::get().snippets;
+    let prefix = options.prefix.as_deref().unwrap_or(&settings.prefix);
+    let fill = FillOptions::default();
+    let mut used = HashSet::new();
+    let mut snippets = Vec::new();
+    for path in list_files(dir, "md")? {
+        if !options
+            .files
+            .matches(path.strip_prefix(dir).unwrap_or(&path))
+        {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let content = fill.read(&path)?;
+        let aliases = aliases::of(&path, &content);
+        for name in std::iter::once(name).chain(aliases) {
+            let base = match settings
+                .triggers
+                .iter()
+                .rev()
+                .find(|(stem, _)| *stem == name)
+            {
+                Some((_, trigger)) => trigger.clone(),
+                None => format!("{}{}", prefix, anchor(&name, &mut used)),
+            };
+            if options.sections.is_empty() {
+                snippets.push(Snippet {
+                    trigger: base,
+                    label: name,
+                    text: markdown_text(&content).trim_end().to_string(),
+                });
+                continue;
+            }
+            let sections = parse_sections(&content);
+            for key in &options.sections {
+                let Some(text) = sections.get(key) else {
+                    continue;
+                };
+                snippets.push(Snippet {
+                    trigger: format!("{}-{}", base, key.replace('_', "-")),
+                    label: format!("{} ({})", name, key),
+                    text: typography::to_plain(text, &config::get().txt),
+                });
+            }
+        }
+    }
+    Ok(snippets)
+}
+
+/// A YAML scalar holding `text`: a literal block when the text allows one,
+/// else a double-quoted string (JSON strings are YAML).
+fn yaml_scalar(text: &str, indent: &str) -> String {
+    let first = text.lines().find(|line| !line.trim().is_empty());
+    let literal = first.is_some_and(|line| !line.starts_with([' ', '\t'])) && !text.contains('\r');
+    if !literal {
+        return serde_json::to_string(text).unwrap_or_default();
+    }
+    let mut block = String::from("|-");
+    for line in text.lines() {
+        block.push('\n');
+        if !line.is_empty() {
+            block.push_str(indent);
+            block.push_str(line);
+        }
+    }
+    block
+}
+
+/// An espanso match file.
+pub fn to_espanso(snippets: &[Snippet]) -> String {
+    let mut yaml = String::from(
+        "# Written by `rtemplates snippets`; edit the templates and export again.\nmatches:\n",
+    );
+    for snippet in snippets {
+        yaml.push_str(&format!(
+            "  - trigger: {}\n    label: {}\n    replace: {}\n",
+            serde_json::to_string(&snippet.trigger).unwrap_or_default(),
+            serde_json::to_string(&snippet.label).unwrap_or_default(),
+            yaml_scalar(&snippet.text, "      ")
+        ));
+    }
+    yaml
+}
+
+/// `text` as an AutoHotkey v2 string literal.
+fn ahk_string(text: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '`' => literal.push_str("``"),
+            '"' => literal.push_str("`\""),
+            '\n' => literal.push_str("`n"),
+            '\r' => literal.push_str("`r"),
+            '\t' => literal.push_str("`t"),
+            _ => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// An AutoHotkey v2 script with a hotstring per snippet. The text is pasted
+/// through the clipboard, which is restored after, as typing out a whole
+/// template key by key is slow.
+pub fn to_autohotkey(snippets: &[Snippet]) -> String {
+    let mut script = String::from(
+        "; Written by `rtemplates snippets`; edit the templates and export again.\n\
+         #Requires AutoHotkey v2.0\n\n\
+         PasteTemplate(text) {\n\
+         \x20   saved := ClipboardAll()\n\
+         \x20   A_Clipboard := text\n\
+         \x20   ClipWait 2\n\
+         \x20   Send \"^v\"\n\
+         \x20   Sleep 250\n\
+         \x20   A_Clipboard := saved\n\
+         }\n",
+    );
+    for snippet in snippets {
+        // Colons and backticks in the abbreviation are escaped.
+        let trigger = snippet.trigger.replace('`', "``").replace(':', "`:");
+        script.push_str(&format!(
+            "\n; {}\n:X:{}::PasteTemplate({})\n",
+            snippet.label.replace('\n', " "),
+            trigger,
+            ahk_string(&snippet.text)
+        ));
+    }
+    script
+}
+
+/// A TextExpander snippet group (an XML property list), which aText
+/// imports as well. `%` starts a TextExpander macro, so it is doubled.
+pub fn to_textexpander(snippets: &[Snippet]) -> String {
+    let mut plist = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n<dict>\n\
+         \t<key>groupInfo</key>\n\t<dict>\n\t\t<key>groupName</key>\n\t\t<string>{}</string>\n\t</dict>\n\
+         \t<key>snippetsTE2</key>\n\t<array>\n",
+        escape_html(GROUP_NAME)
+    );
+    for snippet in snippets {
+        plist.push_str(&format!(
+            "\t\t<dict>\n\
+             \t\t\t<key>abbreviation</key>\n\t\t\t<string>{}</string>\n\
+             \t\t\t<key>abbreviationMode</key>\n\t\t\t<integer>0</integer>\n\
+             \t\t\t<key>label</key>\n\t\t\t<string>{}</string>\n\
+             \t\t\t<key>plainText</key>\n\t\t\t<string>{}</string>\n\
+             \t\t\t<key>snippetType</key>\n\t\t\t<integer>0</integer>\n\
+             \t\t</dict>\n",
+            escape_html(&snippet.trigger),
+            escape_html(&snippet.label),
+            escape_html(&snippet.text.replace('%', "%%"))
+        ));
+    }
+    plist.push_str("\t</array>\n</dict>\n</plist>\n");
+    plist
+}
+
+pub(crate) fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// aText's CSV: abbreviation, content and label on each row.
+pub fn to_csv(snippets: &[Snippet]) -> String {
+    snippets
+        .iter()
+        .map(|snippet| {
+            format!(
+                "{},{},{}\n",
+                csv_field(&snippet.trigger),
+                csv_field(&snippet.text),
+                csv_field(&snippet.label)
+            )
+        })
+        .collect()
+}
+
+/// A macOS Text Replacements list, as System Settings exports it
+/// (Keyboard, Text Replacements) and takes back by dragging the file in.
+pub fn to_text_replacements(snippets: &[Snippet]) -> String {
+    let mut plist = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n<array>\n",
+    );
+    for snippet in snippets {
+        plist.push_str(&format!(
+            "\t<dict>\n\t\t<key>phrase</key>\n\t\t<string>{}</string>\n\
+             \t\t<key>shortcut</key>\n\t\t<string>{}</string>\n\t</dict>\n",
+            escape_html(&snippet.text),
+            escape_html(&snippet.trigger)
+        ));
+    }
+    plist.push_str("</array>\n</plist>\n");
+    plist
+}
+
+/// Writes the snippets of the templates in `dir` to `output`, in the format
+/// its extension names, and returns how many it holds.
+pub fn write_snippets(dir: &Path, options: &SnippetOptions, output: &Path) -> Result<usize> {
+    let format = SnippetFormat::from_path(output)?;
+    let mut snippets = snippets(dir, options)?;
+    if let Some(limit) = format.max_length(options) {
+        let count = snippets.len();
+        snippets.retain(|snippet| snippet.text.chars().count() <= limit);
+        if snippets.len() < count {
+            log::warn!(
+                "{} snippets longer than {} characters left out of {}",
+                count - snippets.len(),
+                limit,
+                output.display()
+            );
+        }
+    }
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, format.write(&snippets))
+        .with_context(|| format!("Cannot write {}", output.display()))?;
+    Ok(snippets.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_and_sections_become_snippets() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("TC Crânio SEM.md"),
+            "**TOMOGRAFIA DO CRÂNIO**\n\n**Técnica:**\n\nAquisição axial.\n\n**Impressão:**\n\nSem alterações – normal.\n\n*Médico responsável*\n",
+        )
+        .unwrap();
+
+        let whole = snippets(dir.path(), &SnippetOptions::default()).unwrap();
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole[0].trigger, ":tc-cranio-sem");
+        assert_eq!(
+            to_espanso(&whole),
+            "# Written by `rtemplates snippets`; edit the templates and export again.\n\
+             matches:\n  - trigger: \":tc-cranio-sem\"\n    label: \"TC Crânio SEM\"\n    replace: |-\n      \
+             TOMOGRAFIA DO CRÂNIO\n\n      Técnica:\n\n      Aquisição axial.\n\n      Impressão:\n\n      \
+             Sem alterações - normal.\n\n      Médico responsável\n"
+        );
+
+        let options = SnippetOptions {
+            prefix: Some(";".to_string()),
+            sections: vec!["impressao".to_string(), "indicacao_clinica".to_string()],
+            ..SnippetOptions::default()
+        };
+        assert_eq!(
+            snippets(dir.path(), &options).unwrap(),
+            [Snippet {
+                trigger: ";tc-cranio-sem-impressao".to_string(),
+                label: "TC Crânio SEM (impressao)".to_string(),
+                text: "Sem alterações - normal.".to_string(),
+            }]
+        );
+        assert_eq!(yaml_scalar("  recuado", "  "), "\"  recuado\"");
+
+        let snippet = [Snippet {
+            trigger: ":rx".to_string(),
+            label: "RX".to_string(),
+            text: "Índice \"cardiotorácico\" < 50%,\nnormal.".to_string(),
+        }];
+        assert!(to_autohotkey(&snippet).ends_with(
+            "\n; RX\n:X:`:rx::PasteTemplate(\"Índice `\"cardiotorácico`\" < 50%,`nnormal.\")\n"
+        ));
+        assert!(to_textexpander(&snippet)
+            .contains("<string>Índice &quot;cardiotorácico&quot; &lt; 50%%,\nnormal.</string>"));
+        assert_eq!(
+            to_csv(&snippet),
+            ":rx,\"Índice \"\"cardiotorácico\"\" < 50%,\nnormal.\",RX\n"
+        );
+        assert!(to_text_replacements(&snippet).contains(
+            "\t<dict>\n\t\t<key>phrase</key>\n\t\t<string>Índice &quot;cardiotorácico&quot; &lt; 50%,\nnormal.</string>\n\
+             \t\t<key>shortcut</key>\n\t\t<string>:rx</string>\n\t</dict>\n"
+        ));
+        assert_eq!(
+            SnippetFormat::from_path(Path::new("laudos.ahk")).unwrap(),
+            SnippetFormat::Autohotkey
+        );
+
+        let output = dir.path().join("Text Replacements.plist");
+        let short = SnippetOptions {
+            sections: vec!["impressao".to_string(), "tecnica".to_string()],
+            max_length: Some(20),
+            ..SnippetOptions::default()
+        };
+        assert_eq!(write_snippets(dir.path(), &short, &output).unwrap(), 1);
+        assert!(fs::read_to_string(&output)
+            .unwrap()
+            .contains("<string>:tc-cranio-sem-tecnica</string>"));
+    }
+
+    #[test]
+    fn text_replacement_lists_keep_only_short_snippets() {
+        let snippet = [Snippet {
+            trigger: ":nl".to_string(),
+            label: "Normal".to_string(),
+            text: "Sem alterações & normal.".to_string(),
+        }];
+        assert_eq!(
+            to_text_replacements(&snippet),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n<array>\n\
+             \t<dict>\n\t\t<key>phrase</key>\n\t\t<string>Sem alterações &amp; normal.</string>\n\
+             \t\t<key>shortcut</key>\n\t\t<string>:nl</string>\n\t</dict>\n\
+             </array>\n</plist>\n"
+        );
+
+        let plist = SnippetFormat::from_path(Path::new("Text Replacements.plist")).unwrap();
+        let yaml = SnippetFormat::from_path(Path::new("laudos.yml")).unwrap();
+        let options = SnippetOptions::default();
+        assert_eq!(plist, SnippetFormat::TextReplacements);
+        assert_eq!(
+            plist.max_length(&options),
+            Some(config::get().snippets.max_length)
+        );
+        // Other expanders take whole templates unless asked otherwise.
+        assert_eq!(yaml.max_length(&options), None);
+        let limited = SnippetOptions {
+            max_length: Some(80),
+            ..SnippetOptions::default()
+        };
+        assert_eq!(yaml.max_length(&limited), Some(80));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let long = format!("**RM DO JOELHO**\n\n{}\n", "Menisco íntegro. ".repeat(40));
+        fs::write(dir.path().join("RM Joelho.md"), long).unwrap();
+        fs::write(dir.path().join("Normal.md"), "Sem alterações.\n").unwrap();
+        let output = dir.path().join("Text Replacements.plist");
+        assert_eq!(write_snippets(dir.path(), &options, &output).unwrap(), 1);
+        let written = fs::read_to_string(&output).unwrap();
+        assert!(written.contains("<string>:normal</string>"), "{written}");
+        assert!(!written.contains(":rm-joelho"), "{written}");
+        assert_eq!(
+            write_snippets(dir.path(), &options, &dir.path().join("laudos.yml")).unwrap(),
+            2
+        );
+    }
+}
@@ -0,0 +1,544 @@
+//! Spellcheck against Hunspell dictionaries (the `spelling` lint rule).
+//!
+//! A dictionary is the usual `.aff`/`.dic` pair, such as the pt-BR one from
+//! LibreOffice (VERO) installed under `/usr/share/hunspell`. Words are
+//! looked up as written in the `.dic` file or derived from a stem by one
+//! prefix, one suffix (optionally followed by a second one its continuation
+//! class allows) or both, as Hunspell does; compounding, suggestions from
+//! `REP` tables and morphology are not read. Medical terms the dictionary
+//! lacks go in an allowlist: a text file with one word per line and `#`
+//! comments.
+
+use anyhow::{Context, Result};
+use encoding_rs::{Encoding, UTF_8};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where `find_dictionary` looks for `pt_BR.aff` and `pt_BR.dic`.
+const SYSTEM_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/hunspell",
+    "/opt/homebrew/share/hunspell",
+    "/Library/Spelling",
+];
+
+/// Suggestions shown at most per unknown word.
+const MAX_SUGGESTIONS: usize = 3;
+
+type Flag = u64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FlagMode {
+    /// One character per flag, the default (`FLAG UTF-8` is the same here).
+    Char,
+    /// Two characters per flag (`FLAG long`).
+    Long,
+    /// Comma-separated numbers (`FLAG num`).
+    Num,
+}
+
+fn parse_flags(text: &str, mode: FlagMode) -> Vec<Flag> {
+    match mode {
+        FlagMode::Char => text.chars().map(|c| c as Flag).collect(),
+        FlagMode::Long => text
+            .chars()
+            .collect::<Vec<_>>()
+            .chunks(2)
+            .map(|pair| pair.iter().fold(0, |flag, &c| flag << 32 | c as Flag))
+            .collect(),
+        FlagMode::Num => text
+            .split(',')
+            .filter_map(|number| number.trim().parse().ok())
+            .collect(),
+    }
+}
+
+/// The flags of `text`, or those of the `AF` alias it numbers (from 1).
+fn expand(text: &str, mode: FlagMode, aliases: &[Vec<Flag>]) -> Vec<Flag> {
+    match text.parse::<usize>() {
+        Ok(alias) if !aliases.is_empty() => aliases
+            .get(alias.wrapping_sub(1))
+            .cloned()
+            .unwrap_or_default(),
+        _ => parse_flags(text, mode),
+    }
+}
+
+/// One piece of an affix condition: `.`, a letter, or `[...]`/`[^...]`.
+#[derive(Clone, Debug)]
+enum Matcher {
+    Any,
+    Char(char),
+    Set(Vec<char>, bool),
+}
+
+impl Matcher {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Matcher::Any => true,
+            Matcher::Char(expected) => c == *expected,
+            Matcher::Set(chars, negated) => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+fn parse_condition(text: &str) -> Vec<Matcher> {
+    let mut matchers = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        matchers.push(match c {
+            '.' => Matcher::Any,
+            '[' => {
+                let mut set: Vec<char> = chars.by_ref().take_while(|&c| c != ']').collect();
+                let negated = set.first() == Some(&'^');
+                if negated {
+                    set.remove(0);
+                }
+                Matcher::Set(set, negated)
+            }
+            c => Matcher::Char(c),
+        });
+    }
+    // A lone `.` means any word.
+    if matches!(matchers.as_slice(), [Matcher::Any]) {
+        matchers.clear();
+    }
+    matchers
+}
+
+#[derive(Clone, Debug)]
+struct Affix {
+    flag: Flag,
+    /// Whether it combines with an affix of the other kind.
+    cross: bool,
+    strip: String,
+    add: String,
+    condition: Vec<Matcher>,
+    /// Flags of the affixes that may follow this one.
+    continuation: Vec<Flag>,
+}
+
+impl Affix {
+    /// Whether the condition holds at the start (prefixes) or the end
+    /// (suffixes) of `stem`.
+    fn applies(&self, stem: &str, at_end: bool) -> bool {
+        let count = self.condition.len();
+        if stem.chars().count() < count {
+            return false;
+        }
+        if at_end {
+            stem.chars()
+                .rev()
+                .zip(self.condition.iter().rev())
+                .all(|(c, matcher)| matcher.matches(c))
+        } else {
+            stem.chars()
+                .zip(&self.condition)
+                .all(|(c, matcher)| matcher.matches(c))
+        }
+    }
+}
+
+/// A Hunspell dictionary and the words allowed on top of it.
+#[derive(Clone, Debug, Default)]
+pub struct Dictionary {
+    words: HashMap<String, Vec<Flag>>,
+    /// Affixes by the text they add.
+    prefixes: HashMap<String, Vec<Affix>>,
+    suffixes: HashMap<String, Vec<Affix>>,
+    need_affix: Option<Flag>,
+    forbidden: Option<Flag>,
+    /// Letters tried when suggesting, most likely first (`TRY`).
+    try_chars: Vec<char>,
+    /// Lowercased allowlist.
+    allowed: HashSet<String>,
+    /// Suggestions already made: the same typo tends to be in many
+    /// templates and each search tries hundreds of words.
+    suggested: RefCell<HashMap<String, Vec<String>>>,
+}
+
+/// Decodes a dictionary file in the `SET` encoding of its `.aff`.
+fn decode(data: &[u8], encoding: &'static Encoding) -> String {
+    encoding.decode(data).0.into_owned()
+}
+
+/// The `SET` of an `.aff` file, read before decoding it.
+fn aff_encoding(data: &[u8]) -> &'static Encoding {
+    String::from_utf8_lossy(data)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("SET "))
+        .and_then(|label| Encoding::for_label(label.trim().as_bytes()))
+        .unwrap_or(UTF_8)
+}
+
+impl Dictionary {
+    /// Reads the `.aff` and `.dic` text of a dictionary.
+    pub fn parse(aff: &str, dic: &str) -> Dictionary {
+        let mut dictionary = Dictionary::default();
+        let mut mode = FlagMode::Char;
+        let mut aliases: Vec<Vec<Flag>> = Vec::new();
+        // Flags whose header line was read, per affix kind, with their cross
+        // product setting.
+        let mut headers: HashMap<(bool, Flag), bool> = HashMap::new();
+
+        for line in aff.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                ["FLAG", "long", ..] => mode = FlagMode::Long,
+                ["FLAG", "num", ..] => mode = FlagMode::Num,
+                ["AF", flags, ..] if !flags.chars().all(|c| c.is_ascii_digit()) => {
+                    aliases.push(parse_flags(flags, mode))
+                }
+                ["NEEDAFFIX", flag, ..] => {
+                    dictionary.need_affix = parse_flags(flag, mode).first().copied()
+                }
+                ["FORBIDDENWORD", flag, ..] => {
+                    dictionary.forbidden = parse_flags(flag, mode).first().copied()
+                }
+                ["TRY", letters, ..] => dictionary.try_chars = letters.chars().collect(),
+                [kind @ ("PFX" | "SFX"), flag, rest @ ..] => {
+                    let is_suffix = *kind == "SFX";
+                    let Some(&flag) = parse_flags(flag, mode).first() else {
+                        continue;
+                    };
+                    let Some(&cross) = headers.get(&(is_suffix, flag)) else {
+                        headers.insert((is_suffix, flag), rest.first() == Some(&"Y"));
+                        continue;
+                    };
+                    let [strip, add, condition, ..] = rest else {
+                        continue;
+                    };
+                    let (add, continuation) = match add.split_once('/') {
+                        Some((add, flags)) => (add, expand(flags, mode, &aliases)),
+                        None => (*add, Vec::new()),
+                    };
+                    let empty = |text: &str| {
+                        if text == "0" {
+                            String::new()
+                        } else {
+                            text.to_string()
+                        }
+                    };
+                    let affix = Affix {
+                        flag,
+                        cross,
+                        strip: empty(strip),
+                        add: empty(add),
+                        condition: parse_condition(condition),
+                        continuation,
+                    };
+                    let table = if is_suffix {
+                        &mut dictionary.suffixes
+                    } else {
+                        &mut dictionary.prefixes
+                    };
+                    table.entry(affix.add.clone()).or_default().push(affix);
+                }
+                _ => {}
+            }
+        }
+
+        let mut lines = dic.lines();
+        // The first line is the word count, used by Hunspell to size its table.
+        if let Some(first) = lines.clone().next() {
+            if first.trim().chars().all(|c| c.is_ascii_digit()) {
+                lines.next();
+            }
+        }
+        for line in lines {
+            let Some(entry) = line.split_whitespace().next() else {
+                continue;
+            };
+            let (word, flags) = match entry.split_once('/') {
+                Some((word, flags)) if !word.is_empty() => (word, expand(flags, mode, &aliases)),
+                _ => (entry, Vec::new()),
+            };
+            dictionary
+                .words
+                .entry(word.to_string())
+                .or_default()
+                .extend(flags);
+        }
+        dictionary
+    }
+
+    /// Reads the dictionary at `path`, with or without the `.aff` or `.dic`
+    /// extension.
+    pub fn open(path: &Path) -> Result<Dictionary> {
+        let aff_path = path.with_extension("aff");
+        let dic_path = path.with_extension("dic");
+        let aff = fs::read(&aff_path)
+            .with_context(|| format!("Cannot read dictionary {}", aff_path.display()))?;
+        let dic = fs::read(&dic_path)
+            .with_context(|| format!("Cannot read dictionary {}", dic_path.display()))?;
+        let encoding = aff_encoding(&aff);
+        Ok(Dictionary::parse(
+            &decode(&aff, encoding),
+            &decode(&dic, encoding),
+        ))
+    }
+
+    /// Adds the words of an allowlist file.
+    pub fn allow_file(&mut self, path: &Path) -> Result<()> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read allowlist {}", path.display()))?;
+        for line in contents.lines() {
+            let word = line.split('#').next().unwrap_or_default().trim();
+            if !word.is_empty() {
+                self.allow(word);
+            }
+        }
+        Ok(())
+    }
+
+    /// Accepts `word` in any case.
+    pub fn allow(&mut self, word: &str) {
+        self.allowed.insert(word.to_lowercase());
+    }
+
+    /// Whether `stem` is a dictionary word carrying `flag`.
+    fn has_flag(&self, stem: &str, flag: Flag) -> bool {
+        self.words
+            .get(stem)
+            .is_some_and(|flags| flags.contains(&flag) && !self.is_forbidden(flags))
+    }
+
+    fn is_forbidden(&self, flags: &[Flag]) -> bool {
+        self.forbidden.is_some_and(|flag| flags.contains(&flag))
+    }
+
+    /// The stems `word` comes from with one of `table`'s affixes removed,
+    /// each with its affix.
+    fn stems<'a>(
+        &'a self,
+        word: &str,
+        at_end: bool,
+    ) -> impl Iterator<Item = (String, &'a Affix)> + 'a {
+        let table = if at_end {
+            &self.suffixes
+        } else {
+            &self.prefixes
+        };
+        let bounds: Vec<usize> = word
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([word.len()])
+            .collect();
+        let word = word.to_string();
+        bounds.into_iter().flat_map(move |i| {
+            let (added, rest) = if at_end {
+                (&word[i..], &word[..i])
+            } else {
+                (&word[..i], &word[i..])
+            };
+            let rest = rest.to_string();
+            table
+                .get(added)
+                .into_iter()
+                .flatten()
+                .filter_map(move |affix| {
+                    let stem = if at_end {
+                        format!("{}{}", rest, affix.strip)
+                    } else {
+                        format!("{}{}", affix.strip, rest)
+                    };
+                    (!stem.is_empty() && affix.applies(&stem, at_end)).then_some((stem, affix))
+                })
+        })
+    }
+
+    /// Whether `word` is a stem plus a suffix (and, when `prefix` is given,
+    /// a stem taking that prefix too).
+    fn has_suffix(&self, word: &str, prefix: Option<&Affix>) -> bool {
+        self.stems(word, true).any(|(stem, suffix)| match prefix {
+            Some(prefix) => {
+                prefix.cross
+                    && suffix.cross
+                    && self.has_flag(&stem, suffix.flag)
+                    && self.has_flag(&stem, prefix.flag)
+            }
+            None => {
+                self.has_flag(&stem, suffix.flag)
+                    // Two suffixes: the inner one allows the outer one.
+                    || self.stems(&stem, true).any(|(root, inner)| {
+                        inner.continuation.contains(&suffix.flag) && self.has_flag(&root, inner.flag)
+                    })
+            }
+        })
+    }
+
+    fn has_prefix(&self, word: &str) -> bool {
+        self.stems(word, false).any(|(stem, prefix)| {
+            self.has_flag(&stem, prefix.flag) || self.has_suffix(&stem, Some(prefix))
+        })
+    }
+
+    /// Whether `word` is spelled as written, ignoring case.
+    fn is_known(&self, word: &str) -> bool {
+        if let Some(flags) = self.words.get(word) {
+            if self.is_forbidden(flags) {
+                return false;
+            }
+            if !self.need_affix.is_some_and(|flag| flags.contains(&flag)) {
+                return true;
+            }
+        }
+        self.has_suffix(word, None) || self.has_prefix(word)
+    }
+
+    /// Whether `word` is in the dictionary or the allowlist. Capitalized
+    /// and all-capital forms of a lowercase word are accepted, as in
+    /// Hunspell, but not the other way round.
+    pub fn check(&self, word: &str) -> bool {
+        let lower = word.to_lowercase();
+        if self.allowed.contains(&lower) || self.is_known(word) {
+            return true;
+        }
+        let mut chars = word.chars();
+        let first: String = chars.next().into_iter().collect();
+        let rest: String = chars.collect();
+        let capitalized = format!("{}{}", first, rest.to_lowercase());
+        let is_upper = !word.chars().any(char::is_lowercase);
+        (rest.chars().all(|c| !c.is_uppercase()) || is_upper)
+            && (self.is_known(&lower) || (is_upper && self.is_known(&capitalized)))
+    }
+
+    /// Known words one edit from `word` (a letter removed, added, replaced
+    /// or swapped with the next), most likely first.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        if let Some(suggestions) = self.suggested.borrow().get(word) {
+            return suggestions.clone();
+        }
+        let chars: Vec<char> = word.chars().collect();
+        let mut letters = self.try_chars.clone();
+        for c in chars.iter().flat_map(|c| c.to_lowercase()) {
+            if !letters.contains(&c) {
+                letters.push(c);
+            }
+        }
+        let mut candidates = Vec::new();
+        for i in 0..=chars.len() {
+            let (before, after) = chars.split_at(i);
+            let join = |middle: &[char], after: &[char]| -> String {
+                before.iter().chain(middle).chain(after).collect()
+            };
+            for &c in &letters {
+                if let Some((_, tail)) = after.split_first() {
+                    candidates.push(join(&[c], tail));
+                }
+                candidates.push(join(&[c], after));
+            }
+            if let [a, b, tail @ ..] = after {
+                candidates.push(join(&[*b, *a], tail));
+            }
+            if let Some((_, tail)) = after.split_first() {
+                candidates.push(join(&[], tail));
+            }
+        }
+        let mut suggestions: Vec<String> = Vec::new();
+        for candidate in candidates {
+            if candidate != word && !suggestions.contains(&candidate) && self.check(&candidate) {
+                suggestions.push(candidate);
+                if suggestions.len() == MAX_SUGGESTIONS {
+                    break;
+                }
+            }
+        }
+        self.suggested
+            .borrow_mut()
+            .insert(word.to_string(), suggestions.clone());
+        suggestions
+    }
+
+    /// The words of a Markdown line the dictionary does not know, in order.
+    pub fn misspellings(&self, line: &str) -> Vec<String> {
+        let text = ignored_regex().replace_all(line, " ");
+        let mut unknown = Vec::new();
+        for token in text.split(|c: char| !(c.is_alphanumeric() || c == '-')) {
+            let token = token.trim_matches('-');
+            if !is_checked(token) || self.check(token) {
+                continue;
+            }
+            // A hyphenated word the dictionary lacks is fine when its parts
+            // are words (`látero-lateral`).
+            let parts: Vec<&str> = token.split('-').filter(|p| !p.is_empty()).collect();
+            if parts.len() > 1 {
+                let wrong: Vec<&str> = parts
+                    .into_iter()
+                    .filter(|part| is_checked(part) && !self.check(part))
+                    .collect();
+                unknown.extend(wrong.into_iter().map(str::to_string));
+            } else {
+                unknown.push(token.to_string());
+            }
+        }
+        unknown
+    }
+}
+
+/// Markup whose text is not prose: placeholders, HTML tags, link targets,
+/// attribute blocks and URLs.
+fn ignored_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\{\{.*?\}\}|<[^>]*>|\]\([^)]*\)|\{[^}]*\}|https?://\S+|www\.\S+").unwrap()
+    })
+}
+
+/// Whether a token is looked up: words of two letters or more, without
+/// digits (`12mm`), and not acronyms of up to four capitals (`TC`, `RADS`).
+fn is_checked(token: &str) -> bool {
+    let letters = token.chars().filter(|c| c.is_alphabetic()).count();
+    let acronym = letters <= 4 && !token.chars().any(char::is_lowercase);
+    letters >= 2 && !acronym && !token.chars().any(|c| c.is_ascii_digit())
+}
+
+/// The first `pt_BR` dictionary in the usual Hunspell folders.
+pub fn find_dictionary() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(|home| Path::new(&home).join("Library/Spelling"));
+    SYSTEM_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .chain(home)
+        .map(|dir| dir.join("pt_BR"))
+        .find(|path| path.with_extension("aff").is_file() && path.with_extension("dic").is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8\nTRY aeiosrnd\nNEEDAFFIX X\n\
+                       SFX S Y 2\nSFX S 0 s [^ã][aeiou]\nSFX S ão ões ão\n\
+                       SFX M Y 1\nSFX M o a/S o\n\
+                       PFX R Y 1\nPFX R 0 re .\n";
+    const DIC: &str = "5\nfígado/S\nlesão/S\nnormal\nfeito/RMS\nhiper/X\n";
+
+    #[test]
+    fn words_are_derived_from_stems_and_affixes() {
+        let mut dictionary = Dictionary::parse(AFF, DIC);
+        for word in [
+            "fígado", "fígados", "lesões", "Fígado", "FÍGADO", "refeito", "refeitos", "feitas",
+        ] {
+            assert!(dictionary.check(word), "{word}");
+        }
+        for word in ["fígadu", "fÍgado", "lesãos", "hiper"] {
+            assert!(!dictionary.check(word), "{word}");
+        }
+        assert_eq!(dictionary.suggest("fígadu"), ["fígado"]);
+
+        dictionary.allow("Hipoatenuante");
+        assert_eq!(
+            dictionary.misspellings(
+                "**Análise:** fígado {{lado}} normal, lesões de 12mm (BI-RADS) hipoatenuantes e hipoatenuante-normal."
+            ),
+            ["Análise", "de", "hipoatenuantes"]
+        );
+    }
+}
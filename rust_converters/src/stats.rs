@@ -0,0 +1,345 @@
+//! Coverage statistics of the template library (`rtemplates stats`), from
+//! the inventory rows (`inventory`).
+//!
+//! The report counts templates by modality and, within each, by body
+//! region; gives the word count total, average and extremes, the average
+//! sentence length and the share of boilerplate (`metrics`); counts the
+//! templates holding each standard section; and lists the templates
+//! without an impression, a modality, a body region or a title, which are
+//! the gaps an audit asks about first. The figures of every template
+//! follow, most words first, so the verbose ones stand out.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::files::FileFilter;
+use crate::inventory::{inventory, Row};
+use crate::metrics::{ratio, round, Metrics};
+use crate::template::SectionKind;
+
+/// Templates the text report lists under "Most verbose".
+const VERBOSE_LISTED: usize = 10;
+
+const SECTION_KINDS: [SectionKind; 4] = [
+    SectionKind::IndicacaoClinica,
+    SectionKind::Tecnica,
+    SectionKind::AspectosObservados,
+    SectionKind::Impressao,
+];
+
+/// How many templates share a name: a modality, region or section key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub templates: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ModalityCount {
+    pub name: String,
+    pub templates: usize,
+    /// Templates of the modality by body region; those without one are
+    /// left out.
+    pub body_regions: Vec<Count>,
+}
+
+/// A template and its word count.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Length {
+    pub file: String,
+    pub words: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Words {
+    pub total: usize,
+    /// Rounded to the nearest word.
+    pub average: usize,
+    pub shortest: Option<Length>,
+    pub longest: Option<Length>,
+    pub sentences: usize,
+    /// Words per sentence over all templates, to one decimal.
+    pub average_sentence_length: f64,
+    /// Share of all words that are boilerplate, to two decimals.
+    pub boilerplate_ratio: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TemplateMetrics {
+    pub file: String,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Stats {
+    pub templates: usize,
+    pub words: Words,
+    /// Most templates first; templates without a modality are left out.
+    pub modalities: Vec<ModalityCount>,
+    /// Templates holding each standard section, by key (`impressao`).
+    pub sections: Vec<Count>,
+    pub missing_impressao: Vec<String>,
+    pub without_modality: Vec<String>,
+    pub without_body_region: Vec<String>,
+    pub without_title: Vec<String>,
+    /// Most words first.
+    pub per_template: Vec<TemplateMetrics>,
+}
+
+/// `counts` most first, then by name.
+fn sorted(counts: BTreeMap<String, usize>) -> Vec<Count> {
+    let mut counts: Vec<Count> = counts
+        .into_iter()
+        .map(|(name, templates)| Count { name, templates })
+        .collect();
+    counts.sort_by(|a, b| b.templates.cmp(&a.templates).then(a.name.cmp(&b.name)));
+    counts
+}
+
+fn files_where(rows: &[Row], test: impl Fn(&Row) -> bool) -> Vec<String> {
+    rows.iter()
+        .filter(|row| test(row))
+        .map(|row| row.file.clone())
+        .collect()
+}
+
+pub fn stats(rows: &[Row]) -> Stats {
+    let mut modalities: BTreeMap<String, (usize, BTreeMap<String, usize>)> = BTreeMap::new();
+    for row in rows {
+        let Some(modality) = &row.modality else {
+            continue;
+        };
+        let (templates, regions) = modalities.entry(modality.clone()).or_default();
+        *templates += 1;
+        if let Some(region) = &row.body_region {
+            *regions.entry(region.clone()).or_default() += 1;
+        }
+    }
+    let mut modalities: Vec<ModalityCount> = modalities
+        .into_iter()
+        .map(|(name, (templates, regions))| ModalityCount {
+            name,
+            templates,
+            body_regions: sorted(regions),
+        })
+        .collect();
+    modalities.sort_by(|a, b| b.templates.cmp(&a.templates).then(a.name.cmp(&b.name)));
+
+    let length = |row: &Row| Length {
+        file: row.file.clone(),
+        words: row.metrics.words,
+    };
+    let total = rows.iter().map(|row| row.metrics.words).sum::<usize>();
+    let sentences = rows.iter().map(|row| row.metrics.sentences).sum::<usize>();
+    let boilerplate = rows
+        .iter()
+        .map(|row| row.metrics.boilerplate_words)
+        .sum::<usize>();
+    let mut per_template: Vec<TemplateMetrics> = rows
+        .iter()
+        .map(|row| TemplateMetrics {
+            file: row.file.clone(),
+            metrics: row.metrics.clone(),
+        })
+        .collect();
+    per_template.sort_by_key(|template| Reverse(template.metrics.words));
+    Stats {
+        templates: rows.len(),
+        words: Words {
+            total,
+            average: (total + rows.len() / 2)
+                .checked_div(rows.len())
+                .unwrap_or(0),
+            // The first of equally short or long templates.
+            shortest: rows.iter().min_by_key(|row| row.metrics.words).map(length),
+            longest: rows
+                .iter()
+                .rev()
+                .max_by_key(|row| row.metrics.words)
+                .map(length),
+            sentences,
+            average_sentence_length: round(ratio(total, sentences), 1),
+            boilerplate_ratio: round(ratio(boilerplate, total), 2),
+        },
+        modalities,
+        sections: SECTION_KINDS
+            .iter()
+            .map(|kind| Count {
+                name: kind.key().to_string(),
+                templates: rows.iter().filter(|row| row.kinds.contains(kind)).count(),
+            })
+            .collect(),
+        missing_impressao: files_where(rows, |row| !row.kinds.contains(&SectionKind::Impressao)),
+        without_modality: files_where(rows, |row| row.modality.is_none()),
+        without_body_region: files_where(rows, |row| row.body_region.is_none()),
+        without_title: files_where(rows, |row| row.title.is_none()),
+        per_template,
+    }
+}
+
+/// The statistics of the Markdown templates in `dir` selected by `files`.
+pub fn folder_stats(dir: &Path, files: &FileFilter) -> Result<Stats> {
+    Ok(stats(&inventory(dir, files)?))
+}
+
+fn percent(count: usize, total: usize) -> usize {
+    (count * 100 + total / 2).checked_div(total).unwrap_or(0)
+}
+
+/// `stats` as the report `rtemplates stats` prints.
+pub fn to_text(stats: &Stats) -> String {
+    let words = &stats.words;
+    let mut text = format!(
+        "{} templates, {} words (average {} per template)\n",
+        stats.templates, words.total, words.average
+    );
+    if let (Some(shortest), Some(longest)) = (&words.shortest, &words.longest) {
+        text.push_str(&format!(
+            "Shortest: {} ({} words); longest: {} ({} words)\n",
+            shortest.file, shortest.words, longest.file, longest.words
+        ));
+    }
+    text.push_str(&format!(
+        "{} sentences, {:.1} words long on average; {}% of the words are boilerplate\n",
+        words.sentences,
+        words.average_sentence_length,
+        (words.boilerplate_ratio * 100.0).round()
+    ));
+
+    text.push_str("\nBy modality and body region:\n");
+    for modality in &stats.modalities {
+        text.push_str(&format!(
+            "  {:<40} {:>4}\n",
+            modality.name, modality.templates
+        ));
+        for region in &modality.body_regions {
+            text.push_str(&format!(
+                "    {:<38} {:>4}\n",
+                region.name, region.templates
+            ));
+        }
+    }
+
+    text.push_str("\nSections:\n");
+    for section in &stats.sections {
+        let label = SectionKind::from_key(&section.name).map_or("", |kind| kind.label());
+        text.push_str(&format!(
+            "  {:<40} {:>4} ({}%)\n",
+            label,
+            section.templates,
+            percent(section.templates, stats.templates)
+        ));
+    }
+
+    if !stats.per_template.is_empty() {
+        text.push_str(&format!(
+            "\nMost verbose:\n  {:<40} {:>6} {:>9} {:>8} {:>11}\n",
+            "", "words", "sentences", "average", "boilerplate"
+        ));
+    }
+    for template in stats.per_template.iter().take(VERBOSE_LISTED) {
+        let metrics = &template.metrics;
+        text.push_str(&format!(
+            "  {:<40} {:>6} {:>9} {:>8.1} {:>10}%\n",
+            template.file,
+            metrics.words,
+            metrics.sentences,
+            metrics.average_sentence_length,
+            (metrics.boilerplate_ratio * 100.0).round()
+        ));
+    }
+
+    for (heading, files) in [
+        ("Missing Impressão", &stats.missing_impressao),
+        ("Without modality", &stats.without_modality),
+        ("Without body region", &stats.without_body_region),
+        ("Without title", &stats.without_title),
+    ] {
+        if files.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("\n{} ({}):\n", heading, files.len()));
+        for file in files {
+            text.push_str(&format!("  {}\n", file));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(file: &str, modality: Option<&str>, region: Option<&str>, words: usize) -> Row {
+        Row {
+            file: file.to_string(),
+            title: Some(file.to_uppercase()),
+            modality: modality.map(str::to_string),
+            body_region: region.map(str::to_string),
+            sections: Vec::new(),
+            kinds: vec![SectionKind::Tecnica, SectionKind::Impressao],
+            modified: String::new(),
+            metrics: Metrics {
+                words,
+                sentences: words / 10,
+                boilerplate_words: words / 4,
+                ..Metrics::default()
+            },
+        }
+    }
+
+    #[test]
+    fn templates_are_counted_by_modality_region_and_section() {
+        let mut rows = vec![
+            row("tc torax.md", Some("CT"), Some("tórax"), 100),
+            row("tc abdome.md", Some("CT"), Some("abdome"), 300),
+            row("tc cranio.md", Some("CT"), Some("tórax"), 200),
+            row("rm joelho.md", Some("MR"), None, 50),
+            row("frases.md", None, None, 50),
+        ];
+        rows[4].kinds.clear();
+        rows[4].title = None;
+
+        let stats = stats(&rows);
+        assert_eq!(stats.templates, 5);
+        assert_eq!(stats.words.total, 700);
+        assert_eq!(stats.words.average, 140);
+        assert_eq!(stats.words.shortest.as_ref().unwrap().file, "rm joelho.md");
+        assert_eq!(stats.words.longest.as_ref().unwrap().file, "tc abdome.md");
+        assert_eq!(stats.words.sentences, 70);
+        assert_eq!(stats.words.average_sentence_length, 10.0);
+        assert_eq!(stats.words.boilerplate_ratio, 0.25);
+        assert_eq!(stats.per_template[0].file, "tc abdome.md");
+        assert_eq!(stats.modalities[0].name, "CT");
+        assert_eq!(stats.modalities[0].templates, 3);
+        assert_eq!(
+            stats.modalities[0].body_regions,
+            [
+                Count {
+                    name: "tórax".to_string(),
+                    templates: 2
+                },
+                Count {
+                    name: "abdome".to_string(),
+                    templates: 1
+                }
+            ]
+        );
+        assert_eq!(stats.sections[0].templates, 0);
+        assert_eq!(stats.sections[3].templates, 4);
+        assert_eq!(stats.missing_impressao, ["frases.md"]);
+        assert_eq!(stats.without_body_region, ["rm joelho.md", "frases.md"]);
+
+        let text = to_text(&stats);
+        assert!(text.starts_with("5 templates, 700 words (average 140 per template)\n"));
+        assert!(text.contains("  Impressão                                   4 (80%)\n"));
+        assert!(text.contains("\nMissing Impressão (1):\n  frases.md\n"));
+        assert!(text.contains(
+            "\n  tc abdome.md                                300        30      0.0          0%\n"
+        ));
+    }
+}
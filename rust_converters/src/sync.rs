@@ -0,0 +1,275 @@
+//! Two-way sync of the Markdown and DOCX folders (`rtemplates sync`), for
+//! libraries where some people edit the templates directly in Word.
+//!
+//! Regenerating a whole folder overwrites those edits. `sync` instead
+//! compares each pair with the conversion cache: a Markdown changed since
+//! its DOCX was written is exported again, a DOCX written to since is
+//! imported back into the Markdown, and a pair changed on both sides is a
+//! conflict left for manual resolution. Without a cache entry the newer
+//! file wins. A DOCX with no Markdown is imported as a new template.
+//!
+//! An import keeps the Markdown's front matter. A template using fill
+//! directives (`{{#if}}`, `{{> partial}}`, placeholders) is never
+//! imported, as the DOCX holds them filled in: its Word edits are a
+//! conflict too. Each import is recorded in the template history first.
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::aliases;
+use crate::cache::Cache;
+use crate::comments;
+use crate::convert::{self, BatchOptions, Format};
+use crate::convert_to_markdown::import_docx;
+use crate::encoding::read_text;
+use crate::exports;
+use crate::files::{display_name, list_files, output_path};
+use crate::fill::fill;
+use crate::front_matter;
+use crate::history;
+use crate::images::save_assets;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncAction {
+    /// Neither file changed since the last conversion.
+    UpToDate,
+    /// The DOCX is missing or older than the Markdown: it is written again.
+    Export,
+    /// The DOCX was edited: the Markdown is rewritten from it.
+    Import,
+    /// Both sides were edited, or the DOCX edits cannot be imported; the
+    /// reason is given.
+    Conflict(&'static str),
+}
+
+/// One Markdown and DOCX pair, either of which may be missing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncItem {
+    pub markdown: PathBuf,
+    pub docx: PathBuf,
+    pub action: SyncAction,
+}
+
+fn mtime(path: &Path) -> Result<std::time::SystemTime> {
+    Ok(fs::metadata(path)?.modified()?)
+}
+
+/// Whether filling `markdown` changes it, so that its DOCX is not a copy
+/// of what is written in it.
+fn has_directives(markdown: &Path, options: &BatchOptions) -> Result<bool> {
+    let content = read_text(markdown)?;
+    Ok(fill(&content, &options.fill).map_or(true, |filled| filled != content))
+}
+
+/// What syncing the templates in `markdown_dir` with their DOCX in
+/// `docx_dir` does to each pair, by file name. Templates that are not
+/// exported to DOCX (see `exports`) and alias copies are left out.
+pub fn plan(markdown_dir: &Path, docx_dir: &Path, options: &BatchOptions) -> Result<Vec<SyncItem>> {
+    let cache = options
+        .cache_path
+        .as_deref()
+        .map(Cache::load)
+        .unwrap_or_default();
+    let context = options.fill.cache_context()?;
+    let aliases = aliases::in_folder(markdown_dir)?;
+
+    let mut names = BTreeSet::new();
+    for markdown in list_files(markdown_dir, "md")? {
+        if exports::is_exported(&markdown, Format::Docx)? {
+            names.insert(
+                markdown
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+    }
+    if docx_dir.is_dir() {
+        for docx in list_files(docx_dir, "docx")? {
+            let stem = docx.file_stem().unwrap_or_default().to_string_lossy();
+            let markdown = output_path(markdown_dir, &docx, "md");
+            if !aliases.contains_key(stem.as_ref()) && !markdown.exists() {
+                names.insert(stem.to_string());
+            }
+        }
+    }
+
+    let mut items = Vec::new();
+    for name in names {
+        let markdown = markdown_dir.join(format!("{}.md", name));
+        if !options
+            .files
+            .matches(markdown.strip_prefix(markdown_dir).unwrap_or(&markdown))
+        {
+            continue;
+        }
+        let docx = output_path(docx_dir, &markdown, "docx");
+        let action = if !docx.exists() {
+            SyncAction::Export
+        } else if !markdown.exists() {
+            SyncAction::Import
+        } else {
+            match cache.changes(&markdown, &docx, &context)? {
+                Some(changes) => match (changes.source, changes.target) {
+                    (false, false) => SyncAction::UpToDate,
+                    (true, false) => SyncAction::Export,
+                    (false, true) => SyncAction::Import,
+                    (true, true) => SyncAction::Conflict("both edited since the last conversion"),
+                },
+                None if mtime(&docx)? > mtime(&markdown)? => SyncAction::Import,
+                None => SyncAction::Export,
+            }
+        };
+        let action = if action == SyncAction::Import
+            && markdown.exists()
+            && has_directives(&markdown, options)?
+        {
+            SyncAction::Conflict("the DOCX was edited but the Markdown uses fill directives")
+        } else {
+            action
+        };
+        items.push(SyncItem {
+            markdown,
+            docx,
+            action,
+        });
+    }
+    Ok(items)
+}
+
+/// Rewrites `markdown` from `docx`, keeping its front matter and saving the
+/// images under `assets/` and the comments in their sidecar.
+fn import(markdown: &Path, docx: &Path, options: &BatchOptions) -> Result<()> {
+    let front = if markdown.exists() {
+        if let Some(dir) = &options.history_dir {
+            history::record(dir, markdown, Some("before sync"))?;
+        }
+        let content = read_text(markdown)?;
+        let body = front_matter::strip(&content);
+        content[..content.len() - body.len()].to_string()
+    } else {
+        String::new()
+    };
+    let imported = import_docx(docx)?;
+    let mut body = imported.markdown;
+    if !body.ends_with('\n') {
+        body.push('\n');
+    }
+    save_assets(markdown.parent().unwrap_or(Path::new("")), &imported.assets)?;
+    fs::write(markdown, front + &body)?;
+    comments::write_sidecar(markdown, &display_name(docx), &imported.comments)?;
+    if let Some(dir) = &options.history_dir {
+        let message = format!("sync: imported {}", display_name(docx));
+        history::record(dir, markdown, Some(&message))?;
+    }
+    Ok(())
+}
+
+/// Exports and imports the pairs `plan` gave, leaving conflicts alone, and
+/// records each pair as in sync in the conversion cache.
+pub fn apply(items: &[SyncItem], options: &BatchOptions) -> Result<()> {
+    let mut cache = options.cache_path.as_deref().map(Cache::load);
+    let context = options.fill.cache_context()?;
+    for item in items {
+        match item.action {
+            SyncAction::Export => {
+                convert::record_history(Format::Md, Format::Docx, &item.markdown, options)?;
+                let docx_dir = item.docx.parent().unwrap_or(Path::new(""));
+                convert::convert_one(
+                    Format::Md,
+                    Format::Docx,
+                    &item.markdown,
+                    docx_dir,
+                    &options.fill,
+                )?;
+            }
+            SyncAction::Import => import(&item.markdown, &item.docx, options)?,
+            SyncAction::UpToDate | SyncAction::Conflict(_) => continue,
+        }
+        if let Some(cache) = &mut cache {
+            cache.record(&item.markdown, &item.docx, &context)?;
+        }
+    }
+    if let Some(cache) = &cache {
+        cache.save()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn touch(path: &Path) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now())
+            .unwrap();
+    }
+
+    #[test]
+    fn sync_exports_imports_and_flags_conflicts() {
+        let root = tempfile::tempdir().unwrap();
+        let (markdown, docx) = (root.path().join("md"), root.path().join("docx"));
+        fs::create_dir_all(&markdown).unwrap();
+        let options = BatchOptions {
+            cache_path: Some(root.path().join("cache.json")),
+            ..BatchOptions::default()
+        };
+        let us = markdown.join("US ABDOME.md");
+        fs::write(
+            &us,
+            "---\ntags: [abdome]\n---\n**US ABDOME**\n\nFígado normal.\n",
+        )
+        .unwrap();
+        fs::write(
+            markdown.join("TC.md"),
+            "**TC**\n\n{{#if contraste}}Com contraste.{{/if}}\n",
+        )
+        .unwrap();
+        let actions = |items: &[SyncItem]| items.iter().map(|i| i.action).collect::<Vec<_>>();
+
+        let items = plan(&markdown, &docx, &options).unwrap();
+        assert_eq!(actions(&items), [SyncAction::Export, SyncAction::Export]);
+        apply(&items, &options).unwrap();
+        let items = plan(&markdown, &docx, &options).unwrap();
+        assert_eq!(
+            actions(&items),
+            [SyncAction::UpToDate, SyncAction::UpToDate]
+        );
+
+        // Edited in Word, and a new template saved from Word.
+        let us_docx = docx.join("US ABDOME.docx");
+        fs::copy(docx.join("TC.docx"), &us_docx).unwrap();
+        fs::copy(docx.join("TC.docx"), docx.join("NOVO.docx")).unwrap();
+        touch(&docx.join("TC.docx"));
+        let items = plan(&markdown, &docx, &options).unwrap();
+        assert_eq!(
+            actions(&items),
+            [
+                SyncAction::Import,
+                SyncAction::Conflict("the DOCX was edited but the Markdown uses fill directives"),
+                SyncAction::Import,
+            ]
+        );
+        apply(&items, &options).unwrap();
+        let imported = read_text(&us).unwrap();
+        assert!(imported.starts_with("---\ntags: [abdome]\n---\n"));
+        assert!(imported.contains("TC") && !imported.contains("Fígado"));
+        assert!(markdown.join("NOVO.md").exists());
+
+        fs::write(&us, "**US ABDOME**\n\nFígado esteatótico.\n").unwrap();
+        touch(&us_docx);
+        let items = plan(&markdown, &docx, &options).unwrap();
+        assert_eq!(
+            items[2].action,
+            SyncAction::Conflict("both edited since the last conversion")
+        );
+    }
+}
@@ -0,0 +1,728 @@
+//! Canonical in-memory form of a report template.
+//!
+//! Every reader ends in Markdown and every writer renders a `ReportTemplate`:
+//! the title/footer rules and the section headings are recognised once, in
+//! `ReportTemplate::from_markdown`, instead of in each converter. Writers live
+//! next to their format (`convert_to_docx::write_docx`,
+//! `convert_to_pdf::render_pdf`, `convert_to_html::template_to_html`,
+//! `convert_to_odt::write_odt`, `sections::ReportSections::from`).
+
+use anyhow::Result;
+use std::path::Path;
+
+use crate::classify;
+use crate::config::{self, LayoutRule};
+use crate::convert_to_markdown::{convert_docx_to_markdown, rows_to_markdown};
+use crate::footnotes::{self, Footnote};
+use crate::front_matter::{self, FrontMatter};
+use crate::language::{self, Language};
+use crate::markdown::{
+    is_image_line, layout, normalize_heading, parse_inline, Alignment, Block, LineStyle,
+    TitleFooter, FOOTER_FONT_SIZE_PT,
+};
+
+/// One line of inline Markdown; empty for a blank line.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Paragraph {
+    /// Inline Markdown with any `#` marker removed.
+    pub text: String,
+    /// Heading level when written as a Markdown heading (`## Name`).
+    pub heading: Option<u8>,
+}
+
+impl Paragraph {
+    pub fn new(text: impl Into<String>) -> Self {
+        Paragraph {
+            text: text.into(),
+            heading: None,
+        }
+    }
+
+    /// The text without emphasis markers.
+    pub fn plain(&self) -> String {
+        plain(&self.text)
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.text.trim().is_empty()
+    }
+
+    /// A line of images only, such as a clinic logo.
+    pub fn is_image(&self) -> bool {
+        is_image_line(&self.text)
+    }
+
+    /// A bold `Name:` line inside the findings, e.g. `**Tórax:**`.
+    pub fn is_subsection(&self) -> bool {
+        let line = self.plain();
+        let runs = parse_inline(&self.text, false);
+        line.ends_with(':')
+            && line.len() <= 120
+            && runs.iter().all(|r| r.bold || r.text.trim().is_empty())
+    }
+
+    /// The line as written in Markdown, `#` markers included.
+    pub fn to_markdown(&self) -> String {
+        match self.heading {
+            Some(level) => format!("{} {}", "#".repeat(level as usize), self.text),
+            None => self.text.clone(),
+        }
+    }
+
+    fn block(&self, style: LineStyle) -> Block {
+        Block::Paragraph {
+            text: self.text.clone(),
+            heading: self.heading,
+            style: style.with_heading(self.heading),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Content {
+    Paragraph(Paragraph),
+    Table {
+        header: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionKind {
+    IndicacaoClinica,
+    Tecnica,
+    AspectosObservados,
+    Impressao,
+}
+
+impl SectionKind {
+    /// The heading this section is usually written with.
+    pub fn label(self) -> &'static str {
+        match self {
+            SectionKind::IndicacaoClinica => "Indicação clínica",
+            SectionKind::Tecnica => "Técnica do exame",
+            SectionKind::AspectosObservados => "Aspectos observados",
+            SectionKind::Impressao => "Impressão",
+        }
+    }
+
+    /// The section whose `key` is `key`.
+    pub fn from_key(key: &str) -> Option<Self> {
+        [
+            SectionKind::IndicacaoClinica,
+            SectionKind::Tecnica,
+            SectionKind::AspectosObservados,
+            SectionKind::Impressao,
+        ]
+        .into_iter()
+        .find(|kind| kind.key() == key)
+    }
+
+    /// The field name used for this section in JSON exports
+    /// (`sections::ReportSections`).
+    pub fn key(self) -> &'static str {
+        match self {
+            SectionKind::IndicacaoClinica => "indicacao_clinica",
+            SectionKind::Tecnica => "tecnica",
+            SectionKind::AspectosObservados => "aspectos_observados",
+            SectionKind::Impressao => "impressao",
+        }
+    }
+}
+
+/// Heading prefixes (lowercase) and the section they open, longest first.
+const PORTUGUESE_KEYWORDS: &[(&str, SectionKind)] = &[
+    ("indicação clínica", SectionKind::IndicacaoClinica),
+    ("informe clínico", SectionKind::IndicacaoClinica),
+    ("indicação", SectionKind::IndicacaoClinica),
+    ("técnica do exame", SectionKind::Tecnica),
+    ("técnica de exame", SectionKind::Tecnica),
+    ("técnica", SectionKind::Tecnica),
+    ("aspectos técnicos", SectionKind::Tecnica),
+    ("aspectos observados", SectionKind::AspectosObservados),
+    ("achados", SectionKind::AspectosObservados),
+    ("impressão", SectionKind::Impressao),
+    ("conclusão", SectionKind::Impressao),
+    ("opinião", SectionKind::Impressao),
+];
+
+const ENGLISH_KEYWORDS: &[(&str, SectionKind)] = &[
+    ("clinical indication", SectionKind::IndicacaoClinica),
+    ("clinical history", SectionKind::IndicacaoClinica),
+    ("indication", SectionKind::IndicacaoClinica),
+    ("history", SectionKind::IndicacaoClinica),
+    ("technique", SectionKind::Tecnica),
+    ("findings", SectionKind::AspectosObservados),
+    ("impression", SectionKind::Impressao),
+    ("conclusion", SectionKind::Impressao),
+    ("opinion", SectionKind::Impressao),
+];
+
+const SPANISH_KEYWORDS: &[(&str, SectionKind)] = &[
+    ("información clínica", SectionKind::IndicacaoClinica),
+    ("indicación clínica", SectionKind::IndicacaoClinica),
+    ("indicación", SectionKind::IndicacaoClinica),
+    ("técnica del examen", SectionKind::Tecnica),
+    ("técnica", SectionKind::Tecnica),
+    ("hallazgos", SectionKind::AspectosObservados),
+    ("impresión diagnóstica", SectionKind::Impressao),
+    ("impresión", SectionKind::Impressao),
+    ("conclusiones", SectionKind::Impressao),
+    ("conclusión", SectionKind::Impressao),
+];
+
+/// The heading prefixes of `language`: those of `[section_keywords]`, for
+/// that language first, then the built-in ones. Each list is longest first.
+pub fn section_keywords(language: Language) -> impl Iterator<Item = (&'static str, SectionKind)> {
+    let configured = &config::get().section_keywords;
+    let for_language = configured
+        .iter()
+        .filter(move |(_, _, only)| *only == Some(language));
+    let for_all = configured.iter().filter(|(_, _, only)| only.is_none());
+    let builtin = match language {
+        Language::Portuguese => PORTUGUESE_KEYWORDS,
+        Language::English => ENGLISH_KEYWORDS,
+        Language::Spanish => SPANISH_KEYWORDS,
+    };
+    for_language
+        .chain(for_all)
+        .map(|(keyword, kind, _)| (keyword.as_str(), *kind))
+        .chain(builtin.iter().copied())
+}
+
+/// A run of template content. `heading` is the line that opened it
+/// (`**TÉCNICA DO EXAME:**`); sections started implicitly, such as the text
+/// before the first heading or organ findings after the technique, have none.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Section {
+    pub heading: Option<Paragraph>,
+    /// `None` for text before the first recognised heading.
+    pub kind: Option<SectionKind>,
+    /// Paragraphs (blank lines included) and tables, in order.
+    pub content: Vec<Content>,
+}
+
+impl Section {
+    /// The heading without its colon or inline text (`Informe clínico`), or
+    /// the kind's label for an implicit section.
+    pub fn name(&self) -> Option<String> {
+        match (&self.heading, self.kind) {
+            (Some(heading), _) => {
+                let line = heading.plain();
+                Some(line.split(':').next().unwrap_or("").trim().to_string())
+            }
+            (None, Some(kind)) => Some(kind.label().to_string()),
+            (None, None) => None,
+        }
+    }
+
+    /// Text written after the heading's colon (`**Informe clínico:** XXX`).
+    pub fn inline_text(&self) -> Option<String> {
+        // A recognised heading is its keyword, then the colon if any.
+        self.kind?;
+        let line = self.heading.as_ref()?.plain();
+        let (_, text) = line.split_once(':')?;
+        Some(text.trim().to_string()).filter(|text| !text.is_empty())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReportTemplate {
+    /// The YAML front matter block as written, fences included; never
+    /// rendered (see `front_matter`).
+    pub front_matter: Option<String>,
+    /// Lines of images above the title (the clinic logo) and the blank lines
+    /// between them, rendered centered.
+    pub letterhead: Vec<Paragraph>,
+    /// The first non-empty line after the letterhead, rendered centered.
+    pub title: Option<Paragraph>,
+    /// Whether the title and the footer are the front matter's `title:` and
+    /// `footer:` rather than lines of the body; `to_markdown` leaves them
+    /// out, as the front matter holds them.
+    pub title_from_front_matter: bool,
+    pub footer_from_front_matter: bool,
+    /// DICOM modality code (`CT`, `MR`, `US`, ...) from the front matter or
+    /// inferred from the title.
+    pub modality: Option<String>,
+    /// The language the section headings were recognised in.
+    pub language: Language,
+    pub sections: Vec<Section>,
+    /// The closing disclaimer: the last non-empty line, centered italic 8pt.
+    pub footer: Option<Paragraph>,
+    /// Blank lines before the title and after the footer, kept so rendering
+    /// reproduces the source spacing.
+    pub leading_blank_lines: usize,
+    pub trailing_blank_lines: usize,
+    /// The `[^label]: text` notes, in the order defined; see `footnotes`.
+    pub footnotes: Vec<Footnote>,
+}
+
+/// Inline Markdown without emphasis markers.
+pub fn plain(text: &str) -> String {
+    parse_inline(text, false)
+        .into_iter()
+        .map(|run| run.text)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Whether `line` is the text `marker` names, as the front matter's
+/// `title:` and `footer:` do: the same words, ignoring case, emphasis and
+/// `#` heading markers.
+fn same_text(line: &str, marker: &str) -> bool {
+    let text = |line: &str| plain(&normalize_heading(line).0).to_lowercase();
+    text(line) == text(marker)
+}
+
+/// Recognises a section heading of `language` and returns its kind plus
+/// any text written after the colon on the same line
+/// (`**Informe clínico:** XXX`).
+pub fn section_heading(line: &str, language: Language) -> Option<(SectionKind, String)> {
+    let lower = line.to_lowercase();
+    let (keyword, kind) =
+        section_keywords(language).find(|(keyword, _)| lower.starts_with(keyword))?;
+    let rest: String = line.chars().skip(keyword.chars().count()).collect();
+    let rest = rest.trim_start();
+    // "Técnica" must be the whole heading word, not "Técnicas utilizadas ...".
+    if !(rest.is_empty() || rest.starts_with(':')) {
+        return None;
+    }
+    Some((kind, rest.trim_start_matches(':').trim().to_string()))
+}
+
+impl ReportTemplate {
+    /// Parses a Markdown template using the layout rules of `markdown::layout`.
+    pub fn from_markdown(content: &str) -> Self {
+        let mut template = ReportTemplate::default();
+        let mut section = Section::default();
+        let mut started = false;
+
+        let (yaml, body) = front_matter::split(content);
+        if yaml.is_some() {
+            template.front_matter = Some(content[..content.len() - body.len()].to_string());
+        }
+        let front = template.metadata();
+        let (body, notes) = footnotes::split(body);
+        let body = body.as_str();
+        template.footnotes = notes;
+        template.language = language::template_language(front.language.as_deref(), body);
+
+        let mut title_footer = TitleFooter::first_and_last(body);
+        let marked = front.title.is_some() || front.footer.is_some();
+        if marked || config::get().layout == LayoutRule::Markers {
+            let lines: Vec<&str> = body.lines().collect();
+            let names = |index: usize, marker: &Option<String>| {
+                marker
+                    .as_deref()
+                    .is_some_and(|marker| same_text(lines[index], marker))
+            };
+            title_footer.title = title_footer.title.filter(|&i| names(i, &front.title));
+            title_footer.footer = title_footer.footer.filter(|&i| names(i, &front.footer));
+            if let (Some(title), None) = (&front.title, title_footer.title) {
+                template.title = Some(Paragraph::new(title.trim()));
+                template.title_from_front_matter = true;
+            }
+            if let (Some(footer), None) = (&front.footer, title_footer.footer) {
+                template.footer = Some(Paragraph::new(footer.trim()));
+                template.footer_from_front_matter = true;
+            }
+        }
+
+        for block in layout(body, title_footer) {
+            let (paragraph, style) = match block {
+                Block::Paragraph {
+                    text,
+                    heading,
+                    style,
+                } => (Paragraph { text, heading }, style),
+                Block::Table { header, rows } => {
+                    started = true;
+                    section.content.push(Content::Table { header, rows });
+                    continue;
+                }
+            };
+
+            if style.force_italic {
+                template.footer = Some(paragraph);
+                started = true;
+                continue;
+            }
+            if template.footer.is_some() && !template.footer_from_front_matter {
+                template.trailing_blank_lines += 1;
+                continue;
+            }
+            if !started {
+                if paragraph.is_blank() && template.letterhead.is_empty() {
+                    template.leading_blank_lines += 1;
+                    continue;
+                }
+                if paragraph.is_blank() || paragraph.is_image() {
+                    template.letterhead.push(paragraph);
+                    continue;
+                }
+                started = true;
+                if style.alignment == Alignment::Center {
+                    template.title = Some(paragraph);
+                    continue;
+                }
+            }
+
+            if let Some((kind, _)) = section_heading(&paragraph.plain(), template.language) {
+                let previous = std::mem::replace(
+                    &mut section,
+                    Section {
+                        heading: Some(paragraph),
+                        kind: Some(kind),
+                        content: Vec::new(),
+                    },
+                );
+                template.push_section(previous);
+                continue;
+            }
+
+            // Organ headings after the technique start the findings even
+            // without an explicit "Aspectos observados" heading.
+            if matches!(
+                section.kind,
+                Some(SectionKind::IndicacaoClinica | SectionKind::Tecnica)
+            ) && paragraph.is_subsection()
+            {
+                let previous = std::mem::replace(
+                    &mut section,
+                    Section {
+                        heading: None,
+                        kind: Some(SectionKind::AspectosObservados),
+                        content: Vec::new(),
+                    },
+                );
+                template.push_section(previous);
+            }
+            section.content.push(Content::Paragraph(paragraph));
+        }
+
+        template.push_section(section);
+        template.modality = match front.modality {
+            Some(modality) => Some(modality.to_uppercase()),
+            None => template
+                .title
+                .as_ref()
+                .and_then(|title| classify::modality(&title.plain())),
+        };
+        template
+    }
+
+    /// The parsed front matter; empty when there is none or it is invalid.
+    pub fn metadata(&self) -> FrontMatter {
+        self.front_matter
+            .as_deref()
+            .and_then(|block| front_matter::split(block).0)
+            .and_then(|yaml| front_matter::parse(yaml).ok())
+            .unwrap_or_default()
+    }
+
+    /// Reads a DOCX template through the Markdown importer.
+    pub fn from_docx(docx_path: &Path) -> Result<Self> {
+        Ok(Self::from_markdown(&convert_docx_to_markdown(docx_path)?))
+    }
+
+    fn push_section(&mut self, section: Section) {
+        if section.heading.is_some() || !section.content.is_empty() {
+            self.sections.push(section);
+        }
+    }
+
+    /// The first section of `kind`, if the template has one.
+    pub fn section(&self, kind: SectionKind) -> Option<&Section> {
+        self.sections.iter().find(|s| s.kind == Some(kind))
+    }
+
+    /// The standard disclaimer the writers add after the footer: the front
+    /// matter's `disclaimer:`, else the `disclaimer` setting. None when
+    /// either is empty or the footer already reads the same, as in
+    /// templates written before the setting.
+    pub fn disclaimer(&self) -> Option<Paragraph> {
+        let text = self
+            .metadata()
+            .disclaimer
+            .or_else(|| config::get().disclaimer.clone())?;
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let repeated = |footer: &Paragraph| same_text(&footer.text, &text);
+        if text.is_empty() || self.footer.as_ref().is_some_and(repeated) {
+            return None;
+        }
+        Some(Paragraph::new(text))
+    }
+
+    /// Lays the template out for the document writers.
+    pub fn blocks(&self) -> Vec<Block> {
+        self.section_blocks()
+            .into_iter()
+            .map(|(_, block)| block)
+            .collect()
+    }
+
+    /// `blocks`, each with the kind of the section it belongs to: its
+    /// heading and content. The title, footer and the lines around them
+    /// belong to none.
+    pub fn section_blocks(&self) -> Vec<(Option<SectionKind>, Block)> {
+        let blank = || Block::Paragraph {
+            text: String::new(),
+            heading: None,
+            style: LineStyle::default(),
+        };
+
+        let mut blocks: Vec<(Option<SectionKind>, Block)> = (0..self.leading_blank_lines)
+            .map(|_| (None, blank()))
+            .collect();
+        for paragraph in &self.letterhead {
+            let mut style = LineStyle::default();
+            if paragraph.is_image() {
+                style.alignment = Alignment::Center;
+            }
+            blocks.push((None, paragraph.block(style)));
+        }
+        if let Some(title) = &self.title {
+            blocks.push((
+                None,
+                title.block(LineStyle {
+                    alignment: Alignment::Center,
+                    ..LineStyle::default()
+                }),
+            ));
+        }
+        for section in &self.sections {
+            if let Some(heading) = &section.heading {
+                blocks.push((section.kind, heading.block(LineStyle::default())));
+            }
+            for content in &section.content {
+                blocks.push((
+                    section.kind,
+                    match content {
+                        Content::Paragraph(paragraph) => paragraph.block(LineStyle::default()),
+                        Content::Table { header, rows } => Block::Table {
+                            header: header.clone(),
+                            rows: rows.clone(),
+                        },
+                    },
+                ));
+            }
+        }
+        let footer_style = LineStyle {
+            alignment: Alignment::Center,
+            force_italic: true,
+            font_size_pt: FOOTER_FONT_SIZE_PT,
+        };
+        if let Some(footer) = &self.footer {
+            blocks.push((None, footer.block(footer_style)));
+        }
+        if let Some(disclaimer) = self.disclaimer() {
+            blocks.push((None, blank()));
+            blocks.push((None, disclaimer.block(footer_style)));
+        }
+        blocks.extend((0..self.trailing_blank_lines).map(|_| (None, blank())));
+        blocks
+    }
+
+    /// The lines of the page header and footer of the selected site (see
+    /// `Config::select_site`) as paragraphs, none without one. The front
+    /// matter's `page_header` and `page_footer` replace the site's, as in
+    /// DOCX; image lines are left out.
+    pub fn page_parts(&self) -> (Vec<Paragraph>, Vec<Paragraph>) {
+        let settings = config::get();
+        if settings.site.is_none() {
+            return (Vec::new(), Vec::new());
+        }
+        let front_matter = self.metadata();
+        let lines = |markdown: Option<&String>| -> Vec<Paragraph> {
+            markdown
+                .into_iter()
+                .flat_map(|markdown| markdown.lines())
+                .flat_map(|line| line.split("<br>"))
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !is_image_line(line))
+                .map(Paragraph::new)
+                .collect()
+        };
+        (
+            lines(
+                front_matter
+                    .page_header
+                    .as_ref()
+                    .or(settings.page_header.as_ref()),
+            ),
+            lines(
+                front_matter
+                    .page_footer
+                    .as_ref()
+                    .or(settings.page_footer.as_ref()),
+            ),
+        )
+    }
+
+    /// The notes the text refers to, in the order of their first reference,
+    /// so the first is note 1.
+    pub fn notes(&self) -> Vec<&Footnote> {
+        footnotes::referenced(&self.blocks(), &self.footnotes)
+    }
+
+    /// `blocks` followed by the notes, a paragraph each at the footer size
+    /// after a blank line, for the formats without footnotes.
+    pub fn blocks_with_notes(&self) -> Vec<Block> {
+        let mut blocks = self.blocks();
+        let notes = self.notes();
+        if !notes.is_empty() {
+            blocks.push(Paragraph::default().block(LineStyle::default()));
+        }
+        blocks.extend(notes.into_iter().map(|note| {
+            Paragraph::new(format!("[^{}] {}", note.label, note.text)).block(LineStyle {
+                alignment: Alignment::Left,
+                font_size_pt: FOOTER_FONT_SIZE_PT,
+                ..LineStyle::default()
+            })
+        }));
+        blocks
+    }
+
+    /// `blocks_with_notes` between the site's page header and footer: the
+    /// layout of the formats without pages of their own (ODT, RTF and
+    /// LaTeX; HTML has its own).
+    pub fn document_blocks(&self) -> Vec<Block> {
+        self.framed(self.blocks_with_notes())
+    }
+
+    /// `body` between the site's page header and footer, centered, the
+    /// footer at the footer size.
+    pub fn framed(&self, body: Vec<Block>) -> Vec<Block> {
+        let (header, footer) = self.page_parts();
+        let mut blocks: Vec<Block> = header
+            .iter()
+            .map(|paragraph| {
+                paragraph.block(LineStyle {
+                    alignment: Alignment::Center,
+                    ..LineStyle::default()
+                })
+            })
+            .collect();
+        blocks.extend(body);
+        blocks.extend(footer.iter().map(|paragraph| {
+            paragraph.block(LineStyle {
+                alignment: Alignment::Center,
+                font_size_pt: FOOTER_FONT_SIZE_PT,
+                ..LineStyle::default()
+            })
+        }));
+        blocks
+    }
+
+    /// The author of the documents written from the template: the front
+    /// matter's, else the name of the selected site.
+    pub fn author(&self) -> Option<String> {
+        self.metadata()
+            .author
+            .or_else(|| config::get().selected_site()?.name.clone())
+            .filter(|author| !author.trim().is_empty())
+    }
+
+    /// Writes the template back as Markdown in the repository's layout.
+    pub fn to_markdown(&self) -> String {
+        let mut lines: Vec<String> = vec![String::new(); self.leading_blank_lines];
+        lines.extend(self.letterhead.iter().map(Paragraph::to_markdown));
+        if let Some(title) = self
+            .title
+            .as_ref()
+            .filter(|_| !self.title_from_front_matter)
+        {
+            lines.push(title.to_markdown());
+        }
+        for section in &self.sections {
+            if let Some(heading) = &section.heading {
+                lines.push(heading.to_markdown());
+            }
+            for content in &section.content {
+                match content {
+                    Content::Paragraph(paragraph) => lines.push(paragraph.to_markdown()),
+                    Content::Table { header, rows } => {
+                        let all_rows: Vec<Vec<String>> =
+                            std::iter::once(header).chain(rows).cloned().collect();
+                        lines.extend(rows_to_markdown(&all_rows));
+                    }
+                }
+            }
+        }
+        if let Some(footer) = self
+            .footer
+            .as_ref()
+            .filter(|_| !self.footer_from_front_matter)
+        {
+            lines.push(footer.to_markdown());
+        }
+        lines.extend(std::iter::repeat_n(
+            String::new(),
+            self.trailing_blank_lines,
+        ));
+        lines.extend(self.footnotes.iter().map(Footnote::to_markdown));
+        let markdown = lines.join("\n") + "\n";
+        match &self.front_matter {
+            Some(front_matter) => format!("{}{}", front_matter, markdown),
+            None => markdown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_split_into_title_sections_and_footer_and_write_back() {
+        let markdown = "\n**TC DE CRÂNIO**\n\n**Informe clínico:** cefaleia.\n\n**Técnica:**\n\n| Série | mm |\n| --- | --- |\n| 1 | 5 |\n\n## Análise\n\n**Encéfalo:**\n\nSem alterações.\n\n**Impressão:**\n\nNormal.\n\n*Laudo assinado eletronicamente.*\n";
+        let template = ReportTemplate::from_markdown(markdown);
+        assert_eq!(template.title, Some(Paragraph::new("**TC DE CRÂNIO**")));
+        assert_eq!(
+            template.footer,
+            Some(Paragraph::new("*Laudo assinado eletronicamente.*"))
+        );
+        assert_eq!(template.leading_blank_lines, 1);
+        let kinds: Vec<_> = template
+            .sections
+            .iter()
+            .map(|section| (section.kind, section.name()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (None, None),
+                (
+                    Some(SectionKind::IndicacaoClinica),
+                    Some("Informe clínico".into())
+                ),
+                (Some(SectionKind::Tecnica), Some("Técnica".into())),
+                (
+                    Some(SectionKind::AspectosObservados),
+                    Some("Aspectos observados".into())
+                ),
+                (Some(SectionKind::Impressao), Some("Impressão".into())),
+            ]
+        );
+        assert_eq!(template.sections[1].inline_text(), Some("cefaleia.".into()));
+        let tecnica = template.section(SectionKind::Tecnica).unwrap();
+        assert!(tecnica.content.contains(&Content::Table {
+            header: vec!["Série".into(), "mm".into()],
+            rows: vec![vec!["1".into(), "5".into()]],
+        }));
+        let findings = template.section(SectionKind::AspectosObservados).unwrap();
+        assert_eq!(findings.heading, None);
+        assert!(tecnica.content.contains(&Content::Paragraph(Paragraph {
+            text: "Análise".into(),
+            heading: Some(2),
+        })));
+        assert_eq!(
+            findings.content[0],
+            Content::Paragraph(Paragraph::new("**Encéfalo:**"))
+        );
+        assert!(Paragraph::new("**Encéfalo:**").is_subsection());
+        assert!(!Paragraph::new("**Encéfalo:** sem alterações").is_subsection());
+        assert_eq!(template.to_markdown(), markdown);
+    }
+}
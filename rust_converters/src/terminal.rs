@@ -0,0 +1,170 @@
+//! The raw terminal behind the interactive commands (`pick`, `browse`).
+//!
+//! Keys are read from `/dev/tty` one at a time, without line buffering,
+//! echo or signal keys, and the screen is drawn on the alternate screen, so
+//! standard output stays free and the shell comes back as it was.
+
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+
+const ENTER_SCREEN: &str = "\x1b[?1049h";
+const LEAVE_SCREEN: &str = "\x1b[?1049l";
+pub(crate) const RESET: &str = "\x1b[0m";
+
+pub(crate) enum Key {
+    Char(char),
+    Backspace,
+    ClearLine,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    Cancel,
+    Other,
+}
+
+/// `/dev/tty` without line buffering, echo, or signal keys; the previous
+/// settings and screen come back when dropped.
+pub(crate) struct RawTerminal {
+    pub(crate) tty: File,
+    saved: libc::termios,
+}
+
+impl RawTerminal {
+    pub(crate) fn open() -> Result<Self> {
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .context("The interactive commands need a terminal")?;
+        let fd = tty.as_raw_fd();
+        // SAFETY: `termios` is plain data filled in by `tcgetattr`.
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("Cannot read terminal mode");
+        }
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("Cannot set terminal mode");
+        }
+        let mut terminal = RawTerminal { tty, saved };
+        write!(terminal.tty, "{}", ENTER_SCREEN)?;
+        Ok(terminal)
+    }
+
+    fn size(&self) -> libc::winsize {
+        // SAFETY: `winsize` is plain data filled in by the ioctl.
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) } != 0 {
+            size.ws_row = 0;
+            size.ws_col = 0;
+        }
+        size
+    }
+
+    pub(crate) fn rows(&self) -> usize {
+        match self.size().ws_row {
+            rows if rows > 2 => rows as usize,
+            _ => 24,
+        }
+    }
+
+    pub(crate) fn columns(&self) -> usize {
+        match self.size().ws_col {
+            columns if columns > 20 => columns as usize,
+            _ => 80,
+        }
+    }
+
+    /// Replaces the screen with `screen`.
+    pub(crate) fn show(&mut self, screen: &str) -> Result<()> {
+        self.tty.write_all(screen.as_bytes())?;
+        self.tty.flush()?;
+        Ok(())
+    }
+
+    /// Whether another byte arrives within 50 ms, to tell a lone Esc
+    /// from the start of an arrow key sequence.
+    fn pending(&self) -> bool {
+        let mut poll = libc::pollfd {
+            fd: self.tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut poll, 1, 50) > 0 }
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let mut buffer = [0u8; 1];
+        self.tty.read_exact(&mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    pub(crate) fn key(&mut self) -> Result<Key> {
+        let first = self.byte()?;
+        Ok(match first {
+            b'\r' | b'\n' => Key::Enter,
+            3 | 7 => Key::Cancel,
+            8 | 127 => Key::Backspace,
+            21 => Key::ClearLine,
+            11 | 16 => Key::Up,
+            14 => Key::Down,
+            0x1b => {
+                if !self.pending() {
+                    return Ok(Key::Cancel);
+                }
+                let second = self.byte()?;
+                if second != b'[' && second != b'O' {
+                    return Ok(Key::Other);
+                }
+                match self.byte()? {
+                    b'A' => Key::Up,
+                    b'B' => Key::Down,
+                    // `ESC [ 5 ~` and `ESC [ 6 ~`.
+                    page @ (b'5' | b'6') => match (self.byte()?, page) {
+                        (b'~', b'5') => Key::PageUp,
+                        (b'~', _) => Key::PageDown,
+                        _ => Key::Other,
+                    },
+                    _ => Key::Other,
+                }
+            }
+            0x20..=0x7e => Key::Char(first as char),
+            0xc0..=0xf7 => {
+                let len = match first {
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    _ => 4,
+                };
+                let mut bytes = vec![first];
+                for _ in 1..len {
+                    bytes.push(self.byte()?);
+                }
+                match std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|s| s.chars().next())
+                {
+                    Some(c) => Key::Char(c),
+                    None => Key::Other,
+                }
+            }
+            _ => Key::Other,
+        })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = write!(self.tty, "{}", LEAVE_SCREEN);
+        let _ = self.tty.flush();
+        unsafe {
+            libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.saved);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Preferred terms (the `terminology` lint rule).
+//!
+//! A lexicon is a CSV file. A RadLex export is read by its `Preferred Label`
+//! and `Synonyms` columns, the synonyms separated by `|`; any other file
+//! has the preferred term in the first column and its synonyms in the
+//! following ones (`derrame pleural,efusão pleural,líquido pleural`).
+//! Terms match whole words, ignoring case and punctuation, and the longest
+//! term wins, so a preferred term that contains a synonym is not flagged.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Splits one CSV line into fields, with `"` quoting and `""` escapes.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// The words of `text`, lowercased, with the byte range of each.
+fn words(text: &str) -> Vec<(std::ops::Range<usize>, String)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(from)) => {
+                words.push((from..i, text[from..i].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+fn key(term: &str) -> String {
+    words(term)
+        .into_iter()
+        .map(|(_, word)| word)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A term written where the lexicon prefers another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonPreferred {
+    /// The term as written in the line.
+    pub written: String,
+    pub preferred: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Lexicon {
+    /// Each term by its lowercased words, with the preferred term for
+    /// synonyms and None for preferred terms.
+    terms: HashMap<String, Option<String>>,
+    /// Words in the longest term.
+    longest: usize,
+}
+
+impl Lexicon {
+    /// Reads the CSV text of a lexicon.
+    pub fn parse_csv(text: &str) -> Lexicon {
+        let mut lexicon = Lexicon::default();
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.clone().next().map(csv_fields).unwrap_or_default();
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|field| field.trim().eq_ignore_ascii_case(name))
+        };
+        let radlex = column("Preferred Label").zip(column("Synonyms"));
+        if radlex.is_some() {
+            lines.next();
+        }
+
+        for line in lines {
+            let fields = csv_fields(line);
+            let (preferred, synonyms): (&str, Vec<&str>) = match radlex {
+                Some((label, synonyms)) => (
+                    fields.get(label).map_or("", String::as_str),
+                    fields
+                        .get(synonyms)
+                        .map_or(Vec::new(), |cell| cell.split('|').collect()),
+                ),
+                None => match fields.split_first() {
+                    Some((first, rest)) => (
+                        first.as_str(),
+                        rest.iter().flat_map(|cell| cell.split('|')).collect(),
+                    ),
+                    None => continue,
+                },
+            };
+            let preferred = preferred.trim();
+            if key(preferred).is_empty() {
+                continue;
+            }
+            lexicon.add(preferred, None);
+            for synonym in synonyms {
+                lexicon.add(synonym, Some(preferred));
+            }
+        }
+        lexicon
+    }
+
+    /// Reads the lexicon at `path`.
+    pub fn open(path: &Path) -> Result<Lexicon> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read lexicon {}", path.display()))?;
+        Ok(Lexicon::parse_csv(&text))
+    }
+
+    /// Adds `term`, preferred or a synonym of `preferred`. A term listed as
+    /// preferred anywhere stays preferred.
+    fn add(&mut self, term: &str, preferred: Option<&str>) {
+        let term_key = key(term);
+        if term_key.is_empty() || preferred.is_some_and(|p| key(p) == term_key) {
+            return;
+        }
+        self.longest = self.longest.max(term_key.split(' ').count());
+        let entry = self
+            .terms
+            .entry(term_key)
+            .or_insert_with(|| preferred.map(str::to_string));
+        if preferred.is_none() {
+            *entry = None;
+        }
+    }
+
+    /// The synonyms written in `line` instead of their preferred term, in
+    /// order.
+    pub fn check_line(&self, line: &str) -> Vec<NonPreferred> {
+        let words = words(line);
+        let mut found = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            let longest = self.longest.min(words.len() - i);
+            let matched = (1..=longest).rev().find_map(|count| {
+                let phrase: Vec<&str> = words[i..i + count]
+                    .iter()
+                    .map(|(_, word)| word.as_str())
+                    .collect();
+                self.terms
+                    .get(&phrase.join(" "))
+                    .map(|preferred| (count, preferred))
+            });
+            match matched {
+                Some((count, preferred)) => {
+                    if let Some(preferred) = preferred {
+                        let range = words[i].0.start..words[i + count - 1].0.end;
+                        found.push(NonPreferred {
+                            written: line[range].to_string(),
+                            preferred: preferred.clone(),
+                        });
+                    }
+                    i += count;
+                }
+                None => i += 1,
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synonyms_suggest_the_preferred_term() {
+        let lexicon = Lexicon::parse_csv(
+            "derrame pleural,efusão pleural,\"líquido pleural\"\n\
+             esteatose hepática,infiltração gordurosa|fígado gorduroso\n\
+             fígado,\n",
+        );
+        let found = lexicon.check_line(
+            "Pequena Efusão pleural à direita; fígado com infiltração gordurosa. Derrame pleural ausente.",
+        );
+        assert_eq!(
+            found,
+            [
+                NonPreferred {
+                    written: "Efusão pleural".to_string(),
+                    preferred: "derrame pleural".to_string()
+                },
+                NonPreferred {
+                    written: "infiltração gordurosa".to_string(),
+                    preferred: "esteatose hepática".to_string()
+                },
+            ]
+        );
+
+        let radlex = Lexicon::parse_csv(
+            "Class ID,Preferred Label,Synonyms\n\
+             RID58,liver,hepar|hepatic organ\n",
+        );
+        assert_eq!(
+            radlex.check_line("The hepar is normal; liver too.")[0].preferred,
+            "liver"
+        );
+    }
+}
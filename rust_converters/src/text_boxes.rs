@@ -0,0 +1,171 @@
+//! Text boxes and shapes of a DOCX being imported, such as the title of a
+//! legacy template drawn in a box over the letterhead. docx-rust skips what
+//! a drawing holds, so their paragraphs are moved into the body before it
+//! reads the package.
+//!
+//! A box's paragraphs go before the paragraph it is anchored to; the boxes
+//! of one paragraph in the order they sit on the page, top to bottom, then
+//! left to right. Word writes each box twice, as DrawingML and as the VML
+//! fallback for older readers; the fallback is dropped, and boxes written as
+//! VML only are read too. `document.xml` is edited as text, as `revisions`
+//! does.
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+// DrawingML offsets are in EMU, VML ones in points.
+const EMU_PER_POINT: f64 = 12700.0;
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid regex"))
+}
+
+/// A box found in the body and where it goes.
+struct TextBox {
+    /// Start of the paragraph it is anchored to, in the rewritten XML.
+    anchor: usize,
+    /// Offsets on the page, in EMU: down, then across.
+    position: (i64, i64),
+    /// Its paragraphs and tables.
+    content: String,
+}
+
+/// `document_xml` with the content of its text boxes in the body, before
+/// the paragraphs they are anchored to.
+pub fn into_body(document_xml: &str) -> String {
+    static FALLBACK: OnceLock<Regex> = OnceLock::new();
+    static SHAPE: OnceLock<Regex> = OnceLock::new();
+    static CONTENT: OnceLock<Regex> = OnceLock::new();
+    static PARAGRAPH: OnceLock<Regex> = OnceLock::new();
+    if !document_xml.contains("<w:txbxContent") {
+        return document_xml.to_string();
+    }
+    let xml = regex(&FALLBACK, r"(?s)<mc:Fallback>.*?</mc:Fallback>").replace_all(
+        document_xml,
+        |caps: &regex::Captures| {
+            if caps[0].contains("<w:txbxContent") {
+                String::new()
+            } else {
+                caps[0].to_string()
+            }
+        },
+    );
+    let shape = regex(
+        &SHAPE,
+        r"(?s)<w:drawing>.*?</w:drawing>|<w:pict>.*?</w:pict>",
+    );
+    let content = regex(&CONTENT, r"(?s)<w:txbxContent>(.*?)</w:txbxContent>");
+    let paragraph = regex(&PARAGRAPH, r"<w:p[\s>/]");
+
+    let mut body = String::with_capacity(xml.len());
+    let mut boxes = Vec::new();
+    let mut copied = 0;
+    // The last paragraph started in `body`, looked for up to `scanned`.
+    let (mut last_paragraph, mut scanned) = (None, 0);
+    for found in shape.find_iter(&xml) {
+        let drawn = found.as_str();
+        if !drawn.contains("<w:txbxContent>") {
+            continue;
+        }
+        body.push_str(&xml[copied..found.start()]);
+        copied = found.end();
+        if let Some(start) = paragraph.find_iter(&body[scanned..]).last() {
+            last_paragraph = Some(scanned + start.start());
+        }
+        scanned = body.len();
+        let Some(anchor) = last_paragraph else {
+            body.push_str(drawn);
+            continue;
+        };
+        boxes.push(TextBox {
+            anchor,
+            position: position(drawn),
+            content: content
+                .captures_iter(drawn)
+                .map(|c| c[1].to_string())
+                .collect(),
+        });
+        body.push_str(&content.replace_all(drawn, "<w:txbxContent></w:txbxContent>"));
+        scanned = body.len();
+    }
+    body.push_str(&xml[copied..]);
+
+    // Inserted from the end, so the anchors before stay where they are.
+    boxes.sort_by_key(|text_box| (std::cmp::Reverse(text_box.anchor), text_box.position));
+    let mut inserts: Vec<(usize, String)> = Vec::new();
+    for text_box in boxes {
+        match inserts.last_mut() {
+            Some((anchor, content)) if *anchor == text_box.anchor => {
+                content.push_str(&text_box.content)
+            }
+            _ => inserts.push((text_box.anchor, text_box.content)),
+        }
+    }
+    for (anchor, content) in inserts {
+        body.insert_str(anchor, &content);
+    }
+    body
+}
+
+/// Where the shape `drawn` sits on the page: DrawingML's `wp:posOffset`, or
+/// the `margin-top` and `margin-left` of a VML shape; 0 when not given, as
+/// for an inline box.
+fn position(drawn: &str) -> (i64, i64) {
+    static OFFSET: OnceLock<Regex> = OnceLock::new();
+    static MARGIN: OnceLock<Regex> = OnceLock::new();
+    let offset = regex(
+        &OFFSET,
+        r"(?s)<wp:position(V|H)\b[^>]*>\s*<wp:posOffset>(-?\d+)</wp:posOffset>",
+    );
+    let margin = regex(&MARGIN, r"margin-(top|left):(-?[\d.]+)pt");
+    let (mut down, mut across) = (0, 0);
+    for caps in offset.captures_iter(drawn) {
+        let value = caps[2].parse().unwrap_or(0);
+        match &caps[1] {
+            "V" => down = value,
+            _ => across = value,
+        }
+    }
+    for caps in margin.captures_iter(drawn) {
+        let value = (caps[2].parse::<f64>().unwrap_or(0.0) * EMU_PER_POINT) as i64;
+        match &caps[1] {
+            "top" => down = value,
+            _ => across = value,
+        }
+    }
+    (down, across)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_boxes_come_before_their_paragraph_in_page_order() {
+        let text_box = |v: i64, h: i64, text: &str| {
+            format!(
+                r#"<mc:AlternateContent><mc:Choice Requires="wps"><w:drawing><wp:anchor><wp:positionH relativeFrom="page"><wp:posOffset>{h}</wp:posOffset></wp:positionH><wp:positionV relativeFrom="page"><wp:posOffset>{v}</wp:posOffset></wp:positionV><a:graphic><a:graphicData><wps:wsp><wps:txbx><w:txbxContent><w:p><w:r><w:t>{text}</w:t></w:r></w:p></w:txbxContent></wps:txbx></wps:wsp></a:graphicData></a:graphic></wp:anchor></w:drawing></mc:Choice><mc:Fallback><w:pict><v:shape><v:textbox><w:txbxContent><w:p><w:r><w:t>{text}</w:t></w:r></w:p></w:txbxContent></v:textbox></v:shape></w:pict></mc:Fallback></mc:AlternateContent>"#
+            )
+        };
+        let xml = format!(
+            r#"<w:body><w:p><w:pPr/><w:r>{}</w:r><w:r>{}</w:r><w:r><w:t>Indicação:</w:t></w:r></w:p><w:p><w:r><w:pict><v:shape style="position:absolute;margin-left:10pt;margin-top:5.5pt"><v:textbox><w:txbxContent><w:p><w:r><w:t>Carimbo</w:t></w:r></w:p></w:txbxContent></v:textbox></v:shape></w:pict></w:r></w:p></w:body>"#,
+            text_box(900000, 0, "Subtítulo"),
+            text_box(100000, 500000, "TC DE CRÂNIO"),
+        );
+        let body = into_body(&xml);
+        assert!(!body.contains("mc:Fallback"));
+        let texts: Vec<&str> = body
+            .split("<w:t>")
+            .skip(1)
+            .map(|text| &text[..text.find('<').unwrap()])
+            .collect();
+        assert_eq!(
+            texts,
+            ["TC DE CRÂNIO", "Subtítulo", "Indicação:", "Carimbo"]
+        );
+        assert!(body.starts_with(
+            "<w:body><w:p><w:r><w:t>TC DE CRÂNIO</w:t></w:r></w:p><w:p><w:r><w:t>Subtítulo"
+        ));
+        assert_eq!(position(r#"style="margin-top:5.5pt""#), (69850, 0));
+    }
+}
@@ -0,0 +1,110 @@
+//! Calendar dates for file metadata and report placeholders, without a
+//! date-time dependency.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A point in time broken down in UTC, or in local time (`now_local`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    /// The current wall-clock time of the local time zone, for dates
+    /// written into reports; UTC where the zone cannot be read.
+    pub fn now_local() -> Self {
+        let now = SystemTime::now();
+        let offset = utc_offset_seconds(now);
+        let shifted = if offset >= 0 {
+            now.checked_add(Duration::from_secs(offset as u64))
+        } else {
+            now.checked_sub(Duration::from_secs(offset.unsigned_abs()))
+        };
+        Self::from_system_time(shifted.unwrap_or(now))
+    }
+
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let of_day = secs % 86_400;
+
+        // Civil-from-days conversion (proleptic Gregorian calendar).
+        let z = (secs / 86_400) as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+
+        Timestamp {
+            year: yoe + era * 400 + i64::from(month <= 2),
+            month,
+            day,
+            hour: (of_day / 3_600) as u32,
+            minute: (of_day % 3_600 / 60) as u32,
+            second: (of_day % 60) as u32,
+        }
+    }
+
+    /// `YYYY-MM-DD`.
+    pub fn iso_date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// `YYYY-MM-DDTHH:MM:SSZ`.
+    pub fn iso_datetime(&self) -> String {
+        format!(
+            "{}T{:02}:{:02}:{:02}Z",
+            self.iso_date(),
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+}
+
+#[cfg(unix)]
+fn utc_offset_seconds(time: SystemTime) -> i64 {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0) as libc::time_t;
+    // SAFETY: `tm` is plain data filled in by `localtime_r`.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+#[cfg(not(unix))]
+fn utc_offset_seconds(_time: SystemTime) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_times_break_down_into_the_utc_calendar() {
+        let at = |secs| Timestamp::from_system_time(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0).iso_datetime(), "1970-01-01T00:00:00Z");
+        assert_eq!(at(1_709_214_307).iso_datetime(), "2024-02-29T13:45:07Z");
+        assert_eq!(at(1_709_251_200).iso_date(), "2024-03-01");
+        assert_eq!(at(4_102_444_799).iso_datetime(), "2099-12-31T23:59:59Z");
+    }
+}
@@ -0,0 +1,257 @@
+//! Translation skeletons of templates (`rtemplates translate-skeleton`).
+//!
+//! The skeleton keeps the structure of a template and translates what a
+//! built-in table knows: the section headings and common organ
+//! subsections (`**Fígado:**` → `**Liver:**`), in the case they were
+//! written in. Every other line, the title and footer included, becomes a
+//! `TODO (pt): ...` line holding the original, to be replaced by the
+//! translation. The front matter gets the target `language:`, so the
+//! headings are read in that language still while the TODO lines remain.
+
+use anyhow::{bail, Result};
+
+use crate::language::Language;
+use crate::template::{plain, Content, Paragraph, ReportTemplate, Section, SectionKind};
+
+/// Section headings per language.
+fn section_label(kind: SectionKind, language: Language) -> &'static str {
+    match (kind, language) {
+        (_, Language::Portuguese) => kind.label(),
+        (SectionKind::IndicacaoClinica, Language::English) => "Clinical indication",
+        (SectionKind::Tecnica, Language::English) => "Technique",
+        (SectionKind::AspectosObservados, Language::English) => "Findings",
+        (SectionKind::Impressao, Language::English) => "Impression",
+        (SectionKind::IndicacaoClinica, Language::Spanish) => "Indicación clínica",
+        (SectionKind::Tecnica, Language::Spanish) => "Técnica del examen",
+        (SectionKind::AspectosObservados, Language::Spanish) => "Hallazgos",
+        (SectionKind::Impressao, Language::Spanish) => "Impresión",
+    }
+}
+
+/// Subsection names in Portuguese, English and Spanish.
+const SUBSECTIONS: &[[&str; 3]] = &[
+    ["Estrutura óssea", "Bone structures", "Estructuras óseas"],
+    ["Tórax", "Chest", "Tórax"],
+    ["Pescoço", "Neck", "Cuello"],
+    ["Crânio", "Head", "Cráneo"],
+    ["Abdome e pelve", "Abdomen and pelvis", "Abdomen y pelvis"],
+    ["Abdome", "Abdomen", "Abdomen"],
+    ["Pelve", "Pelvis", "Pelvis"],
+    ["Coluna cervical", "Cervical spine", "Columna cervical"],
+    ["Coluna torácica", "Thoracic spine", "Columna torácica"],
+    [
+        "Coluna lombossacra",
+        "Lumbosacral spine",
+        "Columna lumbosacra",
+    ],
+    ["Encéfalo", "Brain", "Encéfalo"],
+    [
+        "Vasos arteriais encefálicos",
+        "Intracranial arteries",
+        "Arterias intracraneales",
+    ],
+    [
+        "Vasos arteriais cervicais",
+        "Cervical arteries",
+        "Arterias cervicales",
+    ],
+    ["Pulmões", "Lungs", "Pulmones"],
+    ["Mediastino", "Mediastinum", "Mediastino"],
+    ["Pleura", "Pleura", "Pleura"],
+    ["Coração", "Heart", "Corazón"],
+    ["Aorta", "Aorta", "Aorta"],
+    ["Fígado", "Liver", "Hígado"],
+    ["Vesícula biliar", "Gallbladder", "Vesícula biliar"],
+    ["Vias biliares", "Bile ducts", "Vías biliares"],
+    ["Pâncreas", "Pancreas", "Páncreas"],
+    ["Baço", "Spleen", "Bazo"],
+    ["Rins", "Kidneys", "Riñones"],
+    ["Adrenais", "Adrenal glands", "Glándulas suprarrenales"],
+    ["Bexiga", "Bladder", "Vejiga"],
+    ["Útero", "Uterus", "Útero"],
+    ["Ovários", "Ovaries", "Ovarios"],
+    ["Próstata", "Prostate", "Próstata"],
+    ["Tireoide", "Thyroid", "Tiroides"],
+    ["Linfonodos", "Lymph nodes", "Ganglios linfáticos"],
+    ["Partes moles", "Soft tissues", "Partes blandas"],
+];
+
+fn column(language: Language) -> usize {
+    match language {
+        Language::Portuguese => 0,
+        Language::English => 1,
+        Language::Spanish => 2,
+    }
+}
+
+/// `label` in the case `written` uses: all capitals or as listed.
+fn same_case(label: &str, written: &str) -> String {
+    if written.chars().any(char::is_alphabetic) && !written.chars().any(char::is_lowercase) {
+        label.to_uppercase()
+    } else {
+        label.to_string()
+    }
+}
+
+/// The original line, marked for translation.
+fn todo(text: &str, source: Language) -> String {
+    format!("TODO ({}): {}", source.code(), text)
+}
+
+/// A heading line with its name replaced by `name`, keeping its `#` level
+/// or bold and the colon; text after the colon becomes a TODO.
+fn heading_line(heading: &Paragraph, name: &str, source: Language) -> String {
+    let line = heading.plain();
+    let (written, rest) = match line.split_once(':') {
+        Some((written, rest)) => (written.trim(), Some(rest.trim())),
+        None => (line.trim(), None),
+    };
+    let name = same_case(name, written);
+    let colon = if rest.is_some() { ":" } else { "" };
+    let mut out = match heading.heading {
+        Some(level) => format!("{} {}{}", "#".repeat(level as usize), name, colon),
+        None if heading.text.trim_start().starts_with("**") => format!("**{}{}**", name, colon),
+        None => format!("{}{}", name, colon),
+    };
+    if let Some(rest) = rest.filter(|rest| !rest.is_empty()) {
+        out.push(' ');
+        out.push_str(&todo(rest, source));
+    }
+    out
+}
+
+fn section_lines(section: &Section, source: Language, target: Language) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let (Some(heading), Some(kind)) = (&section.heading, section.kind) {
+        lines.push(heading_line(heading, section_label(kind, target), source));
+    }
+    for content in &section.content {
+        match content {
+            Content::Paragraph(paragraph) if paragraph.is_blank() => {}
+            Content::Paragraph(paragraph) if paragraph.is_subsection() => {
+                let written = plain(&paragraph.text);
+                let written = written.trim_end_matches(':').trim();
+                let lower = written.to_lowercase();
+                let known = SUBSECTIONS
+                    .iter()
+                    .find(|names| names[column(source)].to_lowercase() == lower);
+                lines.push(match known {
+                    Some(names) => heading_line(paragraph, names[column(target)], source),
+                    None => format!("**{}:**", todo(written, source)),
+                });
+            }
+            Content::Paragraph(paragraph) => {
+                lines.push(todo(&paragraph.to_markdown(), source));
+            }
+            Content::Table { header, rows } => {
+                lines.push(todo("the table below", source));
+                let all_rows: Vec<Vec<String>> =
+                    std::iter::once(header).chain(rows).cloned().collect();
+                lines.push(
+                    crate::convert_to_markdown::rows_to_markdown(&all_rows)
+                        .join("\n")
+                        .trim_end()
+                        .to_string(),
+                );
+            }
+        }
+    }
+    lines
+}
+
+/// The BCP 47 tag a skeleton's front matter gets.
+fn language_tag(language: Language) -> &'static str {
+    match language {
+        Language::Portuguese => "pt-BR",
+        Language::English => "en-US",
+        Language::Spanish => "es-ES",
+    }
+}
+
+/// The skeleton of the Markdown template `content` in `target`.
+pub fn skeleton(content: &str, target: Language) -> Result<String> {
+    let template = ReportTemplate::from_markdown(content);
+    let source = template.language;
+    if source == target {
+        bail!("The template is already in {}", target.code());
+    }
+
+    // The front matter as written, less what names source text, with the
+    // target language.
+    let mut front = vec!["---".to_string()];
+    if let Some(block) = &template.front_matter {
+        front.extend(
+            block
+                .lines()
+                .filter(|line| *line != "---")
+                .filter(|line| {
+                    let key = line.split(':').next().unwrap_or_default().trim();
+                    !matches!(
+                        key,
+                        "language" | "title" | "footer" | "keywords" | "disclaimer"
+                    )
+                })
+                .map(str::to_string),
+        );
+    }
+    front.push(format!("language: {}", language_tag(target)));
+    front.push("---".to_string());
+
+    let mut blocks = vec![front.join("\n")];
+    blocks.extend(
+        template
+            .letterhead
+            .iter()
+            .filter(|p| !p.is_blank())
+            .map(Paragraph::to_markdown),
+    );
+    if let Some(title) = &template.title {
+        blocks.push(todo(&title.to_markdown(), source));
+    }
+    for section in &template.sections {
+        blocks.extend(section_lines(section, source, target));
+    }
+    if let Some(footer) = &template.footer {
+        blocks.push(todo(&footer.to_markdown(), source));
+    }
+    Ok(format!("{}\n", blocks.join("\n\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headings_are_translated_and_text_marked() {
+        let skeleton = skeleton(
+            "---\nmodality: CT\ntitle: TC DE TÓRAX\n---\nTC DE TÓRAX\n\n\
+             **TÉCNICA DO EXAME:** Cortes axiais.\n\n**Aspectos observados:**\n\n\
+             **Pulmões:**\n\nSem nódulos.\n\n**Segmento apical:**\n\nNormal.\n\n\
+             **Impressão:**\n\nExame normal.\n\n*Laudo sujeito a revisão.*\n",
+            Language::English,
+        )
+        .unwrap();
+        assert_eq!(
+            skeleton,
+            "---\nmodality: CT\nlanguage: en-US\n---\n\nTODO (pt): TC DE TÓRAX\n\n\
+             **TECHNIQUE:** TODO (pt): Cortes axiais.\n\n**Findings:**\n\n**Lungs:**\n\n\
+             TODO (pt): Sem nódulos.\n\n**TODO (pt): Segmento apical:**\n\nTODO (pt): Normal.\n\n\
+             **Impression:**\n\nTODO (pt): Exame normal.\n\nTODO (pt): *Laudo sujeito a revisão.*\n"
+        );
+
+        let sections: Vec<_> = ReportTemplate::from_markdown(&skeleton)
+            .sections
+            .iter()
+            .filter_map(|section| section.kind)
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                SectionKind::Tecnica,
+                SectionKind::AspectosObservados,
+                SectionKind::Impressao
+            ]
+        );
+        assert!(super::skeleton(&skeleton, Language::English).is_err());
+    }
+}
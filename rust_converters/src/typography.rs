@@ -0,0 +1,90 @@
+//! Plain ASCII punctuation for TXT output.
+//!
+//! Word's autocorrect leaves curly quotes, dashes and non-breaking spaces in
+//! the templates, which many RIS text boxes show as `?` or boxes. TXT export
+//! writes the ASCII equivalents of those instead, per `PLAIN`, and any
+//! character of the `[txt.replace]` table as it says. `typography = false`
+//! under `[txt]` turns `PLAIN` off but not the table. Letters with accents
+//! are left alone.
+
+use crate::config::TxtSettings;
+
+/// The built-in replacements; `[txt.replace]` adds to them or overrides them.
+pub const PLAIN: &[(char, &str)] = &[
+    ('\u{2018}', "'"),   // ‘
+    ('\u{2019}', "'"),   // ’
+    ('\u{201A}', "'"),   // ‚
+    ('\u{201B}', "'"),   // ‛
+    ('\u{2032}', "'"),   // ′
+    ('\u{201C}', "\""),  // “
+    ('\u{201D}', "\""),  // ”
+    ('\u{201E}', "\""),  // „
+    ('\u{2033}', "\""),  // ″
+    ('\u{00AB}', "\""),  // «
+    ('\u{00BB}', "\""),  // »
+    ('\u{2010}', "-"),   // hyphen
+    ('\u{2011}', "-"),   // non-breaking hyphen
+    ('\u{2012}', "-"),   // figure dash
+    ('\u{2013}', "-"),   // –
+    ('\u{2014}', "-"),   // —
+    ('\u{2212}', "-"),   // minus sign
+    ('\u{2026}', "..."), // …
+    ('\u{2022}', "-"),   // •
+    ('\u{00A0}', " "),   // non-breaking space
+    ('\u{2007}', " "),   // figure space
+    ('\u{2009}', " "),   // thin space
+    ('\u{202F}', " "),   // narrow non-breaking space
+    ('\u{00AD}', ""),    // soft hyphen
+    ('\u{200B}', ""),    // zero-width space
+    ('\u{FEFF}', ""),    // zero-width no-break space
+];
+
+/// `text` with typographic characters replaced as `settings` say.
+pub fn to_plain(text: &str, settings: &TxtSettings) -> String {
+    if !settings.typography && settings.replace.is_empty() {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        let replacement = settings
+            .replace
+            .iter()
+            .rev()
+            .find(|(from, _)| *from == c)
+            .map(|(_, to)| to.as_str())
+            .or_else(|| {
+                PLAIN
+                    .iter()
+                    .filter(|_| settings.typography)
+                    .find(|(from, _)| *from == c)
+                    .map(|(_, to)| *to)
+            });
+        match replacement {
+            Some(to) => out.push_str(to),
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typographic_characters_become_ascii() {
+        let mut settings = TxtSettings::default();
+        let text = "“Nódulo” de 1,2\u{a0}cm – sem ‘realce’… 37\u{202f}°C";
+        assert_eq!(
+            to_plain(text, &settings),
+            "\"Nódulo\" de 1,2 cm - sem 'realce'... 37 °C"
+        );
+
+        settings.replace.push(('°', "o".to_string()));
+        settings.replace.push(('–', " - ".to_string()));
+        assert_eq!(to_plain("37° – 38°", &settings), "37o  -  38o");
+
+        settings.typography = false;
+        assert_eq!(to_plain("“37°”", &settings), "“37o”");
+    }
+}
@@ -0,0 +1,238 @@
+//! Cross-checks the DOCX, Markdown and TXT folders against each other and
+//! against `reports_index.json`.
+//!
+//! Every template stem should exist in each folder, every file should match
+//! the checksum the index recorded for it, and each TXT should be exactly
+//! what `convert_to_txt` makes of the Markdown with the same stem. An alias
+//! counts as a Markdown stem, and its TXT is checked against the template
+//! it names. Outputs of formats a template does not export to (see
+//! `exports`) are neither expected nor checked against it.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::aliases;
+use crate::cache::sha256_file;
+use crate::convert_to_txt::clean_markdown_text;
+use crate::exports;
+use crate::fill::FillOptions;
+use crate::front_matter;
+use crate::generate_index::{collect_files, read_index};
+use crate::{config, targets};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Problem {
+    /// A stem present in another folder has no file in this one.
+    Missing,
+    /// The file exists but the index does not list it.
+    NotIndexed,
+    /// The index lists the file but it is gone.
+    IndexedButMissing,
+    /// The contents changed since the index was built.
+    ChecksumMismatch,
+    /// The TXT differs from the cleaned Markdown.
+    TxtOutOfDate,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Mismatch {
+    pub problem: Problem,
+    /// Path relative to the root, e.g. `Templates_txt/AngioTEP.txt`.
+    pub path: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VerifyReport {
+    /// Files found across the three folders.
+    pub checked: usize,
+    /// Whether `reports_index.json` was found; without it only the folders
+    /// are compared.
+    pub index_found: bool,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    fn push(&mut self, problem: Problem, path: impl Into<String>, detail: impl Into<String>) {
+        self.mismatches.push(Mismatch {
+            problem,
+            path: path.into(),
+            detail: detail.into(),
+        });
+    }
+}
+
+fn stem(file: &str) -> String {
+    Path::new(file)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn relative(folder: &str, stem: &str, ext: &str) -> String {
+    format!("{}/{}.{}", folder, stem, ext)
+}
+
+/// Checks the template folders under `root`; `index_path` is the index to
+/// compare checksums with.
+pub fn verify(root: &Path, index_path: &Path) -> Result<VerifyReport> {
+    let folders = collect_files(root)?;
+    let mut report = VerifyReport::default();
+
+    let dirs = &config::get().directories;
+    let aliases = aliases::in_folder(&root.join(&dirs.markdown))?;
+    let mut stems: BTreeMap<&str, BTreeSet<String>> = targets()
+        .iter()
+        .map(|(folder, _)| {
+            let files = folders.get(*folder).into_iter().flatten();
+            (*folder, files.map(|f| stem(f)).collect())
+        })
+        .collect();
+    if let Some(markdown) = stems.get_mut(dirs.markdown.as_str()) {
+        markdown.extend(aliases.keys().cloned());
+    }
+    let all_stems: BTreeSet<&String> = stems.values().flatten().collect();
+    let left_out = exports::left_out_by_folder(root)?;
+    report.checked = folders.values().map(Vec::len).sum();
+
+    for name in &all_stems {
+        for (folder, ext) in targets() {
+            let exported = left_out.get(folder).is_none_or(|l| !l.contains(*name));
+            if exported && !stems[folder].contains(*name) {
+                report.push(Problem::Missing, relative(folder, name, ext), "");
+            }
+        }
+    }
+
+    if index_path.exists() {
+        report.index_found = true;
+        let index = read_index(index_path)?;
+        let listed: BTreeSet<&String> = index.folders.values().flatten().collect();
+        for file in folders.values().flatten() {
+            if !listed.contains(file) {
+                report.push(Problem::NotIndexed, file.as_str(), "");
+                continue;
+            }
+            if let Some(entry) = index.files.get(file) {
+                let actual = sha256_file(&root.join(file))?;
+                if actual != entry.sha256 {
+                    report.push(
+                        Problem::ChecksumMismatch,
+                        file.as_str(),
+                        format!("indexed {}, found {}", entry.sha256, actual),
+                    );
+                }
+            }
+        }
+        let present: BTreeSet<&String> = folders.values().flatten().collect();
+        for file in listed {
+            if !present.contains(file) {
+                report.push(Problem::IndexedButMissing, file.as_str(), "");
+            }
+        }
+    }
+
+    let fill = FillOptions::default();
+    for name in stems[dirs.markdown.as_str()].intersection(&stems[dirs.txt.as_str()]) {
+        if left_out
+            .get(dirs.txt.as_str())
+            .is_some_and(|l| l.contains(name))
+        {
+            continue;
+        }
+        let source = aliases.get(name).map_or(name.as_str(), |path| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+        });
+        let md = relative(&dirs.markdown, source, "md");
+        let txt = relative(&dirs.txt, name, "txt");
+        let expected = clean_markdown_text(front_matter::strip(&fill.read(&root.join(&md))?));
+        let actual = std::fs::read_to_string(root.join(&txt))?;
+        if actual != expected {
+            let line = actual
+                .lines()
+                .zip(expected.lines())
+                .position(|(a, e)| a != e)
+                .unwrap_or_else(|| actual.lines().count().min(expected.lines().count()));
+            report.push(
+                Problem::TxtOutOfDate,
+                txt,
+                format!("differs from {} at line {}", md, line + 1),
+            );
+        }
+    }
+
+    // Stable sort: problems with the same file stay in check order.
+    report.mismatches.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_index::write_index;
+    use std::fs;
+
+    #[test]
+    fn folders_are_checked_against_each_other_and_the_index() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = &config::get().directories;
+        let write = |folder: &str, name: &str, text: &str| {
+            fs::create_dir_all(root.path().join(folder)).unwrap();
+            fs::write(root.path().join(folder).join(name), text).unwrap();
+        };
+        write(&dirs.markdown, "A.md", "**TC A**\n\nTexto.\n");
+        write(
+            &dirs.txt,
+            "A.txt",
+            &clean_markdown_text("**TC A**\n\nTexto.\n"),
+        );
+        write(&dirs.docx, "A.docx", "");
+        write(&dirs.markdown, "B.md", "**TC B**\n");
+        write(&dirs.txt, "B.txt", "TC B antigo\n");
+        write(&dirs.docx, "B.docx", "");
+        let index = root.path().join("reports_index.json");
+        let found = || {
+            let report = verify(root.path(), &index).unwrap();
+            let mismatches: Vec<(Problem, String)> = report
+                .mismatches
+                .into_iter()
+                .map(|m| (m.problem, m.path))
+                .collect();
+            (report.index_found, report.checked, mismatches)
+        };
+        let txt_b = (Problem::TxtOutOfDate, relative(&dirs.txt, "B", "txt"));
+        assert_eq!(found(), (false, 6, vec![txt_b.clone()]));
+
+        write_index(root.path(), &index).unwrap();
+        write(&dirs.markdown, "A.md", "**TC A**\n\nTexto novo.\n");
+        write(&dirs.docx, "C.docx", "");
+        assert_eq!(
+            found(),
+            (
+                true,
+                7,
+                vec![
+                    (Problem::NotIndexed, relative(&dirs.docx, "C", "docx")),
+                    (
+                        Problem::ChecksumMismatch,
+                        relative(&dirs.markdown, "A", "md")
+                    ),
+                    (Problem::Missing, relative(&dirs.markdown, "C", "md")),
+                    (Problem::TxtOutOfDate, relative(&dirs.txt, "A", "txt")),
+                    txt_b,
+                    (Problem::Missing, relative(&dirs.txt, "C", "txt")),
+                ]
+            )
+        );
+    }
+}
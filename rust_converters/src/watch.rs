@@ -0,0 +1,129 @@
+//! Filesystem watching for continuous conversion.
+
+use anyhow::{bail, Result};
+use log::{error, info};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Editors often emit several events per save; changes arriving within this
+/// window are handled once.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.eq_ignore_ascii_case(ext))
+        .unwrap_or(false)
+}
+
+/// The files with extension `ext` that `events` created or modified, each
+/// once.
+fn changed_files(events: Vec<notify::Result<Event>>, ext: &str) -> BTreeSet<PathBuf> {
+    let mut changed = BTreeSet::new();
+    for event in events {
+        match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                changed.extend(
+                    event
+                        .paths
+                        .into_iter()
+                        .filter(|p| has_extension(p, ext) && p.is_file()),
+                );
+            }
+            Ok(_) => {}
+            Err(err) => error!("Watch error: {}", err),
+        }
+    }
+    changed
+}
+
+/// Calls `on_change` for each burst of events received on `rx`, as
+/// `watch_folder` describes, until the watcher is dropped.
+fn handle_events<F>(rx: mpsc::Receiver<notify::Result<Event>>, ext: &str, mut on_change: F)
+where
+    F: FnMut(&Path) -> Result<()>,
+{
+    while let Ok(first) = rx.recv() {
+        let mut events = vec![first];
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            events.push(event);
+        }
+
+        for path in changed_files(events, ext) {
+            if let Err(err) = on_change(&path) {
+                error!("  ✗ {}: {:#}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// Blocks forever, calling `on_change` once per created or modified file with
+/// extension `ext` inside `dir`. Errors from `on_change` are reported and the
+/// watch continues.
+pub fn watch_folder<F>(dir: &Path, ext: &str, on_change: F) -> Result<()>
+where
+    F: FnMut(&Path) -> Result<()>,
+{
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    info!(
+        "Watching {} for .{} changes (Ctrl+C to stop)...",
+        dir.display(),
+        ext
+    );
+    handle_events(rx, ext, on_change);
+    bail!("Stopped watching {}", dir.display())
+}
+
+/// `watch_folder` on another thread: returns once `dir` is watched, so no
+/// change made afterwards is missed.
+pub fn watch_in_background<F>(dir: &Path, ext: &str, on_change: F) -> Result<()>
+where
+    F: FnMut(&Path) -> Result<()> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    let ext = ext.to_string();
+    thread::spawn(move || {
+        // Moved in so the watch lasts as long as the thread.
+        let _watcher = watcher;
+        handle_events(rx, &ext, on_change);
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, ModifyKind, RemoveKind};
+
+    #[test]
+    fn a_burst_of_events_converts_each_saved_file_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "x").unwrap();
+            path
+        };
+        let (report, notes, gone) = (file("TC.md"), file("notas.txt"), dir.path().join("old.md"));
+        let event = |kind, path: &PathBuf| Ok(Event::new(kind).add_path(path.clone()));
+        let events = vec![
+            event(EventKind::Create(CreateKind::File), &report),
+            event(EventKind::Modify(ModifyKind::Any), &report),
+            event(EventKind::Modify(ModifyKind::Any), &notes),
+            event(EventKind::Remove(RemoveKind::File), &gone),
+            event(EventKind::Modify(ModifyKind::Any), &gone),
+            Err(notify::Error::generic("lost")),
+        ];
+        assert_eq!(
+            changed_files(events, "md").into_iter().collect::<Vec<_>>(),
+            [report]
+        );
+    }
+}